@@ -1,4 +1,4 @@
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 
 // Copyright 2022 Jeremy Wall (Jeremy@marzhilsltudios.com)
 //
@@ -21,12 +21,115 @@ use axum::{
 };
 use serde::{Deserialize, Serialize};
 
-use recipes::{IngredientKey, RecipeEntry};
+pub use recipes::rules::{check_plan, month_name, PlanRule, RuleViolation, StoredPlanRule};
+use recipes::{Ingredient, IngredientKey, MealType, Recipe, RecipeEntry, Step, StepTimer};
+
+/// A validation failure for one of the entities in a request.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ValidationError {
+    pub id: String,
+    /// The field of the entity that was invalid.
+    #[serde(default)]
+    pub field: String,
+    pub message: String,
+}
+
+impl ValidationError {
+    pub fn new<S: Into<String>, F: Into<String>, M: Into<String>>(
+        id: S,
+        field: F,
+        message: M,
+    ) -> Self {
+        Self {
+            id: id.into(),
+            field: field.into(),
+            message: message.into(),
+        }
+    }
+}
+
+impl std::fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.id, self.message)
+    }
+}
+
+/// A machine readable reason for an error so clients don't have to guess from
+/// the status code.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum ErrorCode {
+    BadRequest,
+    Unauthorized,
+    Forbidden,
+    NotFound,
+    Conflict,
+    /// A field in the request was invalid.
+    Validation {
+        field: String,
+    },
+    PayloadTooLarge,
+    /// The server is not accepting changes.
+    ReadOnly,
+    /// The server is too busy to handle the request right now. It is safe to
+    /// retry.
+    Busy,
+    /// The client sent too many requests. It is safe to retry later.
+    TooManyRequests,
+    Internal,
+}
+
+impl Default for ErrorCode {
+    fn default() -> Self {
+        Self::Internal
+    }
+}
+
+impl ErrorCode {
+    pub fn from_status(status: u16) -> Self {
+        match status {
+            400 => Self::BadRequest,
+            401 => Self::Unauthorized,
+            403 => Self::Forbidden,
+            404 => Self::NotFound,
+            409 => Self::Conflict,
+            413 => Self::PayloadTooLarge,
+            422 => Self::Validation {
+                field: String::new(),
+            },
+            429 => Self::TooManyRequests,
+            503 => Self::Busy,
+            _ => Self::Internal,
+        }
+    }
+
+    pub fn status(&self) -> u16 {
+        match self {
+            Self::BadRequest => 400,
+            Self::Unauthorized => 401,
+            Self::Forbidden | Self::ReadOnly => 403,
+            Self::NotFound => 404,
+            Self::Conflict => 409,
+            Self::PayloadTooLarge => 413,
+            Self::Validation { field: _ } => 422,
+            Self::TooManyRequests => 429,
+            Self::Internal => 500,
+            Self::Busy => 503,
+        }
+    }
+}
 
 #[derive(Serialize, Deserialize, Debug)]
 pub enum Response<T> {
     Success(T),
-    Err { status: u16, message: String },
+    Err {
+        status: u16,
+        message: String,
+        #[serde(default)]
+        code: ErrorCode,
+    },
+    Invalid(Vec<ValidationError>),
+    /// The request was based on stale data. Holds the current entries.
+    Conflict(Vec<RecipeEntry>),
     NotFound,
     Unauthorized,
 }
@@ -36,6 +139,16 @@ impl<T> Response<T> {
         Self::Err {
             status: code,
             message: msg.into(),
+            code: ErrorCode::from_status(code),
+        }
+    }
+
+    /// Construct an error with a specific [ErrorCode].
+    pub fn error_code<S: Into<String>>(code: ErrorCode, msg: S) -> Self {
+        Self::Err {
+            status: code.status(),
+            message: msg.into(),
+            code,
         }
     }
 
@@ -61,13 +174,17 @@ where
     fn into_response(self) -> AxumResponse {
         match &self {
             Self::Success(_) => (StatusCode::OK, axum::Json::from(self)).into_response(),
-            Self::Err { status, message: _ } => {
+            Self::Err { status, .. } => {
                 let code = match StatusCode::from_u16(*status) {
                     Ok(c) => c,
                     Err(_) => StatusCode::INTERNAL_SERVER_ERROR,
                 };
                 (code, axum::Json::from(self)).into_response()
             }
+            Self::Invalid(_) => {
+                (StatusCode::UNPROCESSABLE_ENTITY, axum::Json::from(self)).into_response()
+            }
+            Self::Conflict(_) => (StatusCode::CONFLICT, axum::Json::from(self)).into_response(),
             // TODO(jwall): Perhaps this can show a more useful json payload?
             Self::NotFound => (StatusCode::NOT_FOUND, axum::Json::from(self)).into_response(),
             Self::Unauthorized => {
@@ -107,9 +224,20 @@ pub type CategoryResponse = Response<String>;
 
 pub type EmptyResponse = Response<()>;
 
+/// Successful writes by a logged in user carry their new data version in this
+/// header.
+pub const DATA_VERSION_HEADER: &'static str = "x-data-version";
+
+/// A counter that goes up every time anything stored for the user changes.
+pub type DataVersionResponse = Response<i64>;
+
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct UserData {
     pub user_id: String,
+    /// True when the account has no recipes or categories yet. The ui offers
+    /// to get the user started when it is set.
+    #[serde(default)]
+    pub empty: bool,
 }
 
 pub type AccountResponse = Response<UserData>;
@@ -128,6 +256,265 @@ impl From<Vec<RecipeEntry>> for RecipeEntryResponse {
     }
 }
 
+/// Summary of a recipe for the recipe index. Times are in seconds.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RecipeIndexEntry {
+    pub recipe_id: String,
+    pub title: String,
+    pub category: Option<String>,
+    pub prep_time: u64,
+    pub cook_time: u64,
+    pub total_time: u64,
+    #[serde(default)]
+    pub archived: bool,
+}
+
+impl RecipeIndexEntry {
+    pub fn new(entry: &RecipeEntry, recipe: &Recipe) -> Self {
+        Self {
+            recipe_id: entry.recipe_id().to_owned(),
+            title: recipe.title.clone(),
+            category: entry.category().cloned(),
+            prep_time: recipe.prep_time().as_secs(),
+            cook_time: recipe.cook_time().as_secs(),
+            total_time: recipe.total_time().as_secs(),
+            archived: false,
+        }
+    }
+}
+
+/// A recipe that was saved along with its new revision. Recipe ids are
+/// normalized when they are saved so the id may differ from the one sent.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SavedRecipe {
+    pub recipe_id: String,
+    pub revision: i64,
+    /// The id the recipe was saved under if it was renamed.
+    pub renamed_from: Option<String>,
+}
+
+/// The recipes that were saved in the order they were sent.
+pub type RecipeSaveResponse = Response<Vec<SavedRecipe>>;
+
+/// A recipe in Cooklang markup to import. Cooklang recipes are usually
+/// titled by their file name so the title is used unless the recipe has
+/// title metadata.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CooklangImport {
+    pub title: String,
+    pub text: String,
+    #[serde(default)]
+    pub category: Option<String>,
+}
+
+/// Draft recipe text read from a photo of a recipe. The text still needs to
+/// be checked in the editor since OCR makes mistakes.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct OcrDraft {
+    pub title: String,
+    pub text: String,
+}
+
+pub type OcrDraftResponse = Response<OcrDraft>;
+
+/// Where an uploaded image can be fetched from. The url goes in an `image:`
+/// line of a recipe step.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct UploadedImage {
+    pub url: String,
+}
+
+pub type UploadedImageResponse = Response<UploadedImage>;
+
+pub type RecipeIndexResponse = Response<Vec<RecipeIndexEntry>>;
+
+impl From<Vec<RecipeIndexEntry>> for RecipeIndexResponse {
+    fn from(entries: Vec<RecipeIndexEntry>) -> Self {
+        Response::Success(entries)
+    }
+}
+
+/// An ingredient for a recipe step with a normalized amount.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct StepIngredient {
+    pub name: String,
+    pub form: Option<String>,
+    pub amt: String,
+}
+
+impl From<&Ingredient> for StepIngredient {
+    fn from(i: &Ingredient) -> Self {
+        Self {
+            name: i.name.clone(),
+            form: i.form.clone(),
+            amt: i.amt.normalize().to_string(),
+        }
+    }
+}
+
+/// A timer for a recipe step. The duration is in seconds.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct StepTimerData {
+    pub duration: u64,
+    pub label: Option<String>,
+}
+
+impl From<&StepTimer> for StepTimerData {
+    fn from(t: &StepTimer) -> Self {
+        Self {
+            duration: t.duration.as_secs(),
+            label: t.label.clone(),
+        }
+    }
+}
+
+/// A single recipe step for voice assistants and other simple clients that
+/// want to walk through a recipe one step at a time. Steps are numbered
+/// starting at 1.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RecipeStepData {
+    pub recipe_id: String,
+    pub title: String,
+    pub step: usize,
+    pub total_steps: usize,
+    pub prep_time: Option<u64>,
+    pub instructions: String,
+    pub ingredients: Vec<StepIngredient>,
+    pub timers: Vec<StepTimerData>,
+    pub temperatures: Vec<String>,
+}
+
+impl RecipeStepData {
+    pub fn new<S: Into<String>>(recipe_id: S, recipe: &Recipe, step: usize) -> Option<Self> {
+        let s: &Step = recipe.steps.get(step.checked_sub(1)?)?;
+        Some(Self {
+            recipe_id: recipe_id.into(),
+            title: recipe.title.clone(),
+            step,
+            total_steps: recipe.steps.len(),
+            prep_time: s.prep_time.map(|d| d.as_secs()),
+            instructions: s.instructions.clone(),
+            ingredients: s.ingredients.iter().map(StepIngredient::from).collect(),
+            timers: s.timers.iter().map(StepTimerData::from).collect(),
+            temperatures: s.temperatures.iter().map(|t| t.to_string()).collect(),
+        })
+    }
+}
+
+pub type RecipeStepResponse = Response<RecipeStepData>;
+
+impl From<RecipeStepData> for RecipeStepResponse {
+    fn from(step: RecipeStepData) -> Self {
+        Response::Success(step)
+    }
+}
+
+/// The mise en place for a recipe. These are the ingredients to prepare
+/// before cooking combined across every step.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PrepList {
+    pub recipe_id: String,
+    pub title: String,
+    pub items: Vec<StepIngredient>,
+}
+
+impl PrepList {
+    pub fn new<S: Into<String>>(recipe_id: S, recipe: &Recipe) -> Self {
+        Self {
+            recipe_id: recipe_id.into(),
+            title: recipe.title.clone(),
+            items: recipe
+                .prep_list()
+                .iter()
+                .map(StepIngredient::from)
+                .collect(),
+        }
+    }
+}
+
+pub type PrepListResponse = Response<PrepList>;
+
+impl From<PrepList> for PrepListResponse {
+    fn from(prep: PrepList) -> Self {
+        Response::Success(prep)
+    }
+}
+
+/// A recipe step parsed on the server. Times are in seconds.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ParsedStep {
+    pub prep_time: Option<u64>,
+    pub instructions: String,
+    pub ingredients: Vec<StepIngredient>,
+    pub images: Vec<String>,
+    pub timers: Vec<StepTimerData>,
+    pub temperatures: Vec<String>,
+}
+
+impl From<&Step> for ParsedStep {
+    fn from(s: &Step) -> Self {
+        Self {
+            prep_time: s.prep_time.map(|d| d.as_secs()),
+            instructions: s.instructions.clone(),
+            ingredients: s.ingredients.iter().map(StepIngredient::from).collect(),
+            images: s.images.clone(),
+            timers: s.timers.iter().map(StepTimerData::from).collect(),
+            temperatures: s.temperatures.iter().map(|t| t.to_string()).collect(),
+        }
+    }
+}
+
+/// A recipe parsed on the server so that clients don't need to run the
+/// recipe parser themselves. Times are in seconds.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ParsedRecipe {
+    pub recipe_id: String,
+    pub category: Option<String>,
+    pub title: String,
+    pub desc: Option<String>,
+    pub serves: Option<u32>,
+    pub tags: Vec<String>,
+    pub source: Option<String>,
+    pub prep_time: u64,
+    pub cook_time: u64,
+    pub total_time: u64,
+    pub steps: Vec<ParsedStep>,
+}
+
+impl ParsedRecipe {
+    pub fn new(entry: &RecipeEntry, recipe: &Recipe) -> Self {
+        Self {
+            recipe_id: entry.recipe_id().to_owned(),
+            category: entry.category().cloned(),
+            title: recipe.title.clone(),
+            desc: recipe.desc.clone(),
+            serves: recipe.metadata.serves,
+            tags: recipe.metadata.tags.iter().cloned().collect(),
+            source: recipe.metadata.source.clone(),
+            prep_time: recipe.prep_time().as_secs(),
+            cook_time: recipe.cook_time().as_secs(),
+            total_time: recipe.total_time().as_secs(),
+            steps: recipe.steps.iter().map(ParsedStep::from).collect(),
+        }
+    }
+}
+
+pub type ParsedRecipeResponse = Response<ParsedRecipe>;
+
+impl From<ParsedRecipe> for ParsedRecipeResponse {
+    fn from(recipe: ParsedRecipe) -> Self {
+        Response::Success(recipe)
+    }
+}
+
+pub type ParsedRecipesResponse = Response<Vec<ParsedRecipe>>;
+
+impl From<Vec<ParsedRecipe>> for ParsedRecipesResponse {
+    fn from(recipes: Vec<ParsedRecipe>) -> Self {
+        Response::Success(recipes)
+    }
+}
+
 pub type PlanDataResponse = Response<Vec<(String, i32)>>;
 
 impl From<Vec<(String, i32)>> for PlanDataResponse {
@@ -145,9 +532,129 @@ impl From<Option<Vec<(String, i32)>>> for PlanDataResponse {
     }
 }
 
+/// A single change to a plan. The server applies changes on top of the
+/// stored plan so small tweaks don't overwrite the rest of the plan.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum PlanChange {
+    /// Set how many times a recipe is planned.
+    SetCount(String, i32),
+    /// Add to how many times a recipe is planned. Negative amounts remove
+    /// and the count never goes below zero.
+    AddCount(String, i32),
+}
+
+impl PlanChange {
+    pub fn recipe_id(&self) -> &str {
+        match self {
+            PlanChange::SetCount(id, _) | PlanChange::AddCount(id, _) => id,
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        if self.recipe_id().trim().is_empty() {
+            return Err(ValidationError::new(
+                "",
+                "recipe_id",
+                "A recipe is required",
+            ));
+        }
+        if let PlanChange::SetCount(id, count) = self {
+            if *count < 0 {
+                return Err(ValidationError::new(
+                    id,
+                    "count",
+                    "A recipe can't be planned less than zero times",
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
 pub type PlanHistoryResponse = Response<BTreeMap<chrono::NaiveDate, Vec<(String, i32)>>>;
 
-#[derive(Serialize, Deserialize)]
+/// The plan dates and recipe ids removed from plans because the recipe no
+/// longer exists.
+pub type PlanRepairResponse = Response<Vec<(chrono::NaiveDate, String)>>;
+
+/// A recipe planned for today in [TodayData]. Times are in seconds.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TodayRecipe {
+    pub recipe_id: String,
+    pub title: String,
+    pub count: i32,
+    pub total_time: u64,
+    #[serde(default)]
+    pub meal: MealType,
+}
+
+/// The meals each recipe in a plan fills when they differ from the meal in
+/// the recipe's metadata. A recipe can fill more than one meal in a day.
+pub type PlanMeals = BTreeMap<String, BTreeSet<MealType>>;
+
+pub type PlanMealsResponse = Response<PlanMeals>;
+
+/// The meals a planned recipe fills. Recipes without planned meals fill the
+/// meal from their metadata.
+pub fn planned_meals_for(
+    meals: &PlanMeals,
+    recipe_id: &str,
+    recipe: Option<&Recipe>,
+) -> BTreeSet<MealType> {
+    match meals.get(recipe_id) {
+        Some(planned) if !planned.is_empty() => planned.clone(),
+        _ => BTreeSet::from([recipe.and_then(|r| r.metadata.meal).unwrap_or_default()]),
+    }
+}
+
+/// A timer from the first step of a planned recipe that has any timers.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TodayTimer {
+    pub recipe_id: String,
+    pub title: String,
+    /// The step the timer is in starting from 1.
+    pub step: usize,
+    pub label: Option<String>,
+    pub seconds: u64,
+}
+
+/// A compact summary of today's meals for things like dashboard cards and
+/// voice assistants.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TodayData {
+    pub date: chrono::NaiveDate,
+    /// The plan today's meals come from if there is one.
+    pub plan_date: Option<chrono::NaiveDate>,
+    /// A sentence describing today's meals suitable for reading aloud.
+    pub summary: String,
+    pub recipes: Vec<TodayRecipe>,
+    /// Shopping list items that haven't been checked off yet.
+    pub shopping_items_remaining: usize,
+    pub timers: Vec<TodayTimer>,
+}
+
+impl TodayData {
+    pub fn new(date: chrono::NaiveDate, plan_date: Option<chrono::NaiveDate>) -> Self {
+        Self {
+            date,
+            plan_date,
+            summary: "Nothing is planned for today.".to_owned(),
+            recipes: Vec::new(),
+            shopping_items_remaining: 0,
+            timers: Vec::new(),
+        }
+    }
+}
+
+pub type TodayResponse = Response<TodayData>;
+
+impl From<TodayData> for TodayResponse {
+    fn from(data: TodayData) -> Self {
+        Response::Success(data)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
 pub struct InventoryData {
     pub filtered_ingredients: Vec<IngredientKey>,
     pub modified_amts: Vec<(IngredientKey, String)>,
@@ -184,6 +691,23 @@ impl From<InventoryData> for InventoryResponse {
     }
 }
 
+/// A single change to the inventory for a plan. The server applies changes on
+/// top of what it has stored so that people shopping from the same list don't
+/// overwrite each other's changes.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum InventoryOp {
+    /// Check an ingredient off the shopping list.
+    Check(IngredientKey),
+    /// Put a checked off ingredient back on the shopping list.
+    Uncheck(IngredientKey),
+    /// Change the amount to buy of an ingredient.
+    SetAmt(IngredientKey, String),
+    /// Replace the extra items on the shopping list.
+    SetExtras(Vec<(String, String)>),
+    /// Clear every change to the shopping list.
+    Reset,
+}
+
 pub type CategoryMappingResponse = Response<Vec<(String, String)>>;
 
 impl From<Vec<(String, String)>> for CategoryMappingResponse {
@@ -191,3 +715,1004 @@ impl From<Vec<(String, String)>> for CategoryMappingResponse {
         Response::Success(mappings)
     }
 }
+
+pub type CategorySuggestionResponse = Response<Vec<(String, Option<String>)>>;
+
+impl From<Vec<(String, Option<String>)>> for CategorySuggestionResponse {
+    fn from(suggestions: Vec<(String, Option<String>)>) -> Self {
+        Response::Success(suggestions)
+    }
+}
+
+/// What applying the server's default categories and staples changed.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct DefaultsApplied {
+    /// The number of ingredients that were given a category.
+    pub categories: usize,
+    /// Whether the default staples were saved. Existing staples are never
+    /// replaced.
+    pub staples: bool,
+}
+
+pub type DefaultsResponse = Response<DefaultsApplied>;
+
+impl From<DefaultsApplied> for DefaultsResponse {
+    fn from(applied: DefaultsApplied) -> Self {
+        Response::Success(applied)
+    }
+}
+
+pub type UserSettingsResponse = Response<BTreeMap<String, String>>;
+
+impl From<BTreeMap<String, String>> for UserSettingsResponse {
+    fn from(settings: BTreeMap<String, String>) -> Self {
+        Response::Success(settings)
+    }
+}
+
+/// User settings starting with this prefix override the density of the
+/// ingredient named by the rest of the key in grams per cup.
+pub const DENSITY_SETTING_PREFIX: &'static str = "density:";
+
+/// The ingredient density overrides in a user's settings. Settings that
+/// aren't a whole number of grams are ignored so an empty value removes an
+/// override.
+pub fn density_overrides(settings: &BTreeMap<String, String>) -> BTreeMap<String, u32> {
+    settings
+        .iter()
+        .filter_map(|(key, value)| {
+            let name = key.strip_prefix(DENSITY_SETTING_PREFIX)?;
+            let grams = value.trim().parse::<u32>().ok().filter(|g| *g > 0)?;
+            Some((name.to_owned(), grams))
+        })
+        .collect()
+}
+
+/// User settings starting with this prefix hold the comma separated allergen
+/// and dietary tags for the ingredient named by the rest of the key.
+pub const INGREDIENT_TAGS_SETTING_PREFIX: &'static str = "diet:";
+
+/// The comma separated tags the user's household avoids.
+pub const RESTRICTIONS_SETTING: &'static str = "dietary_restrictions";
+
+/// Parse a comma separated list of tags. Tags are lowercased.
+pub fn parse_tags(value: &str) -> BTreeSet<String> {
+    value
+        .split(',')
+        .map(|tag| tag.trim().to_lowercase())
+        .filter(|tag| !tag.is_empty())
+        .collect()
+}
+
+/// The allergen and dietary tags for ingredients in a user's settings.
+/// Ingredients with an empty list of tags are left out.
+pub fn ingredient_tags(settings: &BTreeMap<String, String>) -> BTreeMap<String, BTreeSet<String>> {
+    settings
+        .iter()
+        .filter_map(|(key, value)| {
+            let name = key.strip_prefix(INGREDIENT_TAGS_SETTING_PREFIX)?;
+            let tags = parse_tags(value);
+            (!tags.is_empty()).then(|| (name.to_owned(), tags))
+        })
+        .collect()
+}
+
+/// User settings starting with this prefix hold the substitutes for the
+/// ingredient named by the rest of the key separated by `|`. An empty value
+/// hides the server's default substitutes for that ingredient.
+pub const SUBSTITUTES_SETTING_PREFIX: &'static str = "substitute:";
+
+/// Parse a `|` separated list of substitutes.
+pub fn parse_substitutes(value: &str) -> Vec<String> {
+    value
+        .split('|')
+        .map(|substitute| substitute.trim().to_owned())
+        .filter(|substitute| !substitute.is_empty())
+        .collect()
+}
+
+/// The substitutes in a user's settings keyed by ingredient. Unlike the
+/// other overrides ingredients with no substitutes are kept so they can hide
+/// the defaults.
+pub fn substitute_overrides(settings: &BTreeMap<String, String>) -> BTreeMap<String, Vec<String>> {
+    settings
+        .iter()
+        .filter_map(|(key, value)| {
+            let name = key.strip_prefix(SUBSTITUTES_SETTING_PREFIX)?;
+            Some((name.to_owned(), parse_substitutes(value)))
+        })
+        .collect()
+}
+
+/// What can be used in place of an ingredient keyed by the ingredient name.
+pub type SubstitutionsResponse = Response<BTreeMap<String, Vec<String>>>;
+
+impl From<BTreeMap<String, Vec<String>>> for SubstitutionsResponse {
+    fn from(substitutions: BTreeMap<String, Vec<String>>) -> Self {
+        Response::Success(substitutions)
+    }
+}
+
+/// The dietary restrictions in a user's settings.
+pub fn dietary_restrictions(settings: &BTreeMap<String, String>) -> BTreeSet<String> {
+    settings
+        .get(RESTRICTIONS_SETTING)
+        .map(|value| parse_tags(value))
+        .unwrap_or_default()
+}
+
+/// Show temperatures in celsius.
+pub const USE_CELSIUS_SETTING: &'static str = "use_celsius";
+
+/// Show baking ingredients by weight when their density is known.
+pub const USE_WEIGHTS_SETTING: &'static str = "use_weights";
+
+/// A `true` or `false` setting if it is set to one of those.
+pub fn bool_setting(settings: &BTreeMap<String, String>, key: &str) -> Option<bool> {
+    match settings.get(key)?.trim() {
+        "true" => Some(true),
+        "false" => Some(false),
+        _ => None,
+    }
+}
+
+/// The number of people recipes are scaled to feed when they are planned.
+pub const HOUSEHOLD_SIZE_SETTING: &'static str = "household_size";
+
+/// The household size in a user's settings if they have set one.
+pub fn household_size(settings: &BTreeMap<String, String>) -> Option<u32> {
+    settings
+        .get(HOUSEHOLD_SIZE_SETTING)
+        .and_then(|value| value.trim().parse::<u32>().ok())
+        .filter(|size| *size > 0)
+}
+
+/// The number of months of plan history the user wants kept. Older plans are
+/// pruned by the server.
+pub const RETENTION_SETTING: &'static str = "retention_months";
+
+/// The months of history a user wants kept if they have set it. An empty
+/// value or zero keeps the instance default.
+pub fn retention_months(settings: &BTreeMap<String, String>) -> Option<u32> {
+    settings
+        .get(RETENTION_SETTING)
+        .and_then(|value| value.trim().parse::<u32>().ok())
+        .filter(|months| *months > 0)
+}
+
+/// The user's timezone as an offset from UTC like `+01:00`. The server uses it
+/// to decide what day it is for the user.
+pub const TIMEZONE_SETTING: &'static str = "timezone";
+
+/// The UTC offset in a user's settings if they have set a valid one.
+pub fn utc_offset(settings: &BTreeMap<String, String>) -> Option<chrono::FixedOffset> {
+    settings
+        .get(TIMEZONE_SETTING)
+        .and_then(|value| parse_utc_offset(value))
+}
+
+/// Parse a UTC offset like `+01:00`, `-0500`, `+5` or `UTC`.
+pub fn parse_utc_offset(value: &str) -> Option<chrono::FixedOffset> {
+    let value = value.trim();
+    if value.eq_ignore_ascii_case("utc") || value.eq_ignore_ascii_case("z") {
+        return chrono::FixedOffset::east_opt(0);
+    }
+    let (sign, rest) = if let Some(rest) = value.strip_prefix('+') {
+        (1, rest)
+    } else if let Some(rest) = value.strip_prefix('-') {
+        (-1, rest)
+    } else {
+        return None;
+    };
+    if rest.is_empty() || !rest.chars().all(|c| c.is_ascii_digit() || c == ':') {
+        return None;
+    }
+    let (hours, minutes) = match rest.split_once(':') {
+        Some(parts) => parts,
+        None if rest.len() == 4 => rest.split_at(2),
+        None => (rest, "0"),
+    };
+    let hours = hours.parse::<i32>().ok().filter(|h| *h <= 14)?;
+    let minutes = minutes.parse::<i32>().ok().filter(|m| *m < 60)?;
+    chrono::FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60))
+}
+
+/// When this is `false` the user picked their timezone themselves and
+/// clients shouldn't replace it with the device's.
+pub const TIMEZONE_FROM_DEVICE_SETTING: &'static str = "timezone_from_device";
+
+/// Whether the user's timezone follows their device. Defaults to true.
+pub fn timezone_from_device(settings: &BTreeMap<String, String>) -> bool {
+    bool_setting(settings, TIMEZONE_FROM_DEVICE_SETTING).unwrap_or(true)
+}
+
+/// Format a UTC offset the way [parse_utc_offset] expects it.
+pub fn format_utc_offset(offset: &chrono::FixedOffset) -> String {
+    let seconds = offset.local_minus_utc();
+    let sign = if seconds < 0 { '-' } else { '+' };
+    let minutes = seconds.abs() / 60;
+    format!("{}{:02}:{:02}", sign, minutes / 60, minutes % 60)
+}
+
+/// The settings key for the day weeks start on.
+pub const WEEK_START_SETTING: &'static str = "week_start";
+
+/// The day weeks start on when plans are grouped by week.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WeekStart {
+    Sunday,
+    Monday,
+}
+
+impl Default for WeekStart {
+    fn default() -> Self {
+        WeekStart::Monday
+    }
+}
+
+impl WeekStart {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WeekStart::Sunday => "sunday",
+            WeekStart::Monday => "monday",
+        }
+    }
+
+    /// The first day of the week that `date` is in.
+    pub fn start_of_week(&self, date: chrono::NaiveDate) -> chrono::NaiveDate {
+        use chrono::Datelike;
+        let days = match self {
+            WeekStart::Sunday => date.weekday().num_days_from_sunday(),
+            WeekStart::Monday => date.weekday().num_days_from_monday(),
+        };
+        date - chrono::Duration::days(days as i64)
+    }
+}
+
+impl std::str::FromStr for WeekStart {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "sunday" => Ok(WeekStart::Sunday),
+            "monday" => Ok(WeekStart::Monday),
+            _ => Err(format!("Expected sunday or monday but got {}", s)),
+        }
+    }
+}
+
+/// The day weeks start on in a user's settings.
+pub fn week_start(settings: &BTreeMap<String, String>) -> WeekStart {
+    settings
+        .get(WEEK_START_SETTING)
+        .and_then(|value| value.parse().ok())
+        .unwrap_or_default()
+}
+
+/// The plans in a week.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PlanWeek {
+    /// The first day of the week.
+    pub start: chrono::NaiveDate,
+    /// The last day of the week.
+    pub end: chrono::NaiveDate,
+    pub plans: BTreeMap<chrono::NaiveDate, Vec<(String, i32)>>,
+}
+
+pub type PlanWeekResponse = Response<PlanWeek>;
+
+/// The inventory for a plan as it was at some point in time.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct InventorySnapshot {
+    pub saved_at: chrono::NaiveDateTime,
+    pub inventory: InventoryData,
+}
+
+pub type InventoryHistoryResponse = Response<Vec<InventorySnapshot>>;
+
+impl From<Vec<InventorySnapshot>> for InventoryHistoryResponse {
+    fn from(history: Vec<InventorySnapshot>) -> Self {
+        Response::Success(history)
+    }
+}
+
+/// What changed going from one inventory to another.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct InventoryDiff {
+    /// Ingredients checked off or removed from the shopping list.
+    pub checked_off: Vec<IngredientKey>,
+    /// Ingredients that are back on the shopping list.
+    pub unchecked: Vec<IngredientKey>,
+    /// Amounts that changed as (ingredient, old amount, new amount). A
+    /// missing amount is the amount the recipes call for.
+    pub changed_amts: Vec<(IngredientKey, Option<String>, Option<String>)>,
+    pub added_extras: Vec<(String, String)>,
+    pub removed_extras: Vec<(String, String)>,
+}
+
+impl InventoryDiff {
+    pub fn new(from: &InventoryData, to: &InventoryData) -> Self {
+        let from_filtered: BTreeSet<&IngredientKey> = from.filtered_ingredients.iter().collect();
+        let to_filtered: BTreeSet<&IngredientKey> = to.filtered_ingredients.iter().collect();
+        let from_amts: BTreeMap<&IngredientKey, &String> =
+            from.modified_amts.iter().map(|(k, amt)| (k, amt)).collect();
+        let to_amts: BTreeMap<&IngredientKey, &String> =
+            to.modified_amts.iter().map(|(k, amt)| (k, amt)).collect();
+        let changed_amts = from_amts
+            .keys()
+            .chain(to_amts.keys())
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .filter_map(|key| {
+                let (old, new) = (from_amts.get(*key), to_amts.get(*key));
+                if old == new {
+                    return None;
+                }
+                Some((
+                    (*key).clone(),
+                    old.map(|a| (*a).clone()),
+                    new.map(|a| (*a).clone()),
+                ))
+            })
+            .collect();
+        Self {
+            checked_off: to_filtered
+                .difference(&from_filtered)
+                .map(|k| (*k).clone())
+                .collect(),
+            unchecked: from_filtered
+                .difference(&to_filtered)
+                .map(|k| (*k).clone())
+                .collect(),
+            changed_amts,
+            added_extras: to
+                .extra_items
+                .iter()
+                .filter(|extra| !from.extra_items.contains(extra))
+                .cloned()
+                .collect(),
+            removed_extras: from
+                .extra_items
+                .iter()
+                .filter(|extra| !to.extra_items.contains(extra))
+                .cloned()
+                .collect(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+}
+
+pub type InventoryDiffResponse = Response<InventoryDiff>;
+
+impl From<InventoryDiff> for InventoryDiffResponse {
+    fn from(diff: InventoryDiff) -> Self {
+        Response::Success(diff)
+    }
+}
+
+/// A deleted recipe waiting in the trash.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TrashedRecipe {
+    pub entry: RecipeEntry,
+    pub deleted_at: chrono::NaiveDateTime,
+    /// When the recipe will be removed for good.
+    pub expires_at: chrono::NaiveDateTime,
+}
+
+pub type TrashResponse = Response<Vec<TrashedRecipe>>;
+
+impl From<Vec<TrashedRecipe>> for TrashResponse {
+    fn from(trash: Vec<TrashedRecipe>) -> Self {
+        Response::Success(trash)
+    }
+}
+
+/// A single meal plan along with the shopping list state for it.
+#[derive(Serialize, Deserialize)]
+pub struct PlanExport {
+    pub recipe_counts: Vec<(String, i32)>,
+    pub inventory: InventoryData,
+}
+
+/// Everything stored for a user so they can take their data with them.
+#[derive(Serialize, Deserialize)]
+pub struct AccountExport {
+    pub user_id: String,
+    pub exported_at: chrono::DateTime<chrono::Utc>,
+    pub recipes: Vec<RecipeEntry>,
+    pub categories: Option<String>,
+    pub category_mappings: Vec<(String, String)>,
+    pub staples: Option<String>,
+    pub settings: BTreeMap<String, String>,
+    pub plans: BTreeMap<chrono::NaiveDate, PlanExport>,
+}
+
+/// Usage numbers for a single user of the instance.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct UserStats {
+    pub user_id: String,
+    pub recipes: i64,
+    pub plans: i64,
+    /// Sessions that haven't expired yet.
+    pub sessions: i64,
+    /// Bytes of recipe, category, staples and settings text stored.
+    pub storage_bytes: i64,
+}
+
+/// Usage numbers for everyone on the instance.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct InstanceStats {
+    pub users: Vec<UserStats>,
+    /// The sums across all users. Sessions without a logged in user are
+    /// counted here too.
+    pub totals: UserStats,
+}
+
+impl InstanceStats {
+    pub fn new(users: Vec<UserStats>, anonymous_sessions: i64) -> Self {
+        let mut totals = UserStats {
+            user_id: format!("{} users", users.len()),
+            sessions: anonymous_sessions,
+            ..Default::default()
+        };
+        for user in users.iter() {
+            totals.recipes += user.recipes;
+            totals.plans += user.plans;
+            totals.sessions += user.sessions;
+            totals.storage_bytes += user.storage_bytes;
+        }
+        Self { users, totals }
+    }
+}
+
+pub type InstanceStatsResponse = Response<InstanceStats>;
+
+impl From<InstanceStats> for InstanceStatsResponse {
+    fn from(stats: InstanceStats) -> Self {
+        Response::Success(stats)
+    }
+}
+
+/// A recipe and how many times it has been planned.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RecipeCount {
+    pub recipe_id: String,
+    pub title: String,
+    pub count: i64,
+}
+
+pub type RecipeCountsResponse = Response<Vec<RecipeCount>>;
+
+impl From<Vec<RecipeCount>> for RecipeCountsResponse {
+    fn from(counts: Vec<RecipeCount>) -> Self {
+        Response::Success(counts)
+    }
+}
+
+/// An ingredient and how many planned meals called for it.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct IngredientCount {
+    pub name: String,
+    pub count: i64,
+}
+
+pub type IngredientCountsResponse = Response<Vec<IngredientCount>>;
+
+impl From<Vec<IngredientCount>> for IngredientCountsResponse {
+    fn from(counts: Vec<IngredientCount>) -> Self {
+        Response::Success(counts)
+    }
+}
+
+/// How many plans were made in a month. Months look like `2023-10`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MonthCount {
+    pub month: String,
+    pub plans: i64,
+}
+
+/// Numbers about how often a user plans. Plans without any recipes in them
+/// aren't counted.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct PlanStats {
+    /// The number of plans in each month oldest first.
+    pub months: Vec<MonthCount>,
+    /// The number of weeks that have a plan in them.
+    pub weeks: i64,
+    /// The average number of meals planned in the weeks that have a plan.
+    pub average_recipes_per_week: f64,
+}
+
+impl PlanStats {
+    /// Work out the stats from the number of meals in each plan.
+    pub fn new(meal_counts: &BTreeMap<chrono::NaiveDate, i64>, week_start: WeekStart) -> Self {
+        let mut months: BTreeMap<String, i64> = BTreeMap::new();
+        let mut weeks: BTreeMap<chrono::NaiveDate, i64> = BTreeMap::new();
+        for (date, meals) in meal_counts.iter() {
+            *months.entry(date.format("%Y-%m").to_string()).or_insert(0) += 1;
+            *weeks.entry(week_start.start_of_week(*date)).or_insert(0) += meals;
+        }
+        let total: i64 = weeks.values().sum();
+        Self {
+            months: months
+                .into_iter()
+                .map(|(month, plans)| MonthCount { month, plans })
+                .collect(),
+            weeks: weeks.len() as i64,
+            average_recipes_per_week: if weeks.is_empty() {
+                0.0
+            } else {
+                total as f64 / weeks.len() as f64
+            },
+        }
+    }
+}
+
+pub type PlanStatsResponse = Response<PlanStats>;
+
+impl From<PlanStats> for PlanStatsResponse {
+    fn from(stats: PlanStats) -> Self {
+        Response::Success(stats)
+    }
+}
+
+/// What kind of failure a [ClientError] reports.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum ClientErrorKind {
+    Panic,
+    /// An error logged by the client.
+    Error,
+}
+
+/// A failure in the web client sent to the server so it shows up in the
+/// server logs. Clients only send these if the user agreed to it.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ClientError {
+    pub kind: ClientErrorKind,
+    pub message: String,
+    /// The source location of the failure when we know it.
+    pub location: Option<String>,
+    /// The page the client was on.
+    pub url: String,
+    pub user_agent: String,
+    /// The version of the client.
+    pub version: String,
+}
+
+pub type PlanRulesResponse = Response<Vec<StoredPlanRule>>;
+
+impl From<Vec<StoredPlanRule>> for PlanRulesResponse {
+    fn from(rules: Vec<StoredPlanRule>) -> Self {
+        Response::Success(rules)
+    }
+}
+
+pub type PlanRuleResponse = Response<StoredPlanRule>;
+
+pub type RuleViolationsResponse = Response<Vec<RuleViolation>>;
+
+impl From<Vec<RuleViolation>> for RuleViolationsResponse {
+    fn from(violations: Vec<RuleViolation>) -> Self {
+        Response::Success(violations)
+    }
+}
+
+/// A recipe the plan suggestion endpoint thinks fits the user's rules.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PlanSuggestion {
+    pub recipe_id: String,
+    pub title: String,
+    /// Why the recipe was suggested if it helps meet a rule.
+    pub reason: Option<String>,
+}
+
+pub type PlanSuggestionsResponse = Response<Vec<PlanSuggestion>>;
+
+impl From<Vec<PlanSuggestion>> for PlanSuggestionsResponse {
+    fn from(suggestions: Vec<PlanSuggestion>) -> Self {
+        Response::Success(suggestions)
+    }
+}
+
+/// Extra portions of a recipe that were cooked and put in the freezer.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FreezerItem {
+    pub id: i64,
+    pub recipe_id: String,
+    pub portions: i32,
+    pub frozen_on: chrono::NaiveDate,
+}
+
+/// Portions of a recipe to add to the freezer.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct NewFreezerItem {
+    pub recipe_id: String,
+    pub portions: i32,
+    pub frozen_on: chrono::NaiveDate,
+}
+
+impl NewFreezerItem {
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        if self.recipe_id.trim().is_empty() {
+            return Err(ValidationError::new(
+                "",
+                "recipe_id",
+                "A recipe is required",
+            ));
+        }
+        if self.portions < 1 {
+            return Err(ValidationError::new(
+                &self.recipe_id,
+                "portions",
+                "There must be at least one portion",
+            ));
+        }
+        Ok(())
+    }
+}
+
+pub type FreezerResponse = Response<Vec<FreezerItem>>;
+
+/// The freezer item after a change. Items are removed once every portion
+/// has been used so there may be nothing left.
+pub type FreezerItemResponse = Response<Option<FreezerItem>>;
+
+/// The recipes in a plan that are eaten from the freezer rather than cooked.
+pub type PlanFreezerMealsResponse = Response<BTreeSet<String>>;
+
+/// Marks a planned recipe as cooked. Marking it again only updates the day it
+/// was cooked.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MarkCooked {
+    pub recipe_id: String,
+    /// Extra portions that were cooked and put in the freezer.
+    #[serde(default)]
+    pub extra_portions: i32,
+    /// Take the portions for a freezer meal out of the freezer or a cooked
+    /// recipe's ingredients out of the amounts in the plan's inventory.
+    #[serde(default)]
+    pub deplete: bool,
+}
+
+impl MarkCooked {
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        if self.recipe_id.trim().is_empty() {
+            return Err(ValidationError::new(
+                "",
+                "recipe_id",
+                "A recipe is required",
+            ));
+        }
+        if self.extra_portions < 0 {
+            return Err(ValidationError::new(
+                &self.recipe_id,
+                "extra_portions",
+                "Extra portions can't be negative",
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// The recipes in a plan that have been cooked.
+pub type CookedResponse = Response<BTreeSet<String>>;
+
+/// The date each recipe was last cooked.
+pub type LastCookedResponse = Response<BTreeMap<String, chrono::NaiveDate>>;
+
+/// The ids of the recipes that are archived.
+pub type ArchivedResponse = Response<BTreeSet<String>>;
+
+/// Recipes to delete in one request.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BulkRecipes {
+    pub recipe_ids: Vec<String>,
+}
+
+/// Tags to add to and remove from many recipes. Tags are lowercased like
+/// they are when recipes are parsed.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BulkTags {
+    pub recipe_ids: Vec<String>,
+    #[serde(default)]
+    pub add: BTreeSet<String>,
+    #[serde(default)]
+    pub remove: BTreeSet<String>,
+}
+
+/// Archive or unarchive many recipes.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BulkArchive {
+    pub recipe_ids: Vec<String>,
+    pub archived: bool,
+}
+
+/// Set the category of many recipes. None clears it.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BulkCategory {
+    pub recipe_ids: Vec<String>,
+    pub category: Option<String>,
+}
+
+/// The result of a bulk change. Ids that don't match one of the user's
+/// recipes are reported instead of failing the whole request.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct BulkResult {
+    pub updated: Vec<String>,
+    pub not_found: Vec<String>,
+}
+
+pub type BulkResponse = Response<BulkResult>;
+
+/// A read-only link to a week of plans that works without logging in.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PlanShare {
+    pub token: String,
+    /// The first day of the shared week.
+    pub start: chrono::NaiveDate,
+}
+
+impl PlanShare {
+    /// The path of the page that shows the shared week.
+    pub fn path(&self) -> String {
+        format!("/share/plan/{}", self.token)
+    }
+
+    /// The path of a QR code image for the link.
+    pub fn qr_path(&self) -> String {
+        format!("/share/{}/qr.png", self.token)
+    }
+}
+
+/// The share for a week if it is shared.
+pub type PlanShareResponse = Response<Option<PlanShare>>;
+
+/// A recipe in a shared plan.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SharedRecipe {
+    pub title: String,
+    pub count: i32,
+    #[serde(default)]
+    pub meal: MealType,
+}
+
+/// A plan in a shared week with what is still left to buy for it.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SharedPlan {
+    pub date: chrono::NaiveDate,
+    pub recipes: Vec<SharedRecipe>,
+    pub shopping_list: Vec<String>,
+}
+
+/// The plans in a shared week. Only titles and amounts are included so
+/// nothing else about the user is given away.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SharedWeek {
+    pub start: chrono::NaiveDate,
+    pub end: chrono::NaiveDate,
+    pub plans: Vec<SharedPlan>,
+}
+
+pub type SharedWeekResponse = Response<SharedWeek>;
+
+impl From<SharedWeek> for SharedWeekResponse {
+    fn from(week: SharedWeek) -> Self {
+        Response::Success(week)
+    }
+}
+
+/// A GET endpoint of the v2 api. The web ui's api explorer lists these.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Endpoint {
+    /// The path under `/api/v2`. Parameters are written as `:name` and can be
+    /// in the path or the query.
+    pub path: &'static str,
+    /// The type the endpoint responds with.
+    pub response: &'static str,
+    pub description: &'static str,
+}
+
+impl Endpoint {
+    /// The names of the parameters in the path.
+    pub fn params(&self) -> Vec<&'static str> {
+        self.path
+            .split(|c| c == '/' || c == '?' || c == '&' || c == '=')
+            .filter_map(|part| part.strip_prefix(':'))
+            .collect()
+    }
+
+    /// The path with each parameter replaced by its value. Missing values are
+    /// left empty.
+    pub fn fill(&self, values: &BTreeMap<String, String>) -> String {
+        let mut path = self.path.to_owned();
+        // Longer names go first so a name that is the prefix of another one
+        // doesn't replace part of it.
+        let mut params = self.params();
+        params.sort_by_key(|name| std::cmp::Reverse(name.len()));
+        for name in params {
+            let value = values.get(name).map(String::as_str).unwrap_or("");
+            path = path.replace(&format!(":{}", name), value.trim());
+        }
+        path
+    }
+}
+
+macro_rules! endpoint {
+    ($path:expr, $response:ty, $description:expr) => {{
+        // Make sure the response type exists.
+        let _ = std::marker::PhantomData::<$response>;
+        Endpoint {
+            path: $path,
+            response: stringify!($response),
+            description: $description,
+        }
+    }};
+}
+
+/// The GET endpoints of the v2 api.
+pub fn get_endpoints() -> Vec<Endpoint> {
+    vec![
+        endpoint!("/account", AccountResponse, "The logged in user"),
+        endpoint!("/settings", UserSettingsResponse, "Your settings"),
+        endpoint!(
+            "/version",
+            DataVersionResponse,
+            "Goes up every time your data changes"
+        ),
+        endpoint!("/recipes", RecipeEntryResponse, "All of your recipes"),
+        endpoint!(
+            "/recipes/index",
+            RecipeIndexResponse,
+            "Recipe titles, tags, and times"
+        ),
+        endpoint!(
+            "/recipes/parsed",
+            ParsedRecipesResponse,
+            "All of your recipes parsed"
+        ),
+        endpoint!("/recipes/trash", TrashResponse, "Deleted recipes"),
+        endpoint!(
+            "/recipes/last_cooked",
+            LastCookedResponse,
+            "When each recipe was last cooked"
+        ),
+        endpoint!(
+            "/stats/recipes",
+            RecipeCountsResponse,
+            "Recipes by how often they were planned"
+        ),
+        endpoint!(
+            "/stats/ingredients",
+            IngredientCountsResponse,
+            "Ingredients by how many planned meals used them"
+        ),
+        endpoint!(
+            "/stats/plans",
+            PlanStatsResponse,
+            "Plans per month and recipes per week"
+        ),
+        endpoint!(
+            "/recipes/archived",
+            ArchivedResponse,
+            "Recipes hidden from planning"
+        ),
+        endpoint!(
+            "/recipe/:recipe_id",
+            Response<Option<RecipeEntry>>,
+            "One recipe"
+        ),
+        endpoint!(
+            "/recipe/:recipe_id/parsed",
+            ParsedRecipeResponse,
+            "One recipe parsed"
+        ),
+        endpoint!(
+            "/recipe/:recipe_id/steps/:step",
+            RecipeStepResponse,
+            "One step of a recipe"
+        ),
+        endpoint!(
+            "/recipe/:recipe_id/prep",
+            PrepListResponse,
+            "What to prepare before cooking a recipe"
+        ),
+        endpoint!("/plan", PlanDataResponse, "The latest plan"),
+        endpoint!(
+            "/plan/all",
+            Response<Vec<chrono::NaiveDate>>,
+            "The dates of every plan"
+        ),
+        endpoint!(
+            "/plan/since/:date",
+            PlanHistoryResponse,
+            "Plans since a date"
+        ),
+        endpoint!(
+            "/plan/range?from=:from&to=:to",
+            PlanHistoryResponse,
+            "Plans between two dates"
+        ),
+        endpoint!("/plan/week", PlanWeekResponse, "Plans this week"),
+        endpoint!("/plan/today", TodayResponse, "Today's meals"),
+        endpoint!(
+            "/plan/week/:date/share",
+            PlanShareResponse,
+            "The share link for the week of a date"
+        ),
+        endpoint!(
+            "/shared/plan/:token",
+            SharedWeekResponse,
+            "A shared week of plans"
+        ),
+        endpoint!("/plan/at/:date", PlanDataResponse, "The plan for a date"),
+        endpoint!(
+            "/plan/at/:date/meals",
+            PlanMealsResponse,
+            "The meals each recipe in a plan fills"
+        ),
+        endpoint!(
+            "/plan/at/:date/freezer",
+            PlanFreezerMealsResponse,
+            "Freezer meals in a plan"
+        ),
+        endpoint!(
+            "/plan/at/:date/cooked",
+            CookedResponse,
+            "Recipes cooked from a plan"
+        ),
+        endpoint!(
+            "/plan/at/:date/suggestions",
+            PlanSuggestionsResponse,
+            "Recipe suggestions for a plan"
+        ),
+        endpoint!("/plan/rules", PlanRulesResponse, "Your planning rules"),
+        endpoint!("/freezer", FreezerResponse, "What is in the freezer"),
+        endpoint!(
+            "/inventory",
+            InventoryResponse,
+            "The latest shopping list state"
+        ),
+        endpoint!(
+            "/inventory/at/:date",
+            InventoryResponse,
+            "The shopping list state for a plan"
+        ),
+        endpoint!(
+            "/inventory/at/:date/history",
+            InventoryHistoryResponse,
+            "Every saved shopping list state for a plan"
+        ),
+        endpoint!(
+            "/inventory/diff?from=:from&to=:to",
+            InventoryDiffResponse,
+            "Shopping list changes between two plans"
+        ),
+        endpoint!(
+            "/category_map",
+            CategoryMappingResponse,
+            "Ingredient categories"
+        ),
+        endpoint!(
+            "/category_map/suggestions",
+            CategorySuggestionResponse,
+            "Suggested ingredient categories"
+        ),
+        endpoint!("/staples", Response<Option<String>>, "Your staples"),
+        endpoint!(
+            "/substitutions",
+            SubstitutionsResponse,
+            "Ingredient substitutions"
+        ),
+        endpoint!(
+            "/admin/stats",
+            InstanceStatsResponse,
+            "Instance stats for admins"
+        ),
+    ]
+}
+
+#[cfg(test)]
+mod test;
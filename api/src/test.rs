@@ -0,0 +1,115 @@
+// Copyright 2022 Jeremy Wall (Jeremy@marzhilsltudios.com)
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+use crate::*;
+
+fn key(name: &str) -> IngredientKey {
+    IngredientKey::new(name.to_owned(), None, "Volume".to_owned())
+}
+
+fn inventory(filtered: &[&str], amts: &[(&str, &str)], extras: &[(&str, &str)]) -> InventoryData {
+    InventoryData {
+        filtered_ingredients: filtered.iter().map(|name| key(name)).collect(),
+        modified_amts: amts
+            .iter()
+            .map(|(name, amt)| (key(name), amt.to_string()))
+            .collect(),
+        extra_items: extras
+            .iter()
+            .map(|(amt, name)| (amt.to_string(), name.to_string()))
+            .collect(),
+    }
+}
+
+#[test]
+fn test_inventory_diff_of_the_same_inventory_is_empty() {
+    let same = inventory(&["flour"], &[("milk", "2 cups")], &[("1", "lemon")]);
+    assert_eq!(InventoryDiff::new(&same, &same), InventoryDiff::default());
+}
+
+#[test]
+fn test_inventory_diff_checked_off_and_unchecked() {
+    let from = inventory(&["flour", "sugar"], &[], &[]);
+    let to = inventory(&["flour", "eggs"], &[], &[]);
+    let diff = InventoryDiff::new(&from, &to);
+    assert_eq!(diff.checked_off, vec![key("eggs")]);
+    assert_eq!(diff.unchecked, vec![key("sugar")]);
+    assert!(diff.changed_amts.is_empty());
+}
+
+#[test]
+fn test_inventory_diff_changed_amounts() {
+    let from = inventory(&[], &[("milk", "2 cups"), ("butter", "1 stick")], &[]);
+    let to = inventory(&[], &[("milk", "3 cups"), ("salt", "1 tsp")], &[]);
+    let diff = InventoryDiff::new(&from, &to);
+    assert_eq!(
+        diff.changed_amts,
+        vec![
+            (key("butter"), Some("1 stick".to_owned()), None),
+            (
+                key("milk"),
+                Some("2 cups".to_owned()),
+                Some("3 cups".to_owned())
+            ),
+            (key("salt"), None, Some("1 tsp".to_owned())),
+        ]
+    );
+}
+
+#[test]
+fn test_inventory_diff_added_and_removed_extras() {
+    let from = inventory(&[], &[], &[("1", "lemon"), ("2", "limes")]);
+    let to = inventory(&[], &[], &[("2", "limes"), ("1", "basil")]);
+    let diff = InventoryDiff::new(&from, &to);
+    assert_eq!(
+        diff.added_extras,
+        vec![("1".to_owned(), "basil".to_owned())]
+    );
+    assert_eq!(
+        diff.removed_extras,
+        vec![("1".to_owned(), "lemon".to_owned())]
+    );
+}
+
+#[test]
+fn test_parse_utc_offset() {
+    let east = |seconds| chrono::FixedOffset::east_opt(seconds).unwrap();
+    assert_eq!(parse_utc_offset("+05:30"), Some(east(5 * 3600 + 30 * 60)));
+    assert_eq!(parse_utc_offset("-0500"), Some(east(-5 * 3600)));
+    assert_eq!(parse_utc_offset("+5"), Some(east(5 * 3600)));
+    assert_eq!(parse_utc_offset(" -12:00 "), Some(east(-12 * 3600)));
+    assert_eq!(parse_utc_offset("UTC"), Some(east(0)));
+    assert_eq!(parse_utc_offset("z"), Some(east(0)));
+    for invalid in ["", "05:30", "+", "+5:60", "+15:00", "+ab", "America/Denver"] {
+        assert_eq!(parse_utc_offset(invalid), None, "{:?}", invalid);
+    }
+}
+
+#[test]
+fn test_format_utc_offset_round_trips() {
+    for seconds in [
+        0,
+        5 * 3600 + 30 * 60,
+        -5 * 3600,
+        -9 * 3600 - 30 * 60,
+        14 * 3600,
+    ] {
+        let offset = chrono::FixedOffset::east_opt(seconds).unwrap();
+        let formatted = format_utc_offset(&offset);
+        assert_eq!(parse_utc_offset(&formatted), Some(offset), "{}", formatted);
+    }
+    let offset = chrono::FixedOffset::east_opt(-5 * 3600).unwrap();
+    assert_eq!(format_utc_offset(&offset), "-05:00");
+    let offset = chrono::FixedOffset::east_opt(0).unwrap();
+    assert_eq!(format_utc_offset(&offset), "+00:00");
+}
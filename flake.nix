@@ -55,7 +55,7 @@
                 });
                 kitchenWasmDebug = kitchenWasmGen {
                     inherit pkgs rust-wasm wasm-bindgen version;
-                    features = "--features debug_logs";
+                    features = "--features debug_logs,debug_panel";
                 };
                 kitchenDebug = (kitchenGen {
                     inherit pkgs version naersk-lib rust-wasm;
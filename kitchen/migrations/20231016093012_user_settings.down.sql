@@ -0,0 +1,2 @@
+-- Add down migration script here
+drop table user_settings;
\ No newline at end of file
@@ -0,0 +1,7 @@
+-- Add up migration script here
+create table user_settings (
+    user_id TEXT NOT NULL,
+    setting_key TEXT NOT NULL,
+    setting_value TEXT NOT NULL,
+    primary key(user_id, setting_key)
+);
\ No newline at end of file
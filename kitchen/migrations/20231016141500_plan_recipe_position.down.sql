@@ -0,0 +1,2 @@
+-- Add down migration script here
+alter table plan_recipes drop column position;
\ No newline at end of file
@@ -0,0 +1,2 @@
+-- Add up migration script here
+alter table plan_recipes add column position INTEGER NOT NULL DEFAULT 0;
\ No newline at end of file
@@ -0,0 +1,2 @@
+-- Add down migration script here
+alter table recipes drop column revision;
\ No newline at end of file
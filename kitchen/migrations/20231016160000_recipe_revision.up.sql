@@ -0,0 +1,2 @@
+-- Add up migration script here
+alter table recipes add column revision INTEGER NOT NULL DEFAULT 0;
\ No newline at end of file
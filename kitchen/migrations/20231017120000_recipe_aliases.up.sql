@@ -0,0 +1,7 @@
+-- Add up migration script here
+create table recipe_aliases(
+    user_id TEXT NOT NULL,
+    alias_id TEXT NOT NULL,
+    recipe_id TEXT NOT NULL,
+    primary key(user_id, alias_id)
+);
@@ -0,0 +1,9 @@
+-- Add up migration script here
+create table recipe_trash(
+    user_id TEXT NOT NULL,
+    recipe_id TEXT NOT NULL,
+    recipe_text TEXT,
+    category TEXT,
+    deleted_at TEXT NOT NULL,
+    primary key(user_id, recipe_id)
+);
@@ -0,0 +1,9 @@
+-- Add up migration script here
+create table inventory_history(
+    user_id TEXT NOT NULL,
+    plan_date DATE NOT NULL,
+    saved_at TEXT NOT NULL,
+    inventory BLOB NOT NULL
+);
+
+create index inventory_history_lookup on inventory_history (user_id, plan_date, saved_at);
@@ -0,0 +1,8 @@
+-- Add up migration script here
+create table plan_rules(
+    id INTEGER PRIMARY KEY,
+    user_id TEXT NOT NULL,
+    rule BLOB NOT NULL
+);
+
+create index plan_rules_user on plan_rules (user_id);
@@ -0,0 +1,3 @@
+-- Add down migration script here
+drop table plan_freezer_meals;
+drop table freezer_items;
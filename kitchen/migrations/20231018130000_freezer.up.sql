@@ -0,0 +1,17 @@
+-- Add up migration script here
+create table freezer_items(
+    id INTEGER PRIMARY KEY,
+    user_id TEXT NOT NULL,
+    recipe_id TEXT NOT NULL,
+    portions INTEGER NOT NULL,
+    frozen_on DATE NOT NULL
+);
+
+create index freezer_items_user on freezer_items (user_id);
+
+create table plan_freezer_meals(
+    user_id TEXT NOT NULL,
+    plan_date DATE NOT NULL,
+    recipe_id TEXT NOT NULL,
+    primary key(user_id, plan_date, recipe_id)
+);
@@ -0,0 +1,8 @@
+-- Add up migration script here
+create table cooked_recipes(
+    user_id TEXT NOT NULL,
+    plan_date DATE NOT NULL,
+    recipe_id TEXT NOT NULL,
+    cooked_on DATE NOT NULL,
+    primary key(user_id, plan_date, recipe_id)
+);
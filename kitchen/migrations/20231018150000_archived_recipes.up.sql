@@ -0,0 +1,7 @@
+-- Add up migration script here
+create table archived_recipes(
+    user_id TEXT NOT NULL,
+    recipe_id TEXT NOT NULL,
+    archived_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    primary key(user_id, recipe_id)
+);
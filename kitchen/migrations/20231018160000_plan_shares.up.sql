@@ -0,0 +1,8 @@
+-- Add up migration script here
+create table plan_shares(
+    token TEXT NOT NULL PRIMARY KEY,
+    user_id TEXT NOT NULL,
+    week_start DATE NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    unique(user_id, week_start)
+);
@@ -0,0 +1,5 @@
+-- Add up migration script here
+create table scheduled_jobs(
+    name TEXT NOT NULL PRIMARY KEY,
+    last_run TIMESTAMP NOT NULL
+);
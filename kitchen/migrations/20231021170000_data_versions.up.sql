@@ -0,0 +1,5 @@
+-- Add up migration script here
+create table data_versions(
+    user_id TEXT NOT NULL PRIMARY KEY,
+    version INTEGER NOT NULL
+);
@@ -0,0 +1,4 @@
+-- Add down migration script here
+alter table sessions drop column expires_at;
+alter table sessions drop column created_at;
+alter table sessions drop column user_id;
@@ -0,0 +1,6 @@
+-- Add up migration script here
+alter table sessions add column user_id TEXT;
+alter table sessions add column created_at TIMESTAMP;
+alter table sessions add column expires_at TIMESTAMP;
+-- Sessions from before this have no times so their retention window starts now.
+update sessions set created_at = CURRENT_TIMESTAMP;
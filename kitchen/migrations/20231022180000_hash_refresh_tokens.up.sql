@@ -0,0 +1,4 @@
+-- Add up migration script here
+-- Tokens are stored hashed now and the old ones were stored in plaintext so
+-- remembered devices have to log in again.
+delete from refresh_tokens;
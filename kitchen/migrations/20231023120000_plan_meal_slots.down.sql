@@ -0,0 +1,13 @@
+-- Add down migration script here
+create table plan_meal_recipes(
+    user_id TEXT NOT NULL,
+    plan_date DATE NOT NULL,
+    recipe_id TEXT NOT NULL,
+    meal TEXT NOT NULL,
+    primary key(user_id, plan_date, recipe_id)
+);
+insert into plan_meal_recipes (user_id, plan_date, recipe_id, meal)
+    select user_id, plan_date, recipe_id, min(meal) from plan_meals
+    group by user_id, plan_date, recipe_id;
+drop table plan_meals;
+alter table plan_meal_recipes rename to plan_meals;
@@ -0,0 +1,13 @@
+-- Add up migration script here
+-- A recipe can fill more than one meal in a day so the meal is part of the key.
+create table plan_meal_slots(
+    user_id TEXT NOT NULL,
+    plan_date DATE NOT NULL,
+    recipe_id TEXT NOT NULL,
+    meal TEXT NOT NULL,
+    primary key(user_id, plan_date, meal, recipe_id)
+);
+insert into plan_meal_slots (user_id, plan_date, recipe_id, meal)
+    select user_id, plan_date, recipe_id, meal from plan_meals;
+drop table plan_meals;
+alter table plan_meal_slots rename to plan_meals;
@@ -0,0 +1,111 @@
+// Copyright 2023 Jeremy Wall (Jeremy@marzhilsltudios.com)
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//! Check that a deployment is ready before serving it.
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use crate::data_dir::ensure_dir;
+use crate::web::{self, blob::BlobStore};
+
+/// What a server needs to start.
+#[derive(Debug)]
+pub struct Deployment {
+    pub store_path: PathBuf,
+    pub recipe_dir_path: PathBuf,
+    pub recipes_writable: bool,
+    /// The directory blobs are kept in unless they are kept in S3.
+    pub blob_dir_path: Option<PathBuf>,
+    pub blobs: Arc<dyn BlobStore>,
+    pub listen: String,
+    /// The certificate and key paths when serving with TLS.
+    pub tls: Option<(String, String)>,
+}
+
+fn check_pem(path: &str) -> Result<String, String> {
+    let contents =
+        std::fs::read_to_string(path).map_err(|e| format!("Unable to read {}: {}", path, e))?;
+    if contents.contains("-----BEGIN") {
+        Ok(path.to_owned())
+    } else {
+        Err(format!("{} is not a PEM file", path))
+    }
+}
+
+async fn check_blobs(blobs: &dyn BlobStore) -> Result<String, String> {
+    let key = format!("check/{}.txt", uuid::Uuid::new_v4().simple());
+    blobs.put(&key, b"kitchen".to_vec()).await?;
+    let found = blobs.get(&key).await?;
+    blobs.delete(&key).await?;
+    match found {
+        Some(bytes) if bytes == b"kitchen" => Ok(format!("{:?}", blobs)),
+        _ => Err(format!("{:?} didn't return what was written", blobs)),
+    }
+}
+
+/// Run every check for the deployment and print how each went. Returns
+/// whether they all passed.
+pub async fn check_deployment(deployment: &Deployment) -> bool {
+    let mut results: Vec<(&str, Result<String, String>)> = Vec::new();
+    results.push((
+        "listen address",
+        deployment
+            .listen
+            .parse::<SocketAddr>()
+            .map(|addr| addr.to_string())
+            .map_err(|e| format!("{} is not an <addr>:<port>: {}", deployment.listen, e)),
+    ));
+    let store = ensure_dir(&deployment.store_path, true)
+        .map(|_| deployment.store_path.to_string_lossy().into_owned());
+    let store_ok = store.is_ok();
+    results.push(("store directory", store));
+    if store_ok {
+        results.push((
+            "database",
+            web::pending_migrations(deployment.store_path.clone())
+                .await
+                .map(|pending| match pending.len() {
+                    0 => "up to date".to_owned(),
+                    n => format!("{} migrations will run on start: {}", n, pending.join(", ")),
+                }),
+        ));
+    }
+    results.push((
+        "recipe directory",
+        ensure_dir(&deployment.recipe_dir_path, deployment.recipes_writable)
+            .map(|_| deployment.recipe_dir_path.to_string_lossy().into_owned()),
+    ));
+    if let Some(dir) = &deployment.blob_dir_path {
+        results.push((
+            "blob directory",
+            ensure_dir(dir, true).map(|_| dir.to_string_lossy().into_owned()),
+        ));
+    }
+    results.push(("blob store", check_blobs(deployment.blobs.as_ref()).await));
+    if let Some((cert_path, key_path)) = &deployment.tls {
+        results.push(("tls certificate", check_pem(cert_path)));
+        results.push(("tls key", check_pem(key_path)));
+    }
+    let mut passed = true;
+    for (name, result) in results {
+        match result {
+            Ok(detail) => println!("ok\t{}\t{}", name, detail),
+            Err(err) => {
+                passed = false;
+                println!("FAIL\t{}\t{}", name, err);
+            }
+        }
+    }
+    passed
+}
@@ -19,7 +19,7 @@ use std::path::Path;
 
 use csv;
 
-use recipes::{parse, IngredientAccumulator, Recipe};
+use recipes::{parse, serialize, IngredientAccumulator, Recipe};
 use tracing::{error, info, instrument, warn};
 
 #[derive(Debug)]
@@ -65,6 +65,26 @@ where
     Ok(parse::as_recipe(&i)?)
 }
 
+/// Rewrite a recipe file in the canonical recipe format. Returns whether the
+/// file needed formatting. When `check` is true the file is left untouched.
+#[instrument]
+pub fn format_recipe_file<P>(path: P, check: bool) -> Result<bool, ParseError>
+where
+    P: AsRef<Path> + Debug,
+{
+    let mut br = BufReader::new(try_open!(path));
+    let mut text = String::new();
+    br.read_to_string(&mut text)?;
+    let formatted = serialize::format_recipe(&text)?;
+    if formatted == text {
+        return Ok(false);
+    }
+    if !check {
+        std::fs::write(&path, formatted)?;
+    }
+    Ok(true)
+}
+
 #[instrument]
 pub fn read_menu_list<P>(path: P) -> Result<Vec<Recipe>, ParseError>
 where
@@ -0,0 +1,69 @@
+// Copyright 2023 Jeremy Wall (Jeremy@marzhilsltudios.com)
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//! Where the server keeps its state. Everything can live under one data
+//! directory so a container only needs a single volume.
+use std::path::{Path, PathBuf};
+
+/// The environment variable naming the data directory.
+pub const DATA_DIR_ENV: &'static str = "KITCHEN_DATA_DIR";
+
+/// The data directory from `--data_dir` or else the environment.
+pub fn data_dir(flag: Option<&str>) -> Option<PathBuf> {
+    flag.map(PathBuf::from).or_else(|| {
+        std::env::var_os(DATA_DIR_ENV)
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+    })
+}
+
+/// The places state is kept under a data directory.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DataPaths {
+    /// The sqlite database and sessions.
+    pub store: PathBuf,
+    /// Recipe files for logged out users.
+    pub recipes: PathBuf,
+    /// Uploaded images and backups.
+    pub blobs: PathBuf,
+}
+
+impl DataPaths {
+    pub fn new<P: AsRef<Path>>(root: P) -> Self {
+        let root = root.as_ref();
+        Self {
+            store: root.join("store"),
+            recipes: root.join("recipes"),
+            blobs: root.join("blobs"),
+        }
+    }
+}
+
+/// Create the directory if it is missing and make sure we can read it and,
+/// if `writable`, write to it.
+pub fn ensure_dir(path: &Path, writable: bool) -> Result<(), String> {
+    let name = path.to_string_lossy();
+    std::fs::create_dir_all(path).map_err(|e| format!("Unable to create {}: {}", name, e))?;
+    if !path.is_dir() {
+        return Err(format!("{} is not a directory", name));
+    }
+    std::fs::read_dir(path).map_err(|e| format!("Unable to read {}: {}", name, e))?;
+    if writable {
+        // The permission bits don't tell the whole story with container
+        // user mappings and read only mounts so just try it.
+        let probe = path.join(".kitchen-write-check");
+        std::fs::write(&probe, b"").map_err(|e| format!("Unable to write to {}: {}", name, e))?;
+        let _ = std::fs::remove_file(&probe);
+    }
+    Ok(())
+}
@@ -15,6 +15,7 @@ use std::env;
 use std::io;
 use std::net::SocketAddr;
 use std::path::PathBuf;
+use std::sync::Arc;
 
 use clap;
 use clap::ArgMatches;
@@ -22,20 +23,31 @@ use clap::{clap_app, crate_authors, crate_version};
 use tracing::{error, info, instrument, warn, Level};
 use tracing_subscriber::FmtSubscriber;
 
+mod check;
 mod cli;
+mod data_dir;
 mod web;
 
+use data_dir::DataPaths;
+use web::blob::{BlobStore, FsBlobStore, S3BlobStore};
+
 fn create_app<'a>() -> clap::App<'a> {
     clap_app!(kitchen =>
         (version: crate_version!())
         (author: crate_authors!())
         (about: "Kitchen Management CLI")
         (@arg verbose: --verbose -v +takes_value "Verbosity level for logging (error, warn, info, debug, trace")
+        (@arg data_dir: --data_dir +takes_value +global "Directory to keep all state in. The store, recipe and blob directories default to store, recipes and blobs under it. Defaults to $KITCHEN_DATA_DIR")
         (@subcommand recipe =>
             (about: "parse a recipe file and output info about it")
             (@arg ingredients: -i --ingredients "Output the ingredients list.")
             (@arg INPUT: +required "Input recipe file to parse")
         )
+        (@subcommand fmt =>
+            (about: "rewrite recipe files in the canonical recipe format")
+            (@arg check: --check "Don't change any files. Exit with an error if any need formatting.")
+            (@arg INPUT: +required ... "Recipe files to format")
+        )
         (@subcommand groceries =>
             (about: "print out a grocery list for a set of recipes")
             (@arg csv: --csv "output ingredients as csv")
@@ -44,26 +56,113 @@ fn create_app<'a>() -> clap::App<'a> {
         (@subcommand serve =>
             (about: "Serve the interface via the web")
             (@arg recipe_dir: -d --dir +takes_value "Directory containing recipe files to use")
-            (@arg session_dir: --session_dir +takes_value +required "Session store directory to use")
+            (@arg session_dir: --session_dir +takes_value "Session store directory to use")
             (@arg tls: --tls "Use TLS to serve.")
             (@arg cert_path: --cert +takes_value "Certificate path. Required if you specified --tls.")
             (@arg key_path: --cert_key +takes_value "Certificate key path. Required if you specified --tls")
             (@arg listen: --listen +takes_value "address and port to listen on 0.0.0.0:3030")
+            (@arg writable_dir: --writable_dir +takes_value "Save recipe and category edits from this logged in user to the recipe directory. Anyone who can log in as the user can change or delete the recipe files so keep it to an account used only for that.")
+            (@arg demo: --demo "Serve the recipe directory as a read only demo. Logins and all changes are rejected.")
+            (@arg max_recipes: --max_recipes +takes_value "Maximum number of recipes each user can store")
+            (@arg max_recipe_size: --max_recipe_size +takes_value "Maximum size in bytes of a single recipe")
+            (@arg max_image_size: --max_image_size +takes_value "Maximum size in bytes of a single uploaded image")
+            (@arg db_journal_mode: --db_journal_mode +takes_value "sqlite journal mode (wal, delete, truncate, persist, memory, off). Defaults to wal")
+            (@arg db_synchronous: --db_synchronous +takes_value "sqlite synchronous setting (off, normal, full, extra). Defaults to normal")
+            (@arg db_busy_timeout: --db_busy_timeout +takes_value "Milliseconds to wait for another connection's lock before failing a write. Defaults to 5000")
+            (@arg db_max_connections: --db_max_connections +takes_value "Maximum number of sqlite connections in the pool. Defaults to 10")
+            (@arg db_min_connections: --db_min_connections +takes_value "Number of sqlite connections to keep open while idle. Defaults to 0")
+            (@arg admin: --admin +takes_value +multiple_occurrences "User allowed to see the instance stats. May be repeated.")
+            (@arg anonymous: --anonymous +takes_value "What people who aren't logged in can see: files (the recipe directory), off (nothing), or user:<user> (that user's recipes, read only). Defaults to files")
+            (@arg csp: --csp +takes_value "Content-Security-Policy header for every response, or 'off' to leave it out. Defaults to a policy for the kitchen ui")
+            (@arg referrer_policy: --referrer_policy +takes_value "Referrer-Policy header for every response, or 'off' to leave it out. Defaults to same-origin")
+            (@arg hsts: --hsts "Send Strict-Transport-Security. This is on with --tls. Use it when a proxy in front of kitchen serves https.")
+            (@arg ocr_command: --ocr_command +takes_value "Command that reads a recipe photo on stdin and writes its text to stdout, e.g. 'tesseract stdin stdout'. Enables creating recipes from photos.")
+            (@arg retention_months: --retention_months +takes_value "Months of plans and inventory history to keep for users who haven't set their own. Defaults to keeping everything")
+            (@arg backup_hours: --backup_hours +takes_value "Save an export of every account to the blob store every this many hours. Backups are off by default")
+            (@arg blob_dir: --blob_dir +takes_value "Directory to keep uploaded images and backups in. Defaults to blobs in the data directory or else the session directory")
+            (@arg s3_bucket: --s3_bucket +takes_value "Keep uploaded images and backups in this S3 bucket instead of --blob_dir. Credentials come from AWS_ACCESS_KEY_ID and AWS_SECRET_ACCESS_KEY")
+            (@arg s3_region: --s3_region +takes_value "Region of the S3 bucket. Defaults to us-east-1")
+            (@arg s3_endpoint: --s3_endpoint +takes_value "Url of an S3 compatible service like MinIO to use instead of AWS")
+        )
+        (@subcommand check =>
+            (about: "check that the directories, database, blob store and certificates for serving are usable")
+            (@arg recipe_dir: -d --dir +takes_value "Directory containing recipe files to use")
+            (@arg session_dir: --session_dir +takes_value "Session store directory to use")
+            (@arg tls: --tls "Check the certificate and key for serving with TLS.")
+            (@arg cert_path: --cert +takes_value "Certificate path. Required if you specified --tls.")
+            (@arg key_path: --cert_key +takes_value "Certificate key path. Required if you specified --tls")
+            (@arg listen: --listen +takes_value "address and port to listen on 0.0.0.0:3030")
+            (@arg writable_dir: --writable_dir "Check that the recipe directory is writable")
+            (@arg blob_dir: --blob_dir +takes_value "Directory to keep uploaded images and backups in. Defaults to blobs in the data directory or else the session directory")
+            (@arg s3_bucket: --s3_bucket +takes_value "Keep uploaded images and backups in this S3 bucket instead of --blob_dir. Credentials come from AWS_ACCESS_KEY_ID and AWS_SECRET_ACCESS_KEY")
+            (@arg s3_region: --s3_region +takes_value "Region of the S3 bucket. Defaults to us-east-1")
+            (@arg s3_endpoint: --s3_endpoint +takes_value "Url of an S3 compatible service like MinIO to use instead of AWS")
         )
         (@subcommand add_user =>
             (about: "add users to to the interface")
             (@arg recipe_dir: -d --dir +takes_value "Directory containing recipe files to load for user")
             (@arg user: -u --user +takes_value +required "username to add")
             (@arg pass: -p --pass +takes_value +required "password to add for this user")
-            (@arg session_dir: --session_dir +takes_value +required "Session store directory to use")
+            (@arg session_dir: --session_dir +takes_value "Session store directory to use")
+        )
+        (@subcommand seed =>
+            (about: "fill a store with fake users, recipes, plans and inventory history for development")
+            (@arg users: --users +takes_value "Number of users to create. Defaults to 1")
+            (@arg recipes: --recipes +takes_value "Number of recipes for each user. Defaults to 50")
+            (@arg weeks: --weeks +takes_value "Number of weeks of plans and inventory history. Defaults to 12")
+            (@arg pass: -p --pass +takes_value "password for the seeded users. Defaults to 'kitchen'")
+            (@arg seed: --seed +takes_value "Seed for the fake data. The same seed creates the same data")
+            (@arg session_dir: --session_dir +takes_value "Session store directory to use")
+        )
+        (@subcommand stats =>
+            (about: "print recipe, plan, session and storage counts for every user")
+            (@arg session_dir: --session_dir +takes_value "Session store directory to use")
+        )
+        (@subcommand prune =>
+            (about: "delete plans, inventory history, sessions and refresh tokens older than the retention window and expired logins")
+            (@arg retention_months: --retention_months +takes_value "Months of history to keep for users who haven't set their own. Without it only their expired logins are deleted")
+            (@arg dry_run: --dry_run "Print what would be deleted without deleting anything")
+            (@arg session_dir: --session_dir +takes_value "Session store directory to use")
+        )
+        (@subcommand backup =>
+            (about: "save an export of every user's account to the blob store")
+            (@arg session_dir: --session_dir +takes_value "Session store directory to use")
+            (@arg blob_dir: --blob_dir +takes_value "Directory to keep uploaded images and backups in. Defaults to blobs in the data directory or else the session directory")
+            (@arg s3_bucket: --s3_bucket +takes_value "Keep uploaded images and backups in this S3 bucket instead of --blob_dir. Credentials come from AWS_ACCESS_KEY_ID and AWS_SECRET_ACCESS_KEY")
+            (@arg s3_region: --s3_region +takes_value "Region of the S3 bucket. Defaults to us-east-1")
+            (@arg s3_endpoint: --s3_endpoint +takes_value "Url of an S3 compatible service like MinIO to use instead of AWS")
         )
     )
     .setting(clap::AppSettings::SubcommandRequiredElseHelp)
 }
 
+fn get_limit(matches: &ArgMatches, name: &str) -> Option<usize> {
+    matches.value_of(name).map(|limit| {
+        limit
+            .parse()
+            .expect(&format!("--{} must be a number but got {}", name, limit))
+    })
+}
+
+/// Like [get_limit] for settings where zero makes no sense.
+fn get_nonzero(matches: &ArgMatches, name: &str) -> Option<usize> {
+    get_limit(matches, name).map(|value| {
+        if value == 0 {
+            panic!("--{} must be greater than 0", name);
+        }
+        value
+    })
+}
+
+fn get_data_paths(matches: &ArgMatches) -> Option<DataPaths> {
+    data_dir::data_dir(matches.value_of("data_dir")).map(DataPaths::new)
+}
+
 fn get_session_store_path(matches: &ArgMatches) -> PathBuf {
     if let Some(dir) = matches.value_of("session_dir") {
         PathBuf::from(dir)
+    } else if let Some(paths) = get_data_paths(matches) {
+        paths.store
     } else {
         let mut dir = std::env::var("HOME")
             .map(PathBuf::from)
@@ -73,6 +172,53 @@ fn get_session_store_path(matches: &ArgMatches) -> PathBuf {
     }
 }
 
+fn get_recipe_dir_path(matches: &ArgMatches) -> PathBuf {
+    if let Some(dir) = matches.value_of("recipe_dir") {
+        PathBuf::from(dir)
+    } else if let Some(paths) = get_data_paths(matches) {
+        paths.recipes
+    } else {
+        std::env::current_dir().expect("Unable to get current directory. Bailing out.")
+    }
+}
+
+/// The directory blobs are kept in. None when they are kept in S3.
+fn get_blob_dir_path(matches: &ArgMatches, session_store_path: &PathBuf) -> Option<PathBuf> {
+    if matches.contains_id("s3_bucket") {
+        None
+    } else if let Some(dir) = matches.value_of("blob_dir") {
+        Some(PathBuf::from(dir))
+    } else if let Some(paths) = get_data_paths(matches) {
+        Some(paths.blobs)
+    } else {
+        Some(session_store_path.join("blobs"))
+    }
+}
+
+fn get_blob_store(matches: &ArgMatches, session_store_path: &PathBuf) -> Arc<dyn BlobStore> {
+    match get_blob_dir_path(matches, session_store_path) {
+        Some(dir) => Arc::new(FsBlobStore::new(dir)),
+        None => {
+            let store = S3BlobStore::new(
+                matches.value_of("s3_bucket").unwrap(),
+                matches.value_of("s3_region").unwrap_or("us-east-1"),
+                matches.value_of("s3_endpoint"),
+            )
+            .expect("Unable to use the S3 bucket");
+            Arc::new(store)
+        }
+    }
+}
+
+/// Create a directory the server needs or exit with an error saying why it
+/// can't be used.
+fn require_dir(name: &str, path: &PathBuf, writable: bool) {
+    if let Err(err) = data_dir::ensure_dir(path, writable) {
+        error!(err, "Unable to use the {} directory", name);
+        std::process::exit(1);
+    }
+}
+
 #[instrument]
 fn main() {
     let matches = create_app().get_matches();
@@ -107,6 +253,27 @@ fn main() {
                 error!(?err);
             }
         }
+    } else if let Some(matches) = matches.subcommand_matches("fmt") {
+        let check = matches.is_present("check");
+        let mut failed = false;
+        // The input argument is required so if we made it here then it's safe to unrwap this value.
+        for recipe_file in matches.values_of("INPUT").unwrap() {
+            match cli::format_recipe_file(recipe_file, check) {
+                Ok(true) if check => {
+                    println!("{} needs formatting", recipe_file);
+                    failed = true;
+                }
+                Ok(true) => println!("Formatted {}", recipe_file),
+                Ok(false) => (),
+                Err(err) => {
+                    error!(?err, recipe_file, "Unable to format recipe");
+                    failed = true;
+                }
+            }
+        }
+        if failed {
+            std::process::exit(1);
+        }
     } else if let Some(matches) = matches.subcommand_matches("groceries") {
         // The input argument is required so if we made it here then it's safe to unrwap this value.
         let menu_file = matches.value_of("INPUT").unwrap();
@@ -123,11 +290,7 @@ fn main() {
             }
         }
     } else if let Some(matches) = matches.subcommand_matches("serve") {
-        let recipe_dir_path = if let Some(dir) = matches.value_of("recipe_dir") {
-            PathBuf::from(dir)
-        } else {
-            std::env::current_dir().expect("Unable to get current directory. Bailing out.")
-        };
+        let recipe_dir_path = get_recipe_dir_path(matches);
         let session_store_path: PathBuf = get_session_store_path(matches);
         let listen_socket: SocketAddr = if let Some(listen_socket) = matches.value_of("listen") {
             listen_socket.parse().expect(&format!(
@@ -137,13 +300,111 @@ fn main() {
         } else {
             "127.0.0.1:3030".parse().unwrap()
         };
-        info!(listen=%listen_socket, "Launching web interface...");
+        let demo = matches.contains_id("demo");
+        if demo && matches.contains_id("writable_dir") {
+            warn!("Ignoring --writable_dir since --demo is read only");
+        }
+        let dir_editor = if demo {
+            None
+        } else {
+            matches.value_of("writable_dir").map(str::to_owned)
+        };
+        let anonymous = matches
+            .value_of("anonymous")
+            .map(|access| {
+                access
+                    .parse::<web::AnonymousAccess>()
+                    .expect("Invalid --anonymous")
+            })
+            .unwrap_or_default();
+        if demo && anonymous == web::AnonymousAccess::Off {
+            warn!("Nothing will be visible since --demo doesn't allow logging in");
+        }
+        let limits = web::Limits {
+            max_recipes: get_limit(matches, "max_recipes"),
+            max_recipe_bytes: get_limit(matches, "max_recipe_size"),
+            max_image_bytes: get_limit(matches, "max_image_size"),
+        };
+        let mut db_config = web::DbConfig::default();
+        if let Some(mode) = matches.value_of("db_journal_mode") {
+            db_config.journal_mode = mode
+                .parse()
+                .expect(&format!("Invalid --db_journal_mode {}", mode));
+        }
+        if let Some(sync) = matches.value_of("db_synchronous") {
+            db_config.synchronous = sync
+                .parse()
+                .expect(&format!("Invalid --db_synchronous {}", sync));
+        }
+        if let Some(timeout) = get_limit(matches, "db_busy_timeout") {
+            db_config.busy_timeout = std::time::Duration::from_millis(timeout as u64);
+        }
+        if let Some(max) = get_limit(matches, "db_max_connections") {
+            db_config.max_connections = max as u32;
+        }
+        if let Some(min) = get_limit(matches, "db_min_connections") {
+            db_config.min_connections = min as u32;
+        }
+        let admins = web::Admins(
+            matches
+                .values_of("admin")
+                .map(|admins| admins.map(|a| a.to_owned()).collect())
+                .unwrap_or_default(),
+        );
+        let mut security = web::SecurityHeaders::default();
+        if let Some(csp) = matches.value_of("csp") {
+            security.content_security_policy = match csp {
+                "off" => None,
+                csp => Some(csp.parse().expect(&format!("Invalid --csp {}", csp))),
+            };
+        }
+        if let Some(policy) = matches.value_of("referrer_policy") {
+            security.referrer_policy = match policy {
+                "off" => None,
+                policy => Some(
+                    policy
+                        .parse()
+                        .expect(&format!("Invalid --referrer_policy {}", policy)),
+                ),
+            };
+        }
+        security.hsts = matches.contains_id("tls") || matches.contains_id("hsts");
+        let ocr = matches
+            .value_of("ocr_command")
+            .and_then(web::ocr::CommandOcr::from_command_line)
+            .map(|ocr| Arc::new(ocr) as Arc<dyn web::ocr::OcrBackend>);
+        let schedule = web::scheduler::Schedule {
+            retention_months: get_nonzero(matches, "retention_months").map(|m| m as u32),
+            backup_interval: get_nonzero(matches, "backup_hours")
+                .map(|hours| std::time::Duration::from_secs(hours as u64 * 60 * 60)),
+        };
+        require_dir("session", &session_store_path, true);
+        require_dir("recipe", &recipe_dir_path, dir_editor.is_some());
+        if let Some(dir) = get_blob_dir_path(matches, &session_store_path) {
+            require_dir("blob", &dir, true);
+        }
+        let blobs = get_blob_store(matches, &session_store_path);
+        // The socket has to be taken before the runtime starts any threads
+        // since it changes the environment.
+        let systemd_listener = web::listener::from_systemd();
+        info!(listen=%listen_socket, ?dir_editor, demo, "Launching web interface...");
         async_std::task::block_on(async {
             if matches.contains_id("tls") {
                 web::ui_main_tls(
                     recipe_dir_path,
                     session_store_path,
+                    dir_editor,
+                    demo,
+                    limits,
+                    db_config,
+                    admins,
+                    anonymous,
+                    security,
+                    ocr,
+                    blobs,
+                    schedule,
                     listen_socket,
+                    systemd_listener,
                     matches
                         .value_of("cert_path")
                         .expect("You must provide a cert path with --cert"),
@@ -153,9 +414,57 @@ fn main() {
                 )
                 .await
             } else {
-                web::ui_main(recipe_dir_path, session_store_path, listen_socket).await
+                web::ui_main(
+                    recipe_dir_path,
+                    session_store_path,
+                    dir_editor,
+                    demo,
+                    limits,
+                    db_config,
+                    admins,
+                    anonymous,
+                    security,
+                    ocr,
+                    blobs,
+                    schedule,
+                    listen_socket,
+                    systemd_listener,
+                )
+                .await
             }
         });
+    } else if let Some(matches) = matches.subcommand_matches("check") {
+        let session_store_path: PathBuf = get_session_store_path(matches);
+        let tls = if matches.contains_id("tls") {
+            Some((
+                matches
+                    .value_of("cert_path")
+                    .expect("You must provide a cert path with --cert")
+                    .to_owned(),
+                matches
+                    .value_of("key_path")
+                    .expect("You must provide a key path with --cert_key")
+                    .to_owned(),
+            ))
+        } else {
+            None
+        };
+        let deployment = check::Deployment {
+            recipe_dir_path: get_recipe_dir_path(matches),
+            recipes_writable: matches.contains_id("writable_dir"),
+            blob_dir_path: get_blob_dir_path(matches, &session_store_path),
+            blobs: get_blob_store(matches, &session_store_path),
+            listen: matches
+                .value_of("listen")
+                .unwrap_or("127.0.0.1:3030")
+                .to_owned(),
+            tls,
+            store_path: session_store_path,
+        };
+        let passed = async_std::task::block_on(check::check_deployment(&deployment));
+        if !passed {
+            std::process::exit(1);
+        }
     } else if let Some(matches) = matches.subcommand_matches("add_user") {
         let recipe_dir_path = matches.value_of("recipe_dir").map(|dir| PathBuf::from(dir));
         let session_store_path: PathBuf = get_session_store_path(matches);
@@ -168,5 +477,36 @@ fn main() {
             )
             .await;
         });
+    } else if let Some(matches) = matches.subcommand_matches("seed") {
+        let session_store_path: PathBuf = get_session_store_path(matches);
+        let options = web::seed::SeedOptions {
+            users: get_limit(matches, "users").unwrap_or(1),
+            recipes: get_limit(matches, "recipes").unwrap_or(50),
+            weeks: get_limit(matches, "weeks").unwrap_or(12),
+            password: matches.value_of("pass").unwrap_or("kitchen").to_owned(),
+            seed: get_limit(matches, "seed").unwrap_or(1) as u64,
+        };
+        info!(?options, "Seeding store");
+        async_std::task::block_on(async {
+            web::seed::seed_store(session_store_path, options).await;
+        });
+    } else if let Some(matches) = matches.subcommand_matches("stats") {
+        let session_store_path: PathBuf = get_session_store_path(matches);
+        async_std::task::block_on(async {
+            web::print_instance_stats(session_store_path).await;
+        });
+    } else if let Some(matches) = matches.subcommand_matches("prune") {
+        let session_store_path: PathBuf = get_session_store_path(matches);
+        let retention_months = get_nonzero(matches, "retention_months").map(|m| m as u32);
+        let dry_run = matches.contains_id("dry_run");
+        async_std::task::block_on(async {
+            web::print_pruned_history(session_store_path, retention_months, dry_run).await;
+        });
+    } else if let Some(matches) = matches.subcommand_matches("backup") {
+        let session_store_path: PathBuf = get_session_store_path(matches);
+        let blobs = get_blob_store(matches, &session_store_path);
+        async_std::task::block_on(async {
+            web::backup(session_store_path, blobs).await;
+        });
     }
 }
@@ -0,0 +1,130 @@
+// Copyright 2023 Jeremy Wall (Jeremy@marzhilsltudios.com)
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//! Layers that keep every api error in the [api::Response] json format so
+//! that the client can always parse them.
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+use axum::{
+    body::{Body, HttpBody},
+    http::{header, Request, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use client_api as api;
+use http_body::{LengthLimitError, Limited};
+use tracing::{debug, error};
+
+/// The largest request body we will accept for the api.
+pub const MAX_BODY_BYTES: usize = 10 * 1024 * 1024;
+
+fn too_large() -> Response {
+    api::EmptyResponse::error_code(
+        api::ErrorCode::PayloadTooLarge,
+        format!(
+            "Request body is larger than the limit of {} bytes",
+            MAX_BODY_BYTES
+        ),
+    )
+    .into_response()
+}
+
+/// Reject requests with a body larger than [MAX_BODY_BYTES]. Bodies that
+/// declare a larger Content-Length are rejected before they are read. Every
+/// other body stops being read once it goes over the limit so chunked bodies
+/// are limited too.
+pub async fn limit_body_size(req: Request<Body>, next: Next<Body>) -> Response {
+    let content_length = req
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<usize>().ok());
+    if let Some(len) = content_length.filter(|len| *len > MAX_BODY_BYTES) {
+        debug!(len, "Rejecting oversized request body");
+        return too_large();
+    }
+    let over_limit = Arc::new(AtomicBool::new(false));
+    let req = req.map(|body| limit_body(body, over_limit.clone()));
+    let resp = next.run(req).await;
+    // The extractor that hit the limit has already turned it into some other
+    // error response so we replace that with our own.
+    if over_limit.load(Ordering::SeqCst) {
+        debug!("Request body went over the limit while it was read");
+        return too_large();
+    }
+    resp
+}
+
+/// Wrap `body` so that reading more than [MAX_BODY_BYTES] from it fails and
+/// sets `over_limit`.
+fn limit_body(body: Body, over_limit: Arc<AtomicBool>) -> Body {
+    let body = Limited::new(body, MAX_BODY_BYTES);
+    Body::wrap_stream(futures::stream::unfold(body, move |mut body| {
+        let over_limit = over_limit.clone();
+        async move {
+            let chunk = body.data().await?;
+            if let Err(err) = &chunk {
+                if err.is::<LengthLimitError>() {
+                    over_limit.store(true, Ordering::SeqCst);
+                }
+            }
+            Some((chunk, body))
+        }
+    }))
+}
+
+fn is_json(resp: &Response) -> bool {
+    resp.headers()
+        .get(header::CONTENT_TYPE)
+        .map(|v| v.as_bytes().starts_with(b"application/json"))
+        .unwrap_or(false)
+}
+
+/// Convert the plain text error responses from axum's extractors (bad json,
+/// bad path parameters, unknown routes) into [api::Response] errors.
+pub async fn json_errors<B>(req: Request<B>, next: Next<B>) -> Response {
+    let resp = next.run(req).await;
+    let status = resp.status();
+    if !(status.is_client_error() || status.is_server_error()) || is_json(&resp) {
+        return resp;
+    }
+    let mut body = resp.into_body();
+    let mut bytes = Vec::new();
+    while let Some(chunk) = body.data().await {
+        match chunk {
+            Ok(chunk) => bytes.extend_from_slice(&chunk),
+            Err(err) => {
+                error!(?err, "Unable to read error response body");
+                break;
+            }
+        }
+    }
+    let message = String::from_utf8_lossy(&bytes).trim().to_owned();
+    let message = if message.is_empty() {
+        status
+            .canonical_reason()
+            .unwrap_or("Unknown error")
+            .to_owned()
+    } else {
+        message
+    };
+    debug!(status = status.as_u16(), %message, "Normalizing error response");
+    match status {
+        StatusCode::NOT_FOUND => api::EmptyResponse::NotFound.into_response(),
+        StatusCode::UNAUTHORIZED => api::EmptyResponse::Unauthorized.into_response(),
+        _ => api::EmptyResponse::error(status.as_u16(), message).into_response(),
+    }
+}
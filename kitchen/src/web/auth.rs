@@ -16,13 +16,15 @@ use std::sync::Arc;
 
 use async_session::{Session, SessionStore};
 use axum::{
-    extract::{Extension, Host},
+    extract::{Extension, Host, Query, TypedHeader},
+    headers,
     http::{header, HeaderMap, StatusCode},
 };
 use axum_auth::AuthBasic;
 use client_api as api;
-use cookie::{Cookie, SameSite};
+use cookie::{time, Cookie, SameSite};
 use secrecy::Secret;
+use serde::Deserialize;
 use tracing::{debug, error, info, instrument};
 
 use super::storage::{self, AuthStore, UserCreds};
@@ -31,16 +33,118 @@ impl From<UserCreds> for api::AccountResponse {
     fn from(auth: UserCreds) -> Self {
         Self::Success(api::UserData {
             user_id: auth.user_id().to_owned(),
+            empty: false,
         })
     }
 }
 
-#[instrument(skip_all, fields(user=%auth.0.0))]
-pub async fn handler(
+/// The cookie holding a remembered device's refresh token.
+pub const REFRESH_COOKIE_NAME: &'static str = "kitchen-refresh-token";
+
+/// The path the refresh cookie is sent to. Nothing else needs to see it.
+const REFRESH_COOKIE_PATH: &'static str = "/api/v2/auth/refresh";
+
+#[derive(Debug, Default, Deserialize)]
+pub struct LoginParams {
+    /// Keep the device logged in with a refresh token.
+    #[serde(default)]
+    remember: bool,
+}
+
+type AuthResponse = (StatusCode, HeaderMap, axum::Json<api::AccountResponse>);
+
+fn error_response(status: StatusCode, headers: HeaderMap, message: &str) -> AuthResponse {
+    let resp = api::AccountResponse::error(status.as_u16(), message);
+    (status, headers, axum::Json::from(resp))
+}
+
+fn set_cookie(headers: &mut HeaderMap, cookie: Cookie) -> Result<(), &'static str> {
+    match cookie.to_string().parse() {
+        Err(err) => {
+            error!(?err, "Unable to parse cookie");
+            Err("Unable to parse session cookie")
+        }
+        Ok(parsed_cookie) => {
+            headers.append(header::SET_COOKIE, parsed_cookie);
+            Ok(())
+        }
+    }
+}
+
+/// Start a short lived session for a user and add its cookie to the headers.
+async fn start_session<S: AuthStore>(
+    session_store: &S,
+    user_id: &str,
+    domain: &str,
+    headers: &mut HeaderMap,
+) -> Result<(), &'static str> {
+    // 1. Create a session identifier.
+    let mut session = Session::new();
+    if let Err(err) = session.insert("user_id", user_id) {
+        error!(?err, "Unable to insert user id into session");
+        return Err("Unable to insert user id into session");
+    }
+    session.expire_in(std::time::Duration::from_secs(
+        storage::SESSION_HOURS as u64 * 60 * 60,
+    ));
+    // 2. Store the session in the store.
+    let cookie_value = match session_store.store_session(session).await {
+        Err(err) => {
+            error!(?err, "Unable to store session in session store");
+            return Err("Unable to store session in session store");
+        }
+        Ok(None) => {
+            error!("Unable to create session cookie");
+            return Err("Unable to create session cookie");
+        }
+        Ok(Some(value)) => value,
+    };
+    // 3. Construct the Session Cookie.
+    let cookie = Cookie::build(storage::AXUM_SESSION_COOKIE_NAME, cookie_value)
+        .same_site(SameSite::Strict)
+        .domain(domain.to_owned())
+        .secure(true)
+        .path("/")
+        .max_age(time::Duration::hours(storage::SESSION_HOURS))
+        .finish();
+    set_cookie(headers, cookie)
+}
+
+/// The refresh token cookie. It is cleared with the same attributes it was
+/// set with since browsers treat a cookie with a different domain or path as
+/// another cookie.
+fn refresh_cookie(token: String, domain: &str, max_age: time::Duration) -> Cookie<'static> {
+    Cookie::build(REFRESH_COOKIE_NAME, token)
+        .same_site(SameSite::Strict)
+        .domain(domain.to_owned())
+        .secure(true)
+        .http_only(true)
+        .path(REFRESH_COOKIE_PATH)
+        .max_age(max_age)
+        .finish()
+}
+
+/// Give a device a refresh token cookie so it can get new sessions.
+fn set_refresh_cookie(
+    headers: &mut HeaderMap,
+    token: String,
+    domain: &str,
+) -> Result<(), &'static str> {
+    let cookie = refresh_cookie(
+        token,
+        domain,
+        time::Duration::days(storage::REFRESH_TOKEN_DAYS),
+    );
+    set_cookie(headers, cookie)
+}
+
+#[instrument(skip_all, fields(user=%auth.0.0, remember=params.remember))]
+pub async fn handler<S: AuthStore>(
     auth: AuthBasic,
     Host(domain): Host,
-    Extension(session_store): Extension<Arc<storage::SqliteStore>>,
-) -> (StatusCode, HeaderMap, axum::Json<api::AccountResponse>) {
+    Query(params): Query<LoginParams>,
+    Extension(session_store): Extension<Arc<S>>,
+) -> AuthResponse {
     // NOTE(jwall): It is very important that you do **not** log the password
     // here. We convert the AuthBasic into UserCreds immediately to help prevent
     // that. Do not circumvent that protection.
@@ -49,84 +153,87 @@ pub async fn handler(
     let mut headers = HeaderMap::new();
     if let Ok(true) = session_store.check_user_creds(&auth).await {
         debug!("successfully authenticated user");
-        // 1. Create a session identifier.
-        let mut session = Session::new();
-        if let Err(err) = session.insert("user_id", auth.user_id()) {
-            error!(?err, "Unable to insert user id into session");
-            let resp = api::AccountResponse::error(
-                StatusCode::INTERNAL_SERVER_ERROR.as_u16(),
-                "Unable to insert user id into session",
-            );
-            return (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                headers,
-                axum::Json::from(resp),
-            );
+        if let Err(msg) =
+            start_session(&*session_store, auth.user_id(), &domain, &mut headers).await
+        {
+            return error_response(StatusCode::INTERNAL_SERVER_ERROR, headers, msg);
         }
-        // 2. Store the session in the store.
-        let cookie_value = match session_store.store_session(session).await {
-            Err(err) => {
-                error!(?err, "Unable to store session in session store");
-                let resp = api::AccountResponse::error(
-                    StatusCode::INTERNAL_SERVER_ERROR.as_u16(),
-                    "Unable to store session in session store",
-                );
-                return (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    headers,
-                    axum::Json::from(resp),
-                );
-            }
-            Ok(None) => {
-                error!("Unable to create session cookie");
-                let resp = api::AccountResponse::error(
-                    StatusCode::INTERNAL_SERVER_ERROR.as_u16(),
-                    "Unable to create session cookie",
-                );
-                return (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    headers,
-                    axum::Json::from(resp),
-                );
-            }
-            Ok(Some(value)) => value,
-        };
-        // 3. Construct the Session Cookie.
-        let cookie = Cookie::build(storage::AXUM_SESSION_COOKIE_NAME, cookie_value)
-            .same_site(SameSite::Strict)
-            .domain(domain)
-            .secure(true)
-            .path("/")
-            .permanent()
-            .finish();
-        let parsed_cookie = match cookie.to_string().parse() {
-            Err(err) => {
-                error!(?err, "Unable to parse session cookie");
-                let resp = api::AccountResponse::error(
-                    StatusCode::INTERNAL_SERVER_ERROR.as_u16(),
-                    "Unable to parse session cookie",
-                );
-                return (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    headers,
-                    axum::Json::from(resp),
-                );
+        if params.remember {
+            let result = match session_store.create_refresh_token(auth.user_id()).await {
+                Ok(token) => set_refresh_cookie(&mut headers, token, &domain),
+                Err(err) => {
+                    error!(?err, "Unable to create refresh token");
+                    Err("Unable to create refresh token")
+                }
+            };
+            if let Err(msg) = result {
+                return error_response(StatusCode::INTERNAL_SERVER_ERROR, headers, msg);
             }
-            Ok(parsed_cookie) => parsed_cookie,
-        };
-        headers.insert(header::SET_COOKIE, parsed_cookie);
+        }
         // Respond with 200 OK
         let resp: api::AccountResponse = auth.into();
         (StatusCode::OK, headers, axum::Json::from(resp))
     } else {
         debug!("Invalid credentials");
-        let headers = HeaderMap::new();
-        let resp = api::AccountResponse::error(
-            StatusCode::UNAUTHORIZED.as_u16(),
+        error_response(
+            StatusCode::UNAUTHORIZED,
+            HeaderMap::new(),
             "Invalid user id or password",
-        );
-        (StatusCode::UNAUTHORIZED, headers, axum::Json::from(resp))
+        )
+    }
+}
+
+/// Trade a remembered device's refresh token for a new session and a new
+/// refresh token.
+#[instrument(skip_all)]
+pub async fn refresh_handler<S: AuthStore>(
+    Host(domain): Host,
+    cookies: Option<TypedHeader<headers::Cookie>>,
+    Extension(session_store): Extension<Arc<S>>,
+) -> AuthResponse {
+    let mut headers = HeaderMap::new();
+    let token = cookies
+        .as_ref()
+        .and_then(|c| c.get(REFRESH_COOKIE_NAME))
+        .map(str::to_owned);
+    let rotated = match token {
+        Some(token) => session_store.rotate_refresh_token(&token).await,
+        None => Ok(None),
+    };
+    let (user_id, token) = match rotated {
+        Ok(Some(rotated)) => rotated,
+        Ok(None) => {
+            debug!("Unknown or expired refresh token");
+            let cookie = refresh_cookie(String::new(), &domain, time::Duration::ZERO);
+            let _ = set_cookie(&mut headers, cookie);
+            return error_response(
+                StatusCode::UNAUTHORIZED,
+                headers,
+                "This device needs to log in again",
+            );
+        }
+        Err(err) => {
+            error!(?err, "Unable to rotate refresh token");
+            return error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                headers,
+                "Unable to rotate refresh token",
+            );
+        }
+    };
+    info!(user_id = user_id.0, "Refreshing session");
+    let result = match start_session(&*session_store, &user_id.0, &domain, &mut headers).await {
+        Ok(_) => set_refresh_cookie(&mut headers, token, &domain),
+        Err(msg) => Err(msg),
+    };
+    if let Err(msg) = result {
+        return error_response(StatusCode::INTERNAL_SERVER_ERROR, headers, msg);
     }
+    let resp = api::AccountResponse::Success(api::UserData {
+        user_id: user_id.0,
+        empty: false,
+    });
+    (StatusCode::OK, headers, axum::Json::from(resp))
 }
 
 impl From<AuthBasic> for storage::UserCreds {
@@ -0,0 +1,221 @@
+// Copyright 2023 Jeremy Wall (Jeremy@marzhilsltudios.com)
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//! Pluggable storage for blobs like uploaded recipe images and backups. Keeping
+//! them in object storage instead of on the local disk lets the server run in
+//! a container with nothing but the database to keep.
+use std::path::PathBuf;
+
+use async_std::fs;
+use async_trait::async_trait;
+use s3::{creds::Credentials, Bucket, Region};
+use tracing::{debug, instrument};
+
+/// Somewhere to keep blobs by key. Keys are `/` separated paths like
+/// `images/<id>.png`.
+#[async_trait]
+pub trait BlobStore: Send + Sync + std::fmt::Debug {
+    async fn put(&self, key: &str, bytes: Vec<u8>) -> Result<(), String>;
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, String>;
+    async fn delete(&self, key: &str) -> Result<(), String>;
+}
+
+/// Whether a key is safe to use as a path. Each part may only have letters,
+/// numbers, `.`, `-` and `_` and can't start with a `.`.
+pub fn valid_key(key: &str) -> bool {
+    !key.is_empty()
+        && key.split('/').all(|part| {
+            !part.is_empty()
+                && !part.starts_with('.')
+                && part
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-' || c == '_')
+        })
+}
+
+fn check_key(key: &str) -> Result<(), String> {
+    if valid_key(key) {
+        Ok(())
+    } else {
+        Err(format!("Invalid blob key {}", key))
+    }
+}
+
+/// The content type for a blob guessed from the extension on its key.
+pub fn content_type(key: &str) -> String {
+    mime_guess::from_path(key)
+        .first_or_octet_stream()
+        .as_ref()
+        .to_owned()
+}
+
+/// Keeps blobs as files under a directory.
+#[derive(Debug, Clone)]
+pub struct FsBlobStore {
+    root: PathBuf,
+}
+
+impl FsBlobStore {
+    pub fn new<P: Into<PathBuf>>(root: P) -> Self {
+        Self { root: root.into() }
+    }
+
+    fn path(&self, key: &str) -> Result<PathBuf, String> {
+        check_key(key)?;
+        Ok(key
+            .split('/')
+            .fold(self.root.clone(), |path, part| path.join(part)))
+    }
+}
+
+#[async_trait]
+impl BlobStore for FsBlobStore {
+    #[instrument(skip(self, bytes), fields(bytes = bytes.len()))]
+    async fn put(&self, key: &str, bytes: Vec<u8>) -> Result<(), String> {
+        let path = self.path(key)?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)
+                .await
+                .map_err(|e| format!("Unable to create {}: {}", dir.to_string_lossy(), e))?;
+        }
+        // Write somewhere else first so readers never see half of a blob.
+        // Every write gets its own temporary file so two writes to the same
+        // key can't interleave. The leading `.` keeps it from being a valid
+        // key.
+        let tmp_path = path.with_file_name(format!(
+            ".{}.{}.tmp",
+            path.file_name().unwrap_or_default().to_string_lossy(),
+            uuid::Uuid::new_v4().simple()
+        ));
+        let result = match fs::write(&tmp_path, bytes).await {
+            Ok(()) => fs::rename(&tmp_path, &path)
+                .await
+                .map_err(|e| format!("Unable to write {}: {}", path.to_string_lossy(), e)),
+            Err(e) => Err(format!(
+                "Unable to write {}: {}",
+                tmp_path.to_string_lossy(),
+                e
+            )),
+        };
+        if result.is_err() {
+            let _ = fs::remove_file(&tmp_path).await;
+        }
+        result
+    }
+
+    #[instrument(skip(self))]
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, String> {
+        let path = self.path(key)?;
+        match fs::read(&path).await {
+            Ok(bytes) => Ok(Some(bytes)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(format!("Unable to read {}: {}", path.to_string_lossy(), e)),
+        }
+    }
+
+    #[instrument(skip(self))]
+    async fn delete(&self, key: &str) -> Result<(), String> {
+        let path = self.path(key)?;
+        match fs::remove_file(&path).await {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(format!(
+                "Unable to delete {}: {}",
+                path.to_string_lossy(),
+                e
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Keeps blobs in an S3 bucket or a bucket in an S3 compatible service like
+/// MinIO. Credentials come from the usual `AWS_ACCESS_KEY_ID` and
+/// `AWS_SECRET_ACCESS_KEY` environment variables or the AWS profile.
+#[derive(Debug, Clone)]
+pub struct S3BlobStore {
+    bucket: Bucket,
+}
+
+impl S3BlobStore {
+    /// Use `bucket` in `region`. An `endpoint` points at an S3 compatible
+    /// service instead of AWS and uses path style urls.
+    pub fn new(bucket: &str, region: &str, endpoint: Option<&str>) -> Result<Self, String> {
+        let credentials =
+            Credentials::default().map_err(|e| format!("Unable to load S3 credentials: {}", e))?;
+        let region = match endpoint {
+            Some(endpoint) => Region::Custom {
+                region: region.to_owned(),
+                endpoint: endpoint.to_owned(),
+            },
+            None => region
+                .parse()
+                .map_err(|e| format!("Invalid S3 region {}: {}", region, e))?,
+        };
+        let bucket = Bucket::new(bucket, region, credentials)
+            .map_err(|e| format!("Invalid S3 bucket {}: {}", bucket, e))?;
+        let bucket = if endpoint.is_some() {
+            bucket.with_path_style()
+        } else {
+            bucket
+        };
+        Ok(Self { bucket })
+    }
+}
+
+#[async_trait]
+impl BlobStore for S3BlobStore {
+    #[instrument(skip(self, bytes), fields(bucket = %self.bucket.name, bytes = bytes.len()))]
+    async fn put(&self, key: &str, bytes: Vec<u8>) -> Result<(), String> {
+        check_key(key)?;
+        let resp = self
+            .bucket
+            .put_object_with_content_type(key, &bytes, &content_type(key))
+            .await
+            .map_err(|e| format!("Unable to put {}: {}", key, e))?;
+        match resp.status_code() {
+            200..=299 => Ok(()),
+            status => Err(format!("Unable to put {}: status {}", key, status)),
+        }
+    }
+
+    #[instrument(skip(self), fields(bucket = %self.bucket.name))]
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, String> {
+        check_key(key)?;
+        let resp = self
+            .bucket
+            .get_object(key)
+            .await
+            .map_err(|e| format!("Unable to get {}: {}", key, e))?;
+        match resp.status_code() {
+            200..=299 => Ok(Some(resp.bytes().to_vec())),
+            404 => {
+                debug!("No such blob");
+                Ok(None)
+            }
+            status => Err(format!("Unable to get {}: status {}", key, status)),
+        }
+    }
+
+    #[instrument(skip(self), fields(bucket = %self.bucket.name))]
+    async fn delete(&self, key: &str) -> Result<(), String> {
+        check_key(key)?;
+        let resp = self
+            .bucket
+            .delete_object(key)
+            .await
+            .map_err(|e| format!("Unable to delete {}: {}", key, e))?;
+        match resp.status_code() {
+            200..=299 | 404 => Ok(()),
+            status => Err(format!("Unable to delete {}: status {}", key, status)),
+        }
+    }
+}
@@ -0,0 +1,120 @@
+// Copyright 2023 Jeremy Wall (Jeremy@marzhilsltudios.com)
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//! Curated category mappings, staples and ingredient substitutions that ship
+//! with the server. Users can apply the categories and staples to their
+//! account so their shopping list starts out sorted by aisle. The
+//! substitutions are offered to everyone unless they override them.
+use std::collections::BTreeMap;
+
+use client_api as api;
+use recipes::parse;
+use rust_embed::RustEmbed;
+use tracing::error;
+
+use super::storage::{self, APIStore};
+
+#[derive(RustEmbed)]
+#[folder = "defaults/"]
+struct Defaults;
+
+fn default_file(name: &str) -> String {
+    Defaults::get(name)
+        .and_then(|file| String::from_utf8(file.data.into_owned()).ok())
+        .unwrap_or_else(|| {
+            error!(name, "Missing default file");
+            String::new()
+        })
+}
+
+/// The default ingredient to category mappings.
+pub fn default_categories() -> BTreeMap<String, String> {
+    parse::as_categories(&default_file("categories.txt")).unwrap_or_else(|err| {
+        error!(%err, "The default categories do not parse");
+        BTreeMap::new()
+    })
+}
+
+/// The default staples in the same ingredient list format users edit them in.
+pub fn default_staples() -> String {
+    default_file("staples.txt")
+}
+
+/// The default substitutes for ingredients. Each line of the file names an
+/// ingredient followed by a colon and its `|` separated substitutes.
+pub fn default_substitutions() -> BTreeMap<String, Vec<String>> {
+    default_file("substitutions.txt")
+        .lines()
+        .filter_map(|line| {
+            let (ingredient, substitutes) = line.split_once(':')?;
+            let substitutes = api::parse_substitutes(substitutes);
+            let ingredient = ingredient.trim().to_lowercase();
+            (!ingredient.is_empty() && !substitutes.is_empty()).then(|| (ingredient, substitutes))
+        })
+        .collect()
+}
+
+/// The default substitutions with the ones in the user's settings applied.
+/// Ingredients the user cleared the substitutes for are left out.
+pub async fn substitutions_for_user(
+    store: &dyn APIStore,
+    user_id: &str,
+) -> storage::Result<BTreeMap<String, Vec<String>>> {
+    let overrides = api::substitute_overrides(&store.fetch_user_settings(user_id).await?);
+    let mut substitutions = default_substitutions();
+    for (ingredient, substitutes) in overrides {
+        if substitutes.is_empty() {
+            substitutions.remove(&ingredient);
+        } else {
+            substitutions.insert(ingredient, substitutes);
+        }
+    }
+    Ok(substitutions)
+}
+
+/// Map the ingredients the user hasn't mapped yet using the default
+/// categories and save the default staples if the user has none. Nothing the
+/// user already has is changed.
+pub async fn apply_defaults(
+    store: &dyn APIStore,
+    user_id: &str,
+) -> storage::Result<api::DefaultsApplied> {
+    let existing: BTreeMap<String, String> = store
+        .get_category_mappings_for_user(user_id)
+        .await?
+        .unwrap_or_default()
+        .into_iter()
+        .collect();
+    let mappings: Vec<(String, String)> = default_categories()
+        .into_iter()
+        .filter(|(ingredient, _)| !existing.contains_key(ingredient))
+        .collect();
+    if !mappings.is_empty() {
+        store
+            .save_category_mappings_for_user(user_id, &mappings)
+            .await?;
+    }
+    let has_staples = store
+        .fetch_staples(user_id)
+        .await?
+        .map_or(false, |staples| !staples.trim().is_empty());
+    if !has_staples {
+        store
+            .save_staples(user_id, default_staples().as_str())
+            .await?;
+    }
+    Ok(api::DefaultsApplied {
+        categories: mappings.len(),
+        staples: !has_staples,
+    })
+}
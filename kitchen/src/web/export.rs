@@ -0,0 +1,255 @@
+// Copyright 2023 Jeremy Wall (Jeremy@marzhilsltudios.com)
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//! Export bodies that are written out a piece at a time. Recipes and plans
+//! are the parts of an account that grow without bound so only their ids are
+//! gathered up front and each one is fetched when it is written.
+use axum::body::Bytes;
+use chrono::NaiveDate;
+use client_api as api;
+use futures::stream::{self, Stream};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use tracing::error;
+
+use super::storage::{self, APIStore};
+
+pub type Chunk = Result<Bytes, std::io::Error>;
+
+fn json<T: Serialize + ?Sized>(value: &T) -> storage::Result<String> {
+    serde_json::to_string(value).map_err(|e| storage::Error::InternalError(format!("{}", e)))
+}
+
+fn separator(first: bool) -> &'static str {
+    if first {
+        ""
+    } else {
+        ","
+    }
+}
+
+enum AccountState {
+    Start,
+    Recipes {
+        ids: std::collections::btree_set::IntoIter<String>,
+        first: bool,
+    },
+    Plans {
+        dates: std::vec::IntoIter<NaiveDate>,
+        first: bool,
+    },
+    Done,
+}
+
+/// Stream a user's account as JSON with the same layout as
+/// `api::AccountExport`. Each recipe and each plan is its own chunk. A
+/// storage error part way through ends the stream with an error so the
+/// client sees a truncated body rather than a valid looking export.
+pub fn account_export(
+    app_store: Arc<dyn APIStore>,
+    user_id: String,
+    exported_at: chrono::DateTime<chrono::Utc>,
+) -> impl Stream<Item = Chunk> + Send {
+    stream::unfold(AccountState::Start, move |state| {
+        let app_store = app_store.clone();
+        let user_id = user_id.clone();
+        async move {
+            match next_account_chunk(&app_store, &user_id, exported_at, state).await {
+                Ok(Some((chunk, next))) => Some((Ok(Bytes::from(chunk)), next)),
+                Ok(None) => None,
+                Err(err) => {
+                    error!(user_id = %user_id, ?err, "Failed to stream the account export");
+                    let err = std::io::Error::new(std::io::ErrorKind::Other, format!("{:?}", err));
+                    Some((Err(err), AccountState::Done))
+                }
+            }
+        }
+    })
+}
+
+async fn next_account_chunk(
+    app_store: &dyn APIStore,
+    user_id: &str,
+    exported_at: chrono::DateTime<chrono::Utc>,
+    state: AccountState,
+) -> storage::Result<Option<(String, AccountState)>> {
+    Ok(Some(match state {
+        AccountState::Start => {
+            let ids = app_store.fetch_recipe_ids_for_user(user_id).await?;
+            let chunk = format!(
+                r#"{{"user_id":{},"exported_at":{},"recipes":["#,
+                json(user_id)?,
+                json(&exported_at)?
+            );
+            let next = AccountState::Recipes {
+                ids: ids.into_iter(),
+                first: true,
+            };
+            (chunk, next)
+        }
+        AccountState::Recipes { mut ids, first } => match ids.next() {
+            Some(id) => match app_store.get_recipe_entry_for_user(user_id, &id).await? {
+                Some(entry) => {
+                    let chunk = format!("{}{}", separator(first), json(&entry)?);
+                    (chunk, AccountState::Recipes { ids, first: false })
+                }
+                // The recipe was deleted after the export started.
+                None => (String::new(), AccountState::Recipes { ids, first }),
+            },
+            None => {
+                let categories = app_store.get_categories_for_user(user_id).await?;
+                let category_mappings = app_store
+                    .get_category_mappings_for_user(user_id)
+                    .await?
+                    .unwrap_or_default();
+                let staples = app_store.fetch_staples(user_id).await?;
+                let settings = app_store.fetch_user_settings(user_id).await?;
+                let mut dates = app_store
+                    .fetch_all_meal_plans(user_id)
+                    .await?
+                    .unwrap_or_default();
+                dates.sort();
+                let chunk = format!(
+                    r#"],"categories":{},"category_mappings":{},"staples":{},"settings":{},"plans":{{"#,
+                    json(&categories)?,
+                    json(&category_mappings)?,
+                    json(&staples)?,
+                    json(&settings)?
+                );
+                let next = AccountState::Plans {
+                    dates: dates.into_iter(),
+                    first: true,
+                };
+                (chunk, next)
+            }
+        },
+        AccountState::Plans { mut dates, first } => match dates.next() {
+            Some(date) => {
+                let recipe_counts = app_store
+                    .fetch_meal_plan_for_date(user_id, date)
+                    .await?
+                    .unwrap_or_default();
+                let inventory = app_store.fetch_inventory_for_date(user_id, date).await?;
+                let plan = api::PlanExport {
+                    recipe_counts,
+                    inventory: inventory.into(),
+                };
+                let chunk = format!("{}{}:{}", separator(first), json(&date)?, json(&plan)?);
+                (
+                    chunk,
+                    AccountState::Plans {
+                        dates,
+                        first: false,
+                    },
+                )
+            }
+            None => ("}}".to_owned(), AccountState::Done),
+        },
+        AccountState::Done => return Ok(None),
+    }))
+}
+
+enum HistoryState {
+    Start,
+    Dates {
+        dates: std::vec::IntoIter<NaiveDate>,
+        titles: BTreeMap<String, String>,
+    },
+    Done,
+}
+
+/// Stream every plan a user has made as CSV rows of date, recipe id, recipe
+/// title and count with one plan date per chunk.
+pub fn plan_history_csv(
+    app_store: Arc<dyn APIStore>,
+    user_id: String,
+) -> impl Stream<Item = Chunk> + Send {
+    stream::unfold(HistoryState::Start, move |state| {
+        let app_store = app_store.clone();
+        let user_id = user_id.clone();
+        async move {
+            match next_history_chunk(&app_store, &user_id, state).await {
+                Ok(Some((chunk, next))) => Some((Ok(Bytes::from(chunk)), next)),
+                Ok(None) => None,
+                Err(err) => {
+                    error!(user_id = %user_id, ?err, "Failed to stream the plan history");
+                    let err = std::io::Error::new(std::io::ErrorKind::Other, format!("{:?}", err));
+                    Some((Err(err), HistoryState::Done))
+                }
+            }
+        }
+    })
+}
+
+fn csv_rows<I, R>(rows: I) -> storage::Result<Vec<u8>>
+where
+    I: IntoIterator<Item = R>,
+    R: IntoIterator,
+    R::Item: AsRef<[u8]>,
+{
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    for row in rows {
+        writer
+            .write_record(row)
+            .map_err(|e| storage::Error::InternalError(format!("{}", e)))?;
+    }
+    writer
+        .into_inner()
+        .map_err(|e| storage::Error::InternalError(format!("{}", e)))
+}
+
+async fn next_history_chunk(
+    app_store: &dyn APIStore,
+    user_id: &str,
+    state: HistoryState,
+) -> storage::Result<Option<(Vec<u8>, HistoryState)>> {
+    Ok(Some(match state {
+        HistoryState::Start => {
+            // Only the titles are kept so the parsed recipes can be
+            // dropped before any plans are read.
+            let titles = super::user_recipes(app_store, user_id)
+                .await?
+                .into_iter()
+                .map(|(id, recipe)| (id, recipe.title))
+                .collect();
+            let mut dates = app_store
+                .fetch_all_meal_plans(user_id)
+                .await?
+                .unwrap_or_default();
+            dates.sort();
+            let chunk = csv_rows([["date", "recipe_id", "title", "count"]])?;
+            let next = HistoryState::Dates {
+                dates: dates.into_iter(),
+                titles,
+            };
+            (chunk, next)
+        }
+        HistoryState::Dates { mut dates, titles } => match dates.next() {
+            Some(date) => {
+                let recipe_counts = app_store
+                    .fetch_meal_plan_for_date(user_id, date)
+                    .await?
+                    .unwrap_or_default();
+                let date = date.to_string();
+                let chunk = csv_rows(recipe_counts.into_iter().map(|(recipe_id, count)| {
+                    let title = titles.get(&recipe_id).cloned().unwrap_or_default();
+                    [date.clone(), recipe_id, title, count.to_string()]
+                }))?;
+                (chunk, HistoryState::Dates { dates, titles })
+            }
+            None => return Ok(None),
+        },
+        HistoryState::Done => return Ok(None),
+    }))
+}
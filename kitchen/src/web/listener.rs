@@ -0,0 +1,71 @@
+// Copyright 2023 Jeremy Wall (Jeremy@marzhilsltudios.com)
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//! Support for listening sockets handed to us by systemd socket activation.
+//! systemd keeps the socket open while the service restarts so connections
+//! wait for the new process instead of being refused.
+use std::net::TcpListener;
+
+#[cfg(unix)]
+use tracing::{debug, error, info, warn};
+
+/// The first file descriptor systemd passes. 0 through 2 are stdio.
+#[cfg(unix)]
+const SD_LISTEN_FDS_START: i32 = 3;
+
+/// The listener systemd passed in `LISTEN_FDS` if there is one for us.
+/// This changes the environment so it has to be called before any other
+/// threads are started.
+#[cfg(unix)]
+pub fn from_systemd() -> Option<TcpListener> {
+    use std::os::unix::io::FromRawFd;
+
+    let pid = std::env::var("LISTEN_PID").ok()?;
+    // The variables are inherited by child processes so check they were
+    // meant for this process.
+    if pid.parse::<u32>().ok()? != std::process::id() {
+        debug!(pid, "Ignoring LISTEN_FDS meant for another process");
+        return None;
+    }
+    let fds = std::env::var("LISTEN_FDS").ok()?.parse::<i32>().ok()?;
+    std::env::remove_var("LISTEN_PID");
+    std::env::remove_var("LISTEN_FDS");
+    std::env::remove_var("LISTEN_FDNAMES");
+    if fds < 1 {
+        return None;
+    }
+    if fds > 1 {
+        warn!(fds, "Only the first socket passed by systemd will be used");
+    }
+    // SAFETY: systemd guarantees the fd is open and owned by this process
+    // and we only ever take it once since the variables are removed above.
+    let listener = unsafe { TcpListener::from_raw_fd(SD_LISTEN_FDS_START) };
+    let addr = match listener.local_addr() {
+        Ok(addr) => addr,
+        Err(err) => {
+            error!(?err, "The socket passed by systemd is not a tcp socket");
+            return None;
+        }
+    };
+    if let Err(err) = listener.set_nonblocking(true) {
+        error!(?err, "Unable to make the socket from systemd non blocking");
+        return None;
+    }
+    info!(%addr, "Using the socket passed by systemd");
+    Some(listener)
+}
+
+#[cfg(not(unix))]
+pub fn from_systemd() -> Option<TcpListener> {
+    None
+}
@@ -14,29 +14,53 @@ use std::collections::BTreeMap;
 // limitations under the License.
 use std::path::PathBuf;
 use std::sync::Arc;
-use std::{collections::BTreeSet, net::SocketAddr};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+};
 
 use axum::{
-    body::{boxed, Full},
-    extract::{Extension, Json, Path},
-    http::{header, StatusCode},
+    body::{boxed, Bytes, Full, StreamBody},
+    extract::{ConnectInfo, Extension, FromRequest, Json, Path, Query, RequestParts},
+    http::{header, HeaderMap, Method, Request, StatusCode},
+    middleware::{self, Next},
     response::{IntoResponse, Redirect, Response},
-    routing::{get, Router},
+    routing::{delete, get, patch, post, Router},
 };
 use chrono::NaiveDate;
 use client_api as api;
 use metrics_process::Collector;
 use mime_guess;
-use recipes::{IngredientKey, RecipeEntry};
+use recipes::{category, cooklang, parse, plain, serialize, IngredientKey, RecipeEntry};
 use rust_embed::RustEmbed;
+use serde::Deserialize;
 use storage::{APIStore, AuthStore};
 use tower::ServiceBuilder;
 use tower_http::trace::TraceLayer;
-use tracing::{debug, info, instrument};
+use tracing::{debug, error, info, instrument};
 
+mod api_errors;
 mod auth;
+pub mod blob;
+mod defaults;
+mod export;
+pub mod listener;
 mod metrics;
+pub mod ocr;
+mod prerender;
+mod qr;
+mod rate_limit;
+mod request_id;
+mod samples;
+pub mod scheduler;
+mod security;
+pub mod seed;
 mod storage;
+#[cfg(test)]
+mod test;
+
+pub use security::SecurityHeaders;
+pub use storage::{AnonymousAccess, DbConfig, Limits};
 
 #[derive(RustEmbed)]
 #[folder = "../web/dist"]
@@ -68,254 +92,2416 @@ where
     }
 }
 
+async fn prerender_recipe(
+    store: Arc<storage::file_store::AsyncFileStore>,
+    app_store: Arc<dyn storage::APIStore>,
+    session: storage::UserIdFromSession,
+    recipe_id: &str,
+) -> Option<Response> {
+    let entry = recipe_entry_for_session(&store, app_store.as_ref(), &session, recipe_id)
+        .await
+        .ok()
+        .flatten()?;
+    let recipe = match parse::as_recipe(entry.recipe_text()) {
+        Ok(recipe) => recipe,
+        Err(err) => {
+            debug!(?err, recipe_id, "Unable to parse recipe for prerendering");
+            return None;
+        }
+    };
+    let index = UiAssets::get("index.html")?;
+    let index = String::from_utf8_lossy(&index.data);
+    let page = prerender::render_recipe_page(&index, &recipe);
+    Some(
+        Response::builder()
+            .header(header::CONTENT_TYPE, "text/html; charset=utf-8")
+            .body(boxed(Full::from(page)))
+            .unwrap(),
+    )
+}
+
+/// Look up a recipe the way the session sees it. People who aren't logged in
+/// and the editor get the recipe directory. Everyone else gets their own
+/// recipes, following the id if it was renamed.
+async fn recipe_entry_for_session(
+    store: &storage::file_store::AsyncFileStore,
+    app_store: &dyn storage::APIStore,
+    session: &storage::UserIdFromSession,
+    recipe_id: &str,
+) -> storage::Result<Option<RecipeEntry>> {
+    use storage::{UserId, UserIdFromSession::*};
+    let id = match session {
+        FoundUserId(UserId(id)) if !store.is_editor(id) => id,
+        _ => {
+            return store
+                .get_recipe_entry(recipe_id)
+                .await
+                .map_err(|e| storage::Error::IO(format!("{:?}", e)))
+        }
+    };
+    match app_store.get_recipe_entry_for_user(id, recipe_id).await? {
+        Some(entry) => Ok(Some(entry)),
+        None => match app_store.resolve_recipe_alias(id, recipe_id).await? {
+            Some(new_id) => {
+                debug!(%recipe_id, %new_id, "Resolved renamed recipe");
+                app_store.get_recipe_entry_for_user(id, &new_id).await
+            }
+            None => Ok(None),
+        },
+    }
+}
+
+/// Find the current id for a recipe id that was renamed when it was normalized.
+async fn recipe_alias(
+    app_store: &dyn storage::APIStore,
+    session: &storage::UserIdFromSession,
+    recipe_id: &str,
+) -> Option<String> {
+    use storage::{UserId, UserIdFromSession::*};
+    match session {
+        NoUserId => None,
+        FoundUserId(UserId(id)) => app_store
+            .resolve_recipe_alias(id.as_str(), recipe_id)
+            .await
+            .ok()
+            .flatten(),
+    }
+}
+
 #[instrument]
-async fn ui_static_assets(Path(path): Path<String>) -> impl IntoResponse {
+async fn ui_static_assets(
+    Extension(store): Extension<Arc<storage::file_store::AsyncFileStore>>,
+    Extension(app_store): Extension<Arc<dyn storage::APIStore>>,
+    Extension(anonymous): Extension<Arc<AnonymousAccess>>,
+    session: storage::UserIdFromSession,
+    Path(path): Path<String>,
+) -> Response {
     info!("Serving ui path");
 
     let mut path = path.trim_start_matches("/");
+    for prefix in ["recipe/view/", "recipe/edit/"] {
+        if let Some(recipe_id) = path.strip_prefix(prefix) {
+            if let Some(new_id) = recipe_alias(&app_store, &session, recipe_id).await {
+                debug!(recipe_id, %new_id, "Redirecting renamed recipe");
+                return Redirect::permanent(&format!("/ui/{}{}", prefix, new_id)).into_response();
+            }
+        }
+    }
+    // Prerendering for people who aren't logged in shows recipes from the
+    // recipe directory which they may not be allowed to see.
+    let hidden = matches!(
+        (&session, anonymous.as_ref()),
+        (storage::UserIdFromSession::NoUserId, AnonymousAccess::Off)
+    );
+    if let Some(recipe_id) = path.strip_prefix("recipe/view/").filter(|_| !hidden) {
+        debug!(recipe_id, "Prerendering recipe page");
+        if let Some(page) = prerender_recipe(store, app_store, session, recipe_id).await {
+            return page;
+        }
+    }
     if UiAssets::get(path).is_none() {
         path = "index.html";
     }
     debug!(path = path, "Serving transformed path");
-    StaticFile(path.to_owned())
+    StaticFile(path.to_owned()).into_response()
 }
 
 #[instrument]
 async fn api_recipe_entry(
     Extension(store): Extension<Arc<storage::file_store::AsyncFileStore>>,
-    Extension(app_store): Extension<Arc<storage::SqliteStore>>,
+    Extension(app_store): Extension<Arc<dyn storage::APIStore>>,
     session: storage::UserIdFromSession,
     Path(recipe_id): Path<String>,
 ) -> api::Response<Option<RecipeEntry>> {
-    use storage::{UserId, UserIdFromSession::*};
-    match session {
-        NoUserId => store.get_recipe_entry(recipe_id).await.into(),
-        FoundUserId(UserId(id)) => app_store
-            .get_recipe_entry_for_user(id, recipe_id)
-            .await
-            .into(),
-    }
+    storage_response(
+        recipe_entry_for_session(&store, app_store.as_ref(), &session, &recipe_id).await,
+    )
 }
 
-async fn api_recipe_delete(
-    Extension(app_store): Extension<Arc<storage::SqliteStore>>,
+#[instrument]
+async fn api_recipe_step(
+    Extension(store): Extension<Arc<storage::file_store::AsyncFileStore>>,
+    Extension(app_store): Extension<Arc<dyn storage::APIStore>>,
     session: storage::UserIdFromSession,
-    Path(recipe_id): Path<String>,
-) -> api::EmptyResponse {
-    use storage::{UserId, UserIdFromSession::*};
-    match session {
-        NoUserId => api::EmptyResponse::Unauthorized,
-        FoundUserId(UserId(id)) => app_store
-            .delete_recipes_for_user(&id, &vec![recipe_id])
-            .await
-            .into(),
+    Path((recipe_id, step)): Path<(String, usize)>,
+) -> api::RecipeStepResponse {
+    let entry = recipe_entry_for_session(&store, app_store.as_ref(), &session, &recipe_id).await;
+    let entry = match entry {
+        Ok(Some(entry)) => entry,
+        Ok(None) => return api::Response::NotFound,
+        Err(e) => {
+            return api::Response::error(
+                StatusCode::INTERNAL_SERVER_ERROR.as_u16(),
+                format!("{:?}", e),
+            )
+        }
+    };
+    let recipe = match parse::as_recipe(entry.recipe_text()) {
+        Ok(recipe) => recipe,
+        Err(e) => {
+            return api::Response::error_code(
+                api::ErrorCode::Validation {
+                    field: "recipe_text".to_owned(),
+                },
+                e,
+            );
+        }
+    };
+    match api::RecipeStepData::new(recipe_id, &recipe, step) {
+        Some(data) => data.into(),
+        None => api::Response::NotFound,
     }
 }
 
+#[derive(Debug, Deserialize)]
+struct PrepListParams {
+    /// Scale the recipe to feed this many people.
+    people: Option<u32>,
+}
+
+/// The mise en place for a recipe.
 #[instrument]
-async fn api_recipes(
+async fn api_recipe_prep(
     Extension(store): Extension<Arc<storage::file_store::AsyncFileStore>>,
-    Extension(app_store): Extension<Arc<storage::SqliteStore>>,
+    Extension(app_store): Extension<Arc<dyn storage::APIStore>>,
     session: storage::UserIdFromSession,
-) -> api::RecipeEntryResponse {
-    // Select recipes based on the user-id if it exists or serve the default if it does not.
-    use storage::{UserId, UserIdFromSession::*};
-    match session {
-        NoUserId => api::RecipeEntryResponse::from(store.get_recipes().await),
-        FoundUserId(UserId(id)) => app_store.get_recipes_for_user(id.as_str()).await.into(),
+    Path(recipe_id): Path<String>,
+    Query(params): Query<PrepListParams>,
+) -> api::PrepListResponse {
+    let entry = recipe_entry_for_session(&store, app_store.as_ref(), &session, &recipe_id).await;
+    let entry = match entry {
+        Ok(Some(entry)) => entry,
+        Ok(None) => return api::Response::NotFound,
+        Err(e) => {
+            return api::Response::error(
+                StatusCode::INTERNAL_SERVER_ERROR.as_u16(),
+                format!("{:?}", e),
+            )
+        }
+    };
+    match parse::as_recipe(entry.recipe_text()) {
+        Ok(recipe) => {
+            let recipe = match params.people {
+                Some(people) => recipe.scale(recipe.serving_scale(people)),
+                None => recipe,
+            };
+            api::PrepList::new(recipe_id, &recipe).into()
+        }
+        Err(e) => api::Response::error_code(
+            api::ErrorCode::Validation {
+                field: "recipe_text".to_owned(),
+            },
+            e,
+        ),
     }
 }
 
 #[instrument]
-async fn api_category_mappings(
-    Extension(app_store): Extension<Arc<storage::SqliteStore>>,
+async fn api_recipe_parsed(
+    Extension(store): Extension<Arc<storage::file_store::AsyncFileStore>>,
+    Extension(app_store): Extension<Arc<dyn storage::APIStore>>,
     session: storage::UserIdFromSession,
-) -> api::CategoryMappingResponse {
-    use storage::UserIdFromSession::*;
-    match session {
-        NoUserId => api::Response::Unauthorized,
-        FoundUserId(user_id) => app_store
-            .get_category_mappings_for_user(&user_id.0)
-            .await
-            .into(),
+    Path(recipe_id): Path<String>,
+) -> api::ParsedRecipeResponse {
+    let entry = recipe_entry_for_session(&store, app_store.as_ref(), &session, &recipe_id).await;
+    let entry = match entry {
+        Ok(Some(entry)) => entry,
+        Ok(None) => return api::Response::NotFound,
+        Err(e) => {
+            return api::Response::error(
+                StatusCode::INTERNAL_SERVER_ERROR.as_u16(),
+                format!("{:?}", e),
+            )
+        }
+    };
+    match parse::as_recipe(entry.recipe_text()) {
+        Ok(recipe) => api::ParsedRecipe::new(&entry, &recipe).into(),
+        Err(e) => api::Response::error_code(
+            api::ErrorCode::Validation {
+                field: "recipe_text".to_owned(),
+            },
+            e,
+        ),
     }
 }
 
+/// Export a recipe as Cooklang.
 #[instrument]
-async fn api_save_category_mappings(
-    Extension(app_store): Extension<Arc<storage::SqliteStore>>,
+async fn api_recipe_cooklang(
+    Extension(store): Extension<Arc<storage::file_store::AsyncFileStore>>,
+    Extension(app_store): Extension<Arc<dyn storage::APIStore>>,
     session: storage::UserIdFromSession,
-    Json(mappings): Json<Vec<(String, String)>>,
-) -> api::EmptyResponse {
-    use storage::UserIdFromSession::*;
-    match session {
-        NoUserId => api::Response::Unauthorized,
-        FoundUserId(user_id) => match app_store
-            .save_category_mappings_for_user(&user_id.0, &mappings)
-            .await
-        {
-            Ok(_) => api::EmptyResponse::success(()),
-            Err(e) => api::EmptyResponse::error(
+    Path(recipe_id): Path<String>,
+) -> Response {
+    let entry = recipe_entry_for_session(&store, app_store.as_ref(), &session, &recipe_id).await;
+    let entry = match entry {
+        Ok(Some(entry)) => entry,
+        Ok(None) => return api::EmptyResponse::NotFound.into_response(),
+        Err(e) => {
+            return api::EmptyResponse::error(
                 StatusCode::INTERNAL_SERVER_ERROR.as_u16(),
                 format!("{:?}", e),
-            ),
-        },
+            )
+            .into_response()
+        }
+    };
+    match parse::as_recipe(entry.recipe_text()) {
+        Ok(recipe) => Response::builder()
+            .header(header::CONTENT_TYPE, "text/plain; charset=utf-8")
+            .header(
+                header::CONTENT_DISPOSITION,
+                format!(r#"attachment; filename="{}.cook""#, entry.recipe_id()),
+            )
+            .body(boxed(Full::from(cooklang::as_text(&recipe))))
+            .unwrap(),
+        Err(e) => api::EmptyResponse::error_code(
+            api::ErrorCode::Validation {
+                field: "recipe_text".to_owned(),
+            },
+            e,
+        )
+        .into_response(),
     }
 }
 
-#[instrument]
-async fn api_categories(
+/// Import recipes from Cooklang. They are converted to our recipe format and
+/// saved like any other recipe.
+async fn api_import_cooklang(
     Extension(store): Extension<Arc<storage::file_store::AsyncFileStore>>,
-    Extension(app_store): Extension<Arc<storage::SqliteStore>>,
+    Extension(app_store): Extension<Arc<dyn storage::APIStore>>,
     session: storage::UserIdFromSession,
-) -> api::Response<String> {
-    // Select Categories based on the user-id if it exists or serve the default if it does not.
+    Json(imports): Json<Vec<api::CooklangImport>>,
+) -> api::RecipeSaveResponse {
+    let mut entries = Vec::with_capacity(imports.len());
+    for import in imports {
+        let recipe = match cooklang::as_recipe(&import.text, &import.title) {
+            Ok(recipe) => recipe,
+            Err(e) => {
+                debug!(title = %import.title, err = %e, "Rejecting invalid cooklang recipe");
+                return api::Response::error_code(
+                    api::ErrorCode::Validation {
+                        field: "text".to_owned(),
+                    },
+                    format!("{}: {}", import.title, e),
+                );
+            }
+        };
+        let mut entry =
+            RecipeEntry::new(recipes::slugify(&recipe.title), serialize::as_text(&recipe));
+        if let Some(category) = import.category {
+            entry.set_category(category);
+        }
+        entries.push(entry);
+    }
+    api_save_recipes(
+        Extension(store),
+        Extension(app_store),
+        session,
+        Json(entries),
+    )
+    .await
+}
+
+/// Read a photo of a recipe card with the configured OCR backend and return
+/// draft recipe text for the editor. Nothing is saved.
+async fn api_ocr_recipe(
+    Extension(ocr): Extension<Option<Arc<dyn ocr::OcrBackend>>>,
+    session: storage::UserIdFromSession,
+    image: Bytes,
+) -> api::OcrDraftResponse {
+    use storage::UserIdFromSession::FoundUserId;
+    if !matches!(session, FoundUserId(_)) {
+        return api::Response::Unauthorized;
+    }
+    let ocr = match ocr {
+        Some(ocr) => ocr,
+        None => {
+            return api::Response::error(
+                StatusCode::NOT_IMPLEMENTED.as_u16(),
+                "OCR is not configured on this server",
+            )
+        }
+    };
+    if image.is_empty() {
+        return api::Response::error_code(
+            api::ErrorCode::Validation {
+                field: "image".to_owned(),
+            },
+            "No image was uploaded",
+        );
+    }
+    match ocr.recognize(image.to_vec()).await {
+        Ok(text) => {
+            let recipe = plain::as_recipe(&text, "Untitled recipe");
+            api::Response::success(api::OcrDraft {
+                title: recipe.title.clone(),
+                text: serialize::as_text(&recipe),
+            })
+        }
+        Err(e) => api::Response::error(StatusCode::BAD_GATEWAY.as_u16(), e),
+    }
+}
+
+/// Keep an uploaded image in the blob store and return the url to use for it
+/// in a recipe.
+/// The image types that can be uploaded and the extension they are saved
+/// with. Images are served from our own origin so types that can carry
+/// scripts like svg aren't allowed.
+const IMAGE_TYPES: [(&str, &str); 4] = [
+    ("image/png", "png"),
+    ("image/jpeg", "jpg"),
+    ("image/webp", "webp"),
+    ("image/gif", "gif"),
+];
+
+async fn api_upload_image(
+    Extension(app_store): Extension<Arc<dyn storage::APIStore>>,
+    Extension(blobs): Extension<Arc<dyn blob::BlobStore>>,
+    session: storage::UserIdFromSession,
+    headers: HeaderMap,
+    image: Bytes,
+) -> api::UploadedImageResponse {
+    use storage::UserIdFromSession::FoundUserId;
+    if !matches!(session, FoundUserId(_)) {
+        return api::Response::Unauthorized;
+    }
+    if image.is_empty() {
+        return api::Response::error_code(
+            api::ErrorCode::Validation {
+                field: "image".to_owned(),
+            },
+            "No image was uploaded",
+        );
+    }
+    if let Err(storage::Error::LimitExceeded(message)) = app_store.limits().check_image(image.len())
+    {
+        return api::Response::error_code(api::ErrorCode::PayloadTooLarge, message);
+    }
+    let extension = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|content_type| content_type.split(';').next())
+        .and_then(|content_type| {
+            IMAGE_TYPES
+                .iter()
+                .find(|(mime, _)| mime.eq_ignore_ascii_case(content_type.trim()))
+        })
+        .map(|(_, extension)| extension);
+    let extension = match extension {
+        Some(extension) => extension,
+        None => {
+            return api::Response::error_code(
+                api::ErrorCode::Validation {
+                    field: "content-type".to_owned(),
+                },
+                "Uploads must be png, jpeg, webp or gif images",
+            )
+        }
+    };
+    // Images are served to anyone with the url so that shared plans and
+    // anonymous readers see them. The random name keeps them from being
+    // guessed.
+    let name = format!("{}.{}", uuid::Uuid::new_v4().simple(), extension);
+    match blobs.put(&format!("images/{}", name), image.to_vec()).await {
+        Ok(()) => api::Response::success(api::UploadedImage {
+            url: format!("/api/v2/images/{}", name),
+        }),
+        Err(e) => api::Response::error(StatusCode::INTERNAL_SERVER_ERROR.as_u16(), e),
+    }
+}
+
+async fn api_image(
+    Extension(blobs): Extension<Arc<dyn blob::BlobStore>>,
+    Path(name): Path<String>,
+) -> Response {
+    let allowed = IMAGE_TYPES
+        .iter()
+        .any(|(_, extension)| name.ends_with(&format!(".{}", extension)));
+    if name.contains('/') || !blob::valid_key(&name) || !allowed {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+    let key = format!("images/{}", name);
+    match blobs.get(&key).await {
+        Ok(Some(bytes)) => (
+            [
+                (header::CONTENT_TYPE, blob::content_type(&key)),
+                (
+                    header::CACHE_CONTROL,
+                    "public, max-age=31536000, immutable".to_owned(),
+                ),
+            ],
+            bytes,
+        )
+            .into_response(),
+        Ok(None) => StatusCode::NOT_FOUND.into_response(),
+        Err(e) => {
+            error!(key, err = e, "Unable to fetch image");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+async fn api_recipe_delete(
+    Extension(store): Extension<Arc<storage::file_store::AsyncFileStore>>,
+    Extension(app_store): Extension<Arc<dyn storage::APIStore>>,
+    session: storage::UserIdFromSession,
+    Path(recipe_id): Path<String>,
+) -> api::EmptyResponse {
     use storage::{UserId, UserIdFromSession::*};
     match session {
-        NoUserId => store.get_categories().await.into(),
-        FoundUserId(UserId(id)) => app_store.get_categories_for_user(id.as_str()).await.into(),
+        NoUserId => api::EmptyResponse::Unauthorized,
+        FoundUserId(UserId(id)) if store.is_editor(&id) => {
+            store.delete_recipe(&recipe_id).await.into()
+        }
+        FoundUserId(UserId(id)) => storage_response(
+            app_store
+                .delete_recipes_for_user(&id, &vec![recipe_id])
+                .await,
+        ),
     }
 }
 
-async fn api_save_categories(
-    Extension(app_store): Extension<Arc<storage::SqliteStore>>,
+async fn api_recipe_trash(
+    Extension(app_store): Extension<Arc<dyn storage::APIStore>>,
     session: storage::UserIdFromSession,
-    Json(categories): Json<String>,
-) -> api::EmptyResponse {
+) -> api::TrashResponse {
     use storage::{UserId, UserIdFromSession::FoundUserId};
     if let FoundUserId(UserId(id)) = session {
-        app_store
-            .store_categories_for_user(id.as_str(), categories.as_str())
-            .await
-            .into()
+        app_store.fetch_trash_for_user(&id).await.into()
     } else {
-        api::EmptyResponse::Unauthorized
+        api::Response::Unauthorized
     }
 }
 
-async fn api_save_recipes(
-    Extension(app_store): Extension<Arc<storage::SqliteStore>>,
+async fn api_recipe_restore(
+    Extension(app_store): Extension<Arc<dyn storage::APIStore>>,
     session: storage::UserIdFromSession,
-    Json(recipes): Json<Vec<RecipeEntry>>,
-) -> api::EmptyResponse {
+    Path(recipe_id): Path<String>,
+) -> api::RecipeEntryResponse {
     use storage::{UserId, UserIdFromSession::FoundUserId};
     if let FoundUserId(UserId(id)) = session {
-        app_store
-            .store_recipes_for_user(id.as_str(), &recipes)
-            .await
-            .into()
+        match app_store.restore_recipe_for_user(&id, &recipe_id).await {
+            Ok(Some(entry)) => api::Response::success(vec![entry]),
+            Ok(None) => api::Response::NotFound,
+            Err(storage::Error::Conflict(current)) => api::Response::Conflict(current),
+            Err(storage::Error::LimitExceeded(message)) => {
+                api::Response::error_code(api::ErrorCode::PayloadTooLarge, message)
+            }
+            Err(storage::Error::Busy) => busy_response(),
+            Err(err) => api::Response::error(500, format!("{:?}", err)),
+        }
     } else {
-        api::EmptyResponse::Unauthorized
+        api::Response::Unauthorized
     }
 }
 
-async fn api_plan_for_date(
-    Extension(app_store): Extension<Arc<storage::SqliteStore>>,
+async fn api_archived_recipes(
+    Extension(app_store): Extension<Arc<dyn storage::APIStore>>,
     session: storage::UserIdFromSession,
-    Path(date): Path<chrono::NaiveDate>,
-) -> api::PlanDataResponse {
+) -> api::ArchivedResponse {
     use storage::{UserId, UserIdFromSession::FoundUserId};
     if let FoundUserId(UserId(id)) = session {
-        app_store.fetch_meal_plan_for_date(&id, date).await.into()
+        app_store.fetch_archived_recipes_for_user(&id).await.into()
     } else {
         api::Response::Unauthorized
     }
 }
 
-async fn api_plan(
-    Extension(app_store): Extension<Arc<storage::SqliteStore>>,
+async fn set_recipe_archived(
+    app_store: &dyn storage::APIStore,
     session: storage::UserIdFromSession,
-) -> api::PlanDataResponse {
+    recipe_id: String,
+    archived: bool,
+) -> api::EmptyResponse {
     use storage::{UserId, UserIdFromSession::FoundUserId};
     if let FoundUserId(UserId(id)) = session {
-        app_store.fetch_latest_meal_plan(&id).await.into()
+        match app_store.fetch_recipe_ids_for_user(&id).await {
+            Ok(ids) if !ids.contains(&recipe_id) => return api::Response::NotFound,
+            Ok(_) => (),
+            Err(err) => return api::Response::error(500, format!("{:?}", err)),
+        }
+        storage_response(
+            app_store
+                .set_recipe_archived_for_user(&id, &recipe_id, archived)
+                .await,
+        )
     } else {
-        api::Response::Unauthorized
+        api::EmptyResponse::Unauthorized
     }
 }
 
-async fn api_plan_since(
-    Extension(app_store): Extension<Arc<storage::SqliteStore>>,
+/// Hide a recipe from planning without deleting it.
+async fn api_archive_recipe(
+    Extension(app_store): Extension<Arc<dyn storage::APIStore>>,
     session: storage::UserIdFromSession,
-    Path(date): Path<chrono::NaiveDate>,
-) -> api::PlanHistoryResponse {
+    Path(recipe_id): Path<String>,
+) -> api::EmptyResponse {
+    set_recipe_archived(app_store.as_ref(), session, recipe_id, true).await
+}
+
+async fn api_unarchive_recipe(
+    Extension(app_store): Extension<Arc<dyn storage::APIStore>>,
+    session: storage::UserIdFromSession,
+    Path(recipe_id): Path<String>,
+) -> api::EmptyResponse {
+    set_recipe_archived(app_store.as_ref(), session, recipe_id, false).await
+}
+
+/// Split the ids in a bulk request into the ones that match one of the
+/// user's recipes and the ones that don't.
+async fn partition_recipe_ids(
+    app_store: &dyn storage::APIStore,
+    user_id: &str,
+    mut recipe_ids: Vec<String>,
+) -> storage::Result<(Vec<String>, Vec<String>)> {
+    let known = app_store.fetch_recipe_ids_for_user(user_id).await?;
+    recipe_ids.sort();
+    recipe_ids.dedup();
+    Ok(recipe_ids.into_iter().partition(|id| known.contains(id)))
+}
+
+/// Rewrite the entries for the recipes with `change` and save them together.
+async fn update_recipe_entries<F>(
+    app_store: &dyn storage::APIStore,
+    user_id: &str,
+    recipe_ids: &Vec<String>,
+    mut change: F,
+) -> storage::Result<()>
+where
+    F: FnMut(&mut RecipeEntry),
+{
+    let mut entries = Vec::with_capacity(recipe_ids.len());
+    for recipe_id in recipe_ids {
+        if let Some(mut entry) = app_store
+            .get_recipe_entry_for_user(user_id, recipe_id.as_str())
+            .await?
+        {
+            change(&mut entry);
+            entries.push(entry);
+        }
+    }
+    app_store.store_recipes_for_user(user_id, &entries).await?;
+    Ok(())
+}
+
+async fn api_bulk_delete(
+    Extension(app_store): Extension<Arc<dyn storage::APIStore>>,
+    session: storage::UserIdFromSession,
+    Json(request): Json<api::BulkRecipes>,
+) -> api::BulkResponse {
     use storage::{UserId, UserIdFromSession::FoundUserId};
     if let FoundUserId(UserId(id)) = session {
-        app_store.fetch_meal_plans_since(&id, date).await.into()
+        let result = async {
+            let (updated, not_found) =
+                partition_recipe_ids(app_store.as_ref(), &id, request.recipe_ids).await?;
+            app_store.delete_recipes_for_user(&id, &updated).await?;
+            Ok::<_, storage::Error>(api::BulkResult { updated, not_found })
+        };
+        storage_response(result.await)
     } else {
-        api::PlanHistoryResponse::Unauthorized
+        api::Response::Unauthorized
     }
 }
 
-async fn api_all_plans(
-    Extension(app_store): Extension<Arc<storage::SqliteStore>>,
+async fn api_bulk_archive(
+    Extension(app_store): Extension<Arc<dyn storage::APIStore>>,
     session: storage::UserIdFromSession,
-) -> api::Response<Vec<NaiveDate>> {
+    Json(request): Json<api::BulkArchive>,
+) -> api::BulkResponse {
     use storage::{UserId, UserIdFromSession::FoundUserId};
     if let FoundUserId(UserId(id)) = session {
-        app_store.fetch_all_meal_plans(&id).await.into()
+        let result = async {
+            let (updated, not_found) =
+                partition_recipe_ids(app_store.as_ref(), &id, request.recipe_ids).await?;
+            for recipe_id in updated.iter() {
+                app_store
+                    .set_recipe_archived_for_user(&id, recipe_id, request.archived)
+                    .await?;
+            }
+            Ok::<_, storage::Error>(api::BulkResult { updated, not_found })
+        };
+        storage_response(result.await)
     } else {
         api::Response::Unauthorized
     }
 }
 
-async fn api_delete_plan_for_date(
-    Extension(app_store): Extension<Arc<storage::SqliteStore>>,
+async fn api_bulk_tags(
+    Extension(app_store): Extension<Arc<dyn storage::APIStore>>,
     session: storage::UserIdFromSession,
-    Path(date): Path<chrono::NaiveDate>,
-) -> api::EmptyResponse {
+    Json(request): Json<api::BulkTags>,
+) -> api::BulkResponse {
     use storage::{UserId, UserIdFromSession::FoundUserId};
     if let FoundUserId(UserId(id)) = session {
-        app_store
-            .delete_meal_plan_for_date(id.as_str(), date)
-            .await
-            .into()
+        let normalize = |tags: &BTreeSet<String>| {
+            tags.iter()
+                .map(|t| t.trim().to_lowercase())
+                .filter(|t| !t.is_empty() && !t.contains(','))
+                .collect::<BTreeSet<String>>()
+        };
+        let (add, remove) = (normalize(&request.add), normalize(&request.remove));
+        let result = async {
+            let (updated, not_found) =
+                partition_recipe_ids(app_store.as_ref(), &id, request.recipe_ids).await?;
+            update_recipe_entries(app_store.as_ref(), &id, &updated, |entry| {
+                let text = serialize::update_tags(entry.recipe_text(), |mut tags| {
+                    tags.retain(|t| !remove.contains(t));
+                    tags.extend(add.iter().cloned());
+                    tags
+                });
+                entry.set_recipe_text(text);
+            })
+            .await?;
+            Ok::<_, storage::Error>(api::BulkResult { updated, not_found })
+        };
+        storage_response(result.await)
     } else {
-        api::EmptyResponse::Unauthorized
+        api::Response::Unauthorized
     }
 }
 
-async fn api_save_plan_for_date(
-    Extension(app_store): Extension<Arc<storage::SqliteStore>>,
+async fn api_bulk_category(
+    Extension(app_store): Extension<Arc<dyn storage::APIStore>>,
     session: storage::UserIdFromSession,
-    Path(date): Path<chrono::NaiveDate>,
-    Json(meal_plan): Json<Vec<(String, i32)>>,
-) -> api::EmptyResponse {
+    Json(request): Json<api::BulkCategory>,
+) -> api::BulkResponse {
     use storage::{UserId, UserIdFromSession::FoundUserId};
     if let FoundUserId(UserId(id)) = session {
-        app_store
-            .save_meal_plan(id.as_str(), &meal_plan, date)
-            .await
-            .into()
+        let category = request
+            .category
+            .map(|c| c.trim().to_owned())
+            .filter(|c| !c.is_empty());
+        let result = async {
+            let (updated, not_found) =
+                partition_recipe_ids(app_store.as_ref(), &id, request.recipe_ids).await?;
+            update_recipe_entries(app_store.as_ref(), &id, &updated, |entry| match &category {
+                Some(category) => entry.set_category(category),
+                None => entry.clear_category(),
+            })
+            .await?;
+            Ok::<_, storage::Error>(api::BulkResult { updated, not_found })
+        };
+        storage_response(result.await)
     } else {
-        api::EmptyResponse::Unauthorized
+        api::Response::Unauthorized
     }
 }
 
-async fn api_save_plan(
-    Extension(app_store): Extension<Arc<storage::SqliteStore>>,
+async fn api_recipe_purge(
+    Extension(app_store): Extension<Arc<dyn storage::APIStore>>,
     session: storage::UserIdFromSession,
-    Json(meal_plan): Json<Vec<(String, i32)>>,
+    Path(recipe_id): Path<String>,
 ) -> api::EmptyResponse {
     use storage::{UserId, UserIdFromSession::FoundUserId};
     if let FoundUserId(UserId(id)) = session {
+        storage_response(app_store.purge_trash_for_user(&id, &recipe_id).await)
+    } else {
+        api::EmptyResponse::Unauthorized
+    }
+}
+
+#[instrument]
+async fn api_recipes(
+    Extension(store): Extension<Arc<storage::file_store::AsyncFileStore>>,
+    Extension(app_store): Extension<Arc<dyn storage::APIStore>>,
+    session: storage::UserIdFromSession,
+) -> api::RecipeEntryResponse {
+    // Select recipes based on the user-id if it exists or serve the default if it does not.
+    use storage::{UserId, UserIdFromSession::*};
+    match session {
+        NoUserId => api::RecipeEntryResponse::from(store.get_recipes().await),
+        FoundUserId(UserId(id)) if store.is_editor(&id) => store.get_recipes().await.into(),
+        FoundUserId(UserId(id)) => app_store.get_recipes_for_user(id.as_str()).await.into(),
+    }
+}
+
+#[instrument]
+async fn api_recipes_parsed(
+    Extension(store): Extension<Arc<storage::file_store::AsyncFileStore>>,
+    Extension(app_store): Extension<Arc<dyn storage::APIStore>>,
+    session: storage::UserIdFromSession,
+) -> api::ParsedRecipesResponse {
+    use storage::{UserId, UserIdFromSession::*};
+    let entries = match session {
+        NoUserId => store.get_recipes().await,
+        FoundUserId(UserId(id)) if store.is_editor(&id) => store.get_recipes().await,
+        FoundUserId(UserId(id)) => app_store.get_recipes_for_user(id.as_str()).await,
+    };
+    let entries = match entries {
+        Ok(entries) => entries.unwrap_or_default(),
+        Err(e) => {
+            return api::Response::error(
+                StatusCode::INTERNAL_SERVER_ERROR.as_u16(),
+                format!("{:?}", e),
+            )
+        }
+    };
+    let mut recipes = Vec::new();
+    for entry in entries {
+        match parse::as_recipe(entry.recipe_text()) {
+            Ok(recipe) => recipes.push(api::ParsedRecipe::new(&entry, &recipe)),
+            Err(err) => {
+                debug!(recipe_id = entry.recipe_id(), %err, "Skipping unparseable recipe");
+            }
+        }
+    }
+    recipes.into()
+}
+
+#[derive(Debug, Deserialize)]
+struct RecipeIndexParams {
+    /// Only include recipes with a total time at or below this many minutes.
+    max_total_time: Option<u64>,
+    /// Sort by total time instead of by title.
+    sort_by_time: Option<bool>,
+    /// Include archived recipes.
+    archived: Option<bool>,
+}
+
+#[instrument]
+async fn api_recipe_index(
+    Extension(store): Extension<Arc<storage::file_store::AsyncFileStore>>,
+    Extension(app_store): Extension<Arc<dyn storage::APIStore>>,
+    session: storage::UserIdFromSession,
+    Query(params): Query<RecipeIndexParams>,
+) -> api::RecipeIndexResponse {
+    use storage::{UserId, UserIdFromSession::*};
+    let (entries, archived) = match session {
+        NoUserId => (store.get_recipes().await, Ok(BTreeSet::new())),
+        FoundUserId(UserId(id)) if store.is_editor(&id) => {
+            (store.get_recipes().await, Ok(BTreeSet::new()))
+        }
+        FoundUserId(UserId(id)) => (
+            app_store.get_recipes_for_user(id.as_str()).await,
+            app_store.fetch_archived_recipes_for_user(id.as_str()).await,
+        ),
+    };
+    let (entries, archived) = match (entries, archived) {
+        (Ok(entries), Ok(archived)) => (entries.unwrap_or_default(), archived),
+        (Err(e), _) | (_, Err(e)) => {
+            return api::Response::error(
+                StatusCode::INTERNAL_SERVER_ERROR.as_u16(),
+                format!("{:?}", e),
+            )
+        }
+    };
+    let include_archived = params.archived.unwrap_or(false);
+    let mut index = Vec::new();
+    for entry in entries {
+        let is_archived = archived.contains(entry.recipe_id());
+        if is_archived && !include_archived {
+            continue;
+        }
+        match parse::as_recipe(entry.recipe_text()) {
+            Ok(recipe) => {
+                let mut index_entry = api::RecipeIndexEntry::new(&entry, &recipe);
+                index_entry.archived = is_archived;
+                index.push(index_entry);
+            }
+            Err(err) => {
+                debug!(recipe_id = entry.recipe_id(), %err, "Skipping unparseable recipe");
+            }
+        }
+    }
+    if let Some(max) = params.max_total_time {
+        index.retain(|e| e.total_time <= max * 60);
+    }
+    if params.sort_by_time.unwrap_or(false) {
+        index.sort_by(|l, r| l.total_time.cmp(&r.total_time).then(l.title.cmp(&r.title)));
+    } else {
+        index.sort_by(|l, r| l.title.cmp(&r.title));
+    }
+    index.into()
+}
+
+#[instrument]
+async fn api_category_mappings(
+    Extension(app_store): Extension<Arc<dyn storage::APIStore>>,
+    session: storage::UserIdFromSession,
+) -> api::CategoryMappingResponse {
+    use storage::UserIdFromSession::*;
+    match session {
+        NoUserId => api::Response::Unauthorized,
+        FoundUserId(user_id) => app_store
+            .get_category_mappings_for_user(&user_id.0)
+            .await
+            .into(),
+    }
+}
+
+#[instrument]
+async fn api_save_category_mappings(
+    Extension(app_store): Extension<Arc<dyn storage::APIStore>>,
+    session: storage::UserIdFromSession,
+    Json(mappings): Json<Vec<(String, String)>>,
+) -> api::EmptyResponse {
+    use storage::UserIdFromSession::*;
+    match session {
+        NoUserId => api::Response::Unauthorized,
+        FoundUserId(user_id) => match app_store
+            .save_category_mappings_for_user(&user_id.0, &mappings)
+            .await
+        {
+            Ok(_) => api::EmptyResponse::success(()),
+            Err(e) => api::EmptyResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR.as_u16(),
+                format!("{:?}", e),
+            ),
+        },
+    }
+}
+
+#[instrument]
+async fn api_category_suggestions(
+    Extension(app_store): Extension<Arc<dyn storage::APIStore>>,
+    session: storage::UserIdFromSession,
+) -> api::CategorySuggestionResponse {
+    use storage::{UserId, UserIdFromSession::FoundUserId};
+    if let FoundUserId(UserId(id)) = session {
+        let entries = match app_store.get_recipes_for_user(id.as_str()).await {
+            Ok(entries) => entries.unwrap_or_default(),
+            Err(e) => {
+                return api::Response::error(
+                    StatusCode::INTERNAL_SERVER_ERROR.as_u16(),
+                    format!("{:?}", e),
+                )
+            }
+        };
+        let mappings: BTreeMap<String, String> =
+            match app_store.get_category_mappings_for_user(&id).await {
+                Ok(mappings) => mappings.unwrap_or_default().into_iter().collect(),
+                Err(e) => {
+                    return api::Response::error(
+                        StatusCode::INTERNAL_SERVER_ERROR.as_u16(),
+                        format!("{:?}", e),
+                    )
+                }
+            };
+        let mut names = BTreeSet::new();
+        for entry in entries {
+            match parse::as_recipe(entry.recipe_text()) {
+                Ok(recipe) => {
+                    for (_, i) in recipe.get_ingredients() {
+                        if i.recipe_reference().is_none() {
+                            names.insert(i.name);
+                        }
+                    }
+                }
+                Err(err) => {
+                    debug!(recipe_id = entry.recipe_id(), %err, "Skipping unparseable recipe");
+                }
+            }
+        }
+        api::CategorySuggestionResponse::from(category::suggest_categories(
+            names.iter(),
+            &mappings,
+        ))
+    } else {
+        api::Response::Unauthorized
+    }
+}
+
+#[instrument]
+async fn api_categories(
+    Extension(store): Extension<Arc<storage::file_store::AsyncFileStore>>,
+    Extension(app_store): Extension<Arc<dyn storage::APIStore>>,
+    session: storage::UserIdFromSession,
+) -> api::Response<String> {
+    // Select Categories based on the user-id if it exists or serve the default if it does not.
+    use storage::{UserId, UserIdFromSession::*};
+    match session {
+        NoUserId => store.get_categories().await.into(),
+        FoundUserId(UserId(id)) if store.is_editor(&id) => store.get_categories().await.into(),
+        FoundUserId(UserId(id)) => app_store.get_categories_for_user(id.as_str()).await.into(),
+    }
+}
+
+async fn api_save_categories(
+    Extension(store): Extension<Arc<storage::file_store::AsyncFileStore>>,
+    Extension(app_store): Extension<Arc<dyn storage::APIStore>>,
+    session: storage::UserIdFromSession,
+    Json(categories): Json<String>,
+) -> api::EmptyResponse {
+    use storage::{UserId, UserIdFromSession::FoundUserId};
+    match session {
+        FoundUserId(UserId(id)) if store.is_editor(&id) => {
+            store.store_categories(categories.as_str()).await.into()
+        }
+        FoundUserId(UserId(id)) => storage_response(
+            app_store
+                .store_categories_for_user(id.as_str(), categories.as_str())
+                .await,
+        ),
+        _ => api::EmptyResponse::Unauthorized,
+    }
+}
+
+/// Check that every recipe entry has an id and parses so that broken recipes
+/// never make it into the store.
+fn validate_recipes(recipes: &Vec<RecipeEntry>) -> Vec<api::ValidationError> {
+    let mut seen = BTreeMap::new();
+    recipes
+        .iter()
+        .filter_map(|entry| {
+            let recipe_id = recipes::slugify(entry.recipe_id());
+            if entry.recipe_id().trim().is_empty() {
+                Some(api::ValidationError::new(
+                    entry.recipe_id(),
+                    "recipe_id",
+                    "Recipe ids can not be empty",
+                ))
+            } else if recipe_id.is_empty() {
+                Some(api::ValidationError::new(
+                    entry.recipe_id(),
+                    "recipe_id",
+                    "Recipe ids must contain at least one letter or number",
+                ))
+            } else if let Some(first) = seen.insert(recipe_id.clone(), entry.recipe_id()) {
+                Some(api::ValidationError::new(
+                    entry.recipe_id(),
+                    "recipe_id",
+                    format!(
+                        "{} and {} would both be saved as {}",
+                        first,
+                        entry.recipe_id(),
+                        recipe_id
+                    ),
+                ))
+            } else {
+                parse::as_recipe(entry.recipe_text())
+                    .err()
+                    .map(|err| api::ValidationError::new(entry.recipe_id(), "recipe_text", err))
+            }
+        })
+        .collect()
+}
+
+/// Save recipes straight to the recipe directory. The files have no revisions
+/// so the last write always wins. Files are named with the normalized id and
+/// a file saved under an id that needed normalizing is moved.
+async fn save_recipe_files(
+    store: &storage::file_store::AsyncFileStore,
+    recipes: &Vec<RecipeEntry>,
+) -> api::RecipeSaveResponse {
+    let mut saved = Vec::new();
+    for entry in recipes {
+        let recipe_id = recipes::slugify(entry.recipe_id());
+        let mut normalized = entry.clone();
+        normalized.set_recipe_id(recipe_id.as_str());
+        if let Err(err) = store.store_recipe(&normalized).await {
+            return api::Response::error(
+                500,
+                format!("Failed to save recipe {}: {:?}", entry.recipe_id(), err),
+            );
+        }
+        let renamed_from = if recipe_id != entry.recipe_id() {
+            // Ids that aren't valid file names can't have a file to move.
+            if let Err(err) = store.delete_recipe(entry.recipe_id()).await {
+                debug!(
+                    recipe_id = entry.recipe_id(),
+                    ?err,
+                    "No recipe file to move"
+                );
+            }
+            Some(entry.recipe_id().to_owned())
+        } else {
+            None
+        };
+        saved.push(api::SavedRecipe {
+            recipe_id,
+            revision: 0,
+            renamed_from,
+        });
+    }
+    api::Response::success(saved)
+}
+
+async fn api_save_recipes(
+    Extension(store): Extension<Arc<storage::file_store::AsyncFileStore>>,
+    Extension(app_store): Extension<Arc<dyn storage::APIStore>>,
+    session: storage::UserIdFromSession,
+    Json(recipes): Json<Vec<RecipeEntry>>,
+) -> api::RecipeSaveResponse {
+    use storage::{UserId, UserIdFromSession::FoundUserId};
+    if let FoundUserId(UserId(id)) = session {
+        let errors = validate_recipes(&recipes);
+        if !errors.is_empty() {
+            debug!(?errors, "Rejecting invalid recipes");
+            return api::RecipeSaveResponse::Invalid(errors);
+        }
+        if store.is_editor(&id) {
+            return save_recipe_files(store.as_ref(), &recipes).await;
+        }
+        match app_store
+            .store_recipes_for_user(id.as_str(), &recipes)
+            .await
+        {
+            Ok(revisions) => api::Response::success(
+                recipes
+                    .iter()
+                    .zip(revisions)
+                    .map(|(entry, (recipe_id, revision))| api::SavedRecipe {
+                        renamed_from: if entry.recipe_id() != recipe_id {
+                            Some(entry.recipe_id().to_owned())
+                        } else {
+                            None
+                        },
+                        recipe_id,
+                        revision,
+                    })
+                    .collect(),
+            ),
+            Err(storage::Error::Conflict(current)) => {
+                debug!(count = current.len(), "Rejecting stale recipe saves");
+                api::Response::Conflict(current)
+            }
+            Err(storage::Error::LimitExceeded(message)) => {
+                debug!(%message, "Rejecting recipes over the limits");
+                api::Response::error_code(api::ErrorCode::PayloadTooLarge, message)
+            }
+            Err(storage::Error::Busy) => busy_response(),
+            Err(storage::Error::EntryFailed(_, err)) if matches!(*err, storage::Error::Busy) => {
+                busy_response()
+            }
+            Err(storage::Error::EntryFailed(recipe_id, err)) => api::Response::error(
+                500,
+                format!(
+                    "Failed to save recipe {}. No recipes were saved: {:?}",
+                    recipe_id, err
+                ),
+            ),
+            Err(err) => api::Response::error(500, format!("{:?}", err)),
+        }
+    } else {
+        api::Response::Unauthorized
+    }
+}
+
+async fn api_plan_for_date(
+    Extension(app_store): Extension<Arc<dyn storage::APIStore>>,
+    session: storage::UserIdFromSession,
+    Path(date): Path<chrono::NaiveDate>,
+) -> api::PlanDataResponse {
+    use storage::{UserId, UserIdFromSession::FoundUserId};
+    if let FoundUserId(UserId(id)) = session {
+        app_store.fetch_meal_plan_for_date(&id, date).await.into()
+    } else {
+        api::Response::Unauthorized
+    }
+}
+
+async fn api_plan_meals_for_date(
+    Extension(app_store): Extension<Arc<dyn storage::APIStore>>,
+    session: storage::UserIdFromSession,
+    Path(date): Path<chrono::NaiveDate>,
+) -> api::PlanMealsResponse {
+    use storage::{UserId, UserIdFromSession::FoundUserId};
+    if let FoundUserId(UserId(id)) = session {
+        app_store.fetch_plan_meals_for_date(&id, date).await.into()
+    } else {
+        api::Response::Unauthorized
+    }
+}
+
+async fn api_save_plan_meals_for_date(
+    Extension(app_store): Extension<Arc<dyn storage::APIStore>>,
+    session: storage::UserIdFromSession,
+    Path(date): Path<chrono::NaiveDate>,
+    Json(meals): Json<api::PlanMeals>,
+) -> api::EmptyResponse {
+    use storage::{UserId, UserIdFromSession::FoundUserId};
+    if let FoundUserId(UserId(id)) = session {
+        storage_response(
+            app_store
+                .save_plan_meals_for_date(id.as_str(), date, &meals)
+                .await,
+        )
+    } else {
+        api::EmptyResponse::Unauthorized
+    }
+}
+
+async fn api_plan_freezer_meals_for_date(
+    Extension(app_store): Extension<Arc<dyn storage::APIStore>>,
+    session: storage::UserIdFromSession,
+    Path(date): Path<chrono::NaiveDate>,
+) -> api::PlanFreezerMealsResponse {
+    use storage::{UserId, UserIdFromSession::FoundUserId};
+    if let FoundUserId(UserId(id)) = session {
+        app_store
+            .fetch_plan_freezer_meals_for_date(&id, date)
+            .await
+            .into()
+    } else {
+        api::Response::Unauthorized
+    }
+}
+
+async fn api_save_plan_freezer_meals_for_date(
+    Extension(app_store): Extension<Arc<dyn storage::APIStore>>,
+    session: storage::UserIdFromSession,
+    Path(date): Path<chrono::NaiveDate>,
+    Json(recipe_ids): Json<BTreeSet<String>>,
+) -> api::EmptyResponse {
+    use storage::{UserId, UserIdFromSession::FoundUserId};
+    if let FoundUserId(UserId(id)) = session {
+        storage_response(
+            app_store
+                .save_plan_freezer_meals_for_date(id.as_str(), date, &recipe_ids)
+                .await,
+        )
+    } else {
+        api::EmptyResponse::Unauthorized
+    }
+}
+
+/// What day it is for a user with these settings. Users that haven't set a
+/// timezone get the server's.
+fn today_for(settings: &BTreeMap<String, String>) -> NaiveDate {
+    match api::utc_offset(settings) {
+        Some(offset) => chrono::Utc::now().with_timezone(&offset).date_naive(),
+        None => chrono::Local::now().date_naive(),
+    }
+}
+
+async fn user_today(
+    app_store: &dyn storage::APIStore,
+    user_id: &str,
+) -> storage::Result<NaiveDate> {
+    let settings = app_store.fetch_user_settings(user_id).await?;
+    Ok(today_for(&settings))
+}
+
+/// The plans in the user's current week.
+async fn this_week(
+    app_store: &dyn storage::APIStore,
+    user_id: &str,
+) -> storage::Result<api::PlanWeek> {
+    let settings = app_store.fetch_user_settings(user_id).await?;
+    let start = api::week_start(&settings).start_of_week(today_for(&settings));
+    let end = start + chrono::Duration::days(6);
+    let plans = app_store
+        .fetch_meal_plans_in_range(user_id, start, end)
+        .await?
+        .unwrap_or_default();
+    Ok(api::PlanWeek { start, end, plans })
+}
+
+async fn api_plan_this_week(
+    Extension(app_store): Extension<Arc<dyn storage::APIStore>>,
+    session: storage::UserIdFromSession,
+) -> api::PlanWeekResponse {
+    use storage::{UserId, UserIdFromSession::FoundUserId};
+    if let FoundUserId(UserId(id)) = session {
+        this_week(app_store.as_ref(), &id).await.into()
+    } else {
+        api::Response::Unauthorized
+    }
+}
+
+/// The first day of the user's week that `date` is in.
+async fn week_of(
+    app_store: &dyn storage::APIStore,
+    user_id: &str,
+    date: NaiveDate,
+) -> storage::Result<NaiveDate> {
+    let settings = app_store.fetch_user_settings(user_id).await?;
+    Ok(api::week_start(&settings).start_of_week(date))
+}
+
+/// The plans in the week starting on `start` with what is left to buy for
+/// each of them. The shopping lists are built the same way the client builds
+/// them except that staples are left out.
+async fn shared_week(
+    app_store: &dyn storage::APIStore,
+    user_id: &str,
+    start: NaiveDate,
+) -> storage::Result<api::SharedWeek> {
+    let end = start + chrono::Duration::days(6);
+    let settings = app_store.fetch_user_settings(user_id).await?;
+    let all_recipes = user_recipes(app_store, user_id).await?;
+    let densities = recipes::unit::DensityTable::default()
+        .with_overrides(api::density_overrides(&settings).iter());
+    let mut plans = Vec::new();
+    for (date, plan) in app_store
+        .fetch_meal_plans_in_range(user_id, start, end)
+        .await?
+        .unwrap_or_default()
+    {
+        let meals = app_store.fetch_plan_meals_for_date(user_id, date).await?;
+        let freezer_meals = app_store
+            .fetch_plan_freezer_meals_for_date(user_id, date)
+            .await?;
+        let (filtered_ingredients, modified_amts, extra_items) =
+            app_store.fetch_inventory_for_date(user_id, date).await?;
+        let plan = plan
+            .into_iter()
+            .filter(|(_, count)| *count > 0)
+            .collect::<Vec<(String, i32)>>();
+        let mut recipes = plan
+            .iter()
+            .flat_map(|(id, count)| {
+                let recipe = all_recipes.get(id);
+                // Recipes in old plans may have been deleted since.
+                let title = recipe
+                    .map(|r| r.title.clone())
+                    .unwrap_or_else(|| id.clone());
+                api::planned_meals_for(&meals, id, recipe)
+                    .into_iter()
+                    .map(move |meal| api::SharedRecipe {
+                        title: title.clone(),
+                        count: *count,
+                        meal,
+                    })
+            })
+            .collect::<Vec<api::SharedRecipe>>();
+        recipes.sort_by_key(|r| r.meal);
+        let shopping_plan = recipes::shopping::ShoppingPlan {
+            recipe_counts: plan
+                .iter()
+                .map(|(id, count)| (id.clone(), *count as usize))
+                .collect(),
+            freezer_meals,
+            household_size: api::household_size(&settings),
+            filtered_ingredients: filtered_ingredients.into_iter().collect(),
+            densities: Some(densities.clone()),
+            ..Default::default()
+        };
+        let modified_amts = modified_amts.into_iter().collect::<BTreeMap<_, _>>();
+        let mut shopping_list = recipes::shopping::shopping_list(&shopping_plan, &all_recipes)
+            .into_iter()
+            .filter(|(_, item)| !item.filtered)
+            .map(|(key, item)| {
+                let i = item.ingredient;
+                let amt = modified_amts
+                    .get(&key)
+                    .cloned()
+                    .unwrap_or_else(|| format!("{}", i.amt.normalize()));
+                match i.form {
+                    Some(form) => format!("{} {} ({})", amt, i.name, form),
+                    None => format!("{} {}", amt, i.name),
+                }
+            })
+            .collect::<Vec<String>>();
+        shopping_list.extend(
+            extra_items
+                .into_iter()
+                .map(|(amt, name)| format!("{} {}", amt, name)),
+        );
+        plans.push(api::SharedPlan {
+            date,
+            recipes,
+            shopping_list,
+        });
+    }
+    Ok(api::SharedWeek { start, end, plans })
+}
+
+/// The shared week for a share token or None if nothing is shared with it.
+async fn shared_week_for_token(
+    app_store: &dyn storage::APIStore,
+    token: &str,
+) -> storage::Result<Option<api::SharedWeek>> {
+    match app_store.fetch_plan_share(token).await? {
+        Some((user_id, start)) => Ok(Some(shared_week(app_store, &user_id, start).await?)),
+        None => Ok(None),
+    }
+}
+
+async fn api_plan_week_share(
+    Extension(app_store): Extension<Arc<dyn storage::APIStore>>,
+    session: storage::UserIdFromSession,
+    Path(date): Path<chrono::NaiveDate>,
+) -> api::PlanShareResponse {
+    use storage::{UserId, UserIdFromSession::FoundUserId};
+    if let FoundUserId(UserId(id)) = session {
+        storage_response(
+            async {
+                let start = week_of(app_store.as_ref(), &id, date).await?;
+                let token = app_store.fetch_plan_share_for_user(&id, start).await?;
+                Ok::<_, storage::Error>(token.map(|token| api::PlanShare { token, start }))
+            }
+            .await,
+        )
+    } else {
+        api::Response::Unauthorized
+    }
+}
+
+async fn api_share_plan_week(
+    Extension(app_store): Extension<Arc<dyn storage::APIStore>>,
+    session: storage::UserIdFromSession,
+    Path(date): Path<chrono::NaiveDate>,
+) -> api::PlanShareResponse {
+    use storage::{UserId, UserIdFromSession::FoundUserId};
+    if let FoundUserId(UserId(id)) = session {
+        storage_response(
+            async {
+                let start = week_of(app_store.as_ref(), &id, date).await?;
+                let token = app_store.create_plan_share_for_user(&id, start).await?;
+                Ok::<_, storage::Error>(Some(api::PlanShare { token, start }))
+            }
+            .await,
+        )
+    } else {
+        api::Response::Unauthorized
+    }
+}
+
+async fn api_unshare_plan_week(
+    Extension(app_store): Extension<Arc<dyn storage::APIStore>>,
+    session: storage::UserIdFromSession,
+    Path(date): Path<chrono::NaiveDate>,
+) -> api::EmptyResponse {
+    use storage::{UserId, UserIdFromSession::FoundUserId};
+    if let FoundUserId(UserId(id)) = session {
+        storage_response(
+            async {
+                let start = week_of(app_store.as_ref(), &id, date).await?;
+                app_store.delete_plan_share_for_user(&id, start).await
+            }
+            .await,
+        )
+    } else {
+        api::Response::Unauthorized
+    }
+}
+
+/// A shared week. This doesn't need a login since the token is the secret.
+async fn api_shared_plan(
+    Extension(app_store): Extension<Arc<dyn storage::APIStore>>,
+    Path(token): Path<String>,
+) -> api::SharedWeekResponse {
+    storage_response(shared_week_for_token(app_store.as_ref(), &token).await)
+}
+
+/// The page a share link points at. It is rendered on the server so it works
+/// for people who don't have an account.
+async fn shared_plan_page(
+    Extension(app_store): Extension<Arc<dyn storage::APIStore>>,
+    Path(token): Path<String>,
+) -> Response {
+    match shared_week_for_token(app_store.as_ref(), &token).await {
+        Ok(Some(week)) => Response::builder()
+            .header(header::CONTENT_TYPE, "text/html; charset=utf-8")
+            .body(boxed(Full::from(prerender::render_shared_week_page(&week))))
+            .unwrap(),
+        Ok(None) => Response::builder()
+            .status(StatusCode::NOT_FOUND)
+            .body(boxed(Full::from("404")))
+            .unwrap(),
+        Err(err) => {
+            error!(?err, "Failed to load shared plan");
+            Response::builder()
+                .status(StatusCode::INTERNAL_SERVER_ERROR)
+                .body(boxed(Full::from("500")))
+                .unwrap()
+        }
+    }
+}
+
+/// A QR code for a share link. Only weeks of plans can be shared for now so
+/// the token is looked up as a plan share.
+async fn share_qr_code(
+    Extension(app_store): Extension<Arc<dyn storage::APIStore>>,
+    Path(token): Path<String>,
+    scheme: Option<Extension<qr::Scheme>>,
+    headers: HeaderMap,
+) -> Response {
+    let share = match app_store.fetch_plan_share(&token).await {
+        Ok(share) => share,
+        Err(err) => {
+            error!(?err, "Failed to look up share token");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+    let start = match share {
+        Some((_, start)) => start,
+        None => return StatusCode::NOT_FOUND.into_response(),
+    };
+    let path = api::PlanShare { token, start }.path();
+    let scheme = scheme.map(|Extension(scheme)| scheme).unwrap_or_default();
+    let url = match qr::request_origin(&headers, scheme) {
+        Some(origin) => format!("{}{}", origin, path),
+        None => path,
+    };
+    match qr::png(&url) {
+        Ok(png) => Response::builder()
+            .header(header::CONTENT_TYPE, "image/png")
+            .body(boxed(Full::from(png)))
+            .unwrap(),
+        Err(err) => {
+            error!(?err, "Failed to make QR code");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// Take what a cooked recipe used out of the amounts stored in the inventory
+/// for its plan. Ingredients without a stored amount are left alone.
+async fn deplete_inventory(
+    app_store: &dyn storage::APIStore,
+    user_id: &str,
+    date: NaiveDate,
+    recipe_id: &str,
+    count: i32,
+) -> storage::Result<()> {
+    let settings = app_store.fetch_user_settings(user_id).await?;
+    let shopping_plan = recipes::shopping::ShoppingPlan {
+        recipe_counts: BTreeMap::from([(recipe_id.to_owned(), count.max(1) as usize)]),
+        household_size: api::household_size(&settings),
+        densities: Some(
+            recipes::unit::DensityTable::default()
+                .with_overrides(api::density_overrides(&settings).iter()),
+        ),
+        ..Default::default()
+    };
+    let all_recipes = user_recipes(app_store, user_id).await?;
+    let used = recipes::shopping::shopping_list(&shopping_plan, &all_recipes);
+    let (_, modified_amts, _) = app_store.fetch_inventory_for_date(user_id, date).await?;
+    let ops = modified_amts
+        .into_iter()
+        .filter_map(|(key, amt)| {
+            let have = parse::as_measure(&format!("{} ", amt.trim())).ok()?;
+            let left = have.saturating_sub(&used.get(&key)?.ingredient.amt)?;
+            Some(api::InventoryOp::SetAmt(
+                key,
+                format!("{}", left.normalize()),
+            ))
+        })
+        .collect::<Vec<api::InventoryOp>>();
+    if !ops.is_empty() {
+        debug!(recipe_id, count = ops.len(), "Using up cooked ingredients");
+        app_store
+            .apply_inventory_ops_for_date(user_id, &date, &ops)
+            .await?;
+    }
+    Ok(())
+}
+
+/// Record a planned recipe as cooked. Extra portions go in the freezer. When
+/// asked a freezer meal's portions are taken out of the freezer and any
+/// other recipe's ingredients are taken out of the plan's inventory. Marking
+/// a recipe that is already cooked again only updates the day it was cooked.
+async fn mark_cooked(
+    app_store: &dyn storage::APIStore,
+    user_id: &str,
+    date: NaiveDate,
+    cooked: &api::MarkCooked,
+) -> storage::Result<()> {
+    let today = user_today(app_store, user_id).await?;
+    let already_cooked = app_store
+        .fetch_cooked_for_date(user_id, date)
+        .await?
+        .contains(&cooked.recipe_id);
+    app_store
+        .mark_cooked_for_date(user_id, date, &cooked.recipe_id, today)
+        .await?;
+    if already_cooked {
+        debug!(recipe_id = %cooked.recipe_id, %date, "Recipe was already cooked");
+        return Ok(());
+    }
+    let planned = app_store
+        .fetch_meal_plan_for_date(user_id, date)
+        .await?
+        .unwrap_or_default()
+        .into_iter()
+        .find(|(id, _)| id == &cooked.recipe_id)
+        .map(|(_, count)| count)
+        .unwrap_or(0);
+    let from_freezer = app_store
+        .fetch_plan_freezer_meals_for_date(user_id, date)
+        .await?
+        .contains(&cooked.recipe_id);
+    if cooked.deplete && !from_freezer {
+        deplete_inventory(app_store, user_id, date, &cooked.recipe_id, planned).await?;
+    }
+    if cooked.deplete && from_freezer {
+        let mut needed = planned;
+        // The oldest portions come out of the freezer first.
+        for item in app_store
+            .fetch_freezer_items_for_user(user_id)
+            .await?
+            .into_iter()
+            .filter(|item| item.recipe_id == cooked.recipe_id)
+        {
+            if needed <= 0 {
+                break;
+            }
+            let taken = needed.min(item.portions);
+            app_store
+                .take_freezer_portions_for_user(user_id, item.id, taken)
+                .await?;
+            needed -= taken;
+        }
+    }
+    if cooked.extra_portions > 0 {
+        let item = api::NewFreezerItem {
+            recipe_id: cooked.recipe_id.clone(),
+            portions: cooked.extra_portions,
+            frozen_on: today,
+        };
+        app_store.add_freezer_item_for_user(user_id, &item).await?;
+    }
+    Ok(())
+}
+
+async fn api_cooked_for_date(
+    Extension(app_store): Extension<Arc<dyn storage::APIStore>>,
+    session: storage::UserIdFromSession,
+    Path(date): Path<chrono::NaiveDate>,
+) -> api::CookedResponse {
+    use storage::{UserId, UserIdFromSession::FoundUserId};
+    if let FoundUserId(UserId(id)) = session {
+        app_store.fetch_cooked_for_date(&id, date).await.into()
+    } else {
+        api::Response::Unauthorized
+    }
+}
+
+async fn api_mark_cooked(
+    Extension(app_store): Extension<Arc<dyn storage::APIStore>>,
+    session: storage::UserIdFromSession,
+    Path(date): Path<chrono::NaiveDate>,
+    Json(cooked): Json<api::MarkCooked>,
+) -> api::EmptyResponse {
+    use storage::{UserId, UserIdFromSession::FoundUserId};
+    if let FoundUserId(UserId(id)) = session {
+        if let Err(err) = cooked.validate() {
+            return api::Response::error_code(
+                api::ErrorCode::Validation { field: err.field },
+                err.message,
+            );
+        }
+        storage_response(mark_cooked(app_store.as_ref(), &id, date, &cooked).await)
+    } else {
+        api::EmptyResponse::Unauthorized
+    }
+}
+
+async fn api_last_cooked(
+    Extension(app_store): Extension<Arc<dyn storage::APIStore>>,
+    session: storage::UserIdFromSession,
+) -> api::LastCookedResponse {
+    use storage::{UserId, UserIdFromSession::FoundUserId};
+    if let FoundUserId(UserId(id)) = session {
+        app_store.fetch_last_cooked_for_user(&id).await.into()
+    } else {
+        api::Response::Unauthorized
+    }
+}
+
+/// How many of the top entries the stats endpoints return by default.
+const DEFAULT_STATS_LIMIT: usize = 10;
+
+#[derive(Debug, Deserialize)]
+struct StatsParams {
+    /// How many entries to return.
+    limit: Option<usize>,
+}
+
+/// The recipes planned the most. Recipes that have since been deleted are
+/// listed by id.
+async fn recipe_stats(
+    app_store: &dyn storage::APIStore,
+    user_id: &str,
+    limit: usize,
+) -> storage::Result<Vec<api::RecipeCount>> {
+    let all_recipes = user_recipes(app_store, user_id).await?;
+    let mut counts = app_store
+        .fetch_recipe_plan_counts_for_user(user_id)
+        .await?
+        .into_iter()
+        .map(|(recipe_id, count)| api::RecipeCount {
+            title: all_recipes
+                .get(&recipe_id)
+                .map(|r| r.title.clone())
+                .unwrap_or_else(|| recipe_id.clone()),
+            recipe_id,
+            count,
+        })
+        .collect::<Vec<api::RecipeCount>>();
+    counts.sort_by(|l, r| r.count.cmp(&l.count).then(l.title.cmp(&r.title)));
+    counts.truncate(limit);
+    Ok(counts)
+}
+
+/// The ingredients used by the most planned meals. An ingredient counts once
+/// per meal however many steps use it.
+async fn ingredient_stats(
+    app_store: &dyn storage::APIStore,
+    user_id: &str,
+    limit: usize,
+) -> storage::Result<Vec<api::IngredientCount>> {
+    let all_recipes = user_recipes(app_store, user_id).await?;
+    let mut counts: BTreeMap<String, i64> = BTreeMap::new();
+    for (recipe_id, times) in app_store.fetch_recipe_plan_counts_for_user(user_id).await? {
+        let recipe = match all_recipes.get(&recipe_id) {
+            Some(recipe) => recipe,
+            None => continue,
+        };
+        let names = recipe
+            .steps
+            .iter()
+            .flat_map(|s| s.ingredients.iter())
+            .filter(|i| i.recipe_reference().is_none())
+            .map(|i| i.name.to_lowercase())
+            .collect::<BTreeSet<String>>();
+        for name in names {
+            *counts.entry(name).or_insert(0) += times;
+        }
+    }
+    let mut counts = counts
+        .into_iter()
+        .map(|(name, count)| api::IngredientCount { name, count })
+        .collect::<Vec<api::IngredientCount>>();
+    counts.sort_by(|l, r| r.count.cmp(&l.count).then(l.name.cmp(&r.name)));
+    counts.truncate(limit);
+    Ok(counts)
+}
+
+async fn plan_stats(
+    app_store: &dyn storage::APIStore,
+    user_id: &str,
+) -> storage::Result<api::PlanStats> {
+    let settings = app_store.fetch_user_settings(user_id).await?;
+    let meal_counts = app_store.fetch_plan_meal_counts_for_user(user_id).await?;
+    Ok(api::PlanStats::new(
+        &meal_counts,
+        api::week_start(&settings),
+    ))
+}
+
+async fn api_recipe_stats(
+    Extension(app_store): Extension<Arc<dyn storage::APIStore>>,
+    session: storage::UserIdFromSession,
+    Query(params): Query<StatsParams>,
+) -> api::RecipeCountsResponse {
+    use storage::{UserId, UserIdFromSession::FoundUserId};
+    if let FoundUserId(UserId(id)) = session {
+        let limit = params.limit.unwrap_or(DEFAULT_STATS_LIMIT);
+        storage_response(recipe_stats(app_store.as_ref(), &id, limit).await)
+    } else {
+        api::Response::Unauthorized
+    }
+}
+
+async fn api_ingredient_stats(
+    Extension(app_store): Extension<Arc<dyn storage::APIStore>>,
+    session: storage::UserIdFromSession,
+    Query(params): Query<StatsParams>,
+) -> api::IngredientCountsResponse {
+    use storage::{UserId, UserIdFromSession::FoundUserId};
+    if let FoundUserId(UserId(id)) = session {
+        let limit = params.limit.unwrap_or(DEFAULT_STATS_LIMIT);
+        storage_response(ingredient_stats(app_store.as_ref(), &id, limit).await)
+    } else {
+        api::Response::Unauthorized
+    }
+}
+
+async fn api_plan_stats(
+    Extension(app_store): Extension<Arc<dyn storage::APIStore>>,
+    session: storage::UserIdFromSession,
+) -> api::PlanStatsResponse {
+    use storage::{UserId, UserIdFromSession::FoundUserId};
+    if let FoundUserId(UserId(id)) = session {
+        storage_response(plan_stats(app_store.as_ref(), &id).await)
+    } else {
+        api::Response::Unauthorized
+    }
+}
+
+async fn api_plan(
+    Extension(app_store): Extension<Arc<dyn storage::APIStore>>,
+    session: storage::UserIdFromSession,
+) -> api::PlanDataResponse {
+    use storage::{UserId, UserIdFromSession::FoundUserId};
+    if let FoundUserId(UserId(id)) = session {
+        app_store.fetch_latest_meal_plan(&id).await.into()
+    } else {
+        api::Response::Unauthorized
+    }
+}
+
+async fn api_plan_since(
+    Extension(app_store): Extension<Arc<dyn storage::APIStore>>,
+    session: storage::UserIdFromSession,
+    Path(date): Path<chrono::NaiveDate>,
+) -> api::PlanHistoryResponse {
+    use storage::{UserId, UserIdFromSession::FoundUserId};
+    if let FoundUserId(UserId(id)) = session {
+        app_store.fetch_meal_plans_since(&id, date).await.into()
+    } else {
+        api::PlanHistoryResponse::Unauthorized
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct PlanRangeParams {
+    from: NaiveDate,
+    to: NaiveDate,
+}
+
+async fn api_plans_in_range(
+    Extension(app_store): Extension<Arc<dyn storage::APIStore>>,
+    session: storage::UserIdFromSession,
+    Query(params): Query<PlanRangeParams>,
+) -> api::PlanHistoryResponse {
+    use storage::{UserId, UserIdFromSession::FoundUserId};
+    if let FoundUserId(UserId(id)) = session {
+        if params.to < params.from {
+            return api::Response::error_code(
+                api::ErrorCode::Validation {
+                    field: "to".to_owned(),
+                },
+                "The end of the range is before the start",
+            );
+        }
         app_store
-            .save_meal_plan(id.as_str(), &meal_plan, chrono::Local::now().date_naive())
+            .fetch_meal_plans_in_range(&id, params.from, params.to)
+            .await
+            .into()
+    } else {
+        api::PlanHistoryResponse::Unauthorized
+    }
+}
+
+async fn api_all_plans(
+    Extension(app_store): Extension<Arc<dyn storage::APIStore>>,
+    session: storage::UserIdFromSession,
+) -> api::Response<Vec<NaiveDate>> {
+    use storage::{UserId, UserIdFromSession::FoundUserId};
+    if let FoundUserId(UserId(id)) = session {
+        app_store.fetch_all_meal_plans(&id).await.into()
+    } else {
+        api::Response::Unauthorized
+    }
+}
+
+async fn api_repair_plans(
+    Extension(app_store): Extension<Arc<dyn storage::APIStore>>,
+    session: storage::UserIdFromSession,
+) -> api::PlanRepairResponse {
+    use storage::{UserId, UserIdFromSession::FoundUserId};
+    if let FoundUserId(UserId(id)) = session {
+        storage_response(app_store.remove_dangling_plan_recipes(&id).await)
+    } else {
+        api::Response::Unauthorized
+    }
+}
+
+/// The user's recipes keyed by id. Recipes that don't parse are skipped.
+async fn user_recipes(
+    app_store: &dyn storage::APIStore,
+    user_id: &str,
+) -> storage::Result<BTreeMap<String, recipes::Recipe>> {
+    let mut all_recipes = BTreeMap::new();
+    for entry in app_store
+        .get_recipes_for_user(user_id)
+        .await?
+        .unwrap_or_default()
+    {
+        match parse::as_recipe(entry.recipe_text()) {
+            Ok(recipe) => {
+                all_recipes.insert(entry.recipe_id().to_owned(), recipe);
+            }
+            Err(err) => {
+                debug!(recipe_id = entry.recipe_id(), %err, "Skipping unparseable recipe");
+            }
+        }
+    }
+    Ok(all_recipes)
+}
+
+/// Collect today's meals from the most recent plan that isn't in the future.
+/// Plans are made for a shopping trip rather than a single day so that plan
+/// holds the meals for today.
+async fn today_data(
+    app_store: &dyn storage::APIStore,
+    user_id: &str,
+    today: NaiveDate,
+) -> storage::Result<api::TodayData> {
+    let plan_date = app_store
+        .fetch_all_meal_plans(user_id)
+        .await?
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|date| *date <= today)
+        .max();
+    let mut data = api::TodayData::new(today, plan_date);
+    let plan_date = match plan_date {
+        Some(plan_date) => plan_date,
+        None => return Ok(data),
+    };
+    let plan = app_store
+        .fetch_meal_plan_for_date(user_id, plan_date)
+        .await?
+        .unwrap_or_default();
+    let meals = app_store
+        .fetch_plan_meals_for_date(user_id, plan_date)
+        .await?;
+    let freezer_meals = app_store
+        .fetch_plan_freezer_meals_for_date(user_id, plan_date)
+        .await?;
+    let all_recipes = user_recipes(app_store, user_id).await?;
+    let recipe_counts = plan
+        .iter()
+        .filter(|(_, count)| *count > 0)
+        .map(|(id, count)| (id.clone(), *count as usize))
+        .collect();
+    for (recipe_id, count) in plan.into_iter().filter(|(_, count)| *count > 0) {
+        let recipe = match all_recipes.get(&recipe_id) {
+            Some(recipe) => recipe,
+            None => continue,
+        };
+        if let Some((idx, step)) = recipe
+            .steps
+            .iter()
+            .enumerate()
+            .find(|(_, step)| !step.timers.is_empty())
+        {
+            data.timers
+                .extend(step.timers.iter().map(|timer| api::TodayTimer {
+                    recipe_id: recipe_id.clone(),
+                    title: recipe.title.clone(),
+                    step: idx + 1,
+                    label: timer.label.clone(),
+                    seconds: timer.duration.as_secs(),
+                }));
+        }
+        for meal in api::planned_meals_for(&meals, &recipe_id, Some(recipe)) {
+            data.recipes.push(api::TodayRecipe {
+                recipe_id: recipe_id.clone(),
+                title: recipe.title.clone(),
+                count,
+                total_time: recipe.total_time().as_secs(),
+                meal,
+            });
+        }
+    }
+    // The sort is stable so recipes keep the plan order within each meal.
+    data.recipes.sort_by_key(|r| r.meal);
+    if !data.recipes.is_empty() {
+        let mut by_meal: Vec<(recipes::MealType, Vec<&str>)> = Vec::new();
+        for r in data.recipes.iter() {
+            match by_meal.last_mut() {
+                Some((meal, titles)) if *meal == r.meal => titles.push(r.title.as_str()),
+                _ => by_meal.push((r.meal, vec![r.title.as_str()])),
+            }
+        }
+        let meals = by_meal
+            .into_iter()
+            .map(|(meal, titles)| format!("{}: {}", meal, titles.join(", ")))
+            .collect::<Vec<String>>();
+        data.summary = format!("Today's meals: {}.", meals.join(". "));
+    }
+    let (filtered_ingredients, _, extra_items) = app_store
+        .fetch_inventory_for_date(user_id, plan_date)
+        .await?;
+    // This has to match the shopping list the client shows so we use the
+    // same densities and household size. Staples are left out since
+    // whether to show them is up to the client.
+    let settings = app_store.fetch_user_settings(user_id).await?;
+    let shopping_plan = recipes::shopping::ShoppingPlan {
+        recipe_counts,
+        freezer_meals,
+        household_size: api::household_size(&settings),
+        filtered_ingredients: filtered_ingredients.into_iter().collect(),
+        densities: Some(
+            recipes::unit::DensityTable::default()
+                .with_overrides(api::density_overrides(&settings).iter()),
+        ),
+        ..Default::default()
+    };
+    data.shopping_items_remaining = recipes::shopping::shopping_list(&shopping_plan, &all_recipes)
+        .values()
+        .filter(|item| !item.filtered)
+        .count()
+        + extra_items.len();
+    Ok(data)
+}
+
+async fn api_plan_today(
+    Extension(app_store): Extension<Arc<dyn storage::APIStore>>,
+    session: storage::UserIdFromSession,
+) -> api::TodayResponse {
+    use storage::{UserId, UserIdFromSession::FoundUserId};
+    if let FoundUserId(UserId(id)) = session {
+        let today = match user_today(app_store.as_ref(), &id).await {
+            Ok(today) => today,
+            Err(e) => return api::Response::error(500, format!("{:?}", e)),
+        };
+        match today_data(app_store.as_ref(), &id, today).await {
+            Ok(data) => data.into(),
+            Err(e) => api::Response::error(500, format!("{:?}", e)),
+        }
+    } else {
+        api::Response::Unauthorized
+    }
+}
+
+/// The most recipes the plan suggestion endpoint returns.
+const MAX_PLAN_SUGGESTIONS: usize = 10;
+
+/// Everything needed to check plans for `date` against a user's rules.
+struct PlanRuleContext {
+    rules: Vec<api::StoredPlanRule>,
+    /// Recipe id to (title, tags).
+    recipes: BTreeMap<String, (String, BTreeSet<String>)>,
+    /// The recipes in the plans before `date` as far back as the rules need.
+    history: BTreeMap<NaiveDate, Vec<String>>,
+    /// The date each recipe was last cooked.
+    last_cooked: BTreeMap<String, NaiveDate>,
+    /// Recipes that are never suggested.
+    archived: BTreeSet<String>,
+}
+
+impl PlanRuleContext {
+    async fn load(
+        app_store: &dyn storage::APIStore,
+        user_id: &str,
+        date: NaiveDate,
+    ) -> storage::Result<Self> {
+        let rules = app_store.fetch_plan_rules_for_user(user_id).await?;
+        let mut recipes = BTreeMap::new();
+        for entry in app_store
+            .get_recipes_for_user(user_id)
+            .await?
+            .unwrap_or_default()
+        {
+            match parse::as_recipe(entry.recipe_text()) {
+                Ok(recipe) => {
+                    recipes.insert(
+                        entry.recipe_id().to_owned(),
+                        (recipe.title, recipe.metadata.tags),
+                    );
+                }
+                Err(err) => {
+                    debug!(recipe_id = entry.recipe_id(), %err, "Skipping unparseable recipe");
+                }
+            }
+        }
+        let lookback = rules
+            .iter()
+            .map(|r| r.rule.lookback_days())
+            .max()
+            .unwrap_or(0);
+        let since = date - chrono::Duration::days(lookback);
+        let mut history = BTreeMap::new();
+        for plan_date in app_store
+            .fetch_all_meal_plans(user_id)
+            .await?
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|d| *d >= since && *d < date)
+        {
+            let plan = app_store
+                .fetch_meal_plan_for_date(user_id, plan_date)
+                .await?
+                .unwrap_or_default();
+            history.insert(
+                plan_date,
+                plan.into_iter()
+                    .filter(|(_, count)| *count > 0)
+                    .map(|(id, _)| id)
+                    .collect(),
+            );
+        }
+        let last_cooked = app_store.fetch_last_cooked_for_user(user_id).await?;
+        let archived = app_store.fetch_archived_recipes_for_user(user_id).await?;
+        Ok(Self {
+            rules,
+            recipes,
+            history,
+            last_cooked,
+            archived,
+        })
+    }
+
+    fn tags(&self) -> BTreeMap<String, BTreeSet<String>> {
+        self.recipes
+            .iter()
+            .map(|(id, (_, tags))| (id.clone(), tags.clone()))
+            .collect()
+    }
+
+    fn check(&self, date: NaiveDate, plan: &[(String, i32)]) -> Vec<api::RuleViolation> {
+        api::check_plan(&self.rules, date, plan, &self.tags(), &self.history)
+    }
+
+    /// Recipes that can be added to the plan without breaking a rule. Recipes
+    /// that help meet an unmet rule come first and then the ones planned or
+    /// cooked least recently.
+    fn suggest(&self, date: NaiveDate, plan: &[(String, i32)]) -> Vec<api::PlanSuggestion> {
+        let unmet = self
+            .check(date, plan)
+            .into_iter()
+            .filter(|v| v.recipe_id.is_none())
+            .map(|v| v.rule_id)
+            .collect::<BTreeSet<i64>>();
+        let needed = self
+            .rules
+            .iter()
+            .filter(|r| unmet.contains(&r.id))
+            .filter_map(|r| match &r.rule {
+                api::PlanRule::AtLeast { tag, .. } => Some((tag.to_lowercase(), r.rule.describe())),
+                _ => None,
+            })
+            .collect::<Vec<(String, String)>>();
+        let planned = plan
+            .iter()
+            .filter(|(_, count)| *count > 0)
+            .map(|(id, _)| id.as_str())
+            .collect::<BTreeSet<&str>>();
+        let last_planned = |id: &str| {
+            let planned = self
+                .history
+                .iter()
+                .rev()
+                .find(|(_, ids)| ids.iter().any(|i| i == id))
+                .map(|(date, _)| *date);
+            planned.max(self.last_cooked.get(id).copied())
+        };
+        let mut suggestions = self
+            .recipes
+            .iter()
+            .filter(|(id, _)| !planned.contains(id.as_str()) && !self.archived.contains(*id))
+            .filter(|(id, (_, tags))| {
+                self.rules
+                    .iter()
+                    .all(|r| r.rule.allows(date, id, tags, &self.history))
+            })
+            .map(|(id, (title, tags))| {
+                let reason = needed
+                    .iter()
+                    .find(|(tag, _)| tags.contains(tag))
+                    .map(|(_, rule)| rule.clone());
+                (reason.is_none(), last_planned(id), id, title, reason)
+            })
+            .collect::<Vec<_>>();
+        suggestions.sort_by(|a, b| (a.0, a.1, a.2).cmp(&(b.0, b.1, b.2)));
+        suggestions
+            .into_iter()
+            .take(MAX_PLAN_SUGGESTIONS)
+            .map(|(_, _, id, title, reason)| api::PlanSuggestion {
+                recipe_id: id.clone(),
+                title: title.clone(),
+                reason,
+            })
+            .collect()
+    }
+}
+
+async fn api_plan_rules(
+    Extension(app_store): Extension<Arc<dyn storage::APIStore>>,
+    session: storage::UserIdFromSession,
+) -> api::PlanRulesResponse {
+    use storage::{UserId, UserIdFromSession::FoundUserId};
+    if let FoundUserId(UserId(id)) = session {
+        app_store.fetch_plan_rules_for_user(&id).await.into()
+    } else {
+        api::Response::Unauthorized
+    }
+}
+
+async fn api_save_plan_rule(
+    Extension(app_store): Extension<Arc<dyn storage::APIStore>>,
+    session: storage::UserIdFromSession,
+    Json(rule): Json<api::PlanRule>,
+) -> api::PlanRuleResponse {
+    use storage::{UserId, UserIdFromSession::FoundUserId};
+    if let FoundUserId(UserId(id)) = session {
+        if let Err(err) = rule.validate() {
+            return api::Response::error_code(
+                api::ErrorCode::Validation { field: err.field },
+                err.message,
+            );
+        }
+        storage_response(app_store.save_plan_rule_for_user(&id, rule).await)
+    } else {
+        api::Response::Unauthorized
+    }
+}
+
+async fn api_freezer(
+    Extension(app_store): Extension<Arc<dyn storage::APIStore>>,
+    session: storage::UserIdFromSession,
+) -> api::FreezerResponse {
+    use storage::{UserId, UserIdFromSession::FoundUserId};
+    if let FoundUserId(UserId(id)) = session {
+        app_store.fetch_freezer_items_for_user(&id).await.into()
+    } else {
+        api::Response::Unauthorized
+    }
+}
+
+async fn api_add_freezer_item(
+    Extension(app_store): Extension<Arc<dyn storage::APIStore>>,
+    session: storage::UserIdFromSession,
+    Json(item): Json<api::NewFreezerItem>,
+) -> api::Response<api::FreezerItem> {
+    use storage::{UserId, UserIdFromSession::FoundUserId};
+    if let FoundUserId(UserId(id)) = session {
+        if let Err(err) = item.validate() {
+            return api::Response::error_code(
+                api::ErrorCode::Validation { field: err.field },
+                err.message,
+            );
+        }
+        storage_response(app_store.add_freezer_item_for_user(&id, &item).await)
+    } else {
+        api::Response::Unauthorized
+    }
+}
+
+async fn api_take_freezer_portions(
+    Extension(app_store): Extension<Arc<dyn storage::APIStore>>,
+    session: storage::UserIdFromSession,
+    Path(item_id): Path<i64>,
+    Json(portions): Json<i32>,
+) -> api::FreezerItemResponse {
+    use storage::{UserId, UserIdFromSession::FoundUserId};
+    if let FoundUserId(UserId(id)) = session {
+        if portions < 1 {
+            return api::Response::error_code(
+                api::ErrorCode::Validation {
+                    field: "portions".to_owned(),
+                },
+                "Take at least one portion",
+            );
+        }
+        match app_store
+            .take_freezer_portions_for_user(&id, item_id, portions)
+            .await
+        {
+            Err(storage::Error::NoRecords) => api::Response::NotFound,
+            result => storage_response(result),
+        }
+    } else {
+        api::Response::Unauthorized
+    }
+}
+
+async fn api_delete_freezer_item(
+    Extension(app_store): Extension<Arc<dyn storage::APIStore>>,
+    session: storage::UserIdFromSession,
+    Path(item_id): Path<i64>,
+) -> api::EmptyResponse {
+    use storage::{UserId, UserIdFromSession::FoundUserId};
+    if let FoundUserId(UserId(id)) = session {
+        storage_response(app_store.delete_freezer_item_for_user(&id, item_id).await)
+    } else {
+        api::Response::Unauthorized
+    }
+}
+
+async fn api_delete_plan_rule(
+    Extension(app_store): Extension<Arc<dyn storage::APIStore>>,
+    session: storage::UserIdFromSession,
+    Path(rule_id): Path<i64>,
+) -> api::EmptyResponse {
+    use storage::{UserId, UserIdFromSession::FoundUserId};
+    if let FoundUserId(UserId(id)) = session {
+        storage_response(app_store.delete_plan_rule_for_user(&id, rule_id).await)
+    } else {
+        api::Response::Unauthorized
+    }
+}
+
+/// Check a plan against the user's rules. The plan is sent in the request so
+/// that unsaved changes can be checked.
+async fn api_check_plan(
+    Extension(app_store): Extension<Arc<dyn storage::APIStore>>,
+    session: storage::UserIdFromSession,
+    Path(date): Path<chrono::NaiveDate>,
+    Json(plan): Json<Vec<(String, i32)>>,
+) -> api::RuleViolationsResponse {
+    use storage::{UserId, UserIdFromSession::FoundUserId};
+    if let FoundUserId(UserId(id)) = session {
+        PlanRuleContext::load(app_store.as_ref(), &id, date)
             .await
+            .map(|ctx| ctx.check(date, &plan))
             .into()
+    } else {
+        api::Response::Unauthorized
+    }
+}
+
+/// Suggest recipes to add to the saved plan for a date that follow the
+/// user's rules.
+async fn api_plan_suggestions(
+    Extension(app_store): Extension<Arc<dyn storage::APIStore>>,
+    session: storage::UserIdFromSession,
+    Path(date): Path<chrono::NaiveDate>,
+) -> api::PlanSuggestionsResponse {
+    use storage::{UserId, UserIdFromSession::FoundUserId};
+    if let FoundUserId(UserId(id)) = session {
+        let suggestions = async {
+            let ctx = PlanRuleContext::load(app_store.as_ref(), &id, date).await?;
+            let plan = app_store
+                .fetch_meal_plan_for_date(&id, date)
+                .await?
+                .unwrap_or_default();
+            Ok::<_, storage::Error>(ctx.suggest(date, &plan))
+        };
+        suggestions.await.into()
+    } else {
+        api::Response::Unauthorized
+    }
+}
+
+async fn api_delete_plan_for_date(
+    Extension(app_store): Extension<Arc<dyn storage::APIStore>>,
+    session: storage::UserIdFromSession,
+    Path(date): Path<chrono::NaiveDate>,
+) -> api::EmptyResponse {
+    use storage::{UserId, UserIdFromSession::FoundUserId};
+    if let FoundUserId(UserId(id)) = session {
+        storage_response(app_store.delete_meal_plan_for_date(id.as_str(), date).await)
+    } else {
+        api::EmptyResponse::Unauthorized
+    }
+}
+
+async fn api_save_plan_for_date(
+    Extension(app_store): Extension<Arc<dyn storage::APIStore>>,
+    session: storage::UserIdFromSession,
+    Path(date): Path<chrono::NaiveDate>,
+    Json(meal_plan): Json<Vec<(String, i32)>>,
+) -> api::EmptyResponse {
+    use storage::{UserId, UserIdFromSession::FoundUserId};
+    if let FoundUserId(UserId(id)) = session {
+        save_plan(app_store.as_ref(), id.as_str(), meal_plan, date).await
+    } else {
+        api::EmptyResponse::Unauthorized
+    }
+}
+
+/// Check the recipes in a plan against the user's recipes. Recipes that
+/// don't exist are an error if they are planned but are otherwise dropped.
+fn check_plan_recipes(
+    known: &BTreeSet<String>,
+    meal_plan: Vec<(String, i32)>,
+) -> Result<Vec<(String, i32)>, Vec<api::ValidationError>> {
+    let mut errors = Vec::new();
+    let mut checked = Vec::with_capacity(meal_plan.len());
+    for (recipe_id, count) in meal_plan {
+        if known.contains(&recipe_id) {
+            checked.push((recipe_id, count));
+        } else if count > 0 {
+            errors.push(api::ValidationError::new(
+                &recipe_id,
+                "recipe_id",
+                "No such recipe",
+            ));
+        }
+    }
+    if errors.is_empty() {
+        Ok(checked)
+    } else {
+        Err(errors)
+    }
+}
+
+fn busy_response<T>() -> api::Response<T> {
+    api::Response::error_code(
+        api::ErrorCode::Busy,
+        "The database is busy with another change. Try again.",
+    )
+}
+
+/// Convert the result of a write into a response. Writes that gave up
+/// waiting on another connection's lock are reported as busy so the client
+/// knows it can retry.
+fn storage_response<T>(result: storage::Result<T>) -> api::Response<T> {
+    match result {
+        Err(storage::Error::Busy) => busy_response(),
+        result => result.into(),
+    }
+}
+
+async fn save_plan(
+    app_store: &dyn storage::APIStore,
+    user_id: &str,
+    meal_plan: Vec<(String, i32)>,
+    date: chrono::NaiveDate,
+) -> api::EmptyResponse {
+    let known = match app_store.fetch_recipe_ids_for_user(user_id).await {
+        Ok(known) => known,
+        Err(err) => return api::Response::error(500, format!("{:?}", err)),
+    };
+    // Clients send every recipe they know about including ones with a zero
+    // count so a recipe deleted elsewhere shouldn't fail the save.
+    let meal_plan = match check_plan_recipes(&known, meal_plan) {
+        Ok(meal_plan) => meal_plan,
+        Err(errors) => return api::EmptyResponse::Invalid(errors),
+    };
+    storage_response(app_store.save_meal_plan(user_id, &meal_plan, date).await)
+}
+
+async fn api_update_plan_for_date(
+    Extension(app_store): Extension<Arc<dyn storage::APIStore>>,
+    session: storage::UserIdFromSession,
+    Path(date): Path<chrono::NaiveDate>,
+    Json(changes): Json<Vec<api::PlanChange>>,
+) -> api::PlanDataResponse {
+    use storage::{UserId, UserIdFromSession::FoundUserId};
+    if let FoundUserId(UserId(id)) = session {
+        if let Some(err) = changes.iter().find_map(|c| c.validate().err()) {
+            return api::Response::error_code(
+                api::ErrorCode::Validation { field: err.field },
+                err.message,
+            );
+        }
+        let known = match app_store.fetch_recipe_ids_for_user(id.as_str()).await {
+            Ok(known) => known,
+            Err(err) => return api::Response::error(500, format!("{:?}", err)),
+        };
+        let errors: Vec<api::ValidationError> = changes
+            .iter()
+            .filter(|c| !known.contains(c.recipe_id()))
+            .filter(|c| !matches!(c, api::PlanChange::SetCount(_, 0)))
+            .map(|c| api::ValidationError::new(c.recipe_id(), "recipe_id", "No such recipe"))
+            .collect();
+        if !errors.is_empty() {
+            return api::Response::Invalid(errors);
+        }
+        storage_response(
+            app_store
+                .apply_plan_changes(id.as_str(), date, &changes)
+                .await,
+        )
+    } else {
+        api::Response::Unauthorized
+    }
+}
+
+async fn api_save_plan(
+    Extension(app_store): Extension<Arc<dyn storage::APIStore>>,
+    session: storage::UserIdFromSession,
+    Json(meal_plan): Json<Vec<(String, i32)>>,
+) -> api::EmptyResponse {
+    use storage::{UserId, UserIdFromSession::FoundUserId};
+    if let FoundUserId(UserId(id)) = session {
+        let today = match user_today(app_store.as_ref(), &id).await {
+            Ok(today) => today,
+            Err(e) => return api::Response::error(500, format!("{:?}", e)),
+        };
+        save_plan(app_store.as_ref(), id.as_str(), meal_plan, today).await
     } else {
         api::EmptyResponse::Unauthorized
     }
 }
 
 async fn api_inventory_v2(
-    Extension(app_store): Extension<Arc<storage::SqliteStore>>,
+    Extension(app_store): Extension<Arc<dyn storage::APIStore>>,
     session: storage::UserIdFromSession,
 ) -> api::InventoryResponse {
     use storage::{UserId, UserIdFromSession::FoundUserId};
     if let FoundUserId(UserId(id)) = session {
         app_store
-            .fetch_latest_inventory_data(id)
+            .fetch_latest_inventory_data(&id)
             .await
             .map(|d| {
                 let data: api::InventoryData = d.into();
@@ -328,14 +2514,14 @@ async fn api_inventory_v2(
 }
 
 async fn api_inventory_for_date(
-    Extension(app_store): Extension<Arc<storage::SqliteStore>>,
+    Extension(app_store): Extension<Arc<dyn storage::APIStore>>,
     session: storage::UserIdFromSession,
     Path(date): Path<chrono::NaiveDate>,
 ) -> api::InventoryResponse {
     use storage::{UserId, UserIdFromSession::FoundUserId};
     if let FoundUserId(UserId(id)) = session {
         app_store
-            .fetch_inventory_for_date(id, date)
+            .fetch_inventory_for_date(&id, date)
             .await
             .map(|d| {
                 let data: api::InventoryData = d.into();
@@ -347,66 +2533,153 @@ async fn api_inventory_for_date(
     }
 }
 
-async fn api_inventory(
-    Extension(app_store): Extension<Arc<storage::SqliteStore>>,
+async fn api_inventory(
+    Extension(app_store): Extension<Arc<dyn storage::APIStore>>,
+    session: storage::UserIdFromSession,
+) -> api::Response<(Vec<IngredientKey>, Vec<(IngredientKey, String)>)> {
+    use storage::{UserId, UserIdFromSession::FoundUserId};
+    if let FoundUserId(UserId(id)) = session {
+        app_store
+            .fetch_latest_inventory_data(&id)
+            .await
+            .map(|(filtered, modified, _)| (filtered, modified))
+            .into()
+    } else {
+        api::Response::Unauthorized
+    }
+}
+
+async fn api_save_inventory_for_date(
+    Extension(app_store): Extension<Arc<dyn storage::APIStore>>,
+    session: storage::UserIdFromSession,
+    Path(date): Path<NaiveDate>,
+    Json((filtered_ingredients, modified_amts, extra_items)): Json<(
+        Vec<IngredientKey>,
+        Vec<(IngredientKey, String)>,
+        Vec<(String, String)>,
+    )>,
+) -> api::EmptyResponse {
+    use storage::{UserId, UserIdFromSession::FoundUserId};
+    if let FoundUserId(UserId(id)) = session {
+        let filtered_ingredients = filtered_ingredients.into_iter().collect();
+        let modified_amts = modified_amts.into_iter().collect();
+        storage_response(
+            app_store
+                .save_inventory_data_for_date(
+                    &id,
+                    &date,
+                    filtered_ingredients,
+                    modified_amts,
+                    extra_items,
+                )
+                .await,
+        )
+    } else {
+        api::EmptyResponse::Unauthorized
+    }
+}
+
+async fn api_update_inventory_for_date(
+    Extension(app_store): Extension<Arc<dyn storage::APIStore>>,
+    session: storage::UserIdFromSession,
+    Path(date): Path<NaiveDate>,
+    Json(ops): Json<Vec<api::InventoryOp>>,
+) -> api::InventoryResponse {
+    use storage::{UserId, UserIdFromSession::FoundUserId};
+    if let FoundUserId(UserId(id)) = session {
+        storage_response(
+            app_store
+                .apply_inventory_ops_for_date(&id, &date, &ops)
+                .await,
+        )
+    } else {
+        api::Response::Unauthorized
+    }
+}
+
+async fn api_inventory_history(
+    Extension(app_store): Extension<Arc<dyn storage::APIStore>>,
     session: storage::UserIdFromSession,
-) -> api::Response<(Vec<IngredientKey>, Vec<(IngredientKey, String)>)> {
+    Path(date): Path<NaiveDate>,
+) -> api::InventoryHistoryResponse {
     use storage::{UserId, UserIdFromSession::FoundUserId};
     if let FoundUserId(UserId(id)) = session {
         app_store
-            .fetch_latest_inventory_data(id)
+            .fetch_inventory_history_for_date(&id, date)
             .await
-            .map(|(filtered, modified, _)| (filtered, modified))
             .into()
     } else {
         api::Response::Unauthorized
     }
 }
 
-async fn api_save_inventory_for_date(
-    Extension(app_store): Extension<Arc<storage::SqliteStore>>,
+#[derive(Debug, Deserialize)]
+struct InventoryDiffParams {
+    from: NaiveDate,
+    to: NaiveDate,
+}
+
+/// Compare the inventory for two plans. Comparing a plan with itself compares
+/// the inventory as it was first saved with how it is now which shows what was
+/// actually bought versus what was planned.
+async fn inventory_diff(
+    app_store: &dyn storage::APIStore,
+    user_id: &str,
+    from: NaiveDate,
+    to: NaiveDate,
+) -> storage::Result<api::InventoryDiff> {
+    let from_inventory: api::InventoryData = if from == to {
+        app_store
+            .fetch_inventory_history_for_date(user_id, from)
+            .await?
+            .into_iter()
+            .next()
+            .map(|snapshot| snapshot.inventory)
+            .unwrap_or_default()
+    } else {
+        app_store
+            .fetch_inventory_for_date(user_id, from)
+            .await?
+            .into()
+    };
+    let to_inventory: api::InventoryData = app_store
+        .fetch_inventory_for_date(user_id, to)
+        .await?
+        .into();
+    Ok(api::InventoryDiff::new(&from_inventory, &to_inventory))
+}
+
+async fn api_inventory_diff(
+    Extension(app_store): Extension<Arc<dyn storage::APIStore>>,
     session: storage::UserIdFromSession,
-    Path(date): Path<NaiveDate>,
-    Json((filtered_ingredients, modified_amts, extra_items)): Json<(
-        Vec<IngredientKey>,
-        Vec<(IngredientKey, String)>,
-        Vec<(String, String)>,
-    )>,
-) -> api::EmptyResponse {
+    Query(params): Query<InventoryDiffParams>,
+) -> api::InventoryDiffResponse {
     use storage::{UserId, UserIdFromSession::FoundUserId};
     if let FoundUserId(UserId(id)) = session {
-        let filtered_ingredients = filtered_ingredients.into_iter().collect();
-        let modified_amts = modified_amts.into_iter().collect();
-        app_store
-            .save_inventory_data_for_date(
-                id,
-                &date,
-                filtered_ingredients,
-                modified_amts,
-                extra_items,
-            )
+        inventory_diff(app_store.as_ref(), &id, params.from, params.to)
             .await
             .into()
     } else {
-        api::EmptyResponse::Unauthorized
+        api::Response::Unauthorized
     }
 }
 
 async fn save_inventory_data(
-    app_store: Arc<storage::SqliteStore>,
+    app_store: Arc<dyn storage::APIStore>,
     id: String,
     filtered_ingredients: BTreeSet<IngredientKey>,
     modified_amts: BTreeMap<IngredientKey, String>,
     extra_items: Vec<(String, String)>,
 ) -> api::EmptyResponse {
-    app_store
-        .save_inventory_data(id, filtered_ingredients, modified_amts, extra_items)
-        .await
-        .into()
+    storage_response(
+        app_store
+            .save_inventory_data(&id, filtered_ingredients, modified_amts, extra_items)
+            .await,
+    )
 }
 
 async fn api_save_inventory_v2(
-    Extension(app_store): Extension<Arc<storage::SqliteStore>>,
+    Extension(app_store): Extension<Arc<dyn storage::APIStore>>,
     session: storage::UserIdFromSession,
     Json((filtered_ingredients, modified_amts, extra_items)): Json<(
         Vec<IngredientKey>,
@@ -433,7 +2706,7 @@ async fn api_save_inventory_v2(
 }
 
 async fn api_save_inventory(
-    Extension(app_store): Extension<Arc<storage::SqliteStore>>,
+    Extension(app_store): Extension<Arc<dyn storage::APIStore>>,
     session: storage::UserIdFromSession,
     Json((filtered_ingredients, modified_amts)): Json<(
         Vec<IngredientKey>,
@@ -458,40 +2731,268 @@ async fn api_save_inventory(
     }
 }
 
-async fn api_user_account(session: storage::UserIdFromSession) -> api::AccountResponse {
+async fn api_user_account(
+    Extension(app_store): Extension<Arc<dyn storage::APIStore>>,
+    session: storage::UserIdFromSession,
+) -> api::AccountResponse {
+    use storage::{UserId, UserIdFromSession::FoundUserId};
+    if let FoundUserId(UserId(user_id)) = session {
+        match samples::account_is_empty(app_store.as_ref(), &user_id).await {
+            Ok(empty) => api::AccountResponse::from(api::UserData { user_id, empty }),
+            Err(err) => api::Response::error(500, format!("{:?}", err)),
+        }
+    } else {
+        api::Response::Unauthorized
+    }
+}
+
+/// Add the bundled sample recipes to the account returning how many were
+/// added.
+async fn api_add_samples(
+    Extension(app_store): Extension<Arc<dyn storage::APIStore>>,
+    session: storage::UserIdFromSession,
+) -> api::Response<usize> {
+    use storage::{UserId, UserIdFromSession::FoundUserId};
+    if let FoundUserId(UserId(user_id)) = session {
+        storage_response(samples::add_sample_recipes(app_store.as_ref(), &user_id).await)
+    } else {
+        api::Response::Unauthorized
+    }
+}
+
+/// Apply the bundled default categories and staples to the account without
+/// touching anything the user already has.
+async fn api_apply_defaults(
+    Extension(app_store): Extension<Arc<dyn storage::APIStore>>,
+    session: storage::UserIdFromSession,
+) -> api::DefaultsResponse {
+    use storage::{UserId, UserIdFromSession::FoundUserId};
+    if let FoundUserId(UserId(user_id)) = session {
+        storage_response(defaults::apply_defaults(app_store.as_ref(), &user_id).await)
+    } else {
+        api::Response::Unauthorized
+    }
+}
+
+async fn api_substitutions(
+    Extension(app_store): Extension<Arc<dyn storage::APIStore>>,
+    session: storage::UserIdFromSession,
+) -> api::SubstitutionsResponse {
     use storage::{UserId, UserIdFromSession::FoundUserId};
     if let FoundUserId(UserId(user_id)) = session {
-        api::AccountResponse::from(api::UserData { user_id })
+        storage_response(defaults::substitutions_for_user(app_store.as_ref(), &user_id).await)
     } else {
         api::Response::Unauthorized
     }
 }
 
+/// The most bytes of any one field of a client error report we will log.
+const MAX_CLIENT_ERROR_FIELD_BYTES: usize = 4096;
+
+fn truncate_field(mut field: String) -> String {
+    if field.len() > MAX_CLIENT_ERROR_FIELD_BYTES {
+        let mut end = MAX_CLIENT_ERROR_FIELD_BYTES;
+        while !field.is_char_boundary(end) {
+            end -= 1;
+        }
+        field.truncate(end);
+    }
+    field
+}
+
+/// The most client error reports logged from one address each minute.
+const MAX_CLIENT_ERRORS_PER_MINUTE: u32 = 30;
+
+/// Log a failure reported by a web client. These are accepted without a
+/// login since the client may fail before anyone logs in. Every field is
+/// logged with Debug formatting so newlines in a report can't forge log
+/// lines.
+async fn api_client_error(
+    Extension(limit): Extension<Arc<rate_limit::RateLimit>>,
+    connection: Option<ConnectInfo<SocketAddr>>,
+    session: storage::UserIdFromSession,
+    Json(report): Json<api::ClientError>,
+) -> api::EmptyResponse {
+    use storage::{UserId, UserIdFromSession::FoundUserId};
+    // Requests that didn't come over a socket, like the ones in tests, share
+    // one count.
+    let addr = connection
+        .map(|ConnectInfo(addr)| addr.ip())
+        .unwrap_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED));
+    if !limit.check(addr, std::time::Instant::now()) {
+        debug!(%addr, "Dropping client error report over the limit");
+        return api::EmptyResponse::error_code(
+            api::ErrorCode::TooManyRequests,
+            "Too many error reports. Try again later.",
+        );
+    }
+    let user = match session {
+        FoundUserId(UserId(id)) => Some(id),
+        _ => None,
+    };
+    error!(
+        target: "client",
+        user = ?user,
+        kind = ?report.kind,
+        message = ?truncate_field(report.message),
+        location = ?report.location.map(truncate_field),
+        url = ?truncate_field(report.url),
+        user_agent = ?truncate_field(report.user_agent),
+        version = ?truncate_field(report.version),
+        "Client error"
+    );
+    api::EmptyResponse::success(())
+}
+
+async fn api_account_export(
+    Extension(app_store): Extension<Arc<dyn storage::APIStore>>,
+    session: storage::UserIdFromSession,
+) -> Response {
+    use storage::{UserId, UserIdFromSession::FoundUserId};
+    let user_id = match session {
+        FoundUserId(UserId(user_id)) => user_id,
+        _ => return api::EmptyResponse::Unauthorized.into_response(),
+    };
+    let exported_at = chrono::Utc::now();
+    let disposition = format!(
+        r#"attachment; filename="kitchen-{}-{}.json""#,
+        user_id,
+        exported_at.format("%Y-%m-%d")
+    );
+    let body = StreamBody::new(export::account_export(app_store, user_id, exported_at));
+    (
+        [
+            (header::CONTENT_TYPE, "application/json".to_owned()),
+            (header::CONTENT_DISPOSITION, disposition),
+        ],
+        body,
+    )
+        .into_response()
+}
+
+async fn api_plan_history_csv(
+    Extension(app_store): Extension<Arc<dyn storage::APIStore>>,
+    session: storage::UserIdFromSession,
+) -> Response {
+    use storage::{UserId, UserIdFromSession::FoundUserId};
+    let user_id = match session {
+        FoundUserId(UserId(user_id)) => user_id,
+        _ => return api::EmptyResponse::Unauthorized.into_response(),
+    };
+    let disposition = format!(r#"attachment; filename="kitchen-{}-plans.csv""#, user_id);
+    let body = StreamBody::new(export::plan_history_csv(app_store, user_id));
+    (
+        [
+            (header::CONTENT_TYPE, "text/csv".to_owned()),
+            (header::CONTENT_DISPOSITION, disposition),
+        ],
+        body,
+    )
+        .into_response()
+}
+
+/// The user's data version. The ETag is the version so a client that sends
+/// the one it has in `If-None-Match` gets a 304 when nothing has changed.
+async fn api_data_version(
+    Extension(app_store): Extension<Arc<dyn storage::APIStore>>,
+    session: storage::UserIdFromSession,
+    headers: HeaderMap,
+) -> Response {
+    use storage::{UserId, UserIdFromSession::FoundUserId};
+    let user_id = match session {
+        FoundUserId(UserId(user_id)) => user_id,
+        _ => return api::DataVersionResponse::Unauthorized.into_response(),
+    };
+    let version = match app_store.fetch_data_version(&user_id).await {
+        Ok(version) => version,
+        Err(e) => return api::DataVersionResponse::from(Err(e)).into_response(),
+    };
+    let etag = format!(r#""{}""#, version);
+    let unchanged = headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.split(',').any(|tag| tag.trim() == etag))
+        .unwrap_or(false);
+    // Versions are per user so shared caches must not keep them.
+    let headers = [
+        (header::ETAG, etag),
+        (header::CACHE_CONTROL, "private, no-cache".to_owned()),
+    ];
+    if unchanged {
+        (StatusCode::NOT_MODIFIED, headers).into_response()
+    } else {
+        (headers, api::DataVersionResponse::success(version)).into_response()
+    }
+}
+
 async fn api_staples(
-    Extension(app_store): Extension<Arc<storage::SqliteStore>>,
+    Extension(app_store): Extension<Arc<dyn storage::APIStore>>,
     session: storage::UserIdFromSession,
 ) -> api::Response<Option<String>> {
     use storage::{UserId, UserIdFromSession::FoundUserId};
     if let FoundUserId(UserId(user_id)) = session {
-        app_store.fetch_staples(user_id).await.into()
+        app_store.fetch_staples(&user_id).await.into()
     } else {
         api::Response::Unauthorized
     }
 }
 
 async fn api_save_staples(
-    Extension(app_store): Extension<Arc<storage::SqliteStore>>,
+    Extension(app_store): Extension<Arc<dyn storage::APIStore>>,
     session: storage::UserIdFromSession,
     Json(content): Json<String>,
 ) -> api::Response<()> {
     use storage::{UserId, UserIdFromSession::FoundUserId};
     if let FoundUserId(UserId(user_id)) = session {
-        app_store.save_staples(user_id, content).await.into()
+        storage_response(app_store.save_staples(&user_id, &content).await)
+    } else {
+        api::EmptyResponse::Unauthorized
+    }
+}
+
+async fn api_user_settings(
+    Extension(app_store): Extension<Arc<dyn storage::APIStore>>,
+    session: storage::UserIdFromSession,
+) -> api::UserSettingsResponse {
+    use storage::{UserId, UserIdFromSession::FoundUserId};
+    if let FoundUserId(UserId(user_id)) = session {
+        app_store.fetch_user_settings(&user_id).await.into()
+    } else {
+        api::Response::Unauthorized
+    }
+}
+
+async fn api_save_user_settings(
+    Extension(app_store): Extension<Arc<dyn storage::APIStore>>,
+    session: storage::UserIdFromSession,
+    Json(settings): Json<BTreeMap<String, String>>,
+) -> api::EmptyResponse {
+    use storage::{UserId, UserIdFromSession::FoundUserId};
+    if let FoundUserId(UserId(user_id)) = session {
+        storage_response(app_store.save_user_settings(&user_id, &settings).await)
     } else {
         api::EmptyResponse::Unauthorized
     }
 }
 
+/// The users allowed to see operator information like the instance stats.
+#[derive(Clone, Debug, Default)]
+pub struct Admins(pub BTreeSet<String>);
+
+async fn api_admin_stats(
+    Extension(app_store): Extension<Arc<storage::SqliteStore>>,
+    Extension(admins): Extension<Arc<Admins>>,
+    session: storage::UserIdFromSession,
+) -> api::InstanceStatsResponse {
+    use storage::{UserId, UserIdFromSession::FoundUserId};
+    match session {
+        FoundUserId(UserId(user_id)) if admins.0.contains(&user_id) => {
+            app_store.instance_stats().await.into()
+        }
+        _ => api::Response::Unauthorized,
+    }
+}
+
 fn mk_v1_routes() -> Router {
     Router::new()
         .route("/recipes", get(api_recipes).post(api_save_recipes))
@@ -504,17 +3005,49 @@ fn mk_v1_routes() -> Router {
         .route("/inventory", get(api_inventory).post(api_save_inventory))
         .route("/categories", get(api_categories).post(api_save_categories))
         // All the routes above require a UserId.
-        .route("/auth", get(auth::handler).post(auth::handler))
+        .route(
+            "/auth",
+            get(auth::handler::<storage::SqliteStore>).post(auth::handler::<storage::SqliteStore>),
+        )
 }
 
 fn mk_v2_routes() -> Router {
     Router::new()
         .route("/recipes", get(api_recipes).post(api_save_recipes))
+        .route("/recipes/index", get(api_recipe_index))
+        .route("/recipes/parsed", get(api_recipes_parsed))
+        .route("/recipes/import/cooklang", post(api_import_cooklang))
+        .route("/recipes/import/ocr", post(api_ocr_recipe))
+        .route("/images", post(api_upload_image))
+        .route("/images/:name", get(api_image))
+        .route("/recipes/trash", get(api_recipe_trash))
+        .route("/recipes/last_cooked", get(api_last_cooked))
+        .route("/stats/recipes", get(api_recipe_stats))
+        .route("/stats/ingredients", get(api_ingredient_stats))
+        .route("/stats/plans", get(api_plan_stats))
+        .route("/recipes/archived", get(api_archived_recipes))
+        .route("/recipes/bulk/delete", post(api_bulk_delete))
+        .route("/recipes/bulk/archive", post(api_bulk_archive))
+        .route("/recipes/bulk/tags", post(api_bulk_tags))
+        .route("/recipes/bulk/category", post(api_bulk_category))
+        .route("/recipes/trash/:recipe_id", delete(api_recipe_purge))
+        .route(
+            "/recipes/trash/:recipe_id/restore",
+            post(api_recipe_restore),
+        )
         // recipe entry api path route
         .route(
             "/recipe/:recipe_id",
             get(api_recipe_entry).delete(api_recipe_delete),
         )
+        .route(
+            "/recipe/:recipe_id/archive",
+            post(api_archive_recipe).delete(api_unarchive_recipe),
+        )
+        .route("/recipe/:recipe_id/parsed", get(api_recipe_parsed))
+        .route("/recipe/:recipe_id/cooklang", get(api_recipe_cooklang))
+        .route("/recipe/:recipe_id/steps/:step", get(api_recipe_step))
+        .route("/recipe/:recipe_id/prep", get(api_recipe_prep))
         // mealplan api path routes
         .route("/plan", get(api_plan).post(api_save_plan))
         .route("/plan/since/:date", get(api_plan_since))
@@ -522,9 +3055,41 @@ fn mk_v2_routes() -> Router {
             "/plan/at/:date",
             get(api_plan_for_date)
                 .post(api_save_plan_for_date)
+                .patch(api_update_plan_for_date)
                 .delete(api_delete_plan_for_date),
         )
         .route("/plan/all", get(api_all_plans))
+        .route("/plan/range", get(api_plans_in_range))
+        .route("/plan/week", get(api_plan_this_week))
+        .route(
+            "/plan/week/:date/share",
+            get(api_plan_week_share)
+                .post(api_share_plan_week)
+                .delete(api_unshare_plan_week),
+        )
+        .route("/shared/plan/:token", get(api_shared_plan))
+        .route("/plan/repair", post(api_repair_plans))
+        .route("/plan/today", get(api_plan_today))
+        .route("/plan/history.csv", get(api_plan_history_csv))
+        .route("/plan/rules", get(api_plan_rules).post(api_save_plan_rule))
+        .route("/plan/rules/:rule_id", delete(api_delete_plan_rule))
+        .route("/plan/at/:date/check", post(api_check_plan))
+        .route("/plan/at/:date/suggestions", get(api_plan_suggestions))
+        .route(
+            "/plan/at/:date/meals",
+            get(api_plan_meals_for_date).post(api_save_plan_meals_for_date),
+        )
+        .route(
+            "/plan/at/:date/freezer",
+            get(api_plan_freezer_meals_for_date).post(api_save_plan_freezer_meals_for_date),
+        )
+        .route(
+            "/plan/at/:date/cooked",
+            get(api_cooked_for_date).post(api_mark_cooked),
+        )
+        .route("/freezer", get(api_freezer).post(api_add_freezer_item))
+        .route("/freezer/:item_id", delete(api_delete_freezer_item))
+        .route("/freezer/:item_id/take", post(api_take_freezer_portions))
         .route(
             "/inventory",
             get(api_inventory_v2).post(api_save_inventory_v2),
@@ -533,20 +3098,181 @@ fn mk_v2_routes() -> Router {
             "/inventory/at/:date",
             get(api_inventory_for_date).post(api_save_inventory_for_date),
         )
+        .route(
+            "/inventory/at/:date/ops",
+            post(api_update_inventory_for_date),
+        )
+        .route("/inventory/at/:date/history", get(api_inventory_history))
+        .route("/inventory/diff", get(api_inventory_diff))
         // TODO(jwall): This is now deprecated but will still work
         .route("/categories", get(api_categories).post(api_save_categories))
         .route(
             "/category_map",
             get(api_category_mappings).post(api_save_category_mappings),
         )
+        .route("/category_map/suggestions", get(api_category_suggestions))
         .route("/staples", get(api_staples).post(api_save_staples))
+        .route("/substitutions", get(api_substitutions))
+        .route(
+            "/settings",
+            get(api_user_settings).post(api_save_user_settings),
+        )
+        .route("/version", get(api_data_version))
         // All the routes above require a UserId.
-        .route("/auth", get(auth::handler).post(auth::handler))
+        .route(
+            "/auth",
+            get(auth::handler::<storage::SqliteStore>).post(auth::handler::<storage::SqliteStore>),
+        )
+        .route(
+            "/auth/refresh",
+            post(auth::refresh_handler::<storage::SqliteStore>),
+        )
+        .route("/client_errors", post(api_client_error))
         .route("/account", get(api_user_account))
+        .route("/account/export", get(api_account_export))
+        .route("/account/samples", post(api_add_samples))
+        .route("/account/defaults", post(api_apply_defaults))
+        // Only users configured as admins can see these.
+        .route("/admin/stats", get(api_admin_stats))
+}
+
+const DEMO_READ_ONLY_MESSAGE: &'static str =
+    "This is a read only demo. Changes can not be saved here.";
+
+/// Reject anything that could change data or log a user in when serving a
+/// public demo.
+async fn reject_demo_writes<B>(req: Request<B>, next: Next<B>) -> Response {
+    let read_only = req.method() == Method::GET || req.method() == Method::HEAD;
+    if read_only && !req.uri().path().ends_with("/auth") {
+        return next.run(req).await;
+    }
+    debug!(method = %req.method(), path = req.uri().path(), "Rejecting demo write");
+    api::EmptyResponse::error_code(api::ErrorCode::ReadOnly, DEMO_READ_ONLY_MESSAGE).into_response()
+}
+
+/// Bump the data version of a logged in user when one of their writes
+/// succeeds and send the new version back in a header.
+async fn track_data_version<B: Send>(req: Request<B>, next: Next<B>) -> Response {
+    let path = req.uri().path();
+    let read_only = req.method() == Method::GET || req.method() == Method::HEAD;
+    // These post but don't change anything the client loads.
+    let bookkeeping = [
+        "/auth",
+        "/auth/refresh",
+        "/client_errors",
+        "/check",
+        "/import/ocr",
+    ]
+    .iter()
+    .any(|suffix| path.ends_with(suffix));
+    if read_only || bookkeeping {
+        return next.run(req).await;
+    }
+    let mut parts = RequestParts::new(req);
+    let user_id = storage::session_user_id(&mut parts).await;
+    let app_store = Extension::<Arc<dyn storage::APIStore>>::from_request(&mut parts).await;
+    let req = match parts.try_into_request() {
+        Ok(req) => req,
+        Err(err) => {
+            error!(?err, "Unable to rebuild request");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+    let mut resp = next.run(req).await;
+    if let (Some(storage::UserId(user_id)), Ok(Extension(app_store))) = (user_id, app_store) {
+        if resp.status().is_success() {
+            match app_store.bump_data_version(&user_id).await {
+                Ok(version) => {
+                    resp.headers_mut()
+                        .insert(api::DATA_VERSION_HEADER, header::HeaderValue::from(version));
+                }
+                Err(err) => error!(user_id, ?err, "Failed to bump the data version"),
+            }
+        }
+    }
+    resp
+}
+
+/// Keep people who aren't logged in to what the anonymous access setting
+/// allows. Logging in and share links always work.
+async fn limit_anonymous<B: Send>(req: Request<B>, next: Next<B>) -> Response {
+    let path = req.uri().path();
+    if path.ends_with("/auth") || path.ends_with("/auth/refresh") || path.contains("/shared/") {
+        return next.run(req).await;
+    }
+    let mut parts = RequestParts::new(req);
+    let logged_in = storage::session_user_id(&mut parts).await.is_some();
+    let anonymous = match Extension::<Arc<AnonymousAccess>>::from_request(&mut parts).await {
+        Ok(Extension(anonymous)) => anonymous,
+        Err(_) => Arc::new(AnonymousAccess::default()),
+    };
+    let req = match parts.try_into_request() {
+        Ok(req) => req,
+        Err(err) => {
+            error!(?err, "Unable to rebuild request");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+    let read_only = req.method() == Method::GET || req.method() == Method::HEAD;
+    let allowed = match anonymous.as_ref() {
+        _ if logged_in => true,
+        AnonymousAccess::Files => true,
+        AnonymousAccess::Off => false,
+        AnonymousAccess::User(_) => read_only && storage::is_public_route(req.uri().path()),
+    };
+    if allowed {
+        next.run(req).await
+    } else {
+        debug!(method = %req.method(), path = req.uri().path(), "Rejecting anonymous request");
+        api::EmptyResponse::Unauthorized.into_response()
+    }
+}
+
+/// Open the recipe file store. Only `dir_editor` may change the files.
+fn open_file_store(
+    recipe_dir_path: PathBuf,
+    dir_editor: Option<String>,
+) -> Arc<storage::file_store::AsyncFileStore> {
+    Arc::new(storage::file_store::AsyncFileStore::new(recipe_dir_path).with_editor(dir_editor))
 }
 
-#[instrument(fields(recipe_dir=?recipe_dir_path), skip_all)]
-pub async fn make_router(recipe_dir_path: PathBuf, store_path: PathBuf) -> Router {
+/// Poll a writable recipe directory so edits made on disk are picked up
+/// without a restart. The editor's data version is bumped when a file
+/// changes so their clients load the new recipes.
+fn watch_file_store(
+    store: Arc<storage::file_store::AsyncFileStore>,
+    app_store: Arc<dyn storage::APIStore>,
+    interval: std::time::Duration,
+) {
+    let editor = match store.editor() {
+        Some(editor) => editor.to_owned(),
+        None => return,
+    };
+    store.watch(interval, move || {
+        let app_store = app_store.clone();
+        let editor = editor.clone();
+        async move {
+            if let Err(err) = app_store.bump_data_version(&editor).await {
+                error!(editor, ?err, "Failed to bump the data version");
+            }
+        }
+    });
+}
+
+#[instrument(skip_all)]
+pub async fn make_router(
+    store: Arc<storage::file_store::AsyncFileStore>,
+    store_path: PathBuf,
+    demo: bool,
+    limits: Limits,
+    db_config: DbConfig,
+    admins: Admins,
+    anonymous: AnonymousAccess,
+    security: SecurityHeaders,
+    ocr: Option<Arc<dyn ocr::OcrBackend>>,
+    blobs: Arc<dyn blob::BlobStore>,
+    schedule: scheduler::Schedule,
+) -> Router {
     let handle = metrics_exporter_prometheus::PrometheusBuilder::new()
         .install_recorder()
         .expect("Failed to install Prometheus Recorder");
@@ -554,30 +3280,26 @@ pub async fn make_router(recipe_dir_path: PathBuf, store_path: PathBuf) -> Route
     let collector = Collector::default();
     collector.describe();
     let metrics_trace_layer = metrics::make_layer(|b: &axum::body::Bytes| b.len() as u64);
-    let store = Arc::new(storage::file_store::AsyncFileStore::new(
-        recipe_dir_path.clone(),
-    ));
     let app_store = Arc::new(
-        storage::SqliteStore::new(store_path)
+        storage::SqliteStore::open(store_path, &db_config)
             .await
-            .expect("Unable to create app_store"),
+            .expect("Unable to create app_store")
+            .with_limits(limits),
     );
     app_store
         .run_migrations()
         .await
         .expect("Failed to run database migrations");
-    Router::new()
-        .route("/", get(|| async { Redirect::temporary("/ui/plan") }))
-        .route("/favicon.ico", get(|| async { StaticFile("favicon.ico") }))
-        .route("/ui/*path", get(ui_static_assets))
-        // TODO(jwall): We should use route_layer to enforce the authorization
-        // requirements here.
-        .nest(
-            "/api",
-            Router::new()
-                .nest("/v1", mk_v1_routes())
-                .nest("/v2", mk_v2_routes()),
-        )
+    schedule.scheduler(app_store.clone(), blobs.clone()).start();
+    watch_file_store(
+        store.clone(),
+        app_store.clone(),
+        storage::file_store::POLL_INTERVAL,
+    );
+    let router = make_app_router(
+        store, app_store, demo, admins, anonymous, security, ocr, blobs,
+    );
+    router
         .route(
             "/metrics/prometheus",
             get(|| async move {
@@ -592,47 +3314,282 @@ pub async fn make_router(recipe_dir_path: PathBuf, store_path: PathBuf) -> Route
             // to bottom.
             ServiceBuilder::new()
                 .layer(TraceLayer::new_for_http())
-                .layer(metrics_trace_layer)
+                .layer(metrics_trace_layer),
+        )
+}
+
+/// The ui and api routes backed by the given stores. This leaves out the
+/// metrics so it can be built more than once in a process.
+fn make_app_router(
+    store: Arc<storage::file_store::AsyncFileStore>,
+    app_store: Arc<storage::SqliteStore>,
+    demo: bool,
+    admins: Admins,
+    anonymous: AnonymousAccess,
+    security: SecurityHeaders,
+    ocr: Option<Arc<dyn ocr::OcrBackend>>,
+    blobs: Arc<dyn blob::BlobStore>,
+) -> Router {
+    let api_routes = Router::new()
+        .nest("/v1", mk_v1_routes())
+        .nest("/v2", mk_v2_routes())
+        .layer(middleware::from_fn(track_data_version));
+    let api_routes = if demo {
+        info!("Serving a read only demo");
+        api_routes.layer(middleware::from_fn(reject_demo_writes))
+    } else {
+        api_routes
+    };
+    let api_routes = if anonymous != AnonymousAccess::Files {
+        info!(?anonymous, "Limiting anonymous access");
+        api_routes.layer(middleware::from_fn(limit_anonymous))
+    } else {
+        api_routes
+    };
+    let api_routes = api_routes
+        .layer(middleware::from_fn(api_errors::limit_body_size))
+        .layer(middleware::from_fn(api_errors::json_errors));
+    let security = Arc::new(security);
+    Router::new()
+        .route("/", get(|| async { Redirect::temporary("/ui/plan") }))
+        .route("/favicon.ico", get(|| async { StaticFile("favicon.ico") }))
+        .route("/ui/*path", get(ui_static_assets))
+        .route("/share/plan/:token", get(shared_plan_page))
+        .route("/share/:token/qr.png", get(share_qr_code))
+        // TODO(jwall): We should use route_layer to enforce the authorization
+        // requirements here.
+        .nest("/api", api_routes)
+        .layer(
+            ServiceBuilder::new()
                 .layer(Extension(store))
-                .layer(Extension(app_store)),
+                .layer(Extension(app_store.clone() as Arc<dyn storage::APIStore>))
+                .layer(Extension(app_store))
+                .layer(Extension(Arc::new(admins)))
+                .layer(Extension(Arc::new(anonymous)))
+                .layer(Extension(ocr))
+                .layer(Extension(blobs))
+                .layer(Extension(Arc::new(rate_limit::RateLimit::new(
+                    MAX_CLIENT_ERRORS_PER_MINUTE,
+                    std::time::Duration::from_secs(60),
+                )))),
         )
+        .layer(middleware::from_fn(request_id::propagate))
+        .layer(middleware::from_fn(move |req, next| {
+            security::add_headers(security.clone(), req, next)
+        }))
 }
 
 #[instrument(fields(recipe_dir=?recipe_dir_path,listen=?listen_socket), skip_all)]
 pub async fn ui_main_tls(
     recipe_dir_path: PathBuf,
     store_path: PathBuf,
+    dir_editor: Option<String>,
+    demo: bool,
+    limits: Limits,
+    db_config: DbConfig,
+    admins: Admins,
+    anonymous: AnonymousAccess,
+    security: SecurityHeaders,
+    ocr: Option<Arc<dyn ocr::OcrBackend>>,
+    blobs: Arc<dyn blob::BlobStore>,
+    schedule: scheduler::Schedule,
     listen_socket: SocketAddr,
+    systemd_listener: Option<std::net::TcpListener>,
     cert_path: &str,
     key_path: &str,
 ) {
-    let router = make_router(recipe_dir_path, store_path).await;
-    info!(
-        http = format!("https://{}", listen_socket),
-        "Starting server"
-    );
+    let router = make_router(
+        open_file_store(recipe_dir_path, dir_editor),
+        store_path,
+        demo,
+        limits,
+        db_config,
+        admins,
+        anonymous,
+        security,
+        ocr,
+        blobs,
+        schedule,
+    )
+    .await
+    .layer(Extension(qr::Scheme::Https));
     let config = axum_server::tls_rustls::RustlsConfig::from_pem_file(cert_path, key_path)
         .await
         .expect("Failed to parse config from pem files");
-    axum_server::bind_rustls(listen_socket, config)
-        .serve(router.into_make_service())
+    let server = if let Some(tcp_listener) = systemd_listener {
+        axum_server::tls_rustls::from_tcp_rustls(tcp_listener, config)
+    } else {
+        info!(
+            http = format!("https://{}", listen_socket),
+            "Starting server"
+        );
+        axum_server::bind_rustls(listen_socket, config)
+    };
+    server
+        .serve(router.into_make_service_with_connect_info::<SocketAddr>())
         .await
         .expect("Failed to start tls service");
 }
 
 #[instrument(fields(recipe_dir=?recipe_dir_path,listen=?listen_socket), skip_all)]
-pub async fn ui_main(recipe_dir_path: PathBuf, store_path: PathBuf, listen_socket: SocketAddr) {
-    let router = make_router(recipe_dir_path, store_path).await;
-    info!(
-        http = format!("http://{}", listen_socket),
-        "Starting server"
-    );
-    axum_server::bind(listen_socket)
-        .serve(router.into_make_service())
+pub async fn ui_main(
+    recipe_dir_path: PathBuf,
+    store_path: PathBuf,
+    dir_editor: Option<String>,
+    demo: bool,
+    limits: Limits,
+    db_config: DbConfig,
+    admins: Admins,
+    anonymous: AnonymousAccess,
+    security: SecurityHeaders,
+    ocr: Option<Arc<dyn ocr::OcrBackend>>,
+    blobs: Arc<dyn blob::BlobStore>,
+    schedule: scheduler::Schedule,
+    listen_socket: SocketAddr,
+    systemd_listener: Option<std::net::TcpListener>,
+) {
+    let router = make_router(
+        open_file_store(recipe_dir_path, dir_editor),
+        store_path,
+        demo,
+        limits,
+        db_config,
+        admins,
+        anonymous,
+        security,
+        ocr,
+        blobs,
+        schedule,
+    )
+    .await;
+    let server = if let Some(tcp_listener) = systemd_listener {
+        axum_server::from_tcp(tcp_listener)
+    } else {
+        info!(
+            http = format!("http://{}", listen_socket),
+            "Starting server"
+        );
+        axum_server::bind(listen_socket)
+    };
+    server
+        .serve(router.into_make_service_with_connect_info::<SocketAddr>())
         .await
         .expect("Failed to start service");
 }
 
+/// The database migrations that will run when the server starts.
+pub async fn pending_migrations(store_path: PathBuf) -> Result<Vec<String>, String> {
+    let app_store = storage::SqliteStore::new(store_path)
+        .await
+        .map_err(|e| format!("Unable to open the database: {}", e))?;
+    app_store
+        .pending_migrations()
+        .await
+        .map_err(|e| format!("Unable to read the database migrations: {:?}", e))
+}
+
+pub async fn print_instance_stats(store_path: PathBuf) {
+    let app_store = storage::SqliteStore::new(store_path)
+        .await
+        .expect("Unable to create app_store");
+    let stats = app_store
+        .instance_stats()
+        .await
+        .expect("Unable to fetch instance stats");
+    println!("user\trecipes\tplans\tsessions\tstorage_bytes");
+    for user in stats.users.iter().chain(std::iter::once(&stats.totals)) {
+        println!(
+            "{}\t{}\t{}\t{}\t{}",
+            user.user_id, user.recipes, user.plans, user.sessions, user.storage_bytes
+        );
+    }
+}
+
+pub async fn print_pruned_history(
+    store_path: PathBuf,
+    retention_months: Option<u32>,
+    dry_run: bool,
+) {
+    let app_store = storage::SqliteStore::new(store_path)
+        .await
+        .expect("Unable to create app_store");
+    let today = chrono::Local::now().date_naive();
+    let report = app_store
+        .prune_history(retention_months, today, dry_run)
+        .await
+        .expect("Unable to prune history");
+    println!("user\tcutoff\tplans\tinventory_snapshots\tsessions\trefresh_tokens");
+    for user in report.users.iter() {
+        println!(
+            "{}\t{}\t{}\t{}\t{}\t{}",
+            user.user_id,
+            user.cutoff,
+            user.plans.len(),
+            user.inventory_snapshots,
+            user.sessions,
+            user.refresh_tokens
+        );
+    }
+    println!("expired sessions: {}", report.sessions);
+    println!("expired refresh tokens: {}", report.refresh_tokens);
+    if dry_run {
+        println!("dry run: nothing was deleted");
+    }
+}
+
+/// Save an export of every user's account in the blob store under
+/// `backups/<date>/<user>.json` and return the keys they were saved under.
+pub async fn backup_accounts(
+    app_store: Arc<storage::SqliteStore>,
+    blobs: &dyn blob::BlobStore,
+) -> Result<Vec<String>, String> {
+    use futures::TryStreamExt;
+    let stats = app_store
+        .instance_stats()
+        .await
+        .map_err(|e| format!("Unable to list users: {:?}", e))?;
+    let now = chrono::Utc::now();
+    let date = now.format("%Y-%m-%d");
+    let mut keys = Vec::new();
+    for user in stats.users {
+        let payload = export::account_export(app_store.clone(), user.user_id.clone(), now)
+            .try_fold(Vec::new(), |mut payload, chunk| async move {
+                payload.extend_from_slice(&chunk);
+                Ok(payload)
+            })
+            .await
+            .map_err(|e| format!("Unable to export {}: {}", user.user_id, e))?;
+        // User ids can have characters that aren't safe in a key.
+        let name: String = user
+            .user_id
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        let key = format!("backups/{}/{}.json", date, name);
+        blobs.put(&key, payload).await?;
+        keys.push(key);
+    }
+    Ok(keys)
+}
+
+pub async fn backup(store_path: PathBuf, blobs: Arc<dyn blob::BlobStore>) {
+    let app_store = storage::SqliteStore::new(store_path)
+        .await
+        .expect("Unable to create app_store");
+    let keys = backup_accounts(Arc::new(app_store), blobs.as_ref())
+        .await
+        .expect("Unable to back up accounts");
+    for key in keys {
+        println!("{}", key);
+    }
+}
+
 pub async fn add_user(
     store_path: PathBuf,
     username: String,
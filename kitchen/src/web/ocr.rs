@@ -0,0 +1,180 @@
+// Copyright 2023 Jeremy Wall (Jeremy@marzhilsltudios.com)
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//! Pluggable OCR for turning photos of recipe cards into draft recipes.
+use std::{
+    io::{Read, Write},
+    process::{Command, Stdio},
+    time::{Duration, Instant},
+};
+
+use async_std::channel::{bounded, Receiver, Sender};
+use async_trait::async_trait;
+use tracing::{debug, error, instrument};
+
+/// How long an OCR command can run before it is killed.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// How many OCR commands can run at the same time. OCR is slow and memory
+/// hungry so more requests than this wait their turn.
+pub const DEFAULT_CONCURRENCY: usize = 2;
+
+/// How often we check whether a running command has finished.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Something that can read the text out of an image.
+#[async_trait]
+pub trait OcrBackend: Send + Sync + std::fmt::Debug {
+    async fn recognize(&self, image: Vec<u8>) -> Result<String, String>;
+}
+
+/// Runs a command that reads an image on stdin and writes its text to
+/// stdout.
+///
+/// `tesseract stdin stdout` does OCR locally. An external OCR service can be
+/// used by pointing this at a command or script that calls it, e.g. with
+/// `curl --data-binary @- <url>`.
+#[derive(Debug, Clone)]
+pub struct CommandOcr {
+    program: String,
+    args: Vec<String>,
+    timeout: Duration,
+    /// Holds one token for each command that is allowed to start.
+    permits: (Sender<()>, Receiver<()>),
+}
+
+/// Puts its token back when the command it was taken for is done.
+struct Permit(Sender<()>);
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        let _ = self.0.try_send(());
+    }
+}
+
+fn permits(concurrency: usize) -> (Sender<()>, Receiver<()>) {
+    let (send, recv) = bounded(concurrency.max(1));
+    while send.try_send(()).is_ok() {}
+    (send, recv)
+}
+
+impl CommandOcr {
+    pub fn new<S: Into<String>>(program: S, args: Vec<String>) -> Self {
+        Self {
+            program: program.into(),
+            args,
+            timeout: DEFAULT_TIMEOUT,
+            permits: permits(DEFAULT_CONCURRENCY),
+        }
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        self.permits = permits(concurrency);
+        self
+    }
+
+    /// Split a command line on whitespace. There is no shell quoting so use
+    /// a script for anything more complicated.
+    pub fn from_command_line(command: &str) -> Option<Self> {
+        let mut parts = command.split_whitespace().map(|p| p.to_owned());
+        let program = parts.next()?;
+        Some(Self::new(program, parts.collect()))
+    }
+
+    fn run(&self, image: Vec<u8>) -> Result<String, String> {
+        let mut child = Command::new(&self.program)
+            .args(&self.args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("Unable to run {}: {}", self.program, e))?;
+        let mut stdin = child.stdin.take().expect("stdin is piped");
+        let mut stdout = child.stdout.take().expect("stdout is piped");
+        let mut stderr = child.stderr.take().expect("stderr is piped");
+        // We write the image and read the output from other threads so a
+        // command that starts writing output before it has read all of its
+        // input can't deadlock us.
+        let writer = std::thread::spawn(move || stdin.write_all(&image));
+        let stdout = std::thread::spawn(move || {
+            let mut out = Vec::new();
+            stdout.read_to_end(&mut out).map(|_| out)
+        });
+        let stderr = std::thread::spawn(move || {
+            let mut out = Vec::new();
+            stderr.read_to_end(&mut out).map(|_| out)
+        });
+        let deadline = Instant::now() + self.timeout;
+        let status = loop {
+            match child.try_wait() {
+                Ok(Some(status)) => break status,
+                Ok(None) if Instant::now() >= deadline => {
+                    error!(program = %self.program, timeout = ?self.timeout, "Killing slow OCR command");
+                    let _ = child.kill();
+                    let _ = child.wait();
+                    return Err(format!(
+                        "{} took longer than {} seconds",
+                        self.program,
+                        self.timeout.as_secs_f32()
+                    ));
+                }
+                Ok(None) => std::thread::sleep(POLL_INTERVAL),
+                Err(e) => return Err(format!("Unable to run {}: {}", self.program, e)),
+            }
+        };
+        if let Ok(Err(err)) = writer.join() {
+            debug!(?err, "OCR command didn't read the whole image");
+        }
+        let stdout = match stdout.join() {
+            Ok(Ok(out)) => out,
+            _ => return Err(format!("Unable to read the output of {}", self.program)),
+        };
+        let stderr = stderr
+            .join()
+            .ok()
+            .and_then(|out| out.ok())
+            .unwrap_or_default();
+        if !status.success() {
+            let stderr = String::from_utf8_lossy(&stderr);
+            error!(program = %self.program, %status, %stderr, "OCR command failed");
+            return Err(format!("{} failed: {}", self.program, stderr.trim()));
+        }
+        Ok(String::from_utf8_lossy(&stdout).into_owned())
+    }
+}
+
+#[async_trait]
+impl OcrBackend for CommandOcr {
+    #[instrument(skip_all, fields(program = %self.program, image_bytes = image.len()))]
+    async fn recognize(&self, image: Vec<u8>) -> Result<String, String> {
+        let (release, acquire) = self.permits.clone();
+        acquire
+            .recv()
+            .await
+            .map_err(|e| format!("Unable to run {}: {}", self.program, e))?;
+        // The permit goes with the command so it is only released once the
+        // command is done even if the request is dropped first.
+        let permit = Permit(release);
+        let backend = self.clone();
+        async_std::task::spawn_blocking(move || {
+            let _permit = permit;
+            backend.run(image)
+        })
+        .await
+    }
+}
@@ -0,0 +1,165 @@
+// Copyright 2023 Jeremy Wall (Jeremy@marzhilsltudios.com)
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//! Server side rendering of recipe pages into the ui index.html so they load
+//! fast, work without javascript, and have link previews when shared. The
+//! wasm app removes the prerendered markup once it has started. Shared weeks
+//! of plans are rendered as standalone pages since they are for people
+//! without an account.
+use std::fmt::Write;
+
+use client_api::SharedWeek;
+use recipes::Recipe;
+
+/// The id of the element holding the prerendered markup.
+pub const PRERENDER_ID: &'static str = "prerender";
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn render_head(recipe: &Recipe) -> String {
+    let title = escape(&recipe.title);
+    let desc = escape(recipe.desc.as_deref().unwrap_or(""));
+    let mut head = String::new();
+    let _ = write!(head, "<title>{}</title>", title);
+    let _ = write!(head, r#"<meta property="og:title" content="{}">"#, title);
+    let _ = write!(head, r#"<meta property="og:type" content="article">"#);
+    if !desc.is_empty() {
+        let _ = write!(head, r#"<meta name="description" content="{}">"#, desc);
+        let _ = write!(head, r#"<meta property="og:description" content="{}">"#, desc);
+    }
+    head
+}
+
+fn render_body(recipe: &Recipe) -> String {
+    let mut body = String::new();
+    let _ = write!(body, r#"<div id="{}" class="app">"#, PRERENDER_ID);
+    let _ = write!(body, r#"<div class="recipe">"#);
+    let _ = write!(
+        body,
+        r#"<h1 class="recipe_title">{}</h1>"#,
+        escape(&recipe.title)
+    );
+    if let Some(desc) = &recipe.desc {
+        let _ = write!(body, r#"<div class="recipe_description">{}</div>"#, escape(desc));
+    }
+    if let Some(src) = &recipe.metadata.source {
+        let src = escape(src);
+        let _ = match recipe.metadata.source_url() {
+            Some(_) => write!(
+                body,
+                r#"<p class="recipe_source">Source: <a href="{}">{}</a></p>"#,
+                src, src
+            ),
+            None => write!(body, r#"<p class="recipe_source">Source: {}</p>"#, src),
+        };
+    }
+    let _ = write!(body, r#"<h2>Instructions: </h2><div class="recipe_steps">"#);
+    for (idx, step) in recipe.steps.iter().enumerate() {
+        let _ = write!(body, "<div><h3>Step {}</h3>", idx + 1);
+        let _ = write!(body, r#"<ul class="ingredients">"#);
+        for ingredient in step.ingredients.iter() {
+            let _ = write!(body, "<li>{}</li>", escape(&ingredient.to_string()));
+        }
+        let _ = write!(body, "</ul>");
+        let _ = write!(
+            body,
+            r#"<div class="instructions">{}</div></div>"#,
+            escape(&step.instructions)
+        );
+    }
+    let _ = write!(body, "</div></div></div>");
+    body
+}
+
+/// Render the recipe into the ui index.html. The head gets a title and link
+/// preview metadata and the body gets the recipe markup.
+pub fn render_recipe_page(index: &str, recipe: &Recipe) -> String {
+    let head = render_head(recipe);
+    let body = render_body(recipe);
+    let page = match index.find("</head>") {
+        Some(idx) => format!("{}{}{}", &index[..idx], head, &index[idx..]),
+        None => index.to_owned(),
+    };
+    match page.find("<body>") {
+        Some(idx) => {
+            let idx = idx + "<body>".len();
+            format!("{}{}{}", &page[..idx], body, &page[idx..])
+        }
+        None => page,
+    }
+}
+
+/// Render a shared week of plans as a page of its own.
+pub fn render_shared_week_page(week: &SharedWeek) -> String {
+    let title = format!("Meals for {} to {}", week.start, week.end);
+    let mut page = String::new();
+    let _ = write!(page, "<!DOCTYPE html><html><head>");
+    let _ = write!(page, r#"<meta charset="utf-8">"#);
+    let _ = write!(
+        page,
+        r#"<meta name="viewport" content="width=device-width, initial-scale=1.0">"#
+    );
+    let _ = write!(page, "<title>{}</title>", escape(&title));
+    let _ = write!(
+        page,
+        r#"<meta property="og:title" content="{}">"#,
+        escape(&title)
+    );
+    let _ = write!(
+        page,
+        r#"<link rel="stylesheet" href="/ui/static/pico.min.css">"#
+    );
+    let _ = write!(page, r#"<link rel="stylesheet" href="/ui/static/app.css">"#);
+    let _ = write!(page, r#"</head><body><main class="container shared-week">"#);
+    let _ = write!(page, "<h1>{}</h1>", escape(&title));
+    if week.plans.is_empty() {
+        let _ = write!(page, "<p>Nothing is planned for this week.</p>");
+    }
+    for plan in week.plans.iter() {
+        let _ = write!(page, "<section><h2>Plan for {}</h2>", plan.date);
+        let _ = write!(page, "<h3>Recipes</h3><ul>");
+        for recipe in plan.recipes.iter() {
+            let _ = write!(
+                page,
+                "<li>{}: {} x {}</li>",
+                recipe.meal,
+                recipe.count,
+                escape(&recipe.title)
+            );
+        }
+        let _ = write!(page, "</ul>");
+        if !plan.shopping_list.is_empty() {
+            let _ = write!(page, "<h3>Shopping list</h3><ul>");
+            for item in plan.shopping_list.iter() {
+                let _ = write!(page, "<li>{}</li>", escape(item));
+            }
+            let _ = write!(page, "</ul>");
+        }
+        let _ = write!(page, "</section>");
+    }
+    let _ = write!(page, "</main></body></html>");
+    page
+}
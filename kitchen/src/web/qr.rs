@@ -0,0 +1,70 @@
+// Copyright 2023 Jeremy Wall (Jeremy@marzhilsltudios.com)
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//! QR codes for share links so they can be printed, pinned to the fridge and
+//! scanned with a phone.
+use axum::http::{header, HeaderMap};
+use image::{DynamicImage, ImageOutputFormat, Luma};
+use qrcode::QrCode;
+
+/// The smallest width and height of a QR code image in pixels.
+const MIN_SIZE: u32 = 256;
+
+/// The scheme kitchen itself serves. Servers started with `--tls` add
+/// [Scheme::Https] as an extension.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Scheme {
+    Http,
+    Https,
+}
+
+impl Default for Scheme {
+    fn default() -> Self {
+        Scheme::Http
+    }
+}
+
+impl Scheme {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Scheme::Http => "http",
+            Scheme::Https => "https",
+        }
+    }
+}
+
+/// The origin a request was made to so links in QR codes work from another
+/// device. Proxies that terminate TLS should set `X-Forwarded-Proto`,
+/// otherwise the scheme kitchen serves is used.
+pub fn request_origin(headers: &HeaderMap, scheme: Scheme) -> Option<String> {
+    let host = headers.get(header::HOST)?.to_str().ok()?;
+    let scheme = headers
+        .get("x-forwarded-proto")
+        .and_then(|proto| proto.to_str().ok())
+        .unwrap_or(scheme.as_str());
+    Some(format!("{}://{}", scheme, host))
+}
+
+/// Encode `data` as a QR code in a png image.
+pub fn png(data: &str) -> Result<Vec<u8>, String> {
+    let code = QrCode::new(data.as_bytes()).map_err(|err| format!("{}", err))?;
+    let image = code
+        .render::<Luma<u8>>()
+        .min_dimensions(MIN_SIZE, MIN_SIZE)
+        .build();
+    let mut png = Vec::new();
+    DynamicImage::ImageLuma8(image)
+        .write_to(&mut png, ImageOutputFormat::Png)
+        .map_err(|err| format!("{}", err))?;
+    Ok(png)
+}
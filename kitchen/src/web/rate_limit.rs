@@ -0,0 +1,54 @@
+// Copyright 2023 Jeremy Wall (Jeremy@marzhilsltudios.com)
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//! Per address limits for endpoints that accept requests without a login.
+use std::collections::BTreeMap;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Allows up to `max` requests from each address in a fixed window. The
+/// counts for every address start over when the window ends.
+#[derive(Debug)]
+pub struct RateLimit {
+    max: u32,
+    window: Duration,
+    counts: Mutex<(Instant, BTreeMap<IpAddr, u32>)>,
+}
+
+impl RateLimit {
+    pub fn new(max: u32, window: Duration) -> Self {
+        Self {
+            max,
+            window,
+            counts: Mutex::new((Instant::now(), BTreeMap::new())),
+        }
+    }
+
+    /// Count a request from `addr` made at `now` and return whether it is
+    /// under the limit.
+    pub fn check(&self, addr: IpAddr, now: Instant) -> bool {
+        let mut counts = self.counts.lock().expect("Rate limit lock was poisoned");
+        let (started, by_addr) = &mut *counts;
+        if now.duration_since(*started) >= self.window {
+            *started = now;
+            by_addr.clear();
+        }
+        let count = by_addr.entry(addr).or_insert(0);
+        if *count >= self.max {
+            return false;
+        }
+        *count += 1;
+        true
+    }
+}
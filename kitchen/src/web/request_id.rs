@@ -0,0 +1,101 @@
+// Copyright 2023 Jeremy Wall (Jeremy@marzhilsltudios.com)
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//! Trace ids that tie a request from the browser to the server's log lines.
+//! The client sends a W3C `traceparent` header and we log everything for the
+//! request under its trace id. Requests without one get a new trace id. The
+//! trace id is returned in the `x-request-id` header either way.
+use axum::{
+    http::{header::HeaderValue, Request},
+    middleware::Next,
+    response::Response,
+};
+use tracing::{error, info_span, Instrument};
+
+pub const TRACEPARENT: &'static str = "traceparent";
+pub const REQUEST_ID: &'static str = "x-request-id";
+
+/// The parts of a `traceparent` header we care about.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceParent {
+    pub trace_id: String,
+    pub parent_id: String,
+}
+
+/// Ids are lowercase or uppercase hex of a fixed length and not all zeros.
+fn is_valid_id(s: &str, len: usize) -> bool {
+    s.len() == len && s.bytes().all(|b| b.is_ascii_hexdigit()) && s.bytes().any(|b| b != b'0')
+}
+
+impl TraceParent {
+    /// Parse a `traceparent` header. Versions other than 00 may add fields
+    /// after the flags which we ignore.
+    pub fn parse(value: &str) -> Option<Self> {
+        let mut parts = value.trim().split('-');
+        let version = parts.next()?;
+        let trace_id = parts.next()?;
+        let parent_id = parts.next()?;
+        let flags = parts.next()?;
+        if version.len() != 2 || version == "ff" || flags.len() != 2 {
+            return None;
+        }
+        if !is_valid_id(trace_id, 32) || !is_valid_id(parent_id, 16) {
+            return None;
+        }
+        Some(Self {
+            trace_id: trace_id.to_lowercase(),
+            parent_id: parent_id.to_lowercase(),
+        })
+    }
+}
+
+fn new_id(len: usize) -> String {
+    let mut id = uuid::Uuid::new_v4().simple().to_string();
+    id.truncate(len);
+    id
+}
+
+/// Run the request in a span with its trace id and add the ids to the
+/// response.
+pub async fn propagate<B>(req: Request<B>, next: Next<B>) -> Response {
+    let parent = req
+        .headers()
+        .get(TRACEPARENT)
+        .and_then(|v| v.to_str().ok())
+        .and_then(TraceParent::parse);
+    let trace_id = parent
+        .as_ref()
+        .map(|p| p.trace_id.clone())
+        .unwrap_or_else(|| new_id(32));
+    let span_id = new_id(16);
+    let span = info_span!(
+        "request",
+        trace_id = trace_id.as_str(),
+        span_id = span_id.as_str(),
+        parent_id = parent.as_ref().map(|p| p.parent_id.as_str()),
+        method = %req.method(),
+        path = req.uri().path(),
+    );
+    let mut resp = next.run(req).instrument(span.clone()).await;
+    if resp.status().is_server_error() {
+        span.in_scope(|| error!(status = resp.status().as_u16(), "Request failed"));
+    }
+    let headers = resp.headers_mut();
+    if let Ok(value) = HeaderValue::from_str(&trace_id) {
+        headers.insert(REQUEST_ID, value);
+    }
+    if let Ok(value) = HeaderValue::from_str(&format!("00-{}-{}-01", trace_id, span_id)) {
+        headers.insert(TRACEPARENT, value);
+    }
+    resp
+}
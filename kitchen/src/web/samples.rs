@@ -0,0 +1,66 @@
+// Copyright 2023 Jeremy Wall (Jeremy@marzhilsltudios.com)
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//! Sample recipes that a new account can begin from instead of a blank
+//! page.
+use recipes::RecipeEntry;
+use rust_embed::RustEmbed;
+use tracing::error;
+
+use super::storage::{self, APIStore};
+
+#[derive(RustEmbed)]
+#[folder = "../examples/recipes.txt"]
+struct SampleRecipes;
+
+/// The bundled sample recipes. Each is named for the file it came from.
+pub fn sample_recipes() -> Vec<RecipeEntry> {
+    SampleRecipes::iter()
+        .filter_map(|path| {
+            let file = SampleRecipes::get(path.as_ref())?;
+            let id = path.as_ref().trim_end_matches(".txt").to_owned();
+            match String::from_utf8(file.data.into_owned()) {
+                Ok(text) => Some(RecipeEntry::new(id, text)),
+                Err(err) => {
+                    error!(?path, ?err, "Sample recipe is not utf-8");
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+/// An account is empty until it has a recipe or a category mapping.
+pub async fn account_is_empty(store: &dyn APIStore, user_id: &str) -> storage::Result<bool> {
+    if !store.fetch_recipe_ids_for_user(user_id).await?.is_empty() {
+        return Ok(false);
+    }
+    Ok(store
+        .get_category_mappings_for_user(user_id)
+        .await?
+        .map_or(true, |mappings| mappings.is_empty()))
+}
+
+/// Add the sample recipes the user doesn't already have a recipe with the
+/// same id for. Returns the number that were added.
+pub async fn add_sample_recipes(store: &dyn APIStore, user_id: &str) -> storage::Result<usize> {
+    let existing = store.fetch_recipe_ids_for_user(user_id).await?;
+    let recipes: Vec<RecipeEntry> = sample_recipes()
+        .into_iter()
+        .filter(|entry| !existing.contains(entry.recipe_id()))
+        .collect();
+    if !recipes.is_empty() {
+        store.store_recipes_for_user(user_id, &recipes).await?;
+    }
+    Ok(recipes.len())
+}
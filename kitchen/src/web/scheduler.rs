@@ -0,0 +1,200 @@
+// Copyright 2023 Jeremy Wall (Jeremy@marzhilsltudios.com)
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//! Runs periodic jobs like pruning and backups inside the server so operators
+//! don't need cron. When each job last ran is kept in the database so a
+//! restart doesn't run everything again or put it off for a whole interval.
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::NaiveDateTime;
+use tracing::{error, info, instrument};
+
+use super::blob::BlobStore;
+use super::storage::SqliteStore;
+
+/// The most a job is put off by to keep instances that started together from
+/// running their jobs at the same moment.
+const MAX_JITTER: Duration = Duration::from_secs(5 * 60);
+
+const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Something to run every `interval`.
+#[async_trait]
+pub trait Job: Send + Sync {
+    /// A stable name the last run time is saved under.
+    fn name(&self) -> &'static str;
+    fn interval(&self) -> Duration;
+    async fn run(&self) -> Result<(), String>;
+}
+
+/// How long to wait before running a job that last ran at `last_run`. Jobs
+/// that have never run or are overdue run right away.
+pub fn delay(last_run: Option<NaiveDateTime>, now: NaiveDateTime, interval: Duration) -> Duration {
+    let last_run = match last_run {
+        Some(last_run) => last_run,
+        None => return Duration::ZERO,
+    };
+    match chrono::Duration::from_std(interval)
+        .ok()
+        .and_then(|d| last_run.checked_add_signed(d))
+    {
+        Some(due) => (due - now).to_std().unwrap_or(Duration::ZERO),
+        None => interval,
+    }
+}
+
+/// A random amount of time up to a tenth of the interval.
+fn jitter(interval: Duration) -> Duration {
+    let max = (interval / 10).min(MAX_JITTER).as_millis() as u64;
+    if max == 0 {
+        return Duration::ZERO;
+    }
+    Duration::from_millis(uuid::Uuid::new_v4().as_u128() as u64 % max)
+}
+
+/// Runs each of its jobs on their own interval.
+pub struct Scheduler {
+    store: Arc<SqliteStore>,
+    jobs: Vec<Arc<dyn Job>>,
+}
+
+impl Scheduler {
+    pub fn new(store: Arc<SqliteStore>) -> Self {
+        Self {
+            store,
+            jobs: Vec::new(),
+        }
+    }
+
+    pub fn with_job<J: Job + 'static>(mut self, job: J) -> Self {
+        self.jobs.push(Arc::new(job));
+        self
+    }
+
+    /// Start running the jobs in the background.
+    pub fn start(self) {
+        for job in self.jobs {
+            let store = self.store.clone();
+            async_std::task::spawn(async move {
+                loop {
+                    let now = chrono::Utc::now().naive_utc();
+                    let wait = match store.fetch_job_last_run(job.name()).await {
+                        Ok(last_run) => delay(last_run, now, job.interval()),
+                        Err(err) => {
+                            error!(?err, job = job.name(), "Unable to fetch the last run");
+                            job.interval()
+                        }
+                    };
+                    let wait = wait + jitter(job.interval());
+                    async_std::task::sleep(wait).await;
+                    run_job(&store, job.as_ref()).await;
+                }
+            });
+        }
+    }
+}
+
+#[instrument(skip_all, fields(job = job.name()))]
+async fn run_job(store: &SqliteStore, job: &dyn Job) {
+    info!("Running scheduled job");
+    if let Err(err) = job.run().await {
+        error!(err, "Scheduled job failed");
+    }
+    // Failed runs count too so a job that keeps failing waits for its
+    // interval instead of retrying in a tight loop.
+    if let Err(err) = store
+        .save_job_last_run(job.name(), chrono::Utc::now().naive_utc())
+        .await
+    {
+        error!(?err, "Unable to save when the job last ran");
+    }
+}
+
+/// What the server runs on a schedule.
+#[derive(Clone, Debug, Default)]
+pub struct Schedule {
+    /// Months of history to keep for users who haven't set their own.
+    pub retention_months: Option<u32>,
+    /// How often to back up every account. None turns backups off.
+    pub backup_interval: Option<Duration>,
+}
+
+impl Schedule {
+    /// The scheduler for these jobs.
+    pub fn scheduler(&self, store: Arc<SqliteStore>, blobs: Arc<dyn BlobStore>) -> Scheduler {
+        let mut scheduler = Scheduler::new(store.clone()).with_job(PruneJob {
+            store: store.clone(),
+            default_months: self.retention_months,
+        });
+        if let Some(interval) = self.backup_interval {
+            scheduler = scheduler.with_job(BackupJob {
+                store,
+                blobs,
+                interval,
+            });
+        }
+        scheduler
+    }
+}
+
+/// Prunes history past the retention window and expired sessions daily.
+pub struct PruneJob {
+    store: Arc<SqliteStore>,
+    default_months: Option<u32>,
+}
+
+#[async_trait]
+impl Job for PruneJob {
+    fn name(&self) -> &'static str {
+        "prune"
+    }
+
+    fn interval(&self) -> Duration {
+        DAY
+    }
+
+    async fn run(&self) -> Result<(), String> {
+        let today = chrono::Local::now().date_naive();
+        self.store
+            .prune_history(self.default_months, today, false)
+            .await
+            .map(|_| ())
+            .map_err(|e| format!("{:?}", e))
+    }
+}
+
+/// Saves an export of every account to the blob store.
+pub struct BackupJob {
+    store: Arc<SqliteStore>,
+    blobs: Arc<dyn BlobStore>,
+    interval: Duration,
+}
+
+#[async_trait]
+impl Job for BackupJob {
+    fn name(&self) -> &'static str {
+        "backup"
+    }
+
+    fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    async fn run(&self) -> Result<(), String> {
+        let keys = super::backup_accounts(self.store.clone(), self.blobs.as_ref()).await?;
+        info!(accounts = keys.len(), "Backed up accounts");
+        Ok(())
+    }
+}
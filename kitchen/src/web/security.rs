@@ -0,0 +1,94 @@
+// Copyright 2023 Jeremy Wall (Jeremy@marzhilsltudios.com)
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//! Security headers added to every response. The content security policy is
+//! tuned for the wasm ui which loads its script and styles from `/ui` and
+//! talks only to this server.
+use std::sync::Arc;
+
+use axum::{
+    http::{header, HeaderValue, Request},
+    middleware::Next,
+    response::Response,
+};
+
+/// The default content security policy. `wasm-unsafe-eval` lets the ui's
+/// wasm module be compiled without allowing `eval` for scripts. Recipe step
+/// images can be data urls or hosted elsewhere.
+pub const DEFAULT_CSP: &'static str = "default-src 'self'; \
+    script-src 'self' 'wasm-unsafe-eval'; \
+    style-src 'self'; \
+    img-src 'self' data: blob: https:; \
+    connect-src 'self'; \
+    object-src 'none'; \
+    base-uri 'self'; \
+    form-action 'self'; \
+    frame-ancestors 'none'";
+
+pub const DEFAULT_REFERRER_POLICY: &'static str = "same-origin";
+
+/// How long browsers should only use https for this server once they have
+/// seen it over https.
+const HSTS: &'static str = "max-age=31536000";
+
+/// The security headers to add to responses. Headers a handler already set
+/// are left alone.
+#[derive(Clone, Debug)]
+pub struct SecurityHeaders {
+    /// None leaves the header off.
+    pub content_security_policy: Option<HeaderValue>,
+    /// None leaves the header off.
+    pub referrer_policy: Option<HeaderValue>,
+    /// Send Strict-Transport-Security. Only do this when the server is
+    /// reached over https.
+    pub hsts: bool,
+}
+
+impl Default for SecurityHeaders {
+    fn default() -> Self {
+        Self {
+            content_security_policy: Some(HeaderValue::from_static(DEFAULT_CSP)),
+            referrer_policy: Some(HeaderValue::from_static(DEFAULT_REFERRER_POLICY)),
+            hsts: false,
+        }
+    }
+}
+
+/// Add the security headers to the response.
+pub async fn add_headers<B>(
+    headers: Arc<SecurityHeaders>,
+    req: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let mut resp = next.run(req).await;
+    let resp_headers = resp.headers_mut();
+    resp_headers
+        .entry(header::X_CONTENT_TYPE_OPTIONS)
+        .or_insert(HeaderValue::from_static("nosniff"));
+    if let Some(csp) = &headers.content_security_policy {
+        resp_headers
+            .entry(header::CONTENT_SECURITY_POLICY)
+            .or_insert(csp.clone());
+    }
+    if let Some(policy) = &headers.referrer_policy {
+        resp_headers
+            .entry(header::REFERRER_POLICY)
+            .or_insert(policy.clone());
+    }
+    if headers.hsts {
+        resp_headers
+            .entry(header::STRICT_TRANSPORT_SECURITY)
+            .or_insert(HeaderValue::from_static(HSTS));
+    }
+    resp
+}
@@ -0,0 +1,319 @@
+// Copyright 2023 Jeremy Wall (Jeremy@marzhilsltudios.com)
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//! Fake users, recipes, plans and inventory history for working on the ui or
+//! checking performance without anyone's real data. The same seed always
+//! produces the same data.
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::PathBuf;
+
+use chrono::NaiveDate;
+use recipes::{IngredientKey, RecipeEntry};
+use tracing::info;
+
+use super::storage::{self, APIStore, AuthStore};
+
+/// How much data to create.
+#[derive(Debug, Clone)]
+pub struct SeedOptions {
+    pub users: usize,
+    pub recipes: usize,
+    /// The number of weeks of plans and inventory history before today.
+    pub weeks: usize,
+    pub password: String,
+    pub seed: u64,
+}
+
+/// (name, category, measure type, amounts to pick from)
+const INGREDIENTS: &[(&str, &str, &str, &[&str])] = &[
+    ("onion", "Produce", "Count", &["1", "2", "1/2"]),
+    ("garlic", "Produce", "Count", &["2", "3", "4"]),
+    ("carrot", "Produce", "Count", &["1", "2", "3"]),
+    ("potato", "Produce", "Count", &["2", "4", "6"]),
+    ("tomato", "Produce", "Count", &["1", "2", "4"]),
+    ("bell pepper", "Produce", "Count", &["1", "2"]),
+    ("spinach", "Produce", "Volume", &["2 cups", "4 cups"]),
+    ("lemon", "Produce", "Count", &["1", "2"]),
+    ("ground beef", "Meat", "Weight", &["1 lb", "2 lb"]),
+    ("chicken breast", "Meat", "Weight", &["1 lb", "2 lb"]),
+    ("bacon", "Meat", "Weight", &["8 oz", "1 lb"]),
+    ("sausage", "Meat", "Weight", &["12 oz", "1 lb"]),
+    ("milk", "Dairy", "Volume", &["1 cup", "2 cups", "1/2 cup"]),
+    (
+        "butter",
+        "Dairy",
+        "Volume",
+        &["2 tbsp", "1/4 cup", "1/2 cup"],
+    ),
+    ("cheddar cheese", "Dairy", "Volume", &["1 cup", "2 cups"]),
+    ("heavy cream", "Dairy", "Volume", &["1/2 cup", "1 cup"]),
+    ("egg", "Dairy", "Count", &["1", "2", "4"]),
+    (
+        "flour",
+        "Dry Goods",
+        "Volume",
+        &["1 cup", "2 cups", "3 cups"],
+    ),
+    (
+        "sugar",
+        "Dry Goods",
+        "Volume",
+        &["1/4 cup", "1/2 cup", "1 cup"],
+    ),
+    ("rice", "Dry Goods", "Volume", &["1 cup", "2 cups"]),
+    ("pasta", "Dry Goods", "Weight", &["8 oz", "1 lb"]),
+    ("baking powder", "Dry Goods", "Volume", &["1 tsp", "2 tsp"]),
+    (
+        "chicken stock",
+        "Canned Goods",
+        "Volume",
+        &["2 cups", "4 cups"],
+    ),
+    (
+        "diced tomatoes",
+        "Canned Goods",
+        "Volume",
+        &["1 cup", "2 cups"],
+    ),
+    (
+        "black beans",
+        "Canned Goods",
+        "Volume",
+        &["1 cup", "2 cups"],
+    ),
+    (
+        "olive oil",
+        "Oils",
+        "Volume",
+        &["1 tbsp", "2 tbsp", "1/4 cup"],
+    ),
+    ("salt", "Spices", "Volume", &["1/2 tsp", "1 tsp", "2 tsp"]),
+    ("pepper", "Spices", "Volume", &["1/4 tsp", "1/2 tsp"]),
+    ("cumin", "Spices", "Volume", &["1 tsp", "2 tsp"]),
+    ("paprika", "Spices", "Volume", &["1 tsp", "1 tbsp"]),
+    ("oregano", "Spices", "Volume", &["1 tsp", "2 tsp"]),
+];
+
+const ADJECTIVES: &[&str] = &[
+    "Smoky",
+    "Lemony",
+    "Weeknight",
+    "Grandma's",
+    "Spicy",
+    "Creamy",
+    "Roasted",
+    "Skillet",
+    "Slow Cooker",
+    "Crispy",
+    "Cheesy",
+    "Garlic",
+];
+
+const DISHES: &[&str] = &[
+    "Chili",
+    "Casserole",
+    "Soup",
+    "Stew",
+    "Pasta Bake",
+    "Tacos",
+    "Fried Rice",
+    "Frittata",
+    "Pancakes",
+    "Meatloaf",
+    "Hash",
+    "Stir Fry",
+    "Risotto",
+    "Enchiladas",
+];
+
+const INSTRUCTIONS: &[&str] = &[
+    "Chop everything and combine in a large bowl.",
+    "Heat the oil in a pan over medium heat and cook until browned.",
+    "Stir everything together and simmer for 20 minutes.",
+    "Bake at 375 for 30 minutes or until golden.",
+    "Whisk until smooth and let it rest for 10 minutes.",
+    "Season to taste and serve warm.",
+];
+
+const EXTRA_ITEMS: &[(&str, &str)] = &[
+    ("1", "paper towels"),
+    ("2", "dish soap"),
+    ("1 bag", "coffee"),
+    ("1", "birthday candles"),
+    ("6", "sparkling water"),
+];
+
+/// A small xorshift generator. This is only for fake data so it doesn't need
+/// to be any good and we avoid pulling in a dependency for it.
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        // Xorshift gets stuck at zero.
+        Self(seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1)
+    }
+
+    fn next(&mut self) -> u64 {
+        let mut x = self.0;
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.0 = x;
+        x
+    }
+
+    /// A number in `low..=high`.
+    fn range(&mut self, low: usize, high: usize) -> usize {
+        low + (self.next() % (high - low + 1) as u64) as usize
+    }
+
+    fn pick<'a, T>(&mut self, items: &'a [T]) -> &'a T {
+        &items[self.range(0, items.len() - 1)]
+    }
+
+    /// `count` distinct indexes into a list of `len` items.
+    fn sample(&mut self, len: usize, count: usize) -> BTreeSet<usize> {
+        let count = count.min(len);
+        let mut picked = BTreeSet::new();
+        while picked.len() < count {
+            picked.insert(self.range(0, len - 1));
+        }
+        picked
+    }
+}
+
+fn fake_recipe(rng: &mut Rng, title: &str) -> String {
+    let mut text = format!("title: {}\n\nA recipe made up by kitchen seed.\n", title);
+    for _ in 0..rng.range(1, 3) {
+        text.push_str("\nstep:\n\n");
+        let count = rng.range(2, 5);
+        for idx in rng.sample(INGREDIENTS.len(), count) {
+            let (name, _, _, amounts) = INGREDIENTS[idx];
+            text.push_str(&format!("{} {}\n", rng.pick(amounts), name));
+        }
+        text.push_str(&format!("\n{}\n", rng.pick(INSTRUCTIONS)));
+    }
+    text
+}
+
+fn fake_recipes(rng: &mut Rng, count: usize) -> Vec<RecipeEntry> {
+    (0..count)
+        .map(|i| {
+            let title = format!("{} {}", rng.pick(ADJECTIVES), rng.pick(DISHES));
+            // Titles repeat so the index keeps the ids unique.
+            let id = format!("{}-{}", title.to_lowercase().replace(['\'', ' '], "-"), i);
+            RecipeEntry::new(id, fake_recipe(rng, &title))
+        })
+        .collect()
+}
+
+fn category_mappings() -> Vec<(String, String)> {
+    INGREDIENTS
+        .iter()
+        .map(|(name, category, _, _)| (name.to_string(), category.to_string()))
+        .collect()
+}
+
+fn fake_plan(rng: &mut Rng, recipe_ids: &[String]) -> Vec<(String, i32)> {
+    let count = rng.range(3, 7);
+    rng.sample(recipe_ids.len(), count)
+        .into_iter()
+        .map(|idx| (recipe_ids[idx].clone(), rng.range(1, 3) as i32))
+        .collect()
+}
+
+/// Save a few snapshots of a shopping trip so the inventory has history.
+async fn fake_inventory(
+    rng: &mut Rng,
+    app_store: &storage::SqliteStore,
+    user_id: &str,
+    date: &NaiveDate,
+) {
+    let mut filtered = BTreeSet::new();
+    let mut modified = BTreeMap::new();
+    let mut extras = Vec::new();
+    for _ in 0..rng.range(1, 3) {
+        let count = rng.range(1, 4);
+        for idx in rng.sample(INGREDIENTS.len(), count) {
+            let (name, _, measure_type, amounts) = INGREDIENTS[idx];
+            let key = IngredientKey::new(name.to_owned(), None, measure_type.to_owned());
+            if rng.range(0, 1) == 0 {
+                filtered.insert(key);
+            } else {
+                modified.insert(key, rng.pick(amounts).to_string());
+            }
+        }
+        if rng.range(0, 2) == 0 {
+            let (amt, name) = rng.pick(EXTRA_ITEMS);
+            extras.push((amt.to_string(), name.to_string()));
+        }
+        app_store
+            .save_inventory_data_for_date(
+                user_id,
+                date,
+                filtered.clone(),
+                modified.clone(),
+                extras.clone(),
+            )
+            .await
+            .expect("Failed to save inventory");
+    }
+}
+
+/// Fill the store with users named `seed1`, `seed2`, ... and their data.
+pub async fn seed_store(store_path: PathBuf, options: SeedOptions) {
+    let app_store = storage::SqliteStore::new(store_path)
+        .await
+        .expect("Unable to create app_store");
+    app_store
+        .run_migrations()
+        .await
+        .expect("Failed to run database migrations");
+    let mut rng = Rng::new(options.seed);
+    let today = chrono::Local::now().date_naive();
+    for n in 1..=options.users {
+        let user_id = format!("seed{}", n);
+        app_store
+            .store_user_creds(storage::UserCreds {
+                id: storage::UserId(user_id.clone()),
+                pass: secrecy::Secret::from(options.password.clone()),
+            })
+            .await
+            .expect("Failed to store user creds");
+        let recipes = fake_recipes(&mut rng, options.recipes);
+        let recipe_ids: Vec<String> = app_store
+            .store_recipes_for_user(&user_id, &recipes)
+            .await
+            .expect("Failed to store recipes")
+            .into_iter()
+            .map(|(id, _)| id)
+            .collect();
+        app_store
+            .save_category_mappings_for_user(&user_id, &category_mappings())
+            .await
+            .expect("Failed to store category mappings");
+        if recipe_ids.is_empty() {
+            continue;
+        }
+        for week in 0..options.weeks {
+            let date = today - chrono::Duration::weeks(week as i64);
+            let plan = fake_plan(&mut rng, &recipe_ids);
+            app_store
+                .save_meal_plan(user_id.as_str(), &plan, date)
+                .await
+                .expect("Failed to save meal plan");
+            fake_inventory(&mut rng, &app_store, &user_id, &date).await;
+        }
+        info!(user_id, recipes = recipe_ids.len(), "Seeded user");
+    }
+}
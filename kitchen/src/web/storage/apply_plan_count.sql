@@ -0,0 +1,3 @@
+insert into plan_recipes (user_id, plan_date, recipe_id, count, position)
+    values (?, ?, ?, ?, (select coalesce(max(position) + 1, 0) from plan_recipes where user_id = ? and plan_date = ?))
+    on conflict (user_id, plan_date, recipe_id) do update set count=excluded.count;
\ No newline at end of file
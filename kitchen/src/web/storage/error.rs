@@ -12,6 +12,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use recipes::RecipeEntry;
 use sqlx::Error as SqliteErr;
 use tracing::error;
 
@@ -21,10 +22,19 @@ pub enum Error {
     Protocol(String),
     BadQuery(String),
     Timeout,
+    /// Another connection held a lock for longer than the busy timeout.
+    Busy,
     NoRecords,
     Configuration(String),
     MalformedData(String),
     InternalError(String),
+    /// A batch operation failed on the entry with this id. Nothing in the
+    /// batch was written.
+    EntryFailed(String, Box<Error>),
+    /// The entries were based on a stale revision. Holds the current entries.
+    Conflict(Vec<RecipeEntry>),
+    /// The write would put the user over one of the configured limits.
+    LimitExceeded(String),
 }
 
 impl From<SqliteErr> for Error {
@@ -34,6 +44,16 @@ impl From<SqliteErr> for Error {
             SqliteErr::PoolTimedOut => Error::Timeout,
             SqliteErr::PoolClosed => Error::InternalError(format!("Pool Closed")),
             SqliteErr::WorkerCrashed => Error::InternalError(format!("Worker Crashed!")),
+            // Extended result codes keep the primary code in the low byte. 5
+            // is SQLITE_BUSY and 6 is SQLITE_LOCKED.
+            SqliteErr::Database(e)
+                if e.code()
+                    .and_then(|c| c.parse::<i32>().ok())
+                    .map(|c| c & 0xff == 5 || c & 0xff == 6)
+                    .unwrap_or(false) =>
+            {
+                Error::Busy
+            }
             SqliteErr::Database(e) => Error::InternalError(format!("{:?}", e)),
             SqliteErr::Io(e) => Error::IO(format!("{:?}", e)),
             SqliteErr::Tls(e) => Error::Protocol(format!("{:?}", e)),
@@ -53,7 +73,7 @@ impl From<SqliteErr> for Error {
                 index, source
             )),
             SqliteErr::Decode(e) => Error::MalformedData(format!("Decode error: {}", e)),
-            SqliteErr::Migrate(_) => todo!(),
+            SqliteErr::Migrate(e) => Error::InternalError(format!("Migration failed: {}", e)),
             err => {
                 error!(?err, "Unhandled Error type encountered");
                 Error::InternalError(format!("Unhandled Error type encountered {:?}", err))
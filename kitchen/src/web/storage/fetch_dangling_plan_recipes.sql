@@ -0,0 +1,7 @@
+select plan_date as "plan_date: NaiveDate", recipe_id
+    from plan_recipes
+where
+    user_id = ?
+    and recipe_id not in (select recipe_id from recipes where user_id = ?)
+    and recipe_id not in (select recipe_id from recipe_trash where user_id = ?)
+order by plan_date, recipe_id
\ No newline at end of file
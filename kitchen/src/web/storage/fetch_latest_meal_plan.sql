@@ -7,4 +7,5 @@ select plan_recipes.plan_date as "plan_date: NaiveDate", plan_recipes.recipe_id,
     inner join max_date on plan_recipes.user_id = max_date.user_id
 where
     plan_recipes.user_id = ?
-    and plan_recipes.plan_date = max_date.plan_date
\ No newline at end of file
+    and plan_recipes.plan_date = max_date.plan_date
+order by plan_recipes.position, plan_recipes.recipe_id
\ No newline at end of file
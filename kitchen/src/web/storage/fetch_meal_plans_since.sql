@@ -3,4 +3,4 @@ from plan_recipes
 where
     user_id = ?
     and date(plan_date) > ?
-order by user_id, plan_date
\ No newline at end of file
+order by user_id, plan_date, position
\ No newline at end of file
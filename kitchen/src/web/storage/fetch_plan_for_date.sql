@@ -2,4 +2,5 @@ select plan_date as "plan_date: NaiveDate", recipe_id, count
     from plan_recipes
 where
     user_id = ?
-    and plan_date = ?
\ No newline at end of file
+    and plan_date = ?
+order by position, recipe_id
\ No newline at end of file
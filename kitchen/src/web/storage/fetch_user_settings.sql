@@ -0,0 +1 @@
+select setting_key, setting_value from user_settings where user_id = ?
\ No newline at end of file
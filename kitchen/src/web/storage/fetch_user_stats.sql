@@ -0,0 +1,11 @@
+select
+    users.id as user_id,
+    (select count(*) from recipes where recipes.user_id = users.id) as "recipes!: i64",
+    (select count(*) from plan_table where plan_table.user_id = users.id) as "plans!: i64",
+    coalesce((select sum(length(cast(recipe_text as blob))) from recipes where recipes.user_id = users.id), 0)
+        + coalesce((select sum(length(cast(category_text as blob))) from categories where categories.user_id = users.id), 0)
+        + coalesce((select sum(length(cast(content as blob))) from staples where staples.user_id = users.id), 0)
+        + coalesce((select sum(length(cast(setting_value as blob))) from user_settings where user_settings.user_id = users.id), 0)
+        as "storage_bytes!: i64"
+from users
+order by users.id
\ No newline at end of file
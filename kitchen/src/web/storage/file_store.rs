@@ -11,14 +11,21 @@
 // WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
 // See the License for the specific language governing permissions and
 // limitations under the License.
+use std::collections::{BTreeMap, BTreeSet};
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, SystemTime};
+
 use async_std::{
-    fs::{read_dir, read_to_string, DirEntry, File},
-    io::{self, ReadExt},
-    path::PathBuf,
+    fs::{read_dir, read_to_string, remove_file, rename, DirEntry, File},
+    io::WriteExt,
+    path::{Path, PathBuf},
     stream::StreamExt,
+    sync::{Arc, Mutex, RwLock},
+    task,
 };
 use tracing::warn;
-use tracing::{debug, instrument};
+use tracing::{debug, info, instrument};
 
 use super::RecipeEntry;
 
@@ -43,14 +50,59 @@ impl From<std::string::FromUtf8Error> for Error {
     }
 }
 
+/// How often the watcher checks the recipe directory for changes.
+pub const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// The contents of a file along with the metadata it had when we read it.
+#[derive(Debug)]
+struct CachedFile {
+    modified: SystemTime,
+    len: u64,
+    contents: String,
+}
+
 #[derive(Clone, Debug)]
 pub struct AsyncFileStore {
     path: PathBuf,
+    editor: Option<String>,
+    cache: Arc<RwLock<BTreeMap<PathBuf, CachedFile>>>,
+    /// How many times a file was found added, changed or removed on disk
+    /// after it was cached. Our own writes don't count.
+    disk_changes: Arc<AtomicU64>,
+    /// Held while writing or refreshing so the watcher never sees one of our
+    /// writes half done.
+    write_lock: Arc<Mutex<()>>,
 }
 
 impl AsyncFileStore {
     pub fn new<P: Into<PathBuf>>(root: P) -> Self {
-        Self { path: root.into() }
+        Self {
+            path: root.into(),
+            editor: None,
+            cache: Arc::new(RwLock::new(BTreeMap::new())),
+            disk_changes: Arc::new(AtomicU64::new(0)),
+            write_lock: Arc::new(Mutex::new(())),
+        }
+    }
+
+    /// Allow `editor` to write changes back to the files on disk.
+    pub fn with_editor(mut self, editor: Option<String>) -> Self {
+        self.editor = editor;
+        self
+    }
+
+    pub fn is_writable(&self) -> bool {
+        self.editor.is_some()
+    }
+
+    /// Whether `user_id` is the user allowed to change the files.
+    pub fn is_editor(&self, user_id: &str) -> bool {
+        self.editor.as_deref() == Some(user_id)
+    }
+
+    /// The user allowed to change the files if there is one.
+    pub fn editor(&self) -> Option<&str> {
+        self.editor.as_deref()
     }
 }
 
@@ -61,6 +113,126 @@ impl AsyncFileStore {
         recipe_path.push("recipes");
         recipe_path
     }
+
+    fn get_recipe_path(&self, id: &str) -> Result<PathBuf, Error> {
+        // Recipe ids are file names so we can't allow them to escape the
+        // recipe directory or collide with our temp files.
+        if id.is_empty() || id.starts_with('.') || id.contains(|c: char| c == '/' || c == '\\') {
+            return Err(format!("Invalid recipe file name {}", id).into());
+        }
+        let mut recipe_path = self.get_recipe_path_root();
+        recipe_path.push(id);
+        Ok(recipe_path)
+    }
+
+    fn check_writable(&self) -> Result<(), Error> {
+        if self.is_writable() {
+            Ok(())
+        } else {
+            Err("The file store is read only".to_owned().into())
+        }
+    }
+
+    /// Read a file using our cached copy unless it has changed on disk since
+    /// we last read it.
+    async fn read_cached(&self, path: &Path) -> Result<String, Error> {
+        let metadata = path.metadata().await?;
+        let modified = metadata.modified()?;
+        let len = metadata.len();
+        if let Some(cached) = self.cache.read().await.get(path) {
+            if cached.modified == modified && cached.len == len {
+                return Ok(cached.contents.clone());
+            }
+            info!(path = %path.to_string_lossy(), "Reloading changed file");
+        }
+        self.disk_changes.fetch_add(1, Ordering::SeqCst);
+        let contents = read_to_string(path).await?;
+        self.cache.write().await.insert(
+            path.to_owned(),
+            CachedFile {
+                modified,
+                len,
+                contents: contents.clone(),
+            },
+        );
+        Ok(contents)
+    }
+
+    /// Remember what we just wrote to `path` so the watcher doesn't mistake
+    /// our own writes for edits made on disk.
+    async fn cache_written(&self, path: &Path, contents: &str) -> Result<(), Error> {
+        let metadata = path.metadata().await?;
+        self.cache.write().await.insert(
+            path.to_owned(),
+            CachedFile {
+                modified: metadata.modified()?,
+                len: metadata.len(),
+                contents: contents.to_owned(),
+            },
+        );
+        Ok(())
+    }
+
+    /// Reload the recipe files and return the count of changes found on disk
+    /// so far.
+    async fn refresh(&self) -> Result<u64, Error> {
+        let _guard = self.write_lock.lock().await;
+        self.get_recipes().await?;
+        Ok(self.disk_changes.load(Ordering::SeqCst))
+    }
+
+    /// Poll the recipe directory in the background so edits on disk are
+    /// picked up and removed files are dropped without a restart. `on_change`
+    /// runs after a poll when files changed on disk since the last one, even
+    /// if another read noticed the change first.
+    pub fn watch<F, Fut>(self: Arc<Self>, interval: Duration, on_change: F)
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send,
+    {
+        task::spawn(async move {
+            // The first read only fills the cache.
+            let mut seen = match self.refresh().await {
+                Ok(changes) => changes,
+                Err(err) => {
+                    warn!(?err, "Failed to read recipe files");
+                    0
+                }
+            };
+            loop {
+                task::sleep(interval).await;
+                match self.refresh().await {
+                    Ok(changes) if changes != seen => {
+                        info!("Recipe files changed on disk");
+                        seen = changes;
+                        on_change().await;
+                    }
+                    Ok(_) => (),
+                    Err(err) => warn!(?err, "Failed to refresh recipe files"),
+                }
+            }
+        });
+    }
+}
+
+/// Write the contents to a temp file next to the path and then rename it into
+/// place so readers never see a partially written file.
+#[instrument(skip(contents))]
+async fn write_atomic(path: &Path, contents: &str) -> Result<(), Error> {
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| format!("No file name in path {}", path.to_string_lossy()))?;
+    let tmp_path = path.with_file_name(format!(".{}.tmp", file_name.to_string_lossy()));
+    let mut tmp_file = File::create(&tmp_path).await?;
+    tmp_file.write_all(contents.as_bytes()).await?;
+    tmp_file.sync_all().await?;
+    drop(tmp_file);
+    if let Err(err) = rename(&tmp_path, path).await {
+        let _ = remove_file(&tmp_path).await;
+        return Err(err.into());
+    }
+    debug!("Wrote file");
+    Ok(())
 }
 
 // TODO(jwall): We need to model our own set of errors for this.
@@ -70,12 +242,8 @@ impl AsyncFileStore {
         let mut category_path = PathBuf::new();
         category_path.push(&self.path);
         category_path.push("categories.txt");
-        let category_file = File::open(&category_path).await?;
-        debug!(category_file = ?category_path, "Opened category file");
-        let mut buf_reader = io::BufReader::new(category_file);
-        let mut contents = Vec::new();
-        buf_reader.read_to_end(&mut contents).await?;
-        Ok(Some(String::from_utf8(contents)?))
+        debug!(category_file = ?category_path, "Reading category file");
+        Ok(Some(self.read_cached(&category_path).await?))
     }
 
     pub async fn get_recipes(&self) -> Result<Option<Vec<RecipeEntry>>, Error> {
@@ -84,12 +252,14 @@ impl AsyncFileStore {
         recipe_path.push("recipes");
         let mut entries = read_dir(&recipe_path).await?;
         let mut entry_vec = Vec::new();
+        let mut seen = BTreeSet::new();
         // Special files that we ignore when fetching recipes
         let filtered = vec!["menu.txt", "categories.txt"];
         while let Some(res) = entries.next().await {
             let entry: DirEntry = res?;
 
             if !entry.file_type().await?.is_dir()
+                && !entry.file_name().to_string_lossy().starts_with('.')
                 && !filtered
                     .iter()
                     .any(|&s| s == entry.file_name().to_string_lossy().to_string())
@@ -97,8 +267,9 @@ impl AsyncFileStore {
                 // add it to the entry
                 let file_name = entry.file_name().to_string_lossy().to_string();
                 debug!("adding recipe file {}", file_name);
-                let recipe_contents = read_to_string(entry.path()).await?;
-                entry_vec.push(RecipeEntry(file_name, recipe_contents, None));
+                let recipe_contents = self.read_cached(&entry.path()).await?;
+                seen.insert(entry.path());
+                entry_vec.push(RecipeEntry(file_name, recipe_contents, None, None));
             } else {
                 warn!(
                     file = %entry.path().to_string_lossy(),
@@ -106,6 +277,12 @@ impl AsyncFileStore {
                 );
             }
         }
+        let mut cache = self.cache.write().await;
+        let cached = cache.len();
+        cache.retain(|path, _| !path.starts_with(&recipe_path) || seen.contains(path));
+        if cache.len() != cached {
+            self.disk_changes.fetch_add(1, Ordering::SeqCst);
+        }
         Ok(Some(entry_vec))
     }
 
@@ -113,18 +290,53 @@ impl AsyncFileStore {
         &self,
         id: S,
     ) -> Result<Option<RecipeEntry>, Error> {
-        let mut recipe_path = self.get_recipe_path_root();
-        recipe_path.push(id.as_ref());
+        let recipe_path = match self.get_recipe_path(id.as_ref()) {
+            Ok(recipe_path) => recipe_path,
+            Err(_) => return Ok(None),
+        };
         if recipe_path.exists().await && recipe_path.is_file().await {
             debug!("Found recipe file {}", recipe_path.to_string_lossy());
-            let recipe_contents = read_to_string(recipe_path).await?;
+            let recipe_contents = self.read_cached(&recipe_path).await?;
             return Ok(Some(RecipeEntry(
                 id.as_ref().to_owned(),
                 recipe_contents,
                 None,
+                None,
             )));
         } else {
             return Ok(None);
         }
     }
+
+    #[instrument(skip_all, fields(recipe_id=entry.recipe_id()))]
+    pub async fn store_recipe(&self, entry: &RecipeEntry) -> Result<(), Error> {
+        self.check_writable()?;
+        let recipe_path = self.get_recipe_path(entry.recipe_id())?;
+        let _guard = self.write_lock.lock().await;
+        write_atomic(&recipe_path, entry.recipe_text()).await?;
+        self.cache_written(&recipe_path, entry.recipe_text()).await
+    }
+
+    #[instrument(skip(self))]
+    pub async fn delete_recipe(&self, id: &str) -> Result<(), Error> {
+        self.check_writable()?;
+        let recipe_path = self.get_recipe_path(id)?;
+        let _guard = self.write_lock.lock().await;
+        if recipe_path.exists().await {
+            remove_file(&recipe_path).await?;
+        }
+        self.cache.write().await.remove(&recipe_path);
+        Ok(())
+    }
+
+    #[instrument(skip_all)]
+    pub async fn store_categories(&self, categories: &str) -> Result<(), Error> {
+        self.check_writable()?;
+        let mut category_path = PathBuf::new();
+        category_path.push(&self.path);
+        category_path.push("categories.txt");
+        let _guard = self.write_lock.lock().await;
+        write_atomic(&category_path, categories).await?;
+        self.cache_written(&category_path, categories).await
+    }
 }
@@ -0,0 +1,1070 @@
+// Copyright 2023 Jeremy Wall (Jeremy@marzhilsltudios.com)
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//! A store that keeps everything in memory so handlers can be tested without
+//! a database. It follows what the [SqliteStore](super::SqliteStore) does
+//! closely enough for tests but nothing is ever persisted.
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use async_session::{Session, SessionStore};
+use async_trait::async_trait;
+use chrono::{NaiveDate, NaiveDateTime};
+use client_api::{
+    FreezerItem, InventoryData, InventoryOp, InventorySnapshot, NewFreezerItem, PlanChange,
+    PlanMeals, PlanRule, StoredPlanRule, TrashedRecipe,
+};
+use recipes::{IngredientKey, RecipeEntry};
+
+use super::{
+    check_pass, hash_pass, hash_refresh_token, APIStore, AuthStore, Error, Limits, Result,
+    UserCreds, UserId, REFRESH_TOKEN_DAYS, TRASH_RETENTION_DAYS,
+};
+
+fn now() -> NaiveDateTime {
+    chrono::Utc::now().naive_utc()
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+struct Inventory {
+    filtered_ingredients: BTreeSet<IngredientKey>,
+    modified_amts: BTreeMap<IngredientKey, String>,
+    extra_items: Vec<(String, String)>,
+}
+
+impl Inventory {
+    fn set_extra_item(&mut self, name: &str, amt: &str) {
+        match self.extra_items.iter_mut().find(|(n, _)| n == name) {
+            Some(item) => item.1 = amt.to_owned(),
+            None => self.extra_items.push((name.to_owned(), amt.to_owned())),
+        }
+    }
+
+    fn parts(
+        &self,
+    ) -> (
+        Vec<IngredientKey>,
+        Vec<(IngredientKey, String)>,
+        Vec<(String, String)>,
+    ) {
+        (
+            self.filtered_ingredients.iter().cloned().collect(),
+            self.modified_amts
+                .iter()
+                .map(|(key, amt)| (key.clone(), amt.clone()))
+                .collect(),
+            self.extra_items.clone(),
+        )
+    }
+}
+
+/// Everything stored for one user.
+#[derive(Debug, Default)]
+struct UserData {
+    data_version: i64,
+    categories: Option<String>,
+    category_mappings: BTreeMap<String, String>,
+    recipes: BTreeMap<String, RecipeEntry>,
+    aliases: BTreeMap<String, String>,
+    trash: BTreeMap<String, TrashedRecipe>,
+    archived: BTreeSet<String>,
+    plan_rules: Vec<StoredPlanRule>,
+    /// Every date with a plan even if it has no recipes in it.
+    plan_dates: BTreeSet<NaiveDate>,
+    /// The recipes in each plan in the order they will be cooked.
+    plans: BTreeMap<NaiveDate, Vec<(String, i32)>>,
+    plan_meals: BTreeMap<NaiveDate, PlanMeals>,
+    plan_freezer_meals: BTreeMap<NaiveDate, BTreeSet<String>>,
+    freezer: Vec<FreezerItem>,
+    cooked: BTreeMap<NaiveDate, BTreeMap<String, NaiveDate>>,
+    inventory: BTreeMap<NaiveDate, Inventory>,
+    inventory_history: BTreeMap<NaiveDate, Vec<InventorySnapshot>>,
+    staples: Option<String>,
+    settings: BTreeMap<String, String>,
+}
+
+impl UserData {
+    fn store_recipes(
+        &mut self,
+        limits: &Limits,
+        entries: &Vec<RecipeEntry>,
+    ) -> Result<Vec<(String, i64)>> {
+        let mut conflicts = Vec::new();
+        let mut seen = BTreeSet::new();
+        for entry in entries {
+            let recipe_id = recipes::slugify(entry.recipe_id());
+            if !seen.insert(recipe_id.clone()) {
+                return Err(Error::EntryFailed(
+                    entry.recipe_id().to_owned(),
+                    Box::new(Error::MalformedData(format!(
+                        "More than one recipe in the batch has the id {}",
+                        recipe_id
+                    ))),
+                ));
+            }
+            let existing = self.recipes.get(&recipe_id);
+            let current = if recipe_id == entry.recipe_id() {
+                existing
+            } else {
+                let alias_id = entry.recipe_id();
+                match (self.recipes.get(alias_id), existing) {
+                    (None, Some(current)) if self.aliases.get(alias_id) == Some(&recipe_id) => {
+                        Some(current)
+                    }
+                    (_, Some(current)) => {
+                        conflicts.push(current.clone());
+                        continue;
+                    }
+                    (renamed, None) => renamed,
+                }
+            };
+            if let (Some(revision), Some(current)) = (entry.revision(), current) {
+                if current.revision() != Some(revision) {
+                    conflicts.push(current.clone());
+                }
+            }
+        }
+        if !conflicts.is_empty() {
+            return Err(Error::Conflict(conflicts));
+        }
+        for entry in entries {
+            limits.check_recipe(entry)?;
+        }
+        // Changes are made to copies so going over the recipe limit leaves
+        // everything as it was.
+        let mut recipes = self.recipes.clone();
+        let mut aliases = self.aliases.clone();
+        let mut plans = self.plans.clone();
+        let mut revisions = Vec::new();
+        for entry in entries {
+            let recipe_id = recipes::slugify(entry.recipe_id());
+            let revision = recipes
+                .get(&recipe_id)
+                .and_then(|current| current.revision())
+                .map_or(0, |revision| revision + 1);
+            recipes.insert(
+                recipe_id.clone(),
+                RecipeEntry(
+                    recipe_id.clone(),
+                    entry.recipe_text().to_owned(),
+                    entry.category().cloned(),
+                    Some(revision),
+                ),
+            );
+            if recipe_id != entry.recipe_id() {
+                let alias_id = entry.recipe_id().to_owned();
+                recipes.remove(&alias_id);
+                for target in aliases.values_mut() {
+                    if *target == alias_id {
+                        *target = recipe_id.clone();
+                    }
+                }
+                aliases.insert(alias_id.clone(), recipe_id.clone());
+                for plan in plans.values_mut() {
+                    let moved: i32 = plan
+                        .iter()
+                        .filter(|(id, _)| *id == alias_id)
+                        .map(|(_, count)| *count)
+                        .sum();
+                    if let Some(merged) = plan.iter_mut().find(|(id, _)| *id == recipe_id) {
+                        merged.1 += moved;
+                        plan.retain(|(id, _)| *id != alias_id);
+                    } else {
+                        for (id, _) in plan.iter_mut().filter(|(id, _)| *id == alias_id) {
+                            *id = recipe_id.clone();
+                        }
+                    }
+                }
+            }
+            revisions.push((recipe_id, revision));
+        }
+        if let Some(max) = limits.max_recipes {
+            // Only new recipes count against the limit.
+            if recipes.len() > max && recipes.len() > self.recipes.len() {
+                return Err(Error::LimitExceeded(format!(
+                    "Saving these recipes would go over the limit of {} recipes",
+                    max
+                )));
+            }
+        }
+        self.recipes = recipes;
+        self.aliases = aliases;
+        self.plans = plans;
+        Ok(revisions)
+    }
+
+    fn expire_trash(&mut self) {
+        let cutoff = now() - chrono::Duration::days(TRASH_RETENTION_DAYS);
+        self.trash.retain(|_, trashed| trashed.deleted_at >= cutoff);
+    }
+
+    fn plan(&self, date: &NaiveDate) -> Option<Vec<(String, i32)>> {
+        self.plans
+            .get(date)
+            .filter(|plan| !plan.is_empty())
+            .cloned()
+    }
+
+    fn plans_where<F>(&self, include: F) -> Option<BTreeMap<NaiveDate, Vec<(String, i32)>>>
+    where
+        F: Fn(&NaiveDate) -> bool,
+    {
+        let plans: BTreeMap<NaiveDate, Vec<(String, i32)>> = self
+            .plans
+            .iter()
+            .filter(|(date, plan)| include(date) && !plan.is_empty())
+            .map(|(date, plan)| (*date, plan.clone()))
+            .collect();
+        if plans.is_empty() {
+            None
+        } else {
+            Some(plans)
+        }
+    }
+
+    fn latest_plan_date(&self) -> Option<NaiveDate> {
+        self.plans
+            .iter()
+            .rev()
+            .find(|(_, plan)| !plan.is_empty())
+            .map(|(date, _)| *date)
+    }
+
+    /// Keep a snapshot of the inventory for a date when it differs from the
+    /// last one kept.
+    fn record_inventory_snapshot(&mut self, date: &NaiveDate, inventory: InventoryData) {
+        let history = self.inventory_history.entry(*date).or_default();
+        if history.last().map(|snapshot| &snapshot.inventory) != Some(&inventory) {
+            history.push(InventorySnapshot {
+                saved_at: now(),
+                inventory,
+            });
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Data {
+    users: BTreeMap<String, UserData>,
+    passwords: BTreeMap<String, String>,
+    refresh_tokens: BTreeMap<String, (String, NaiveDateTime)>,
+    plan_shares: BTreeMap<String, (String, NaiveDate)>,
+    last_id: i64,
+}
+
+impl Data {
+    fn user(&mut self, user_id: &str) -> &mut UserData {
+        self.users.entry(user_id.to_owned()).or_default()
+    }
+
+    fn next_id(&mut self) -> i64 {
+        self.last_id += 1;
+        self.last_id
+    }
+
+    fn new_refresh_token(&mut self, user_id: &str) -> String {
+        let token = uuid::Uuid::new_v4().simple().to_string();
+        let expires_at = now() + chrono::Duration::days(REFRESH_TOKEN_DAYS);
+        self.refresh_tokens
+            .insert(hash_refresh_token(&token), (user_id.to_owned(), expires_at));
+        token
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct MemoryStore {
+    data: Arc<Mutex<Data>>,
+    sessions: async_session::MemoryStore,
+    limits: Limits,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn data(&self) -> MutexGuard<'_, Data> {
+        self.data.lock().expect("Memory store lock was poisoned")
+    }
+}
+
+#[async_trait]
+impl SessionStore for MemoryStore {
+    async fn load_session(&self, cookie_value: String) -> async_session::Result<Option<Session>> {
+        self.sessions.load_session(cookie_value).await
+    }
+
+    async fn store_session(&self, session: Session) -> async_session::Result<Option<String>> {
+        self.sessions.store_session(session).await
+    }
+
+    async fn destroy_session(&self, session: Session) -> async_session::Result {
+        self.sessions.destroy_session(session).await
+    }
+
+    async fn clear_store(&self) -> async_session::Result {
+        self.sessions.clear_store().await
+    }
+}
+
+#[async_trait]
+impl AuthStore for MemoryStore {
+    async fn check_user_creds(&self, user_creds: &UserCreds) -> Result<bool> {
+        Ok(self
+            .data()
+            .passwords
+            .get(user_creds.user_id())
+            .map_or(false, |hash| check_pass(hash, &user_creds.pass)))
+    }
+
+    async fn store_user_creds(&self, user_creds: UserCreds) -> Result<()> {
+        let hash = hash_pass(&user_creds.pass);
+        self.data()
+            .passwords
+            .insert(user_creds.user_id().to_owned(), hash);
+        Ok(())
+    }
+
+    async fn create_refresh_token(&self, user_id: &str) -> Result<String> {
+        Ok(self.data().new_refresh_token(user_id))
+    }
+
+    async fn rotate_refresh_token(&self, token: &str) -> Result<Option<(UserId, String)>> {
+        let mut data = self.data();
+        let now = now();
+        let user_id = data
+            .refresh_tokens
+            .remove(&hash_refresh_token(token))
+            .filter(|(_, expires_at)| *expires_at > now)
+            .map(|(user_id, _)| user_id);
+        data.refresh_tokens
+            .retain(|_, (_, expires_at)| *expires_at > now);
+        Ok(user_id.map(|user_id| {
+            let token = data.new_refresh_token(&user_id);
+            (UserId(user_id), token)
+        }))
+    }
+}
+
+#[async_trait]
+impl APIStore for MemoryStore {
+    fn limits(&self) -> &Limits {
+        &self.limits
+    }
+
+    async fn fetch_data_version(&self, user_id: &str) -> Result<i64> {
+        Ok(self.data().user(user_id).data_version)
+    }
+
+    async fn bump_data_version(&self, user_id: &str) -> Result<i64> {
+        let mut data = self.data();
+        let user = data.user(user_id);
+        user.data_version += 1;
+        Ok(user.data_version)
+    }
+
+    async fn get_categories_for_user(&self, user_id: &str) -> Result<Option<String>> {
+        Ok(self.data().user(user_id).categories.clone())
+    }
+
+    async fn get_category_mappings_for_user(
+        &self,
+        user_id: &str,
+    ) -> Result<Option<Vec<(String, String)>>> {
+        let mut data = self.data();
+        let mappings = &data.user(user_id).category_mappings;
+        if mappings.is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some(
+                mappings
+                    .iter()
+                    .map(|(name, category)| (name.clone(), category.clone()))
+                    .collect(),
+            ))
+        }
+    }
+
+    async fn save_category_mappings_for_user(
+        &self,
+        user_id: &str,
+        mappings: &Vec<(String, String)>,
+    ) -> Result<()> {
+        let mut data = self.data();
+        let user = data.user(user_id);
+        for (name, category) in mappings.iter() {
+            user.category_mappings
+                .insert(name.clone(), category.clone());
+        }
+        Ok(())
+    }
+
+    async fn get_recipes_for_user(&self, user_id: &str) -> Result<Option<Vec<RecipeEntry>>> {
+        Ok(Some(
+            self.data()
+                .user(user_id)
+                .recipes
+                .values()
+                .cloned()
+                .collect(),
+        ))
+    }
+
+    async fn delete_recipes_for_user(&self, user_id: &str, recipes: &Vec<String>) -> Result<()> {
+        let mut data = self.data();
+        let user = data.user(user_id);
+        for recipe_id in recipes {
+            if let Some(mut entry) = user.recipes.remove(recipe_id) {
+                entry.set_revision(None);
+                let deleted_at = now();
+                user.trash.insert(
+                    recipe_id.clone(),
+                    TrashedRecipe {
+                        entry,
+                        deleted_at,
+                        expires_at: deleted_at + chrono::Duration::days(TRASH_RETENTION_DAYS),
+                    },
+                );
+            }
+            user.aliases.retain(|_, target| target != recipe_id);
+        }
+        Ok(())
+    }
+
+    async fn fetch_trash_for_user(&self, user_id: &str) -> Result<Vec<TrashedRecipe>> {
+        let mut data = self.data();
+        let user = data.user(user_id);
+        user.expire_trash();
+        let mut trash: Vec<TrashedRecipe> = user.trash.values().cloned().collect();
+        trash.sort_by(|a, b| {
+            b.deleted_at
+                .cmp(&a.deleted_at)
+                .then_with(|| a.entry.recipe_id().cmp(b.entry.recipe_id()))
+        });
+        Ok(trash)
+    }
+
+    async fn restore_recipe_for_user(
+        &self,
+        user_id: &str,
+        recipe_id: &str,
+    ) -> Result<Option<RecipeEntry>> {
+        let mut data = self.data();
+        let user = data.user(user_id);
+        user.expire_trash();
+        let entry = match user.trash.get(recipe_id) {
+            Some(trashed) => trashed.entry.clone(),
+            None => return Ok(None),
+        };
+        if let Some(current) = user.recipes.get(recipe_id) {
+            return Err(Error::Conflict(vec![current.clone()]));
+        }
+        let (restored_id, revision) = user
+            .store_recipes(&self.limits, &vec![entry.clone()])?
+            .pop()
+            .expect("Stored recipes should have a revision");
+        user.trash.remove(recipe_id);
+        user.archived.remove(recipe_id);
+        Ok(Some(RecipeEntry(
+            restored_id,
+            entry.1,
+            entry.2,
+            Some(revision),
+        )))
+    }
+
+    async fn purge_trash_for_user(&self, user_id: &str, recipe_id: &str) -> Result<()> {
+        let mut data = self.data();
+        let user = data.user(user_id);
+        user.trash.remove(recipe_id);
+        user.archived.remove(recipe_id);
+        Ok(())
+    }
+
+    async fn fetch_archived_recipes_for_user(&self, user_id: &str) -> Result<BTreeSet<String>> {
+        Ok(self.data().user(user_id).archived.clone())
+    }
+
+    async fn set_recipe_archived_for_user(
+        &self,
+        user_id: &str,
+        recipe_id: &str,
+        archived: bool,
+    ) -> Result<()> {
+        let mut data = self.data();
+        let user = data.user(user_id);
+        if archived {
+            user.archived.insert(recipe_id.to_owned());
+        } else {
+            user.archived.remove(recipe_id);
+        }
+        Ok(())
+    }
+
+    async fn fetch_plan_share_for_user(
+        &self,
+        user_id: &str,
+        week_start: NaiveDate,
+    ) -> Result<Option<String>> {
+        Ok(self
+            .data()
+            .plan_shares
+            .iter()
+            .find(|(_, (user, week))| user == user_id && *week == week_start)
+            .map(|(token, _)| token.clone()))
+    }
+
+    async fn create_plan_share_for_user(
+        &self,
+        user_id: &str,
+        week_start: NaiveDate,
+    ) -> Result<String> {
+        if let Some(token) = self.fetch_plan_share_for_user(user_id, week_start).await? {
+            return Ok(token);
+        }
+        let token = uuid::Uuid::new_v4().simple().to_string();
+        self.data()
+            .plan_shares
+            .insert(token.clone(), (user_id.to_owned(), week_start));
+        Ok(token)
+    }
+
+    async fn delete_plan_share_for_user(&self, user_id: &str, week_start: NaiveDate) -> Result<()> {
+        self.data()
+            .plan_shares
+            .retain(|_, (user, week)| !(user == user_id && *week == week_start));
+        Ok(())
+    }
+
+    async fn fetch_plan_share(&self, token: &str) -> Result<Option<(String, NaiveDate)>> {
+        Ok(self.data().plan_shares.get(token).cloned())
+    }
+
+    async fn fetch_plan_rules_for_user(&self, user_id: &str) -> Result<Vec<StoredPlanRule>> {
+        Ok(self.data().user(user_id).plan_rules.clone())
+    }
+
+    async fn save_plan_rule_for_user(
+        &self,
+        user_id: &str,
+        rule: PlanRule,
+    ) -> Result<StoredPlanRule> {
+        let mut data = self.data();
+        let rule = StoredPlanRule {
+            id: data.next_id(),
+            rule,
+        };
+        data.user(user_id).plan_rules.push(rule.clone());
+        Ok(rule)
+    }
+
+    async fn delete_plan_rule_for_user(&self, user_id: &str, id: i64) -> Result<()> {
+        self.data()
+            .user(user_id)
+            .plan_rules
+            .retain(|rule| rule.id != id);
+        Ok(())
+    }
+
+    async fn store_recipes_for_user(
+        &self,
+        user_id: &str,
+        recipes: &Vec<RecipeEntry>,
+    ) -> Result<Vec<(String, i64)>> {
+        self.data()
+            .user(user_id)
+            .store_recipes(&self.limits, recipes)
+    }
+
+    async fn store_categories_for_user(&self, user_id: &str, categories: &str) -> Result<()> {
+        self.data().user(user_id).categories = Some(categories.to_owned());
+        Ok(())
+    }
+
+    async fn resolve_recipe_alias(&self, user_id: &str, alias_id: &str) -> Result<Option<String>> {
+        Ok(self.data().user(user_id).aliases.get(alias_id).cloned())
+    }
+
+    async fn fetch_recipe_ids_for_user(&self, user_id: &str) -> Result<BTreeSet<String>> {
+        Ok(self.data().user(user_id).recipes.keys().cloned().collect())
+    }
+
+    async fn get_recipe_entry_for_user(
+        &self,
+        user_id: &str,
+        id: &str,
+    ) -> Result<Option<RecipeEntry>> {
+        Ok(self.data().user(user_id).recipes.get(id).cloned())
+    }
+
+    async fn fetch_latest_meal_plan(&self, user_id: &str) -> Result<Option<Vec<(String, i32)>>> {
+        let mut data = self.data();
+        let user = data.user(user_id);
+        Ok(user.latest_plan_date().and_then(|date| user.plan(&date)))
+    }
+
+    async fn fetch_meal_plan_for_date(
+        &self,
+        user_id: &str,
+        date: NaiveDate,
+    ) -> Result<Option<Vec<(String, i32)>>> {
+        Ok(self.data().user(user_id).plan(&date))
+    }
+
+    async fn fetch_meal_plans_since(
+        &self,
+        user_id: &str,
+        date: NaiveDate,
+    ) -> Result<Option<BTreeMap<NaiveDate, Vec<(String, i32)>>>> {
+        Ok(self.data().user(user_id).plans_where(|d| *d > date))
+    }
+
+    async fn fetch_meal_plans_in_range(
+        &self,
+        user_id: &str,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> Result<Option<BTreeMap<NaiveDate, Vec<(String, i32)>>>> {
+        Ok(self
+            .data()
+            .user(user_id)
+            .plans_where(|d| *d >= from && *d <= to))
+    }
+
+    async fn fetch_all_meal_plans(&self, user_id: &str) -> Result<Option<Vec<NaiveDate>>> {
+        let mut data = self.data();
+        let dates = &data.user(user_id).plan_dates;
+        if dates.is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some(dates.iter().cloned().collect()))
+        }
+    }
+
+    async fn delete_meal_plan_for_date(&self, user_id: &str, date: NaiveDate) -> Result<()> {
+        let mut data = self.data();
+        let user = data.user(user_id);
+        user.plan_dates.remove(&date);
+        user.plans.remove(&date);
+        user.inventory.remove(&date);
+        user.plan_meals.remove(&date);
+        user.plan_freezer_meals.remove(&date);
+        Ok(())
+    }
+
+    async fn save_meal_plan(
+        &self,
+        user_id: &str,
+        recipe_counts: &Vec<(String, i32)>,
+        date: NaiveDate,
+    ) -> Result<()> {
+        let mut plan: Vec<(String, i32)> = Vec::with_capacity(recipe_counts.len());
+        for (id, count) in recipe_counts.iter() {
+            plan.retain(|(planned, _)| planned != id);
+            plan.push((id.clone(), *count));
+        }
+        let mut data = self.data();
+        let user = data.user(user_id);
+        user.plan_dates.insert(date);
+        user.plans.insert(date, plan);
+        Ok(())
+    }
+
+    async fn apply_plan_changes(
+        &self,
+        user_id: &str,
+        date: NaiveDate,
+        changes: &[PlanChange],
+    ) -> Result<Vec<(String, i32)>> {
+        let mut data = self.data();
+        let user = data.user(user_id);
+        user.plan_dates.insert(date);
+        let plan = user.plans.entry(date).or_default();
+        for change in changes {
+            let recipe_id = change.recipe_id();
+            let current = plan.iter_mut().find(|(id, _)| id == recipe_id);
+            let count = match change {
+                PlanChange::SetCount(_, count) => *count,
+                PlanChange::AddCount(_, amount) => {
+                    (current.as_ref().map_or(0, |(_, count)| *count) + amount).max(0)
+                }
+            };
+            match current {
+                Some(planned) => planned.1 = count,
+                None => plan.push((recipe_id.to_owned(), count)),
+            }
+        }
+        Ok(plan.clone())
+    }
+
+    async fn remove_dangling_plan_recipes(
+        &self,
+        user_id: &str,
+    ) -> Result<Vec<(NaiveDate, String)>> {
+        let mut data = self.data();
+        let user = data.user(user_id);
+        // Recipes in the trash can still be restored so we leave them in the
+        // plans.
+        let mut removed = Vec::new();
+        for (date, plan) in user.plans.iter() {
+            let mut dangling: Vec<String> = plan
+                .iter()
+                .map(|(id, _)| id)
+                .filter(|id| !user.recipes.contains_key(*id) && !user.trash.contains_key(*id))
+                .cloned()
+                .collect();
+            dangling.sort();
+            removed.extend(dangling.into_iter().map(|id| (*date, id)));
+        }
+        for (date, recipe_id) in removed.iter() {
+            if let Some(plan) = user.plans.get_mut(date) {
+                plan.retain(|(id, _)| id != recipe_id);
+            }
+            if let Some(meals) = user.plan_meals.get_mut(date) {
+                meals.remove(recipe_id);
+            }
+            if let Some(freezer_meals) = user.plan_freezer_meals.get_mut(date) {
+                freezer_meals.remove(recipe_id);
+            }
+        }
+        Ok(removed)
+    }
+
+    async fn fetch_plan_meals_for_date(&self, user_id: &str, date: NaiveDate) -> Result<PlanMeals> {
+        Ok(self
+            .data()
+            .user(user_id)
+            .plan_meals
+            .get(&date)
+            .cloned()
+            .unwrap_or_default())
+    }
+
+    async fn save_plan_meals_for_date(
+        &self,
+        user_id: &str,
+        date: NaiveDate,
+        meals: &PlanMeals,
+    ) -> Result<()> {
+        self.data()
+            .user(user_id)
+            .plan_meals
+            .insert(date, meals.clone());
+        Ok(())
+    }
+
+    async fn fetch_plan_freezer_meals_for_date(
+        &self,
+        user_id: &str,
+        date: NaiveDate,
+    ) -> Result<BTreeSet<String>> {
+        Ok(self
+            .data()
+            .user(user_id)
+            .plan_freezer_meals
+            .get(&date)
+            .cloned()
+            .unwrap_or_default())
+    }
+
+    async fn save_plan_freezer_meals_for_date(
+        &self,
+        user_id: &str,
+        date: NaiveDate,
+        recipe_ids: &BTreeSet<String>,
+    ) -> Result<()> {
+        self.data()
+            .user(user_id)
+            .plan_freezer_meals
+            .insert(date, recipe_ids.clone());
+        Ok(())
+    }
+
+    async fn fetch_freezer_items_for_user(&self, user_id: &str) -> Result<Vec<FreezerItem>> {
+        let mut items = self.data().user(user_id).freezer.clone();
+        items.sort_by_key(|item| (item.frozen_on, item.id));
+        Ok(items)
+    }
+
+    async fn add_freezer_item_for_user(
+        &self,
+        user_id: &str,
+        item: &NewFreezerItem,
+    ) -> Result<FreezerItem> {
+        let mut data = self.data();
+        let item = FreezerItem {
+            id: data.next_id(),
+            recipe_id: item.recipe_id.clone(),
+            portions: item.portions,
+            frozen_on: item.frozen_on,
+        };
+        data.user(user_id).freezer.push(item.clone());
+        Ok(item)
+    }
+
+    async fn take_freezer_portions_for_user(
+        &self,
+        user_id: &str,
+        id: i64,
+        portions: i32,
+    ) -> Result<Option<FreezerItem>> {
+        let mut data = self.data();
+        let freezer = &mut data.user(user_id).freezer;
+        let item = match freezer.iter_mut().find(|item| item.id == id) {
+            Some(item) => item,
+            None => return Err(Error::NoRecords),
+        };
+        item.portions -= portions;
+        if item.portions > 0 {
+            Ok(Some(item.clone()))
+        } else {
+            freezer.retain(|item| item.id != id);
+            Ok(None)
+        }
+    }
+
+    async fn delete_freezer_item_for_user(&self, user_id: &str, id: i64) -> Result<()> {
+        self.data()
+            .user(user_id)
+            .freezer
+            .retain(|item| item.id != id);
+        Ok(())
+    }
+
+    async fn fetch_cooked_for_date(
+        &self,
+        user_id: &str,
+        date: NaiveDate,
+    ) -> Result<BTreeSet<String>> {
+        Ok(self
+            .data()
+            .user(user_id)
+            .cooked
+            .get(&date)
+            .map(|cooked| cooked.keys().cloned().collect())
+            .unwrap_or_default())
+    }
+
+    async fn mark_cooked_for_date(
+        &self,
+        user_id: &str,
+        date: NaiveDate,
+        recipe_id: &str,
+        cooked_on: NaiveDate,
+    ) -> Result<()> {
+        self.data()
+            .user(user_id)
+            .cooked
+            .entry(date)
+            .or_default()
+            .insert(recipe_id.to_owned(), cooked_on);
+        Ok(())
+    }
+
+    async fn fetch_last_cooked_for_user(
+        &self,
+        user_id: &str,
+    ) -> Result<BTreeMap<String, NaiveDate>> {
+        let mut last_cooked: BTreeMap<String, NaiveDate> = BTreeMap::new();
+        for cooked in self.data().user(user_id).cooked.values() {
+            for (recipe_id, cooked_on) in cooked.iter() {
+                let last = last_cooked.entry(recipe_id.clone()).or_insert(*cooked_on);
+                *last = (*last).max(*cooked_on);
+            }
+        }
+        Ok(last_cooked)
+    }
+
+    async fn fetch_recipe_plan_counts_for_user(
+        &self,
+        user_id: &str,
+    ) -> Result<BTreeMap<String, i64>> {
+        let mut counts = BTreeMap::new();
+        for plan in self.data().user(user_id).plans.values() {
+            for (recipe_id, count) in plan.iter().filter(|(_, count)| *count > 0) {
+                *counts.entry(recipe_id.clone()).or_insert(0) += *count as i64;
+            }
+        }
+        Ok(counts)
+    }
+
+    async fn fetch_plan_meal_counts_for_user(
+        &self,
+        user_id: &str,
+    ) -> Result<BTreeMap<NaiveDate, i64>> {
+        Ok(self
+            .data()
+            .user(user_id)
+            .plans
+            .iter()
+            .filter(|(_, plan)| plan.iter().any(|(_, count)| *count > 0))
+            .map(|(date, plan)| {
+                let meals: i64 = plan
+                    .iter()
+                    .filter(|(_, count)| *count > 0)
+                    .map(|(_, count)| *count as i64)
+                    .sum();
+                (*date, meals)
+            })
+            .collect())
+    }
+
+    async fn fetch_inventory_for_date(
+        &self,
+        user_id: &str,
+        date: NaiveDate,
+    ) -> Result<(
+        Vec<IngredientKey>,
+        Vec<(IngredientKey, String)>,
+        Vec<(String, String)>,
+    )> {
+        Ok(self
+            .data()
+            .user(user_id)
+            .inventory
+            .get(&date)
+            .map(Inventory::parts)
+            .unwrap_or_default())
+    }
+
+    async fn fetch_latest_inventory_data(
+        &self,
+        user_id: &str,
+    ) -> Result<(
+        Vec<IngredientKey>,
+        Vec<(IngredientKey, String)>,
+        Vec<(String, String)>,
+    )> {
+        let mut data = self.data();
+        let user = data.user(user_id);
+        Ok(user
+            .latest_plan_date()
+            .and_then(|date| user.inventory.get(&date))
+            .map(Inventory::parts)
+            .unwrap_or_default())
+    }
+
+    async fn save_inventory_data_for_date(
+        &self,
+        user_id: &str,
+        date: &NaiveDate,
+        filtered_ingredients: BTreeSet<IngredientKey>,
+        modified_amts: BTreeMap<IngredientKey, String>,
+        extra_items: Vec<(String, String)>,
+    ) -> Result<()> {
+        let mut inventory = Inventory {
+            filtered_ingredients,
+            modified_amts,
+            extra_items: Vec::new(),
+        };
+        for (name, amt) in extra_items.iter() {
+            inventory.set_extra_item(name, amt);
+        }
+        let mut data = self.data();
+        let user = data.user(user_id);
+        user.record_inventory_snapshot(date, inventory.parts().into());
+        user.inventory.insert(*date, inventory);
+        Ok(())
+    }
+
+    async fn apply_inventory_ops_for_date(
+        &self,
+        user_id: &str,
+        date: &NaiveDate,
+        ops: &[InventoryOp],
+    ) -> Result<InventoryData> {
+        let mut data = self.data();
+        let user = data.user(user_id);
+        let inventory = user.inventory.entry(*date).or_default();
+        for op in ops {
+            match op {
+                InventoryOp::Check(key) => {
+                    inventory.filtered_ingredients.insert(key.clone());
+                }
+                InventoryOp::Uncheck(key) => {
+                    inventory.filtered_ingredients.remove(key);
+                }
+                InventoryOp::SetAmt(key, amt) => {
+                    inventory.modified_amts.insert(key.clone(), amt.clone());
+                }
+                InventoryOp::SetExtras(extra_items) => {
+                    inventory.extra_items.clear();
+                    for (name, amt) in extra_items.iter() {
+                        inventory.set_extra_item(name, amt);
+                    }
+                }
+                InventoryOp::Reset => *inventory = Inventory::default(),
+            }
+        }
+        let merged: InventoryData = inventory.parts().into();
+        user.record_inventory_snapshot(date, merged.clone());
+        Ok(merged)
+    }
+
+    async fn fetch_inventory_history_for_date(
+        &self,
+        user_id: &str,
+        date: NaiveDate,
+    ) -> Result<Vec<InventorySnapshot>> {
+        Ok(self
+            .data()
+            .user(user_id)
+            .inventory_history
+            .get(&date)
+            .cloned()
+            .unwrap_or_default())
+    }
+
+    async fn save_inventory_data(
+        &self,
+        user_id: &str,
+        filtered_ingredients: BTreeSet<IngredientKey>,
+        modified_amts: BTreeMap<IngredientKey, String>,
+        extra_items: Vec<(String, String)>,
+    ) -> Result<()> {
+        let today = chrono::Utc::now().date_naive();
+        let mut data = self.data();
+        let inventory = data.user(user_id).inventory.entry(today).or_default();
+        inventory.filtered_ingredients.extend(filtered_ingredients);
+        inventory.modified_amts.extend(modified_amts);
+        for (name, amt) in extra_items.iter() {
+            inventory.set_extra_item(name, amt);
+        }
+        Ok(())
+    }
+
+    async fn fetch_staples(&self, user_id: &str) -> Result<Option<String>> {
+        Ok(self.data().user(user_id).staples.clone())
+    }
+
+    async fn save_staples(&self, user_id: &str, content: &str) -> Result<()> {
+        self.data().user(user_id).staples = Some(content.to_owned());
+        Ok(())
+    }
+
+    async fn fetch_user_settings(&self, user_id: &str) -> Result<BTreeMap<String, String>> {
+        Ok(self.data().user(user_id).settings.clone())
+    }
+
+    async fn save_user_settings(
+        &self,
+        user_id: &str,
+        settings: &BTreeMap<String, String>,
+    ) -> Result<()> {
+        self.data()
+            .user(user_id)
+            .settings
+            .extend(settings.iter().map(|(k, v)| (k.clone(), v.clone())));
+        Ok(())
+    }
+}
@@ -25,27 +25,44 @@ use async_trait::async_trait;
 use axum::{
     extract::{Extension, FromRequest, RequestParts, TypedHeader},
     headers::Cookie,
-    http::StatusCode,
+    http::{Method, StatusCode},
 };
-use chrono::NaiveDate;
+use chrono::{NaiveDate, NaiveDateTime};
 use ciborium;
-use recipes::{IngredientKey, RecipeEntry};
+use client_api::{
+    FreezerItem, InstanceStats, InventoryData, InventoryOp, InventorySnapshot, NewFreezerItem,
+    PlanChange, PlanMeals, PlanRule, StoredPlanRule, TrashedRecipe, UserStats,
+};
+use recipes::{IngredientKey, MealType, RecipeEntry};
 use secrecy::{ExposeSecret, Secret};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use sqlx::{
     self,
-    sqlite::{SqliteConnectOptions, SqliteJournalMode},
+    sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePoolOptions, SqliteSynchronous},
     SqlitePool,
 };
 use tracing::{debug, error, info, instrument};
 
 mod error;
 pub mod file_store;
+#[cfg(test)]
+pub mod memory;
 
 pub use error::*;
 
 pub const AXUM_SESSION_COOKIE_NAME: &'static str = "kitchen-session-cookie";
 
+/// How long deleted recipes stay in the trash.
+pub const TRASH_RETENTION_DAYS: i64 = 30;
+
+/// How long a session lasts after logging in.
+pub const SESSION_HOURS: i64 = 12;
+
+/// How long a device that is remembered can get new sessions without logging
+/// in again.
+pub const REFRESH_TOKEN_DAYS: i64 = 30;
+
 // TODO(jwall): Should this move to the recipe crate?
 #[derive(Debug, Serialize, Deserialize)]
 pub struct UserId(pub String);
@@ -74,6 +91,14 @@ fn make_id_key(cookie_value: &str) -> async_session::Result<String> {
     Ok(Session::id_from_cookie_value(cookie_value)?)
 }
 
+fn hash_pass(pass: &Secret<String>) -> String {
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(pass.expose_secret().as_bytes(), &salt)
+        .expect("failed to hash password")
+        .to_string()
+}
+
 #[instrument(skip_all, fields(hash=payload))]
 fn check_pass(payload: &String, pass: &Secret<String>) -> bool {
     let parsed_hash = PasswordHash::new(&payload).expect("Invalid Password Hash");
@@ -86,8 +111,24 @@ fn check_pass(payload: &String, pass: &Secret<String>) -> bool {
     check.is_ok()
 }
 
+/// Refresh tokens are only stored hashed so a copy of the database can't be
+/// used to log in. They are random so a plain hash is enough.
+pub fn hash_refresh_token(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
 #[async_trait]
-pub trait APIStore {
+pub trait APIStore: Send + Sync {
+    /// The limits enforced on every user's data.
+    fn limits(&self) -> &Limits;
+
+    /// The version of everything stored for a user. It starts at 0 and goes
+    /// up by one on every write so clients can tell when their copy is stale.
+    async fn fetch_data_version(&self, user_id: &str) -> Result<i64>;
+
+    /// Record a write for the user and return their new data version.
+    async fn bump_data_version(&self, user_id: &str) -> Result<i64>;
+
     async fn get_categories_for_user(&self, user_id: &str) -> Result<Option<String>>;
 
     async fn get_category_mappings_for_user(
@@ -103,57 +144,225 @@ pub trait APIStore {
 
     async fn get_recipes_for_user(&self, user_id: &str) -> Result<Option<Vec<RecipeEntry>>>;
 
+    /// Move the recipes to the trash. They can be restored until they are
+    /// older than [TRASH_RETENTION_DAYS].
     async fn delete_recipes_for_user(&self, user_id: &str, recipes: &Vec<String>) -> Result<()>;
 
-    async fn store_recipes_for_user(&self, user_id: &str, recipes: &Vec<RecipeEntry>)
-        -> Result<()>;
+    /// The recipes in the trash, most recently deleted first.
+    async fn fetch_trash_for_user(&self, user_id: &str) -> Result<Vec<TrashedRecipe>>;
+
+    /// Move a recipe out of the trash returning the restored entry or None if
+    /// it isn't in the trash. Fails with a Conflict if a recipe with the same
+    /// id exists.
+    async fn restore_recipe_for_user(
+        &self,
+        user_id: &str,
+        recipe_id: &str,
+    ) -> Result<Option<RecipeEntry>>;
+
+    /// Remove a recipe from the trash for good.
+    async fn purge_trash_for_user(&self, user_id: &str, recipe_id: &str) -> Result<()>;
+
+    /// The ids of the recipes the user has archived.
+    async fn fetch_archived_recipes_for_user(&self, user_id: &str) -> Result<BTreeSet<String>>;
+
+    /// Archive or unarchive a recipe. Archived recipes are kept but left out
+    /// of planning.
+    async fn set_recipe_archived_for_user(
+        &self,
+        user_id: &str,
+        recipe_id: &str,
+        archived: bool,
+    ) -> Result<()>;
+
+    /// The token a user's plans for the week starting on `week_start` are
+    /// shared with if they are shared.
+    async fn fetch_plan_share_for_user(
+        &self,
+        user_id: &str,
+        week_start: NaiveDate,
+    ) -> Result<Option<String>>;
+
+    /// Share a user's plans for the week starting on `week_start` returning
+    /// the token for the share. Sharing a week again keeps the same token.
+    async fn create_plan_share_for_user(
+        &self,
+        user_id: &str,
+        week_start: NaiveDate,
+    ) -> Result<String>;
+
+    /// Stop sharing a week. Links with the old token stop working.
+    async fn delete_plan_share_for_user(&self, user_id: &str, week_start: NaiveDate) -> Result<()>;
+
+    /// The user and week start a share token is for.
+    async fn fetch_plan_share(&self, token: &str) -> Result<Option<(String, NaiveDate)>>;
+
+    async fn fetch_plan_rules_for_user(&self, user_id: &str) -> Result<Vec<StoredPlanRule>>;
+
+    /// Add a planning rule returning it with its new id.
+    async fn save_plan_rule_for_user(
+        &self,
+        user_id: &str,
+        rule: PlanRule,
+    ) -> Result<StoredPlanRule>;
+
+    async fn delete_plan_rule_for_user(&self, user_id: &str, id: i64) -> Result<()>;
+
+    /// Store the recipes returning the normalized id and new revision for each
+    /// of them in order. Recipes with ids that aren't normalized are renamed
+    /// and their old id is kept as an alias. Fails with a Conflict if any
+    /// recipe was based on a stale revision.
+    async fn store_recipes_for_user(
+        &self,
+        user_id: &str,
+        recipes: &Vec<RecipeEntry>,
+    ) -> Result<Vec<(String, i64)>>;
 
     async fn store_categories_for_user(&self, user_id: &str, categories: &str) -> Result<()>;
 
-    async fn get_recipe_entry_for_user<S: AsRef<str> + Send>(
+    /// Look up the current id for a recipe id that was renamed.
+    async fn resolve_recipe_alias(&self, user_id: &str, alias_id: &str) -> Result<Option<String>>;
+
+    /// The ids of every recipe the user has.
+    async fn fetch_recipe_ids_for_user(&self, user_id: &str) -> Result<BTreeSet<String>>;
+
+    async fn get_recipe_entry_for_user(
         &self,
-        user_id: S,
-        id: S,
+        user_id: &str,
+        id: &str,
     ) -> Result<Option<RecipeEntry>>;
 
-    async fn fetch_latest_meal_plan<S: AsRef<str> + Send>(
-        &self,
-        user_id: S,
-    ) -> Result<Option<Vec<(String, i32)>>>;
+    async fn fetch_latest_meal_plan(&self, user_id: &str) -> Result<Option<Vec<(String, i32)>>>;
 
-    async fn fetch_meal_plan_for_date<S: AsRef<str> + Send>(
+    async fn fetch_meal_plan_for_date(
         &self,
-        user_id: S,
+        user_id: &str,
         date: NaiveDate,
     ) -> Result<Option<Vec<(String, i32)>>>;
 
-    async fn fetch_meal_plans_since<S: AsRef<str> + Send>(
+    async fn fetch_meal_plans_since(
         &self,
-        user_id: S,
+        user_id: &str,
         date: NaiveDate,
     ) -> Result<Option<BTreeMap<NaiveDate, Vec<(String, i32)>>>>;
 
-    async fn fetch_all_meal_plans<S: AsRef<str> + Send>(
+    /// The plans between `from` and `to` inclusive.
+    async fn fetch_meal_plans_in_range(
+        &self,
+        user_id: &str,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> Result<Option<BTreeMap<NaiveDate, Vec<(String, i32)>>>>;
+
+    async fn fetch_all_meal_plans(&self, user_id: &str) -> Result<Option<Vec<NaiveDate>>>;
+
+    async fn delete_meal_plan_for_date(&self, user_id: &str, date: NaiveDate) -> Result<()>;
+
+    async fn save_meal_plan(
+        &self,
+        user_id: &str,
+        recipe_counts: &Vec<(String, i32)>,
+        date: NaiveDate,
+    ) -> Result<()>;
+
+    /// Apply changes to the plan for a date on top of what is stored and
+    /// return the merged plan.
+    async fn apply_plan_changes(
         &self,
-        user_id: S,
-    ) -> Result<Option<Vec<NaiveDate>>>;
+        user_id: &str,
+        date: NaiveDate,
+        changes: &[PlanChange],
+    ) -> Result<Vec<(String, i32)>>;
 
-    async fn delete_meal_plan_for_date<S: AsRef<str> + Send>(
+    /// Remove recipes from every plan that no longer correspond to one of
+    /// the user's recipes and return the plan dates and ids that were removed.
+    async fn remove_dangling_plan_recipes(&self, user_id: &str)
+        -> Result<Vec<(NaiveDate, String)>>;
+
+    /// The meals recipes in the plan for a date fill when they differ from
+    /// the meal in the recipe's metadata.
+    async fn fetch_plan_meals_for_date(&self, user_id: &str, date: NaiveDate) -> Result<PlanMeals>;
+
+    /// Replace the planned meals for a date.
+    async fn save_plan_meals_for_date(
         &self,
-        user_id: S,
+        user_id: &str,
         date: NaiveDate,
+        meals: &PlanMeals,
     ) -> Result<()>;
 
-    async fn save_meal_plan<S: AsRef<str> + Send>(
+    /// The recipes in the plan for a date that come from the freezer.
+    async fn fetch_plan_freezer_meals_for_date(
         &self,
-        user_id: S,
-        recipe_counts: &Vec<(String, i32)>,
+        user_id: &str,
+        date: NaiveDate,
+    ) -> Result<BTreeSet<String>>;
+
+    /// Replace the recipes in the plan for a date that come from the
+    /// freezer.
+    async fn save_plan_freezer_meals_for_date(
+        &self,
+        user_id: &str,
+        date: NaiveDate,
+        recipe_ids: &BTreeSet<String>,
+    ) -> Result<()>;
+
+    async fn fetch_freezer_items_for_user(&self, user_id: &str) -> Result<Vec<FreezerItem>>;
+
+    async fn add_freezer_item_for_user(
+        &self,
+        user_id: &str,
+        item: &NewFreezerItem,
+    ) -> Result<FreezerItem>;
+
+    /// Take portions out of a freezer item. The item is removed once every
+    /// portion has been taken so this returns None then. Items the user
+    /// doesn't have are an [Error::NoRecords].
+    async fn take_freezer_portions_for_user(
+        &self,
+        user_id: &str,
+        id: i64,
+        portions: i32,
+    ) -> Result<Option<FreezerItem>>;
+
+    async fn delete_freezer_item_for_user(&self, user_id: &str, id: i64) -> Result<()>;
+
+    /// The recipes in the plan for a date that have been cooked.
+    async fn fetch_cooked_for_date(
+        &self,
+        user_id: &str,
+        date: NaiveDate,
+    ) -> Result<BTreeSet<String>>;
+
+    async fn mark_cooked_for_date(
+        &self,
+        user_id: &str,
         date: NaiveDate,
+        recipe_id: &str,
+        cooked_on: NaiveDate,
     ) -> Result<()>;
 
-    async fn fetch_inventory_for_date<S: AsRef<str> + Send>(
+    /// The date each recipe was last cooked.
+    async fn fetch_last_cooked_for_user(
+        &self,
+        user_id: &str,
+    ) -> Result<BTreeMap<String, NaiveDate>>;
+
+    /// How many times each recipe has been planned across every plan.
+    async fn fetch_recipe_plan_counts_for_user(
+        &self,
+        user_id: &str,
+    ) -> Result<BTreeMap<String, i64>>;
+
+    /// How many meals are in each plan that has any.
+    async fn fetch_plan_meal_counts_for_user(
         &self,
-        user_id: S,
+        user_id: &str,
+    ) -> Result<BTreeMap<NaiveDate, i64>>;
+
+    async fn fetch_inventory_for_date(
+        &self,
+        user_id: &str,
         date: NaiveDate,
     ) -> Result<(
         Vec<IngredientKey>,
@@ -161,35 +370,61 @@ pub trait APIStore {
         Vec<(String, String)>,
     )>;
 
-    async fn fetch_latest_inventory_data<S: AsRef<str> + Send>(
+    async fn fetch_latest_inventory_data(
         &self,
-        user_id: S,
+        user_id: &str,
     ) -> Result<(
         Vec<IngredientKey>,
         Vec<(IngredientKey, String)>,
         Vec<(String, String)>,
     )>;
 
-    async fn save_inventory_data_for_date<S: AsRef<str> + Send>(
+    /// Save the inventory for a date. Every change is also kept as a
+    /// snapshot in the inventory history.
+    async fn save_inventory_data_for_date(
         &self,
-        user_id: S,
+        user_id: &str,
         date: &NaiveDate,
         filtered_ingredients: BTreeSet<IngredientKey>,
         modified_amts: BTreeMap<IngredientKey, String>,
         extra_items: Vec<(String, String)>,
     ) -> Result<()>;
 
-    async fn save_inventory_data<S: AsRef<str> + Send>(
+    /// Apply item level changes to the inventory for a date on top of what
+    /// is stored and return the merged inventory.
+    async fn apply_inventory_ops_for_date(
+        &self,
+        user_id: &str,
+        date: &NaiveDate,
+        ops: &[InventoryOp],
+    ) -> Result<InventoryData>;
+
+    /// The saved snapshots of the inventory for a date, oldest first.
+    async fn fetch_inventory_history_for_date(
+        &self,
+        user_id: &str,
+        date: NaiveDate,
+    ) -> Result<Vec<InventorySnapshot>>;
+
+    async fn save_inventory_data(
         &self,
-        user_id: S,
+        user_id: &str,
         filtered_ingredients: BTreeSet<IngredientKey>,
         modified_amts: BTreeMap<IngredientKey, String>,
         extra_items: Vec<(String, String)>,
     ) -> Result<()>;
 
-    async fn fetch_staples<S: AsRef<str> + Send>(&self, user_id: S) -> Result<Option<String>>;
+    async fn fetch_staples(&self, user_id: &str) -> Result<Option<String>>;
+
+    async fn save_staples(&self, user_id: &str, content: &str) -> Result<()>;
 
-    async fn save_staples<S: AsRef<str> + Send>(&self, user_id: S, content: S) -> Result<()>;
+    async fn fetch_user_settings(&self, user_id: &str) -> Result<BTreeMap<String, String>>;
+
+    async fn save_user_settings(
+        &self,
+        user_id: &str,
+        settings: &BTreeMap<String, String>,
+    ) -> Result<()>;
 }
 
 #[async_trait]
@@ -199,6 +434,55 @@ pub trait AuthStore: SessionStore {
 
     /// Insert or update user credentials in the user store.
     async fn store_user_creds(&self, user_creds: UserCreds) -> Result<()>;
+
+    /// Issue a refresh token that can be traded for a new session until it
+    /// is [REFRESH_TOKEN_DAYS] old.
+    async fn create_refresh_token(&self, user_id: &str) -> Result<String>;
+
+    /// Trade a refresh token for a new one. Each token only works once so a
+    /// stolen token stops working as soon as either holder uses it. Returns
+    /// the user and the new token or None if the token is unknown or expired.
+    async fn rotate_refresh_token(&self, token: &str) -> Result<Option<(UserId, String)>>;
+}
+
+/// The user logged in with the session cookie in a request if there is one.
+#[instrument(skip_all)]
+pub async fn session_user_id<B: Send>(req: &mut RequestParts<B>) -> Option<UserId> {
+    let Extension(session_store) = Extension::<Arc<SqliteStore>>::from_request(req)
+        .await
+        .expect("No Session store configured!");
+    let cookies = Option::<TypedHeader<Cookie>>::from_request(req)
+        .await
+        .expect("Unable to get headers fromrequest");
+    // TODO(jwall): We should really validate the expiration and such on this cookie.
+    if let Some(session_cookie) = cookies
+        .as_ref()
+        .and_then(|c| c.get(AXUM_SESSION_COOKIE_NAME))
+    {
+        debug!(?session_cookie, "processing session cookie");
+        match session_store.load_session(session_cookie.to_owned()).await {
+            Ok(Some(session)) => {
+                if let Some(user_id) = session.get::<UserId>("user_id") {
+                    info!(user_id = user_id.0, "Found Authenticated session");
+                    Some(user_id)
+                } else {
+                    error!("No user id found in session");
+                    None
+                }
+            }
+            Ok(None) => {
+                debug!("no session defined in headers.");
+                None
+            }
+            Err(e) => {
+                debug!(err=?e, "error deserializing session");
+                None
+            }
+        }
+    } else {
+        debug!("no cookies defined in headers.");
+        None
+    }
 }
 
 #[async_trait]
@@ -210,60 +494,217 @@ where
 
     #[instrument(skip_all)]
     async fn from_request(req: &mut RequestParts<B>) -> std::result::Result<Self, Self::Rejection> {
-        let Extension(session_store) = Extension::<Arc<SqliteStore>>::from_request(req)
-            .await
-            .expect("No Session store configured!");
-        let cookies = Option::<TypedHeader<Cookie>>::from_request(req)
+        if let Some(user_id) = session_user_id(req).await {
+            return Ok(Self::FoundUserId(user_id));
+        }
+        // Routers built without an anonymous access setting serve the
+        // file store like they always have.
+        let anonymous = Extension::<Arc<AnonymousAccess>>::from_request(req)
             .await
-            .expect("Unable to get headers fromrequest");
-        // TODO(jwall): We should really validate the expiration and such on this cookie.
-        if let Some(session_cookie) = cookies
-            .as_ref()
-            .and_then(|c| c.get(AXUM_SESSION_COOKIE_NAME))
-        {
-            debug!(?session_cookie, "processing session cookie");
-            match session_store.load_session(session_cookie.to_owned()).await {
-                Ok(Some(session)) => {
-                    if let Some(user_id) = session.get::<UserId>("user_id") {
-                        info!(user_id = user_id.0, "Found Authenticated session");
-                        return Ok(Self::FoundUserId(user_id));
-                    } else {
-                        error!("No user id found in session");
-                        return Ok(Self::NoUserId);
-                    }
-                }
-                Ok(None) => {
-                    debug!("no session defined in headers.");
-                    return Ok(Self::NoUserId);
-                }
-                Err(e) => {
-                    debug!(err=?e, "error deserializing session");
-                    return Ok(Self::NoUserId);
+            .ok();
+        let read_only = req.method() == Method::GET || req.method() == Method::HEAD;
+        match anonymous.as_ref().map(|Extension(a)| a.as_ref()) {
+            Some(AnonymousAccess::User(user_id))
+                if read_only && is_public_route(req.uri().path()) =>
+            {
+                debug!(user_id, "Showing published recipes to anonymous user");
+                Ok(Self::FoundUserId(UserId(user_id.clone())))
+            }
+            _ => Ok(Self::NoUserId),
+        }
+    }
+}
+
+/// What people who aren't logged in can see.
+#[derive(Clone, Debug, PartialEq)]
+pub enum AnonymousAccess {
+    /// The recipes and categories in the recipe directory.
+    Files,
+    /// Nothing. Everything but logging in and share links needs a login.
+    Off,
+    /// A user's recipes and categories, read only. See [is_public_route].
+    User(String),
+}
+
+/// The read only routes that show a user's recipes to people who aren't
+/// logged in when anonymous access points at that user. Paths can be given
+/// with or without the api and version prefix.
+pub fn is_public_route(path: &str) -> bool {
+    let path = path.strip_prefix("/api").unwrap_or(path);
+    let path = path
+        .strip_prefix("/v1")
+        .or_else(|| path.strip_prefix("/v2"))
+        .unwrap_or(path);
+    let segments = path.trim_matches('/').split('/').collect::<Vec<&str>>();
+    matches!(
+        segments.as_slice(),
+        ["recipes"]
+            | ["recipes", "index" | "parsed"]
+            | ["recipe", _]
+            | ["recipe", _, "parsed" | "cooklang" | "prep"]
+            | ["recipe", _, "steps", _]
+            | ["categories"]
+            | ["ui", "recipe", "view", _]
+    )
+}
+
+impl Default for AnonymousAccess {
+    fn default() -> Self {
+        AnonymousAccess::Files
+    }
+}
+
+impl FromStr for AnonymousAccess {
+    type Err = String;
+
+    /// Parses `files`, `off` or `user:<user id>`.
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "files" => Ok(AnonymousAccess::Files),
+            "off" => Ok(AnonymousAccess::Off),
+            s => match s.strip_prefix("user:") {
+                Some(user_id) if !user_id.is_empty() => {
+                    Ok(AnonymousAccess::User(user_id.to_owned()))
                 }
+                _ => Err(format!(
+                    "Expected files, off or user:<user id> but got {}",
+                    s
+                )),
+            },
+        }
+    }
+}
+
+/// Per user limits on stored data. A limit of None means unlimited.
+#[derive(Clone, Debug, Default)]
+pub struct Limits {
+    pub max_recipes: Option<usize>,
+    /// The maximum size of a single recipe's text in bytes.
+    pub max_recipe_bytes: Option<usize>,
+    /// The maximum size in bytes of a single uploaded image.
+    pub max_image_bytes: Option<usize>,
+}
+
+impl Limits {
+    /// Check a recipe entry against the size limits.
+    pub fn check_recipe(&self, entry: &RecipeEntry) -> Result<()> {
+        if let Some(max) = self.max_recipe_bytes {
+            if entry.recipe_text().len() > max {
+                return Err(Error::LimitExceeded(format!(
+                    "Recipe {} is larger than the limit of {} bytes",
+                    entry.recipe_id(),
+                    max
+                )));
             }
-        } else {
-            debug!("no cookies defined in headers.");
-            return Ok(Self::NoUserId);
+        }
+        Ok(())
+    }
+
+    /// Check the size of an uploaded image against the image limit.
+    pub fn check_image(&self, bytes: usize) -> Result<()> {
+        match self.max_image_bytes {
+            Some(max) if bytes > max => Err(Error::LimitExceeded(format!(
+                "Image is larger than the limit of {} bytes",
+                max
+            ))),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Connection settings for the sqlite database.
+#[derive(Clone, Debug)]
+pub struct DbConfig {
+    pub journal_mode: SqliteJournalMode,
+    pub synchronous: SqliteSynchronous,
+    /// How long a connection waits for a lock held by another connection
+    /// before giving up with a "database is locked" error.
+    pub busy_timeout: std::time::Duration,
+    pub max_connections: u32,
+    pub min_connections: u32,
+    /// How long a request waits for a free connection from the pool.
+    pub acquire_timeout: std::time::Duration,
+}
+
+impl Default for DbConfig {
+    fn default() -> Self {
+        // WAL lets readers carry on while a write is in progress and NORMAL
+        // sync is safe with WAL. Writers still take turns so the busy timeout
+        // has to cover a save from another device.
+        Self {
+            journal_mode: SqliteJournalMode::Wal,
+            synchronous: SqliteSynchronous::Normal,
+            busy_timeout: std::time::Duration::from_secs(5),
+            max_connections: 10,
+            min_connections: 0,
+            acquire_timeout: std::time::Duration::from_secs(30),
         }
     }
 }
 
+/// The history pruned for a user.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UserPrune {
+    pub user_id: String,
+    /// Plans, inventory snapshots, sessions and refresh tokens from before
+    /// this date were pruned.
+    pub cutoff: NaiveDate,
+    pub plans: Vec<NaiveDate>,
+    pub inventory_snapshots: i64,
+    pub sessions: i64,
+    pub refresh_tokens: i64,
+}
+
+/// What pruning old history removed or would remove on a dry run.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PruneReport {
+    pub users: Vec<UserPrune>,
+    /// Expired sessions and sessions without a user from before the
+    /// instance default cutoff.
+    pub sessions: i64,
+    pub refresh_tokens: i64,
+}
+
 #[derive(Clone, Debug)]
 pub struct SqliteStore {
     pool: Arc<SqlitePool>,
     url: String,
+    limits: Limits,
 }
 
 impl SqliteStore {
     pub async fn new<P: AsRef<Path>>(path: P) -> sqlx::Result<Self> {
+        Self::open(path, &DbConfig::default()).await
+    }
+
+    pub async fn open<P: AsRef<Path>>(path: P, config: &DbConfig) -> sqlx::Result<Self> {
         std::fs::create_dir_all(&path)?;
         let url = format!("sqlite://{}/store.db", path.as_ref().to_string_lossy());
         let options = SqliteConnectOptions::from_str(&url)?
-            .journal_mode(SqliteJournalMode::Wal)
+            .journal_mode(config.journal_mode)
+            .synchronous(config.synchronous)
+            .busy_timeout(config.busy_timeout)
             .create_if_missing(true);
-        info!(?options, "Connecting to sqlite db");
-        let pool = Arc::new(sqlx::SqlitePool::connect_with(options).await?);
-        Ok(Self { pool, url })
+        info!(?options, ?config, "Connecting to sqlite db");
+        let pool = Arc::new(
+            SqlitePoolOptions::new()
+                .max_connections(config.max_connections)
+                .min_connections(config.min_connections)
+                .acquire_timeout(config.acquire_timeout)
+                .connect_with(options)
+                .await?,
+        );
+        Ok(Self {
+            pool,
+            url,
+            limits: Limits::default(),
+        })
+    }
+
+    /// Enforce these limits on every user's data.
+    pub fn with_limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
     }
 
     #[instrument(fields(conn_string=self.url), skip_all)]
@@ -274,309 +715,1603 @@ impl SqliteStore {
             .await?;
         Ok(())
     }
-}
 
-#[async_trait]
-impl SessionStore for SqliteStore {
-    #[instrument(fields(conn_string=self.url), skip_all)]
-    async fn load_session(&self, cookie_value: String) -> async_session::Result<Option<Session>> {
-        let id = make_id_key(&cookie_value)?;
-        debug!(id, "fetching session from sqlite");
-        if let Some(payload) =
-            sqlx::query_scalar!("select session_value from sessions where id = ?", id)
-                .fetch_optional(self.pool.as_ref())
-                .await?
-        {
-            debug!(sesion_id = id, "found session key");
-            let session: Session = ciborium::de::from_reader(payload.as_slice())?;
-            return Ok(Some(session));
-        }
-        return Ok(None);
+    /// Run `sql` as is so tests can set up rows the api would never write.
+    #[cfg(test)]
+    pub async fn execute_sql(&self, sql: &str) -> Result<()> {
+        sqlx::query(sql).execute(self.pool.as_ref()).await?;
+        Ok(())
     }
 
-    #[instrument(fields(conn_string=self.url), skip_all)]
-    async fn store_session(&self, session: Session) -> async_session::Result<Option<String>> {
-        let id = session.id();
-        let mut payload: Vec<u8> = Vec::new();
-        ciborium::ser::into_writer(&session, &mut payload)?;
-        sqlx::query!(
-            "insert into sessions (id, session_value) values (?, ?)",
-            id,
-            payload
-        )
-        .execute(self.pool.as_ref())
-        .await?;
-        debug!(sesion_id = id, "successfully inserted session key");
-        return Ok(session.into_cookie_value());
+    /// Hold the database write lock until the returned transaction is
+    /// dropped so tests can make other connections wait on it.
+    #[cfg(test)]
+    pub async fn lock_for_writes(&self) -> Result<sqlx::Transaction<'static, sqlx::Sqlite>> {
+        let mut transaction = self.pool.begin().await?;
+        sqlx::query("DELETE FROM sessions WHERE 0")
+            .execute(&mut transaction)
+            .await?;
+        Ok(transaction)
     }
 
+    /// The migrations that haven't been run on the database yet. They run
+    /// when the server starts.
     #[instrument(fields(conn_string=self.url), skip_all)]
-    async fn destroy_session(&self, session: Session) -> async_session::Result {
-        let id = session.id();
-        sqlx::query!("delete from sessions where id = ?", id,)
-            .execute(self.pool.as_ref())
-            .await?;
-        return Ok(());
+    pub async fn pending_migrations(&self) -> Result<Vec<String>> {
+        use sqlx::migrate::Migrate;
+        let mut conn = self.pool.acquire().await?;
+        conn.ensure_migrations_table()
+            .await
+            .map_err(sqlx::Error::from)?;
+        let applied: BTreeSet<i64> = conn
+            .list_applied_migrations()
+            .await
+            .map_err(sqlx::Error::from)?
+            .into_iter()
+            .map(|migration| migration.version)
+            .collect();
+        Ok(sqlx::migrate!("./migrations")
+            .iter()
+            .filter(|migration| !migration.migration_type.is_down_migration())
+            .filter(|migration| !applied.contains(&migration.version))
+            .map(|migration| format!("{}_{}", migration.version, migration.description))
+            .collect())
     }
 
+    /// Usage numbers for every user on this instance for operators.
     #[instrument(fields(conn_string=self.url), skip_all)]
-    async fn clear_store(&self) -> async_session::Result {
-        sqlx::query!("delete from sessions")
-            .execute(self.pool.as_ref())
+    pub async fn instance_stats(&self) -> Result<InstanceStats> {
+        struct Row {
+            user_id: String,
+            recipes: i64,
+            plans: i64,
+            storage_bytes: i64,
+        }
+        let rows = sqlx::query_file_as!(Row, "src/web/storage/fetch_user_stats.sql")
+            .fetch_all(self.pool.as_ref())
             .await?;
-        return Ok(());
-    }
-}
-
-#[async_trait]
-impl AuthStore for SqliteStore {
-    #[instrument(fields(user=%user_creds.id.0, conn_string=self.url), skip_all)]
-    async fn check_user_creds(&self, user_creds: &UserCreds) -> Result<bool> {
-        let id = user_creds.user_id().to_owned();
-        if let Some(payload) =
-            sqlx::query_scalar!("select password_hashed from users where id = ?", id)
-                .fetch_optional(self.pool.as_ref())
-                .await?
+        // Sessions are stored as opaque blobs so we have to decode them to find
+        // out who they belong to.
+        let mut sessions: BTreeMap<String, i64> = BTreeMap::new();
+        let mut anonymous_sessions = 0;
+        for payload in sqlx::query_scalar!("select session_value from sessions")
+            .fetch_all(self.pool.as_ref())
+            .await?
         {
-            debug!("Testing password for user");
-            return Ok(check_pass(&payload, &user_creds.pass));
+            let session: Session = match ciborium::de::from_reader(payload.as_slice()) {
+                Ok(session) => session,
+                Err(err) => {
+                    error!(?err, "Unable to decode session");
+                    continue;
+                }
+            };
+            if session.is_expired() {
+                continue;
+            }
+            match session.get::<UserId>("user_id") {
+                Some(UserId(user_id)) => *sessions.entry(user_id).or_insert(0) += 1,
+                None => anonymous_sessions += 1,
+            }
         }
-        Ok(false)
-    }
-
-    #[instrument(fields(user=%user_creds.id.0, conn_string=self.url), skip_all)]
-    async fn store_user_creds(&self, user_creds: UserCreds) -> Result<()> {
-        let salt = SaltString::generate(&mut OsRng);
-        let password_hash = Argon2::default()
-            .hash_password(user_creds.pass.expose_secret().as_bytes(), &salt)
-            .expect("failed to hash password");
-        let id = user_creds.user_id().to_owned();
-        let password_hashed = password_hash.to_string();
-        debug!("adding password for user");
-        sqlx::query!(
-            "insert into users (id, password_hashed) values (?, ?)",
-            id,
-            password_hashed,
-        )
-        .execute(self.pool.as_ref())
-        .await?;
-        Ok(())
+        let users = rows
+            .into_iter()
+            .map(|row| UserStats {
+                sessions: sessions.get(&row.user_id).cloned().unwrap_or(0),
+                user_id: row.user_id,
+                recipes: row.recipes,
+                plans: row.plans,
+                storage_bytes: row.storage_bytes,
+            })
+            .collect();
+        Ok(InstanceStats::new(users, anonymous_sessions))
     }
-}
 
-// TODO(jwall): We need to do some serious error modeling here.
-#[async_trait]
-impl APIStore for SqliteStore {
-    async fn get_categories_for_user(&self, user_id: &str) -> Result<Option<String>> {
-        match sqlx::query_scalar!(
-            "select category_text from categories where user_id = ?",
+    /// Remove plans, inventory snapshots, sessions and refresh tokens older
+    /// than each user's retention window along with expired sessions and
+    /// refresh tokens. A user's own setting wins over `default_months` and
+    /// users with neither keep everything. With `dry_run` nothing is deleted
+    /// and the report says what would have been.
+    #[instrument(fields(conn_string=self.url), skip(self))]
+    pub async fn prune_history(
+        &self,
+        default_months: Option<u32>,
+        today: NaiveDate,
+        dry_run: bool,
+    ) -> Result<PruneReport> {
+        let mut report = PruneReport::default();
+        let user_ids = sqlx::query_scalar!("select id from users order by id")
+            .fetch_all(self.pool.as_ref())
+            .await?;
+        for user_id in user_ids {
+            let settings = self.fetch_user_settings(&user_id).await?;
+            let months = match client_api::retention_months(&settings).or(default_months) {
+                Some(months) => months,
+                None => continue,
+            };
+            let cutoff = match today.checked_sub_months(chrono::Months::new(months)) {
+                Some(cutoff) => cutoff,
+                None => continue,
+            };
+            let plans: Vec<NaiveDate> = self
+                .fetch_all_meal_plans(&user_id)
+                .await?
+                .unwrap_or_default()
+                .into_iter()
+                .filter(|date| *date < cutoff)
+                .collect();
+            let inventory_snapshots = sqlx::query_scalar!(
+                r#"select count(*) as "count!: i64" from inventory_history where user_id = ? and plan_date < ?"#,
+                user_id,
+                cutoff
+            )
+            .fetch_one(self.pool.as_ref())
+            .await?;
+            let sessions = sqlx::query_scalar!(
+                r#"select count(*) as "count!: i64" from sessions where user_id = ? and created_at < ?"#,
+                user_id,
+                cutoff
+            )
+            .fetch_one(self.pool.as_ref())
+            .await?;
+            let refresh_tokens = sqlx::query_scalar!(
+                r#"select count(*) as "count!: i64" from refresh_tokens where user_id = ? and created_at < ?"#,
+                user_id,
+                cutoff
+            )
+            .fetch_one(self.pool.as_ref())
+            .await?;
+            if plans.is_empty() && inventory_snapshots == 0 && sessions == 0 && refresh_tokens == 0
+            {
+                continue;
+            }
+            if !dry_run {
+                for date in plans.iter() {
+                    self.delete_meal_plan_for_date(&user_id, *date).await?;
+                }
+                sqlx::query!(
+                    "delete from inventory_history where user_id = ? and plan_date < ?",
+                    user_id,
+                    cutoff
+                )
+                .execute(self.pool.as_ref())
+                .await?;
+                sqlx::query!(
+                    "delete from sessions where user_id = ? and created_at < ?",
+                    user_id,
+                    cutoff
+                )
+                .execute(self.pool.as_ref())
+                .await?;
+                sqlx::query!(
+                    "delete from refresh_tokens where user_id = ? and created_at < ?",
+                    user_id,
+                    cutoff
+                )
+                .execute(self.pool.as_ref())
+                .await?;
+                self.bump_data_version(&user_id).await?;
+            }
+            info!(
+                user_id,
+                ?cutoff,
+                plans = plans.len(),
+                inventory_snapshots,
+                sessions,
+                refresh_tokens,
+                dry_run,
+                "Pruned history"
+            );
+            report.users.push(UserPrune {
+                user_id,
+                cutoff,
+                plans,
+                inventory_snapshots,
+                sessions,
+                refresh_tokens,
+            });
+        }
+        // Sessions from before they were saved with a user fall back to the
+        // instance default. Expired sessions and refresh tokens are never
+        // accepted again so they go no matter what the retention window is.
+        let default_cutoff =
+            default_months.and_then(|months| today.checked_sub_months(chrono::Months::new(months)));
+        report.sessions = sqlx::query_scalar!(
+            r#"select count(*) as "count!: i64" from sessions
+    where expires_at <= datetime('now') or (user_id is null and created_at < ?)"#,
+            default_cutoff,
+        )
+        .fetch_one(self.pool.as_ref())
+        .await?;
+        report.refresh_tokens = sqlx::query_scalar!(
+            r#"select count(*) as "count!: i64" from refresh_tokens where expires_at <= datetime('now')"#
+        )
+        .fetch_one(self.pool.as_ref())
+        .await?;
+        if !dry_run {
+            sqlx::query!(
+                "delete from sessions
+    where expires_at <= datetime('now') or (user_id is null and created_at < ?)",
+                default_cutoff,
+            )
+            .execute(self.pool.as_ref())
+            .await?;
+            sqlx::query!("delete from refresh_tokens where expires_at <= datetime('now')")
+                .execute(self.pool.as_ref())
+                .await?;
+        }
+        Ok(report)
+    }
+
+    /// When the scheduled job with this name last ran.
+    pub async fn fetch_job_last_run(&self, name: &str) -> Result<Option<NaiveDateTime>> {
+        Ok(sqlx::query_scalar!(
+            r#"select last_run as "last_run: NaiveDateTime" from scheduled_jobs where name = ?"#,
+            name
+        )
+        .fetch_optional(self.pool.as_ref())
+        .await?)
+    }
+
+    /// Remember when the scheduled job with this name last ran.
+    pub async fn save_job_last_run(&self, name: &str, last_run: NaiveDateTime) -> Result<()> {
+        sqlx::query!(
+            "insert into scheduled_jobs (name, last_run) values (?, ?)
+    on conflict(name) do update set last_run=excluded.last_run",
+            name,
+            last_run
+        )
+        .execute(self.pool.as_ref())
+        .await?;
+        Ok(())
+    }
+
+    /// Keep a snapshot of the inventory for a date when it differs from the
+    /// last one kept.
+    async fn record_inventory_snapshot(
+        transaction: &mut sqlx::Transaction<'_, sqlx::Sqlite>,
+        user_id: &str,
+        date: &NaiveDate,
+        snapshot: &InventoryData,
+    ) -> Result<()> {
+        let mut payload: Vec<u8> = Vec::new();
+        ciborium::ser::into_writer(snapshot, &mut payload)
+            .map_err(|e| Error::MalformedData(format!("{:?}", e)))?;
+        // The inventory is saved far more often than it changes so we only
+        // keep a snapshot when it differs from the last one.
+        let last = sqlx::query_scalar!(
+            "select inventory from inventory_history where user_id = ? and plan_date = ? order by saved_at desc, rowid desc limit 1",
+            user_id,
+            date
+        )
+        .fetch_optional(&mut *transaction)
+        .await?;
+        if last.as_ref() != Some(&payload) {
+            sqlx::query!(
+                "insert into inventory_history (user_id, plan_date, saved_at, inventory) values (?, ?, datetime('now'), ?)",
+                user_id,
+                date,
+                payload
+            )
+            .execute(&mut *transaction)
+            .await?;
+        }
+        Ok(())
+    }
+
+    /// How many recipes the user has as `transaction` sees it.
+    async fn count_recipes(
+        transaction: &mut sqlx::Transaction<'_, sqlx::Sqlite>,
+        user_id: &str,
+    ) -> Result<usize> {
+        let count = sqlx::query_scalar!(
+            "select count(*) as \"count: i64\" from recipes where user_id = ?",
+            user_id,
+        )
+        .fetch_one(&mut *transaction)
+        .await?;
+        Ok(count as usize)
+    }
+
+    /// The inventory for a date as `transaction` sees it.
+    async fn fetch_inventory_in(
+        transaction: &mut sqlx::Transaction<'_, sqlx::Sqlite>,
+        user_id: &str,
+        date: NaiveDate,
+    ) -> Result<(
+        Vec<IngredientKey>,
+        Vec<(IngredientKey, String)>,
+        Vec<(String, String)>,
+    )> {
+        struct FilteredIngredientRow {
+            name: String,
+            form: String,
+            measure_type: String,
+        }
+        let filtered_ingredient_rows: Vec<FilteredIngredientRow> = sqlx::query_file_as!(
+            FilteredIngredientRow,
+            "src/web/storage/fetch_filtered_ingredients_for_date.sql",
+            user_id,
+            date,
+        )
+        .fetch_all(&mut *transaction)
+        .await?;
+        let mut filtered_ingredients = Vec::new();
+        for row in filtered_ingredient_rows {
+            filtered_ingredients.push(IngredientKey::new(
+                row.name,
+                if row.form.is_empty() {
+                    None
+                } else {
+                    Some(row.form)
+                },
+                row.measure_type,
+            ));
+        }
+        struct ModifiedAmtRow {
+            name: String,
+            form: String,
+            measure_type: String,
+            amt: String,
+        }
+        let modified_amt_rows = sqlx::query_file_as!(
+            ModifiedAmtRow,
+            "src/web/storage/fetch_modified_amts_for_date.sql",
+            user_id,
+            date,
+        )
+        .fetch_all(&mut *transaction)
+        .await?;
+        let mut modified_amts = Vec::new();
+        for row in modified_amt_rows {
+            modified_amts.push((
+                IngredientKey::new(
+                    row.name,
+                    if row.form.is_empty() {
+                        None
+                    } else {
+                        Some(row.form)
+                    },
+                    row.measure_type,
+                ),
+                row.amt,
+            ));
+        }
+        pub struct ExtraItemRow {
+            name: String,
+            amt: String,
+        }
+        let extra_items_rows = sqlx::query_file_as!(
+            ExtraItemRow,
+            "src/web/storage/fetch_extra_items_for_date.sql",
+            user_id,
+            date,
+        )
+        .fetch_all(&mut *transaction)
+        .await?;
+        let mut extra_items = Vec::new();
+        for row in extra_items_rows {
+            extra_items.push((row.name, row.amt));
+        }
+        Ok((filtered_ingredients, modified_amts, extra_items))
+    }
+}
+
+#[async_trait]
+impl SessionStore for SqliteStore {
+    #[instrument(fields(conn_string=self.url), skip_all)]
+    async fn load_session(&self, cookie_value: String) -> async_session::Result<Option<Session>> {
+        let id = make_id_key(&cookie_value)?;
+        debug!(id, "fetching session from sqlite");
+        if let Some(payload) =
+            sqlx::query_scalar!("select session_value from sessions where id = ?", id)
+                .fetch_optional(self.pool.as_ref())
+                .await?
+        {
+            debug!(sesion_id = id, "found session key");
+            let session: Session = ciborium::de::from_reader(payload.as_slice())?;
+            // Sessions from before sessions expired have no expiry and
+            // stay valid.
+            return Ok(session.validate());
+        }
+        return Ok(None);
+    }
+
+    #[instrument(fields(conn_string=self.url), skip_all)]
+    async fn store_session(&self, session: Session) -> async_session::Result<Option<String>> {
+        let id = session.id();
+        let mut payload: Vec<u8> = Vec::new();
+        ciborium::ser::into_writer(&session, &mut payload)?;
+        // The user and expiry are kept next to the payload so pruning can
+        // find old sessions without decoding every one of them.
+        let user_id = session.get::<UserId>("user_id").map(|UserId(id)| id);
+        let expires_at = session.expiry().map(|expiry| expiry.timestamp());
+        sqlx::query!(
+            "insert into sessions (id, session_value, user_id, created_at, expires_at)
+    values (?, ?, ?, datetime('now'), datetime(?, 'unixepoch'))",
+            id,
+            payload,
+            user_id,
+            expires_at,
+        )
+        .execute(self.pool.as_ref())
+        .await?;
+        debug!(sesion_id = id, "successfully inserted session key");
+        return Ok(session.into_cookie_value());
+    }
+
+    #[instrument(fields(conn_string=self.url), skip_all)]
+    async fn destroy_session(&self, session: Session) -> async_session::Result {
+        let id = session.id();
+        sqlx::query!("delete from sessions where id = ?", id,)
+            .execute(self.pool.as_ref())
+            .await?;
+        return Ok(());
+    }
+
+    #[instrument(fields(conn_string=self.url), skip_all)]
+    async fn clear_store(&self) -> async_session::Result {
+        sqlx::query!("delete from sessions")
+            .execute(self.pool.as_ref())
+            .await?;
+        return Ok(());
+    }
+}
+
+#[async_trait]
+impl AuthStore for SqliteStore {
+    #[instrument(fields(user=%user_creds.id.0, conn_string=self.url), skip_all)]
+    async fn check_user_creds(&self, user_creds: &UserCreds) -> Result<bool> {
+        let id = user_creds.user_id().to_owned();
+        if let Some(payload) =
+            sqlx::query_scalar!("select password_hashed from users where id = ?", id)
+                .fetch_optional(self.pool.as_ref())
+                .await?
+        {
+            debug!("Testing password for user");
+            return Ok(check_pass(&payload, &user_creds.pass));
+        }
+        Ok(false)
+    }
+
+    #[instrument(fields(user=%user_creds.id.0, conn_string=self.url), skip_all)]
+    async fn store_user_creds(&self, user_creds: UserCreds) -> Result<()> {
+        let id = user_creds.user_id().to_owned();
+        let password_hashed = hash_pass(&user_creds.pass);
+        debug!("adding password for user");
+        sqlx::query!(
+            "insert into users (id, password_hashed) values (?, ?)",
+            id,
+            password_hashed,
+        )
+        .execute(self.pool.as_ref())
+        .await?;
+        Ok(())
+    }
+
+    async fn create_refresh_token(&self, user_id: &str) -> Result<String> {
+        let token = uuid::Uuid::new_v4().simple().to_string();
+        let hashed = hash_refresh_token(&token);
+        let lifetime = format!("+{} days", REFRESH_TOKEN_DAYS);
+        sqlx::query!(
+            "insert into refresh_tokens (token, user_id, expires_at) values (?, ?, datetime('now', ?))",
+            hashed,
+            user_id,
+            lifetime,
+        )
+        .execute(self.pool.as_ref())
+        .await?;
+        Ok(token)
+    }
+
+    async fn rotate_refresh_token(&self, token: &str) -> Result<Option<(UserId, String)>> {
+        let mut transaction = self.pool.as_ref().begin().await?;
+        let hashed = hash_refresh_token(token);
+        let user_id = sqlx::query_scalar!(
+            "select user_id from refresh_tokens where token = ? and expires_at > datetime('now')",
+            hashed,
+        )
+        .fetch_optional(&mut transaction)
+        .await?;
+        // Expired tokens are cleaned up here too since this is the only
+        // place they are looked at.
+        sqlx::query!(
+            "delete from refresh_tokens where token = ? or expires_at <= datetime('now')",
+            hashed,
+        )
+        .execute(&mut transaction)
+        .await?;
+        let user_id = match user_id {
+            Some(user_id) => user_id,
+            None => {
+                transaction.commit().await?;
+                return Ok(None);
+            }
+        };
+        let new_token = uuid::Uuid::new_v4().simple().to_string();
+        let new_hashed = hash_refresh_token(&new_token);
+        let lifetime = format!("+{} days", REFRESH_TOKEN_DAYS);
+        sqlx::query!(
+            "insert into refresh_tokens (token, user_id, expires_at) values (?, ?, datetime('now', ?))",
+            new_hashed,
+            user_id,
+            lifetime,
+        )
+        .execute(&mut transaction)
+        .await?;
+        transaction.commit().await?;
+        Ok(Some((UserId(user_id), new_token)))
+    }
+}
+
+// TODO(jwall): We need to do some serious error modeling here.
+#[async_trait]
+impl APIStore for SqliteStore {
+    fn limits(&self) -> &Limits {
+        &self.limits
+    }
+
+    async fn fetch_data_version(&self, user_id: &str) -> Result<i64> {
+        Ok(sqlx::query_scalar!(
+            "select version from data_versions where user_id = ?",
+            user_id
+        )
+        .fetch_optional(self.pool.as_ref())
+        .await?
+        .unwrap_or(0))
+    }
+
+    async fn bump_data_version(&self, user_id: &str) -> Result<i64> {
+        let mut transaction = self.pool.as_ref().begin().await?;
+        sqlx::query!(
+            "insert into data_versions (user_id, version) values (?, 1)
+    on conflict(user_id) do update set version=data_versions.version + 1",
+            user_id
+        )
+        .execute(&mut transaction)
+        .await?;
+        let version = sqlx::query_scalar!(
+            "select version from data_versions where user_id = ?",
+            user_id
+        )
+        .fetch_one(&mut transaction)
+        .await?;
+        transaction.commit().await?;
+        Ok(version)
+    }
+
+    async fn get_categories_for_user(&self, user_id: &str) -> Result<Option<String>> {
+        match sqlx::query_scalar!(
+            "select category_text from categories where user_id = ?",
+            user_id,
+        )
+        .fetch_optional(self.pool.as_ref())
+        .await?
+        {
+            Some(result) => Ok(result),
+            None => Ok(None),
+        }
+    }
+
+    async fn get_category_mappings_for_user(
+        &self,
+        user_id: &str,
+    ) -> Result<Option<Vec<(String, String)>>> {
+        struct Row {
+            ingredient_name: String,
+            category_name: String,
+        }
+        let rows: Vec<Row> = sqlx::query_file_as!(
+            Row,
+            "src/web/storage/fetch_category_mappings_for_user.sql",
+            user_id
+        )
+        .fetch_all(self.pool.as_ref())
+        .await?;
+        if rows.is_empty() {
+            Ok(None)
+        } else {
+            let mut mappings = Vec::new();
+            for r in rows {
+                mappings.push((r.ingredient_name, r.category_name));
+            }
+            Ok(Some(mappings))
+        }
+    }
+
+    async fn save_category_mappings_for_user(
+        &self,
+        user_id: &str,
+        mappings: &Vec<(String, String)>,
+    ) -> Result<()> {
+        for (name, category) in mappings.iter() {
+            sqlx::query_file!(
+                "src/web/storage/save_category_mappings_for_user.sql",
+                user_id,
+                name,
+                category,
+            )
+            .execute(self.pool.as_ref())
+            .await?;
+        }
+        Ok(())
+    }
+
+    async fn get_recipe_entry_for_user(
+        &self,
+        user_id: &str,
+        id: &str,
+    ) -> Result<Option<RecipeEntry>> {
+        // NOTE(jwall): We allow dead code becaue Rust can't figure out that
+        // this code is actually constructed but it's done via the query_as
+        // macro.
+        #[allow(dead_code)]
+        struct RecipeRow {
+            pub recipe_id: String,
+            pub recipe_text: Option<String>,
+            pub category: Option<String>,
+            pub revision: i64,
+        }
+        let entry = sqlx::query_as!(
+            RecipeRow,
+            "select recipe_id, recipe_text, category, revision from recipes where user_id = ? and recipe_id = ?",
+            user_id,
+            id,
+        )
+        .fetch_all(self.pool.as_ref())
+        .await?
+        .iter()
+        .map(|row| {
+            RecipeEntry(
+                row.recipe_id.clone(),
+                row.recipe_text.clone().unwrap_or_else(|| String::new()),
+                row.category.clone(),
+                Some(row.revision),
+            )
+        })
+        .nth(0);
+        Ok(entry)
+    }
+
+    async fn get_recipes_for_user(&self, user_id: &str) -> Result<Option<Vec<RecipeEntry>>> {
+        // NOTE(jwall): We allow dead code becaue Rust can't figure out that
+        // this code is actually constructed but it's done via the query_as
+        // macro.
+        #[allow(dead_code)]
+        struct RecipeRow {
+            pub recipe_id: String,
+            pub recipe_text: Option<String>,
+            pub category: Option<String>,
+            pub revision: i64,
+        }
+        let rows = sqlx::query_as!(
+            RecipeRow,
+            "select recipe_id, recipe_text, category, revision from recipes where user_id = ?",
+            user_id,
+        )
+        .fetch_all(self.pool.as_ref())
+        .await?
+        .iter()
+        .map(|row| {
+            RecipeEntry(
+                row.recipe_id.clone(),
+                row.recipe_text.clone().unwrap_or_else(|| String::new()),
+                row.category.clone(),
+                Some(row.revision),
+            )
+        })
+        .collect();
+        Ok(Some(rows))
+    }
+
+    async fn store_recipes_for_user(
+        &self,
+        user_id: &str,
+        recipes: &Vec<RecipeEntry>,
+    ) -> Result<Vec<(String, i64)>> {
+        // NOTE(jwall): We allow dead code becaue Rust can't figure out that
+        // this code is actually constructed but it's done via the query_as
+        // macro.
+        #[allow(dead_code)]
+        struct RecipeRow {
+            pub recipe_id: String,
+            pub recipe_text: Option<String>,
+            pub category: Option<String>,
+            pub revision: i64,
+        }
+        impl RecipeRow {
+            fn into_entry(self) -> RecipeEntry {
+                RecipeEntry(
+                    self.recipe_id,
+                    self.recipe_text.unwrap_or_else(|| String::new()),
+                    self.category,
+                    Some(self.revision),
+                )
+            }
+        }
+        // All of the recipes are saved in one transaction so a failure
+        // partway through doesn't leave the collection half updated.
+        let mut transaction = self.pool.as_ref().begin().await?;
+        let mut conflicts = Vec::new();
+        let mut seen = BTreeSet::new();
+        for entry in recipes {
+            // Entries are written under their normalized id so
+            // that is the row the revision has to be checked against.
+            let recipe_id = recipes::slugify(entry.recipe_id());
+            if !seen.insert(recipe_id.clone()) {
+                return Err(Error::EntryFailed(
+                    entry.recipe_id().to_owned(),
+                    Box::new(Error::MalformedData(format!(
+                        "More than one recipe in the batch has the id {}",
+                        recipe_id
+                    ))),
+                ));
+            }
+            let existing = sqlx::query_as!(
+                RecipeRow,
+                "select recipe_id, recipe_text, category, revision from recipes where user_id = ? and recipe_id = ?",
+                user_id,
+                recipe_id,
+            )
+            .fetch_optional(&mut transaction)
+            .await?;
+            let current = if recipe_id == entry.recipe_id() {
+                existing
+            } else {
+                let alias_id = entry.recipe_id();
+                let renamed = sqlx::query_as!(
+                    RecipeRow,
+                    "select recipe_id, recipe_text, category, revision from recipes where user_id = ? and recipe_id = ?",
+                    user_id,
+                    alias_id,
+                )
+                .fetch_optional(&mut transaction)
+                .await?;
+                let alias_of = sqlx::query_scalar!(
+                    "select recipe_id from recipe_aliases where user_id = ? and alias_id = ?",
+                    user_id,
+                    alias_id,
+                )
+                .fetch_optional(&mut transaction)
+                .await?;
+                match (renamed, existing) {
+                    (None, Some(row)) if alias_of.as_ref() == Some(&recipe_id) => Some(row),
+                    (_, Some(row)) => {
+                        debug!(alias_id, %recipe_id, "Normalized id belongs to another recipe");
+                        conflicts.push(row.into_entry());
+                        continue;
+                    }
+                    (renamed, None) => renamed,
+                }
+            };
+            if let (Some(base_revision), Some(row)) = (entry.revision(), current) {
+                if row.revision != base_revision {
+                    debug!(%recipe_id, base_revision, current_revision = row.revision, "Stale recipe revision");
+                    conflicts.push(row.into_entry());
+                }
+            }
+        }
+        if !conflicts.is_empty() {
+            return Err(Error::Conflict(conflicts));
+        }
+        for entry in recipes {
+            self.limits.check_recipe(entry)?;
+        }
+        let count_before = Self::count_recipes(&mut transaction, user_id).await?;
+        let mut revisions = Vec::new();
+        for entry in recipes {
+            let recipe_id = recipes::slugify(entry.recipe_id());
+            let recipe_text = entry.recipe_text().to_owned();
+            let category = entry.category();
+            sqlx::query!(
+                "insert into recipes (user_id, recipe_id, recipe_text, category) values (?, ?, ?, ?)
+    on conflict(user_id, recipe_id) do update set recipe_text=excluded.recipe_text, category=excluded.category, revision=recipes.revision + 1",
+                user_id,
+                recipe_id,
+                recipe_text,
+                category,
+            )
+            .execute(&mut transaction)
+            .await
+            .map_err(|e| Error::EntryFailed(recipe_id.clone(), Box::new(e.into())))?;
+            let row = sqlx::query_as!(
+                RecipeRow,
+                "select recipe_id, recipe_text, category, revision from recipes where user_id = ? and recipe_id = ?",
+                user_id,
+                recipe_id,
+            )
+            .fetch_one(&mut transaction)
+            .await?;
+            if recipe_id != entry.recipe_id() {
+                let alias_id = entry.recipe_id();
+                debug!(alias_id, %recipe_id, "Renaming recipe to normalized id");
+                // The old id becomes an alias for the new one and anything
+                // that pointed at the old id gets moved over.
+                sqlx::query!(
+                    "delete from recipes where user_id = ? and recipe_id = ?",
+                    user_id,
+                    alias_id,
+                )
+                .execute(&mut transaction)
+                .await?;
+                sqlx::query!(
+                    "insert into recipe_aliases (user_id, alias_id, recipe_id) values (?, ?, ?)
+    on conflict(user_id, alias_id) do update set recipe_id=excluded.recipe_id",
+                    user_id,
+                    alias_id,
+                    recipe_id,
+                )
+                .execute(&mut transaction)
+                .await?;
+                sqlx::query!(
+                    "update recipe_aliases set recipe_id = ? where user_id = ? and recipe_id = ?",
+                    recipe_id,
+                    user_id,
+                    alias_id,
+                )
+                .execute(&mut transaction)
+                .await?;
+                // Plans that already have the new id get the old
+                // counts added to them before the rest of the rows are moved.
+                sqlx::query!(
+                    "update plan_recipes set count = count + (select old.count from plan_recipes as old where old.user_id = plan_recipes.user_id and old.plan_date = plan_recipes.plan_date and old.recipe_id = ?)
+    where user_id = ? and recipe_id = ? and plan_date in (select plan_date from plan_recipes where user_id = ? and recipe_id = ?)",
+                    alias_id,
+                    user_id,
+                    recipe_id,
+                    user_id,
+                    alias_id,
+                )
+                .execute(&mut transaction)
+                .await?;
+                sqlx::query!(
+                    "update or ignore plan_recipes set recipe_id = ? where user_id = ? and recipe_id = ?",
+                    recipe_id,
+                    user_id,
+                    alias_id,
+                )
+                .execute(&mut transaction)
+                .await?;
+                sqlx::query!(
+                    "delete from plan_recipes where user_id = ? and recipe_id = ?",
+                    user_id,
+                    alias_id,
+                )
+                .execute(&mut transaction)
+                .await?;
+            }
+            revisions.push((recipe_id, row.revision));
+        }
+        if let Some(max) = self.limits.max_recipes {
+            let count = Self::count_recipes(&mut transaction, user_id).await?;
+            // Only new recipes count against the limit so a user already
+            // over a lowered limit can still edit what they have. Returning
+            // here drops the transaction which rolls back the whole batch.
+            if count > max && count > count_before {
+                return Err(Error::LimitExceeded(format!(
+                    "Saving these recipes would go over the limit of {} recipes",
+                    max
+                )));
+            }
+        }
+        transaction.commit().await?;
+        Ok(revisions)
+    }
+
+    async fn delete_recipes_for_user(&self, user_id: &str, recipes: &Vec<String>) -> Result<()> {
+        let mut transaction = self.pool.as_ref().begin().await?;
+        for recipe_id in recipes {
+            sqlx::query_file!("src/web/storage/trash_recipe.sql", user_id, recipe_id)
+                .execute(&mut transaction)
+                .await?;
+            sqlx::query!(
+                "delete from recipes where user_id = ? and recipe_id = ?",
+                user_id,
+                recipe_id,
+            )
+            .execute(&mut transaction)
+            .await?;
+            sqlx::query!(
+                "delete from recipe_aliases where user_id = ? and recipe_id = ?",
+                user_id,
+                recipe_id,
+            )
+            .execute(&mut transaction)
+            .await?;
+        }
+        transaction.commit().await?;
+        Ok(())
+    }
+
+    async fn fetch_trash_for_user(&self, user_id: &str) -> Result<Vec<TrashedRecipe>> {
+        let retention = format!("-{} days", TRASH_RETENTION_DAYS);
+        sqlx::query!(
+            "delete from recipe_trash where user_id = ? and deleted_at < datetime('now', ?)",
+            user_id,
+            retention,
+        )
+        .execute(self.pool.as_ref())
+        .await?;
+        struct Row {
+            recipe_id: String,
+            recipe_text: Option<String>,
+            category: Option<String>,
+            deleted_at: NaiveDateTime,
+        }
+        let rows = sqlx::query_as!(
+            Row,
+            r#"select recipe_id, recipe_text, category, deleted_at as "deleted_at: NaiveDateTime"
+    from recipe_trash where user_id = ? order by deleted_at desc, recipe_id"#,
+            user_id,
+        )
+        .fetch_all(self.pool.as_ref())
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|row| TrashedRecipe {
+                entry: RecipeEntry(
+                    row.recipe_id,
+                    row.recipe_text.unwrap_or_else(|| String::new()),
+                    row.category,
+                    None,
+                ),
+                deleted_at: row.deleted_at,
+                expires_at: row.deleted_at + chrono::Duration::days(TRASH_RETENTION_DAYS),
+            })
+            .collect())
+    }
+
+    async fn restore_recipe_for_user(
+        &self,
+        user_id: &str,
+        recipe_id: &str,
+    ) -> Result<Option<RecipeEntry>> {
+        let trashed = self
+            .fetch_trash_for_user(user_id)
+            .await?
+            .into_iter()
+            .find(|trashed| trashed.entry.recipe_id() == recipe_id);
+        let entry = match trashed {
+            Some(trashed) => trashed.entry,
+            None => return Ok(None),
+        };
+        if let Some(current) = self.get_recipe_entry_for_user(user_id, recipe_id).await? {
+            return Err(Error::Conflict(vec![current]));
+        }
+        let (restored_id, revision) = self
+            .store_recipes_for_user(user_id, &vec![entry.clone()])
+            .await?
+            .pop()
+            .expect("Stored recipes should have a revision");
+        self.purge_trash_for_user(user_id, recipe_id).await?;
+        Ok(Some(RecipeEntry(
+            restored_id,
+            entry.1,
+            entry.2,
+            Some(revision),
+        )))
+    }
+
+    async fn purge_trash_for_user(&self, user_id: &str, recipe_id: &str) -> Result<()> {
+        sqlx::query!(
+            "delete from recipe_trash where user_id = ? and recipe_id = ?",
+            user_id,
+            recipe_id,
+        )
+        .execute(self.pool.as_ref())
+        .await?;
+        sqlx::query!(
+            "delete from archived_recipes where user_id = ? and recipe_id = ?",
+            user_id,
+            recipe_id,
+        )
+        .execute(self.pool.as_ref())
+        .await?;
+        Ok(())
+    }
+
+    async fn fetch_archived_recipes_for_user(&self, user_id: &str) -> Result<BTreeSet<String>> {
+        let rows = sqlx::query!(
+            "select recipe_id from archived_recipes where user_id = ?",
+            user_id,
+        )
+        .fetch_all(self.pool.as_ref())
+        .await?;
+        Ok(rows.into_iter().map(|row| row.recipe_id).collect())
+    }
+
+    async fn set_recipe_archived_for_user(
+        &self,
+        user_id: &str,
+        recipe_id: &str,
+        archived: bool,
+    ) -> Result<()> {
+        if archived {
+            sqlx::query!(
+                "insert into archived_recipes (user_id, recipe_id) values (?, ?)
+    on conflict (user_id, recipe_id) do nothing",
+                user_id,
+                recipe_id,
+            )
+            .execute(self.pool.as_ref())
+            .await?;
+        } else {
+            sqlx::query!(
+                "delete from archived_recipes where user_id = ? and recipe_id = ?",
+                user_id,
+                recipe_id,
+            )
+            .execute(self.pool.as_ref())
+            .await?;
+        }
+        Ok(())
+    }
+
+    async fn fetch_plan_share_for_user(
+        &self,
+        user_id: &str,
+        week_start: NaiveDate,
+    ) -> Result<Option<String>> {
+        let row = sqlx::query!(
+            "select token from plan_shares where user_id = ? and week_start = ?",
+            user_id,
+            week_start,
+        )
+        .fetch_optional(self.pool.as_ref())
+        .await?;
+        Ok(row.map(|row| row.token))
+    }
+
+    async fn create_plan_share_for_user(
+        &self,
+        user_id: &str,
+        week_start: NaiveDate,
+    ) -> Result<String> {
+        let token = uuid::Uuid::new_v4().simple().to_string();
+        sqlx::query!(
+            "insert into plan_shares (token, user_id, week_start) values (?, ?, ?)
+    on conflict (user_id, week_start) do nothing",
+            token,
+            user_id,
+            week_start,
+        )
+        .execute(self.pool.as_ref())
+        .await?;
+        // The insert does nothing if the week was already shared so we look
+        // the token up instead of returning the one we made.
+        Ok(self
+            .fetch_plan_share_for_user(user_id, week_start)
+            .await?
+            .unwrap_or(token))
+    }
+
+    async fn delete_plan_share_for_user(&self, user_id: &str, week_start: NaiveDate) -> Result<()> {
+        sqlx::query!(
+            "delete from plan_shares where user_id = ? and week_start = ?",
+            user_id,
+            week_start,
+        )
+        .execute(self.pool.as_ref())
+        .await?;
+        Ok(())
+    }
+
+    async fn fetch_plan_share(&self, token: &str) -> Result<Option<(String, NaiveDate)>> {
+        let row = sqlx::query!(
+            r#"select user_id, week_start as "week_start: NaiveDate" from plan_shares where token = ?"#,
+            token,
+        )
+        .fetch_optional(self.pool.as_ref())
+        .await?;
+        Ok(row.map(|row| (row.user_id, row.week_start)))
+    }
+
+    async fn fetch_plan_rules_for_user(&self, user_id: &str) -> Result<Vec<StoredPlanRule>> {
+        struct Row {
+            id: i64,
+            rule: Vec<u8>,
+        }
+        let rows = sqlx::query_as!(
+            Row,
+            "select id, rule from plan_rules where user_id = ? order by id",
+            user_id,
+        )
+        .fetch_all(self.pool.as_ref())
+        .await?;
+        let mut rules = Vec::with_capacity(rows.len());
+        for row in rows {
+            let rule: PlanRule = ciborium::de::from_reader(row.rule.as_slice())
+                .map_err(|e| Error::MalformedData(format!("{:?}", e)))?;
+            rules.push(StoredPlanRule { id: row.id, rule });
+        }
+        Ok(rules)
+    }
+
+    async fn save_plan_rule_for_user(
+        &self,
+        user_id: &str,
+        rule: PlanRule,
+    ) -> Result<StoredPlanRule> {
+        let mut payload: Vec<u8> = Vec::new();
+        ciborium::ser::into_writer(&rule, &mut payload)
+            .map_err(|e| Error::MalformedData(format!("{:?}", e)))?;
+        let id = sqlx::query!(
+            "insert into plan_rules (user_id, rule) values (?, ?)",
             user_id,
+            payload,
         )
-        .fetch_optional(self.pool.as_ref())
+        .execute(self.pool.as_ref())
         .await?
-        {
-            Some(result) => Ok(result),
-            None => Ok(None),
+        .last_insert_rowid();
+        Ok(StoredPlanRule { id, rule })
+    }
+
+    async fn delete_plan_rule_for_user(&self, user_id: &str, id: i64) -> Result<()> {
+        sqlx::query!(
+            "delete from plan_rules where user_id = ? and id = ?",
+            user_id,
+            id,
+        )
+        .execute(self.pool.as_ref())
+        .await?;
+        Ok(())
+    }
+
+    async fn resolve_recipe_alias(&self, user_id: &str, alias_id: &str) -> Result<Option<String>> {
+        let recipe_id = sqlx::query_scalar!(
+            "select recipe_id from recipe_aliases where user_id = ? and alias_id = ?",
+            user_id,
+            alias_id,
+        )
+        .fetch_optional(self.pool.as_ref())
+        .await?;
+        Ok(recipe_id)
+    }
+
+    async fn fetch_recipe_ids_for_user(&self, user_id: &str) -> Result<BTreeSet<String>> {
+        let ids = sqlx::query_scalar!("select recipe_id from recipes where user_id = ?", user_id)
+            .fetch_all(self.pool.as_ref())
+            .await?;
+        Ok(ids.into_iter().collect())
+    }
+
+    async fn store_categories_for_user(&self, user_id: &str, categories: &str) -> Result<()> {
+        sqlx::query!(
+            "insert into categories (user_id, category_text) values (?, ?)
+    on conflict(user_id) do update set category_text=excluded.category_text",
+            user_id,
+            categories,
+        )
+        .execute(self.pool.as_ref())
+        .await?;
+        Ok(())
+    }
+
+    async fn save_meal_plan(
+        &self,
+        user_id: &str,
+        recipe_counts: &Vec<(String, i32)>,
+        date: NaiveDate,
+    ) -> Result<()> {
+        let mut transaction = self.pool.as_ref().begin().await?;
+        sqlx::query!(
+            "delete from plan_recipes where user_id = ? and plan_date = ?",
+            user_id,
+            date,
+        )
+        .execute(&mut transaction)
+        .await?;
+        sqlx::query_file!("src/web/storage/init_meal_plan.sql", user_id, date)
+            .execute(&mut transaction)
+            .await?;
+        // The order of the recipe counts is the order we intend to cook them in
+        // so we record it as the position.
+        for (position, (id, count)) in recipe_counts.iter().enumerate() {
+            let position = position as i64;
+            sqlx::query_file!(
+                "src/web/storage/save_meal_plan.sql",
+                user_id,
+                date,
+                id,
+                count,
+                position
+            )
+            .execute(&mut transaction)
+            .await?;
         }
+        transaction.commit().await?;
+        Ok(())
     }
 
-    async fn get_category_mappings_for_user(
+    async fn apply_plan_changes(
         &self,
         user_id: &str,
-    ) -> Result<Option<Vec<(String, String)>>> {
+        date: NaiveDate,
+        changes: &[PlanChange],
+    ) -> Result<Vec<(String, i32)>> {
+        let mut transaction = self.pool.as_ref().begin().await?;
+        sqlx::query_file!("src/web/storage/init_meal_plan.sql", user_id, date)
+            .execute(&mut transaction)
+            .await?;
+        for change in changes {
+            let recipe_id = change.recipe_id();
+            let count = match change {
+                PlanChange::SetCount(_, count) => *count,
+                PlanChange::AddCount(_, amount) => {
+                    let current = sqlx::query_scalar!(
+                        r#"select count as "count: i32" from plan_recipes where user_id = ? and plan_date = ? and recipe_id = ?"#,
+                        user_id,
+                        date,
+                        recipe_id,
+                    )
+                    .fetch_optional(&mut transaction)
+                    .await?
+                    .unwrap_or(0);
+                    (current + amount).max(0)
+                }
+            };
+            // Newly planned recipes go at the end of the cooking order.
+            sqlx::query_file!(
+                "src/web/storage/apply_plan_count.sql",
+                user_id,
+                date,
+                recipe_id,
+                count,
+                user_id,
+                date,
+            )
+            .execute(&mut transaction)
+            .await?;
+        }
+        transaction.commit().await?;
+        Ok(self
+            .fetch_meal_plan_for_date(user_id, date)
+            .await?
+            .unwrap_or_default())
+    }
+
+    async fn remove_dangling_plan_recipes(
+        &self,
+        user_id: &str,
+    ) -> Result<Vec<(NaiveDate, String)>> {
         struct Row {
-            ingredient_name: String,
-            category_name: String,
+            pub plan_date: NaiveDate,
+            pub recipe_id: String,
         }
-        let rows: Vec<Row> = sqlx::query_file_as!(
+        let mut transaction = self.pool.as_ref().begin().await?;
+        // Recipes in the trash can still be restored so we leave them in the
+        // plans.
+        let rows = sqlx::query_file_as!(
             Row,
-            "src/web/storage/fetch_category_mappings_for_user.sql",
-            user_id
+            "src/web/storage/fetch_dangling_plan_recipes.sql",
+            user_id,
+            user_id,
+            user_id,
+        )
+        .fetch_all(&mut transaction)
+        .await?;
+        for row in rows.iter() {
+            sqlx::query!(
+                "delete from plan_recipes where user_id = ? and plan_date = ? and recipe_id = ?",
+                user_id,
+                row.plan_date,
+                row.recipe_id,
+            )
+            .execute(&mut transaction)
+            .await?;
+            sqlx::query!(
+                "delete from plan_meals where user_id = ? and plan_date = ? and recipe_id = ?",
+                user_id,
+                row.plan_date,
+                row.recipe_id,
+            )
+            .execute(&mut transaction)
+            .await?;
+            sqlx::query!(
+                "delete from plan_freezer_meals where user_id = ? and plan_date = ? and recipe_id = ?",
+                user_id,
+                row.plan_date,
+                row.recipe_id,
+            )
+            .execute(&mut transaction)
+            .await?;
+        }
+        transaction.commit().await?;
+        Ok(rows
+            .into_iter()
+            .map(|row| (row.plan_date, row.recipe_id))
+            .collect())
+    }
+
+    async fn fetch_plan_meals_for_date(&self, user_id: &str, date: NaiveDate) -> Result<PlanMeals> {
+        struct Row {
+            recipe_id: String,
+            meal: String,
+        }
+        let rows = sqlx::query_as!(
+            Row,
+            "select recipe_id, meal from plan_meals where user_id = ? and plan_date = ?",
+            user_id,
+            date,
         )
         .fetch_all(self.pool.as_ref())
         .await?;
-        if rows.is_empty() {
-            Ok(None)
-        } else {
-            let mut mappings = Vec::new();
-            for r in rows {
-                mappings.push((r.ingredient_name, r.category_name));
-            }
-            Ok(Some(mappings))
+        let mut meals = PlanMeals::new();
+        for row in rows {
+            let meal = MealType::from_str(&row.meal).map_err(Error::MalformedData)?;
+            meals.entry(row.recipe_id).or_default().insert(meal);
         }
+        Ok(meals)
     }
 
-    async fn save_category_mappings_for_user(
+    async fn save_plan_meals_for_date(
         &self,
         user_id: &str,
-        mappings: &Vec<(String, String)>,
+        date: NaiveDate,
+        meals: &PlanMeals,
     ) -> Result<()> {
-        for (name, category) in mappings.iter() {
-            sqlx::query_file!(
-                "src/web/storage/save_category_mappings_for_user.sql",
+        let mut transaction = self.pool.as_ref().begin().await?;
+        sqlx::query!(
+            "delete from plan_meals where user_id = ? and plan_date = ?",
+            user_id,
+            date,
+        )
+        .execute(&mut transaction)
+        .await?;
+        for (recipe_id, meal) in meals
+            .iter()
+            .flat_map(|(id, meals)| meals.iter().map(move |meal| (id, meal)))
+        {
+            let meal = meal.as_str();
+            sqlx::query!(
+                "insert into plan_meals (user_id, plan_date, recipe_id, meal) values (?, ?, ?, ?)",
                 user_id,
-                name,
-                category,
+                date,
+                recipe_id,
+                meal,
             )
-            .execute(self.pool.as_ref())
+            .execute(&mut transaction)
             .await?;
         }
+        transaction.commit().await?;
         Ok(())
     }
 
-    async fn get_recipe_entry_for_user<S: AsRef<str> + Send>(
+    async fn fetch_plan_freezer_meals_for_date(
         &self,
-        user_id: S,
-        id: S,
-    ) -> Result<Option<RecipeEntry>> {
-        // NOTE(jwall): We allow dead code becaue Rust can't figure out that
-        // this code is actually constructed but it's done via the query_as
-        // macro.
-        #[allow(dead_code)]
-        struct RecipeRow {
-            pub recipe_id: String,
-            pub recipe_text: Option<String>,
-            pub category: Option<String>,
-        }
-        let id = id.as_ref();
-        let user_id = user_id.as_ref();
-        let entry = sqlx::query_as!(
-            RecipeRow,
-            "select recipe_id, recipe_text, category from recipes where user_id = ? and recipe_id = ?",
+        user_id: &str,
+        date: NaiveDate,
+    ) -> Result<BTreeSet<String>> {
+        let rows = sqlx::query_scalar!(
+            "select recipe_id from plan_freezer_meals where user_id = ? and plan_date = ?",
             user_id,
-            id,
+            date,
         )
         .fetch_all(self.pool.as_ref())
-        .await?
-        .iter()
-        .map(|row| {
-            RecipeEntry(
-                row.recipe_id.clone(),
-                row.recipe_text.clone().unwrap_or_else(|| String::new()),
-                row.category.clone()
-            )
-        })
-        .nth(0);
-        Ok(entry)
+        .await?;
+        Ok(rows.into_iter().collect())
     }
 
-    async fn get_recipes_for_user(&self, user_id: &str) -> Result<Option<Vec<RecipeEntry>>> {
-        // NOTE(jwall): We allow dead code becaue Rust can't figure out that
-        // this code is actually constructed but it's done via the query_as
-        // macro.
-        #[allow(dead_code)]
-        struct RecipeRow {
-            pub recipe_id: String,
-            pub recipe_text: Option<String>,
-            pub category: Option<String>,
+    async fn save_plan_freezer_meals_for_date(
+        &self,
+        user_id: &str,
+        date: NaiveDate,
+        recipe_ids: &BTreeSet<String>,
+    ) -> Result<()> {
+        let mut transaction = self.pool.as_ref().begin().await?;
+        sqlx::query!(
+            "delete from plan_freezer_meals where user_id = ? and plan_date = ?",
+            user_id,
+            date,
+        )
+        .execute(&mut transaction)
+        .await?;
+        for recipe_id in recipe_ids.iter() {
+            sqlx::query!(
+                "insert into plan_freezer_meals (user_id, plan_date, recipe_id) values (?, ?, ?)",
+                user_id,
+                date,
+                recipe_id,
+            )
+            .execute(&mut transaction)
+            .await?;
         }
-        let rows = sqlx::query_as!(
-            RecipeRow,
-            "select recipe_id, recipe_text, category from recipes where user_id = ?",
+        transaction.commit().await?;
+        Ok(())
+    }
+
+    async fn fetch_freezer_items_for_user(&self, user_id: &str) -> Result<Vec<FreezerItem>> {
+        let items = sqlx::query_as!(
+            FreezerItem,
+            r#"select id, recipe_id, portions as "portions: i32", frozen_on as "frozen_on: NaiveDate"
+from freezer_items where user_id = ? order by frozen_on, id"#,
             user_id,
         )
         .fetch_all(self.pool.as_ref())
+        .await?;
+        Ok(items)
+    }
+
+    async fn add_freezer_item_for_user(
+        &self,
+        user_id: &str,
+        item: &NewFreezerItem,
+    ) -> Result<FreezerItem> {
+        let id = sqlx::query!(
+            "insert into freezer_items (user_id, recipe_id, portions, frozen_on) values (?, ?, ?, ?)",
+            user_id,
+            item.recipe_id,
+            item.portions,
+            item.frozen_on,
+        )
+        .execute(self.pool.as_ref())
         .await?
-        .iter()
-        .map(|row| {
-            RecipeEntry(
-                row.recipe_id.clone(),
-                row.recipe_text.clone().unwrap_or_else(|| String::new()),
-                row.category.clone(),
-            )
+        .last_insert_rowid();
+        Ok(FreezerItem {
+            id,
+            recipe_id: item.recipe_id.clone(),
+            portions: item.portions,
+            frozen_on: item.frozen_on,
         })
-        .collect();
-        Ok(Some(rows))
     }
 
-    async fn store_recipes_for_user(
+    async fn take_freezer_portions_for_user(
         &self,
         user_id: &str,
-        recipes: &Vec<RecipeEntry>,
-    ) -> Result<()> {
-        for entry in recipes {
-            let recipe_id = entry.recipe_id().to_owned();
-            let recipe_text = entry.recipe_text().to_owned();
-            let category = entry.category();
+        id: i64,
+        portions: i32,
+    ) -> Result<Option<FreezerItem>> {
+        let mut transaction = self.pool.as_ref().begin().await?;
+        let item = sqlx::query_as!(
+            FreezerItem,
+            r#"select id, recipe_id, portions as "portions: i32", frozen_on as "frozen_on: NaiveDate"
+from freezer_items where user_id = ? and id = ?"#,
+            user_id,
+            id,
+        )
+        .fetch_optional(&mut transaction)
+        .await?;
+        let mut item = match item {
+            Some(item) => item,
+            None => return Err(Error::NoRecords),
+        };
+        item.portions -= portions;
+        let remaining = if item.portions > 0 {
             sqlx::query!(
-                "insert into recipes (user_id, recipe_id, recipe_text, category) values (?, ?, ?, ?)
-    on conflict(user_id, recipe_id) do update set recipe_text=excluded.recipe_text, category=excluded.category",
-                user_id,
-                recipe_id,
-                recipe_text,
-                category,
+                "update freezer_items set portions = ? where user_id = ? and id = ?",
+                item.portions,
+                user_id,
+                id,
             )
-            .execute(self.pool.as_ref())
+            .execute(&mut transaction)
             .await?;
-        }
-        Ok(())
-    }
-
-    async fn delete_recipes_for_user(&self, user_id: &str, recipes: &Vec<String>) -> Result<()> {
-        let mut transaction = self.pool.as_ref().begin().await?;
-        for recipe_id in recipes {
+            Some(item)
+        } else {
             sqlx::query!(
-                "delete from recipes where user_id = ? and recipe_id = ?",
+                "delete from freezer_items where user_id = ? and id = ?",
                 user_id,
-                recipe_id,
+                id,
             )
             .execute(&mut transaction)
             .await?;
-        }
+            None
+        };
         transaction.commit().await?;
-        Ok(())
+        Ok(remaining)
     }
 
-    async fn store_categories_for_user(&self, user_id: &str, categories: &str) -> Result<()> {
+    async fn delete_freezer_item_for_user(&self, user_id: &str, id: i64) -> Result<()> {
         sqlx::query!(
-            "insert into categories (user_id, category_text) values (?, ?)
-    on conflict(user_id) do update set category_text=excluded.category_text",
+            "delete from freezer_items where user_id = ? and id = ?",
             user_id,
-            categories,
+            id,
         )
         .execute(self.pool.as_ref())
         .await?;
         Ok(())
     }
 
-    async fn save_meal_plan<S: AsRef<str> + Send>(
+    async fn fetch_cooked_for_date(
         &self,
-        user_id: S,
-        recipe_counts: &Vec<(String, i32)>,
+        user_id: &str,
+        date: NaiveDate,
+    ) -> Result<BTreeSet<String>> {
+        let rows = sqlx::query_scalar!(
+            "select recipe_id from cooked_recipes where user_id = ? and plan_date = ?",
+            user_id,
+            date,
+        )
+        .fetch_all(self.pool.as_ref())
+        .await?;
+        Ok(rows.into_iter().collect())
+    }
+
+    async fn mark_cooked_for_date(
+        &self,
+        user_id: &str,
         date: NaiveDate,
+        recipe_id: &str,
+        cooked_on: NaiveDate,
     ) -> Result<()> {
-        let user_id = user_id.as_ref();
-        let mut transaction = self.pool.as_ref().begin().await?;
         sqlx::query!(
-            "delete from plan_recipes where user_id = ? and plan_date = ?",
+            r#"insert into cooked_recipes (user_id, plan_date, recipe_id, cooked_on) values (?, ?, ?, ?)
+    on conflict (user_id, plan_date, recipe_id) do update set cooked_on=excluded.cooked_on"#,
             user_id,
             date,
+            recipe_id,
+            cooked_on,
         )
-        .execute(&mut transaction)
+        .execute(self.pool.as_ref())
         .await?;
-        sqlx::query_file!("src/web/storage/init_meal_plan.sql", user_id, date)
-            .execute(&mut transaction)
-            .await?;
-        for (id, count) in recipe_counts {
-            sqlx::query_file!(
-                "src/web/storage/save_meal_plan.sql",
-                user_id,
-                date,
-                id,
-                count
-            )
-            .execute(&mut transaction)
-            .await?;
-        }
-        transaction.commit().await?;
         Ok(())
     }
 
-    async fn fetch_all_meal_plans<S: AsRef<str> + Send>(
+    async fn fetch_last_cooked_for_user(
+        &self,
+        user_id: &str,
+    ) -> Result<BTreeMap<String, NaiveDate>> {
+        struct Row {
+            recipe_id: String,
+            cooked_on: NaiveDate,
+        }
+        let rows = sqlx::query_as!(
+            Row,
+            r#"select recipe_id, max(cooked_on) as "cooked_on!: NaiveDate"
+from cooked_recipes where user_id = ? group by recipe_id"#,
+            user_id,
+        )
+        .fetch_all(self.pool.as_ref())
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|row| (row.recipe_id, row.cooked_on))
+            .collect())
+    }
+
+    async fn fetch_recipe_plan_counts_for_user(
+        &self,
+        user_id: &str,
+    ) -> Result<BTreeMap<String, i64>> {
+        struct Row {
+            recipe_id: String,
+            times: i64,
+        }
+        let rows = sqlx::query_as!(
+            Row,
+            r#"select recipe_id, sum(count) as "times!: i64"
+from plan_recipes where user_id = ? and count > 0 group by recipe_id"#,
+            user_id,
+        )
+        .fetch_all(self.pool.as_ref())
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|row| (row.recipe_id, row.times))
+            .collect())
+    }
+
+    async fn fetch_plan_meal_counts_for_user(
         &self,
-        user_id: S,
-    ) -> Result<Option<Vec<NaiveDate>>> {
-        let user_id = user_id.as_ref();
+        user_id: &str,
+    ) -> Result<BTreeMap<NaiveDate, i64>> {
+        struct Row {
+            plan_date: NaiveDate,
+            meals: i64,
+        }
+        let rows = sqlx::query_as!(
+            Row,
+            r#"select plan_date as "plan_date: NaiveDate", sum(count) as "meals!: i64"
+from plan_recipes where user_id = ? and count > 0 group by plan_date"#,
+            user_id,
+        )
+        .fetch_all(self.pool.as_ref())
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|row| (row.plan_date, row.meals))
+            .collect())
+    }
+
+    async fn fetch_all_meal_plans(&self, user_id: &str) -> Result<Option<Vec<NaiveDate>>> {
         struct Row {
             pub plan_date: NaiveDate,
         }
@@ -594,12 +2329,11 @@ impl APIStore for SqliteStore {
         Ok(Some(result))
     }
 
-    async fn fetch_meal_plans_since<S: AsRef<str> + Send>(
+    async fn fetch_meal_plans_since(
         &self,
-        user_id: S,
+        user_id: &str,
         date: NaiveDate,
     ) -> Result<Option<BTreeMap<NaiveDate, Vec<(String, i32)>>>> {
-        let user_id = user_id.as_ref();
         struct Row {
             pub plan_date: NaiveDate,
             pub recipe_id: String,
@@ -631,14 +2365,42 @@ impl APIStore for SqliteStore {
         Ok(Some(result))
     }
 
-    #[instrument(skip_all, fields(user_id=user_id.as_ref(), date))]
-    async fn delete_meal_plan_for_date<S: AsRef<str> + Send>(
+    async fn fetch_meal_plans_in_range(
         &self,
-        user_id: S,
-        date: NaiveDate,
-    ) -> Result<()> {
+        user_id: &str,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> Result<Option<BTreeMap<NaiveDate, Vec<(String, i32)>>>> {
+        struct Row {
+            pub plan_date: NaiveDate,
+            pub recipe_id: String,
+            pub count: i64,
+        }
+        let rows = sqlx::query_file_as!(
+            Row,
+            r#"src/web/storage/fetch_meal_plans_in_range.sql"#,
+            user_id,
+            from,
+            to
+        )
+        .fetch_all(self.pool.as_ref())
+        .await?;
+        if rows.is_empty() {
+            return Ok(None);
+        }
+        let mut result = BTreeMap::new();
+        for row in rows {
+            result
+                .entry(row.plan_date)
+                .or_insert_with(|| Vec::new())
+                .push((row.recipe_id, row.count as i32));
+        }
+        Ok(Some(result))
+    }
+
+    #[instrument(skip_all, fields(user_id, date))]
+    async fn delete_meal_plan_for_date(&self, user_id: &str, date: NaiveDate) -> Result<()> {
         debug!("Processing delete request");
-        let user_id = user_id.as_ref();
         let mut transaction = self.pool.as_ref().begin().await?;
         sqlx::query!(
             "delete from plan_table where user_id = ? and plan_date = ?",
@@ -675,16 +2437,29 @@ impl APIStore for SqliteStore {
         )
         .execute(&mut transaction)
         .await?;
+        sqlx::query!(
+            "delete from plan_meals where user_id = ? and plan_date = ?",
+            user_id,
+            date
+        )
+        .execute(&mut transaction)
+        .await?;
+        sqlx::query!(
+            "delete from plan_freezer_meals where user_id = ? and plan_date = ?",
+            user_id,
+            date
+        )
+        .execute(&mut transaction)
+        .await?;
         transaction.commit().await?;
         Ok(())
     }
 
-    async fn fetch_meal_plan_for_date<S: AsRef<str> + Send>(
+    async fn fetch_meal_plan_for_date(
         &self,
-        user_id: S,
+        user_id: &str,
         date: NaiveDate,
     ) -> Result<Option<Vec<(String, i32)>>> {
-        let user_id = user_id.as_ref();
         struct Row {
             pub plan_date: NaiveDate,
             pub recipe_id: String,
@@ -713,11 +2488,7 @@ impl APIStore for SqliteStore {
         Ok(Some(result))
     }
 
-    async fn fetch_latest_meal_plan<S: AsRef<str> + Send>(
-        &self,
-        user_id: S,
-    ) -> Result<Option<Vec<(String, i32)>>> {
-        let user_id = user_id.as_ref();
+    async fn fetch_latest_meal_plan(&self, user_id: &str) -> Result<Option<Vec<(String, i32)>>> {
         struct Row {
             pub plan_date: NaiveDate,
             pub recipe_id: String,
@@ -742,99 +2513,30 @@ impl APIStore for SqliteStore {
         Ok(Some(result))
     }
 
-    async fn fetch_inventory_for_date<S: AsRef<str> + Send>(
+    async fn fetch_inventory_for_date(
         &self,
-        user_id: S,
+        user_id: &str,
         date: NaiveDate,
     ) -> Result<(
         Vec<IngredientKey>,
         Vec<(IngredientKey, String)>,
         Vec<(String, String)>,
     )> {
-        let user_id = user_id.as_ref();
-        struct FilteredIngredientRow {
-            name: String,
-            form: String,
-            measure_type: String,
-        }
-        let filtered_ingredient_rows: Vec<FilteredIngredientRow> = sqlx::query_file_as!(
-            FilteredIngredientRow,
-            "src/web/storage/fetch_filtered_ingredients_for_date.sql",
-            user_id,
-            date,
-        )
-        .fetch_all(self.pool.as_ref())
-        .await?;
-        let mut filtered_ingredients = Vec::new();
-        for row in filtered_ingredient_rows {
-            filtered_ingredients.push(IngredientKey::new(
-                row.name,
-                if row.form.is_empty() {
-                    None
-                } else {
-                    Some(row.form)
-                },
-                row.measure_type,
-            ));
-        }
-        struct ModifiedAmtRow {
-            name: String,
-            form: String,
-            measure_type: String,
-            amt: String,
-        }
-        let modified_amt_rows = sqlx::query_file_as!(
-            ModifiedAmtRow,
-            "src/web/storage/fetch_modified_amts_for_date.sql",
-            user_id,
-            date,
-        )
-        .fetch_all(self.pool.as_ref())
-        .await?;
-        let mut modified_amts = Vec::new();
-        for row in modified_amt_rows {
-            modified_amts.push((
-                IngredientKey::new(
-                    row.name,
-                    if row.form.is_empty() {
-                        None
-                    } else {
-                        Some(row.form)
-                    },
-                    row.measure_type,
-                ),
-                row.amt,
-            ));
-        }
-        pub struct ExtraItemRow {
-            name: String,
-            amt: String,
-        }
-        let extra_items_rows = sqlx::query_file_as!(
-            ExtraItemRow,
-            "src/web/storage/fetch_extra_items_for_date.sql",
-            user_id,
-            date,
-        )
-        .fetch_all(self.pool.as_ref())
-        .await?;
-        let mut extra_items = Vec::new();
-        for row in extra_items_rows {
-            extra_items.push((row.name, row.amt));
-        }
-        Ok((filtered_ingredients, modified_amts, extra_items))
+        let mut transaction = self.pool.as_ref().begin().await?;
+        let inventory = Self::fetch_inventory_in(&mut transaction, user_id, date).await?;
+        transaction.commit().await?;
+        Ok(inventory)
     }
 
     // TODO(jwall): Deprecated
-    async fn fetch_latest_inventory_data<S: AsRef<str> + Send>(
+    async fn fetch_latest_inventory_data(
         &self,
-        user_id: S,
+        user_id: &str,
     ) -> Result<(
         Vec<IngredientKey>,
         Vec<(IngredientKey, String)>,
         Vec<(String, String)>,
     )> {
-        let user_id = user_id.as_ref();
         struct FilteredIngredientRow {
             name: String,
             form: String,
@@ -905,16 +2607,24 @@ impl APIStore for SqliteStore {
         Ok((filtered_ingredients, modified_amts, extra_items))
     }
 
-    async fn save_inventory_data_for_date<S: AsRef<str> + Send>(
+    async fn save_inventory_data_for_date(
         &self,
-        user_id: S,
+        user_id: &str,
         date: &NaiveDate,
         filtered_ingredients: BTreeSet<IngredientKey>,
         modified_amts: BTreeMap<IngredientKey, String>,
         extra_items: Vec<(String, String)>,
     ) -> Result<()> {
-        let user_id = user_id.as_ref();
+        let snapshot = InventoryData {
+            filtered_ingredients: filtered_ingredients.iter().cloned().collect(),
+            modified_amts: modified_amts
+                .iter()
+                .map(|(k, amt)| (k.clone(), amt.clone()))
+                .collect(),
+            extra_items: extra_items.clone(),
+        };
         let mut transaction = self.pool.as_ref().begin().await?;
+        Self::record_inventory_snapshot(&mut transaction, user_id, date, &snapshot).await?;
         // store the filtered_ingredients
         sqlx::query!(
             "delete from filtered_ingredients where user_id = ? and plan_date = ?",
@@ -986,14 +2696,153 @@ impl APIStore for SqliteStore {
         Ok(())
     }
 
-    async fn save_inventory_data<S: AsRef<str> + Send>(
+    async fn apply_inventory_ops_for_date(
+        &self,
+        user_id: &str,
+        date: &NaiveDate,
+        ops: &[InventoryOp],
+    ) -> Result<InventoryData> {
+        let mut transaction = self.pool.as_ref().begin().await?;
+        for op in ops {
+            match op {
+                InventoryOp::Check(key) => {
+                    let name = key.name();
+                    let form = key.form();
+                    let measure_type = key.measure_type();
+                    sqlx::query_file!(
+                        "src/web/storage/save_filtered_ingredients_for_date.sql",
+                        user_id,
+                        name,
+                        form,
+                        measure_type,
+                        date,
+                    )
+                    .execute(&mut transaction)
+                    .await?;
+                }
+                InventoryOp::Uncheck(key) => {
+                    let name = key.name();
+                    let form = key.form();
+                    let measure_type = key.measure_type();
+                    sqlx::query!(
+                        "delete from filtered_ingredients where user_id = ? and name = ? and form = ? and measure_type = ? and plan_date = ?",
+                        user_id,
+                        name,
+                        form,
+                        measure_type,
+                        date,
+                    )
+                    .execute(&mut transaction)
+                    .await?;
+                }
+                InventoryOp::SetAmt(key, amt) => {
+                    let name = key.name();
+                    let form = key.form();
+                    let measure_type = key.measure_type();
+                    sqlx::query_file!(
+                        "src/web/storage/save_modified_amts_for_date.sql",
+                        user_id,
+                        name,
+                        form,
+                        measure_type,
+                        amt,
+                        date,
+                    )
+                    .execute(&mut transaction)
+                    .await?;
+                }
+                InventoryOp::SetExtras(extra_items) => {
+                    sqlx::query!(
+                        "delete from extra_items where user_id = ? and plan_date = ?",
+                        user_id,
+                        date
+                    )
+                    .execute(&mut transaction)
+                    .await?;
+                    for (name, amt) in extra_items {
+                        sqlx::query_file!(
+                            "src/web/storage/store_extra_items_for_date.sql",
+                            user_id,
+                            name,
+                            amt,
+                            date
+                        )
+                        .execute(&mut transaction)
+                        .await?;
+                    }
+                }
+                InventoryOp::Reset => {
+                    sqlx::query!(
+                        "delete from filtered_ingredients where user_id = ? and plan_date = ?",
+                        user_id,
+                        date
+                    )
+                    .execute(&mut transaction)
+                    .await?;
+                    sqlx::query!(
+                        "delete from modified_amts where user_id = ? and plan_date = ?",
+                        user_id,
+                        date
+                    )
+                    .execute(&mut transaction)
+                    .await?;
+                    sqlx::query!(
+                        "delete from extra_items where user_id = ? and plan_date = ?",
+                        user_id,
+                        date
+                    )
+                    .execute(&mut transaction)
+                    .await?;
+                }
+            }
+        }
+        // The snapshot is of the inventory this transaction merged so a save
+        // that lands in between can't end up in it.
+        let merged: InventoryData = Self::fetch_inventory_in(&mut transaction, user_id, *date)
+            .await?
+            .into();
+        Self::record_inventory_snapshot(&mut transaction, user_id, date, &merged).await?;
+        transaction.commit().await?;
+        Ok(merged)
+    }
+
+    async fn fetch_inventory_history_for_date(
+        &self,
+        user_id: &str,
+        date: NaiveDate,
+    ) -> Result<Vec<InventorySnapshot>> {
+        struct Row {
+            saved_at: NaiveDateTime,
+            inventory: Vec<u8>,
+        }
+        let rows = sqlx::query_as!(
+            Row,
+            r#"select saved_at as "saved_at: NaiveDateTime", inventory from inventory_history
+    where user_id = ? and plan_date = ? order by saved_at, rowid"#,
+            user_id,
+            date
+        )
+        .fetch_all(self.pool.as_ref())
+        .await?;
+        let mut history = Vec::new();
+        for row in rows {
+            let inventory: InventoryData = ciborium::de::from_reader(row.inventory.as_slice())
+                .map_err(|e| Error::MalformedData(format!("{:?}", e)))?;
+            history.push(InventorySnapshot {
+                saved_at: row.saved_at,
+                inventory,
+            });
+        }
+        Ok(history)
+    }
+
+    async fn save_inventory_data(
         &self,
-        user_id: S,
+        user_id: &str,
         filtered_ingredients: BTreeSet<IngredientKey>,
         modified_amts: BTreeMap<IngredientKey, String>,
         extra_items: Vec<(String, String)>,
     ) -> Result<()> {
-        let user_id = user_id.as_ref();
         let mut transaction = self.pool.as_ref().begin().await?;
         // store the filtered_ingredients
         for key in filtered_ingredients {
@@ -1037,16 +2886,14 @@ impl APIStore for SqliteStore {
         Ok(())
     }
 
-    async fn save_staples<S: AsRef<str> + Send>(&self, user_id: S, content: S) -> Result<()> {
-        let (user_id, content) = (user_id.as_ref(), content.as_ref());
+    async fn save_staples(&self, user_id: &str, content: &str) -> Result<()> {
         sqlx::query_file!("src/web/storage/save_staples.sql", user_id, content)
             .execute(self.pool.as_ref())
             .await?;
         Ok(())
     }
 
-    async fn fetch_staples<S: AsRef<str> + Send>(&self, user_id: S) -> Result<Option<String>> {
-        let user_id = user_id.as_ref();
+    async fn fetch_staples(&self, user_id: &str) -> Result<Option<String>> {
         if let Some(content) =
             sqlx::query_file_scalar!("src/web/storage/fetch_staples.sql", user_id)
                 .fetch_optional(self.pool.as_ref())
@@ -1056,4 +2903,38 @@ impl APIStore for SqliteStore {
         }
         Ok(None)
     }
+
+    async fn fetch_user_settings(&self, user_id: &str) -> Result<BTreeMap<String, String>> {
+        struct Row {
+            setting_key: String,
+            setting_value: String,
+        }
+        let rows = sqlx::query_file_as!(Row, "src/web/storage/fetch_user_settings.sql", user_id)
+            .fetch_all(self.pool.as_ref())
+            .await?;
+        Ok(rows
+            .into_iter()
+            .map(|row| (row.setting_key, row.setting_value))
+            .collect())
+    }
+
+    async fn save_user_settings(
+        &self,
+        user_id: &str,
+        settings: &BTreeMap<String, String>,
+    ) -> Result<()> {
+        let mut transaction = self.pool.as_ref().begin().await?;
+        for (key, value) in settings.iter() {
+            sqlx::query_file!(
+                "src/web/storage/save_user_setting.sql",
+                user_id,
+                key,
+                value
+            )
+            .execute(&mut transaction)
+            .await?;
+        }
+        transaction.commit().await?;
+        Ok(())
+    }
 }
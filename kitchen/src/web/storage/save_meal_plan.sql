@@ -1,2 +1,2 @@
-insert into plan_recipes (user_id, plan_date, recipe_id, count) values (?, ?, ?, ?)
-    on conflict (user_id, plan_date, recipe_id) do update set count=excluded.count;
\ No newline at end of file
+insert into plan_recipes (user_id, plan_date, recipe_id, count, position) values (?, ?, ?, ?, ?)
+    on conflict (user_id, plan_date, recipe_id) do update set count=excluded.count, position=excluded.position;
\ No newline at end of file
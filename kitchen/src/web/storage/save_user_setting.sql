@@ -0,0 +1,2 @@
+insert into user_settings (user_id, setting_key, setting_value) values (?, ?, ?)
+    on conflict(user_id, setting_key) do update set setting_value = excluded.setting_value
\ No newline at end of file
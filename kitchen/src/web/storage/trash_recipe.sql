@@ -0,0 +1,5 @@
+insert into recipe_trash (user_id, recipe_id, recipe_text, category, deleted_at)
+    select user_id, recipe_id, recipe_text, category, datetime('now') from recipes
+    where user_id = ? and recipe_id = ?
+    on conflict(user_id, recipe_id) do update set
+        recipe_text=excluded.recipe_text, category=excluded.category, deleted_at=excluded.deleted_at
\ No newline at end of file
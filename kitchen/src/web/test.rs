@@ -0,0 +1,3127 @@
+// Copyright 2023 Jeremy Wall (Jeremy@marzhilsltudios.com)
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//! End to end tests that send requests through the full router backed by a
+//! throwaway sqlite database. The handler tests at the end call handlers
+//! directly with an in memory store instead.
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Debug;
+use std::future::Future;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use async_session::SessionStore;
+use axum::{
+    body::{Body, HttpBody},
+    extract::{Extension, Host, Path, Query},
+    http::{header, HeaderMap, HeaderValue, Method, Request, StatusCode},
+    Json, Router,
+};
+use base64::Engine;
+use chrono::NaiveDate;
+use client_api as api;
+use recipes::{IngredientKey, RecipeEntry};
+use serde::{de::DeserializeOwned, Serialize};
+use tower::ServiceExt;
+
+use super::blob::{BlobStore, FsBlobStore};
+use super::ocr::{CommandOcr, OcrBackend};
+use super::qr;
+use super::rate_limit::RateLimit;
+use super::request_id::{TraceParent, REQUEST_ID, TRACEPARENT};
+use super::scheduler;
+use super::storage::{self, memory::MemoryStore, APIStore, AuthStore};
+use super::{make_app_router, Admins, AnonymousAccess, SecurityHeaders};
+
+const USER: &'static str = "cook";
+const PASSWORD: &'static str = "hunter2";
+
+const PANCAKES: &'static str = "title: Pancakes
+
+step:
+
+1 cup flour
+1 cup milk
+2 egg
+
+Whisk everything together and fry in a hot pan.
+";
+
+fn run<F: Future>(f: F) -> F::Output {
+    async_std::task::block_on(f)
+}
+
+/// The payload of a successful response.
+fn success<T: Debug>(resp: api::Response<T>) -> T {
+    match resp {
+        api::Response::Success(val) => val,
+        other => panic!("Expected a successful response but got {:?}", other),
+    }
+}
+
+pub struct TestResponse {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: Vec<u8>,
+}
+
+impl TestResponse {
+    pub fn json<T: DeserializeOwned>(&self) -> T {
+        serde_json::from_slice(&self.body).unwrap_or_else(|err| {
+            panic!(
+                "Invalid json response {:?}: {}",
+                String::from_utf8_lossy(&self.body),
+                err
+            )
+        })
+    }
+}
+
+/// The full router with its own recipe directory and database. Both are
+/// removed when it is dropped. Requests carry the session cookie from the
+/// last successful login.
+pub struct TestApp {
+    router: Router,
+    app_store: Arc<storage::SqliteStore>,
+    dir: PathBuf,
+    cookie: Option<String>,
+}
+
+impl TestApp {
+    pub async fn new() -> Self {
+        Self::with_anonymous(AnonymousAccess::Files).await
+    }
+
+    /// A new app that lets people who aren't logged in see what `anonymous`
+    /// allows.
+    pub async fn with_anonymous(anonymous: AnonymousAccess) -> Self {
+        Self::build(anonymous, SecurityHeaders::default()).await
+    }
+
+    /// A new app that adds `security` headers to responses.
+    pub async fn with_security(security: SecurityHeaders) -> Self {
+        Self::build(AnonymousAccess::Files, security).await
+    }
+
+    async fn build(anonymous: AnonymousAccess, security: SecurityHeaders) -> Self {
+        let dir = std::env::temp_dir().join(format!("kitchen-test-{}", uuid::Uuid::new_v4()));
+        let recipe_dir = dir.join("recipes");
+        std::fs::create_dir_all(&recipe_dir).expect("Unable to create the recipe dir");
+        let store = Arc::new(storage::file_store::AsyncFileStore::new(recipe_dir));
+        let app_store = Arc::new(
+            storage::SqliteStore::new(dir.join("store"))
+                .await
+                .expect("Unable to create app_store"),
+        );
+        app_store
+            .run_migrations()
+            .await
+            .expect("Failed to run database migrations");
+        let router = make_app_router(
+            store,
+            app_store.clone(),
+            false,
+            Admins(BTreeSet::new()),
+            anonymous,
+            security,
+            None,
+            Arc::new(FsBlobStore::new(dir.join("blobs"))),
+        );
+        Self {
+            router,
+            app_store,
+            dir,
+            cookie: None,
+        }
+    }
+
+    /// A new app with a user that is already logged in.
+    pub async fn logged_in() -> Self {
+        let mut app = Self::new().await;
+        app.add_user(USER, PASSWORD).await;
+        let resp = app.login(USER, PASSWORD).await;
+        assert_eq!(resp.status, StatusCode::OK);
+        app
+    }
+
+    pub async fn add_user(&self, user: &str, password: &str) {
+        self.app_store
+            .store_user_creds(storage::UserCreds {
+                id: storage::UserId(user.to_owned()),
+                pass: secrecy::Secret::from(password.to_owned()),
+            })
+            .await
+            .expect("Failed to store user creds");
+    }
+
+    /// Log in with basic auth and keep the session cookie if it worked.
+    pub async fn login(&mut self, user: &str, password: &str) -> TestResponse {
+        self.login_to("/api/v2/auth", user, password).await
+    }
+
+    /// Log in with basic auth at `path` which may have query parameters.
+    pub async fn login_to(&mut self, path: &str, user: &str, password: &str) -> TestResponse {
+        let creds =
+            base64::engine::general_purpose::STANDARD.encode(format!("{}:{}", user, password));
+        let req = Request::builder()
+            .method(Method::GET)
+            .uri(path)
+            .header(header::HOST, "localhost")
+            .header(header::AUTHORIZATION, format!("Basic {}", creds))
+            .body(Body::empty())
+            .expect("Invalid auth request");
+        let resp = self.send(req).await;
+        if let Some(cookie) = resp.headers.get(header::SET_COOKIE) {
+            let cookie = cookie.to_str().expect("Invalid session cookie");
+            // Only the name and value go back to the server.
+            self.cookie = cookie.split(';').next().map(str::to_owned);
+        }
+        resp
+    }
+
+    async fn send(&self, req: Request<Body>) -> TestResponse {
+        let resp = self
+            .router
+            .clone()
+            .oneshot(req)
+            .await
+            .expect("The router failed");
+        let status = resp.status();
+        let headers = resp.headers().clone();
+        let mut body = resp.into_body();
+        let mut bytes = Vec::new();
+        while let Some(chunk) = body.data().await {
+            bytes.extend_from_slice(&chunk.expect("Unable to read the response body"));
+        }
+        TestResponse {
+            status,
+            headers,
+            body: bytes,
+        }
+    }
+
+    pub async fn request(&self, method: Method, path: &str, body: Option<String>) -> TestResponse {
+        let mut req = Request::builder()
+            .method(method)
+            .uri(path)
+            .header(header::HOST, "localhost");
+        if let Some(cookie) = &self.cookie {
+            req = req.header(header::COOKIE, cookie);
+        }
+        let body = match body {
+            Some(body) => {
+                req = req.header(header::CONTENT_TYPE, "application/json");
+                Body::from(body)
+            }
+            None => Body::empty(),
+        };
+        self.send(req.body(body).expect("Invalid request")).await
+    }
+
+    pub async fn get(&self, path: &str) -> TestResponse {
+        self.request(Method::GET, path, None).await
+    }
+
+    /// GET `path` with an extra header.
+    pub async fn get_with_header(
+        &self,
+        path: &str,
+        name: header::HeaderName,
+        value: &str,
+    ) -> TestResponse {
+        let mut req = Request::builder()
+            .method(Method::GET)
+            .uri(path)
+            .header(header::HOST, "localhost")
+            .header(name, value);
+        if let Some(cookie) = &self.cookie {
+            req = req.header(header::COOKIE, cookie);
+        }
+        self.send(req.body(Body::empty()).expect("Invalid request"))
+            .await
+    }
+
+    pub async fn delete(&self, path: &str) -> TestResponse {
+        self.request(Method::DELETE, path, None).await
+    }
+
+    pub async fn post<B: Serialize>(&self, path: &str, body: &B) -> TestResponse {
+        let body = serde_json::to_string(body).expect("Unable to serialize the request body");
+        self.request(Method::POST, path, Some(body)).await
+    }
+
+    /// Post `body` as is with the given content type.
+    pub async fn post_bytes(&self, path: &str, content_type: &str, body: Vec<u8>) -> TestResponse {
+        let mut req = Request::builder()
+            .method(Method::POST)
+            .uri(path)
+            .header(header::HOST, "localhost")
+            .header(header::CONTENT_TYPE, content_type);
+        if let Some(cookie) = &self.cookie {
+            req = req.header(header::COOKIE, cookie);
+        }
+        let req = req.body(Body::from(body)).expect("Invalid request");
+        self.send(req).await
+    }
+}
+
+/// The value of the cookie called `name` set by a response.
+fn response_cookie(resp: &TestResponse, name: &str) -> Option<String> {
+    resp.headers
+        .get_all(header::SET_COOKIE)
+        .iter()
+        .filter_map(|cookie| cookie.to_str().ok())
+        .filter_map(|cookie| cookie.split(';').next())
+        .find(|pair| pair.starts_with(&format!("{}=", name)))
+        .map(str::to_owned)
+}
+
+impl Drop for TestApp {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+#[test]
+fn test_account_requires_login() {
+    run(async {
+        let app = TestApp::new().await;
+        let resp = app.get("/api/v2/account").await;
+        assert_eq!(resp.status, StatusCode::UNAUTHORIZED);
+    })
+}
+
+#[test]
+fn test_anonymous_access_off() {
+    run(async {
+        let mut app = TestApp::with_anonymous(AnonymousAccess::Off).await;
+        let resp = app.get("/api/v1/recipes").await;
+        assert_eq!(resp.status, StatusCode::UNAUTHORIZED);
+        let resp = app.get("/api/v2/recipes").await;
+        assert_eq!(resp.status, StatusCode::UNAUTHORIZED);
+        let resp = app.get("/api/v2/categories").await;
+        assert_eq!(resp.status, StatusCode::UNAUTHORIZED);
+
+        app.add_user(USER, PASSWORD).await;
+        let resp = app.login(USER, PASSWORD).await;
+        assert_eq!(resp.status, StatusCode::OK);
+        let resp = app.get("/api/v2/recipes").await;
+        assert_eq!(resp.status, StatusCode::OK);
+    })
+}
+
+#[test]
+fn test_anonymous_access_to_user() {
+    run(async {
+        let mut app = TestApp::with_anonymous(AnonymousAccess::User(USER.to_owned())).await;
+        app.add_user(USER, PASSWORD).await;
+        let resp = app.login(USER, PASSWORD).await;
+        assert_eq!(resp.status, StatusCode::OK);
+        let resp = app
+            .post(
+                "/api/v2/recipes",
+                &vec![RecipeEntry::new("pancakes", PANCAKES)],
+            )
+            .await;
+        assert_eq!(resp.status, StatusCode::OK);
+
+        app.cookie = None;
+        let recipes = success(
+            app.get("/api/v2/recipes")
+                .await
+                .json::<api::RecipeEntryResponse>(),
+        );
+        assert_eq!(recipes.len(), 1);
+        assert_eq!(recipes[0].recipe_id(), "pancakes");
+        let resp = app.get("/api/v2/recipe/pancakes/parsed").await;
+        assert_eq!(resp.status, StatusCode::OK);
+        let resp = app
+            .post(
+                "/api/v2/recipes",
+                &vec![RecipeEntry::new("waffles", PANCAKES)],
+            )
+            .await;
+        assert_eq!(resp.status, StatusCode::UNAUTHORIZED);
+        let resp = app.delete("/api/v2/recipe/pancakes").await;
+        assert_eq!(resp.status, StatusCode::UNAUTHORIZED);
+        // Only the recipes are published. The rest of the account isn't.
+        for path in [
+            "/api/v2/account/export",
+            "/api/v2/inventory",
+            "/api/v2/plan",
+        ] {
+            let resp = app.get(path).await;
+            assert_eq!(resp.status, StatusCode::UNAUTHORIZED, "{}", path);
+        }
+    })
+}
+
+#[test]
+fn test_recipe_prep_list() {
+    run(async {
+        let app = TestApp::logged_in().await;
+        let soup = "---
+serves: 2
+---
+title: Soup
+
+step:
+
+1 onion (diced)
+1 cup stock
+
+Saute the onion.
+
+step:
+
+1 onion (diced)
+
+Add the stock and the rest of the onion.
+";
+        let resp = app
+            .post("/api/v2/recipes", &vec![RecipeEntry::new("soup", soup)])
+            .await;
+        assert_eq!(resp.status, StatusCode::OK);
+
+        let prep: api::PrepListResponse = app.get("/api/v2/recipe/soup/prep").await.json();
+        let prep = success(prep);
+        assert_eq!(prep.title, "Soup");
+        assert_eq!(
+            prep.items,
+            vec![api::StepIngredient {
+                name: "onion".to_owned(),
+                form: Some("diced".to_owned()),
+                amt: "2".to_owned(),
+            }]
+        );
+
+        let prep: api::PrepListResponse = app.get("/api/v2/recipe/soup/prep?people=4").await.json();
+        assert_eq!(success(prep).items[0].amt, "4");
+
+        let resp = app.get("/api/v2/recipe/missing/prep").await;
+        assert_eq!(resp.status, StatusCode::NOT_FOUND);
+    })
+}
+
+#[test]
+fn test_security_headers() {
+    run(async {
+        let app = TestApp::new().await;
+        let resp = app.get("/api/v2/account").await;
+        assert_eq!(
+            resp.headers.get(header::CONTENT_SECURITY_POLICY).unwrap(),
+            super::security::DEFAULT_CSP
+        );
+        assert_eq!(
+            resp.headers.get(header::X_CONTENT_TYPE_OPTIONS).unwrap(),
+            "nosniff"
+        );
+        assert_eq!(
+            resp.headers.get(header::REFERRER_POLICY).unwrap(),
+            super::security::DEFAULT_REFERRER_POLICY
+        );
+        assert!(resp
+            .headers
+            .get(header::STRICT_TRANSPORT_SECURITY)
+            .is_none());
+
+        let app = TestApp::with_security(SecurityHeaders {
+            content_security_policy: None,
+            referrer_policy: Some(HeaderValue::from_static("no-referrer")),
+            hsts: true,
+        })
+        .await;
+        let resp = app.get("/api/v2/account").await;
+        assert!(resp.headers.get(header::CONTENT_SECURITY_POLICY).is_none());
+        assert_eq!(
+            resp.headers.get(header::REFERRER_POLICY).unwrap(),
+            "no-referrer"
+        );
+        assert!(resp
+            .headers
+            .get(header::STRICT_TRANSPORT_SECURITY)
+            .is_some());
+    })
+}
+
+#[test]
+fn test_login_with_bad_password() {
+    run(async {
+        let mut app = TestApp::new().await;
+        app.add_user(USER, PASSWORD).await;
+        let resp = app.login(USER, "wrong").await;
+        assert_eq!(resp.status, StatusCode::UNAUTHORIZED);
+        assert!(resp.headers.get(header::SET_COOKIE).is_none());
+        let resp = app.get("/api/v2/account").await;
+        assert_eq!(resp.status, StatusCode::UNAUTHORIZED);
+    })
+}
+
+#[test]
+fn test_login_sets_session() {
+    run(async {
+        let app = TestApp::logged_in().await;
+        let resp = app.get("/api/v2/account").await;
+        assert_eq!(resp.status, StatusCode::OK);
+        let account = success(resp.json::<api::AccountResponse>());
+        assert_eq!(account.user_id, USER);
+    })
+}
+
+#[test]
+fn test_remembered_device_gets_new_sessions() {
+    run(async {
+        let mut app = TestApp::new().await;
+        app.add_user(USER, PASSWORD).await;
+        let resp = app.login(USER, PASSWORD).await;
+        assert_eq!(resp.status, StatusCode::OK);
+        assert!(response_cookie(&resp, super::auth::REFRESH_COOKIE_NAME).is_none());
+
+        let resp = app
+            .login_to("/api/v2/auth?remember=true", USER, PASSWORD)
+            .await;
+        assert_eq!(resp.status, StatusCode::OK);
+        let refresh = response_cookie(&resp, super::auth::REFRESH_COOKIE_NAME)
+            .expect("No refresh token cookie");
+
+        app.cookie = Some(refresh.clone());
+        let resp = app.post("/api/v2/auth/refresh", &()).await;
+        assert_eq!(resp.status, StatusCode::OK);
+        let account = success(resp.json::<api::AccountResponse>());
+        assert_eq!(account.user_id, USER);
+        let session =
+            response_cookie(&resp, storage::AXUM_SESSION_COOKIE_NAME).expect("No session cookie");
+        let rotated = response_cookie(&resp, super::auth::REFRESH_COOKIE_NAME)
+            .expect("No rotated refresh token cookie");
+        assert_ne!(rotated, refresh);
+
+        // The old token only works once and the device is told to forget it
+        // with a cookie that matches the one it has.
+        let resp = app.post("/api/v2/auth/refresh", &()).await;
+        assert_eq!(resp.status, StatusCode::UNAUTHORIZED);
+        let cleared = resp
+            .headers
+            .get_all(header::SET_COOKIE)
+            .iter()
+            .filter_map(|cookie| cookie.to_str().ok())
+            .find(|cookie| cookie.starts_with(super::auth::REFRESH_COOKIE_NAME))
+            .expect("The refresh token cookie wasn't cleared");
+        for attribute in [
+            "Domain=",
+            "Path=/api/v2/auth/refresh",
+            "HttpOnly",
+            "Secure",
+            "Max-Age=0",
+        ] {
+            assert!(cleared.contains(attribute), "{} is missing", attribute);
+        }
+
+        app.cookie = Some(session);
+        let resp = app.get("/api/v2/account").await;
+        assert_eq!(resp.status, StatusCode::OK);
+        app.cookie = Some(rotated);
+        let resp = app.post("/api/v2/auth/refresh", &()).await;
+        assert_eq!(resp.status, StatusCode::OK);
+
+        // Tokens are looked up by their hash so a token stored in plaintext
+        // doesn't work.
+        for token in [
+            "plaintext".to_owned(),
+            storage::hash_refresh_token("hashed"),
+        ] {
+            app.app_store
+                .execute_sql(&format!(
+                    "insert into refresh_tokens (token, user_id, expires_at) values ('{}', '{}', '2099-01-01 00:00:00')",
+                    token, USER
+                ))
+                .await
+                .expect("Failed to add a refresh token");
+        }
+        app.cookie = Some(format!("{}=plaintext", super::auth::REFRESH_COOKIE_NAME));
+        let resp = app.post("/api/v2/auth/refresh", &()).await;
+        assert_eq!(resp.status, StatusCode::UNAUTHORIZED);
+        app.cookie = Some(format!("{}=hashed", super::auth::REFRESH_COOKIE_NAME));
+        let resp = app.post("/api/v2/auth/refresh", &()).await;
+        assert_eq!(resp.status, StatusCode::OK);
+    })
+}
+
+#[test]
+fn test_recipe_crud() {
+    run(async {
+        let app = TestApp::logged_in().await;
+        let resp = app
+            .post(
+                "/api/v2/recipes",
+                &vec![RecipeEntry::new("pancakes", PANCAKES)],
+            )
+            .await;
+        assert_eq!(resp.status, StatusCode::OK);
+        let saved = success(resp.json::<api::RecipeSaveResponse>());
+        assert_eq!(saved.len(), 1);
+        assert_eq!(saved[0].recipe_id, "pancakes");
+
+        let recipes = success(
+            app.get("/api/v2/recipes")
+                .await
+                .json::<api::RecipeEntryResponse>(),
+        );
+        assert_eq!(recipes.len(), 1);
+        assert_eq!(recipes[0].recipe_text(), PANCAKES);
+
+        let updated = PANCAKES.replace("2 egg", "3 egg");
+        let resp = app
+            .post(
+                "/api/v2/recipes",
+                &vec![RecipeEntry::new("pancakes", updated.as_str())],
+            )
+            .await;
+        assert_eq!(resp.status, StatusCode::OK);
+        let entry = success(
+            app.get("/api/v2/recipe/pancakes")
+                .await
+                .json::<api::Response<Option<RecipeEntry>>>(),
+        )
+        .expect("The recipe is missing");
+        assert_eq!(entry.recipe_text(), updated);
+
+        let resp = app.delete("/api/v2/recipe/pancakes").await;
+        assert_eq!(resp.status, StatusCode::OK);
+        let recipes = success(
+            app.get("/api/v2/recipes")
+                .await
+                .json::<api::RecipeEntryResponse>(),
+        );
+        assert!(recipes.is_empty());
+        let trash = success(
+            app.get("/api/v2/recipes/trash")
+                .await
+                .json::<api::TrashResponse>(),
+        );
+        assert_eq!(trash.len(), 1);
+        assert_eq!(trash[0].entry.recipe_id(), "pancakes");
+    })
+}
+
+#[test]
+fn test_invalid_recipe_is_rejected() {
+    run(async {
+        let app = TestApp::logged_in().await;
+        let resp = app
+            .post(
+                "/api/v2/recipes",
+                &vec![RecipeEntry::new("broken", "not a recipe")],
+            )
+            .await;
+        assert_eq!(resp.status, StatusCode::UNPROCESSABLE_ENTITY);
+        let recipes = success(
+            app.get("/api/v2/recipes")
+                .await
+                .json::<api::RecipeEntryResponse>(),
+        );
+        assert!(recipes.is_empty());
+    })
+}
+
+#[test]
+fn test_recipe_id_collisions_are_rejected() {
+    run(async {
+        let app = TestApp::logged_in().await;
+        let resp = app
+            .post(
+                "/api/v2/recipes",
+                &vec![RecipeEntry::new("pizza_dough", PANCAKES)],
+            )
+            .await;
+        assert_eq!(resp.status, StatusCode::OK);
+
+        // A new recipe whose id normalizes to an existing one doesn't
+        // overwrite it.
+        let resp = app
+            .post(
+                "/api/v2/recipes",
+                &vec![RecipeEntry::new("Pizza Dough", PANCAKES)],
+            )
+            .await;
+        assert_eq!(resp.status, StatusCode::CONFLICT);
+        let entry = success(
+            app.get("/api/v2/recipe/pizza_dough")
+                .await
+                .json::<api::Response<Option<RecipeEntry>>>(),
+        )
+        .expect("The recipe is missing");
+        assert_eq!(entry.revision(), Some(0));
+
+        let resp = app
+            .post(
+                "/api/v2/recipes",
+                &vec![
+                    RecipeEntry::new("Waffles", PANCAKES),
+                    RecipeEntry::new("waffles", PANCAKES),
+                ],
+            )
+            .await;
+        assert_eq!(resp.status, StatusCode::UNPROCESSABLE_ENTITY);
+        let recipes = success(
+            app.get("/api/v2/recipes")
+                .await
+                .json::<api::RecipeEntryResponse>(),
+        );
+        assert_eq!(recipes.len(), 1);
+    })
+}
+
+#[test]
+fn test_renamed_recipe_merges_plans() {
+    run(async {
+        let app = TestApp::logged_in().await;
+        let date = NaiveDate::from_ymd_opt(2023, 10, 16).unwrap();
+        // Recipes saved before ids were normalized can have any id.
+        app.app_store
+            .execute_sql(&format!(
+                "insert into recipes (user_id, recipe_id, recipe_text) values ('{}', 'Pizza Dough', '{}')",
+                USER, PANCAKES
+            ))
+            .await
+            .expect("Failed to add the old recipe");
+        app.app_store
+            .save_meal_plan(
+                USER,
+                &vec![("Pizza Dough".to_owned(), 1), ("pizza_dough".to_owned(), 2)],
+                date,
+            )
+            .await
+            .expect("Failed to save the plan");
+
+        let old = RecipeEntry("Pizza Dough".to_owned(), PANCAKES.to_owned(), None, Some(0));
+        let saved = success(
+            app.post("/api/v2/recipes", &vec![old])
+                .await
+                .json::<api::RecipeSaveResponse>(),
+        );
+        assert_eq!(saved[0].recipe_id, "pizza_dough");
+        assert_eq!(saved[0].renamed_from.as_deref(), Some("Pizza Dough"));
+        let plan = success(
+            app.get("/api/v2/plan/at/2023-10-16")
+                .await
+                .json::<api::PlanDataResponse>(),
+        );
+        assert_eq!(plan, vec![("pizza_dough".to_owned(), 3)]);
+        let recipes = success(
+            app.get("/api/v2/recipes")
+                .await
+                .json::<api::RecipeEntryResponse>(),
+        );
+        assert_eq!(recipes.len(), 1);
+        assert_eq!(recipes[0].recipe_id(), "pizza_dough");
+    })
+}
+
+#[test]
+fn test_plan_round_trip() {
+    run(async {
+        let app = TestApp::logged_in().await;
+        let resp = app
+            .post(
+                "/api/v2/recipes",
+                &vec![RecipeEntry::new("pancakes", PANCAKES)],
+            )
+            .await;
+        assert_eq!(resp.status, StatusCode::OK);
+
+        let resp = app
+            .post("/api/v2/plan", &vec![("pancakes".to_owned(), 2)])
+            .await;
+        assert_eq!(resp.status, StatusCode::OK);
+        let plan = success(
+            app.get("/api/v2/plan")
+                .await
+                .json::<api::PlanDataResponse>(),
+        );
+        assert_eq!(plan, vec![("pancakes".to_owned(), 2)]);
+
+        let resp = app
+            .post("/api/v2/plan", &vec![("waffles".to_owned(), 1)])
+            .await;
+        assert_eq!(resp.status, StatusCode::UNPROCESSABLE_ENTITY);
+        let plan = success(
+            app.get("/api/v2/plan")
+                .await
+                .json::<api::PlanDataResponse>(),
+        );
+        assert_eq!(plan, vec![("pancakes".to_owned(), 2)]);
+    })
+}
+
+#[test]
+fn test_plan_meal_slots() {
+    run(async {
+        let app = TestApp::logged_in().await;
+        let resp = app
+            .post(
+                "/api/v2/recipes",
+                &vec![RecipeEntry::new("pancakes", PANCAKES)],
+            )
+            .await;
+        assert_eq!(resp.status, StatusCode::OK);
+        let resp = app
+            .post(
+                "/api/v2/plan/at/2023-10-18",
+                &vec![("pancakes".to_owned(), 2)],
+            )
+            .await;
+        assert_eq!(resp.status, StatusCode::OK);
+
+        // A recipe can fill more than one meal on the same day.
+        let mut meals = api::PlanMeals::new();
+        meals.insert(
+            "pancakes".to_owned(),
+            BTreeSet::from([recipes::MealType::Breakfast, recipes::MealType::Lunch]),
+        );
+        let resp = app.post("/api/v2/plan/at/2023-10-18/meals", &meals).await;
+        assert_eq!(resp.status, StatusCode::OK);
+        let saved = success(
+            app.get("/api/v2/plan/at/2023-10-18/meals")
+                .await
+                .json::<api::PlanMealsResponse>(),
+        );
+        assert_eq!(saved, meals);
+
+        let share = success(
+            app.post("/api/v2/plan/week/2023-10-18/share", &())
+                .await
+                .json::<api::PlanShareResponse>(),
+        )
+        .expect("The week should be shared");
+        let week = success(
+            app.get(&format!("/api/v2/shared/plan/{}", share.token))
+                .await
+                .json::<api::SharedWeekResponse>(),
+        );
+        let planned = week.plans[0]
+            .recipes
+            .iter()
+            .map(|r| (r.meal, r.title.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            planned,
+            vec![
+                (recipes::MealType::Breakfast, "Pancakes"),
+                (recipes::MealType::Lunch, "Pancakes"),
+            ]
+        );
+    })
+}
+
+#[test]
+fn test_cooklang_import_and_export() {
+    run(async {
+        let app = TestApp::logged_in().await;
+        let import = api::CooklangImport {
+            title: "Scrambled Eggs".to_owned(),
+            text: "Crack @eggs{3} into a #bowl{} and whisk.\n\nCook for ~{3%minutes}.\n".to_owned(),
+            category: Some("Breakfast".to_owned()),
+        };
+        let saved = success(
+            app.post("/api/v2/recipes/import/cooklang", &vec![import])
+                .await
+                .json::<api::RecipeSaveResponse>(),
+        );
+        assert_eq!(saved[0].recipe_id, "scrambled_eggs");
+
+        let resp = app.get("/api/v2/recipe/scrambled_eggs/cooklang").await;
+        assert_eq!(resp.status, StatusCode::OK);
+        assert_eq!(
+            resp.headers[header::CONTENT_DISPOSITION],
+            r#"attachment; filename="scrambled_eggs.cook""#
+        );
+        let exported = String::from_utf8(resp.body).expect("The export is text");
+        let recipe = recipes::cooklang::as_recipe(&exported, "").expect("The export parses");
+        assert_eq!(recipe.title, "Scrambled Eggs");
+        assert_eq!(recipe.steps[0].ingredients[0].name, "eggs");
+
+        // Renamed recipes are exported under their new id.
+        let saved = success(
+            app.post(
+                "/api/v2/recipes",
+                &vec![RecipeEntry::new("Pancake Mix", PANCAKES)],
+            )
+            .await
+            .json::<api::RecipeSaveResponse>(),
+        );
+        assert_eq!(saved[0].recipe_id, "pancake_mix");
+        let resp = app.get("/api/v2/recipe/Pancake%20Mix/cooklang").await;
+        assert_eq!(resp.status, StatusCode::OK);
+        assert_eq!(
+            resp.headers[header::CONTENT_DISPOSITION],
+            r#"attachment; filename="pancake_mix.cook""#
+        );
+
+        let bad = api::CooklangImport {
+            title: "Bad".to_owned(),
+            text: "Add @x{1.aaaaa\u{e9}}.".to_owned(),
+            category: None,
+        };
+        let resp = app
+            .post("/api/v2/recipes/import/cooklang", &vec![bad])
+            .await;
+        assert_eq!(resp.status, StatusCode::UNPROCESSABLE_ENTITY);
+    })
+}
+
+#[test]
+fn test_stats() {
+    run(async {
+        let mut app = TestApp::logged_in().await;
+        let waffles = PANCAKES
+            .replace("Pancakes", "Waffles")
+            .replace("1 cup milk\n", "");
+        let resp = app
+            .post(
+                "/api/v2/recipes",
+                &vec![
+                    RecipeEntry::new("pancakes", PANCAKES),
+                    RecipeEntry::new("waffles", waffles.as_str()),
+                ],
+            )
+            .await;
+        assert_eq!(resp.status, StatusCode::OK);
+        for (date, plan) in [
+            ("2023-09-04", vec![("pancakes".to_owned(), 2)]),
+            (
+                "2023-10-02",
+                vec![("pancakes".to_owned(), 1), ("waffles".to_owned(), 1)],
+            ),
+            ("2023-10-04", vec![("waffles".to_owned(), 0)]),
+        ] {
+            let resp = app.post(&format!("/api/v2/plan/at/{}", date), &plan).await;
+            assert_eq!(resp.status, StatusCode::OK);
+        }
+
+        let recipes = success(
+            app.get("/api/v2/stats/recipes")
+                .await
+                .json::<api::RecipeCountsResponse>(),
+        );
+        assert_eq!(
+            recipes,
+            vec![
+                api::RecipeCount {
+                    recipe_id: "pancakes".to_owned(),
+                    title: "Pancakes".to_owned(),
+                    count: 3,
+                },
+                api::RecipeCount {
+                    recipe_id: "waffles".to_owned(),
+                    title: "Waffles".to_owned(),
+                    count: 1,
+                },
+            ]
+        );
+
+        let ingredients = success(
+            app.get("/api/v2/stats/ingredients?limit=2")
+                .await
+                .json::<api::IngredientCountsResponse>(),
+        );
+        assert_eq!(
+            ingredients,
+            vec![
+                api::IngredientCount {
+                    name: "egg".to_owned(),
+                    count: 4,
+                },
+                api::IngredientCount {
+                    name: "flour".to_owned(),
+                    count: 4,
+                },
+            ]
+        );
+
+        let plans = success(
+            app.get("/api/v2/stats/plans")
+                .await
+                .json::<api::PlanStatsResponse>(),
+        );
+        assert_eq!(
+            plans.months,
+            vec![
+                api::MonthCount {
+                    month: "2023-09".to_owned(),
+                    plans: 1,
+                },
+                api::MonthCount {
+                    month: "2023-10".to_owned(),
+                    plans: 1,
+                },
+            ]
+        );
+        assert_eq!(plans.weeks, 2);
+        assert_eq!(plans.average_recipes_per_week, 2.0);
+
+        app.cookie = None;
+        let resp = app.get("/api/v2/stats/plans").await;
+        assert_eq!(resp.status, StatusCode::UNAUTHORIZED);
+    })
+}
+
+#[test]
+fn test_plan_rules_and_suggestions() {
+    run(async {
+        let app = TestApp::logged_in().await;
+        let recipe = |id: &str, title: &str, tags: &str| {
+            let text = format!(
+                "---\ntags: {}\n---\n{}",
+                tags,
+                PANCAKES.replace("Pancakes", title)
+            );
+            RecipeEntry::new(id, text.as_str())
+        };
+        let resp = app
+            .post(
+                "/api/v2/recipes",
+                &vec![
+                    recipe("salmon", "Salmon", "fish"),
+                    recipe("chili", "Chili", "soup"),
+                    recipe("pancakes", "Pancakes", "breakfast"),
+                    recipe("waffles", "Waffles", "breakfast"),
+                ],
+            )
+            .await;
+        assert_eq!(resp.status, StatusCode::OK);
+
+        let resp = app
+            .post(
+                "/api/v2/plan/rules",
+                &api::PlanRule::AtLeast {
+                    tag: "fish".to_owned(),
+                    count: 0,
+                },
+            )
+            .await;
+        assert_eq!(resp.status, StatusCode::UNPROCESSABLE_ENTITY);
+        let rules = vec![
+            api::PlanRule::Season {
+                tag: "Soup".to_owned(),
+                from_month: 10,
+                to_month: 3,
+            },
+            api::PlanRule::AtLeast {
+                tag: "fish".to_owned(),
+                count: 1,
+            },
+            api::PlanRule::NoRepeat { weeks: 1 },
+        ];
+        let mut ids = Vec::new();
+        for rule in rules.iter() {
+            let stored = success(
+                app.post("/api/v2/plan/rules", rule)
+                    .await
+                    .json::<api::PlanRuleResponse>(),
+            );
+            ids.push(stored.id);
+        }
+        let stored = success(
+            app.get("/api/v2/plan/rules")
+                .await
+                .json::<api::PlanRulesResponse>(),
+        );
+        assert_eq!(
+            stored.into_iter().map(|r| r.rule).collect::<Vec<_>>(),
+            rules
+        );
+
+        let resp = app
+            .post(
+                "/api/v2/plan/at/2023-06-05",
+                &vec![("pancakes".to_owned(), 1)],
+            )
+            .await;
+        assert_eq!(resp.status, StatusCode::OK);
+
+        // Chili is out of season and pancakes were planned too recently. The
+        // recipe that meets the fish rule comes first.
+        let suggestions = success(
+            app.get("/api/v2/plan/at/2023-06-10/suggestions")
+                .await
+                .json::<api::PlanSuggestionsResponse>(),
+        );
+        assert_eq!(
+            suggestions
+                .iter()
+                .map(|s| (s.recipe_id.as_str(), s.reason.as_deref()))
+                .collect::<Vec<_>>(),
+            vec![
+                ("salmon", Some("fish at least once a plan")),
+                ("waffles", None),
+            ]
+        );
+        // In December chili is in season and the pancakes were planned long
+        // enough ago.
+        let suggestions = success(
+            app.get("/api/v2/plan/at/2023-12-04/suggestions")
+                .await
+                .json::<api::PlanSuggestionsResponse>(),
+        );
+        assert_eq!(
+            suggestions
+                .iter()
+                .map(|s| s.recipe_id.as_str())
+                .collect::<Vec<_>>(),
+            vec!["salmon", "chili", "pancakes", "waffles"]
+        );
+
+        let violations = success(
+            app.post(
+                "/api/v2/plan/at/2023-06-10/check",
+                &vec![("chili".to_owned(), 1)],
+            )
+            .await
+            .json::<api::RuleViolationsResponse>(),
+        );
+        assert_eq!(
+            violations
+                .iter()
+                .map(|v| (v.rule_id, v.recipe_id.as_deref()))
+                .collect::<Vec<_>>(),
+            vec![(ids[0], Some("chili")), (ids[1], None)]
+        );
+    })
+}
+
+#[test]
+fn test_shared_week() {
+    run(async {
+        let mut app = TestApp::logged_in().await;
+        let resp = app
+            .post(
+                "/api/v2/recipes",
+                &vec![RecipeEntry::new("pancakes", PANCAKES)],
+            )
+            .await;
+        assert_eq!(resp.status, StatusCode::OK);
+        let resp = app
+            .post(
+                "/api/v2/plan/at/2023-10-18",
+                &vec![("pancakes".to_owned(), 1)],
+            )
+            .await;
+        assert_eq!(resp.status, StatusCode::OK);
+        let share = success(
+            app.get("/api/v2/plan/week/2023-10-18/share")
+                .await
+                .json::<api::PlanShareResponse>(),
+        );
+        assert_eq!(share, None);
+
+        let share = success(
+            app.post("/api/v2/plan/week/2023-10-18/share", &())
+                .await
+                .json::<api::PlanShareResponse>(),
+        )
+        .expect("The week should be shared");
+        // Sharing another day in the same week keeps the same link.
+        let again = success(
+            app.post("/api/v2/plan/week/2023-10-20/share", &())
+                .await
+                .json::<api::PlanShareResponse>(),
+        );
+        assert_eq!(again, Some(share.clone()));
+
+        // The link works without logging in.
+        let cookie = app.cookie.take();
+        let week = success(
+            app.get(&format!("/api/v2/shared/plan/{}", share.token))
+                .await
+                .json::<api::SharedWeekResponse>(),
+        );
+        assert_eq!(week.start, share.start);
+        assert_eq!(week.plans.len(), 1);
+        assert_eq!(week.plans[0].recipes[0].title, "Pancakes");
+        assert!(week.plans[0].shopping_list.contains(&"2 egg".to_owned()));
+        let resp = app.get(&share.path()).await;
+        assert_eq!(resp.status, StatusCode::OK);
+        assert!(String::from_utf8_lossy(&resp.body).contains("Pancakes"));
+
+        app.cookie = cookie;
+        let resp = app.delete("/api/v2/plan/week/2023-10-18/share").await;
+        assert_eq!(resp.status, StatusCode::OK);
+        let resp = app
+            .get(&format!("/api/v2/shared/plan/{}", share.token))
+            .await;
+        assert_eq!(resp.status, StatusCode::NOT_FOUND);
+        let resp = app.get(&share.path()).await;
+        assert_eq!(resp.status, StatusCode::NOT_FOUND);
+    })
+}
+
+#[test]
+fn test_share_qr_code() {
+    run(async {
+        let app = TestApp::logged_in().await;
+        let share = success(
+            app.post("/api/v2/plan/week/2023-10-18/share", &())
+                .await
+                .json::<api::PlanShareResponse>(),
+        )
+        .expect("The week should be shared");
+        let resp = app.get(&share.qr_path()).await;
+        assert_eq!(resp.status, StatusCode::OK);
+        assert_eq!(resp.headers.get(header::CONTENT_TYPE).unwrap(), "image/png");
+        assert!(resp.body.starts_with(b"\x89PNG"));
+        let resp = app.get("/share/nosuchtoken/qr.png").await;
+        assert_eq!(resp.status, StatusCode::NOT_FOUND);
+    })
+}
+
+#[test]
+fn test_qr_code_origin() {
+    let mut headers = HeaderMap::new();
+    assert_eq!(qr::request_origin(&headers, qr::Scheme::Http), None);
+    headers.insert(header::HOST, HeaderValue::from_static("kitchen.local:3030"));
+    assert_eq!(
+        qr::request_origin(&headers, qr::Scheme::Http).as_deref(),
+        Some("http://kitchen.local:3030")
+    );
+    assert_eq!(
+        qr::request_origin(&headers, qr::Scheme::Https).as_deref(),
+        Some("https://kitchen.local:3030")
+    );
+    // A proxy in front of kitchen knows better.
+    headers.insert("x-forwarded-proto", HeaderValue::from_static("https"));
+    assert_eq!(
+        qr::request_origin(&headers, qr::Scheme::Http).as_deref(),
+        Some("https://kitchen.local:3030")
+    );
+}
+
+#[test]
+fn test_freezer_portions() {
+    run(async {
+        let mut app = TestApp::logged_in().await;
+        let resp = app
+            .post(
+                "/api/v2/recipes",
+                &vec![RecipeEntry::new("pancakes", PANCAKES)],
+            )
+            .await;
+        assert_eq!(resp.status, StatusCode::OK);
+        let resp = app
+            .post(
+                "/api/v2/plan/at/2023-10-18",
+                &vec![("pancakes".to_owned(), 1)],
+            )
+            .await;
+        assert_eq!(resp.status, StatusCode::OK);
+
+        // Extra portions cooked with a plan go in the freezer.
+        let resp = app
+            .post(
+                "/api/v2/plan/at/2023-10-18/cooked",
+                &api::MarkCooked {
+                    recipe_id: "pancakes".to_owned(),
+                    extra_portions: 3,
+                    deplete: false,
+                },
+            )
+            .await;
+        assert_eq!(resp.status, StatusCode::OK);
+        let cooked = success(
+            app.get("/api/v2/plan/at/2023-10-18/cooked")
+                .await
+                .json::<api::CookedResponse>(),
+        );
+        assert!(cooked.contains("pancakes"));
+        let items = success(
+            app.get("/api/v2/freezer")
+                .await
+                .json::<api::FreezerResponse>(),
+        );
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].recipe_id, "pancakes");
+        assert_eq!(items[0].portions, 3);
+        let item_id = items[0].id;
+
+        // Marking it cooked again doesn't freeze the portions twice.
+        let resp = app
+            .post(
+                "/api/v2/plan/at/2023-10-18/cooked",
+                &api::MarkCooked {
+                    recipe_id: "pancakes".to_owned(),
+                    extra_portions: 3,
+                    deplete: false,
+                },
+            )
+            .await;
+        assert_eq!(resp.status, StatusCode::OK);
+        let items = success(
+            app.get("/api/v2/freezer")
+                .await
+                .json::<api::FreezerResponse>(),
+        );
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].portions, 3);
+
+        let path = format!("/api/v2/freezer/{}/take", item_id);
+        let item = success(app.post(&path, &2).await.json::<api::FreezerItemResponse>());
+        assert_eq!(item.map(|item| item.portions), Some(1));
+        // Taking the last portions empties the freezer.
+        let item = success(app.post(&path, &1).await.json::<api::FreezerItemResponse>());
+        assert_eq!(item, None);
+        let items = success(
+            app.get("/api/v2/freezer")
+                .await
+                .json::<api::FreezerResponse>(),
+        );
+        assert!(items.is_empty());
+        let resp = app.post(&path, &1).await;
+        assert_eq!(resp.status, StatusCode::NOT_FOUND);
+
+        // Nobody else can take portions out of the freezer.
+        let item = success(
+            app.post(
+                "/api/v2/freezer",
+                &api::NewFreezerItem {
+                    recipe_id: "pancakes".to_owned(),
+                    portions: 2,
+                    frozen_on: NaiveDate::from_ymd_opt(2023, 10, 1).unwrap(),
+                },
+            )
+            .await
+            .json::<api::Response<api::FreezerItem>>(),
+        );
+        let path = format!("/api/v2/freezer/{}/take", item.id);
+        let cookie = app.cookie.take();
+        app.add_user("other", PASSWORD).await;
+        let resp = app.login("other", PASSWORD).await;
+        assert_eq!(resp.status, StatusCode::OK);
+        let resp = app.post(&path, &1).await;
+        assert_eq!(resp.status, StatusCode::NOT_FOUND);
+        app.cookie = cookie;
+
+        // Freezer meals are already cooked so there is nothing to buy for them.
+        let resp = app
+            .post(
+                "/api/v2/plan/at/2023-10-18/freezer",
+                &BTreeSet::from(["pancakes".to_owned()]),
+            )
+            .await;
+        assert_eq!(resp.status, StatusCode::OK);
+        let share = success(
+            app.post("/api/v2/plan/week/2023-10-18/share", &())
+                .await
+                .json::<api::PlanShareResponse>(),
+        )
+        .expect("The week should be shared");
+        let week = success(
+            app.get(&format!("/api/v2/shared/plan/{}", share.token))
+                .await
+                .json::<api::SharedWeekResponse>(),
+        );
+        assert_eq!(week.plans[0].recipes[0].title, "Pancakes");
+        assert!(week.plans[0].shopping_list.is_empty());
+
+        // Cooking a freezer meal takes its portions out of the freezer.
+        let resp = app
+            .post(
+                "/api/v2/plan/at/2023-10-19",
+                &vec![("pancakes".to_owned(), 1)],
+            )
+            .await;
+        assert_eq!(resp.status, StatusCode::OK);
+        let resp = app
+            .post(
+                "/api/v2/plan/at/2023-10-19/freezer",
+                &BTreeSet::from(["pancakes".to_owned()]),
+            )
+            .await;
+        assert_eq!(resp.status, StatusCode::OK);
+        let resp = app
+            .post(
+                "/api/v2/plan/at/2023-10-19/cooked",
+                &api::MarkCooked {
+                    recipe_id: "pancakes".to_owned(),
+                    extra_portions: 0,
+                    deplete: true,
+                },
+            )
+            .await;
+        assert_eq!(resp.status, StatusCode::OK);
+        let items = success(
+            app.get("/api/v2/freezer")
+                .await
+                .json::<api::FreezerResponse>(),
+        );
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].portions, 1);
+    })
+}
+
+const STEW: &'static str = "---
+serves: 4
+---
+title: Stew
+
+step:
+
+timer: 90 min simmer
+2 carrot
+1 cup broth
+1 tsp salt
+
+Simmer everything until the carrots are soft.
+";
+
+#[test]
+fn test_plans_in_range() {
+    run(async {
+        let app = TestApp::logged_in().await;
+        let resp = app
+            .post(
+                "/api/v2/recipes",
+                &vec![RecipeEntry::new("pancakes", PANCAKES)],
+            )
+            .await;
+        assert_eq!(resp.status, StatusCode::OK);
+        for (date, count) in [("2023-10-16", 1), ("2023-10-18", 2), ("2023-10-23", 3)] {
+            let resp = app
+                .post(
+                    &format!("/api/v2/plan/at/{}", date),
+                    &vec![("pancakes".to_owned(), count)],
+                )
+                .await;
+            assert_eq!(resp.status, StatusCode::OK);
+        }
+        let date = |day| NaiveDate::from_ymd_opt(2023, 10, day).unwrap();
+
+        // Both ends of the range are included.
+        let plans = success(
+            app.get("/api/v2/plan/range?from=2023-10-16&to=2023-10-18")
+                .await
+                .json::<api::PlanHistoryResponse>(),
+        );
+        assert_eq!(
+            plans,
+            BTreeMap::from([
+                (date(16), vec![("pancakes".to_owned(), 1)]),
+                (date(18), vec![("pancakes".to_owned(), 2)]),
+            ])
+        );
+        let plans = success(
+            app.get("/api/v2/plan/range?from=2023-10-19&to=2023-10-22")
+                .await
+                .json::<api::PlanHistoryResponse>(),
+        );
+        assert!(plans.is_empty());
+
+        let resp = app
+            .get("/api/v2/plan/range?from=2023-10-18&to=2023-10-16")
+            .await
+            .json::<api::PlanHistoryResponse>();
+        assert!(matches!(
+            resp,
+            api::Response::Err {
+                status: 422,
+                code: api::ErrorCode::Validation { field },
+                ..
+            } if field == "to"
+        ));
+        let resp = app.get("/api/v2/plan/range?from=2023-10-16").await;
+        assert_eq!(resp.status, StatusCode::BAD_REQUEST);
+    })
+}
+
+#[test]
+fn test_plan_today() {
+    run(async {
+        let app = TestApp::logged_in().await;
+        let resp = app
+            .post(
+                "/api/v2/recipes",
+                &vec![
+                    RecipeEntry::new("pancakes", PANCAKES),
+                    RecipeEntry::new("stew", STEW),
+                ],
+            )
+            .await;
+        assert_eq!(resp.status, StatusCode::OK);
+        // Today is always a later date at +14:00 than at -12:00.
+        let east = chrono::FixedOffset::east_opt(14 * 3600).unwrap();
+        let west = chrono::FixedOffset::east_opt(-12 * 3600).unwrap();
+        let today = chrono::Utc::now().with_timezone(&east).date_naive();
+        let settings = BTreeMap::from([
+            (
+                api::TIMEZONE_SETTING.to_owned(),
+                api::format_utc_offset(&east),
+            ),
+            (api::HOUSEHOLD_SIZE_SETTING.to_owned(), "2".to_owned()),
+        ]);
+        let resp = app.post("/api/v2/settings", &settings).await;
+        assert_eq!(resp.status, StatusCode::OK);
+        let resp = app
+            .post(
+                &format!("/api/v2/plan/at/{}", today),
+                &vec![("pancakes".to_owned(), 1), ("stew".to_owned(), 2)],
+            )
+            .await;
+        assert_eq!(resp.status, StatusCode::OK);
+        // Freezer meals are already cooked so nothing is bought for them.
+        let resp = app
+            .post(
+                &format!("/api/v2/plan/at/{}/freezer", today),
+                &BTreeSet::from(["pancakes".to_owned()]),
+            )
+            .await;
+        assert_eq!(resp.status, StatusCode::OK);
+        let broth = IngredientKey::new("broth".to_owned(), None, "Volume".to_owned());
+        let resp = app
+            .post(
+                &format!("/api/v2/inventory/at/{}/ops", today),
+                &vec![
+                    api::InventoryOp::Check(broth),
+                    api::InventoryOp::SetExtras(vec![("1".to_owned(), "bread".to_owned())]),
+                ],
+            )
+            .await;
+        assert_eq!(resp.status, StatusCode::OK);
+
+        let data = success(
+            app.get("/api/v2/plan/today")
+                .await
+                .json::<api::TodayResponse>(),
+        );
+        assert_eq!(data.date, today);
+        assert_eq!(data.plan_date, Some(today));
+        let recipes: BTreeSet<(&str, i32)> = data
+            .recipes
+            .iter()
+            .map(|r| (r.recipe_id.as_str(), r.count))
+            .collect();
+        assert_eq!(recipes, BTreeSet::from([("pancakes", 1), ("stew", 2)]));
+        assert!(data.summary.contains("Stew"), "{}", data.summary);
+        // The household size scales amounts but not the number of items. The
+        // carrots, salt and the extra bread are left to buy.
+        assert_eq!(data.shopping_items_remaining, 3);
+        assert_eq!(
+            data.timers,
+            vec![api::TodayTimer {
+                recipe_id: "stew".to_owned(),
+                title: "Stew".to_owned(),
+                step: 1,
+                label: Some("simmer".to_owned()),
+                seconds: 90 * 60,
+            }]
+        );
+
+        // It is still yesterday or earlier at -12:00 so the plan hasn't
+        // started yet.
+        let settings = BTreeMap::from([(
+            api::TIMEZONE_SETTING.to_owned(),
+            api::format_utc_offset(&west),
+        )]);
+        let resp = app.post("/api/v2/settings", &settings).await;
+        assert_eq!(resp.status, StatusCode::OK);
+        let data = success(
+            app.get("/api/v2/plan/today")
+                .await
+                .json::<api::TodayResponse>(),
+        );
+        assert!(data.date < today);
+        assert_eq!(data.plan_date, None);
+        assert!(data.recipes.is_empty());
+        assert_eq!(data.shopping_items_remaining, 0);
+
+        let app = TestApp::new().await;
+        let resp = app.get("/api/v2/plan/today").await;
+        assert_eq!(resp.status, StatusCode::UNAUTHORIZED);
+    })
+}
+
+#[test]
+fn test_mark_cooked_uses_up_the_inventory() {
+    run(async {
+        let app = TestApp::logged_in().await;
+        let resp = app
+            .post(
+                "/api/v2/recipes",
+                &vec![RecipeEntry::new("pancakes", PANCAKES)],
+            )
+            .await;
+        assert_eq!(resp.status, StatusCode::OK);
+        let resp = app
+            .post(
+                "/api/v2/plan/at/2023-10-20",
+                &vec![("pancakes".to_owned(), 2)],
+            )
+            .await;
+        assert_eq!(resp.status, StatusCode::OK);
+        let flour = IngredientKey::new("flour".to_owned(), None, "Volume".to_owned());
+        let milk = IngredientKey::new("milk".to_owned(), None, "Volume".to_owned());
+        let egg = IngredientKey::new("egg".to_owned(), None, "Count".to_owned());
+        let salt = IngredientKey::new("salt".to_owned(), None, "Weight".to_owned());
+        let resp = app
+            .post(
+                "/api/v2/inventory/at/2023-10-20",
+                &(
+                    Vec::<IngredientKey>::new(),
+                    vec![
+                        (flour.clone(), "1 cup".to_owned()),
+                        (milk.clone(), "3 cups".to_owned()),
+                        (egg.clone(), "5".to_owned()),
+                        (salt.clone(), "10 g".to_owned()),
+                    ],
+                    Vec::<(String, String)>::new(),
+                ),
+            )
+            .await;
+        assert_eq!(resp.status, StatusCode::OK);
+
+        let cooked = api::MarkCooked {
+            recipe_id: "pancakes".to_owned(),
+            extra_portions: 0,
+            deplete: true,
+        };
+        let resp = app.post("/api/v2/plan/at/2023-10-20/cooked", &cooked).await;
+        assert_eq!(resp.status, StatusCode::OK);
+        // The plan makes the recipe twice. Running out leaves nothing and
+        // ingredients the recipe doesn't use are left alone.
+        let expected = BTreeMap::from([
+            (flour, "0 cup".to_owned()),
+            (milk, "1 cup".to_owned()),
+            (egg, "1".to_owned()),
+            (salt, "10 g".to_owned()),
+        ]);
+        let inventory = success(
+            app.get("/api/v2/inventory/at/2023-10-20")
+                .await
+                .json::<api::InventoryResponse>(),
+        );
+        assert_eq!(inventory_changes(&inventory).1, expected);
+
+        // Cooking it again doesn't use anything up twice.
+        let resp = app.post("/api/v2/plan/at/2023-10-20/cooked", &cooked).await;
+        assert_eq!(resp.status, StatusCode::OK);
+        let inventory = success(
+            app.get("/api/v2/inventory/at/2023-10-20")
+                .await
+                .json::<api::InventoryResponse>(),
+        );
+        assert_eq!(inventory_changes(&inventory).1, expected);
+    })
+}
+
+#[test]
+fn test_inventory_round_trip() {
+    run(async {
+        let app = TestApp::logged_in().await;
+        let flour = IngredientKey::new("flour".to_owned(), None, "Volume".to_owned());
+        let milk = IngredientKey::new("milk".to_owned(), None, "Volume".to_owned());
+        let resp = app
+            .post(
+                "/api/v2/inventory",
+                &(
+                    vec![flour.clone()],
+                    vec![(milk.clone(), "2 cups".to_owned())],
+                    vec![("1".to_owned(), "lemon".to_owned())],
+                ),
+            )
+            .await;
+        assert_eq!(resp.status, StatusCode::OK);
+        let inventory = success(
+            app.get("/api/v2/inventory")
+                .await
+                .json::<api::InventoryResponse>(),
+        );
+        assert_eq!(inventory.filtered_ingredients, vec![flour]);
+        assert_eq!(inventory.modified_amts, vec![(milk, "2 cups".to_owned())]);
+        assert_eq!(
+            inventory.extra_items,
+            vec![("1".to_owned(), "lemon".to_owned())]
+        );
+    })
+}
+
+/// The checked off ingredients and changed amounts of an inventory without
+/// depending on the order they came back in.
+fn inventory_changes(
+    inventory: &api::InventoryData,
+) -> (BTreeSet<IngredientKey>, BTreeMap<IngredientKey, String>) {
+    (
+        inventory.filtered_ingredients.iter().cloned().collect(),
+        inventory.modified_amts.iter().cloned().collect(),
+    )
+}
+
+#[test]
+fn test_inventory_ops_from_two_clients_are_merged() {
+    run(async {
+        let mut app = TestApp::logged_in().await;
+        let first = app.cookie.clone();
+        let resp = app.login(USER, PASSWORD).await;
+        assert_eq!(resp.status, StatusCode::OK);
+        let second = app.cookie.clone();
+        assert_ne!(first, second);
+        let app = &app;
+        let send_ops = move |cookie: &Option<String>, ops: Vec<api::InventoryOp>| {
+            let mut req = Request::builder()
+                .method(Method::POST)
+                .uri("/api/v2/inventory/at/2023-10-16/ops")
+                .header(header::HOST, "localhost")
+                .header(header::CONTENT_TYPE, "application/json");
+            if let Some(cookie) = cookie {
+                req = req.header(header::COOKIE, cookie);
+            }
+            let body = serde_json::to_string(&ops).expect("Unable to serialize the ops");
+            app.send(req.body(Body::from(body)).expect("Invalid request"))
+        };
+        let key = |name: &str| IngredientKey::new(name.to_owned(), None, "Volume".to_owned());
+        use api::InventoryOp::{Check, SetAmt, Uncheck};
+
+        let resp = send_ops(&first, vec![Check(key("flour")), Check(key("sugar"))]).await;
+        assert_eq!(resp.status, StatusCode::OK);
+        let resp = send_ops(
+            &second,
+            vec![Check(key("eggs")), SetAmt(key("milk"), "2 cups".to_owned())],
+        )
+        .await;
+        assert_eq!(resp.status, StatusCode::OK);
+        let resp = send_ops(
+            &first,
+            vec![
+                Uncheck(key("sugar")),
+                SetAmt(key("butter"), "1 stick".to_owned()),
+            ],
+        )
+        .await;
+        assert_eq!(resp.status, StatusCode::OK);
+        let (a, b) = futures::join!(
+            send_ops(&first, vec![Check(key("salt"))]),
+            send_ops(&second, vec![SetAmt(key("milk"), "3 cups".to_owned())]),
+        );
+        assert_eq!(a.status, StatusCode::OK);
+        assert_eq!(b.status, StatusCode::OK);
+
+        let expected = (
+            BTreeSet::from([key("eggs"), key("flour"), key("salt")]),
+            BTreeMap::from([
+                (key("butter"), "1 stick".to_owned()),
+                (key("milk"), "3 cups".to_owned()),
+            ]),
+        );
+        let inventory = success(
+            app.get("/api/v2/inventory/at/2023-10-16")
+                .await
+                .json::<api::InventoryResponse>(),
+        );
+        assert_eq!(inventory_changes(&inventory), expected);
+        // The last snapshot is the fully merged inventory.
+        let history = success(
+            app.get("/api/v2/inventory/at/2023-10-16/history")
+                .await
+                .json::<api::InventoryHistoryResponse>(),
+        );
+        let last = history.last().expect("No inventory history was kept");
+        assert_eq!(inventory_changes(&last.inventory), expected);
+    })
+}
+
+#[test]
+fn test_inventory_diff() {
+    run(async {
+        let app = TestApp::logged_in().await;
+        let key = |name: &str| IngredientKey::new(name.to_owned(), None, "Volume".to_owned());
+        use api::InventoryOp::{Check, SetAmt, SetExtras};
+
+        let resp = app
+            .post(
+                "/api/v2/inventory/at/2023-10-16/ops",
+                &vec![
+                    Check(key("flour")),
+                    SetAmt(key("milk"), "2 cups".to_owned()),
+                ],
+            )
+            .await;
+        assert_eq!(resp.status, StatusCode::OK);
+        let resp = app
+            .post(
+                "/api/v2/inventory/at/2023-10-16/ops",
+                &vec![
+                    Check(key("eggs")),
+                    SetAmt(key("milk"), "3 cups".to_owned()),
+                    SetExtras(vec![("1".to_owned(), "lemon".to_owned())]),
+                ],
+            )
+            .await;
+        assert_eq!(resp.status, StatusCode::OK);
+
+        // A plan compared with itself shows what changed since it was first
+        // saved.
+        let diff = success(
+            app.get("/api/v2/inventory/diff?from=2023-10-16&to=2023-10-16")
+                .await
+                .json::<api::InventoryDiffResponse>(),
+        );
+        assert_eq!(
+            diff,
+            api::InventoryDiff {
+                checked_off: vec![key("eggs")],
+                changed_amts: vec![(
+                    key("milk"),
+                    Some("2 cups".to_owned()),
+                    Some("3 cups".to_owned())
+                )],
+                added_extras: vec![("1".to_owned(), "lemon".to_owned())],
+                ..Default::default()
+            }
+        );
+
+        let resp = app
+            .post(
+                "/api/v2/inventory/at/2023-10-23/ops",
+                &vec![Check(key("eggs"))],
+            )
+            .await;
+        assert_eq!(resp.status, StatusCode::OK);
+        let diff = success(
+            app.get("/api/v2/inventory/diff?from=2023-10-16&to=2023-10-23")
+                .await
+                .json::<api::InventoryDiffResponse>(),
+        );
+        assert_eq!(diff.unchecked, vec![key("flour")]);
+        assert!(diff.checked_off.is_empty());
+        assert_eq!(
+            diff.changed_amts,
+            vec![(key("milk"), Some("3 cups".to_owned()), None)]
+        );
+        assert_eq!(
+            diff.removed_extras,
+            vec![("1".to_owned(), "lemon".to_owned())]
+        );
+
+        let resp = app.get("/api/v2/inventory/diff?from=2023-10-16").await;
+        assert_eq!(resp.status, StatusCode::BAD_REQUEST);
+    })
+}
+
+#[test]
+fn test_prune_history() {
+    run(async {
+        let app = TestApp::logged_in().await;
+        let resp = app
+            .post(
+                "/api/v2/recipes",
+                &vec![RecipeEntry::new("pancakes", PANCAKES)],
+            )
+            .await;
+        assert_eq!(resp.status, StatusCode::OK);
+        for date in ["2023-06-01", "2023-09-01"] {
+            let plan = vec![("pancakes".to_owned(), 1)];
+            let resp = app.post(&format!("/api/v2/plan/at/{}", date), &plan).await;
+            assert_eq!(resp.status, StatusCode::OK);
+            let flour = IngredientKey::new("flour".to_owned(), None, "Volume".to_owned());
+            let inventory: (
+                Vec<IngredientKey>,
+                Vec<(IngredientKey, String)>,
+                Vec<(String, String)>,
+            ) = (vec![flour], Vec::new(), Vec::new());
+            let resp = app
+                .post(&format!("/api/v2/inventory/at/{}", date), &inventory)
+                .await;
+            assert_eq!(resp.status, StatusCode::OK);
+        }
+        // A session from before sessions were saved with a user, an old
+        // session and refresh token for the user and an expired refresh token.
+        for sql in [
+            "insert into sessions (id, session_value, created_at) values ('legacy', x'', '2023-01-01 00:00:00')",
+            "insert into sessions (id, session_value, user_id, created_at) values ('old', x'', 'cook', '2023-06-01 00:00:00')",
+            "insert into refresh_tokens (token, user_id, expires_at, created_at) values ('old', 'cook', '2099-01-01 00:00:00', '2023-06-01 00:00:00')",
+            "insert into refresh_tokens (token, user_id, expires_at) values ('expired', 'cook', '2023-01-01 00:00:00')",
+        ] {
+            app.app_store
+                .execute_sql(sql)
+                .await
+                .expect("Failed to add old logins");
+        }
+        let today = NaiveDate::from_ymd_opt(2023, 10, 16).unwrap();
+        let june = NaiveDate::from_ymd_opt(2023, 6, 1).unwrap();
+        let september = NaiveDate::from_ymd_opt(2023, 9, 1).unwrap();
+
+        let report = app
+            .app_store
+            .prune_history(None, today, false)
+            .await
+            .expect("Failed to prune history");
+        assert!(report.users.is_empty());
+        assert_eq!(report.sessions, 0);
+        assert_eq!(report.refresh_tokens, 1);
+
+        let report = app
+            .app_store
+            .prune_history(Some(3), today, true)
+            .await
+            .expect("Failed to prune history");
+        assert_eq!(
+            report.users,
+            vec![storage::UserPrune {
+                user_id: USER.to_owned(),
+                cutoff: NaiveDate::from_ymd_opt(2023, 7, 16).unwrap(),
+                plans: vec![june],
+                inventory_snapshots: 1,
+                sessions: 1,
+                refresh_tokens: 1,
+            }]
+        );
+        assert_eq!(report.sessions, 1);
+        assert_eq!(report.refresh_tokens, 0);
+        let plans = success(
+            app.get("/api/v2/plan/all")
+                .await
+                .json::<api::Response<Vec<NaiveDate>>>(),
+        );
+        assert_eq!(plans.len(), 2);
+
+        app.app_store
+            .prune_history(Some(3), today, false)
+            .await
+            .expect("Failed to prune history");
+        let report = app
+            .app_store
+            .prune_history(Some(3), today, true)
+            .await
+            .expect("Failed to prune history");
+        assert_eq!(report, storage::PruneReport::default());
+        // The login the test is using is newer than the cutoff.
+        let plans = success(
+            app.get("/api/v2/plan/all")
+                .await
+                .json::<api::Response<Vec<NaiveDate>>>(),
+        );
+        assert_eq!(plans, vec![september]);
+
+        // The user's own setting wins over the instance default.
+        let settings = BTreeMap::from([(api::RETENTION_SETTING.to_owned(), "1".to_owned())]);
+        let resp = app.post("/api/v2/settings", &settings).await;
+        assert_eq!(resp.status, StatusCode::OK);
+        let report = app
+            .app_store
+            .prune_history(Some(3), today, false)
+            .await
+            .expect("Failed to prune history");
+        assert_eq!(report.users.len(), 1);
+        assert_eq!(report.users[0].plans, vec![september]);
+        assert_eq!(report.users[0].inventory_snapshots, 1);
+    })
+}
+
+#[test]
+fn test_streamed_exports() {
+    run(async {
+        let app = TestApp::logged_in().await;
+        let resp = app
+            .post(
+                "/api/v2/recipes",
+                &vec![
+                    RecipeEntry::new("waffles", PANCAKES),
+                    RecipeEntry::new("pancakes", PANCAKES),
+                ],
+            )
+            .await;
+        assert_eq!(resp.status, StatusCode::OK);
+        for date in ["2023-09-01", "2023-06-01"] {
+            let plan = vec![("pancakes".to_owned(), 2)];
+            let resp = app.post(&format!("/api/v2/plan/at/{}", date), &plan).await;
+            assert_eq!(resp.status, StatusCode::OK);
+        }
+
+        let resp = app.get("/api/v2/account/export").await;
+        assert_eq!(resp.status, StatusCode::OK);
+        assert!(resp.headers[header::CONTENT_DISPOSITION]
+            .to_str()
+            .unwrap()
+            .starts_with("attachment"));
+        let export: api::AccountExport = resp.json();
+        assert_eq!(export.user_id, USER);
+        let ids: Vec<&str> = export.recipes.iter().map(|e| e.recipe_id()).collect();
+        assert_eq!(ids, vec!["pancakes", "waffles"]);
+        assert_eq!(export.recipes[0].recipe_text(), PANCAKES);
+        assert_eq!(export.plans.len(), 2);
+        let june = NaiveDate::from_ymd_opt(2023, 6, 1).unwrap();
+        assert_eq!(
+            export.plans[&june].recipe_counts,
+            vec![("pancakes".to_owned(), 2)]
+        );
+
+        let resp = app.get("/api/v2/plan/history.csv").await;
+        assert_eq!(resp.status, StatusCode::OK);
+        assert_eq!(resp.headers[header::CONTENT_TYPE], "text/csv");
+        assert_eq!(
+            String::from_utf8(resp.body).unwrap(),
+            "date,recipe_id,title,count\n\
+             2023-06-01,pancakes,Pancakes,2\n\
+             2023-09-01,pancakes,Pancakes,2\n"
+        );
+
+        let app = TestApp::new().await;
+        let resp = app.get("/api/v2/plan/history.csv").await;
+        assert_eq!(resp.status, StatusCode::UNAUTHORIZED);
+    });
+}
+
+#[test]
+fn test_fs_blob_store() {
+    run(async {
+        let dir = std::env::temp_dir().join(format!("kitchen-test-{}", uuid::Uuid::new_v4()));
+        let blobs = FsBlobStore::new(&dir);
+        assert_eq!(blobs.get("backups/a.json").await, Ok(None));
+        blobs
+            .put("backups/a.json", b"{}".to_vec())
+            .await
+            .expect("Failed to put blob");
+        assert_eq!(blobs.get("backups/a.json").await, Ok(Some(b"{}".to_vec())));
+        blobs
+            .delete("backups/a.json")
+            .await
+            .expect("Failed to delete blob");
+        assert_eq!(blobs.get("backups/a.json").await, Ok(None));
+        assert!(blobs.put("../escape", Vec::new()).await.is_err());
+        assert!(blobs.get("backups/.hidden").await.is_err());
+
+        // Writes to the same key at once each finish with a whole blob.
+        let (first, second) = futures::join!(
+            blobs.put("backups/b.json", vec![b'1'; 1 << 20]),
+            blobs.put("backups/b.json", vec![b'2'; 1 << 20]),
+        );
+        assert_eq!(first, Ok(()));
+        assert_eq!(second, Ok(()));
+        let blob = blobs
+            .get("backups/b.json")
+            .await
+            .expect("Failed to get blob")
+            .expect("The blob is missing");
+        assert!(blob == vec![b'1'; 1 << 20] || blob == vec![b'2'; 1 << 20]);
+        let files = std::fs::read_dir(dir.join("backups"))
+            .expect("Failed to list blobs")
+            .count();
+        assert_eq!(files, 1);
+        let _ = std::fs::remove_dir_all(dir);
+    })
+}
+
+#[test]
+fn test_scheduler_delay() {
+    let now = NaiveDate::from_ymd_opt(2023, 10, 20)
+        .unwrap()
+        .and_hms_opt(12, 0, 0)
+        .unwrap();
+    let hour = std::time::Duration::from_secs(60 * 60);
+    assert_eq!(scheduler::delay(None, now, hour), std::time::Duration::ZERO);
+    let last_run = now - chrono::Duration::minutes(15);
+    assert_eq!(
+        scheduler::delay(Some(last_run), now, hour),
+        std::time::Duration::from_secs(45 * 60)
+    );
+    let last_run = now - chrono::Duration::hours(3);
+    assert_eq!(
+        scheduler::delay(Some(last_run), now, hour),
+        std::time::Duration::ZERO
+    );
+}
+
+#[test]
+fn test_job_last_run_round_trip() {
+    run(async {
+        let app = TestApp::new().await;
+        assert_eq!(
+            app.app_store.fetch_job_last_run("prune").await.unwrap(),
+            None
+        );
+        let last_run = NaiveDate::from_ymd_opt(2023, 10, 20)
+            .unwrap()
+            .and_hms_opt(12, 0, 0)
+            .unwrap();
+        for last_run in [last_run, last_run + chrono::Duration::days(1)] {
+            app.app_store
+                .save_job_last_run("prune", last_run)
+                .await
+                .expect("Failed to save the last run");
+            assert_eq!(
+                app.app_store.fetch_job_last_run("prune").await.unwrap(),
+                Some(last_run)
+            );
+        }
+        assert_eq!(
+            app.app_store.fetch_job_last_run("backup").await.unwrap(),
+            None
+        );
+    })
+}
+
+#[test]
+fn test_image_upload() {
+    run(async {
+        let mut app = TestApp::logged_in().await;
+        let png = vec![0x89, b'P', b'N', b'G'];
+        let upload = success(
+            app.post_bytes("/api/v2/images", "image/png", png.clone())
+                .await
+                .json::<api::UploadedImageResponse>(),
+        );
+        assert!(upload.url.starts_with("/api/v2/images/"));
+        assert!(upload.url.ends_with(".png"));
+
+        let resp = app
+            .post_bytes("/api/v2/images", "text/plain", b"hello".to_vec())
+            .await;
+        assert_eq!(resp.status, StatusCode::UNPROCESSABLE_ENTITY);
+        // An svg could run scripts from our origin.
+        let resp = app
+            .post_bytes(
+                "/api/v2/images",
+                "image/svg+xml",
+                b"<svg><script>alert(1)</script></svg>".to_vec(),
+            )
+            .await;
+        assert_eq!(resp.status, StatusCode::UNPROCESSABLE_ENTITY);
+
+        // Images can be fetched by anyone with the url.
+        app.cookie = None;
+        let resp = app.get(&upload.url).await;
+        assert_eq!(resp.status, StatusCode::OK);
+        assert_eq!(resp.headers.get(header::CONTENT_TYPE).unwrap(), "image/png");
+        assert_eq!(resp.body, png);
+        let resp = app.get("/api/v2/images/missing.png").await;
+        assert_eq!(resp.status, StatusCode::NOT_FOUND);
+        let resp = app
+            .post_bytes("/api/v2/images", "image/png", vec![1, 2, 3])
+            .await;
+        assert_eq!(resp.status, StatusCode::UNAUTHORIZED);
+    })
+}
+
+#[test]
+fn test_users_do_not_see_each_others_recipes() {
+    run(async {
+        let app = TestApp::logged_in().await;
+        let resp = app
+            .post(
+                "/api/v2/recipes",
+                &vec![RecipeEntry::new("pancakes", PANCAKES)],
+            )
+            .await;
+        assert_eq!(resp.status, StatusCode::OK);
+
+        let mut app = app;
+        app.add_user("other", PASSWORD).await;
+        let resp = app.login("other", PASSWORD).await;
+        assert_eq!(resp.status, StatusCode::OK);
+        let recipes = success(
+            app.get("/api/v2/recipes")
+                .await
+                .json::<api::RecipeEntryResponse>(),
+        );
+        assert!(recipes.is_empty());
+    })
+}
+
+#[test]
+fn test_request_id_is_returned() {
+    run(async {
+        let app = TestApp::new().await;
+        let resp = app.get("/api/v2/account").await;
+        let request_id = resp
+            .headers
+            .get(REQUEST_ID)
+            .expect("Missing request id")
+            .to_str()
+            .unwrap();
+        assert_eq!(request_id.len(), 32);
+    })
+}
+
+#[test]
+fn test_traceparent_is_propagated() {
+    run(async {
+        let app = TestApp::new().await;
+        let trace_id = "4bf92f3577b34da6a3ce929d0e0e4736";
+        let req = Request::builder()
+            .uri("/api/v2/account")
+            .header(header::HOST, "localhost")
+            .header(TRACEPARENT, format!("00-{}-00f067aa0ba902b7-01", trace_id))
+            .body(Body::empty())
+            .unwrap();
+        let resp = app.send(req).await;
+        assert_eq!(resp.headers.get(REQUEST_ID).unwrap(), trace_id);
+        let traceparent = resp.headers.get(TRACEPARENT).unwrap().to_str().unwrap();
+        let parsed = TraceParent::parse(traceparent).expect("Invalid traceparent");
+        assert_eq!(parsed.trace_id, trace_id);
+        assert_ne!(parsed.parent_id, "00f067aa0ba902b7");
+    })
+}
+
+#[test]
+fn test_traceparent_parse() {
+    assert_eq!(
+        TraceParent::parse("00-4BF92F3577B34DA6A3CE929D0E0E4736-00f067aa0ba902b7-01"),
+        Some(TraceParent {
+            trace_id: "4bf92f3577b34da6a3ce929d0e0e4736".to_owned(),
+            parent_id: "00f067aa0ba902b7".to_owned(),
+        })
+    );
+    assert_eq!(TraceParent::parse(""), None);
+    assert_eq!(TraceParent::parse("00-abc-00f067aa0ba902b7-01"), None);
+    assert_eq!(
+        TraceParent::parse("00-00000000000000000000000000000000-00f067aa0ba902b7-01"),
+        None
+    );
+    assert_eq!(
+        TraceParent::parse("ff-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01"),
+        None
+    );
+}
+
+#[test]
+fn test_chunked_body_over_the_limit_is_rejected() {
+    run(async {
+        let app = TestApp::logged_in().await;
+        // A stream body goes out without a Content-Length.
+        let chunks = (0..11).map(|_| Ok::<_, std::io::Error>(vec![b' '; 1024 * 1024]));
+        let mut req = Request::builder()
+            .method(Method::POST)
+            .uri("/api/v2/recipes")
+            .header(header::HOST, "localhost")
+            .header(header::CONTENT_TYPE, "application/json");
+        if let Some(cookie) = &app.cookie {
+            req = req.header(header::COOKIE, cookie);
+        }
+        let req = req
+            .body(Body::wrap_stream(futures::stream::iter(chunks)))
+            .expect("Invalid request");
+        assert!(req.headers().get(header::CONTENT_LENGTH).is_none());
+        let resp = app.send(req).await;
+        assert_eq!(resp.status, StatusCode::PAYLOAD_TOO_LARGE);
+        let body = resp.json::<api::EmptyResponse>();
+        assert!(matches!(
+            body,
+            api::Response::Err {
+                code: api::ErrorCode::PayloadTooLarge,
+                ..
+            }
+        ));
+    })
+}
+
+#[test]
+fn test_client_errors_are_accepted_without_login() {
+    run(async {
+        let app = TestApp::new().await;
+        let resp = app
+            .post(
+                "/api/v2/client_errors",
+                &api::ClientError {
+                    kind: api::ClientErrorKind::Panic,
+                    message: "x".repeat(10_000),
+                    location: Some("src/lib.rs:1:1".to_owned()),
+                    url: "http://localhost/ui/plan".to_owned(),
+                    user_agent: "test".to_owned(),
+                    version: "0.0.0".to_owned(),
+                },
+            )
+            .await;
+        assert_eq!(resp.status, StatusCode::OK);
+    })
+}
+
+#[test]
+fn test_client_errors_are_rate_limited() {
+    run(async {
+        let app = TestApp::new().await;
+        let report = api::ClientError {
+            kind: api::ClientErrorKind::Error,
+            message: "first line\nERROR forged line".to_owned(),
+            location: None,
+            url: "http://localhost/ui/plan".to_owned(),
+            user_agent: "test".to_owned(),
+            version: "0.0.0".to_owned(),
+        };
+        for _ in 0..super::MAX_CLIENT_ERRORS_PER_MINUTE {
+            let resp = app.post("/api/v2/client_errors", &report).await;
+            assert_eq!(resp.status, StatusCode::OK);
+        }
+        let resp = app.post("/api/v2/client_errors", &report).await;
+        assert_eq!(resp.status, StatusCode::TOO_MANY_REQUESTS);
+        assert!(matches!(
+            resp.json::<api::EmptyResponse>(),
+            api::Response::Err {
+                code: api::ErrorCode::TooManyRequests,
+                ..
+            }
+        ));
+    })
+}
+
+#[test]
+fn test_rate_limit() {
+    let limit = RateLimit::new(2, std::time::Duration::from_secs(60));
+    let start = std::time::Instant::now();
+    let (a, b) = ("10.0.0.1".parse().unwrap(), "10.0.0.2".parse().unwrap());
+    assert!(limit.check(a, start));
+    assert!(limit.check(a, start));
+    assert!(!limit.check(a, start));
+    // Each address has its own count.
+    assert!(limit.check(b, start));
+    // Counts start over in the next window.
+    let later = start + std::time::Duration::from_secs(60);
+    assert!(limit.check(a, later));
+}
+
+#[test]
+fn test_new_account_is_empty_until_samples_are_added() {
+    run(async {
+        let app = TestApp::logged_in().await;
+        let account = success(
+            app.get("/api/v2/account")
+                .await
+                .json::<api::AccountResponse>(),
+        );
+        assert!(account.empty);
+
+        let resp = app.post("/api/v2/account/samples", &()).await;
+        assert_eq!(resp.status, StatusCode::OK);
+        let added = success(resp.json::<api::Response<usize>>());
+        assert_eq!(added, super::samples::sample_recipes().len());
+        let account = success(
+            app.get("/api/v2/account")
+                .await
+                .json::<api::AccountResponse>(),
+        );
+        assert!(!account.empty);
+
+        // Adding them again doesn't duplicate them.
+        let resp = app.post("/api/v2/account/samples", &()).await;
+        assert_eq!(success(resp.json::<api::Response<usize>>()), 0);
+    })
+}
+
+#[test]
+fn test_defaults_keep_existing_mappings() {
+    run(async {
+        let app = TestApp::logged_in().await;
+        let resp = app
+            .post(
+                "/api/v2/category_map",
+                &vec![("onion".to_owned(), "Pantry".to_owned())],
+            )
+            .await;
+        assert_eq!(resp.status, StatusCode::OK);
+        let resp = app.post("/api/v2/account/defaults", &()).await;
+        assert_eq!(resp.status, StatusCode::OK);
+        let applied = success(resp.json::<api::DefaultsResponse>());
+        assert_eq!(
+            applied.categories,
+            super::defaults::default_categories().len() - 1
+        );
+        assert!(applied.staples);
+        let mappings = success(
+            app.get("/api/v2/category_map")
+                .await
+                .json::<api::CategoryMappingResponse>(),
+        );
+        assert!(mappings.contains(&("onion".to_owned(), "Pantry".to_owned())));
+        assert!(mappings.contains(&("ground beef".to_owned(), "Meat".to_owned())));
+
+        // Staples the user already has are left alone.
+        let resp = app.post("/api/v2/staples", &"1 lb coffee").await;
+        assert_eq!(resp.status, StatusCode::OK);
+        let resp = app.post("/api/v2/account/defaults", &()).await;
+        let applied = success(resp.json::<api::DefaultsResponse>());
+        assert_eq!(applied, api::DefaultsApplied::default());
+        let staples = success(
+            app.get("/api/v2/staples")
+                .await
+                .json::<api::Response<Option<String>>>(),
+        );
+        assert_eq!(staples.as_deref(), Some("1 lb coffee"));
+    })
+}
+
+#[test]
+fn test_defaults_parse() {
+    let categories = super::defaults::default_categories();
+    assert!(!categories.is_empty());
+    recipes::parse::as_ingredient_list(&super::defaults::default_staples())
+        .expect("The default staples don't parse");
+}
+
+#[test]
+fn test_archived_recipes_are_left_out_of_the_index() {
+    run(async {
+        let app = TestApp::logged_in().await;
+        let resp = app
+            .post(
+                "/api/v2/recipes",
+                &vec![RecipeEntry::new("pancakes", PANCAKES)],
+            )
+            .await;
+        assert_eq!(resp.status, StatusCode::OK);
+        let resp = app.post("/api/v2/recipe/pancakes/archive", &()).await;
+        assert_eq!(resp.status, StatusCode::OK);
+        let resp = app.post("/api/v2/recipe/waffles/archive", &()).await;
+        assert_eq!(resp.status, StatusCode::NOT_FOUND);
+
+        let archived = success(
+            app.get("/api/v2/recipes/archived")
+                .await
+                .json::<api::ArchivedResponse>(),
+        );
+        assert_eq!(archived, BTreeSet::from(["pancakes".to_owned()]));
+        let index = success(
+            app.get("/api/v2/recipes/index")
+                .await
+                .json::<api::RecipeIndexResponse>(),
+        );
+        assert!(index.is_empty());
+        let index = success(
+            app.get("/api/v2/recipes/index?archived=true")
+                .await
+                .json::<api::RecipeIndexResponse>(),
+        );
+        assert_eq!(index.len(), 1);
+        assert!(index[0].archived);
+        // Archived recipes can still be viewed.
+        let resp = app.get("/api/v2/recipe/pancakes").await;
+        assert_eq!(resp.status, StatusCode::OK);
+
+        let resp = app.delete("/api/v2/recipe/pancakes/archive").await;
+        assert_eq!(resp.status, StatusCode::OK);
+        let index = success(
+            app.get("/api/v2/recipes/index")
+                .await
+                .json::<api::RecipeIndexResponse>(),
+        );
+        assert_eq!(index.len(), 1);
+        assert!(!index[0].archived);
+    })
+}
+
+#[test]
+fn test_bulk_recipe_changes() {
+    run(async {
+        let app = TestApp::logged_in().await;
+        let waffles = PANCAKES.replace("Pancakes", "Waffles");
+        let resp = app
+            .post(
+                "/api/v2/recipes",
+                &vec![
+                    RecipeEntry::new("pancakes", PANCAKES),
+                    RecipeEntry::new("waffles", waffles.as_str()),
+                ],
+            )
+            .await;
+        assert_eq!(resp.status, StatusCode::OK);
+        let ids = vec![
+            "pancakes".to_owned(),
+            "waffles".to_owned(),
+            "crepes".to_owned(),
+        ];
+
+        let resp = app
+            .post(
+                "/api/v2/recipes/bulk/tags",
+                &api::BulkTags {
+                    recipe_ids: ids.clone(),
+                    add: BTreeSet::from(["Breakfast".to_owned()]),
+                    remove: BTreeSet::new(),
+                },
+            )
+            .await;
+        let result = success(resp.json::<api::BulkResponse>());
+        assert_eq!(result.updated, vec!["pancakes", "waffles"]);
+        assert_eq!(result.not_found, vec!["crepes"]);
+
+        let resp = app
+            .post(
+                "/api/v2/recipes/bulk/category",
+                &api::BulkCategory {
+                    recipe_ids: ids.clone(),
+                    category: Some("Brunch".to_owned()),
+                },
+            )
+            .await;
+        assert_eq!(resp.status, StatusCode::OK);
+        let recipes = success(
+            app.get("/api/v2/recipes")
+                .await
+                .json::<api::RecipeEntryResponse>(),
+        );
+        for entry in recipes.iter() {
+            assert_eq!(entry.category().map(|c| c.as_str()), Some("Brunch"));
+            let recipe = recipes::parse::as_recipe(entry.recipe_text()).unwrap();
+            assert!(recipe.metadata.tags.contains("breakfast"));
+        }
+
+        let resp = app
+            .post(
+                "/api/v2/recipes/bulk/archive",
+                &api::BulkArchive {
+                    recipe_ids: vec!["waffles".to_owned()],
+                    archived: true,
+                },
+            )
+            .await;
+        assert_eq!(resp.status, StatusCode::OK);
+        let archived = success(
+            app.get("/api/v2/recipes/archived")
+                .await
+                .json::<api::ArchivedResponse>(),
+        );
+        assert_eq!(archived, BTreeSet::from(["waffles".to_owned()]));
+
+        let resp = app
+            .post(
+                "/api/v2/recipes/bulk/delete",
+                &api::BulkRecipes { recipe_ids: ids },
+            )
+            .await;
+        assert_eq!(resp.status, StatusCode::OK);
+        let recipes = success(
+            app.get("/api/v2/recipes")
+                .await
+                .json::<api::RecipeEntryResponse>(),
+        );
+        assert!(recipes.is_empty());
+    })
+}
+
+#[test]
+fn test_substitutions_use_settings_over_defaults() {
+    run(async {
+        let app = TestApp::logged_in().await;
+        let defaults = super::defaults::default_substitutions();
+        assert!(defaults.contains_key("buttermilk"));
+        assert!(defaults.contains_key("egg"));
+        let substitutions = success(
+            app.get("/api/v2/substitutions")
+                .await
+                .json::<api::SubstitutionsResponse>(),
+        );
+        assert_eq!(substitutions, defaults);
+
+        let settings = BTreeMap::from([
+            (
+                format!("{}buttermilk", api::SUBSTITUTES_SETTING_PREFIX),
+                "kefir | sour milk".to_owned(),
+            ),
+            (
+                format!("{}egg", api::SUBSTITUTES_SETTING_PREFIX),
+                String::new(),
+            ),
+        ]);
+        let resp = app.post("/api/v2/settings", &settings).await;
+        assert_eq!(resp.status, StatusCode::OK);
+        let substitutions = success(
+            app.get("/api/v2/substitutions")
+                .await
+                .json::<api::SubstitutionsResponse>(),
+        );
+        assert_eq!(
+            substitutions.get("buttermilk"),
+            Some(&vec!["kefir".to_owned(), "sour milk".to_owned()])
+        );
+        assert!(!substitutions.contains_key("egg"));
+        assert_eq!(substitutions.get("honey"), defaults.get("honey"));
+    })
+}
+
+#[test]
+fn test_data_version() {
+    run(async {
+        let app = TestApp::logged_in().await;
+        let resp = app.get("/api/v2/version").await;
+        assert_eq!(resp.status, StatusCode::OK);
+        assert_eq!(resp.headers[header::ETAG], r#""0""#);
+        assert_eq!(
+            resp.json::<api::DataVersionResponse>().as_success(),
+            Some(0)
+        );
+
+        let resp = app
+            .post(
+                "/api/v2/recipes",
+                &vec![RecipeEntry::new("pancakes", PANCAKES)],
+            )
+            .await;
+        assert_eq!(resp.status, StatusCode::OK);
+        assert_eq!(resp.headers[api::DATA_VERSION_HEADER], "1");
+
+        // Reads and failed writes leave the version alone.
+        let resp = app.get("/api/v2/recipes").await;
+        assert!(resp.headers.get(api::DATA_VERSION_HEADER).is_none());
+        let resp = app.post("/api/v2/plan/at/not-a-date", &"nope").await;
+        assert!(!resp.status.is_success());
+        assert!(resp.headers.get(api::DATA_VERSION_HEADER).is_none());
+
+        let resp = app
+            .get_with_header("/api/v2/version", header::IF_NONE_MATCH, r#""0""#)
+            .await;
+        assert_eq!(resp.status, StatusCode::OK);
+        assert_eq!(
+            resp.json::<api::DataVersionResponse>().as_success(),
+            Some(1)
+        );
+        let resp = app
+            .get_with_header("/api/v2/version", header::IF_NONE_MATCH, r#""1""#)
+            .await;
+        assert_eq!(resp.status, StatusCode::NOT_MODIFIED);
+        assert!(resp.body.is_empty());
+
+        let app = TestApp::new().await;
+        let resp = app.get("/api/v2/version").await;
+        assert_eq!(resp.status, StatusCode::UNAUTHORIZED);
+    });
+}
+
+/// A session for `user` to pass to handlers called directly.
+fn session(user: &str) -> storage::UserIdFromSession {
+    storage::UserIdFromSession::FoundUserId(storage::UserId(user.to_owned()))
+}
+
+fn memory_store() -> Extension<Arc<dyn APIStore>> {
+    let store: Arc<dyn APIStore> = Arc::new(MemoryStore::new());
+    Extension(store)
+}
+
+/// The recipe directory store. Handlers only use it when nobody is logged in.
+fn file_store() -> Extension<Arc<storage::file_store::AsyncFileStore>> {
+    Extension(Arc::new(storage::file_store::AsyncFileStore::new(
+        std::env::temp_dir(),
+    )))
+}
+
+#[test]
+fn test_recipe_handlers_with_memory_store() {
+    run(async {
+        let app_store = memory_store();
+        let saved = success(
+            super::api_save_recipes(
+                file_store(),
+                app_store.clone(),
+                session(USER),
+                Json(vec![RecipeEntry::new("Pancakes", PANCAKES)]),
+            )
+            .await,
+        );
+        assert_eq!(saved.len(), 1);
+        assert_eq!(saved[0].recipe_id, "pancakes");
+        assert_eq!(saved[0].renamed_from.as_deref(), Some("Pancakes"));
+
+        // The old id still finds the renamed recipe.
+        let entry = success(
+            super::api_recipe_entry(
+                file_store(),
+                app_store.clone(),
+                session(USER),
+                Path("Pancakes".to_owned()),
+            )
+            .await,
+        )
+        .expect("Renamed recipe was not found");
+        assert_eq!(entry.recipe_id(), "pancakes");
+        assert_eq!(entry.revision(), Some(0));
+
+        let stale = RecipeEntry("pancakes".to_owned(), PANCAKES.to_owned(), None, Some(5));
+        let resp = super::api_save_recipes(
+            file_store(),
+            app_store.clone(),
+            session(USER),
+            Json(vec![stale]),
+        )
+        .await;
+        assert!(matches!(resp, api::Response::Conflict(_)));
+
+        let recipes = success(
+            super::api_recipes(file_store(), app_store.clone(), session("someone-else")).await,
+        );
+        assert!(recipes.is_empty());
+    })
+}
+
+#[test]
+fn test_recipe_renames_with_memory_store() {
+    run(async {
+        let app_store = memory_store();
+        let date = NaiveDate::from_ymd_opt(2023, 10, 16).unwrap();
+        app_store
+            .save_meal_plan(
+                USER,
+                &vec![("Pizza Dough".to_owned(), 1), ("pizza_dough".to_owned(), 2)],
+                date,
+            )
+            .await
+            .expect("Failed to save the plan");
+        let saved = app_store
+            .store_recipes_for_user(USER, &vec![RecipeEntry::new("Pizza Dough", PANCAKES)])
+            .await
+            .expect("Failed to store recipes");
+        assert_eq!(saved, vec![("pizza_dough".to_owned(), 0)]);
+        let plan = app_store
+            .fetch_meal_plan_for_date(USER, date)
+            .await
+            .expect("Failed to fetch the plan");
+        assert_eq!(plan, Some(vec![("pizza_dough".to_owned(), 3)]));
+
+        // Saving under the old id again updates the renamed recipe.
+        let old = RecipeEntry("Pizza Dough".to_owned(), PANCAKES.to_owned(), None, Some(0));
+        let saved = app_store
+            .store_recipes_for_user(USER, &vec![old])
+            .await
+            .expect("Failed to store recipes");
+        assert_eq!(saved, vec![("pizza_dough".to_owned(), 1)]);
+
+        let result = app_store
+            .store_recipes_for_user(USER, &vec![RecipeEntry::new("PIZZA dough", PANCAKES)])
+            .await;
+        match result {
+            Err(storage::Error::Conflict(current)) => {
+                assert_eq!(current.len(), 1);
+                assert_eq!(current[0].recipe_id(), "pizza_dough");
+                assert_eq!(current[0].revision(), Some(1));
+            }
+            other => panic!("Expected a conflict but got {:?}", other),
+        }
+        let result = app_store
+            .store_recipes_for_user(
+                USER,
+                &vec![
+                    RecipeEntry::new("Waffles", PANCAKES),
+                    RecipeEntry::new("waffles", PANCAKES),
+                ],
+            )
+            .await;
+        assert!(matches!(result, Err(storage::Error::EntryFailed(_, _))));
+        let ids = app_store
+            .fetch_recipe_ids_for_user(USER)
+            .await
+            .expect("Failed to fetch recipe ids");
+        assert_eq!(ids, BTreeSet::from(["pizza_dough".to_owned()]));
+    })
+}
+
+#[test]
+fn test_only_the_editor_writes_to_the_recipe_dir() {
+    run(async {
+        let dir = std::env::temp_dir().join(format!("kitchen-test-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(dir.join("recipes")).expect("Unable to create the recipe dir");
+        let store = Extension(Arc::new(
+            storage::file_store::AsyncFileStore::new(dir.clone())
+                .with_editor(Some("editor".to_owned())),
+        ));
+        let app_store = memory_store();
+        let recipes = vec![RecipeEntry::new("pancakes", PANCAKES)];
+
+        let resp = super::api_save_recipes(
+            store.clone(),
+            app_store.clone(),
+            storage::UserIdFromSession::NoUserId,
+            Json(recipes.clone()),
+        )
+        .await;
+        assert!(matches!(resp, api::Response::Unauthorized));
+        assert!(!dir.join("recipes").join("pancakes").exists());
+
+        success(
+            super::api_save_recipes(
+                store.clone(),
+                app_store.clone(),
+                session(USER),
+                Json(recipes.clone()),
+            )
+            .await,
+        );
+        assert!(!dir.join("recipes").join("pancakes").exists());
+
+        success(
+            super::api_save_recipes(
+                store.clone(),
+                app_store.clone(),
+                session("editor"),
+                Json(recipes),
+            )
+            .await,
+        );
+        assert!(dir.join("recipes").join("pancakes").exists());
+        let listed =
+            success(super::api_recipes(store.clone(), app_store.clone(), session("editor")).await);
+        assert_eq!(listed.len(), 1);
+
+        // The editor reads recipes from the recipe dir too.
+        let entry = success(
+            super::api_recipe_entry(
+                store.clone(),
+                app_store.clone(),
+                session("editor"),
+                Path("pancakes".to_owned()),
+            )
+            .await,
+        );
+        assert_eq!(
+            entry.map(|e| e.recipe_text().to_owned()),
+            Some(PANCAKES.to_owned())
+        );
+        let parsed = super::api_recipe_parsed(
+            store.clone(),
+            app_store.clone(),
+            session("editor"),
+            Path("pancakes".to_owned()),
+        )
+        .await;
+        assert!(matches!(parsed, api::Response::Success(_)));
+
+        // Ids are normalized the same way they are for everyone else.
+        std::fs::write(dir.join("recipes").join("Waffle Mix"), PANCAKES)
+            .expect("Unable to write a recipe file");
+        let saved = success(
+            super::api_save_recipes(
+                store.clone(),
+                app_store.clone(),
+                session("editor"),
+                Json(vec![RecipeEntry::new("Waffle Mix", PANCAKES)]),
+            )
+            .await,
+        );
+        assert_eq!(saved[0].recipe_id, "waffle_mix");
+        assert_eq!(saved[0].renamed_from.as_deref(), Some("Waffle Mix"));
+        assert!(dir.join("recipes").join("waffle_mix").exists());
+        assert!(!dir.join("recipes").join("Waffle Mix").exists());
+
+        let resp = super::api_recipe_delete(
+            store.clone(),
+            app_store.clone(),
+            storage::UserIdFromSession::NoUserId,
+            Path("pancakes".to_owned()),
+        )
+        .await;
+        assert!(matches!(resp, api::Response::Unauthorized));
+        assert!(dir.join("recipes").join("pancakes").exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    })
+}
+
+#[test]
+fn test_recipe_dir_changes_bump_the_editors_data_version() {
+    run(async {
+        let dir = std::env::temp_dir().join(format!("kitchen-test-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(dir.join("recipes")).expect("Unable to create the recipe dir");
+        std::fs::write(dir.join("recipes").join("pancakes"), PANCAKES)
+            .expect("Unable to write a recipe file");
+        let store = Arc::new(
+            storage::file_store::AsyncFileStore::new(dir.clone())
+                .with_editor(Some("editor".to_owned())),
+        );
+        let Extension(app_store) = memory_store();
+        let interval = std::time::Duration::from_millis(20);
+        super::watch_file_store(store.clone(), app_store.clone(), interval);
+        // Give the watcher time to read what is already there.
+        async_std::task::sleep(interval * 10).await;
+        assert_eq!(app_store.fetch_data_version("editor").await.unwrap(), 0);
+
+        std::fs::write(dir.join("recipes").join("waffles"), PANCAKES)
+            .expect("Unable to write a recipe file");
+        let mut version = 0;
+        for _ in 0..250 {
+            version = app_store.fetch_data_version("editor").await.unwrap();
+            if version > 0 {
+                break;
+            }
+            async_std::task::sleep(interval).await;
+        }
+        assert_eq!(version, 1);
+
+        // Writes made through the store are already known to the client.
+        store
+            .store_recipe(&RecipeEntry::new("crepes", PANCAKES))
+            .await
+            .expect("Failed to store a recipe");
+        async_std::task::sleep(interval * 10).await;
+        assert_eq!(app_store.fetch_data_version("editor").await.unwrap(), 1);
+        let _ = std::fs::remove_dir_all(&dir);
+    })
+}
+
+#[test]
+fn test_plan_and_inventory_handlers_with_memory_store() {
+    run(async {
+        let app_store = memory_store();
+        let date = NaiveDate::from_ymd_opt(2023, 10, 16).unwrap();
+        app_store
+            .store_recipes_for_user(USER, &vec![RecipeEntry::new("pancakes", PANCAKES)])
+            .await
+            .expect("Failed to store recipes");
+
+        let resp = super::api_save_plan_for_date(
+            app_store.clone(),
+            session(USER),
+            Path(date),
+            Json(vec![("pancakes".to_owned(), 1)]),
+        )
+        .await;
+        assert!(matches!(resp, api::Response::Success(_)));
+        let plan = super::api_update_plan_for_date(
+            app_store.clone(),
+            session(USER),
+            Path(date),
+            Json(vec![api::PlanChange::AddCount("pancakes".to_owned(), 2)]),
+        )
+        .await;
+        assert_eq!(plan.as_success(), Some(vec![("pancakes".to_owned(), 3)]));
+        let resp = super::api_update_plan_for_date(
+            app_store.clone(),
+            session(USER),
+            Path(date),
+            Json(vec![api::PlanChange::SetCount("waffles".to_owned(), 1)]),
+        )
+        .await;
+        assert!(matches!(resp, api::Response::Invalid(_)));
+
+        let flour = IngredientKey::new("flour".to_owned(), None, "Volume".to_owned());
+        let inventory = success(
+            super::api_update_inventory_for_date(
+                app_store.clone(),
+                session(USER),
+                Path(date),
+                Json(vec![
+                    api::InventoryOp::Check(flour.clone()),
+                    api::InventoryOp::SetExtras(vec![("1".to_owned(), "syrup".to_owned())]),
+                ]),
+            )
+            .await,
+        );
+        assert_eq!(inventory.filtered_ingredients, vec![flour]);
+        assert_eq!(inventory.extra_items.len(), 1);
+
+        let resp =
+            super::api_plan_for_date(app_store, storage::UserIdFromSession::NoUserId, Path(date))
+                .await;
+        assert!(matches!(resp, api::Response::Unauthorized));
+    })
+}
+
+#[test]
+fn test_writes_report_a_busy_database() {
+    run(async {
+        let dir = std::env::temp_dir().join(format!("kitchen-test-{}", uuid::Uuid::new_v4()));
+        let locker = storage::SqliteStore::new(&dir)
+            .await
+            .expect("Unable to create the store");
+        locker
+            .run_migrations()
+            .await
+            .expect("Failed to run database migrations");
+        let config = storage::DbConfig {
+            busy_timeout: std::time::Duration::from_millis(10),
+            ..storage::DbConfig::default()
+        };
+        let app_store: Arc<dyn APIStore> = Arc::new(
+            storage::SqliteStore::open(&dir, &config)
+                .await
+                .expect("Unable to open the store"),
+        );
+        let date = NaiveDate::from_ymd_opt(2023, 10, 16).unwrap();
+        app_store
+            .store_recipes_for_user(USER, &vec![RecipeEntry::new("pancakes", PANCAKES)])
+            .await
+            .expect("Failed to store recipes");
+
+        let lock = locker
+            .lock_for_writes()
+            .await
+            .expect("Failed to lock the database");
+        let resp = super::api_save_staples(
+            Extension(app_store.clone()),
+            session(USER),
+            Json("1 cup flour".to_owned()),
+        )
+        .await;
+        assert!(matches!(
+            resp,
+            api::Response::Err {
+                status: 503,
+                code: api::ErrorCode::Busy,
+                ..
+            }
+        ));
+        let resp = super::api_update_plan_for_date(
+            Extension(app_store.clone()),
+            session(USER),
+            Path(date),
+            Json(vec![api::PlanChange::AddCount("pancakes".to_owned(), 1)]),
+        )
+        .await;
+        assert!(matches!(
+            resp,
+            api::Response::Err {
+                code: api::ErrorCode::Busy,
+                ..
+            }
+        ));
+
+        drop(lock);
+        let resp = super::api_save_staples(
+            Extension(app_store),
+            session(USER),
+            Json("1 cup flour".to_owned()),
+        )
+        .await;
+        assert!(matches!(resp, api::Response::Success(_)));
+        std::fs::remove_dir_all(dir).ok();
+    })
+}
+
+#[test]
+fn test_lowered_recipe_limit_only_stops_new_recipes() {
+    run(async {
+        let dir = std::env::temp_dir().join(format!("kitchen-test-{}", uuid::Uuid::new_v4()));
+        let unlimited = storage::SqliteStore::new(&dir)
+            .await
+            .expect("Unable to create the store");
+        unlimited
+            .run_migrations()
+            .await
+            .expect("Failed to run database migrations");
+        unlimited
+            .store_recipes_for_user(
+                USER,
+                &vec![
+                    RecipeEntry::new("pancakes", PANCAKES),
+                    RecipeEntry::new("waffles", PANCAKES),
+                ],
+            )
+            .await
+            .expect("Failed to store recipes");
+        // The operator lowered the limit below what the user already has.
+        let limited = storage::SqliteStore::new(&dir)
+            .await
+            .expect("Unable to open the store")
+            .with_limits(storage::Limits {
+                max_recipes: Some(1),
+                ..storage::Limits::default()
+            });
+
+        let edited = PANCAKES.replace("2 egg", "3 egg");
+        limited
+            .store_recipes_for_user(USER, &vec![RecipeEntry::new("pancakes", &edited)])
+            .await
+            .expect("Editing an existing recipe should work");
+        let err = limited
+            .store_recipes_for_user(
+                USER,
+                &vec![
+                    RecipeEntry::new("pancakes", PANCAKES),
+                    RecipeEntry::new("crepes", PANCAKES),
+                ],
+            )
+            .await
+            .expect_err("A new recipe should be over the limit");
+        assert!(matches!(err, storage::Error::LimitExceeded(_)), "{:?}", err);
+        let entry = limited
+            .get_recipe_entry_for_user(USER, "pancakes")
+            .await
+            .expect("Failed to read the recipe")
+            .expect("The recipe is missing");
+        assert_eq!(entry.recipe_text(), edited);
+        assert_eq!(
+            limited.fetch_recipe_ids_for_user(USER).await.unwrap(),
+            BTreeSet::from(["pancakes".to_owned(), "waffles".to_owned()])
+        );
+        std::fs::remove_dir_all(dir).ok();
+    })
+}
+
+#[test]
+fn test_login_handler_with_memory_store() {
+    run(async {
+        let store = Arc::new(MemoryStore::new());
+        store
+            .store_user_creds(storage::UserCreds {
+                id: storage::UserId(USER.to_owned()),
+                pass: secrecy::Secret::from(PASSWORD.to_owned()),
+            })
+            .await
+            .expect("Failed to store user creds");
+        let login = |password: &str| {
+            super::auth::handler::<MemoryStore>(
+                axum_auth::AuthBasic((USER.to_owned(), Some(password.to_owned()))),
+                Host("localhost".to_owned()),
+                Query(super::auth::LoginParams::default()),
+                Extension(store.clone()),
+            )
+        };
+
+        let (status, _, _) = login("wrong").await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        let (status, headers, _) = login(PASSWORD).await;
+        assert_eq!(status, StatusCode::OK);
+        let cookie = headers
+            .get_all(header::SET_COOKIE)
+            .iter()
+            .filter_map(|value| cookie::Cookie::parse(value.to_str().ok()?).ok())
+            .find(|cookie| cookie.name() == storage::AXUM_SESSION_COOKIE_NAME)
+            .expect("No session cookie was set");
+        let session = store
+            .load_session(cookie.value().to_owned())
+            .await
+            .expect("Failed to load the session")
+            .expect("The session was not stored");
+        assert_eq!(
+            session.get::<storage::UserId>("user_id").map(|id| id.0),
+            Some(USER.to_owned())
+        );
+    })
+}
+
+#[test]
+fn test_command_ocr() {
+    run(async {
+        let ocr = CommandOcr::new("cat", vec![]);
+        let text = ocr.recognize(b"1 cup flour".to_vec()).await;
+        assert_eq!(text, Ok("1 cup flour".to_owned()));
+
+        let ocr = CommandOcr::new("sh", vec!["-c".to_owned(), "exit 3".to_owned()]);
+        assert!(ocr.recognize(Vec::new()).await.is_err());
+    })
+}
+
+#[test]
+fn test_command_ocr_is_killed_after_the_timeout() {
+    run(async {
+        let ocr = CommandOcr::new("sh", vec!["-c".to_owned(), "sleep 10".to_owned()])
+            .with_timeout(std::time::Duration::from_millis(200));
+        let start = std::time::Instant::now();
+        let result = ocr.recognize(Vec::new()).await;
+        assert!(result.is_err(), "{:?}", result);
+        assert!(start.elapsed() < std::time::Duration::from_secs(5));
+    })
+}
+
+#[test]
+fn test_command_ocr_limits_concurrent_commands() {
+    run(async {
+        let ocr = CommandOcr::new("sh", vec!["-c".to_owned(), "sleep 0.2".to_owned()])
+            .with_concurrency(1);
+        let start = std::time::Instant::now();
+        let (first, second) = futures::join!(ocr.recognize(Vec::new()), ocr.recognize(Vec::new()));
+        assert!(first.is_ok() && second.is_ok());
+        // The second command waited for the first to finish.
+        assert!(start.elapsed() >= std::time::Duration::from_millis(400));
+    })
+}
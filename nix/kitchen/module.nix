@@ -10,6 +10,12 @@ with lib;
             default = "0.0.0.0:9003";
             defaultText = "0.0.0.0:9003";
         };
+
+        services.kitchen.socketActivation = mkOption {
+            description = "Let systemd own the listen socket so connections wait while kitchen restarts";
+            type = types.bool;
+            default = false;
+        };
     };
     
     config = mkIf config.services.kitchen.enable {
@@ -18,6 +24,10 @@ with lib;
                 recipes = (import ../packages/recipes/package.nix) { inherit pkgs; };
             })
         ];
+        systemd.sockets.kitchen = mkIf config.services.kitchen.socketActivation {
+            wantedBy = [ "sockets.target" ];
+            socketConfig.ListenStream = config.services.kitchen.listenSocket;
+        };
         systemd.services.kitchen = {
             wantedBy = [ "multi-user.target" "default.target" ];
             wants = [ "network.target" ];
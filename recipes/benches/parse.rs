@@ -0,0 +1,128 @@
+// Copyright 2023 Jeremy Wall (Jeremy@marzhilsltudios.com)
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//! Benchmarks for the recipe parser. The ui runs it on every edit of a recipe
+//! so regressions here are felt directly.
+//!
+//! Run with `cargo bench -p recipes` and compare against a saved baseline with
+//! `cargo bench -p recipes -- --baseline main`.
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+use recipes::parse;
+
+const MEATLOAF: &'static str = include_str!("../../examples/recipes.txt/meatloaf.txt");
+const DRESSING: &'static str = include_str!("../../examples/recipes.txt/cornbread_dressing.txt");
+
+/// A recipe with `steps` steps each with a handful of ingredients.
+fn large_recipe(steps: usize) -> String {
+    let mut text = String::from("title: A very large recipe\n\nIt just keeps going.\n\n");
+    for i in 0..steps {
+        text.push_str(&format!("step: prep_time: {} min\n\n", i + 1));
+        text.push_str("1 1/2 cups flour (sifted)\n");
+        text.push_str("2 tbsp butter\n");
+        text.push_str("3 large eggs\n");
+        text.push_str("250 ml milk\n");
+        text.push_str("1 pinch salt\n");
+        text.push_str(&format!(
+            "\nMix everything together for step {} and bake at 350 until golden.\n\n",
+            i
+        ));
+    }
+    text
+}
+
+fn bench_examples(c: &mut Criterion) {
+    let mut group = c.benchmark_group("examples");
+    for (name, text) in [("meatloaf", MEATLOAF), ("cornbread_dressing", DRESSING)] {
+        group.throughput(Throughput::Bytes(text.len() as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(name), text, |b, text| {
+            b.iter(|| parse::as_recipe(black_box(text)))
+        });
+    }
+    group.finish();
+}
+
+fn bench_large_recipes(c: &mut Criterion) {
+    let mut group = c.benchmark_group("large_recipe");
+    for steps in [10, 100, 500] {
+        let text = large_recipe(steps);
+        group.throughput(Throughput::Bytes(text.len() as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(steps), &text, |b, text| {
+            b.iter(|| parse::as_recipe(black_box(text)))
+        });
+    }
+    group.finish();
+}
+
+fn bench_pathological(c: &mut Criterion) {
+    let mut group = c.benchmark_group("pathological");
+    let inputs = [
+        // A very long title line.
+        (
+            "long_title",
+            format!(
+                "title: {}\n\nstep:\n\n1 egg\n\nBeat it.\n",
+                "a".repeat(10_000)
+            ),
+        ),
+        // Lots of blank lines between the parts of the recipe.
+        (
+            "blank_lines",
+            format!(
+                "title: blank\n{}step:\n\n1 egg\n\nBeat it.\n",
+                "\n".repeat(10_000)
+            ),
+        ),
+        // An ingredient list that never ends in any instructions.
+        (
+            "unterminated_ingredients",
+            format!(
+                "title: unterminated\n\nstep:\n\n{}",
+                "1 cup flour\n".repeat(2_000)
+            ),
+        ),
+        // A very long ingredient modifier.
+        (
+            "long_modifier",
+            format!(
+                "title: modifier\n\nstep:\n\n1 cup flour ({})\n\nMix.\n",
+                "sifted ".repeat(2_000)
+            ),
+        ),
+        // Text that is almost but not quite a recipe so the parser has to give up.
+        ("garbage", "step: title: step: (((( 1/ /1 ".repeat(1_000)),
+    ];
+    for (name, text) in inputs.iter() {
+        group.throughput(Throughput::Bytes(text.len() as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(name), text, |b, text| {
+            b.iter(|| parse::as_recipe(black_box(text)))
+        });
+    }
+    group.finish();
+}
+
+fn bench_ingredients(c: &mut Criterion) {
+    let list = "1 1/2 cups flour (sifted)\n2 tbsp butter\n3 large eggs\n250 ml milk\n".repeat(250);
+    c.bench_function("ingredient_list", |b| {
+        b.iter(|| parse::as_ingredient_list(black_box(&list)))
+    });
+}
+
+criterion_group!(
+    benches,
+    bench_examples,
+    bench_large_recipes,
+    bench_pathological,
+    bench_ingredients
+);
+criterion_main!(benches);
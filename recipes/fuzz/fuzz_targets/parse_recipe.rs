@@ -0,0 +1,24 @@
+// Copyright 2023 Jeremy Wall (Jeremy@marzhilsltudios.com)
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+use recipes::parse;
+
+// The parser should return an error for bad input and never panic or hang.
+fuzz_target!(|text: &str| {
+    let _ = parse::as_recipe(text);
+    let _ = parse::as_ingredient_list(text);
+    let _ = parse::as_categories(text);
+});
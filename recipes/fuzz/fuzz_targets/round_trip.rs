@@ -0,0 +1,26 @@
+// Copyright 2023 Jeremy Wall (Jeremy@marzhilsltudios.com)
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+use recipes::{parse, serialize};
+
+// Any recipe we can parse should serialize to text that parses back into the
+// same recipe.
+fuzz_target!(|text: &str| {
+    if let Ok(recipe) = parse::as_recipe(text) {
+        let serialized = serialize::as_text(&recipe);
+        assert_eq!(parse::as_recipe(&serialized), Ok(recipe), "{}", serialized);
+    }
+});
@@ -0,0 +1,78 @@
+// Copyright 2023 Jeremy Wall
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+/*!
+Heuristics for suggesting categories for ingredients that have not been
+mapped to a category yet.
+*/
+use std::collections::BTreeMap;
+
+use inflector::Inflector;
+
+pub(crate) fn stem_words(name: &str) -> Vec<String> {
+    name.split_whitespace()
+        .map(|w| w.to_lowercase().to_singular())
+        .collect()
+}
+
+pub(crate) fn contains_words(haystack: &[String], needle: &[String]) -> bool {
+    if needle.is_empty() || needle.len() > haystack.len() {
+        return false;
+    }
+    haystack.windows(needle.len()).any(|w| w == needle)
+}
+
+/// Suggest a category for an ingredient name based on existing mappings.
+///
+/// Names are compared word by word after lowercasing and singularizing. A
+/// mapped ingredient that appears inside the name wins over one that only
+/// shares the final word. Longer matches win over shorter ones.
+pub fn suggest_category(name: &str, mappings: &BTreeMap<String, String>) -> Option<String> {
+    let words = stem_words(name);
+    if words.is_empty() {
+        return None;
+    }
+    let mut best: Option<(usize, &String)> = None;
+    for (mapped, category) in mappings.iter() {
+        let mapped_words = stem_words(mapped);
+        let score = if mapped_words == words {
+            usize::MAX
+        } else if contains_words(&words, &mapped_words) {
+            mapped_words.len() * 2
+        } else if mapped_words.last() == words.last() {
+            1
+        } else {
+            0
+        };
+        if score > 0 && best.map_or(true, |(best_score, _)| score > best_score) {
+            best = Some((score, category));
+        }
+    }
+    best.map(|(_, category)| category.clone())
+}
+
+/// Find the names that have no category mapping and pair each of them with
+/// a suggested category if one could be found.
+pub fn suggest_categories<'a, Iter>(
+    names: Iter,
+    mappings: &BTreeMap<String, String>,
+) -> Vec<(String, Option<String>)>
+where
+    Iter: IntoIterator<Item = &'a String>,
+{
+    names
+        .into_iter()
+        .filter(|n| !mappings.contains_key(*n))
+        .map(|n| (n.clone(), suggest_category(n, mappings)))
+        .collect()
+}
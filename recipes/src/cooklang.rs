@@ -0,0 +1,496 @@
+// Copyright 2023 Jeremy Wall (Jeremy@marzhilsltudios.com)
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//! Conversion to and from [Cooklang](https://cooklang.org) recipes.
+//!
+//! Cooklang marks ingredients up inline in the instructions while our steps
+//! have a separate ingredient list so the conversion isn't exact. Exported
+//! steps list their ingredients on the first line of the step and images and
+//! temperatures are kept in comments so they survive an import.
+use std::fmt::Write;
+use std::time::Duration;
+
+use num_rational::Ratio;
+
+use crate::{
+    parse,
+    unit::{Measure, Quantity, Temperature, VolumeMeasure::*, WeightMeasure::*},
+    Ingredient, Recipe, RecipeMetadata, Step, StepTimer,
+};
+
+fn quantity_text(qty: &Quantity) -> String {
+    // Cooklang doesn't understand mixed numbers like 1 1/2.
+    match qty.normalize() {
+        Quantity::Whole(n) => n.to_string(),
+        Quantity::Frac(r) => format!("{}/{}", r.numer(), r.denom()),
+    }
+}
+
+fn amount_text(amt: &Measure) -> String {
+    let (qty, unit) = match amt {
+        Measure::Count(qty) => return quantity_text(qty),
+        Measure::Volume(Tsp(qty)) => (qty, "tsp"),
+        Measure::Volume(Tbsp(qty)) => (qty, "tbsp"),
+        Measure::Volume(Cup(qty)) => (qty, "cup"),
+        Measure::Volume(Pint(qty)) => (qty, "pint"),
+        Measure::Volume(Qrt(qty)) => (qty, "quart"),
+        Measure::Volume(Gal(qty)) => (qty, "gal"),
+        Measure::Volume(Floz(qty)) => (qty, "floz"),
+        Measure::Volume(ML(qty)) => (qty, "ml"),
+        Measure::Volume(Ltr(qty)) => (qty, "l"),
+        Measure::Weight(Gram(qty)) => (qty, "g"),
+        Measure::Weight(Kilogram(qty)) => (qty, "kg"),
+        Measure::Weight(Pound(qty)) => (qty, "lb"),
+        Measure::Weight(Oz(qty)) => (qty, "oz"),
+    };
+    format!("{}%{}", quantity_text(qty), unit)
+}
+
+fn duration_parts(d: &Duration) -> (u64, &'static str) {
+    let secs = d.as_secs();
+    if secs > 0 && secs % (60 * 60) == 0 {
+        (secs / (60 * 60), "hours")
+    } else if secs > 0 && secs % 60 == 0 {
+        (secs / 60, "minutes")
+    } else {
+        (secs, "seconds")
+    }
+}
+
+fn ingredient_marker(i: &Ingredient) -> String {
+    let mut marker = format!("@{}{{{}}}", i.name, amount_text(&i.amt));
+    if let Some(form) = &i.form {
+        write!(marker, "({})", form).unwrap();
+    }
+    marker
+}
+
+fn timer_marker(t: &StepTimer) -> String {
+    let (n, unit) = duration_parts(&t.duration);
+    format!(
+        "~{}{{{}%{}}}",
+        t.label.as_deref().unwrap_or_default(),
+        n,
+        unit
+    )
+}
+
+/// Render a recipe as Cooklang.
+pub fn as_text(recipe: &Recipe) -> String {
+    let mut out = String::new();
+    writeln!(out, ">> title: {}", recipe.title).unwrap();
+    if let Some(desc) = &recipe.desc {
+        let desc = desc.split_whitespace().collect::<Vec<&str>>().join(" ");
+        writeln!(out, ">> description: {}", desc).unwrap();
+    }
+    if let Some(serves) = recipe.metadata.serves {
+        writeln!(out, ">> servings: {}", serves).unwrap();
+    }
+    let prep_time = recipe.prep_time();
+    if !prep_time.is_zero() {
+        let (n, unit) = duration_parts(&prep_time);
+        writeln!(out, ">> prep time: {} {}", n, unit).unwrap();
+    }
+    if !recipe.metadata.tags.is_empty() {
+        let tags = recipe
+            .metadata
+            .tags
+            .iter()
+            .map(|t| t.as_str())
+            .collect::<Vec<&str>>();
+        writeln!(out, ">> tags: {}", tags.join(", ")).unwrap();
+    }
+    if let Some(meal) = &recipe.metadata.meal {
+        writeln!(out, ">> course: {}", meal.as_str()).unwrap();
+    }
+    if let Some(source) = &recipe.metadata.source {
+        writeln!(out, ">> source: {}", source).unwrap();
+    }
+    for step in recipe.steps.iter() {
+        out.push('\n');
+        for image in step.images.iter() {
+            writeln!(out, "-- image: {}", image).unwrap();
+        }
+        for temp in step.temperatures.iter() {
+            writeln!(out, "-- temp: {}", temp).unwrap();
+        }
+        let ingredients = step
+            .ingredients
+            .iter()
+            .map(ingredient_marker)
+            .collect::<Vec<String>>();
+        writeln!(out, "{}", ingredients.join(", ")).unwrap();
+        if !step.timers.is_empty() {
+            let timers = step
+                .timers
+                .iter()
+                .map(timer_marker)
+                .collect::<Vec<String>>();
+            writeln!(out, "{}", timers.join(", ")).unwrap();
+        }
+        writeln!(out, "{}", step.instructions.trim()).unwrap();
+    }
+    out
+}
+
+fn decimal(s: &str) -> Option<Ratio<u32>> {
+    let (whole, frac) = s.split_once('.')?;
+    let whole: u32 = if whole.is_empty() {
+        0
+    } else {
+        whole.parse().ok()?
+    };
+    let frac = frac.chars().take(6).collect::<String>();
+    if !frac.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let denom = 10u32.pow(frac.len() as u32);
+    let numer: u32 = if frac.is_empty() {
+        0
+    } else {
+        frac.parse().ok()?
+    };
+    Some(Ratio::new(
+        whole.checked_mul(denom)?.checked_add(numer)?,
+        denom,
+    ))
+}
+
+fn fraction(s: &str) -> Option<Ratio<u32>> {
+    match s.split_once('/') {
+        Some((numer, denom)) => {
+            let denom: u32 = denom.trim().parse().ok()?;
+            if denom == 0 {
+                return None;
+            }
+            Some(Ratio::new(numer.trim().parse().ok()?, denom))
+        }
+        None if s.contains('.') => decimal(s),
+        None => Some(Ratio::from_integer(s.parse().ok()?)),
+    }
+}
+
+/// Parse a Cooklang quantity. These can be whole numbers, fractions, mixed
+/// numbers, or decimals.
+fn quantity(s: &str) -> Result<Ratio<u32>, String> {
+    let s = s.trim();
+    let parsed = match s.split_once(' ') {
+        Some((whole, frac)) => fraction(frac.trim())
+            .zip(whole.parse::<u32>().ok())
+            .and_then(|(frac, whole)| {
+                let numer = whole
+                    .checked_mul(*frac.denom())?
+                    .checked_add(*frac.numer())?;
+                Some(Ratio::new(numer, *frac.denom()))
+            }),
+        None => fraction(s),
+    };
+    parsed.ok_or_else(|| format!("Invalid quantity: {}", s))
+}
+
+fn unit_seconds(unit: &str) -> Option<u32> {
+    match unit.trim().to_lowercase().as_str() {
+        "s" | "sec" | "secs" | "second" | "seconds" => Some(1),
+        "m" | "min" | "mins" | "minute" | "minutes" => Some(60),
+        "h" | "hr" | "hrs" | "hour" | "hours" => Some(60 * 60),
+        _ => None,
+    }
+}
+
+fn duration(qty: &str, unit: &str) -> Result<Duration, String> {
+    let secs = unit_seconds(unit).ok_or_else(|| format!("Invalid time unit: {}", unit))?;
+    let qty = quantity(qty)?;
+    let numer = qty
+        .numer()
+        .checked_mul(secs)
+        .ok_or_else(|| format!("Duration is too long: {} {}", qty, unit.trim()))?;
+    Ok(Duration::from_secs((numer / qty.denom()) as u64))
+}
+
+/// Parse durations like "1 hour 30 minutes" from the metadata.
+fn duration_text(text: &str) -> Option<Duration> {
+    let parts = text.split_whitespace().collect::<Vec<&str>>();
+    let mut total = Duration::ZERO;
+    for pair in parts.chunks(2) {
+        match pair {
+            [qty, unit] => total += duration(qty, unit).ok()?,
+            _ => return None,
+        }
+    }
+    Some(total)
+}
+
+fn temperature(text: &str) -> Option<Temperature> {
+    let text = text.trim();
+    let (idx, _) = text.char_indices().last()?;
+    let (degrees, unit) = text.split_at(idx);
+    let degrees = degrees.trim_end_matches('°').trim().parse().ok()?;
+    match unit {
+        "F" | "f" => Some(Temperature::Fahrenheit(degrees)),
+        "C" | "c" => Some(Temperature::Celsius(degrees)),
+        _ => None,
+    }
+}
+
+fn ingredient(name: &str, amount: Option<&str>, form: Option<&str>) -> Result<Ingredient, String> {
+    let (qty, unit) = match amount.map(|a| a.split_once('%').unwrap_or((a, ""))) {
+        Some((qty, unit)) if !qty.trim().is_empty() => (quantity(qty)?, unit.trim().to_lowercase()),
+        // Cooklang allows ingredients without an amount. We have no way
+        // to say "some" so they become a count of one.
+        _ => (Ratio::from_integer(1), String::new()),
+    };
+    let qty = Quantity::from(qty);
+    let unit = match unit.as_str() {
+        "l" | "liter" | "liters" | "litre" | "litres" => "ltr".to_owned(),
+        _ => unit,
+    };
+    let mut name = name.trim().to_owned();
+    let amt = if unit.is_empty() {
+        Measure::Count(qty)
+    } else {
+        match parse::as_measure(&format!("{} {} ", qty, unit)) {
+            Ok(Measure::Count(_)) if unit != "cnt" && unit != "count" => {
+                // Units we don't know like cloves become part of the name so
+                // they aren't lost.
+                name = format!("{} {}", unit, name);
+                Measure::Count(qty)
+            }
+            Ok(amt) => amt,
+            Err(e) => return Err(e),
+        }
+    };
+    Ok(Ingredient::new(
+        name,
+        form.map(|f| f.trim().to_owned()),
+        amt,
+    ))
+}
+
+#[derive(Default)]
+struct Paragraph {
+    lines: Vec<String>,
+    ingredients: Vec<Ingredient>,
+    timers: Vec<StepTimer>,
+    images: Vec<String>,
+    temperatures: Vec<Temperature>,
+}
+
+impl Paragraph {
+    fn is_empty(&self) -> bool {
+        self.lines.is_empty()
+            && self.ingredients.is_empty()
+            && self.timers.is_empty()
+            && self.images.is_empty()
+            && self.temperatures.is_empty()
+    }
+
+    fn merge(&mut self, other: Paragraph) {
+        self.lines.extend(other.lines);
+        self.ingredients.extend(other.ingredients);
+        self.timers.extend(other.timers);
+        self.images.extend(other.images);
+        self.temperatures.extend(other.temperatures);
+    }
+
+    fn into_step(self) -> Step {
+        Step::new(None, self.lines.join("\n"))
+            .with_ingredients(self.ingredients)
+            .with_timers(self.timers)
+            .with_images(self.images)
+            .with_temperatures(self.temperatures)
+    }
+
+    /// Add a line of Cooklang text to the paragraph.
+    fn add_line(&mut self, line: &str) -> Result<(), String> {
+        let trimmed = line.trim();
+        if let Some(comment) = trimmed.strip_prefix("--") {
+            let comment = comment.trim();
+            if let Some(image) = comment.strip_prefix("image:") {
+                self.images.push(image.trim().to_owned());
+            } else if let Some(temp) = comment.strip_prefix("temp:").and_then(temperature) {
+                self.temperatures.push(temp);
+            }
+            return Ok(());
+        }
+        let line = match line.find("--") {
+            Some(idx) => &line[0..idx],
+            None => line,
+        };
+        let is_marker = |c: char| c == '@' || c == '#' || c == '~';
+        // The instructions with the markers replaced by plain text.
+        let mut plain = String::new();
+        // Just the text outside of any markers.
+        let mut leftover = String::new();
+        let mut markers = 0;
+        let mut rest = line;
+        while let Some(idx) = rest.find(is_marker) {
+            plain.push_str(&rest[0..idx]);
+            leftover.push_str(&rest[0..idx]);
+            let kind = &rest[idx..idx + 1];
+            let body = &rest[idx + 1..];
+            let brace = body
+                .find(|c: char| c == '{' || is_marker(c))
+                .filter(|i| body[*i..].starts_with('{'));
+            let (name, amount, mut remainder) = match brace {
+                Some(open) => {
+                    let close = body[open..]
+                        .find('}')
+                        .map(|i| open + i)
+                        .ok_or_else(|| format!("Missing closing }} in: {}", line))?;
+                    (
+                        &body[0..open],
+                        Some(&body[open + 1..close]),
+                        &body[close + 1..],
+                    )
+                }
+                None => {
+                    let end = body
+                        .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+                        .unwrap_or(body.len());
+                    (&body[0..end], None, &body[end..])
+                }
+            };
+            if name.is_empty() && amount.is_none() {
+                // Not a marker after all.
+                plain.push_str(kind);
+                leftover.push_str(kind);
+                rest = body;
+                continue;
+            }
+            markers += 1;
+            match kind {
+                "@" => {
+                    let mut form = None;
+                    if remainder.starts_with('(') {
+                        if let Some(close) = remainder.find(')') {
+                            form = Some(&remainder[1..close]);
+                            remainder = &remainder[close + 1..];
+                        }
+                    }
+                    self.ingredients.push(ingredient(name, amount, form)?);
+                    plain.push_str(name.trim());
+                }
+                "~" => {
+                    let (qty, unit) = amount
+                        .and_then(|a| a.split_once('%'))
+                        .ok_or_else(|| format!("Timer needs a duration: {}", line))?;
+                    let label = name.trim();
+                    self.timers.push(StepTimer::new(
+                        duration(qty, unit)?,
+                        if label.is_empty() {
+                            None
+                        } else {
+                            Some(label.to_owned())
+                        },
+                    ));
+                    write!(plain, "{} {}", qty.trim(), unit.trim()).unwrap();
+                }
+                // Cookware just becomes part of the instructions.
+                _ => plain.push_str(name.trim()),
+            }
+            rest = remainder;
+        }
+        plain.push_str(rest);
+        leftover.push_str(rest);
+        let plain = plain.trim_end();
+        // Lines that only list ingredients or timers, like the ones we
+        // export, don't add anything to the instructions.
+        let only_markers = markers > 0
+            && leftover
+                .chars()
+                .all(|c| c.is_whitespace() || c == ',' || c == '.' || c == ';');
+        if !plain.trim().is_empty() && !only_markers {
+            self.lines.push(plain.to_owned());
+        }
+        Ok(())
+    }
+}
+
+fn strip_block_comments(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("[-") {
+        out.push_str(&rest[0..start]);
+        rest = match rest[start..].find("-]") {
+            Some(end) => &rest[start + end + 2..],
+            None => "",
+        };
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Parse a Cooklang recipe. Cooklang recipes are usually titled by their file
+/// name so `default_title` is used unless there is title metadata.
+pub fn as_recipe(text: &str, default_title: &str) -> Result<Recipe, String> {
+    let text = strip_block_comments(text);
+    let mut title = default_title.trim().to_owned();
+    let mut desc = None;
+    let mut metadata = RecipeMetadata::default();
+    let mut paragraphs = vec![Paragraph::default()];
+    for line in text.lines() {
+        let trimmed = line.trim();
+        if let Some(field) = trimmed.strip_prefix(">>") {
+            let (key, value) = field
+                .split_once(':')
+                .ok_or_else(|| format!("Invalid metadata: {}", trimmed))?;
+            let value = value.trim();
+            match key.trim().to_lowercase().as_str() {
+                "title" => title = value.to_owned(),
+                "description" => desc = Some(value.to_owned()),
+                "servings" | "serves" => {
+                    metadata.serves = value.split_whitespace().next().and_then(|n| n.parse().ok())
+                }
+                "prep time" | "prep_time" => metadata.prep_time = duration_text(value),
+                "tags" => metadata.tags.extend(
+                    value
+                        .split(',')
+                        .map(|t| t.trim().to_lowercase())
+                        .filter(|t| !t.is_empty()),
+                ),
+                "source" | "source.url" => metadata.source = Some(value.to_owned()),
+                "course" | "meal" => metadata.meal = value.parse().ok(),
+                _ => (),
+            }
+        } else if trimmed.is_empty() {
+            if !paragraphs.last().map(|p| p.is_empty()).unwrap_or(true) {
+                paragraphs.push(Paragraph::default());
+            }
+        } else {
+            paragraphs.last_mut().unwrap().add_line(line)?;
+        }
+    }
+    if title.is_empty() {
+        return Err("Recipe has no title".to_owned());
+    }
+    // Every step needs an ingredient list and instructions so paragraphs
+    // missing either are folded into their neighbors.
+    let mut steps: Vec<Paragraph> = Vec::new();
+    let mut pending = Paragraph::default();
+    for p in paragraphs {
+        pending.merge(p);
+        if !pending.ingredients.is_empty() && !pending.lines.is_empty() {
+            steps.push(std::mem::take(&mut pending));
+        }
+    }
+    match steps.last_mut() {
+        Some(last) => last.merge(pending),
+        None if pending.ingredients.is_empty() => {
+            return Err("Recipe has no ingredients".to_owned())
+        }
+        None => return Err("Recipe has no instructions".to_owned()),
+    }
+    Ok(Recipe::new(title, desc)
+        .with_metadata(metadata)
+        .with_steps(steps.into_iter().map(Paragraph::into_step)))
+}
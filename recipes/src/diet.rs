@@ -0,0 +1,78 @@
+// Copyright 2023 Jeremy Wall (Jeremy@marzhilsltudios.com)
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//! Matching ingredients against the allergens and dietary tags a user has
+//! given them so plans can warn about restrictions.
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::category::{contains_words, stem_words};
+use crate::Ingredient;
+
+/// The allergen and dietary tags for ingredients.
+///
+/// An ingredient gets the tags of every tagged ingredient that appears in its
+/// name so tagging "peanut" also tags "roasted peanuts".
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DietTable {
+    tags: Vec<(Vec<String>, BTreeSet<String>)>,
+}
+
+impl DietTable {
+    pub fn new<'a, Iter>(tags: Iter) -> Self
+    where
+        Iter: IntoIterator<Item = (&'a String, &'a BTreeSet<String>)>,
+    {
+        Self {
+            tags: tags
+                .into_iter()
+                .map(|(name, tags)| (stem_words(name), tags.clone()))
+                .filter(|(words, _)| !words.is_empty())
+                .collect(),
+        }
+    }
+
+    /// All of the tags for an ingredient name.
+    pub fn tags_for(&self, name: &str) -> BTreeSet<String> {
+        let words = stem_words(name);
+        self.tags
+            .iter()
+            .filter(|(mapped, _)| contains_words(&words, mapped))
+            .flat_map(|(_, tags)| tags.iter().cloned())
+            .collect()
+    }
+
+    /// The ingredients that conflict with each of the restrictions.
+    /// Restrictions with no conflicting ingredients are left out.
+    pub fn conflicts<'a, Iter>(
+        &self,
+        ingredients: Iter,
+        restrictions: &BTreeSet<String>,
+    ) -> BTreeMap<String, BTreeSet<String>>
+    where
+        Iter: IntoIterator<Item = &'a Ingredient>,
+    {
+        let mut conflicts = BTreeMap::new();
+        if restrictions.is_empty() {
+            return conflicts;
+        }
+        for ingredient in ingredients {
+            for tag in self.tags_for(&ingredient.name).intersection(restrictions) {
+                conflicts
+                    .entry(tag.clone())
+                    .or_insert_with(BTreeSet::new)
+                    .insert(ingredient.name.clone());
+            }
+        }
+        conflicts
+    }
+}
@@ -11,13 +11,21 @@
 // WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
 // See the License for the specific language governing permissions and
 // limitations under the License.
+pub mod category;
+pub mod cooklang;
+pub mod diet;
 pub mod parse;
+pub mod plain;
+pub mod rules;
+pub mod serialize;
+pub mod shopping;
 pub mod unit;
 
 use std::collections::{BTreeMap, BTreeSet};
 
 use chrono::NaiveDate;
 use serde::{Deserialize, Serialize};
+use unicode_normalization::UnicodeNormalization;
 
 use unit::*;
 use Measure::*;
@@ -49,12 +57,36 @@ impl Mealplan {
     }
 }
 
+/// Turn a recipe title into an id that is safe to use in urls and file paths.
+/// The title is NFC normalized so the same text always gives the same id.
+/// Letters and numbers are lowercased and every run of other characters
+/// becomes a single underscore.
+pub fn slugify(title: &str) -> String {
+    let mut slug = String::with_capacity(title.len());
+    for c in title.trim().nfc() {
+        if c.is_alphanumeric() {
+            slug.extend(c.to_lowercase());
+        } else if !slug.ends_with('_') {
+            slug.push('_');
+        }
+    }
+    slug.trim_matches('_').to_owned()
+}
+
+/// A stored recipe as (id, text, category, revision). The revision is the
+/// version of the recipe the entry was based on. Entries without one are
+/// saved without checking for concurrent edits.
 #[derive(Serialize, Deserialize, Clone, Debug)]
-pub struct RecipeEntry(pub String, pub String, pub Option<String>);
+pub struct RecipeEntry(
+    pub String,
+    pub String,
+    pub Option<String>,
+    #[serde(default)] pub Option<i64>,
+);
 
 impl RecipeEntry {
     pub fn new<IS: Into<String>, TS: Into<String>>(recipe_id: IS, text: TS) -> Self {
-        Self(recipe_id.into(), text.into(), None)
+        Self(recipe_id.into(), text.into(), None, None)
     }
 
     pub fn set_recipe_id<S: Into<String>>(&mut self, id: S) {
@@ -77,9 +109,113 @@ impl RecipeEntry {
         self.2 = Some(cat.into());
     }
 
+    pub fn clear_category(&mut self) {
+        self.2 = None;
+    }
+
     pub fn category(&self) -> Option<&String> {
         self.2.as_ref()
     }
+
+    pub fn set_revision(&mut self, revision: Option<i64>) {
+        self.3 = revision;
+    }
+
+    pub fn revision(&self) -> Option<i64> {
+        self.3
+    }
+}
+
+/// The meal a recipe is planned for.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Ord, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MealType {
+    Breakfast,
+    Lunch,
+    Dinner,
+    Side,
+    Dessert,
+}
+
+impl MealType {
+    pub const ALL: [MealType; 5] = [
+        MealType::Breakfast,
+        MealType::Lunch,
+        MealType::Dinner,
+        MealType::Side,
+        MealType::Dessert,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MealType::Breakfast => "breakfast",
+            MealType::Lunch => "lunch",
+            MealType::Dinner => "dinner",
+            MealType::Side => "side",
+            MealType::Dessert => "dessert",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            MealType::Breakfast => "Breakfast",
+            MealType::Lunch => "Lunch",
+            MealType::Dinner => "Dinner",
+            MealType::Side => "Side",
+            MealType::Dessert => "Dessert",
+        }
+    }
+}
+
+impl std::str::FromStr for MealType {
+    type Err = String;
+
+    fn from_str(name: &str) -> std::result::Result<Self, Self::Err> {
+        let lowered = name.trim().to_lowercase();
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|meal| meal.as_str() == lowered)
+            .ok_or_else(|| format!("Unknown meal {}", name.trim()))
+    }
+}
+
+// Recipes were all treated as dinner before they had a meal type so that
+// stays the default.
+impl Default for MealType {
+    fn default() -> Self {
+        MealType::Dinner
+    }
+}
+
+impl std::fmt::Display for MealType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.label())
+    }
+}
+
+/// Optional metadata for a Recipe from the front-matter block of the recipe text.
+#[derive(Debug, Clone, Default, PartialEq, PartialOrd, Ord, Eq)]
+pub struct RecipeMetadata {
+    pub serves: Option<u32>,
+    pub prep_time: Option<std::time::Duration>,
+    pub tags: BTreeSet<String>,
+    pub source: Option<String>,
+    pub meal: Option<MealType>,
+}
+
+impl RecipeMetadata {
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+
+    /// The source if it is a web address we can link back to. Sources can
+    /// also be a book and page which aren't links.
+    pub fn source_url(&self) -> Option<&str> {
+        self.source
+            .as_deref()
+            .filter(|src| src.starts_with("https://") || src.starts_with("http://"))
+    }
 }
 
 /// A Recipe with a title, description, and a series of steps.
@@ -87,6 +223,7 @@ impl RecipeEntry {
 pub struct Recipe {
     pub title: String,
     pub desc: Option<String>,
+    pub metadata: RecipeMetadata,
     pub steps: Vec<Step>,
 }
 
@@ -95,10 +232,16 @@ impl Recipe {
         Self {
             title: title.into(),
             desc: desc.map(|s| s.into()),
+            metadata: RecipeMetadata::default(),
             steps: Vec::new(),
         }
     }
 
+    pub fn with_metadata(mut self, metadata: RecipeMetadata) -> Self {
+        self.metadata = metadata;
+        self
+    }
+
     pub fn with_steps<Iter>(mut self, steps: Iter) -> Self
     where
         Iter: IntoIterator<Item = Step>,
@@ -120,6 +263,57 @@ impl Recipe {
         self.steps.push(step);
     }
 
+    /// Time spent preparing the recipe. The front-matter prep_time wins if
+    /// there is one otherwise this is the sum of the step durations.
+    pub fn prep_time(&self) -> std::time::Duration {
+        self.metadata
+            .prep_time
+            .unwrap_or_else(|| self.steps.iter().filter_map(|s| s.prep_time).sum())
+    }
+
+    /// Time spent cooking the recipe. This is the sum of the timers in steps
+    /// without a duration. A step's duration already covers its timers so
+    /// when the front-matter prep_time replaces the step durations in the
+    /// prep time those durations are counted here instead.
+    pub fn cook_time(&self) -> std::time::Duration {
+        let durations_in_prep = self.metadata.prep_time.is_none();
+        self.steps
+            .iter()
+            .map(|s| match s.prep_time {
+                Some(_) if durations_in_prep => std::time::Duration::ZERO,
+                Some(duration) => duration,
+                None => s.timers.iter().map(|t| t.duration).sum(),
+            })
+            .sum()
+    }
+
+    /// Total time for the recipe. The sum of the prep and cook times.
+    pub fn total_time(&self) -> std::time::Duration {
+        self.prep_time() + self.cook_time()
+    }
+
+    /// Scale every ingredient amount in the recipe by a `Quantity`.
+    pub fn scale(&self, factor: Quantity) -> Self {
+        let mut recipe = self.clone();
+        for step in recipe.steps.iter_mut() {
+            for ingredient in step.ingredients.iter_mut() {
+                ingredient.amt = ingredient.amt.scale(factor);
+            }
+        }
+        recipe
+    }
+
+    /// The factor to scale the recipe by to feed `people`. Recipes that don't
+    /// say how many they serve aren't scaled.
+    pub fn serving_scale(&self, people: u32) -> Quantity {
+        match self.metadata.serves {
+            Some(serves) if serves > 0 && people > 0 => {
+                Quantity::from(num_rational::Ratio::new(people, serves))
+            }
+            _ => Quantity::whole(1),
+        }
+    }
+
     /// Get entire ingredients list for each step of the recipe. With duplicate
     /// ingredients added together.
     pub fn get_ingredients(&self) -> BTreeMap<IngredientKey, Ingredient> {
@@ -130,19 +324,38 @@ impl Recipe {
             .map(|(k, v)| (k, v.0))
             .collect()
     }
+
+    /// The mise en place for the recipe. These are the ingredients that have
+    /// a form like diced or minced combined across all the steps so they can
+    /// be prepared before cooking starts.
+    pub fn prep_list(&self) -> Vec<Ingredient> {
+        self.get_ingredients()
+            .into_values()
+            .filter(|i| i.form.is_some() && i.recipe_reference().is_none())
+            .collect()
+    }
 }
 
 pub struct IngredientAccumulator {
     inner: BTreeMap<IngredientKey, (Ingredient, BTreeSet<String>)>,
+    densities: Option<DensityTable>,
 }
 
 impl IngredientAccumulator {
     pub fn new() -> Self {
         Self {
             inner: BTreeMap::new(),
+            densities: None,
         }
     }
 
+    /// Use the densities to combine volume and weight measures of the same
+    /// ingredient into a single weight.
+    pub fn with_densities(mut self, densities: DensityTable) -> Self {
+        self.densities = Some(densities);
+        self
+    }
+
     pub fn accumulate_ingredients_for<'a, Iter, S>(&'a mut self, recipe_title: S, ingredients: Iter)
     where
         Iter: Iterator<Item = &'a Ingredient>,
@@ -177,9 +390,102 @@ impl IngredientAccumulator {
         );
     }
 
-    pub fn ingredients(self) -> BTreeMap<IngredientKey, (Ingredient, BTreeSet<String>)> {
+    /// Accumulate the ingredients for the recipe with the given id expanding
+    /// any sub-recipe references using the recipes map. References to recipes
+    /// that can't be found are accumulated as regular ingredients.
+    pub fn accumulate_from_with_references(
+        &mut self,
+        id: &str,
+        r: &Recipe,
+        recipes: &BTreeMap<String, Recipe>,
+    ) {
+        let mut expanded = Vec::new();
+        let mut seen = vec![id.to_lowercase()];
+        expand_ingredients(r, Quantity::Whole(1), recipes, &mut seen, &mut expanded);
+        self.accumulate_ingredients_for(&r.title, expanded.iter());
+    }
+
+    pub fn ingredients(mut self) -> BTreeMap<IngredientKey, (Ingredient, BTreeSet<String>)> {
+        if let Some(densities) = self.densities.take() {
+            self.merge_volumes_into_weights(&densities);
+        }
         self.inner
     }
+
+    fn merge_volumes_into_weights(&mut self, densities: &DensityTable) {
+        // We only convert volumes when there is also a weight for the
+        // ingredient so that things like milk are still listed the way the
+        // recipes call for them.
+        let volume_keys: Vec<IngredientKey> = self
+            .inner
+            .keys()
+            .filter(|k| k.measure_type() == "Volume")
+            .filter(|k| {
+                let weight_key = IngredientKey::new(k.0.clone(), k.1.clone(), "Weight".to_owned());
+                self.inner.contains_key(&weight_key)
+            })
+            .cloned()
+            .collect();
+        for key in volume_keys {
+            let weight = match self
+                .inner
+                .get(&key)
+                .and_then(|(i, _)| densities.to_weight(&i.name, &i.amt))
+            {
+                Some(Weight(wm)) => wm,
+                _ => continue,
+            };
+            let (_, recipes) = self.inner.remove(&key).expect("volume key is present");
+            let weight_key = IngredientKey::new(key.0, key.1, "Weight".to_owned());
+            if let Some((i, set)) = self.inner.get_mut(&weight_key) {
+                if let Weight(wm) = i.amt {
+                    i.amt = Weight(wm + weight);
+                }
+                set.extend(recipes);
+            }
+        }
+    }
+}
+
+/// Find the recipe for a reference returning its lower cased id along with it.
+fn lookup_recipe<'a>(
+    id: &str,
+    recipes: &'a BTreeMap<String, Recipe>,
+) -> Option<(String, &'a Recipe)> {
+    let id = id.to_lowercase();
+    let recipe = recipes.get(&id).or_else(|| {
+        recipes
+            .iter()
+            .find(|(k, _)| k.to_lowercase() == id)
+            .map(|(_, r)| r)
+    })?;
+    Some((id, recipe))
+}
+
+fn expand_ingredients(
+    r: &Recipe,
+    scale: Quantity,
+    recipes: &BTreeMap<String, Recipe>,
+    seen: &mut Vec<String>,
+    out: &mut Vec<Ingredient>,
+) {
+    for i in r.steps.iter().map(|s| s.ingredients.iter()).flatten() {
+        if let (Some(id), Count(qty)) = (i.recipe_reference(), i.amt) {
+            // We track the references we are currently expanding so that a
+            // cycle of references can't recurse forever.
+            if let Some((id, sub_recipe)) = lookup_recipe(id, recipes) {
+                if !seen.contains(&id) {
+                    seen.push(id);
+                    expand_ingredients(sub_recipe, (qty * scale).normalize(), recipes, seen, out);
+                    seen.pop();
+                    continue;
+                }
+            }
+        }
+        let mut i = i.clone();
+        i.amt = i.amt.scale(scale);
+        out.push(i);
+    }
 }
 
 /// A Recipe step. It has the time for the step if there is one, instructions, and an ingredients
@@ -189,6 +495,9 @@ pub struct Step {
     pub prep_time: Option<std::time::Duration>,
     pub instructions: String,
     pub ingredients: Vec<Ingredient>,
+    pub images: Vec<String>,
+    pub timers: Vec<StepTimer>,
+    pub temperatures: Vec<Temperature>,
 }
 
 impl Step {
@@ -197,9 +506,36 @@ impl Step {
             prep_time: prep_time,
             instructions: instructions.into(),
             ingredients: Vec::new(),
+            images: Vec::new(),
+            timers: Vec::new(),
+            temperatures: Vec::new(),
         }
     }
 
+    pub fn with_images<Iter>(mut self, images: Iter) -> Step
+    where
+        Iter: IntoIterator<Item = String>,
+    {
+        self.images.extend(images.into_iter());
+        self
+    }
+
+    pub fn with_timers<Iter>(mut self, timers: Iter) -> Step
+    where
+        Iter: IntoIterator<Item = StepTimer>,
+    {
+        self.timers.extend(timers.into_iter());
+        self
+    }
+
+    pub fn with_temperatures<Iter>(mut self, temperatures: Iter) -> Step
+    where
+        Iter: IntoIterator<Item = Temperature>,
+    {
+        self.temperatures.extend(temperatures.into_iter());
+        self
+    }
+
     pub fn with_ingredients<Iter>(mut self, ingredients: Iter) -> Step
     where
         Iter: IntoIterator<Item = Ingredient>,
@@ -220,6 +556,19 @@ impl Step {
     }
 }
 
+/// A timer for a Recipe step with an optional label describing what it is for.
+#[derive(Debug, Clone, PartialEq, PartialOrd, Ord, Eq)]
+pub struct StepTimer {
+    pub duration: std::time::Duration,
+    pub label: Option<String>,
+}
+
+impl StepTimer {
+    pub fn new(duration: std::time::Duration, label: Option<String>) -> Self {
+        Self { duration, label }
+    }
+}
+
 /// Unique identifier for an Ingredient. Ingredients are identified by name, form,
 /// and measurement type. (Volume, Count, Weight)
 #[derive(PartialEq, PartialOrd, Eq, Ord, Clone, Hash, Debug, Deserialize, Serialize)]
@@ -277,6 +626,12 @@ impl Ingredient {
         }
     }
 
+    /// The recipe id this ingredient refers to if it is a sub-recipe reference
+    /// like `1 cnt recipe:pizza_dough`.
+    pub fn recipe_reference(&self) -> Option<&str> {
+        self.name.strip_prefix("recipe:").map(|id| id.trim())
+    }
+
     /// Unique identifier for this Ingredient.
     pub fn key(&self) -> IngredientKey {
         return IngredientKey(
@@ -23,8 +23,8 @@ use inflector::Inflector;
 use num_rational::Ratio;
 
 use crate::{
-    unit::{Measure, Measure::*, Quantity, VolumeMeasure::*, WeightMeasure::*},
-    Ingredient, Recipe, Step,
+    unit::{Measure, Measure::*, Quantity, Temperature, VolumeMeasure::*, WeightMeasure::*},
+    Ingredient, MealType, Recipe, RecipeMetadata, Step, StepTimer,
 };
 
 fn format_err(err: Error<StrIter>) -> String {
@@ -43,6 +43,24 @@ pub fn as_recipe(i: &str) -> std::result::Result<Recipe, String> {
     }
 }
 
+/// Like [as_recipe] but it is an error if any of the text is left over. The
+/// recipe parser stops at the first paragraph it doesn't recognize after the
+/// steps and quietly drops the rest.
+pub fn as_complete_recipe(i: &str) -> std::result::Result<Recipe, String> {
+    match recipe(StrIter::new(i)) {
+        Result::Abort(e) | Result::Fail(e) => Err(format_err(e)),
+        Result::Incomplete(_) => Err(format!("Incomplete recipe can not parse")),
+        Result::Complete(rest, r) => match trailing_space(rest.clone()) {
+            Result::Complete(_, _) => Ok(r),
+            _ => Err(format!(
+                "Unrecognized text at line {} column {}",
+                rest.line(),
+                rest.column()
+            )),
+        },
+    }
+}
+
 pub fn as_categories(i: &str) -> std::result::Result<BTreeMap<String, String>, String> {
     match categories(StrIter::new(i)) {
         Result::Abort(e) | Result::Fail(e) => Err(format_err(e)),
@@ -59,6 +77,80 @@ pub fn as_measure(i: &str) -> std::result::Result<Measure, String> {
     }
 }
 
+/// Find the temperatures mentioned in free text like "Bake at 375F". This
+/// accepts the same temperatures as [temperature]. Lower case units and a
+/// space before a "C" need a degree sign so that things like "2 C" or "2c"
+/// aren't mistaken for temperatures. Returns the byte range of each
+/// temperature in the text.
+pub fn find_temperatures(text: &str) -> Vec<(std::ops::Range<usize>, Temperature)> {
+    let bytes = text.as_bytes();
+    let mut found = Vec::new();
+    let mut idx = 0;
+    while idx < bytes.len() {
+        if !bytes[idx].is_ascii_digit() || (idx > 0 && bytes[idx - 1].is_ascii_alphanumeric()) {
+            idx += 1;
+            continue;
+        }
+        let start = idx;
+        while idx < bytes.len() && bytes[idx].is_ascii_digit() {
+            idx += 1;
+        }
+        let n = match i32::from_str(&text[start..idx]) {
+            Ok(n) => n,
+            Err(_) => continue,
+        };
+        let mut end = idx;
+        let spaced = text[end..].starts_with(' ');
+        if spaced {
+            end += 1;
+        }
+        let degree = text[end..].starts_with('°');
+        if degree {
+            end += '°'.len_utf8();
+        }
+        let boundary = bytes
+            .get(end + 1)
+            .map(|b| !b.is_ascii_alphanumeric())
+            .unwrap_or(true);
+        let temp = match (bytes.get(end), boundary) {
+            (Some(b'F'), true) => Temperature::Fahrenheit(n),
+            (Some(b'C'), true) if degree || !spaced => Temperature::Celsius(n),
+            (Some(b'f'), true) if degree => Temperature::Fahrenheit(n),
+            (Some(b'c'), true) if degree => Temperature::Celsius(n),
+            _ => continue,
+        };
+        idx = end + 1;
+        found.push((start..idx, temp));
+    }
+    found
+}
+
+/// Rewrite any temperatures in the text into either Celsius or Fahrenheit.
+pub fn convert_temperatures(text: &str, celsius: bool) -> String {
+    let mut converted = String::with_capacity(text.len());
+    let mut last = 0;
+    for (range, temp) in find_temperatures(text) {
+        converted.push_str(&text[last..range.start]);
+        let temp = if celsius {
+            temp.into_celsius()
+        } else {
+            temp.into_fahrenheit()
+        };
+        converted.push_str(&temp.to_string());
+        last = range.end;
+    }
+    converted.push_str(&text[last..]);
+    converted
+}
+
+pub fn as_quantity(i: &str) -> std::result::Result<Quantity, String> {
+    match quantity_only(StrIter::new(i.trim())) {
+        Result::Abort(e) | Result::Fail(e) => Err(format_err(e)),
+        Result::Incomplete(_) => Err(format!("Incomplete quantity can not parse")),
+        Result::Complete(_, q) => Ok(q),
+    }
+}
+
 pub fn as_ingredient_list(i: &str) -> std::result::Result<Vec<Ingredient>, String> {
     match ingredient_list(StrIter::new(i)) {
         Result::Abort(e) | Result::Fail(e) => Err(format_err(e)),
@@ -142,11 +234,109 @@ make_fn!(
 
 );
 
+enum MetadataField {
+    Serves(u32),
+    PrepTime(Duration),
+    Tags(Vec<String>),
+    Source(String),
+    Meal(MealType),
+}
+
+make_fn!(
+    metadata_field<StrIter, MetadataField>,
+    do_each!(
+        field => either!(
+            do_each!(
+                _ => text_token!("serves:"),
+                _ => optional!(ws),
+                n => with_err!(must!(num), "Invalid serves value"),
+                (MetadataField::Serves(n))
+            ),
+            do_each!(
+                _ => text_token!("prep_time:"),
+                _ => optional!(ws),
+                dur => with_err!(must!(step_time), "Invalid prep_time value"),
+                (MetadataField::PrepTime(dur))
+            ),
+            do_each!(
+                _ => text_token!("tags:"),
+                _ => optional!(ws),
+                tags => until!(text_token!("\n")),
+                (MetadataField::Tags(
+                    tags.split(',')
+                        .map(|t| t.trim().to_lowercase())
+                        .filter(|t| !t.is_empty())
+                        .collect()
+                ))
+            ),
+            do_each!(
+                _ => text_token!("source:"),
+                _ => optional!(ws),
+                source => until!(text_token!("\n")),
+                (MetadataField::Source(source.trim().to_owned()))
+            ),
+            do_each!(
+                _ => text_token!("meal:"),
+                _ => optional!(ws),
+                meal => with_err!(must!(meal_type), "Invalid meal value"),
+                (MetadataField::Meal(meal))
+            )
+        ),
+        _ => optional!(ws),
+        _ => text_token!("\n"),
+        (field)
+    )
+);
+
+make_fn!(
+    meal_name<StrIter, &str>,
+    until!(text_token!("\n"))
+);
+
+fn meal_type(i: StrIter) -> abortable_parser::Result<StrIter, MealType> {
+    match meal_name(i.clone()) {
+        Result::Complete(rest, name) => match MealType::from_str(name) {
+            Ok(meal) => Result::Complete(rest, meal),
+            Err(msg) => Result::Fail(Error::new(msg, Box::new(i))),
+        },
+        Result::Fail(e) => Result::Fail(e),
+        Result::Abort(e) => Result::Abort(e),
+        Result::Incomplete(i) => Result::Incomplete(i),
+    }
+}
+
+make_fn!(
+    pub front_matter<StrIter, RecipeMetadata>,
+    do_each!(
+        _ => text_token!("---"),
+        _ => optional!(ws),
+        _ => text_token!("\n"),
+        fields => repeat!(metadata_field),
+        _ => with_err!(must!(text_token!("---")), "Invalid front-matter field"),
+        _ => optional!(ws),
+        _ => text_token!("\n"),
+        ({
+            let mut metadata = RecipeMetadata::default();
+            for field in fields {
+                match field {
+                    MetadataField::Serves(n) => metadata.serves = Some(n),
+                    MetadataField::PrepTime(dur) => metadata.prep_time = Some(dur),
+                    MetadataField::Tags(tags) => metadata.tags.extend(tags),
+                    MetadataField::Source(source) => metadata.source = Some(source),
+                    MetadataField::Meal(meal) => metadata.meal = Some(meal),
+                }
+            }
+            metadata
+        })
+    )
+);
+
 make_fn!(
     pub recipe<StrIter, Recipe>,
     do_each!(
+        metadata => optional!(front_matter),
         title => must!(title),
-        _ => optional!(para_separator),
+        _ => optional!(blank_line),
         desc => optional!(do_each!(
             _ => peek!(not!(step_prefix)),
             desc => description,
@@ -154,7 +344,9 @@ make_fn!(
         )),
         _ => optional!(para_separator),
         steps => step_list,
-        (Recipe::new(title, desc).with_steps(steps))
+        (Recipe::new(title, desc)
+            .with_metadata(metadata.unwrap_or_default())
+            .with_steps(steps))
     )
 );
 
@@ -169,6 +361,24 @@ make_fn!(
     )
 );
 
+make_fn!(
+    trailing_space<StrIter, ()>,
+    do_each!(
+        _ => repeat!(either!(discard!(ws), discard!(text_token!("\n")))),
+        _ => eoi,
+        (())
+    )
+);
+
+make_fn!(
+    blank_line<StrIter, &str>,
+    do_each!(
+        _ => optional!(ws),
+        _ => text_token!("\n"),
+        ("")
+    )
+);
+
 make_fn!(
     para_separator<StrIter, &str>,
     do_each!(
@@ -231,15 +441,104 @@ make_fn!(
     )
 );
 
+enum StepAnnotation {
+    Image(String),
+    Timer(StepTimer),
+    Temp(Temperature),
+}
+
+make_fn!(
+    pub temperature<StrIter, Temperature>,
+    do_each!(
+        n => num,
+        unit => either!(
+            do_each!(
+                _ => optional!(text_token!(" ")),
+                _ => text_token!("°"),
+                unit => either!(
+                    text_token!("F"),
+                    text_token!("f"),
+                    text_token!("C"),
+                    text_token!("c")
+                ),
+                (unit)
+            ),
+            do_each!(
+                _ => optional!(text_token!(" ")),
+                unit => text_token!("F"),
+                (unit)
+            ),
+            text_token!("C")
+        ),
+        (match unit {
+            "F" | "f" => Temperature::Fahrenheit(n as i32),
+            _ => Temperature::Celsius(n as i32),
+        })
+    )
+);
+
+make_fn!(
+    step_annotation<StrIter, StepAnnotation>,
+    do_each!(
+        annotation => either!(
+            do_each!(
+                _ => text_token!("image:"),
+                _ => optional!(ws),
+                image => until!(text_token!("\n")),
+                (StepAnnotation::Image(image.trim().to_owned()))
+            ),
+            do_each!(
+                _ => text_token!("timer:"),
+                _ => optional!(ws),
+                dur => with_err!(must!(step_time), "Invalid timer duration"),
+                label => until!(text_token!("\n")),
+                ({
+                    let label = label.trim();
+                    StepAnnotation::Timer(StepTimer::new(
+                        dur,
+                        if label.is_empty() { None } else { Some(label.to_owned()) },
+                    ))
+                })
+            ),
+            do_each!(
+                _ => text_token!("temp:"),
+                _ => optional!(ws),
+                temp => with_err!(must!(temperature), "Invalid temperature"),
+                _ => optional!(ws),
+                (StepAnnotation::Temp(temp))
+            )
+        ),
+        _ => text_token!("\n"),
+        (annotation)
+    )
+);
+
 make_fn!(
     pub step<StrIter, Step>,
     do_each!(
         dur => step_prefix,
+        annotations => repeat!(step_annotation),
         ingredients => with_err!(must!(ingredient_list), "Missing ingredient list"),
         _ => para_separator,
         desc => description,
         _ => either!(discard!(para_separator), eoi),
-        (Step::new(dur, desc).with_ingredients(ingredients))
+        ({
+            let mut images = Vec::new();
+            let mut timers = Vec::new();
+            let mut temperatures = Vec::new();
+            for annotation in annotations {
+                match annotation {
+                    StepAnnotation::Image(image) => images.push(image),
+                    StepAnnotation::Timer(timer) => timers.push(timer),
+                    StepAnnotation::Temp(temp) => temperatures.push(temp),
+                }
+            }
+            Step::new(dur, desc)
+                .with_ingredients(ingredients)
+                .with_images(images)
+                .with_timers(timers)
+                .with_temperatures(temperatures)
+        })
     )
 );
 
@@ -363,6 +662,15 @@ make_fn!(
     )
 );
 
+make_fn!(
+    quantity_only<StrIter, Quantity>,
+    do_each!(
+        q => quantity,
+        _ => eoi,
+        (q)
+    )
+);
+
 make_fn!(
     pub measure_parts<StrIter, (Quantity, Option<String>)>,
     do_each!(
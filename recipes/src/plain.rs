@@ -0,0 +1,122 @@
+// Copyright 2023 Jeremy Wall (Jeremy@marzhilsltudios.com)
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//! Turn plain text, like the output of OCR on a recipe card, into a draft
+//! recipe.
+//!
+//! Plain text doesn't mark which lines are ingredients so this is a guess.
+//! Lines that parse as an ingredient go in the ingredient list and the rest
+//! become instructions. The draft always parses so it can be saved and then
+//! cleaned up in the editor.
+use crate::{parse, serialize, Ingredient, Recipe, Step};
+
+const FRACTIONS: &'static [(&'static str, &'static str)] = &[
+    ("½", " 1/2"),
+    ("⅓", " 1/3"),
+    ("⅔", " 2/3"),
+    ("¼", " 1/4"),
+    ("¾", " 3/4"),
+    ("⅛", " 1/8"),
+];
+
+const BULLETS: &'static [char] = &['-', '*', '•', '·', '○', '□'];
+
+/// Normalize a line of scanned text. Bullets are dropped and unicode
+/// fractions are spelled out so the ingredient parser understands them.
+fn clean_line(line: &str) -> String {
+    let mut line = line.trim().trim_start_matches(BULLETS).to_owned();
+    for (frac, text) in FRACTIONS {
+        line = line.replace(frac, text);
+    }
+    line.split_whitespace().collect::<Vec<&str>>().join(" ")
+}
+
+fn as_ingredient(line: &str) -> Option<Ingredient> {
+    match parse::as_ingredient_list(line) {
+        Ok(mut ingredients) if ingredients.len() == 1 => ingredients.pop(),
+        _ => None,
+    }
+}
+
+#[derive(Default)]
+struct DraftStep {
+    ingredients: Vec<Ingredient>,
+    lines: Vec<String>,
+}
+
+impl DraftStep {
+    fn into_step(self) -> Step {
+        // Every step needs ingredients and instructions to parse so we use
+        // the same placeholders as the new recipe template.
+        let mut ingredients = self.ingredients;
+        if ingredients.is_empty() {
+            ingredients.extend(as_ingredient("1 ingredient"));
+        }
+        let instructions = if self.lines.is_empty() {
+            "Instructions here".to_owned()
+        } else {
+            self.lines.join("\n")
+        };
+        Step::new(None, instructions).with_ingredients(ingredients)
+    }
+}
+
+/// Guess at a recipe from plain text. The first line that isn't an
+/// ingredient is the title unless there isn't one and then `default_title`
+/// is used. Text between the title and the first ingredient is the
+/// description. A run of ingredients followed by instructions is a step.
+pub fn as_recipe(text: &str, default_title: &str) -> Recipe {
+    let mut title = None;
+    let mut desc = Vec::new();
+    let mut steps: Vec<DraftStep> = Vec::new();
+    for line in text.lines().map(clean_line).filter(|l| !l.is_empty()) {
+        if let Some(ingredient) = as_ingredient(&line) {
+            match steps.last_mut() {
+                Some(step) if step.lines.is_empty() => step.ingredients.push(ingredient),
+                _ => steps.push(DraftStep {
+                    ingredients: vec![ingredient],
+                    lines: Vec::new(),
+                }),
+            }
+        } else if title.is_none() && steps.is_empty() {
+            let line = match line.split_once(':') {
+                Some((key, rest)) if key.trim().eq_ignore_ascii_case("title") => {
+                    rest.trim().to_owned()
+                }
+                _ => line,
+            };
+            title = Some(line);
+        } else if let Some(step) = steps.last_mut() {
+            step.lines.push(line);
+        } else {
+            desc.push(line);
+        }
+    }
+    let title = title
+        .filter(|t| !t.is_empty())
+        .unwrap_or_else(|| default_title.trim().to_owned());
+    let desc = if desc.is_empty() {
+        None
+    } else {
+        Some(desc.join("\n"))
+    };
+    if steps.is_empty() {
+        steps.push(DraftStep::default());
+    }
+    Recipe::new(title, desc).with_steps(steps.into_iter().map(DraftStep::into_step))
+}
+
+/// Guess at a recipe from plain text and render it as recipe text.
+pub fn as_draft(text: &str, default_title: &str) -> String {
+    serialize::as_text(&as_recipe(text, default_title))
+}
@@ -0,0 +1,208 @@
+// Copyright 2023 Jeremy Wall (Jeremy@marzhilsltudios.com)
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+/*!
+Rules a user sets for planning meals and checking plans against them.
+*/
+use std::collections::{BTreeMap, BTreeSet};
+
+use chrono::{Datelike, Duration, NaiveDate};
+use serde::{Deserialize, Serialize};
+
+const MONTHS: [&'static str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+/// The short name for a month numbered from 1.
+pub fn month_name(month: u32) -> &'static str {
+    MONTHS
+        .get((month as usize).wrapping_sub(1))
+        .copied()
+        .unwrap_or("?")
+}
+
+/// Why a [PlanRule] is invalid.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InvalidRule {
+    pub field: String,
+    pub message: String,
+}
+
+/// A rule for planning meals. Recipes are matched by the tags in their
+/// metadata.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum PlanRule {
+    /// Recipes with the tag are only planned from `from_month` through
+    /// `to_month`. Months are numbered from 1 and the range can wrap around
+    /// the end of the year.
+    Season {
+        tag: String,
+        from_month: u32,
+        to_month: u32,
+    },
+    /// Every plan should have at least `count` recipes with the tag.
+    AtLeast { tag: String, count: u32 },
+    /// Don't plan a recipe again within this many weeks.
+    NoRepeat { weeks: u32 },
+}
+
+impl PlanRule {
+    pub fn validate(&self) -> Result<(), InvalidRule> {
+        let invalid = |field: &str, message: &str| {
+            Err(InvalidRule {
+                field: field.to_owned(),
+                message: message.to_owned(),
+            })
+        };
+        match self {
+            Self::Season { tag, .. } | Self::AtLeast { tag, .. } if tag.trim().is_empty() => {
+                invalid("tag", "A tag is required")
+            }
+            Self::Season {
+                from_month,
+                to_month,
+                ..
+            } if !(1..=12).contains(from_month) || !(1..=12).contains(to_month) => {
+                invalid("month", "Months must be from 1 to 12")
+            }
+            Self::AtLeast { count: 0, .. } => invalid("count", "The count must be at least 1"),
+            Self::NoRepeat { weeks: 0 } => invalid("weeks", "The weeks must be at least 1"),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            Self::Season {
+                tag,
+                from_month,
+                to_month,
+            } => format!(
+                "{} only {}–{}",
+                tag,
+                month_name(*from_month),
+                month_name(*to_month)
+            ),
+            Self::AtLeast { tag, count: 1 } => format!("{} at least once a plan", tag),
+            Self::AtLeast { tag, count } => format!("{} at least {} times a plan", tag, count),
+            Self::NoRepeat { weeks } => format!("No repeats within {} weeks", weeks),
+        }
+    }
+
+    /// How far back we need to look at previous plans for this rule.
+    pub fn lookback_days(&self) -> i64 {
+        match self {
+            Self::NoRepeat { weeks } => *weeks as i64 * 7,
+            _ => 0,
+        }
+    }
+
+    /// Whether this rule lets a recipe be planned on `date`. `history` holds
+    /// the recipes in earlier plans.
+    pub fn allows(
+        &self,
+        date: NaiveDate,
+        recipe_id: &str,
+        tags: &BTreeSet<String>,
+        history: &BTreeMap<NaiveDate, Vec<String>>,
+    ) -> bool {
+        match self {
+            Self::Season {
+                tag,
+                from_month,
+                to_month,
+            } => {
+                let month = date.month();
+                let in_season = if from_month <= to_month {
+                    *from_month <= month && month <= *to_month
+                } else {
+                    month >= *from_month || month <= *to_month
+                };
+                in_season || !tags.contains(&tag.to_lowercase())
+            }
+            Self::AtLeast { .. } => true,
+            Self::NoRepeat { .. } => {
+                let since = date - Duration::days(self.lookback_days());
+                !history
+                    .range(since..date)
+                    .any(|(_, ids)| ids.iter().any(|id| id == recipe_id))
+            }
+        }
+    }
+}
+
+/// A [PlanRule] as stored for a user.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct StoredPlanRule {
+    pub id: i64,
+    pub rule: PlanRule,
+}
+
+/// A plan that breaks one of the user's [PlanRule]s.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RuleViolation {
+    pub rule_id: i64,
+    /// The recipe that breaks the rule if it is about a single recipe.
+    pub recipe_id: Option<String>,
+    pub message: String,
+}
+
+/// Check a plan for `date` against the rules. `tags` holds the tags for each
+/// recipe and `history` the recipes in earlier plans.
+pub fn check_plan(
+    rules: &[StoredPlanRule],
+    date: NaiveDate,
+    plan: &[(String, i32)],
+    tags: &BTreeMap<String, BTreeSet<String>>,
+    history: &BTreeMap<NaiveDate, Vec<String>>,
+) -> Vec<RuleViolation> {
+    let no_tags = BTreeSet::new();
+    let planned = plan
+        .iter()
+        .filter(|(_, count)| *count > 0)
+        .collect::<Vec<&(String, i32)>>();
+    let mut violations = Vec::new();
+    for StoredPlanRule { id, rule } in rules {
+        if let PlanRule::AtLeast { tag, count } = rule {
+            let tag = tag.to_lowercase();
+            let found: i32 = planned
+                .iter()
+                .filter(|(recipe_id, _)| {
+                    tags.get(recipe_id)
+                        .map(|t| t.contains(&tag))
+                        .unwrap_or(false)
+                })
+                .map(|(_, count)| *count)
+                .sum();
+            if found < *count as i32 {
+                violations.push(RuleViolation {
+                    rule_id: *id,
+                    recipe_id: None,
+                    message: format!("{} (only {} planned)", rule.describe(), found),
+                });
+            }
+            continue;
+        }
+        for (recipe_id, _) in planned.iter() {
+            let recipe_tags = tags.get(recipe_id).unwrap_or(&no_tags);
+            if !rule.allows(date, recipe_id, recipe_tags, history) {
+                violations.push(RuleViolation {
+                    rule_id: *id,
+                    recipe_id: Some(recipe_id.clone()),
+                    message: format!("{}: {}", recipe_id, rule.describe()),
+                });
+            }
+        }
+    }
+    violations
+}
@@ -0,0 +1,191 @@
+// Copyright 2023 Jeremy Wall (Jeremy@marzhilsltudios.com)
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//! Render a [Recipe] back into the canonical recipe text format.
+//!
+//! The output of [as_text] parses back into an equal [Recipe] with
+//! [crate::parse::as_recipe]. [format_recipe] uses it to tidy up recipe text.
+use std::collections::BTreeSet;
+use std::fmt::Write;
+use std::time::Duration;
+
+use crate::{parse, Recipe, RecipeMetadata, Step};
+
+/// Format a duration in the units the parser understands. Anything less than
+/// a second is dropped since the parser can't represent it.
+pub fn duration(d: &Duration) -> String {
+    let secs = d.as_secs();
+    if secs > 0 && secs % (60 * 60) == 0 {
+        format!("{} hr", secs / (60 * 60))
+    } else if secs > 0 && secs % 60 == 0 {
+        format!("{} min", secs / 60)
+    } else {
+        format!("{} sec", secs)
+    }
+}
+
+fn front_matter(out: &mut String, metadata: &RecipeMetadata) {
+    if metadata.is_empty() {
+        return;
+    }
+    out.push_str("---\n");
+    if let Some(serves) = metadata.serves {
+        writeln!(out, "serves: {}", serves).unwrap();
+    }
+    if let Some(prep_time) = &metadata.prep_time {
+        writeln!(out, "prep_time: {}", duration(prep_time)).unwrap();
+    }
+    if !metadata.tags.is_empty() {
+        let tags = metadata
+            .tags
+            .iter()
+            .map(|t| t.as_str())
+            .collect::<Vec<&str>>();
+        writeln!(out, "tags: {}", tags.join(", ")).unwrap();
+    }
+    if let Some(source) = &metadata.source {
+        writeln!(out, "source: {}", source).unwrap();
+    }
+    if let Some(meal) = &metadata.meal {
+        writeln!(out, "meal: {}", meal.as_str()).unwrap();
+    }
+    out.push_str("---\n");
+}
+
+fn step(out: &mut String, step: &Step) {
+    out.push_str("step:");
+    if let Some(prep_time) = &step.prep_time {
+        write!(out, " {}", duration(prep_time)).unwrap();
+    }
+    out.push_str("\n\n");
+    for image in step.images.iter() {
+        writeln!(out, "image: {}", image).unwrap();
+    }
+    for timer in step.timers.iter() {
+        write!(out, "timer: {}", duration(&timer.duration)).unwrap();
+        if let Some(label) = &timer.label {
+            write!(out, " {}", label).unwrap();
+        }
+        out.push('\n');
+    }
+    for temp in step.temperatures.iter() {
+        writeln!(out, "temp: {}", temp).unwrap();
+    }
+    let ingredients = step
+        .ingredients
+        .iter()
+        .map(|i| i.to_string())
+        .collect::<Vec<String>>();
+    out.push_str(&ingredients.join("\n"));
+    out.push_str("\n\n");
+    out.push_str(&step.instructions);
+}
+
+/// Render a recipe as text.
+pub fn as_text(recipe: &Recipe) -> String {
+    let mut out = String::new();
+    front_matter(&mut out, &recipe.metadata);
+    writeln!(out, "title: {}", recipe.title).unwrap();
+    out.push('\n');
+    if let Some(desc) = &recipe.desc {
+        out.push_str(desc);
+        out.push_str("\n\n");
+    }
+    for (idx, s) in recipe.steps.iter().enumerate() {
+        if idx > 0 {
+            out.push_str("\n\n");
+        }
+        step(&mut out, s);
+    }
+    // The last step's instructions run to the end of the text so we don't add
+    // a trailing newline that would end up in them.
+    out
+}
+
+/// Trim the trailing whitespace from every line and the text as a whole.
+fn tidy(text: &str) -> String {
+    text.lines()
+        .map(|l| l.trim_end())
+        .collect::<Vec<&str>>()
+        .join("\n")
+        .trim()
+        .to_owned()
+}
+
+/// Rewrite recipe text in the canonical format. Whitespace is tidied, units
+/// use one spelling, and quantities are written as whole numbers and reduced
+/// fractions. Text the parser would drop is an error rather than lost.
+pub fn format_recipe(text: &str) -> Result<String, String> {
+    let mut recipe = parse::as_complete_recipe(text)?;
+    recipe.title = recipe.title.trim().to_owned();
+    recipe.desc = recipe.desc.as_deref().map(tidy);
+    for step in recipe.steps.iter_mut() {
+        step.instructions = tidy(&step.instructions);
+    }
+    let mut out = as_text(&recipe);
+    out.push('\n');
+    Ok(out)
+}
+
+/// Change the tags in the front matter of recipe text without touching the
+/// rest of it. `update` is given the current tags and returns the new ones.
+/// Front matter is added when it is needed and removed when it ends up empty.
+pub fn update_tags<F>(text: &str, update: F) -> String
+where
+    F: FnOnce(BTreeSet<String>) -> BTreeSet<String>,
+{
+    let lines = text.split_inclusive('\n').collect::<Vec<&str>>();
+    let end = if lines.first().map_or(false, |l| l.trim_end() == "---") {
+        lines
+            .iter()
+            .skip(1)
+            .position(|l| l.trim_end() == "---")
+            .map(|idx| idx + 1)
+    } else {
+        None
+    };
+    let (fields, rest) = match end {
+        Some(end) => (&lines[1..end], &lines[end + 1..]),
+        None => (&lines[0..0], &lines[..]),
+    };
+    let mut tags = BTreeSet::new();
+    let mut kept = Vec::new();
+    for field in fields {
+        match field.strip_prefix("tags:") {
+            Some(value) => tags.extend(
+                value
+                    .split(',')
+                    .map(|t| t.trim().to_lowercase())
+                    .filter(|t| !t.is_empty()),
+            ),
+            None => kept.push(*field),
+        }
+    }
+    let tags = update(tags);
+    let mut out = String::new();
+    if !tags.is_empty() || !kept.is_empty() {
+        out.push_str("---\n");
+        for field in kept {
+            out.push_str(field);
+        }
+        if !tags.is_empty() {
+            let tags = tags.iter().map(|t| t.as_str()).collect::<Vec<&str>>();
+            writeln!(out, "tags: {}", tags.join(", ")).unwrap();
+        }
+        out.push_str("---\n");
+    }
+    for line in rest {
+        out.push_str(line);
+    }
+    out
+}
@@ -0,0 +1,99 @@
+// Copyright 2023 Jeremy Wall (Jeremy@marzhilsltudios.com)
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+/*!
+Building the shopping list for a meal plan. The server and the web client both
+use this so they agree on what is left to buy.
+*/
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::unit::DensityTable;
+use crate::{Ingredient, IngredientAccumulator, IngredientKey, Recipe};
+
+/// The title staples are listed under on a shopping list.
+pub const STAPLES_TITLE: &'static str = "Staples";
+
+/// Everything besides the recipes themselves that decides what goes on the
+/// shopping list for a plan.
+#[derive(Debug, Clone, Default)]
+pub struct ShoppingPlan {
+    /// How many times each recipe is made keyed by recipe id.
+    pub recipe_counts: BTreeMap<String, usize>,
+    /// Planned recipes that come out of the freezer. They are already cooked
+    /// so there is nothing to buy for them.
+    pub freezer_meals: BTreeSet<String>,
+    /// The number of people to scale recipes for.
+    pub household_size: Option<u32>,
+    /// Staples to add to the list.
+    pub staples: Option<BTreeSet<Ingredient>>,
+    /// Ingredients that have been crossed off the list.
+    pub filtered_ingredients: BTreeSet<IngredientKey>,
+    /// Densities used to combine volume and weight measures of an ingredient.
+    pub densities: Option<DensityTable>,
+}
+
+/// An ingredient on the shopping list.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShoppingItem {
+    /// The combined amount of the ingredient across the plan.
+    pub ingredient: Ingredient,
+    /// The titles of the recipes that call for the ingredient.
+    pub recipes: BTreeSet<String>,
+    /// Whether the ingredient has been crossed off the list.
+    pub filtered: bool,
+}
+
+/// The shopping list for a plan including the items that have been crossed
+/// off. Recipes in the plan that aren't in `recipes` are skipped.
+pub fn shopping_list(
+    plan: &ShoppingPlan,
+    recipes: &BTreeMap<String, Recipe>,
+) -> BTreeMap<IngredientKey, ShoppingItem> {
+    let mut acc = IngredientAccumulator::new();
+    if let Some(densities) = &plan.densities {
+        acc = acc.with_densities(densities.clone());
+    }
+    for (id, count) in plan.recipe_counts.iter() {
+        if *count == 0 || plan.freezer_meals.contains(id) {
+            continue;
+        }
+        let recipe = match recipes.get(id) {
+            Some(recipe) => recipe,
+            None => continue,
+        };
+        let recipe = match plan.household_size {
+            Some(people) => recipe.scale(recipe.serving_scale(people)),
+            None => recipe.clone(),
+        };
+        for _ in 0..*count {
+            acc.accumulate_from_with_references(id, &recipe, recipes);
+        }
+    }
+    if let Some(staples) = &plan.staples {
+        acc.accumulate_ingredients_for(STAPLES_TITLE, staples.iter());
+    }
+    acc.ingredients()
+        .into_iter()
+        .map(|(key, (ingredient, recipes))| {
+            let filtered = plan.filtered_ingredients.contains(&key);
+            (
+                key,
+                ShoppingItem {
+                    ingredient,
+                    recipes,
+                    filtered,
+                },
+            )
+        })
+        .collect()
+}
@@ -18,6 +18,7 @@ use std::convert::Into;
 
 use abortable_parser::{Result as ParseResult, StrIter};
 use num_rational::Ratio;
+use proptest::prelude::*;
 
 #[test]
 fn test_volume_measure_conversion() {
@@ -549,3 +550,1461 @@ fn test_ingredients_list_happy_path() {
         }
     }
 }
+
+#[test]
+fn test_suggest_category() {
+    let mut mappings = BTreeMap::new();
+    mappings.insert("onion".to_owned(), "Produce".to_owned());
+    mappings.insert("cheddar cheese".to_owned(), "Dairy".to_owned());
+    mappings.insert("cheese".to_owned(), "Deli".to_owned());
+    mappings.insert("ground beef".to_owned(), "Meat".to_owned());
+    for (name, expected) in vec![
+        ("onion", Some("Produce")),
+        ("green onions", Some("Produce")),
+        ("Sharp Cheddar Cheese", Some("Dairy")),
+        ("swiss cheese", Some("Deli")),
+        ("beef", Some("Meat")),
+        ("lean ground beef", Some("Meat")),
+        ("flour", None),
+    ] {
+        assert_eq!(
+            category::suggest_category(name, &mappings),
+            expected.map(|s| s.to_owned()),
+            "suggestion for {}",
+            name
+        );
+    }
+}
+
+#[test]
+fn test_suggest_categories_skips_mapped() {
+    let mut mappings = BTreeMap::new();
+    mappings.insert("onion".to_owned(), "Produce".to_owned());
+    let names = vec!["onion".to_owned(), "red onion".to_owned(), "salt".to_owned()];
+    assert_eq!(
+        category::suggest_categories(names.iter(), &mappings),
+        vec![
+            ("red onion".to_owned(), Some("Produce".to_owned())),
+            ("salt".to_owned(), None),
+        ]
+    );
+}
+
+#[test]
+fn test_diet_table_conflicts() {
+    let mut tags = BTreeMap::new();
+    tags.insert(
+        "peanut".to_owned(),
+        BTreeSet::from(["peanut".to_owned(), "legume".to_owned()]),
+    );
+    tags.insert("flour".to_owned(), BTreeSet::from(["gluten".to_owned()]));
+    let table = diet::DietTable::new(tags.iter());
+    assert_eq!(
+        table.tags_for("roasted peanuts"),
+        BTreeSet::from(["legume".to_owned(), "peanut".to_owned()])
+    );
+    assert!(table.tags_for("rice").is_empty());
+    let ingredients = vec![
+        Ingredient::new("roasted peanuts", None, Measure::cup(1.into())),
+        Ingredient::new("rice flour", None, Measure::cup(2.into())),
+        Ingredient::new("rice", None, Measure::cup(2.into())),
+    ];
+    let restrictions = BTreeSet::from(["peanut".to_owned(), "dairy".to_owned()]);
+    let conflicts = table.conflicts(ingredients.iter(), &restrictions);
+    assert_eq!(conflicts.len(), 1);
+    assert_eq!(
+        conflicts.get("peanut"),
+        Some(&BTreeSet::from(["roasted peanuts".to_owned()]))
+    );
+    assert!(table
+        .conflicts(ingredients.iter(), &BTreeSet::new())
+        .is_empty());
+}
+
+#[test]
+fn test_recipe_with_front_matter() {
+    let recipe = "---
+serves: 4
+prep_time: 45 min
+tags: Dinner, easy
+source: https://example.com/apple-bake
+---
+title: gooey apple bake
+
+A simple gooey apple bake recipe.
+
+step:
+
+1 tbsp flour
+2 tbsp butter
+
+Saute apples in butter until golden brown.
+";
+    match parse::as_recipe(recipe) {
+        Ok(recipe) => {
+            assert_eq!(recipe.title, "gooey apple bake");
+            assert_eq!(recipe.metadata.serves, Some(4));
+            assert_eq!(
+                recipe.metadata.prep_time,
+                Some(std::time::Duration::from_secs(45 * 60))
+            );
+            assert!(recipe.metadata.tags.contains("dinner"));
+            assert!(recipe.metadata.tags.contains("easy"));
+            assert_eq!(
+                recipe.metadata.source.as_ref().map(|s| s.as_str()),
+                Some("https://example.com/apple-bake")
+            );
+            assert_eq!(recipe.steps.len(), 1);
+        }
+        Err(e) => assert!(false, "{:?}", e),
+    }
+}
+
+#[test]
+fn test_recipe_meal_front_matter() {
+    let recipe = "---
+meal: Breakfast
+---
+title: pancakes
+
+step:
+
+1 cup flour
+
+Mix and fry.
+";
+    match parse::as_recipe(recipe) {
+        Ok(parsed) => {
+            assert_eq!(parsed.metadata.meal, Some(MealType::Breakfast));
+            let text = serialize::as_text(&parsed);
+            assert!(text.contains("meal: breakfast\n"));
+            assert_eq!(parse::as_recipe(&text).unwrap(), parsed);
+        }
+        Err(e) => assert!(false, "{:?}", e),
+    }
+    assert_eq!(" dessert ".parse::<MealType>(), Ok(MealType::Dessert));
+    assert!("brunch".parse::<MealType>().is_err());
+    assert_eq!(MealType::default(), MealType::Dinner);
+}
+
+#[test]
+fn test_recipe_unknown_meal_is_an_error() {
+    let recipe = "---
+meal: brunch
+---
+title: pancakes
+
+step:
+
+1 cup flour
+
+Mix and fry.
+";
+    assert!(parse::as_recipe(recipe).is_err());
+}
+
+#[test]
+fn test_recipe_without_front_matter_has_empty_metadata() {
+    let recipe = "title: gooey apple bake
+
+step:
+
+1 tbsp flour
+
+Saute apples in butter until golden brown.
+";
+    match parse::as_recipe(recipe) {
+        Ok(recipe) => assert!(recipe.metadata.is_empty()),
+        Err(e) => assert!(false, "{:?}", e),
+    }
+}
+
+#[test]
+fn test_recipe_front_matter_invalid_field() {
+    let recipe = "---
+servings: 4
+---
+title: gooey apple bake
+
+step:
+
+1 tbsp flour
+
+Saute apples in butter until golden brown.
+";
+    match parse::recipe(StrIter::new(recipe)) {
+        ParseResult::Abort(e) => {
+            assert_eq!(e.get_msg(), "Invalid front-matter field");
+        }
+        other => assert!(false, "{:?}", other),
+    }
+}
+
+#[test]
+fn test_measure_scale() {
+    assert_eq!(Measure::cup(1.into()).scale(2.into()), Measure::cup(2.into()));
+    assert_eq!(
+        Measure::gram(3.into()).scale(Ratio::new(1, 3).into()),
+        Measure::gram(1.into())
+    );
+    assert_eq!(Measure::count(2).scale(3.into()), Measure::count(6));
+}
+
+#[test]
+fn test_measure_saturating_sub() {
+    assert_eq!(
+        Measure::cup(2.into()).saturating_sub(&Measure::cup(1.into())),
+        Some(Measure::cup(1.into()))
+    );
+    assert_eq!(
+        Measure::count(2).saturating_sub(&Measure::count(3)),
+        Some(Measure::count(0))
+    );
+    assert_eq!(
+        Measure::kilogram(1.into()).saturating_sub(&Measure::gram(250.into())),
+        Some(Measure::gram(750.into()))
+    );
+    assert_eq!(
+        Measure::cup(1.into()).saturating_sub(&Measure::gram(1.into())),
+        None
+    );
+}
+
+#[test]
+fn test_sub_recipe_reference_parse() {
+    match parse::as_ingredient_list("2 cnt recipe:pizza_dough\n1 cup cheese") {
+        Ok(ingredients) => {
+            assert_eq!(ingredients[0].recipe_reference(), Some("pizza_dough"));
+            assert_eq!(ingredients[1].recipe_reference(), None);
+        }
+        Err(e) => assert!(false, "{:?}", e),
+    }
+}
+
+#[test]
+fn test_accumulate_with_sub_recipe_references() {
+    let dough = Recipe::new("pizza dough", None).with_steps(vec![Step::new(None, "knead")
+        .with_ingredients(vec![
+            Ingredient::new("flour", None, Measure::cup(2.into())),
+            Ingredient::new("salt", None, Measure::tsp(1.into())),
+        ])]);
+    let pizza = Recipe::new("pizza", None).with_steps(vec![Step::new(None, "bake")
+        .with_ingredients(vec![
+            Ingredient::new("recipe:pizza_dough", None, Measure::count(2)),
+            Ingredient::new("recipe:missing", None, Measure::count(1)),
+            Ingredient::new("salt", None, Measure::tsp(1.into())),
+        ])]);
+    let mut recipes = BTreeMap::new();
+    recipes.insert("pizza_dough".to_owned(), dough);
+    recipes.insert("pizza".to_owned(), pizza.clone());
+    let mut acc = IngredientAccumulator::new();
+    acc.accumulate_from_with_references("pizza", &pizza, &recipes);
+    let ingredients = acc.ingredients();
+    assert_eq!(ingredients.len(), 3);
+    let flour = &ingredients[&IngredientKey::new("flour".to_owned(), None, "Volume".to_owned())];
+    assert_eq!(flour.0.amt, Measure::cup(4.into()));
+    let salt = &ingredients[&IngredientKey::new("salt".to_owned(), None, "Volume".to_owned())];
+    assert_eq!(salt.0.amt, Measure::tsp(3.into()));
+    assert!(ingredients.contains_key(&IngredientKey::new(
+        "recipe:missing".to_owned(),
+        None,
+        "Count".to_owned()
+    )));
+}
+
+#[test]
+fn test_accumulate_with_recursive_sub_recipe_references() {
+    let sauce = Recipe::new("sauce", None).with_steps(vec![Step::new(None, "stir")
+        .with_ingredients(vec![
+            Ingredient::new("recipe:sauce", None, Measure::count(1)),
+            Ingredient::new("tomato", None, Measure::count(1)),
+        ])]);
+    let mut recipes = BTreeMap::new();
+    recipes.insert("sauce".to_owned(), sauce.clone());
+    let mut acc = IngredientAccumulator::new();
+    acc.accumulate_from_with_references("sauce", &sauce, &recipes);
+    let ingredients = acc.ingredients();
+    let tomato = &ingredients[&IngredientKey::new("tomato".to_owned(), None, "Count".to_owned())];
+    assert_eq!(tomato.0.amt, Measure::count(1));
+}
+
+#[test]
+fn test_accumulate_with_mixed_case_sub_recipe_references() {
+    let dough = Recipe::new("pizza dough", None).with_steps(vec![Step::new(None, "knead")
+        .with_ingredients(vec![
+            Ingredient::new("recipe:Pizza", None, Measure::count(1)),
+            Ingredient::new("flour", None, Measure::cup(2.into())),
+        ])]);
+    let pizza = Recipe::new("pizza", None).with_steps(vec![Step::new(None, "bake")
+        .with_ingredients(vec![Ingredient::new(
+            "recipe:pizza_dough",
+            None,
+            Measure::count(1),
+        )])]);
+    let mut recipes = BTreeMap::new();
+    recipes.insert("Pizza_Dough".to_owned(), dough);
+    recipes.insert("Pizza".to_owned(), pizza.clone());
+    let mut acc = IngredientAccumulator::new();
+    acc.accumulate_from_with_references("Pizza", &pizza, &recipes);
+    let ingredients = acc.ingredients();
+    assert_eq!(ingredients.len(), 2);
+    let flour = &ingredients[&IngredientKey::new("flour".to_owned(), None, "Volume".to_owned())];
+    assert_eq!(flour.0.amt, Measure::cup(2.into()));
+    assert!(ingredients.contains_key(&IngredientKey::new(
+        "recipe:Pizza".to_owned(),
+        None,
+        "Count".to_owned()
+    )));
+}
+
+#[test]
+fn test_single_step_with_images_and_timers() {
+    let step = "step: 30 min
+
+image: dough.jpg
+timer: 10 min rest
+timer: 20 min
+1 cup flour
+
+Knead the dough then rest it.";
+
+    match parse::step(StrIter::new(step)) {
+        ParseResult::Complete(_, step) => {
+            assert_eq!(step.ingredients.len(), 1);
+            assert_eq!(step.images, vec!["dough.jpg".to_owned()]);
+            assert_eq!(
+                step.timers,
+                vec![
+                    StepTimer::new(
+                        std::time::Duration::from_secs(10 * 60),
+                        Some("rest".to_owned())
+                    ),
+                    StepTimer::new(std::time::Duration::from_secs(20 * 60), None),
+                ]
+            );
+            assert_eq!(step.instructions, "Knead the dough then rest it.");
+        }
+        err => assert!(false, "{:?}", err),
+    }
+}
+
+#[test]
+fn test_single_step_invalid_timer() {
+    let step = "step:
+
+timer: soon
+1 cup flour
+
+Knead the dough.";
+
+    match parse::step(StrIter::new(step)) {
+        ParseResult::Abort(e) => {
+            assert_eq!(e.get_msg(), "Invalid timer duration");
+        }
+        other => assert!(false, "{:?}", other),
+    }
+}
+
+#[test]
+fn test_recipe_times() {
+    let recipe = "title: gnocchi
+
+step: 10 min
+
+1 cup flour
+
+Make the dough.
+
+step: 5 min
+
+timer: 3 min boil
+timer: 2 min
+1 tsp salt
+
+Boil the gnocchi.
+
+step:
+
+timer: 4 min
+1 tbsp butter
+
+Brown the gnocchi in the butter.";
+    match parse::as_recipe(recipe) {
+        Ok(recipe) => {
+            // The boiling step's timers are part of its 5 minutes so only the
+            // last step's timer is cooking time.
+            assert_eq!(recipe.prep_time(), std::time::Duration::from_secs(15 * 60));
+            assert_eq!(recipe.cook_time(), std::time::Duration::from_secs(4 * 60));
+            assert_eq!(recipe.total_time(), std::time::Duration::from_secs(19 * 60));
+        }
+        Err(e) => assert!(false, "{:?}", e),
+    }
+}
+
+#[test]
+fn test_recipe_times_front_matter_prep_time_wins() {
+    let recipe = Recipe::new("toast", None)
+        .with_metadata(RecipeMetadata {
+            prep_time: Some(std::time::Duration::from_secs(60)),
+            ..RecipeMetadata::default()
+        })
+        .with_steps(vec![Step::new(
+            Some(std::time::Duration::from_secs(600)),
+            "toast it",
+        )]);
+    // The step's duration isn't part of the prep time anymore so it is
+    // counted as cooking time instead of being dropped.
+    assert_eq!(recipe.prep_time(), std::time::Duration::from_secs(60));
+    assert_eq!(recipe.cook_time(), std::time::Duration::from_secs(600));
+    assert_eq!(recipe.total_time(), std::time::Duration::from_secs(660));
+}
+
+#[test]
+fn test_recipe_times_front_matter_prep_time_with_step_durations() {
+    let recipe = "---
+prep_time: 10 min
+---
+title: gnocchi
+
+step: 5 min
+
+timer: 3 min boil
+1 tsp salt
+
+Boil the gnocchi.
+
+step:
+
+timer: 4 min
+1 tbsp butter
+
+Brown the gnocchi in the butter.";
+    match parse::as_recipe(recipe) {
+        Ok(recipe) => {
+            assert_eq!(recipe.prep_time(), std::time::Duration::from_secs(10 * 60));
+            assert_eq!(recipe.cook_time(), std::time::Duration::from_secs(9 * 60));
+            assert_eq!(recipe.total_time(), std::time::Duration::from_secs(19 * 60));
+        }
+        Err(e) => assert!(false, "{:?}", e),
+    }
+}
+
+#[test]
+fn test_temperature_conversion() {
+    assert_eq!(
+        Temperature::Fahrenheit(375).into_celsius(),
+        Temperature::Celsius(191)
+    );
+    assert_eq!(
+        Temperature::Celsius(200).into_fahrenheit(),
+        Temperature::Fahrenheit(392)
+    );
+    assert_eq!(
+        Temperature::Celsius(-5).into_fahrenheit(),
+        Temperature::Fahrenheit(23)
+    );
+    assert_eq!(format!("{}", Temperature::Fahrenheit(350)), "350°F");
+}
+
+#[test]
+fn test_temperature_parse() {
+    for (input, expected) in vec![
+        ("375F", Temperature::Fahrenheit(375)),
+        ("375 F", Temperature::Fahrenheit(375)),
+        ("190°C", Temperature::Celsius(190)),
+        ("190 °c", Temperature::Celsius(190)),
+    ] {
+        match parse::temperature(StrIter::new(input)) {
+            ParseResult::Complete(_, temp) => assert_eq!(temp, expected),
+            err => assert!(false, "{:?}", err),
+        }
+    }
+}
+
+#[test]
+fn test_find_temperatures_in_text() {
+    let text = "Preheat to 375F. Add 2 C of milk then bake at 200 °C for 4 min.";
+    let found = parse::find_temperatures(text);
+    assert_eq!(found.len(), 2);
+    assert_eq!(&text[found[0].0.clone()], "375F");
+    assert_eq!(found[0].1, Temperature::Fahrenheit(375));
+    assert_eq!(&text[found[1].0.clone()], "200 °C");
+    assert_eq!(found[1].1, Temperature::Celsius(200));
+    assert_eq!(
+        parse::convert_temperatures(text, true),
+        "Preheat to 191°C. Add 2 C of milk then bake at 200°C for 4 min."
+    );
+}
+
+#[test]
+fn test_temperature_parsers_agree() {
+    for (input, expected) in vec![
+        ("375F", Some(Temperature::Fahrenheit(375))),
+        ("375 F", Some(Temperature::Fahrenheit(375))),
+        ("375°F", Some(Temperature::Fahrenheit(375))),
+        ("375 °f", Some(Temperature::Fahrenheit(375))),
+        ("190C", Some(Temperature::Celsius(190))),
+        ("190 °C", Some(Temperature::Celsius(190))),
+        ("190°c", Some(Temperature::Celsius(190))),
+        ("2 C", None),
+        ("2c", None),
+        ("375f", None),
+    ] {
+        let step = format!("step:\n\ntemp: {}\n1 cup flour\n\nBake.", input);
+        let parsed = match parse::step(StrIter::new(&step)) {
+            ParseResult::Complete(_, step) => step.temperatures.first().cloned(),
+            _ => None,
+        };
+        let found = parse::find_temperatures(input)
+            .into_iter()
+            .find(|(range, _)| *range == (0..input.len()))
+            .map(|(_, temp)| temp);
+        assert_eq!(parsed, expected, "temp: {:?}", input);
+        assert_eq!(found, expected, "find_temperatures({:?})", input);
+    }
+}
+
+#[test]
+fn test_single_step_with_temperature() {
+    let step = "step:
+
+temp: 425F
+1 cup flour
+
+Bake until golden.";
+
+    match parse::step(StrIter::new(step)) {
+        ParseResult::Complete(_, step) => {
+            assert_eq!(step.temperatures, vec![Temperature::Fahrenheit(425)]);
+        }
+        err => assert!(false, "{:?}", err),
+    }
+}
+
+#[test]
+fn test_as_quantity() {
+    assert_eq!(parse::as_quantity("2"), Ok(Quantity::whole(2)));
+    assert_eq!(parse::as_quantity("1/2"), Ok(Quantity::frac(0, 1, 2)));
+    assert_eq!(parse::as_quantity("1 1/2"), Ok(Quantity::frac(1, 1, 2)));
+    assert!(parse::as_quantity("2 cups").is_err());
+}
+
+#[test]
+fn test_recipe_scale() {
+    let recipe = Recipe::new("pancakes", None).with_steps(vec![Step::new(None, "mix")
+        .with_ingredients(vec![
+            Ingredient::new("flour", None, Measure::cup(1.into())),
+            Ingredient::new("egg", None, Measure::count(2)),
+        ])]);
+    let scaled = recipe.scale(Ratio::new(3, 2).into());
+    assert_eq!(scaled.title, recipe.title);
+    assert_eq!(
+        scaled.steps[0].ingredients[0].amt,
+        Measure::cup(Quantity::frac(1, 1, 2))
+    );
+    assert_eq!(scaled.steps[0].ingredients[1].amt, Measure::count(3));
+}
+
+#[test]
+fn test_recipe_serving_scale() {
+    let mut recipe = Recipe::new("pancakes", None);
+    assert_eq!(recipe.serving_scale(6), Quantity::whole(1));
+    recipe.metadata.serves = Some(4);
+    assert_eq!(recipe.serving_scale(6), Quantity::frac(1, 1, 2));
+    assert_eq!(recipe.serving_scale(2), Ratio::new(1, 2).into());
+    assert_eq!(recipe.serving_scale(4), Quantity::whole(1));
+    assert_eq!(recipe.serving_scale(0), Quantity::whole(1));
+}
+
+#[test]
+fn test_slugify() {
+    assert_eq!(slugify("Meat Loaf"), "meat_loaf");
+    assert_eq!(slugify("  Mom's Chili -- Extra Hot!  "), "mom_s_chili_extra_hot");
+    assert_eq!(slugify("../etc/passwd"), "etc_passwd");
+    assert_eq!(slugify("Crème Brûlée"), "crème_brûlée");
+    assert_eq!(slugify("!!!"), "");
+}
+
+#[test]
+fn test_slugify_normalizes_unicode() {
+    let precomposed = "Caf\u{e9} au lait";
+    let decomposed = "Cafe\u{301} au lait";
+    assert_ne!(precomposed, decomposed);
+    assert_eq!(slugify(precomposed), "caf\u{e9}_au_lait");
+    assert_eq!(slugify(decomposed), slugify(precomposed));
+}
+
+#[test]
+fn test_recipe_without_description() {
+    let recipe = "title: toast
+
+step:
+
+1 slice bread
+
+Toast it.";
+    match parse::as_recipe(recipe) {
+        Ok(recipe) => {
+            assert_eq!(recipe.title, "toast");
+            assert_eq!(recipe.desc, None);
+            assert_eq!(recipe.steps.len(), 1);
+        }
+        Err(e) => assert!(false, "{:?}", e),
+    }
+}
+
+#[test]
+fn test_serialize_duration() {
+    use std::time::Duration;
+    assert_eq!(
+        serialize::duration(&Duration::from_secs(2 * 60 * 60)),
+        "2 hr"
+    );
+    assert_eq!(serialize::duration(&Duration::from_secs(90 * 60)), "90 min");
+    assert_eq!(serialize::duration(&Duration::from_secs(90)), "90 sec");
+}
+
+#[test]
+fn test_serialize_example_recipes_round_trip() {
+    for text in [
+        include_str!("../../examples/recipes.txt/meatloaf.txt"),
+        include_str!("../../examples/recipes.txt/cornbread_dressing.txt"),
+    ] {
+        let recipe = parse::as_recipe(text).expect("example recipe parses");
+        let serialized = serialize::as_text(&recipe);
+        assert_eq!(
+            parse::as_recipe(&serialized).as_ref(),
+            Ok(&recipe),
+            "{}",
+            serialized
+        );
+        // Serializing is stable once the text is canonical.
+        assert_eq!(
+            serialize::as_text(&parse::as_recipe(&serialized).unwrap()),
+            serialized
+        );
+    }
+}
+
+#[test]
+fn test_update_tags() {
+    let text = "---
+serves: 4
+tags: Dinner, easy
+---
+title: toast
+
+step:
+
+1 slice bread
+
+Toast it.";
+    let updated = serialize::update_tags(text, |mut tags| {
+        tags.remove("easy");
+        tags.insert("breakfast".to_owned());
+        tags
+    });
+    assert_eq!(
+        updated,
+        "---
+serves: 4
+tags: breakfast, dinner
+---
+title: toast
+
+step:
+
+1 slice bread
+
+Toast it."
+    );
+    let recipe = parse::as_recipe(&updated).expect("updated recipe parses");
+    assert_eq!(
+        recipe.metadata.tags,
+        BTreeSet::from(["breakfast".to_owned(), "dinner".to_owned()])
+    );
+
+    // Front matter is added when there wasn't any and removed when it is empty.
+    let plain = "title: toast
+
+step:
+
+1 slice bread
+
+Toast it.";
+    let tagged = serialize::update_tags(plain, |_| BTreeSet::from(["quick".to_owned()]));
+    assert_eq!(tagged, format!("---\ntags: quick\n---\n{}", plain));
+    assert_eq!(serialize::update_tags(&tagged, |_| BTreeSet::new()), plain);
+}
+
+#[test]
+fn test_format_recipe() {
+    let messy = "title: toast   
+
+Crunchy.   
+
+step: 90 sec
+
+3/2 cups flour
+1 teaspoon salt
+2 g butter (melted)
+
+Toast it.   
+Eat it.
+
+
+";
+    let expected = "title: toast
+
+Crunchy.
+
+step: 90 sec
+
+1 1/2 cups flour
+1 tsp salt
+2 grams butter (melted)
+
+Toast it.
+Eat it.
+";
+    assert_eq!(serialize::format_recipe(messy), Ok(expected.to_owned()));
+    // Formatting is idempotent.
+    assert_eq!(serialize::format_recipe(expected), Ok(expected.to_owned()));
+}
+
+#[test]
+fn test_format_recipe_rejects_unparsed_text() {
+    let recipe = "title: toast
+
+step:
+
+1 slice bread
+
+Toast it.
+
+Eat it.
+";
+    assert!(parse::as_recipe(recipe).is_ok());
+    assert!(serialize::format_recipe(recipe).is_err());
+}
+
+#[test]
+fn test_cooklang_import() {
+    let text = ">> servings: 4
+>> tags: Breakfast, easy
+[- A block comment -]
+Crack @eggs{3} into a #bowl{} and whisk with @milk{1/2%cup}.
+
+Melt @butter{1.5%tbsp} in a #pan{}. -- a comment
+Add the eggs and cook for ~{3%minutes} with @black pepper{}(ground).
+";
+    let recipe = cooklang::as_recipe(text, "scrambled eggs").expect("cooklang parses");
+    assert_eq!(recipe.title, "scrambled eggs");
+    assert_eq!(recipe.metadata.serves, Some(4));
+    assert!(recipe.metadata.tags.contains("breakfast"));
+    assert_eq!(recipe.steps.len(), 2);
+    assert_eq!(
+        recipe.steps[0].ingredients,
+        vec![
+            Ingredient::new("eggs", None, Measure::count(3)),
+            Ingredient::new("milk", None, Measure::cup(Ratio::new(1, 2).into())),
+        ]
+    );
+    assert_eq!(
+        recipe.steps[0].instructions,
+        "Crack eggs into a bowl and whisk with milk."
+    );
+    assert_eq!(
+        recipe.steps[1].ingredients[0],
+        Ingredient::new("butter", None, Measure::tbsp(Ratio::new(3, 2).into()))
+    );
+    assert_eq!(
+        recipe.steps[1].ingredients[1],
+        Ingredient::new("black pepper", Some("ground".to_owned()), Measure::count(1))
+    );
+    assert_eq!(
+        recipe.steps[1].timers,
+        vec![StepTimer::new(std::time::Duration::from_secs(180), None)]
+    );
+    assert_eq!(
+        recipe.steps[1].instructions,
+        "Melt butter in a pan.\nAdd the eggs and cook for 3 minutes with black pepper."
+    );
+    // The imported recipe can be stored in our own format.
+    let serialized = serialize::as_text(&recipe);
+    assert_eq!(parse::as_recipe(&serialized), Ok(recipe));
+}
+
+#[test]
+fn test_cooklang_unknown_units_become_part_of_the_name() {
+    let recipe = cooklang::as_recipe("Mince @garlic{2%cloves}.", "garlic").unwrap();
+    assert_eq!(
+        recipe.steps[0].ingredients,
+        vec![Ingredient::new("cloves garlic", None, Measure::count(2))]
+    );
+}
+
+#[test]
+fn test_cooklang_rejects_bad_decimals() {
+    // The fraction is cut off by characters, not bytes.
+    assert!(cooklang::as_recipe("Add @x{1.aaaaa\u{e9}}.", "x").is_err());
+    assert!(cooklang::as_recipe("Add @x{5000.123456}.", "x").is_err());
+    let recipe = cooklang::as_recipe("Add @x{0.1234567}.", "x").unwrap();
+    assert_eq!(
+        recipe.steps[0].ingredients[0].amt,
+        Count(Quantity::from(Ratio::new(123456, 1000000)))
+    );
+}
+
+#[test]
+fn test_cooklang_rejects_durations_that_overflow() {
+    assert!(cooklang::as_recipe("Add @x{1}. Wait ~{100000000%hours}.", "x").is_err());
+    assert!(cooklang::as_recipe("Add @x{4294967295 1/2}.", "x").is_err());
+}
+
+#[test]
+fn test_cooklang_requires_ingredients() {
+    assert!(cooklang::as_recipe("Just some text.", "nothing").is_err());
+}
+
+#[test]
+fn test_cooklang_round_trip() {
+    let text = "---
+serves: 2
+prep_time: 10 min
+tags: dinner
+source: https://example.com/gnocchi
+---
+title: gnocchi
+
+Little dumplings.
+
+step:
+
+image: dough.png
+temp: 400°F
+1 1/2 cups flour (sifted)
+2 eggs
+250 ml water
+
+Make the dough.
+
+step:
+
+timer: 3 min boil
+1 tsp salt
+
+Boil the gnocchi.";
+    let recipe = parse::as_recipe(text).unwrap();
+    let imported = cooklang::as_recipe(&cooklang::as_text(&recipe), "").unwrap();
+    assert_eq!(imported, recipe);
+}
+
+#[test]
+fn test_prep_list() {
+    let recipe = parse::as_recipe(
+        "title: soup
+
+step:
+
+1 onion (diced)
+2 clove garlic (minced)
+1 cup stock
+
+Saute the onion and garlic.
+
+step:
+
+1 onion (diced)
+1 recipe:croutons (crushed)
+
+Add the stock and the rest of the onion.",
+    )
+    .unwrap();
+    let prep = recipe
+        .prep_list()
+        .into_iter()
+        .map(|i| i.to_string())
+        .collect::<Vec<String>>();
+    assert_eq!(prep, vec!["2 clove garlic (minced)", "2 onion (diced)"]);
+}
+
+#[test]
+fn test_book_source_round_trip() {
+    let text = "---
+source: The Joy of Cooking, p. 212
+---
+title: pancakes
+
+step:
+
+1 cup flour
+
+Mix.";
+    let recipe = parse::as_recipe(text).unwrap();
+    assert_eq!(
+        recipe.metadata.source.as_deref(),
+        Some("The Joy of Cooking, p. 212")
+    );
+    assert_eq!(recipe.metadata.source_url(), None);
+    assert_eq!(
+        parse::as_recipe(&serialize::as_text(&recipe)).unwrap(),
+        recipe
+    );
+    assert_eq!(
+        cooklang::as_recipe(&cooklang::as_text(&recipe), "").unwrap(),
+        recipe
+    );
+}
+
+#[test]
+fn test_source_url() {
+    let mut metadata = RecipeMetadata::default();
+    assert_eq!(metadata.source_url(), None);
+    metadata.source = Some("https://example.com/pancakes".to_owned());
+    assert_eq!(metadata.source_url(), Some("https://example.com/pancakes"));
+    metadata.source = Some("javascript:alert(1)".to_owned());
+    assert_eq!(metadata.source_url(), None);
+}
+
+const WORDS: &[&str] = &[
+    "apple", "butter", "flour", "salt", "onion", "pepper", "rice", "carrot", "tomato", "basil",
+];
+
+fn word() -> impl Strategy<Value = String> {
+    prop::sample::select(WORDS).prop_map(|w| w.to_owned())
+}
+
+fn sentence() -> impl Strategy<Value = String> {
+    prop::collection::vec(word(), 1..8).prop_map(|words| format!("{}.", words.join(" ")))
+}
+
+fn paragraph() -> impl Strategy<Value = String> {
+    prop::collection::vec(sentence(), 1..4).prop_map(|lines| lines.join("\n"))
+}
+
+fn duration() -> impl Strategy<Value = std::time::Duration> {
+    prop_oneof![
+        (1u64..10).prop_map(|h| h * 60 * 60),
+        (1u64..600).prop_map(|m| m * 60),
+        1u64..3600,
+    ]
+    .prop_map(std::time::Duration::from_secs)
+}
+
+fn quantity() -> impl Strategy<Value = Quantity> {
+    (1u32..20, 0u32..8, prop::sample::select(vec![2u32, 3, 4, 8])).prop_map(
+        |(whole, numer, denom)| {
+            if numer == 0 {
+                Quantity::Whole(whole)
+            } else {
+                Quantity::frac(whole, numer % denom, denom)
+            }
+        },
+    )
+}
+
+fn measure() -> impl Strategy<Value = Measure> {
+    prop_oneof![
+        quantity().prop_map(Measure::tsp),
+        quantity().prop_map(Measure::tbsp),
+        quantity().prop_map(Measure::cup),
+        quantity().prop_map(Measure::ml),
+        quantity().prop_map(Measure::gram),
+        quantity().prop_map(Measure::lb),
+        quantity().prop_map(Measure::oz),
+        quantity().prop_map(Measure::Count),
+    ]
+}
+
+fn ingredient() -> impl Strategy<Value = Ingredient> {
+    (
+        word(),
+        prop::option::of(prop::sample::select(vec!["chopped", "diced", "sifted"])),
+        measure(),
+    )
+        .prop_map(|(name, form, amt)| Ingredient::new(name, form.map(|f| f.to_owned()), amt))
+}
+
+fn temperature() -> impl Strategy<Value = Temperature> {
+    prop_oneof![
+        (0i32..500).prop_map(Temperature::Fahrenheit),
+        (0i32..260).prop_map(Temperature::Celsius),
+    ]
+}
+
+fn step() -> impl Strategy<Value = Step> {
+    (
+        prop::option::of(duration()),
+        paragraph(),
+        prop::collection::vec(ingredient(), 1..6),
+        prop::collection::vec(word().prop_map(|w| format!("{}.png", w)), 0..2),
+        prop::collection::vec(
+            (duration(), prop::option::of(word())).prop_map(|(d, l)| StepTimer::new(d, l)),
+            0..3,
+        ),
+        prop::collection::vec(temperature(), 0..2),
+    )
+        .prop_map(
+            |(prep_time, instructions, ingredients, images, timers, temps)| {
+                Step::new(prep_time, instructions)
+                    .with_ingredients(ingredients)
+                    .with_images(images)
+                    .with_timers(timers)
+                    .with_temperatures(temps)
+            },
+        )
+}
+
+fn metadata() -> impl Strategy<Value = RecipeMetadata> {
+    (
+        prop::option::of(1u32..12),
+        prop::option::of(duration()),
+        prop::collection::btree_set(word(), 0..4),
+        prop::option::of(prop::sample::select(vec![
+            "https://example.com/recipe",
+            "grandma",
+        ])),
+        prop::option::of(prop::sample::select(MealType::ALL.to_vec())),
+    )
+        .prop_map(|(serves, prep_time, tags, source, meal)| RecipeMetadata {
+            serves,
+            prep_time,
+            tags,
+            source: source.map(|s| s.to_owned()),
+            meal,
+        })
+}
+
+fn recipe() -> impl Strategy<Value = Recipe> {
+    (
+        prop::collection::vec(word(), 1..4).prop_map(|words| words.join(" ")),
+        prop::option::of(paragraph()),
+        metadata(),
+        prop::collection::vec(step(), 1..5),
+    )
+        .prop_map(|(title, desc, metadata, steps)| {
+            Recipe::new(title, desc)
+                .with_metadata(metadata)
+                .with_steps(steps)
+        })
+}
+
+proptest! {
+    #[test]
+    fn test_recipe_serialize_round_trip(recipe in recipe()) {
+        let serialized = serialize::as_text(&recipe);
+        prop_assert_eq!(parse::as_recipe(&serialized), Ok(recipe), "{}", serialized);
+    }
+
+    #[test]
+    fn test_ingredient_list_round_trip(ingredients in prop::collection::vec(ingredient(), 1..10)) {
+        let serialized = ingredients
+            .iter()
+            .map(|i| i.to_string())
+            .collect::<Vec<String>>()
+            .join("\n");
+        prop_assert_eq!(parse::as_ingredient_list(&serialized), Ok(ingredients), "{}", serialized);
+    }
+}
+
+#[test]
+fn test_plain_text_draft() {
+    let text = "Grandma's Pancakes
+The fluffy ones.
+
+• 1½ cups flour
+• 2 eggs
+- 1 cup milk
+
+1. Whisk everything together
+until smooth.
+2. Cook on a hot griddle.
+";
+    let recipe = plain::as_recipe(text, "untitled");
+    assert_eq!(recipe.title, "Grandma's Pancakes");
+    assert_eq!(recipe.desc.as_deref(), Some("The fluffy ones."));
+    assert_eq!(recipe.steps.len(), 1);
+    assert_eq!(recipe.steps[0].ingredients.len(), 3);
+    assert_eq!(
+        recipe.steps[0].ingredients[0].amt,
+        Measure::cup(Ratio::new(3, 2).into())
+    );
+    assert_eq!(
+        recipe.steps[0].instructions,
+        "1. Whisk everything together\nuntil smooth.\n2. Cook on a hot griddle."
+    );
+    let draft = plain::as_draft(text, "untitled");
+    assert_eq!(parse::as_recipe(&draft), Ok(recipe));
+}
+
+#[test]
+fn test_plain_text_draft_always_parses() {
+    for text in [
+        "",
+        "Just a title",
+        "2 eggs\n1 cup milk",
+        "title: Toast\nToast the bread.\n1 slice bread\nButter it.\n1 tbsp butter",
+    ] {
+        let draft = plain::as_draft(text, "untitled");
+        assert!(parse::as_recipe(&draft).is_ok(), "draft for {:?}", text);
+    }
+    let recipe = plain::as_recipe("2 eggs\nScramble them.", "eggs");
+    assert_eq!(recipe.title, "eggs");
+    let recipe = plain::as_recipe(
+        "title: Toast\n1 slice bread\nToast it.\n1 tbsp butter\nButter it.",
+        "x",
+    );
+    assert_eq!(recipe.title, "Toast");
+    assert_eq!(recipe.steps.len(), 2);
+}
+
+#[test]
+fn test_density_conversion() {
+    let densities = DensityTable::default();
+    assert_eq!(
+        densities.to_weight("flour", &Measure::cup(2.into())),
+        Some(Measure::gram(240.into()))
+    );
+    // Longer names fall back to the ingredient they end with.
+    assert_eq!(
+        densities.to_weight("unbleached all-purpose flour", &Measure::cup(1.into())),
+        Some(Measure::gram(120.into()))
+    );
+    assert_eq!(
+        densities.to_volume("sugar", &Measure::gram(100.into())),
+        Some(Measure::cup(Ratio::new(1, 2).into()))
+    );
+    assert_eq!(densities.to_weight("gravel", &Measure::cup(1.into())), None);
+    assert_eq!(densities.to_weight("flour", &Measure::count(1)), None);
+    let mut overrides = BTreeMap::new();
+    overrides.insert("flour".to_owned(), 130);
+    let densities = densities.with_overrides(overrides.iter());
+    assert_eq!(
+        densities.to_weight("flour", &Measure::cup(1.into())),
+        Some(Measure::gram(130.into()))
+    );
+}
+
+#[test]
+fn test_accumulator_merges_volumes_with_densities() {
+    let recipe = parse::as_recipe(
+        "title: bread
+
+step:
+
+1 cup flour
+1 cup milk
+
+Mix.
+
+step:
+
+100 g flour
+
+Knead.",
+    )
+    .expect("recipe parses");
+    let mut acc = IngredientAccumulator::new().with_densities(DensityTable::default());
+    acc.accumulate_from(&recipe);
+    let ingredients = acc.ingredients();
+    let flour = ingredients
+        .iter()
+        .filter(|(k, _)| k.name() == "flour")
+        .collect::<Vec<_>>();
+    assert_eq!(flour.len(), 1);
+    assert_eq!(flour[0].1 .0.amt, Measure::gram(220.into()));
+    // Milk has no weight in the plan so it stays a volume.
+    assert!(ingredients
+        .values()
+        .any(|(i, _)| i.name == "milk" && i.amt == Measure::cup(1.into())));
+}
+
+/// Render a shopping list one item per line. Crossed off items start with an
+/// `x`.
+fn render_shopping_list(list: &BTreeMap<IngredientKey, shopping::ShoppingItem>) -> String {
+    let mut out = String::new();
+    for item in list.values() {
+        let i = &item.ingredient;
+        out.push_str(if item.filtered { "x " } else { "- " });
+        out.push_str(&format!("{} {}", i.amt.normalize(), i.name));
+        if let Some(form) = &i.form {
+            out.push_str(&format!(" ({})", form));
+        }
+        let recipes = item.recipes.iter().cloned().collect::<Vec<String>>();
+        out.push_str(&format!(" <- {}\n", recipes.join(", ")));
+    }
+    out
+}
+
+/// Compare a rendered shopping list with the golden file of the same name in
+/// `testdata/shopping`. Set `UPDATE_GOLDEN` to rewrite the golden files.
+fn assert_shopping_golden(name: &str, list: &BTreeMap<IngredientKey, shopping::ShoppingItem>) {
+    let actual = render_shopping_list(list);
+    let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("testdata/shopping")
+        .join(format!("{}.golden", name));
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        std::fs::write(&path, &actual).expect("Unable to write golden file");
+        return;
+    }
+    let expected = std::fs::read_to_string(&path).expect("Unable to read golden file");
+    assert_eq!(
+        expected,
+        actual,
+        "Shopping list differs from {}",
+        path.display()
+    );
+}
+
+fn shopping_recipes() -> BTreeMap<String, Recipe> {
+    let mut pancakes = Recipe::new("pancakes", None).with_steps(vec![Step::new(None, "fry")
+        .with_ingredients(vec![
+            Ingredient::new("flour", None, Measure::cup(2.into())),
+            Ingredient::new("milk", None, Measure::cup(1.into())),
+            Ingredient::new("egg", None, Measure::count(2)),
+        ])]);
+    pancakes.metadata.serves = Some(2);
+    let omelet = Recipe::new("omelet", None).with_steps(vec![Step::new(None, "fold")
+        .with_ingredients(vec![
+            Ingredient::new("egg", None, Measure::count(3)),
+            Ingredient::new("milk", None, Measure::tbsp(2.into())),
+            Ingredient::new("butter", None, Measure::tbsp(1.into())),
+            Ingredient::new("onion", Some("chopped".to_owned()), Measure::count(1)),
+        ])]);
+    let chili = Recipe::new("chili", None).with_steps(vec![Step::new(None, "simmer")
+        .with_ingredients(vec![Ingredient::new("beans", None, Measure::cup(2.into()))])]);
+    let dough = Recipe::new("pizza dough", None).with_steps(vec![Step::new(None, "knead")
+        .with_ingredients(vec![
+            Ingredient::new("flour", None, Measure::cup(2.into())),
+            Ingredient::new("salt", None, Measure::tsp(1.into())),
+        ])]);
+    let pizza = Recipe::new("pizza", None).with_steps(vec![Step::new(None, "bake")
+        .with_ingredients(vec![
+            Ingredient::new("recipe:pizza_dough", None, Measure::count(2)),
+            Ingredient::new("recipe:missing", None, Measure::count(1)),
+            Ingredient::new("salt", None, Measure::tsp(1.into())),
+        ])]);
+    let bread = Recipe::new("bread", None).with_steps(vec![
+        Step::new(None, "mix").with_ingredients(vec![
+            Ingredient::new("flour", None, Measure::cup(1.into())),
+            Ingredient::new("milk", None, Measure::cup(1.into())),
+        ]),
+        Step::new(None, "knead").with_ingredients(vec![Ingredient::new(
+            "flour",
+            None,
+            Measure::gram(100.into()),
+        )]),
+    ]);
+    BTreeMap::from([
+        ("pancakes".to_owned(), pancakes),
+        ("omelet".to_owned(), omelet),
+        ("chili".to_owned(), chili),
+        ("pizza_dough".to_owned(), dough),
+        ("pizza".to_owned(), pizza),
+        ("bread".to_owned(), bread),
+    ])
+}
+
+fn plan_counts(counts: &[(&str, usize)]) -> BTreeMap<String, usize> {
+    counts
+        .iter()
+        .map(|(id, count)| (id.to_string(), *count))
+        .collect()
+}
+
+#[test]
+fn test_shopping_list_combines_recipes() {
+    let plan = shopping::ShoppingPlan {
+        recipe_counts: plan_counts(&[("pancakes", 1), ("omelet", 2)]),
+        ..Default::default()
+    };
+    let list = shopping::shopping_list(&plan, &shopping_recipes());
+    assert_shopping_golden("combines_recipes", &list);
+}
+
+#[test]
+fn test_shopping_list_scales_for_household() {
+    let plan = shopping::ShoppingPlan {
+        recipe_counts: plan_counts(&[
+            ("chili", 1),
+            ("missing", 1),
+            ("omelet", 1),
+            ("pancakes", 1),
+            ("bread", 0),
+        ]),
+        freezer_meals: BTreeSet::from(["chili".to_owned()]),
+        household_size: Some(4),
+        ..Default::default()
+    };
+    let list = shopping::shopping_list(&plan, &shopping_recipes());
+    assert_shopping_golden("household", &list);
+}
+
+#[test]
+fn test_shopping_list_staples_and_filters() {
+    let plan = shopping::ShoppingPlan {
+        recipe_counts: plan_counts(&[("pancakes", 1)]),
+        staples: Some(BTreeSet::from([
+            Ingredient::new("egg", None, Measure::count(12)),
+            Ingredient::new("salt", None, Measure::tsp(1.into())),
+        ])),
+        filtered_ingredients: BTreeSet::from([IngredientKey::new(
+            "flour".to_owned(),
+            None,
+            "Volume".to_owned(),
+        )]),
+        ..Default::default()
+    };
+    let list = shopping::shopping_list(&plan, &shopping_recipes());
+    assert_shopping_golden("staples_and_filters", &list);
+}
+
+#[test]
+fn test_shopping_list_expands_references() {
+    let plan = shopping::ShoppingPlan {
+        recipe_counts: plan_counts(&[("pizza", 1)]),
+        ..Default::default()
+    };
+    let list = shopping::shopping_list(&plan, &shopping_recipes());
+    assert_shopping_golden("references", &list);
+}
+
+#[test]
+fn test_shopping_list_merges_with_densities() {
+    let plan = shopping::ShoppingPlan {
+        recipe_counts: plan_counts(&[("bread", 1)]),
+        densities: Some(DensityTable::default()),
+        ..Default::default()
+    };
+    let list = shopping::shopping_list(&plan, &shopping_recipes());
+    assert_shopping_golden("densities", &list);
+}
+
+fn rule_date(month: u32, day: u32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(2023, month, day).unwrap()
+}
+
+fn rule_tags(tags: &[&str]) -> BTreeSet<String> {
+    tags.iter().map(|t| t.to_string()).collect()
+}
+
+#[test]
+fn test_season_rule_wraps_around_the_year() {
+    let rule = rules::PlanRule::Season {
+        tag: "Soup".to_owned(),
+        from_month: 10,
+        to_month: 3,
+    };
+    let soup = rule_tags(&["soup"]);
+    let history = BTreeMap::new();
+    for month in [10, 11, 12, 1, 3] {
+        assert!(rule.allows(rule_date(month, 15), "chili", &soup, &history));
+    }
+    for month in [4, 6, 9] {
+        assert!(!rule.allows(rule_date(month, 15), "chili", &soup, &history));
+    }
+    // Recipes without the tag can be planned any time.
+    assert!(rule.allows(rule_date(6, 15), "salad", &rule_tags(&["salad"]), &history));
+
+    let summer = rules::PlanRule::Season {
+        tag: "grill".to_owned(),
+        from_month: 5,
+        to_month: 8,
+    };
+    let grill = rule_tags(&["grill"]);
+    assert!(summer.allows(rule_date(5, 1), "burgers", &grill, &history));
+    assert!(!summer.allows(rule_date(9, 1), "burgers", &grill, &history));
+}
+
+#[test]
+fn test_no_repeat_rule_only_looks_back_its_window() {
+    let rule = rules::PlanRule::NoRepeat { weeks: 2 };
+    assert_eq!(rule.lookback_days(), 14);
+    let date = rule_date(10, 16);
+    let tags = BTreeSet::new();
+    let history = BTreeMap::from([(rule_date(10, 2), vec!["chili".to_owned()])]);
+    assert!(!rule.allows(date, "chili", &tags, &history));
+    assert!(rule.allows(date, "pancakes", &tags, &history));
+    // A day past the window is fine again.
+    assert!(rule.allows(rule_date(10, 17), "chili", &tags, &history));
+    // Plans on or after the date being checked don't count.
+    let later = BTreeMap::from([(date, vec!["chili".to_owned()])]);
+    assert!(rule.allows(date, "chili", &tags, &later));
+}
+
+#[test]
+fn test_check_plan() {
+    let stored = vec![
+        rules::StoredPlanRule {
+            id: 1,
+            rule: rules::PlanRule::AtLeast {
+                tag: "Fish".to_owned(),
+                count: 2,
+            },
+        },
+        rules::StoredPlanRule {
+            id: 2,
+            rule: rules::PlanRule::NoRepeat { weeks: 1 },
+        },
+    ];
+    let date = rule_date(10, 16);
+    let tags = BTreeMap::from([
+        ("salmon".to_owned(), rule_tags(&["fish"])),
+        ("tacos".to_owned(), rule_tags(&["fish", "mexican"])),
+    ]);
+    let history = BTreeMap::from([(rule_date(10, 12), vec!["chili".to_owned()])]);
+
+    // Counts add up and recipes with a count of zero don't count.
+    let plan = vec![
+        ("salmon".to_owned(), 1),
+        ("tacos".to_owned(), 1),
+        ("chili".to_owned(), 0),
+    ];
+    assert!(rules::check_plan(&stored, date, &plan, &tags, &history).is_empty());
+    let plan = vec![("salmon".to_owned(), 2)];
+    assert!(rules::check_plan(&stored, date, &plan, &tags, &history).is_empty());
+
+    let plan = vec![("tacos".to_owned(), 1), ("chili".to_owned(), 1)];
+    let violations = rules::check_plan(&stored, date, &plan, &tags, &history);
+    assert_eq!(
+        violations,
+        vec![
+            rules::RuleViolation {
+                rule_id: 1,
+                recipe_id: None,
+                message: "Fish at least 2 times a plan (only 1 planned)".to_owned(),
+            },
+            rules::RuleViolation {
+                rule_id: 2,
+                recipe_id: Some("chili".to_owned()),
+                message: "chili: No repeats within 1 weeks".to_owned(),
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_plan_rule_validation() {
+    let invalid = |rule: rules::PlanRule| rule.validate().err().map(|err| err.field);
+    assert_eq!(
+        invalid(rules::PlanRule::Season {
+            tag: "soup".to_owned(),
+            from_month: 0,
+            to_month: 3,
+        }),
+        Some("month".to_owned())
+    );
+    assert_eq!(
+        invalid(rules::PlanRule::AtLeast {
+            tag: " ".to_owned(),
+            count: 1,
+        }),
+        Some("tag".to_owned())
+    );
+    assert_eq!(
+        invalid(rules::PlanRule::NoRepeat { weeks: 0 }),
+        Some("weeks".to_owned())
+    );
+    assert_eq!(invalid(rules::PlanRule::NoRepeat { weeks: 3 }), None);
+}
@@ -99,6 +99,21 @@ impl VolumeMeasure {
         }
     }
 
+    /// Scale this measure by a `Quantity` keeping the same unit.
+    pub fn scale(&self, factor: Quantity) -> Self {
+        match self {
+            Tsp(qty) => Tsp((*qty * factor).normalize()),
+            Tbsp(qty) => Tbsp((*qty * factor).normalize()),
+            Cup(qty) => Cup((*qty * factor).normalize()),
+            Pint(qty) => Pint((*qty * factor).normalize()),
+            Qrt(qty) => Qrt((*qty * factor).normalize()),
+            Gal(qty) => Gal((*qty * factor).normalize()),
+            Floz(qty) => Floz((*qty * factor).normalize()),
+            ML(qty) => ML((*qty * factor).normalize()),
+            Ltr(qty) => Ltr((*qty * factor).normalize()),
+        }
+    }
+
     /// Convert into milliliters.
     pub fn into_ml(self) -> Self {
         ML(self.get_ml())
@@ -255,6 +270,16 @@ impl WeightMeasure {
         }
     }
 
+    /// Scale this measure by a `Quantity` keeping the same unit.
+    pub fn scale(&self, factor: Quantity) -> Self {
+        match self {
+            &Self::Gram(qty) => Self::Gram((qty * factor).normalize()),
+            &Self::Kilogram(qty) => Self::Kilogram((qty * factor).normalize()),
+            &Self::Pound(qty) => Self::Pound((qty * factor).normalize()),
+            &Self::Oz(qty) => Self::Oz((qty * factor).normalize()),
+        }
+    }
+
     pub fn into_gram(self) -> Self {
         Self::Gram(self.get_grams())
     }
@@ -424,6 +449,15 @@ impl Measure {
         }
     }
 
+    /// Scale this measure by a `Quantity` keeping the same unit.
+    pub fn scale(&self, factor: Quantity) -> Self {
+        match self {
+            Volume(vm) => Volume(vm.scale(factor)),
+            Count(qty) => Count((*qty * factor).normalize()),
+            Weight(wm) => Weight(wm.scale(factor)),
+        }
+    }
+
     pub fn normalize(&self) -> Self {
         match self {
             Volume(vm) => Volume(vm.normalize()),
@@ -431,6 +465,26 @@ impl Measure {
             Weight(wm) => Weight(wm.normalize()),
         }
     }
+
+    /// Take `used` away from this measure. Using more than there is leaves
+    /// nothing rather than a negative amount. Returns None if the measures
+    /// aren't the same type.
+    pub fn saturating_sub(&self, used: &Measure) -> Option<Self> {
+        let zero = Quantity::Whole(0);
+        match (self, used) {
+            (Volume(have), Volume(used)) if used.get_ml() >= have.get_ml() => {
+                Some(Volume(have.scale(zero)))
+            }
+            (Volume(have), Volume(used)) => Some(Volume(*have - *used)),
+            (Weight(have), Weight(used)) if used.get_grams() >= have.get_grams() => {
+                Some(Weight(have.scale(zero)))
+            }
+            (Weight(have), Weight(used)) => Some(Weight(*have - *used)),
+            (Count(have), Count(used)) if used >= have => Some(Count(zero)),
+            (Count(have), Count(used)) => Some(Count((*have - *used).normalize())),
+            _ => None,
+        }
+    }
 }
 
 impl Display for Measure {
@@ -443,6 +497,44 @@ impl Display for Measure {
     }
 }
 
+/// Temperatures for cooking in either Fahrenheit or Celsius.
+#[derive(Copy, Clone, Debug, PartialEq, PartialOrd, Eq, Ord)]
+pub enum Temperature {
+    Fahrenheit(i32),
+    Celsius(i32),
+}
+
+impl Temperature {
+    /// Convert into Fahrenheit rounding to the nearest degree.
+    pub fn into_fahrenheit(self) -> Self {
+        match self {
+            Temperature::Fahrenheit(_) => self,
+            Temperature::Celsius(c) => {
+                Temperature::Fahrenheit((c as f64 * 9.0 / 5.0 + 32.0).round() as i32)
+            }
+        }
+    }
+
+    /// Convert into Celsius rounding to the nearest degree.
+    pub fn into_celsius(self) -> Self {
+        match self {
+            Temperature::Celsius(_) => self,
+            Temperature::Fahrenheit(f) => {
+                Temperature::Celsius(((f - 32) as f64 * 5.0 / 9.0).round() as i32)
+            }
+        }
+    }
+}
+
+impl Display for Temperature {
+    fn fmt(&self, w: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Temperature::Fahrenheit(f) => write!(w, "{}°F", f),
+            Temperature::Celsius(c) => write!(w, "{}°C", c),
+        }
+    }
+}
+
 /// Represents a Quantity for an ingredient of a recipe.
 #[derive(Copy, Clone, Debug, Eq, Ord)]
 pub enum Quantity {
@@ -593,3 +685,137 @@ impl Display for Quantity {
         }
     }
 }
+
+/// Round a quantity to the nearest `1/denom`. Quantities that would round to
+/// zero are left alone so small amounts don't disappear.
+fn round_to(qty: Quantity, denom: u32) -> Quantity {
+    let ratio = match qty {
+        Whole(_) => return qty,
+        Frac(r) => r,
+    };
+    let rounded = (ratio * Ratio::from_integer(denom)).round() / Ratio::from_integer(denom);
+    if rounded == Ratio::from_integer(0) {
+        qty
+    } else {
+        Quantity::from(rounded)
+    }
+}
+
+/// Grams per cup for common baking ingredients. Names are in the normalized
+/// form the parser produces.
+const DEFAULT_DENSITIES: &'static [(&'static str, u32)] = &[
+    ("flour", 120),
+    ("all-purpose flour", 120),
+    ("all purpose flour", 120),
+    ("bread flour", 127),
+    ("cake flour", 114),
+    ("whole wheat flour", 113),
+    ("almond flour", 96),
+    ("rye flour", 102),
+    ("cornmeal", 138),
+    ("cornstarch", 128),
+    ("cocoa powder", 84),
+    ("rolled oat", 89),
+    ("sugar", 200),
+    ("granulated sugar", 200),
+    ("brown sugar", 213),
+    ("powdered sugar", 120),
+    ("confectioners sugar", 120),
+    ("honey", 336),
+    ("maple syrup", 312),
+    ("molasses", 336),
+    ("butter", 227),
+    ("oil", 200),
+    ("vegetable oil", 200),
+    ("olive oil", 200),
+    ("water", 236),
+    ("milk", 227),
+    ("buttermilk", 227),
+    ("heavy cream", 227),
+    ("sour cream", 227),
+    ("yogurt", 227),
+    ("salt", 288),
+    ("kosher salt", 144),
+    ("baking soda", 288),
+    ("baking powder", 192),
+    ("instant yeast", 150),
+    ("chocolate chip", 170),
+    ("rice", 198),
+    ("peanut butter", 270),
+];
+
+/// Densities for converting ingredients between volume and weight measures,
+/// e.g. cups of flour to grams. Ingredients are looked up by name and fall
+/// back to shorter versions of the name so "unbleached all-purpose flour"
+/// uses the density for "all-purpose flour".
+#[derive(Debug, Clone, PartialEq)]
+pub struct DensityTable {
+    grams_per_cup: std::collections::BTreeMap<String, Quantity>,
+}
+
+impl Default for DensityTable {
+    /// A table with densities for common baking ingredients.
+    fn default() -> Self {
+        let mut table = Self::empty();
+        for (name, grams) in DEFAULT_DENSITIES {
+            table.set(name, *grams);
+        }
+        table
+    }
+}
+
+impl DensityTable {
+    pub fn empty() -> Self {
+        Self {
+            grams_per_cup: std::collections::BTreeMap::new(),
+        }
+    }
+
+    /// Add or replace the density for an ingredient.
+    pub fn set(&mut self, name: &str, grams_per_cup: u32) {
+        self.grams_per_cup
+            .insert(name.trim().to_lowercase(), Whole(grams_per_cup));
+    }
+
+    /// Replace the densities for the ingredients in `overrides`.
+    pub fn with_overrides<'a, Iter>(mut self, overrides: Iter) -> Self
+    where
+        Iter: IntoIterator<Item = (&'a String, &'a u32)>,
+    {
+        for (name, grams) in overrides {
+            self.set(name, *grams);
+        }
+        self
+    }
+
+    /// The grams per cup for an ingredient if we know it.
+    pub fn grams_per_cup(&self, name: &str) -> Option<Quantity> {
+        let name = name.trim().to_lowercase();
+        let words: Vec<&str> = name.split_whitespace().collect();
+        (0..words.len())
+            .find_map(|start| self.grams_per_cup.get(&words[start..].join(" ")).copied())
+    }
+
+    /// Convert a volume measure of an ingredient to grams. Returns None if
+    /// the measure isn't a volume or the density isn't known.
+    pub fn to_weight(&self, name: &str, amt: &Measure) -> Option<Measure> {
+        let vm = match amt {
+            Measure::Volume(vm) => vm,
+            _ => return None,
+        };
+        let grams = vm.get_ml() * self.grams_per_cup(name)? / CUP;
+        Some(Measure::Weight(Gram(round_to(grams, 1))))
+    }
+
+    /// Convert a weight measure of an ingredient to cups or smaller units.
+    /// Returns None if the measure isn't a weight or the density isn't
+    /// known.
+    pub fn to_volume(&self, name: &str, amt: &Measure) -> Option<Measure> {
+        let wm = match amt {
+            Measure::Weight(wm) => wm,
+            _ => return None,
+        };
+        let cups = wm.get_grams() / self.grams_per_cup(name)?;
+        Some(Measure::Volume(Cup(round_to(cups, 8)).normalize()))
+    }
+}
@@ -0,0 +1,82 @@
+// Copyright 2023 Jeremy Wall (Jeremy@marzhilsltudios.com)
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//! A log of the dispatched messages and the state they produced for debugging.
+use std::{cell::Cell, rc::Rc};
+
+use sycamore::prelude::*;
+
+use crate::app_state::{AppState, Message};
+
+/// The most messages we keep in the log.
+pub const MAX_ENTRIES: usize = 100;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct LogEntry {
+    pub seq: usize,
+    pub message: String,
+    /// The state right after the message was reduced.
+    pub state: Rc<AppState>,
+}
+
+#[derive(Clone)]
+pub struct ActionLog {
+    entries: RcSignal<Vec<LogEntry>>,
+    next_seq: Rc<Cell<usize>>,
+}
+
+impl ActionLog {
+    pub fn new() -> Self {
+        Self {
+            entries: create_rc_signal(Vec::new()),
+            next_seq: Rc::new(Cell::new(0)),
+        }
+    }
+
+    /// The log is only kept when the debug panel is compiled in since every
+    /// entry holds a copy of the state.
+    pub fn is_enabled() -> bool {
+        cfg!(feature = "debug_panel")
+    }
+
+    pub fn get_from_context(cx: Scope) -> Self {
+        use_context::<Self>(cx).clone()
+    }
+
+    /// Record a message along with the state it produced.
+    pub fn record(&self, msg: &Message, state: &AppState) {
+        if !Self::is_enabled() {
+            return;
+        }
+        let seq = self.next_seq.get();
+        self.next_seq.set(seq + 1);
+        let mut entries = self.entries.modify();
+        entries.push(LogEntry {
+            seq,
+            message: format!("{:?}", msg),
+            state: Rc::new(state.clone()),
+        });
+        if entries.len() > MAX_ENTRIES {
+            let extra = entries.len() - MAX_ENTRIES;
+            entries.drain(0..extra);
+        }
+    }
+
+    pub fn entries(&self) -> &RcSignal<Vec<LogEntry>> {
+        &self.entries
+    }
+
+    pub fn clear(&self) {
+        self.entries.set(Vec::new());
+    }
+}
@@ -18,18 +18,25 @@ use chrono::NaiveDate;
 use reqwasm;
 use serde_json::{from_str, to_string};
 use sycamore::prelude::*;
-use tracing::{debug, error, instrument};
+use tracing::{debug, error, info, instrument, warn};
 
 use client_api::*;
 use recipes::{IngredientKey, RecipeEntry};
 use wasm_bindgen::JsValue;
 use web_sys::Storage;
 
-use crate::{app_state::{AppState, parse_recipes}, js_lib};
+use crate::{app_state::{AppState, parse_recipes}, cache::{self, LocalCache}, components::toast::Notifier, js_lib};
+use crate::transport::{self, FetchTransport, Transport};
 
 #[derive(Debug)]
 pub struct Error(String);
 
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
 impl From<std::io::Error> for Error {
     fn from(item: std::io::Error) -> Self {
         Error(format!("{:?}", item))
@@ -76,184 +83,665 @@ fn recipe_key<S: std::fmt::Display>(id: S) -> String {
     format!("recipe:{}", id)
 }
 
+fn draft_key<S: std::fmt::Display>(id: S) -> String {
+    format!("draft:{}", id)
+}
+
+/// Cooking progress is kept per recipe per day under this prefix.
+const COOK_PROGRESS_PREFIX: &'static str = "cook_progress:";
+
+fn cook_progress_key<S: std::fmt::Display>(id: S, date: &NaiveDate) -> String {
+    format!("{}{}:{}", COOK_PROGRESS_PREFIX, date, id)
+}
+
+/// The key that records which version of the local storage layout we are on.
+const SCHEMA_VERSION_KEY: &'static str = "schema_version";
+/// Entries we couldn't parse are moved under this prefix so they don't break
+/// loading but are still around to debug.
+const QUARANTINE_PREFIX: &'static str = "quarantine:";
+
+/// The migrations for the local storage layout in order. Migration `n` moves
+/// the layout from version `n` to version `n + 1` so the current version is
+/// the number of migrations.
+const MIGRATIONS: &'static [fn(&LocalStore) -> Result<(), Error>] =
+    &[LocalStore::remove_legacy_keys];
+
 fn token68(user: String, pass: String) -> String {
     base64::engine::general_purpose::STANDARD.encode(format!("{}:{}", user, pass))
 }
 
+/// Turn a storage exception into an error. A full storage quota gets a message
+/// the user can act on.
+fn storage_error(err: JsValue) -> Error {
+    let name = js_sys::Reflect::get(&err, &JsValue::from_str("name"))
+        .ok()
+        .and_then(|n| n.as_string());
+    match name.as_deref() {
+        Some("QuotaExceededError") | Some("NS_ERROR_DOM_QUOTA_REACHED") => {
+            "Local storage on this device is full".into()
+        }
+        _ => Error(format!("Local storage error: {:?}", err)),
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct LocalStore {
-    store: Storage,
+    // Storage can be missing entirely when the browser has it disabled,
+    // for instance in some private browsing modes.
+    store: Option<Storage>,
 }
 
 impl LocalStore {
     pub fn new() -> Self {
-        Self {
+        let local_store = Self {
             store: js_lib::get_storage(),
+        };
+        if let Err(err) = local_store.run_migrations() {
+            error!(?err, "Failed to migrate the local store");
         }
+        local_store
     }
 
-    pub fn store_app_state(&self, state: &AppState) {
-        self.migrate_local_store();
+    /// Whether the browser gave us any local storage to use.
+    pub fn is_available(&self) -> bool {
+        self.store.is_some()
+    }
+
+    fn storage(&self) -> Result<&Storage, Error> {
         self.store
-            .set("app_state", &to_string(state).unwrap())
-            .expect("Failed to set our app state");
+            .as_ref()
+            .ok_or_else(|| Error::from("Local storage is unavailable"))
+    }
+
+    /// Get the raw value for a key.
+    pub fn get(&self, key: &str) -> Result<Option<String>, Error> {
+        self.storage()?.get(key).map_err(storage_error)
+    }
+
+    /// Set the raw value for a key.
+    pub fn set(&self, key: &str, value: &str) -> Result<(), Error> {
+        self.storage()?.set(key, value).map_err(storage_error)
+    }
+
+    fn delete(&self, key: &str) -> Result<(), Error> {
+        self.storage()?.delete(key).map_err(storage_error)
+    }
+
+    fn schema_version(&self) -> Result<usize, Error> {
+        Ok(self
+            .get(SCHEMA_VERSION_KEY)?
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(0))
+    }
+
+    /// Bring the local storage layout up to the current version.
+    fn run_migrations(&self) -> Result<(), Error> {
+        let version = self.schema_version()?;
+        if version >= MIGRATIONS.len() {
+            return Ok(());
+        }
+        for (idx, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+            info!(from = idx, to = idx + 1, "Migrating local store");
+            migration(self)?;
+        }
+        self.set(SCHEMA_VERSION_KEY, &MIGRATIONS.len().to_string())
+    }
+
+    /// Move an entry we couldn't parse out of the way so that it doesn't
+    /// break loading.
+    fn quarantine(&self, key: &str, value: &str) {
+        error!(key, "Quarantining unparseable local store entry");
+        if let Err(err) = self.set(&format!("{}{}", QUARANTINE_PREFIX, key), value) {
+            error!(key, ?err, "Failed to save quarantined entry");
+        }
+        if let Err(err) = self.delete(key) {
+            error!(key, ?err, "Failed to remove quarantined entry");
+        }
+    }
+
+    /// Parse the entry at key quarantining it if it is corrupt.
+    fn parse_entry<T: serde::de::DeserializeOwned>(&self, key: &str, value: &str) -> Option<T> {
+        match from_str(value) {
+            Ok(parsed) => Some(parsed),
+            Err(err) => {
+                error!(key, ?err, "Failed to parse local store entry");
+                self.quarantine(key, value);
+                None
+            }
+        }
+    }
+
+    pub fn store_app_state(&self, state: &AppState) -> Result<(), Error> {
+        let serialized = to_string(state).map_err(|e| format!("{}", e))?;
+        self.set("app_state", &serialized)
     }
 
-    pub fn fetch_app_state(&self) -> Option<AppState> {
+    pub fn fetch_app_state(&self) -> Result<Option<AppState>, Error> {
         debug!("Loading state from local store");
-        self.store.get("app_state").map_or(None, |val| {
-            val.map(|s| {
-                debug!("Found an app_state object");
-                let mut app_state: AppState = from_str(&s).expect("Failed to deserialize app state");
-                let recipes = parse_recipes(&self.get_recipes()).expect("Failed to parse recipes");
-                if let Some(recipes) = recipes {
-                    debug!("Populating recipes");
-                    for (id, recipe) in recipes {
-                        debug!(id, "Adding recipe from local storage");
-                        app_state.recipes.insert(id, recipe);
-                    }
+        let s = match self.get("app_state")? {
+            Some(s) => s,
+            None => return Ok(None),
+        };
+        debug!("Found an app_state object");
+        let mut app_state: AppState = match self.parse_entry("app_state", &s) {
+            Some(app_state) => app_state,
+            None => return Ok(None),
+        };
+        match parse_recipes(&self.get_recipes()?) {
+            Ok(Some(recipes)) => {
+                debug!("Populating recipes");
+                for (id, recipe) in recipes {
+                    debug!(id, "Adding recipe from local storage");
+                    app_state.recipes.insert(id, recipe);
                 }
-                app_state
-            })
-        })
+            }
+            Ok(None) => (),
+            Err(err) => error!(err, "Failed to parse recipes from local storage"),
+        }
+        Ok(Some(app_state))
+    }
+
+    /// Settings changed on this device that haven't reached the server yet.
+    pub fn get_pending_settings(&self) -> Result<BTreeMap<String, String>, Error> {
+        Ok(self
+            .get("pending_settings")?
+            .and_then(|val| self.parse_entry("pending_settings", &val))
+            .unwrap_or_default())
+    }
+
+    /// Remember settings that failed to save so they can be sent again.
+    /// Newer values replace older ones for the same setting.
+    pub fn add_pending_settings(&self, settings: &BTreeMap<String, String>) -> Result<(), Error> {
+        let mut pending = self.get_pending_settings()?;
+        pending.extend(settings.clone());
+        let serialized = to_string(&pending).map_err(|e| format!("{}", e))?;
+        self.set("pending_settings", &serialized)
+    }
+
+    pub fn clear_pending_settings(&self) -> Result<(), Error> {
+        self.delete("pending_settings")
     }
 
     /// Gets user data from local storage.
-    pub fn get_user_data(&self) -> Option<UserData> {
-        self.store
-            .get("user_data")
-            .map_or(None, |val| val.map(|val| from_str(&val).unwrap_or(None)))
-            .flatten()
+    pub fn get_user_data(&self) -> Result<Option<UserData>, Error> {
+        Ok(self
+            .get("user_data")?
+            .and_then(|val| self.parse_entry::<Option<UserData>>("user_data", &val))
+            .flatten())
     }
 
     // Set's user data to local storage.
-    pub fn set_user_data(&self, data: Option<&UserData>) {
+    pub fn set_user_data(&self, data: Option<&UserData>) -> Result<(), Error> {
         if let Some(data) = data {
-            self.store
-                .set(
-                    "user_data",
-                    &to_string(data).expect("Failed to desrialize user_data"),
-                )
-                .expect("Failed to set user_data");
+            let serialized = to_string(data).map_err(|e| format!("{}", e))?;
+            self.set("user_data", &serialized)
         } else {
-            self.store
-                .delete("user_data")
-                .expect("Failed to delete user_data");
+            self.delete("user_data")
         }
     }
 
-    fn get_storage_keys(&self) -> Vec<String> {
+    fn get_storage_keys(&self) -> Result<Vec<String>, Error> {
+        let storage = self.storage()?;
         let mut keys = Vec::new();
-        for idx in 0..self.store.length().unwrap() {
-            if let Some(k) = self.store.key(idx).expect("Failed to get storage key") {
+        for idx in 0..storage.length().map_err(storage_error)? {
+            if let Some(k) = storage.key(idx).map_err(storage_error)? {
                 keys.push(k)
             }
         }
-        keys
+        Ok(keys)
     }
 
-    fn migrate_local_store(&self) {
-        for k in self.get_storage_keys()
-            .into_iter()
-            .filter(|k| k.starts_with("categor") || k == "inventory" || k.starts_with("plan") || k == "staples") {
-                // Deleting old local store key
-               debug!("Deleting old local store key {}", k);         
-               self.store.delete(&k).expect("Failed to delete storage key");
+    /// Version 0 cached categories, inventory, plans, and staples under their
+    /// own keys. They live in the app_state now.
+    fn remove_legacy_keys(&self) -> Result<(), Error> {
+        for k in self.get_storage_keys()?.into_iter().filter(|k| {
+            k.starts_with("categor") || k == "inventory" || k.starts_with("plan") || k == "staples"
+        }) {
+            debug!("Deleting old local store key {}", k);
+            self.delete(&k)?;
         }
+        Ok(())
     }
 
-    fn get_recipe_keys(&self) -> impl Iterator<Item = String> {
-        self.get_storage_keys()
+    fn get_recipe_keys(&self) -> Result<impl Iterator<Item = String>, Error> {
+        Ok(self
+            .get_storage_keys()?
             .into_iter()
-            .filter(|k| k.starts_with("recipe:"))
+            .filter(|k| k.starts_with("recipe:")))
     }
 
     /// Gets all the recipes from local storage.
-    pub fn get_recipes(&self) -> Option<Vec<RecipeEntry>> {
+    pub fn get_recipes(&self) -> Result<Option<Vec<RecipeEntry>>, Error> {
         let mut recipe_list = Vec::new();
-        for recipe_key in self.get_recipe_keys() {
-            if let Some(entry) = self
-                .store
-                .get(&recipe_key)
-                .expect(&format!("Failed to get recipe: {}", recipe_key))
-            {
-                match from_str(&entry) {
-                    Ok(entry) => {
-                        recipe_list.push(entry);
-                    }
-                    Err(e) => {
-                        error!(recipe_key, err = ?e, "Failed to parse recipe entry");
-                    }
+        for recipe_key in self.get_recipe_keys()? {
+            if let Some(entry) = self.get(&recipe_key)? {
+                if let Some(entry) = self.parse_entry(&recipe_key, &entry) {
+                    recipe_list.push(entry);
                 }
             }
         }
         if recipe_list.is_empty() {
-            return None;
+            return Ok(None);
         }
-        Some(recipe_list)
+        Ok(Some(recipe_list))
     }
 
-    pub fn get_recipe_entry(&self, id: &str) -> Option<RecipeEntry> {
+    pub fn get_recipe_entry(&self, id: &str) -> Result<Option<RecipeEntry>, Error> {
         let key = recipe_key(id);
-        self.store
-            .get(&key)
-            .expect(&format!("Failed to get recipe {}", key))
-            .map(|entry| from_str(&entry).expect(&format!("Failed to get recipe {}", key)))
+        Ok(self
+            .get(&key)?
+            .and_then(|entry| self.parse_entry(&key, &entry)))
     }
 
     /// Sets the set of recipes to the entries passed in. Deletes any recipes not
     /// in the list.
-    pub fn set_all_recipes(&self, entries: &Vec<RecipeEntry>) {
-        for recipe_key in self.get_recipe_keys() {
-            self.store
-                .delete(&recipe_key)
-                .expect(&format!("Failed to get recipe {}", recipe_key));
+    pub fn set_all_recipes(&self, entries: &Vec<RecipeEntry>) -> Result<(), Error> {
+        for recipe_key in self.get_recipe_keys()? {
+            self.delete(&recipe_key)?;
         }
         for entry in entries {
-            self.set_recipe_entry(entry);
+            self.set_recipe_entry(entry)?;
         }
+        Ok(())
     }
 
     /// Set recipe entry in local storage.
-    pub fn set_recipe_entry(&self, entry: &RecipeEntry) {
-        self.store
-            .set(
-                &recipe_key(entry.recipe_id()),
-                &to_string(&entry).expect(&format!("Failed to get recipe {}", entry.recipe_id())),
-            )
-            .expect(&format!("Failed to store recipe {}", entry.recipe_id()))
+    pub fn set_recipe_entry(&self, entry: &RecipeEntry) -> Result<(), Error> {
+        let serialized = to_string(&entry).map_err(|e| format!("{}", e))?;
+        self.set(&recipe_key(entry.recipe_id()), &serialized)
     }
 
     /// Delete recipe entry from local storage.
-    pub fn delete_recipe_entry(&self, recipe_id: &str) {
-        self.store
-            .delete(&recipe_key(recipe_id))
-            .expect(&format!("Failed to delete recipe {}", recipe_id))
+    pub fn delete_recipe_entry(&self, recipe_id: &str) -> Result<(), Error> {
+        self.delete(&recipe_key(recipe_id))
+    }
+
+    /// Gets the unsaved draft text for a recipe from local storage.
+    pub fn get_draft(&self, recipe_id: &str) -> Result<Option<String>, Error> {
+        self.get(&draft_key(recipe_id))
     }
+
+    /// Set the unsaved draft text for a recipe in local storage.
+    pub fn set_draft(&self, recipe_id: &str, text: &str) -> Result<(), Error> {
+        self.set(&draft_key(recipe_id), text)
+    }
+
+    /// Delete the unsaved draft for a recipe from local storage.
+    pub fn delete_draft(&self, recipe_id: &str) -> Result<(), Error> {
+        self.delete(&draft_key(recipe_id))
+    }
+
+    /// The ingredients and steps checked off while cooking a recipe on `date`.
+    pub fn get_cook_progress(
+        &self,
+        recipe_id: &str,
+        date: &NaiveDate,
+    ) -> Result<BTreeSet<String>, Error> {
+        let key = cook_progress_key(recipe_id, date);
+        Ok(self
+            .get(&key)?
+            .and_then(|value| self.parse_entry(&key, &value))
+            .unwrap_or_default())
+    }
+
+    /// Save the ingredients and steps checked off while cooking a recipe on
+    /// `date`. Progress from earlier days is removed since nobody picks up
+    /// yesterday's cooking where they left off.
+    pub fn set_cook_progress(
+        &self,
+        recipe_id: &str,
+        date: &NaiveDate,
+        checked: &BTreeSet<String>,
+    ) -> Result<(), Error> {
+        let today = cook_progress_key("", date);
+        for k in self
+            .get_storage_keys()?
+            .into_iter()
+            .filter(|k| k.starts_with(COOK_PROGRESS_PREFIX) && !k.starts_with(&today))
+        {
+            self.delete(&k)?;
+        }
+        let key = cook_progress_key(recipe_id, date);
+        if checked.is_empty() {
+            self.delete(&key)
+        } else {
+            let serialized = to_string(checked).map_err(|e| format!("{}", e))?;
+            self.set(&key, &serialized)
+        }
+    }
+}
+
+/// A message for the user describing an error from the server.
+pub fn error_code_message(code: &ErrorCode, message: &str) -> String {
+    match code {
+        ErrorCode::BadRequest => format!("The request was invalid: {}", message),
+        ErrorCode::Unauthorized => "You need to log in to do that.".to_owned(),
+        ErrorCode::Forbidden => "You aren't allowed to do that.".to_owned(),
+        ErrorCode::NotFound => "That could not be found.".to_owned(),
+        ErrorCode::Conflict => "That was changed somewhere else. Reload and try again.".to_owned(),
+        ErrorCode::Validation { field } if !field.is_empty() => {
+            format!("Invalid {}: {}", field, message)
+        }
+        ErrorCode::Validation { field: _ } => format!("Invalid request: {}", message),
+        ErrorCode::PayloadTooLarge | ErrorCode::ReadOnly => message.to_owned(),
+        ErrorCode::Busy => "The server is busy. Try again in a moment.".to_owned(),
+        ErrorCode::TooManyRequests => "Too many requests. Try again later.".to_owned(),
+        ErrorCode::Internal => format!("Something went wrong on the server: {}", message),
+    }
+}
+
+/// Random lowercase hex for `bytes` bytes.
+fn random_hex(bytes: usize) -> String {
+    (0..bytes)
+        .map(|_| format!("{:02x}", (js_sys::Math::random() * 256.0) as u8))
+        .collect()
+}
+
+/// A W3C `traceparent` header that starts a new trace. The server logs the
+/// request under the same trace id.
+fn new_traceparent() -> String {
+    format!("00-{}-{}-01", random_hex(16), random_hex(8))
+}
+
+/// The id the server logged a request under.
+fn request_id(resp: &transport::Response) -> Option<String> {
+    resp.headers().get("x-request-id")
+}
+
+/// Turn a failed response into an error with a message for the user.
+async fn error_from_response(resp: transport::Response) -> Error {
+    let status = resp.status();
+    warn!(
+        path = %resp.url(),
+        status,
+        request_id = request_id(&resp).as_deref(),
+        "Request failed"
+    );
+    match resp.json::<EmptyResponse>().await {
+        Ok(EmptyResponse::Err { message, code, .. }) => error_code_message(&code, &message).into(),
+        Ok(EmptyResponse::Invalid(errors)) => errors
+            .iter()
+            .map(|e| e.to_string())
+            .collect::<Vec<String>>()
+            .join("; ")
+            .into(),
+        Ok(EmptyResponse::Unauthorized) => error_code_message(&ErrorCode::Unauthorized, "").into(),
+        Ok(EmptyResponse::NotFound) => error_code_message(&ErrorCode::NotFound, "").into(),
+        Ok(EmptyResponse::Conflict(_)) => error_code_message(&ErrorCode::Conflict, "").into(),
+        Ok(EmptyResponse::Success(_)) | Err(_) => format!("Status: {}", status).into(),
+    }
+}
+
+/// The kinds of data we fetch from the server.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Operation {
+    Recipes,
+    Plan,
+    Inventory,
+    Staples,
+    Categories,
+    Settings,
+}
+
+/// Tracks which operations have requests in flight so the UI can show that
+/// data is still loading.
+#[derive(Clone)]
+pub struct RequestTracker {
+    in_flight: RcSignal<BTreeMap<Operation, usize>>,
+    retrying: RcSignal<usize>,
+}
+
+impl std::fmt::Debug for RequestTracker {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RequestTracker")
+            .field("in_flight", &self.in_flight.get_untracked())
+            .field("retrying", &self.retrying.get_untracked())
+            .finish()
+    }
+}
+
+impl RequestTracker {
+    pub fn new() -> Self {
+        Self {
+            in_flight: create_rc_signal(BTreeMap::new()),
+            retrying: create_rc_signal(0),
+        }
+    }
+
+    /// Mark a request for the operation as in flight until the returned guard
+    /// is dropped.
+    pub fn start(&self, op: Operation) -> InFlight {
+        *self.in_flight.modify().entry(op).or_insert(0) += 1;
+        InFlight {
+            tracker: self.clone(),
+            op,
+        }
+    }
+
+    /// Whether any requests for the operation are in flight. This is tracked
+    /// so it can be used in reactive contexts.
+    pub fn is_loading(&self, op: Operation) -> bool {
+        self.in_flight
+            .get()
+            .get(&op)
+            .map_or(false, |count| *count > 0)
+    }
+
+    /// Whether any requests are waiting to retry after a transient failure.
+    /// This is tracked so it can be used in reactive contexts.
+    pub fn is_reconnecting(&self) -> bool {
+        *self.retrying.get() > 0
+    }
+}
+
+/// A guard for an in flight request.
+pub struct InFlight {
+    tracker: RequestTracker,
+    op: Operation,
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        if let Some(count) = self.tracker.in_flight.modify().get_mut(&self.op) {
+            *count = count.saturating_sub(1);
+        }
+    }
+}
+
+/// The most times we will try an idempotent request before giving up.
+pub const MAX_ATTEMPTS: u32 = 4;
+/// The delay before the first retry. Each retry after that doubles it.
+pub const BASE_RETRY_DELAY_MS: u32 = 250;
+
+/// The delay before the given retry with up to 50% random jitter added so
+/// that clients don't all retry in lockstep.
+fn retry_delay(attempt: u32) -> i32 {
+    let delay = BASE_RETRY_DELAY_MS * 2u32.pow(attempt);
+    let jitter = (js_sys::Math::random() * (delay as f64) / 2.0) as u32;
+    (delay + jitter) as i32
+}
+
+/// Whether a response status means the server or a proxy in front of it is
+/// temporarily unavailable.
+fn is_transient_status(status: u16) -> bool {
+    matches!(status, 502 | 503 | 504)
 }
 
 #[derive(Clone, Debug)]
 pub struct HttpStore {
     root: String,
-    local_store: LocalStore,
+    cache: std::rc::Rc<dyn LocalCache>,
+    transport: std::rc::Rc<dyn Transport>,
+    notifier: Notifier,
+    tracker: RequestTracker,
+    cache_warned: std::rc::Rc<std::cell::Cell<bool>>,
+    /// The server's data version for what we last loaded if we know it.
+    data_version: std::rc::Rc<std::cell::Cell<Option<i64>>>,
 }
 
 impl HttpStore {
-    pub fn new(root: String) -> Self {
+    pub fn new(root: String, notifier: Notifier) -> Self {
+        Self::with_transport(root, notifier, std::rc::Rc::new(FetchTransport))
+    }
+
+    /// A store that sends its requests through `transport` instead of
+    /// fetching them from the server.
+    pub fn with_transport(
+        root: String,
+        notifier: Notifier,
+        transport: std::rc::Rc<dyn Transport>,
+    ) -> Self {
         Self {
             root,
-            local_store: LocalStore::new(),
+            cache: cache::new_cache(),
+            transport,
+            notifier,
+            tracker: RequestTracker::new(),
+            cache_warned: std::rc::Rc::new(std::cell::Cell::new(false)),
+            data_version: std::rc::Rc::new(std::cell::Cell::new(None)),
         }
     }
 
+    pub fn tracker(&self) -> &RequestTracker {
+        &self.tracker
+    }
+
+    /// The cache for recipes and images on this device.
+    pub fn cache(&self) -> std::rc::Rc<dyn LocalCache> {
+        self.cache.clone()
+    }
+
     pub fn v2_path(&self) -> String {
         let mut path = self.root.clone();
         path.push_str("/v2");
         path
     }
 
+    /// Expects a [Notifier] to already be provided as context.
     pub fn provide_context<S: Into<String>>(cx: Scope, root: S) {
-        provide_context(cx, std::rc::Rc::new(Self::new(root.into())));
+        let notifier = Notifier::get_from_context(cx);
+        provide_context(cx, std::rc::Rc::new(Self::new(root.into(), notifier)));
+    }
+
+    /// Let the user know about an error and hand it back.
+    fn notify_error<E: Into<Error>>(&self, err: E) -> Error {
+        let err = err.into();
+        self.notifier.error(&err.to_string());
+        err
+    }
+
+    /// Report a local cache failure to the user without failing the
+    /// operation. The user is only told once since it usually means the cache
+    /// is unusable for the rest of the session.
+    pub fn cache_result<T: Default>(&self, result: Result<T, Error>) -> T {
+        match result {
+            Ok(val) => val,
+            Err(err) => {
+                error!(?err, "Local cache failure");
+                if !self.cache_warned.replace(true) {
+                    self.notifier
+                        .error(&format!("{}. Your data won't be available offline.", err));
+                }
+                T::default()
+            }
+        }
+    }
+
+    async fn response_error(&self, resp: transport::Response) -> Error {
+        self.notify_error(error_from_response(resp).await)
+    }
+
+    /// Send a request that changes data. Unlike fetches there is no local
+    /// fallback so the user needs to know if it never reached the server.
+    async fn send_change(&self, request: transport::Request) -> Result<transport::Response, Error> {
+        let traceparent = new_traceparent();
+        let request = request.header("traceparent", &traceparent);
+        let resp = self.transport.send(request).await.map_err(|err| {
+            error!(?err, traceparent, "Unable to reach the server");
+            self.notify_error(format!("Unable to reach the server: {}", err))
+        })?;
+        self.track_data_version(&resp);
+        Ok(resp)
+    }
+
+    /// Keep up with the data version our own writes move the server to. A
+    /// version more than one ahead of ours means someone else wrote too so
+    /// we forget ours and the next load fetches everything.
+    fn track_data_version(&self, resp: &transport::Response) {
+        let version = resp
+            .headers()
+            .get(DATA_VERSION_HEADER)
+            .and_then(|v| v.parse::<i64>().ok());
+        if let Some(version) = version {
+            let known = self.data_version.get();
+            self.data_version
+                .set(known.filter(|known| known + 1 == version).map(|_| version));
+        }
+    }
+
+    /// Remember the data version of what we just loaded.
+    pub fn set_data_version(&self, version: Option<i64>) {
+        self.data_version.set(version);
+    }
+
+    /// Ask the server for its data version. Returns `None` if it is the one
+    /// we already have.
+    pub async fn check_data_version(&self) -> Result<Option<i64>, Error> {
+        let mut path = self.v2_path();
+        path.push_str("/version");
+        let known = self.data_version.get();
+        let mut request = transport::Request::get(&path);
+        if let Some(version) = known {
+            request = request.header("If-None-Match", &format!(r#""{}""#, version));
+        }
+        let resp = self.transport.send(request).await?;
+        match resp.status() {
+            304 => Ok(None),
+            200 => {
+                let version = resp
+                    .json::<DataVersionResponse>()
+                    .await
+                    .map_err(|e| format!("{}", e))?
+                    .as_success();
+                Ok(version.filter(|version| Some(*version) != known))
+            }
+            _ => Err(error_from_response(resp).await),
+        }
+    }
+
+    /// Send a GET request retrying network failures and transient server
+    /// errors with exponential backoff. The tracker reports that we are
+    /// reconnecting while we wait to retry.
+    async fn get(&self, path: &str) -> Result<transport::Response, reqwasm::Error> {
+        let mut attempt = 0;
+        // Retries share a trace id so they can be found together in the
+        // server logs.
+        let traceparent = new_traceparent();
+        loop {
+            let request = transport::Request::get(path).header("traceparent", &traceparent);
+            let result = self.transport.send(request).await;
+            let transient = match &result {
+                Ok(resp) => is_transient_status(resp.status()),
+                Err(reqwasm::Error::JsError(_)) => true,
+                Err(_) => false,
+            };
+            attempt += 1;
+            if !transient || attempt >= MAX_ATTEMPTS {
+                return result;
+            }
+            let delay = retry_delay(attempt - 1);
+            debug!(
+                path,
+                attempt,
+                delay,
+                request_id = ?result.as_ref().ok().and_then(request_id),
+                "Retrying transient failure"
+            );
+            *self.tracker.retrying.modify() += 1;
+            js_lib::sleep(delay).await;
+            let remaining = self.tracker.retrying.get_untracked().saturating_sub(1);
+            self.tracker.retrying.set(remaining);
+        }
     }
 
     pub fn get_from_context(cx: Scope) -> std::rc::Rc<Self> {
@@ -262,17 +750,23 @@ impl HttpStore {
 
     // NOTE(jwall): We do **not** want to record the password in our logs.
     #[instrument(skip_all, fields(?self, user))]
-    pub async fn authenticate(&self, user: String, pass: String) -> Option<UserData> {
+    pub async fn authenticate(
+        &self,
+        user: String,
+        pass: String,
+        remember: bool,
+    ) -> Option<UserData> {
         debug!("attempting login request against api.");
         let mut path = self.v2_path();
         path.push_str("/auth");
-        let result = reqwasm::http::Request::get(&path)
-            .header(
-                "Authorization",
-                format!("Basic {}", token68(user, pass)).as_str(),
-            )
-            .send()
-            .await;
+        if remember {
+            path.push_str("?remember=true");
+        }
+        let request = transport::Request::get(&path).header(
+            "Authorization",
+            format!("Basic {}", token68(user, pass)).as_str(),
+        );
+        let result = self.transport.send(request).await;
         if let Ok(resp) = &result {
             if resp.status() == 200 {
                 let user_data = resp
@@ -280,6 +774,9 @@ impl HttpStore {
                     .await
                     .expect("Unparseable authentication response")
                     .as_success();
+                // The version we had belongs to whoever was logged in
+                // before.
+                self.data_version.set(None);
                 return user_data;
             }
             error!(status = resp.status(), "Login was unsuccessful")
@@ -289,12 +786,35 @@ impl HttpStore {
         return None;
     }
 
+    /// Trade this device's refresh token for a new session. Devices that
+    /// weren't remembered at login don't have one.
+    #[instrument]
+    pub async fn refresh_session(&self) -> Option<UserData> {
+        let mut path = self.v2_path();
+        path.push_str("/auth/refresh");
+        match self.transport.send(transport::Request::post(&path)).await {
+            Ok(resp) if resp.status() == 200 => resp
+                .json::<AccountResponse>()
+                .await
+                .ok()
+                .and_then(|resp| resp.as_success()),
+            Ok(resp) => {
+                debug!(status = resp.status(), "No session to refresh");
+                None
+            }
+            Err(err) => {
+                error!(?err, "Failed to send refresh request");
+                None
+            }
+        }
+    }
+
     #[instrument]
     pub async fn fetch_user_data(&self) -> Option<UserData> {
         debug!("Retrieving User Account data");
         let mut path = self.v2_path();
         path.push_str("/account");
-        let result = reqwasm::http::Request::get(&path).send().await;
+        let result = self.get(&path).await;
         if let Ok(resp) = &result {
             if resp.status() == 200 {
                 let user_data = resp
@@ -313,9 +833,10 @@ impl HttpStore {
 
     //#[instrument]
     pub async fn fetch_categories(&self) -> Result<Option<Vec<(String, String)>>, Error> {
+        let _in_flight = self.tracker.start(Operation::Categories);
         let mut path = self.v2_path();
         path.push_str("/category_map");
-        let resp = match reqwasm::http::Request::get(&path).send().await {
+        let resp = match self.get(&path).await {
             Ok(resp) => resp,
             Err(reqwasm::Error::JsError(err)) => {
                 error!(path, ?err, "Error hitting api");
@@ -329,7 +850,7 @@ impl HttpStore {
             debug!("Categories returned 404");
             Ok(None)
         } else if resp.status() != 200 {
-            Err(format!("Status: {}", resp.status()).into())
+            Err(self.response_error(resp).await)
         } else {
             debug!("We got a valid response back!");
             let resp = resp
@@ -341,22 +862,45 @@ impl HttpStore {
         }
     }
 
+    #[instrument]
+    pub async fn fetch_category_suggestions(
+        &self,
+    ) -> Result<Vec<(String, Option<String>)>, Error> {
+        let _in_flight = self.tracker.start(Operation::Categories);
+        let mut path = self.v2_path();
+        path.push_str("/category_map/suggestions");
+        let resp = self.get(&path).await?;
+        if resp.status() != 200 {
+            Err(self.response_error(resp).await)
+        } else {
+            debug!("We got a valid response back!");
+            let suggestions = resp
+                .json::<CategorySuggestionResponse>()
+                .await
+                .map_err(|e| format!("{}", e))?
+                .as_success()
+                .unwrap_or_default();
+            Ok(suggestions)
+        }
+    }
+
     #[instrument]
     pub async fn fetch_recipes(&self) -> Result<Option<Vec<RecipeEntry>>, Error> {
+        let _in_flight = self.tracker.start(Operation::Recipes);
         let mut path = self.v2_path();
         path.push_str("/recipes");
-        let resp = match reqwasm::http::Request::get(&path).send().await {
+        let resp = match self.get(&path).await {
             Ok(resp) => resp,
             Err(reqwasm::Error::JsError(err)) => {
                 error!(path, ?err, "Error hitting api");
-                return Ok(self.local_store.get_recipes());
+                return Ok(self.cache_result(self.cache.get_recipes().await));
             }
             Err(err) => {
                 return Err(err)?;
             }
         };
         if resp.status() != 200 {
-            Err(format!("Status: {}", resp.status()).into())
+            Err(self.response_error(resp).await)
         } else {
             debug!("We got a valid response back!");
             let entries = resp
@@ -368,25 +912,63 @@ impl HttpStore {
         }
     }
 
+    /// Get an image as a data url from the local cache, fetching and caching
+    /// it if we don't have it yet. Returns None if the image can't be fetched
+    /// so the caller can fall back to the original src.
+    pub async fn fetch_image(&self, src: &str) -> Option<String> {
+        if src.starts_with("data:") {
+            return None;
+        }
+        if let Some(cached) = self.cache_result(self.cache.get_image(src).await) {
+            return Some(cached);
+        }
+        // Images are often hosted elsewhere and blocked by CORS so we don't
+        // retry failures here.
+        let resp = match self.transport.send(transport::Request::get(src)).await {
+            Ok(resp) if resp.status() == 200 => resp,
+            Ok(resp) => {
+                debug!(src, status = resp.status(), "Unable to fetch image");
+                return None;
+            }
+            Err(err) => {
+                debug!(src, ?err, "Unable to fetch image");
+                return None;
+            }
+        };
+        let content_type = resp
+            .headers()
+            .get("Content-Type")
+            .unwrap_or_else(|| "application/octet-stream".to_owned());
+        let bytes = resp.binary().await.ok()?;
+        let data_url = format!(
+            "data:{};base64,{}",
+            content_type,
+            base64::engine::general_purpose::STANDARD.encode(bytes)
+        );
+        self.cache_result(self.cache.set_image(src, &data_url).await);
+        Some(data_url)
+    }
+
     pub async fn fetch_recipe_text<S: AsRef<str> + std::fmt::Display>(
         &self,
         id: S,
     ) -> Result<Option<RecipeEntry>, Error> {
+        let _in_flight = self.tracker.start(Operation::Recipes);
         let mut path = self.v2_path();
         path.push_str("/recipe/");
         path.push_str(id.as_ref());
-        let resp = match reqwasm::http::Request::get(&path).send().await {
+        let resp = match self.get(&path).await {
             Ok(resp) => resp,
             Err(reqwasm::Error::JsError(err)) => {
                 error!(path, ?err, "Error hitting api");
-                return Ok(self.local_store.get_recipe_entry(id.as_ref()));
+                return Ok(self.cache_result(self.cache.get_recipe_entry(id.as_ref()).await));
             }
             Err(err) => {
                 return Err(err)?;
             }
         };
         if resp.status() != 200 {
-            Err(format!("Status: {}", resp.status()).into())
+            Err(self.response_error(resp).await)
         } else if resp.status() == 404 {
             debug!("Recipe doesn't exist");
             Ok(None)
@@ -399,12 +981,37 @@ impl HttpStore {
                 .as_success()
                 .unwrap();
             if let Some(ref entry) = entry {
-                self.local_store.set_recipe_entry(entry);
+                self.cache_result(self.cache.set_recipe_entry(entry).await);
             }
             Ok(entry)
         }
     }
 
+    /// The mise en place for a recipe scaled to feed `people`.
+    #[instrument]
+    pub async fn fetch_prep_list(
+        &self,
+        recipe_id: &str,
+        people: Option<u32>,
+    ) -> Result<PrepList, Error> {
+        let mut path = self.v2_path();
+        path.push_str(&format!("/recipe/{}/prep", recipe_id));
+        if let Some(people) = people {
+            path.push_str(&format!("?people={}", people));
+        }
+        let resp = self.get(&path).await?;
+        if resp.status() != 200 {
+            Err(self.response_error(resp).await)
+        } else {
+            Ok(resp
+                .json::<PrepListResponse>()
+                .await
+                .map_err(|e| format!("{}", e))?
+                .as_success()
+                .unwrap())
+        }
+    }
+
     #[instrument]
     pub async fn delete_recipe<S>(&self, recipe: S) -> Result<(), Error>
     where
@@ -413,88 +1020,280 @@ impl HttpStore {
         let mut path = self.v2_path();
         path.push_str("/recipe");
         path.push_str(&format!("/{}", recipe.as_ref()));
-        let resp = reqwasm::http::Request::delete(&path).send().await?;
+        let resp = self.send_change(transport::Request::delete(&path)).await?;
         if resp.status() != 200 {
-            Err(format!("Status: {}", resp.status()).into())
+            Err(self.response_error(resp).await)
         } else {
             debug!("We got a valid response back!");
+            self.notifier.info("Moved recipe to the trash");
             Ok(())
         }
     }
 
-    #[instrument(skip(recipes), fields(count=recipes.len()))]
-    pub async fn store_recipes(&self, recipes: Vec<RecipeEntry>) -> Result<(), Error> {
+    pub async fn fetch_trash(&self) -> Result<Vec<TrashedRecipe>, Error> {
         let mut path = self.v2_path();
-        path.push_str("/recipes");
-        for r in recipes.iter() {
-            if r.recipe_id().is_empty() {
-                return Err("Recipe Ids can not be empty".into());
+        path.push_str("/recipes/trash");
+        let resp = self.get(&path).await?;
+        if resp.status() != 200 {
+            Err(self.response_error(resp).await)
+        } else {
+            Ok(resp
+                .json::<TrashResponse>()
+                .await
+                .map_err(|e| format!("{}", e))?
+                .as_success()
+                .unwrap_or_default())
+        }
+    }
+
+    /// Move a recipe out of the trash returning the restored entry.
+    #[instrument]
+    pub async fn restore_recipe(&self, recipe_id: &str) -> Result<Option<RecipeEntry>, Error> {
+        let mut path = self.v2_path();
+        path.push_str(&format!("/recipes/trash/{}/restore", recipe_id));
+        let resp = self.send_change(transport::Request::post(&path)).await?;
+        if resp.status() != 200 {
+            Err(self.response_error(resp).await)
+        } else {
+            let entry = resp
+                .json::<RecipeEntryResponse>()
+                .await
+                .map_err(|e| format!("{}", e))?
+                .as_success()
+                .and_then(|mut entries| entries.pop());
+            if let Some(ref entry) = entry {
+                self.cache_result(self.cache.set_recipe_entry(entry).await);
             }
+            self.notifier.info("Restored recipe");
+            Ok(entry)
         }
-        let serialized = to_string(&recipes).expect("Unable to serialize recipe entries");
-        let resp = reqwasm::http::Request::post(&path)
-            .body(&serialized)
-            .header("content-type", "application/json")
-            .send()
-            .await?;
+    }
+
+    #[instrument]
+    pub async fn purge_recipe(&self, recipe_id: &str) -> Result<(), Error> {
+        let mut path = self.v2_path();
+        path.push_str(&format!("/recipes/trash/{}", recipe_id));
+        let resp = self.send_change(transport::Request::delete(&path)).await?;
         if resp.status() != 200 {
-            Err(format!("Status: {}", resp.status()).into())
+            Err(self.response_error(resp).await)
         } else {
-            debug!("We got a valid response back!");
+            self.notifier.info("Deleted recipe for good");
             Ok(())
         }
     }
 
-    #[instrument(skip(categories))]
-    pub async fn store_categories(&self, categories: &Vec<(String, String)>) -> Result<(), Error> {
+    /// Upload a photo of a recipe and get back draft recipe text read from it.
+    #[instrument(skip(image), fields(size=image.size()))]
+    pub async fn ocr_recipe(&self, image: web_sys::File) -> Result<OcrDraft, Error> {
         let mut path = self.v2_path();
-        path.push_str("/category_map");
-        let resp = reqwasm::http::Request::post(&path)
-            .body(to_string(&categories).expect("Unable to encode categories as json"))
-            .header("content-type", "application/json")
-            .send()
+        path.push_str("/recipes/import/ocr");
+        let resp = self
+            .send_change(transport::Request::post(&path).body(image))
             .await?;
         if resp.status() != 200 {
-            Err(format!("Status: {}", resp.status()).into())
+            Err(self.response_error(resp).await)
         } else {
-            debug!("We got a valid response back!");
-            Ok(())
+            resp.json::<OcrDraftResponse>()
+                .await
+                .map_err(|e| format!("{}", e))?
+                .as_success()
+                .ok_or_else(|| self.notify_error("No text was found in the photo"))
         }
     }
 
-    #[instrument(skip_all)]
-    pub async fn store_app_state(&self, state: &AppState) -> Result<(), Error> {
-        let mut plan = Vec::new();
-        for (key, count) in state.recipe_counts.iter() {
-            plan.push((key.clone(), *count as i32));
-        }
-        if let Some(cached_plan_date) = &state.selected_plan_date {
-            debug!(?plan, "Saving plan data");
-            self.store_plan_for_date(plan, cached_plan_date).await?;
-            debug!("Saving inventory data");
-            self.store_inventory_data_for_date(
-                state.filtered_ingredients.clone(),
-                state.modified_amts.clone(),
-                state
-                    .extras
+    /// Add the server's sample recipes to the account returning how many
+    /// were added.
+    #[instrument]
+    pub async fn add_sample_recipes(&self) -> Result<usize, Error> {
+        let mut path = self.v2_path();
+        path.push_str("/account/samples");
+        let resp = self.send_change(transport::Request::post(&path)).await?;
+        if resp.status() != 200 {
+            Err(self.response_error(resp).await)
+        } else {
+            Ok(resp
+                .json::<Response<usize>>()
+                .await
+                .map_err(|e| format!("{}", e))?
+                .as_success()
+                .unwrap_or_default())
+        }
+    }
+
+    /// Apply the server's default categories and staples to the account.
+    /// Anything the user already has is left alone.
+    #[instrument]
+    pub async fn apply_defaults(&self) -> Result<DefaultsApplied, Error> {
+        let _in_flight = self.tracker.start(Operation::Categories);
+        let mut path = self.v2_path();
+        path.push_str("/account/defaults");
+        let resp = self.send_change(transport::Request::post(&path)).await?;
+        if resp.status() != 200 {
+            Err(self.response_error(resp).await)
+        } else {
+            Ok(resp
+                .json::<DefaultsResponse>()
+                .await
+                .map_err(|e| format!("{}", e))?
+                .as_success()
+                .unwrap_or_default())
+        }
+    }
+
+    #[instrument(skip(recipes), fields(count=recipes.len()))]
+    pub async fn store_recipes(&self, recipes: Vec<RecipeEntry>) -> Result<(), Error> {
+        let mut path = self.v2_path();
+        path.push_str("/recipes");
+        for r in recipes.iter() {
+            if r.recipe_id().is_empty() {
+                return Err(self.notify_error("Recipe Ids can not be empty"));
+            }
+        }
+        let serialized = to_string(&recipes).expect("Unable to serialize recipe entries");
+        let request = transport::Request::post(&path)
+            .body(&serialized)
+            .header("content-type", "application/json");
+        let resp = self.send_change(request).await?;
+        if resp.status() == 422 {
+            let resp = resp
+                .json::<EmptyResponse>()
+                .await
+                .map_err(|e| format!("{}", e))?;
+            if let EmptyResponse::Invalid(errors) = resp {
+                let errors = errors
                     .iter()
-                    .cloned()
-                    .collect::<Vec<(String, String)>>(),
+                    .map(|e| e.to_string())
+                    .collect::<Vec<String>>()
+                    .join("; ");
+                return Err(self.notify_error(format!("Invalid recipes: {}", errors)));
+            }
+            Err(self.notify_error("Status: 422"))
+        } else if resp.status() == 409 {
+            let resp = resp
+                .json::<RecipeSaveResponse>()
+                .await
+                .map_err(|e| format!("{}", e))?;
+            if let RecipeSaveResponse::Conflict(current) = resp {
+                // We keep the current entries so that saving again will
+                // knowingly overwrite the changes made elsewhere.
+                let ids = current
+                    .iter()
+                    .map(|e| e.recipe_id().to_owned())
+                    .collect::<Vec<String>>()
+                    .join(", ");
+                for entry in current.iter() {
+                    self.cache_result(self.cache.set_recipe_entry(entry).await);
+                }
+                return Err(self.notify_error(format!(
+                    "Recipes changed on another device: {}. Save again to overwrite them.",
+                    ids
+                )));
+            }
+            Err(self.notify_error("Status: 409"))
+        } else if resp.status() != 200 {
+            Err(self.response_error(resp).await)
+        } else {
+            debug!("We got a valid response back!");
+            let saved = resp
+                .json::<RecipeSaveResponse>()
+                .await
+                .map_err(|e| format!("{}", e))?
+                .as_success()
+                .unwrap_or_default();
+            for SavedRecipe {
+                recipe_id,
+                revision,
+                renamed_from,
+            } in saved
+            {
+                let old_entry = match renamed_from {
+                    Some(old_id) => {
+                        let entry = self.cache_result(self.cache.get_recipe_entry(&old_id).await);
+                        self.cache_result(self.cache.delete_recipe_entry(&old_id).await);
+                        entry
+                    }
+                    None => None,
+                };
+                let entry = self.cache_result(self.cache.get_recipe_entry(&recipe_id).await);
+                if let Some(mut entry) = entry.or(old_entry) {
+                    entry.set_recipe_id(recipe_id);
+                    entry.set_revision(Some(revision));
+                    self.cache_result(self.cache.set_recipe_entry(&entry).await);
+                }
+            }
+            self.notifier.info("Saved recipe");
+            Ok(())
+        }
+    }
+
+    #[instrument(skip(categories))]
+    pub async fn store_categories(&self, categories: &Vec<(String, String)>) -> Result<(), Error> {
+        let mut path = self.v2_path();
+        path.push_str("/category_map");
+        let request = transport::Request::post(&path)
+            .body(to_string(&categories).expect("Unable to encode categories as json"))
+            .header("content-type", "application/json");
+        let resp = self.send_change(request).await?;
+        if resp.status() != 200 {
+            Err(self.response_error(resp).await)
+        } else {
+            debug!("We got a valid response back!");
+            self.notifier.info("Saved categories");
+            Ok(())
+        }
+    }
+
+    #[instrument(skip_all)]
+    pub async fn store_app_state(&self, state: &AppState) -> Result<(), Error> {
+        // The planned recipes come first so that the server records the
+        // order we intend to cook them in.
+        let mut plan = Vec::new();
+        for (key, count) in state.planned_recipes() {
+            plan.push((key, count as i32));
+        }
+        for (key, count) in state.recipe_counts.iter() {
+            if *count == 0 {
+                plan.push((key.clone(), 0));
+            }
+        }
+        if let Some(cached_plan_date) = &state.selected_plan_date {
+            debug!(?plan, "Saving plan data");
+            self.store_plan_for_date(plan, cached_plan_date).await?;
+            self.store_plan_meals_for_date(&state.planned_meals(), cached_plan_date)
+                .await?;
+            self.store_plan_freezer_meals_for_date(
+                &state.planned_freezer_meals(),
                 cached_plan_date,
             )
-            .await
+            .await?;
         } else {
             debug!("Saving plan data");
             self.store_plan(plan).await?;
-            debug!("Saving inventory data");
+        }
+        self.store_inventory_from_state(state).await?;
+        self.notifier.info("Saved user state");
+        Ok(())
+    }
+
+    /// Store just the inventory portion of the app state for the selected plan.
+    /// Checked off ingredients and amounts are synced as they change so only
+    /// the extra items are saved for a plan.
+    pub async fn store_inventory_from_state(&self, state: &AppState) -> Result<(), Error> {
+        debug!("Saving inventory data");
+        let extras = state
+            .extras
+            .iter()
+            .cloned()
+            .collect::<Vec<(String, String)>>();
+        if let Some(cached_plan_date) = &state.selected_plan_date {
+            self.update_inventory_for_date(&vec![InventoryOp::SetExtras(extras)], cached_plan_date)
+                .await?;
+            Ok(())
+        } else {
             self.store_inventory_data(
                 state.filtered_ingredients.clone(),
                 state.modified_amts.clone(),
-                state
-                    .extras
-                    .iter()
-                    .cloned()
-                    .collect::<Vec<(String, String)>>(),
+                extras,
             )
             .await
         }
@@ -503,13 +1302,12 @@ impl HttpStore {
     pub async fn store_plan(&self, plan: Vec<(String, i32)>) -> Result<(), Error> {
         let mut path = self.v2_path();
         path.push_str("/plan");
-        let resp = reqwasm::http::Request::post(&path)
+        let request = transport::Request::post(&path)
             .body(to_string(&plan).expect("Unable to encode plan as json"))
-            .header("content-type", "application/json")
-            .send()
-            .await?;
+            .header("content-type", "application/json");
+        let resp = self.send_change(request).await?;
         if resp.status() != 200 {
-            Err(format!("Status: {}", resp.status()).into())
+            Err(self.response_error(resp).await)
         } else {
             debug!("We got a valid response back!");
             Ok(())
@@ -525,26 +1323,123 @@ impl HttpStore {
         path.push_str("/plan");
         path.push_str("/at");
         path.push_str(&format!("/{}", date));
-        let resp = reqwasm::http::Request::post(&path)
+        let request = transport::Request::post(&path)
             .body(to_string(&plan).expect("Unable to encode plan as json"))
-            .header("content-type", "application/json")
-            .send()
-            .await?;
+            .header("content-type", "application/json");
+        let resp = self.send_change(request).await?;
         if resp.status() != 200 {
-            Err(format!("Status: {}", resp.status()).into())
+            Err(self.response_error(resp).await)
         } else {
             debug!("We got a valid response back!");
             Ok(())
         }
     }
 
+    /// Send changes to the plan for a date. Returns the plan with everyone's
+    /// changes merged.
+    #[instrument]
+    pub async fn update_plan_for_date(
+        &self,
+        changes: &Vec<PlanChange>,
+        date: &NaiveDate,
+    ) -> Result<Vec<(String, i32)>, Error> {
+        let mut path = self.v2_path();
+        path.push_str(&format!("/plan/at/{}", date));
+        let request = transport::Request::patch(&path)
+            .body(to_string(changes).expect("Unable to encode plan changes as json"))
+            .header("content-type", "application/json");
+        let resp = self.send_change(request).await?;
+        if resp.status() != 200 {
+            Err(self.response_error(resp).await)
+        } else {
+            Ok(resp
+                .json::<PlanDataResponse>()
+                .await
+                .map_err(|e| format!("{}", e))?
+                .as_success()
+                .ok_or_else(|| "No plan in the response".to_owned())?)
+        }
+    }
+
+    pub async fn store_plan_meals_for_date(
+        &self,
+        meals: &PlanMeals,
+        date: &NaiveDate,
+    ) -> Result<(), Error> {
+        let mut path = self.v2_path();
+        path.push_str(&format!("/plan/at/{}/meals", date));
+        let request = transport::Request::post(&path)
+            .body(to_string(meals).expect("Unable to encode plan meals as json"))
+            .header("content-type", "application/json");
+        let resp = self.send_change(request).await?;
+        if resp.status() != 200 {
+            Err(self.response_error(resp).await)
+        } else {
+            Ok(())
+        }
+    }
+
+    pub async fn fetch_plan_meals_for_date(&self, date: &NaiveDate) -> Result<PlanMeals, Error> {
+        let mut path = self.v2_path();
+        path.push_str(&format!("/plan/at/{}/meals", date));
+        let resp = self.get(&path).await?;
+        if resp.status() != 200 {
+            Err(self.response_error(resp).await)
+        } else {
+            Ok(resp
+                .json::<PlanMealsResponse>()
+                .await
+                .map_err(|e| format!("{}", e))?
+                .as_success()
+                .unwrap_or_default())
+        }
+    }
+
+    pub async fn store_plan_freezer_meals_for_date(
+        &self,
+        recipe_ids: &BTreeSet<String>,
+        date: &NaiveDate,
+    ) -> Result<(), Error> {
+        let mut path = self.v2_path();
+        path.push_str(&format!("/plan/at/{}/freezer", date));
+        let request = transport::Request::post(&path)
+            .body(to_string(recipe_ids).expect("Unable to encode freezer meals as json"))
+            .header("content-type", "application/json");
+        let resp = self.send_change(request).await?;
+        if resp.status() != 200 {
+            Err(self.response_error(resp).await)
+        } else {
+            Ok(())
+        }
+    }
+
+    pub async fn fetch_plan_freezer_meals_for_date(
+        &self,
+        date: &NaiveDate,
+    ) -> Result<BTreeSet<String>, Error> {
+        let mut path = self.v2_path();
+        path.push_str(&format!("/plan/at/{}/freezer", date));
+        let resp = self.get(&path).await?;
+        if resp.status() != 200 {
+            Err(self.response_error(resp).await)
+        } else {
+            Ok(resp
+                .json::<PlanFreezerMealsResponse>()
+                .await
+                .map_err(|e| format!("{}", e))?
+                .as_success()
+                .unwrap_or_default())
+        }
+    }
+
     pub async fn fetch_plan_dates(&self) -> Result<Option<Vec<NaiveDate>>, Error> {
+        let _in_flight = self.tracker.start(Operation::Plan);
         let mut path = self.v2_path();
         path.push_str("/plan");
         path.push_str("/all");
-        let resp = reqwasm::http::Request::get(&path).send().await?;
+        let resp = self.get(&path).await?;
         if resp.status() != 200 {
-            Err(format!("Status: {}", resp.status()).into())
+            Err(self.response_error(resp).await)
         } else {
             debug!("We got a valid response back");
             let plan = resp
@@ -561,10 +1456,11 @@ impl HttpStore {
         path.push_str("/plan");
         path.push_str("/at");
         path.push_str(&format!("/{}", date));
-        let resp = reqwasm::http::Request::delete(&path).send().await?;
+        let resp = self.send_change(transport::Request::delete(&path)).await?;
         if resp.status() != 200 {
-            Err(format!("Status: {}", resp.status()).into())
+            Err(self.response_error(resp).await)
         } else {
+            self.notifier.info("Deleted plan");
             Ok(())
         }
     }
@@ -573,13 +1469,14 @@ impl HttpStore {
         &self,
         date: &NaiveDate,
     ) -> Result<Option<Vec<(String, i32)>>, Error> {
+        let _in_flight = self.tracker.start(Operation::Plan);
         let mut path = self.v2_path();
         path.push_str("/plan");
         path.push_str("/at");
         path.push_str(&format!("/{}", date));
-        let resp = reqwasm::http::Request::get(&path).send().await?;
+        let resp = self.get(&path).await?;
         if resp.status() != 200 {
-            Err(format!("Status: {}", resp.status()).into())
+            Err(self.response_error(resp).await)
         } else {
             debug!("We got a valid response back");
             let plan = resp
@@ -594,7 +1491,7 @@ impl HttpStore {
     //pub async fn fetch_plan(&self) -> Result<Option<Vec<(String, i32)>>, Error> {
     //    let mut path = self.v2_path();
     //    path.push_str("/plan");
-    //    let resp = reqwasm::http::Request::get(&path).send().await?;
+    //    let resp = self.get(&path).await?;
     //    if resp.status() != 200 {
     //        Err(format!("Status: {}", resp.status()).into())
     //    } else {
@@ -619,13 +1516,14 @@ impl HttpStore {
         ),
         Error,
     > {
+        let _in_flight = self.tracker.start(Operation::Inventory);
         let mut path = self.v2_path();
         path.push_str("/inventory");
         path.push_str("/at");
         path.push_str(&format!("/{}", date));
-        let resp = reqwasm::http::Request::get(&path).send().await?;
+        let resp = self.get(&path).await?;
         if resp.status() != 200 {
-            Err(format!("Status: {}", resp.status()).into())
+            Err(self.response_error(resp).await)
         } else {
             debug!("We got a valid response back");
             let InventoryData {
@@ -646,6 +1544,518 @@ impl HttpStore {
         }
     }
 
+    /// Compare the inventory for two plans. Comparing a plan with itself
+    /// shows what changed since it was first saved.
+    pub async fn fetch_inventory_diff(
+        &self,
+        from: &NaiveDate,
+        to: &NaiveDate,
+    ) -> Result<InventoryDiff, Error> {
+        let _in_flight = self.tracker.start(Operation::Inventory);
+        let mut path = self.v2_path();
+        path.push_str(&format!("/inventory/diff?from={}&to={}", from, to));
+        let resp = self.get(&path).await?;
+        if resp.status() != 200 {
+            Err(self.response_error(resp).await)
+        } else {
+            Ok(resp
+                .json::<InventoryDiffResponse>()
+                .await
+                .map_err(|e| format!("{}", e))?
+                .as_success()
+                .unwrap_or_default())
+        }
+    }
+
+    pub async fn fetch_plan_rules(&self) -> Result<Vec<StoredPlanRule>, Error> {
+        let mut path = self.v2_path();
+        path.push_str("/plan/rules");
+        let resp = self.get(&path).await?;
+        if resp.status() != 200 {
+            Err(self.response_error(resp).await)
+        } else {
+            Ok(resp
+                .json::<PlanRulesResponse>()
+                .await
+                .map_err(|e| format!("{}", e))?
+                .as_success()
+                .unwrap_or_default())
+        }
+    }
+
+    #[instrument]
+    pub async fn save_plan_rule(&self, rule: &PlanRule) -> Result<Option<StoredPlanRule>, Error> {
+        let mut path = self.v2_path();
+        path.push_str("/plan/rules");
+        let serialized = to_string(rule).expect("Unable to serialize plan rule");
+        let request = transport::Request::post(&path)
+            .body(&serialized)
+            .header("content-type", "application/json");
+        let resp = self.send_change(request).await?;
+        if resp.status() != 200 {
+            Err(self.response_error(resp).await)
+        } else {
+            Ok(resp
+                .json::<PlanRuleResponse>()
+                .await
+                .map_err(|e| format!("{}", e))?
+                .as_success())
+        }
+    }
+
+    #[instrument]
+    pub async fn delete_plan_rule(&self, id: i64) -> Result<(), Error> {
+        let mut path = self.v2_path();
+        path.push_str(&format!("/plan/rules/{}", id));
+        let resp = self.send_change(transport::Request::delete(&path)).await?;
+        if resp.status() != 200 {
+            Err(self.response_error(resp).await)
+        } else {
+            Ok(())
+        }
+    }
+
+    pub async fn fetch_freezer(&self) -> Result<Vec<FreezerItem>, Error> {
+        let mut path = self.v2_path();
+        path.push_str("/freezer");
+        let resp = self.get(&path).await?;
+        if resp.status() != 200 {
+            Err(self.response_error(resp).await)
+        } else {
+            Ok(resp
+                .json::<FreezerResponse>()
+                .await
+                .map_err(|e| format!("{}", e))?
+                .as_success()
+                .unwrap_or_default())
+        }
+    }
+
+    #[instrument]
+    pub async fn add_freezer_item(
+        &self,
+        item: &NewFreezerItem,
+    ) -> Result<Option<FreezerItem>, Error> {
+        let mut path = self.v2_path();
+        path.push_str("/freezer");
+        let serialized = to_string(item).expect("Unable to serialize freezer item");
+        let request = transport::Request::post(&path)
+            .body(&serialized)
+            .header("content-type", "application/json");
+        let resp = self.send_change(request).await?;
+        if resp.status() != 200 {
+            Err(self.response_error(resp).await)
+        } else {
+            Ok(resp
+                .json::<FreezerItemResponse>()
+                .await
+                .map_err(|e| format!("{}", e))?
+                .as_success()
+                .flatten())
+        }
+    }
+
+    /// Use up some portions of a freezer item. Returns what is left, if anything.
+    #[instrument]
+    pub async fn take_freezer_portions(
+        &self,
+        id: i64,
+        portions: i32,
+    ) -> Result<Option<FreezerItem>, Error> {
+        let mut path = self.v2_path();
+        path.push_str(&format!("/freezer/{}/take", id));
+        let request = transport::Request::post(&path)
+            .body(to_string(&portions).expect("Unable to serialize portions"))
+            .header("content-type", "application/json");
+        let resp = self.send_change(request).await?;
+        if resp.status() != 200 {
+            Err(self.response_error(resp).await)
+        } else {
+            Ok(resp
+                .json::<FreezerItemResponse>()
+                .await
+                .map_err(|e| format!("{}", e))?
+                .as_success()
+                .flatten())
+        }
+    }
+
+    #[instrument]
+    pub async fn delete_freezer_item(&self, id: i64) -> Result<(), Error> {
+        let mut path = self.v2_path();
+        path.push_str(&format!("/freezer/{}", id));
+        let resp = self.send_change(transport::Request::delete(&path)).await?;
+        if resp.status() != 200 {
+            Err(self.response_error(resp).await)
+        } else {
+            Ok(())
+        }
+    }
+
+    pub async fn fetch_cooked_for_date(&self, date: &NaiveDate) -> Result<BTreeSet<String>, Error> {
+        let mut path = self.v2_path();
+        path.push_str(&format!("/plan/at/{}/cooked", date));
+        let resp = self.get(&path).await?;
+        if resp.status() != 200 {
+            Err(self.response_error(resp).await)
+        } else {
+            Ok(resp
+                .json::<CookedResponse>()
+                .await
+                .map_err(|e| format!("{}", e))?
+                .as_success()
+                .unwrap_or_default())
+        }
+    }
+
+    #[instrument]
+    pub async fn mark_cooked(&self, date: &NaiveDate, cooked: &MarkCooked) -> Result<(), Error> {
+        let mut path = self.v2_path();
+        path.push_str(&format!("/plan/at/{}/cooked", date));
+        let request = transport::Request::post(&path)
+            .body(to_string(cooked).expect("Unable to serialize cooked recipe"))
+            .header("content-type", "application/json");
+        let resp = self.send_change(request).await?;
+        if resp.status() != 200 {
+            Err(self.response_error(resp).await)
+        } else {
+            Ok(())
+        }
+    }
+
+    pub async fn fetch_last_cooked(&self) -> Result<BTreeMap<String, NaiveDate>, Error> {
+        let mut path = self.v2_path();
+        path.push_str("/recipes/last_cooked");
+        let resp = self.get(&path).await?;
+        if resp.status() != 200 {
+            Err(self.response_error(resp).await)
+        } else {
+            Ok(resp
+                .json::<LastCookedResponse>()
+                .await
+                .map_err(|e| format!("{}", e))?
+                .as_success()
+                .unwrap_or_default())
+        }
+    }
+
+    /// The recipes planned the most.
+    pub async fn fetch_recipe_stats(&self) -> Result<Vec<RecipeCount>, Error> {
+        let mut path = self.v2_path();
+        path.push_str("/stats/recipes");
+        let resp = self.get(&path).await?;
+        if resp.status() != 200 {
+            Err(self.response_error(resp).await)
+        } else {
+            Ok(resp
+                .json::<RecipeCountsResponse>()
+                .await
+                .map_err(|e| format!("{}", e))?
+                .as_success()
+                .unwrap_or_default())
+        }
+    }
+
+    /// The ingredients used by the most planned meals.
+    pub async fn fetch_ingredient_stats(&self) -> Result<Vec<IngredientCount>, Error> {
+        let mut path = self.v2_path();
+        path.push_str("/stats/ingredients");
+        let resp = self.get(&path).await?;
+        if resp.status() != 200 {
+            Err(self.response_error(resp).await)
+        } else {
+            Ok(resp
+                .json::<IngredientCountsResponse>()
+                .await
+                .map_err(|e| format!("{}", e))?
+                .as_success()
+                .unwrap_or_default())
+        }
+    }
+
+    /// Plans per month and recipes per week.
+    pub async fn fetch_plan_stats(&self) -> Result<PlanStats, Error> {
+        let mut path = self.v2_path();
+        path.push_str("/stats/plans");
+        let resp = self.get(&path).await?;
+        if resp.status() != 200 {
+            Err(self.response_error(resp).await)
+        } else {
+            Ok(resp
+                .json::<PlanStatsResponse>()
+                .await
+                .map_err(|e| format!("{}", e))?
+                .as_success()
+                .unwrap_or_default())
+        }
+    }
+
+    /// The titles and times of the recipes that aren't archived.
+    pub async fn fetch_recipe_index(&self) -> Result<Vec<RecipeIndexEntry>, Error> {
+        let mut path = self.v2_path();
+        path.push_str("/recipes/index");
+        let resp = self.get(&path).await?;
+        if resp.status() != 200 {
+            Err(self.response_error(resp).await)
+        } else {
+            Ok(resp
+                .json::<RecipeIndexResponse>()
+                .await
+                .map_err(|e| format!("{}", e))?
+                .as_success()
+                .unwrap_or_default())
+        }
+    }
+
+    pub async fn fetch_archived(&self) -> Result<BTreeSet<String>, Error> {
+        let mut path = self.v2_path();
+        path.push_str("/recipes/archived");
+        let resp = self.get(&path).await?;
+        if resp.status() != 200 {
+            Err(self.response_error(resp).await)
+        } else {
+            Ok(resp
+                .json::<ArchivedResponse>()
+                .await
+                .map_err(|e| format!("{}", e))?
+                .as_success()
+                .unwrap_or_default())
+        }
+    }
+
+    /// The substitutions for ingredients with the user's own in place of the
+    /// server's defaults.
+    pub async fn fetch_substitutions(&self) -> Result<BTreeMap<String, Vec<String>>, Error> {
+        let mut path = self.v2_path();
+        path.push_str("/substitutions");
+        let resp = self.get(&path).await?;
+        if resp.status() != 200 {
+            Err(self.response_error(resp).await)
+        } else {
+            Ok(resp
+                .json::<SubstitutionsResponse>()
+                .await
+                .map_err(|e| format!("{}", e))?
+                .as_success()
+                .unwrap_or_default())
+        }
+    }
+
+    /// Archive a recipe or bring it back from the archive.
+    pub async fn set_recipe_archived(&self, recipe_id: &str, archived: bool) -> Result<(), Error> {
+        let mut path = self.v2_path();
+        path.push_str(&format!("/recipe/{}/archive", recipe_id));
+        let request = if archived {
+            transport::Request::post(&path)
+        } else {
+            transport::Request::delete(&path)
+        };
+        let resp = self.send_change(request).await?;
+        if resp.status() != 200 {
+            Err(self.response_error(resp).await)
+        } else {
+            Ok(())
+        }
+    }
+
+    async fn post_bulk<T: serde::Serialize>(
+        &self,
+        action: &str,
+        body: &T,
+    ) -> Result<BulkResult, Error> {
+        let mut path = self.v2_path();
+        path.push_str(&format!("/recipes/bulk/{}", action));
+        let serialized = to_string(body).expect("Unable to serialize bulk request");
+        let request = transport::Request::post(&path)
+            .body(&serialized)
+            .header("content-type", "application/json");
+        let resp = self.send_change(request).await?;
+        if resp.status() != 200 {
+            Err(self.response_error(resp).await)
+        } else {
+            Ok(resp
+                .json::<BulkResponse>()
+                .await
+                .map_err(|e| format!("{}", e))?
+                .as_success()
+                .unwrap_or_default())
+        }
+    }
+
+    /// Delete a set of recipes in one request.
+    #[instrument(skip(recipe_ids), fields(count=recipe_ids.len()))]
+    pub async fn bulk_delete(&self, recipe_ids: Vec<String>) -> Result<BulkResult, Error> {
+        self.post_bulk("delete", &BulkRecipes { recipe_ids }).await
+    }
+
+    /// Archive or unarchive a set of recipes in one request.
+    #[instrument(skip(recipe_ids), fields(count=recipe_ids.len()))]
+    pub async fn bulk_archive(
+        &self,
+        recipe_ids: Vec<String>,
+        archived: bool,
+    ) -> Result<BulkResult, Error> {
+        self.post_bulk(
+            "archive",
+            &BulkArchive {
+                recipe_ids,
+                archived,
+            },
+        )
+        .await
+    }
+
+    /// Add and remove tags on a set of recipes in one request.
+    #[instrument(skip(recipe_ids), fields(count=recipe_ids.len()))]
+    pub async fn bulk_tags(
+        &self,
+        recipe_ids: Vec<String>,
+        add: BTreeSet<String>,
+        remove: BTreeSet<String>,
+    ) -> Result<BulkResult, Error> {
+        self.post_bulk(
+            "tags",
+            &BulkTags {
+                recipe_ids,
+                add,
+                remove,
+            },
+        )
+        .await
+    }
+
+    /// Set or clear the category of a set of recipes in one request.
+    #[instrument(skip(recipe_ids), fields(count=recipe_ids.len()))]
+    pub async fn bulk_category(
+        &self,
+        recipe_ids: Vec<String>,
+        category: Option<String>,
+    ) -> Result<BulkResult, Error> {
+        self.post_bulk(
+            "category",
+            &BulkCategory {
+                recipe_ids,
+                category,
+            },
+        )
+        .await
+    }
+
+    /// The plans in the current week. The server decides which week that is
+    /// from the user's timezone and week start.
+    pub async fn fetch_this_week(&self) -> Result<PlanWeek, Error> {
+        let mut path = self.v2_path();
+        path.push_str("/plan/week");
+        let resp = self.get(&path).await?;
+        if resp.status() != 200 {
+            Err(self.response_error(resp).await)
+        } else {
+            Ok(resp
+                .json::<PlanWeekResponse>()
+                .await
+                .map_err(|e| format!("{}", e))?
+                .as_success()
+                .ok_or_else(|| "No week in the response".to_owned())?)
+        }
+    }
+
+    /// The share link for the week `date` is in if the week is shared.
+    pub async fn fetch_week_share(&self, date: &NaiveDate) -> Result<Option<PlanShare>, Error> {
+        let mut path = self.v2_path();
+        path.push_str(&format!("/plan/week/{}/share", date));
+        let resp = self.get(&path).await?;
+        if resp.status() != 200 {
+            Err(self.response_error(resp).await)
+        } else {
+            Ok(resp
+                .json::<PlanShareResponse>()
+                .await
+                .map_err(|e| format!("{}", e))?
+                .as_success()
+                .flatten())
+        }
+    }
+
+    /// Share the week `date` is in. Sharing a shared week again returns the
+    /// link it already has.
+    #[instrument]
+    pub async fn share_week(&self, date: &NaiveDate) -> Result<PlanShare, Error> {
+        let mut path = self.v2_path();
+        path.push_str(&format!("/plan/week/{}/share", date));
+        let resp = self.send_change(transport::Request::post(&path)).await?;
+        if resp.status() != 200 {
+            Err(self.response_error(resp).await)
+        } else {
+            Ok(resp
+                .json::<PlanShareResponse>()
+                .await
+                .map_err(|e| format!("{}", e))?
+                .as_success()
+                .flatten()
+                .ok_or_else(|| "No share in the response".to_owned())?)
+        }
+    }
+
+    /// Stop sharing the week `date` is in.
+    #[instrument]
+    pub async fn unshare_week(&self, date: &NaiveDate) -> Result<(), Error> {
+        let mut path = self.v2_path();
+        path.push_str(&format!("/plan/week/{}/share", date));
+        let resp = self.send_change(transport::Request::delete(&path)).await?;
+        if resp.status() != 200 {
+            Err(self.response_error(resp).await)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Check a plan that may not be saved yet against the user's rules.
+    #[instrument(skip(plan))]
+    pub async fn check_plan(
+        &self,
+        date: &NaiveDate,
+        plan: &Vec<(String, i32)>,
+    ) -> Result<Vec<RuleViolation>, Error> {
+        let mut path = self.v2_path();
+        path.push_str(&format!("/plan/at/{}/check", date));
+        let serialized = to_string(plan).expect("Unable to serialize plan");
+        // Plans are checked in the background as they change so we don't
+        // notify the user when a check fails.
+        let request = transport::Request::post(&path)
+            .body(&serialized)
+            .header("content-type", "application/json");
+        let resp = self.transport.send(request).await?;
+        if resp.status() != 200 {
+            Err(error_from_response(resp).await)
+        } else {
+            Ok(resp
+                .json::<RuleViolationsResponse>()
+                .await
+                .map_err(|e| format!("{}", e))?
+                .as_success()
+                .unwrap_or_default())
+        }
+    }
+
+    pub async fn fetch_plan_suggestions(
+        &self,
+        date: &NaiveDate,
+    ) -> Result<Vec<PlanSuggestion>, Error> {
+        let mut path = self.v2_path();
+        path.push_str(&format!("/plan/at/{}/suggestions", date));
+        let resp = self.get(&path).await?;
+        if resp.status() != 200 {
+            Err(self.response_error(resp).await)
+        } else {
+            Ok(resp
+                .json::<PlanSuggestionsResponse>()
+                .await
+                .map_err(|e| format!("{}", e))?
+                .as_success()
+                .unwrap_or_default())
+        }
+    }
+
     pub async fn fetch_inventory_data(
         &self,
     ) -> Result<
@@ -656,11 +2066,12 @@ impl HttpStore {
         ),
         Error,
     > {
+        let _in_flight = self.tracker.start(Operation::Inventory);
         let mut path = self.v2_path();
         path.push_str("/inventory");
-        let resp = reqwasm::http::Request::get(&path).send().await?;
+        let resp = self.get(&path).await?;
         if resp.status() != 200 {
-            Err(format!("Status: {}", resp.status()).into())
+            Err(self.response_error(resp).await)
         } else {
             debug!("We got a valid response back");
             let InventoryData {
@@ -681,35 +2092,29 @@ impl HttpStore {
         }
     }
 
+    /// Send item level changes to the inventory for a plan. Returns the
+    /// inventory with everyone's changes merged.
     #[instrument]
-    pub async fn store_inventory_data_for_date(
+    pub async fn update_inventory_for_date(
         &self,
-        filtered_ingredients: BTreeSet<IngredientKey>,
-        modified_amts: BTreeMap<IngredientKey, String>,
-        extra_items: Vec<(String, String)>,
+        ops: &Vec<InventoryOp>,
         date: &NaiveDate,
-    ) -> Result<(), Error> {
+    ) -> Result<InventoryData, Error> {
         let mut path = self.v2_path();
-        path.push_str("/inventory");
-        path.push_str("/at");
-        path.push_str(&format!("/{}", date));
-        let filtered_ingredients: Vec<IngredientKey> = filtered_ingredients.into_iter().collect();
-        let modified_amts: Vec<(IngredientKey, String)> = modified_amts.into_iter().collect();
-        debug!("Storing inventory data in cache");
-        let serialized_inventory = to_string(&(filtered_ingredients, modified_amts, extra_items))
-            .expect("Unable to encode plan as json");
-        debug!("Storing inventory data via API");
-        let resp = reqwasm::http::Request::post(&path)
-            .body(&serialized_inventory)
-            .header("content-type", "application/json")
-            .send()
-            .await?;
+        path.push_str(&format!("/inventory/at/{}/ops", date));
+        let request = transport::Request::post(&path)
+            .body(to_string(ops).expect("Unable to encode inventory changes as json"))
+            .header("content-type", "application/json");
+        let resp = self.send_change(request).await?;
         if resp.status() != 200 {
-            debug!("Invalid response back");
-            Err(format!("Status: {}", resp.status()).into())
+            Err(self.response_error(resp).await)
         } else {
-            debug!("We got a valid response back!");
-            Ok(())
+            Ok(resp
+                .json::<InventoryResponse>()
+                .await
+                .map_err(|e| format!("{}", e))?
+                .as_success()
+                .ok_or_else(|| "No inventory in the response".to_owned())?)
         }
     }
 
@@ -728,14 +2133,49 @@ impl HttpStore {
         let serialized_inventory = to_string(&(filtered_ingredients, modified_amts, extra_items))
             .expect("Unable to encode plan as json");
         debug!("Storing inventory data via API");
-        let resp = reqwasm::http::Request::post(&path)
+        let request = transport::Request::post(&path)
             .body(&serialized_inventory)
-            .header("content-type", "application/json")
-            .send()
-            .await?;
+            .header("content-type", "application/json");
+        let resp = self.send_change(request).await?;
+        if resp.status() != 200 {
+            debug!("Invalid response back");
+            Err(self.response_error(resp).await)
+        } else {
+            debug!("We got a valid response back!");
+            Ok(())
+        }
+    }
+
+    pub async fn fetch_settings(&self) -> Result<BTreeMap<String, String>, Error> {
+        let _in_flight = self.tracker.start(Operation::Settings);
+        let mut path = self.v2_path();
+        path.push_str("/settings");
+        let resp = self.get(&path).await?;
         if resp.status() != 200 {
             debug!("Invalid response back");
-            Err(format!("Status: {}", resp.status()).into())
+            Err(self.response_error(resp).await)
+        } else {
+            Ok(resp
+                .json::<UserSettingsResponse>()
+                .await
+                .map_err(|e| format!("{}", e))?
+                .as_success()
+                .unwrap_or_default())
+        }
+    }
+
+    pub async fn store_settings(&self, settings: &BTreeMap<String, String>) -> Result<(), Error> {
+        let mut path = self.v2_path();
+        path.push_str("/settings");
+        let serialized_settings =
+            to_string(settings).expect("Failed to serialize settings to json");
+        let request = transport::Request::post(&path)
+            .body(&serialized_settings)
+            .header("content-type", "application/json");
+        let resp = self.send_change(request).await?;
+        if resp.status() != 200 {
+            debug!("Invalid response back");
+            Err(self.response_error(resp).await)
         } else {
             debug!("We got a valid response back!");
             Ok(())
@@ -743,12 +2183,13 @@ impl HttpStore {
     }
 
     pub async fn fetch_staples(&self) -> Result<Option<String>, Error> {
+        let _in_flight = self.tracker.start(Operation::Staples);
         let mut path = self.v2_path();
         path.push_str("/staples");
-        let resp = reqwasm::http::Request::get(&path).send().await?;
+        let resp = self.get(&path).await?;
         if resp.status() != 200 {
             debug!("Invalid response back");
-            Err(format!("Status: {}", resp.status()).into())
+            Err(self.response_error(resp).await)
         } else {
             Ok(resp
                 .json::<Response<Option<String>>>()
@@ -759,22 +2200,32 @@ impl HttpStore {
         }
     }
 
+    /// Send a GET to a path under the v2 api and hand back the status and
+    /// body as they are. The api explorer uses this to show raw responses.
+    pub async fn fetch_raw(&self, path: &str) -> Result<(u16, String), Error> {
+        let mut full_path = self.v2_path();
+        full_path.push_str(path);
+        let resp = self.get(&full_path).await?;
+        let status = resp.status();
+        Ok((status, resp.text().await?))
+    }
+
     pub async fn store_staples<S: AsRef<str>>(&self, content: S) -> Result<(), Error> {
         let mut path = self.v2_path();
         path.push_str("/staples");
         let serialized_staples: String =
             to_string(content.as_ref()).expect("Failed to serialize staples to json");
 
-        let resp = reqwasm::http::Request::post(&path)
+        let request = transport::Request::post(&path)
             .body(&serialized_staples)
-            .header("content-type", "application/json")
-            .send()
-            .await?;
+            .header("content-type", "application/json");
+        let resp = self.send_change(request).await?;
         if resp.status() != 200 {
             debug!("Invalid response back");
-            Err(format!("Status: {}", resp.status()).into())
+            Err(self.response_error(resp).await)
         } else {
             debug!("We got a valid response back!");
+            self.notifier.info("Updated staples");
             Ok(())
         }
     }
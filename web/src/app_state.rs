@@ -12,13 +12,23 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 use std::{
-    collections::{BTreeMap, BTreeSet},
+    cell::RefCell,
+    collections::{hash_map::DefaultHasher, BTreeMap, BTreeSet},
     fmt::Debug,
+    future::Future,
+    hash::{Hash, Hasher},
+    time::Duration,
 };
 
 use chrono::NaiveDate;
-use client_api::UserData;
-use recipes::{parse, Ingredient, IngredientKey, Recipe, RecipeEntry};
+use client_api::{InventoryOp, MarkCooked, PlanChange, PlanMeals, UserData, WeekStart};
+use recipes::{
+    diet::DietTable,
+    parse,
+    shopping::ShoppingPlan,
+    unit::{DensityTable, Quantity},
+    Ingredient, IngredientAccumulator, IngredientKey, MealType, Recipe, RecipeEntry,
+};
 use serde::{Deserialize, Serialize};
 use sycamore::futures::spawn_local_scoped;
 use sycamore::prelude::*;
@@ -27,14 +37,29 @@ use tracing::{debug, error, info, instrument, warn};
 use wasm_bindgen::throw_str;
 
 use crate::{
+    action_log::ActionLog,
     api::{HttpStore, LocalStore},
-    components, linear::LinearSignal,
+    components,
+    i18n::{self, Locale},
+    linear::LinearSignal,
+    theme::{self, Theme},
 };
 
 fn bool_true() -> bool {
     true
 }
 
+/// Totals for the recipes in the plan.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PlanSummary {
+    /// The number of meals planned counting each time a recipe is made.
+    pub meals: usize,
+    /// The number of different recipes planned.
+    pub recipes: usize,
+    /// The total time to cook everything in the plan.
+    pub cook_time: Duration,
+}
+
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct AppState {
     pub recipe_counts: BTreeMap<String, usize>,
@@ -52,6 +77,65 @@ pub struct AppState {
     pub selected_plan_date: Option<NaiveDate>,
     #[serde(default = "bool_true")]
     pub use_staples: bool,
+    /// Show temperatures in Celsius or Fahrenheit. None shows them in the
+    /// unit the recipe was written in.
+    #[serde(default)]
+    pub celsius: Option<bool>,
+    /// Show baking ingredients by weight when we know their density.
+    #[serde(default)]
+    pub use_weights: bool,
+    /// The user's ingredient densities in grams per cup.
+    #[serde(default)]
+    pub densities: BTreeMap<String, u32>,
+    /// How many people planned recipes are scaled to feed.
+    #[serde(default)]
+    pub household_size: Option<u32>,
+    /// How many months of plan history the server keeps for the user.
+    #[serde(default)]
+    pub retention_months: Option<u32>,
+    /// The user's allergen and dietary tags for ingredients.
+    #[serde(default)]
+    pub ingredient_tags: BTreeMap<String, BTreeSet<String>>,
+    /// The tags the household avoids.
+    #[serde(default)]
+    pub restrictions: BTreeSet<String>,
+    #[serde(default)]
+    pub theme: Theme,
+    /// The day weeks start on when plans are grouped by week.
+    #[serde(default)]
+    pub week_start: WeekStart,
+    /// The language for the ui. Without one we use the browser's.
+    #[serde(default)]
+    pub locale: Option<Locale>,
+    /// Whether the timezone the server uses for us follows this device.
+    #[serde(default = "bool_true")]
+    pub timezone_from_device: bool,
+    /// The timezone the server uses for us as a UTC offset like `+01:00`.
+    #[serde(default)]
+    pub timezone: Option<String>,
+    /// The order we intend to cook the planned recipes in.
+    #[serde(default)]
+    pub plan_order: Vec<String>,
+    /// The meals planned recipes fill when they differ from the meal in the
+    /// recipe's metadata. This used to hold a single meal per recipe so it is
+    /// stored under a new name to keep old saved states loading.
+    #[serde(default, rename = "plan_meal_slots")]
+    pub plan_meals: PlanMeals,
+    /// Planned recipes that come out of the freezer instead of being cooked.
+    #[serde(default)]
+    pub plan_freezer_meals: BTreeSet<String>,
+    /// The planned recipes that have been cooked.
+    #[serde(default)]
+    pub cooked: BTreeSet<String>,
+    /// The date each recipe was last cooked.
+    #[serde(default)]
+    pub last_cooked: BTreeMap<String, NaiveDate>,
+    /// Recipes that are hidden from planning but not deleted.
+    #[serde(default)]
+    pub archived: BTreeSet<String>,
+    /// What can be used in place of an ingredient keyed by the ingredient.
+    #[serde(default)]
+    pub substitutions: BTreeMap<String, Vec<String>>,
 }
 
 impl AppState {
@@ -69,6 +153,212 @@ impl AppState {
             plan_dates: BTreeSet::new(),
             selected_plan_date: None,
             use_staples: true,
+            celsius: None,
+            use_weights: false,
+            densities: BTreeMap::new(),
+            household_size: None,
+            retention_months: None,
+            ingredient_tags: BTreeMap::new(),
+            restrictions: BTreeSet::new(),
+            theme: Theme::default(),
+            week_start: WeekStart::default(),
+            locale: None,
+            timezone_from_device: true,
+            timezone: None,
+            plan_order: Vec::new(),
+            plan_meals: BTreeMap::new(),
+            plan_freezer_meals: BTreeSet::new(),
+            cooked: BTreeSet::new(),
+            last_cooked: BTreeMap::new(),
+            archived: BTreeSet::new(),
+            substitutions: BTreeMap::new(),
+        }
+    }
+
+    /// The default densities with the user's overrides applied.
+    pub fn density_table(&self) -> DensityTable {
+        DensityTable::default().with_overrides(self.densities.iter())
+    }
+
+    /// What goes into the shopping list for the current plan.
+    pub fn shopping_plan(&self, use_staples: bool) -> ShoppingPlan {
+        ShoppingPlan {
+            recipe_counts: self.recipe_counts.clone(),
+            freezer_meals: self.plan_freezer_meals.clone(),
+            household_size: self.household_size,
+            staples: if use_staples {
+                self.staples.clone()
+            } else {
+                None
+            },
+            filtered_ingredients: self.filtered_ingredients.clone(),
+            densities: Some(self.density_table()),
+        }
+    }
+
+    /// The plan dates grouped by the week they are in with the newest weeks
+    /// and plans first.
+    pub fn plan_weeks(&self) -> Vec<(NaiveDate, Vec<NaiveDate>)> {
+        let mut weeks: BTreeMap<NaiveDate, Vec<NaiveDate>> = BTreeMap::new();
+        for date in self.plan_dates.iter().rev() {
+            weeks
+                .entry(self.week_start.start_of_week(*date))
+                .or_default()
+                .push(*date);
+        }
+        weeks.into_iter().rev().collect()
+    }
+
+    /// The meals a recipe is planned for.
+    pub fn meals_for(&self, recipe_id: &str) -> BTreeSet<MealType> {
+        client_api::planned_meals_for(&self.plan_meals, recipe_id, self.recipes.get(recipe_id))
+    }
+
+    /// The planned meals for the recipes in the plan.
+    pub fn planned_meals(&self) -> PlanMeals {
+        self.planned_recipes()
+            .into_iter()
+            .filter_map(|(id, _)| self.plan_meals.get(&id).map(|meals| (id, meals.clone())))
+            .collect()
+    }
+
+    /// The planned recipes that come out of the freezer.
+    pub fn planned_freezer_meals(&self) -> BTreeSet<String> {
+        self.planned_recipes()
+            .into_iter()
+            .map(|(id, _)| id)
+            .filter(|id| self.plan_freezer_meals.contains(id))
+            .collect()
+    }
+
+    /// The planned recipes that conflict with the household's restrictions
+    /// as the recipe title, the restriction, and the conflicting ingredients.
+    pub fn diet_conflicts(&self) -> Vec<(String, String, BTreeSet<String>)> {
+        let table = DietTable::new(self.ingredient_tags.iter());
+        let mut conflicts = Vec::new();
+        for (id, _) in self.planned_recipes() {
+            let recipe = match self.recipes.get(&id) {
+                Some(recipe) => recipe,
+                None => continue,
+            };
+            let mut acc = IngredientAccumulator::new();
+            acc.accumulate_from_with_references(&id, recipe, &self.recipes);
+            let ingredients = acc.ingredients();
+            for (restriction, names) in
+                table.conflicts(ingredients.values().map(|(i, _)| i), &self.restrictions)
+            {
+                conflicts.push((recipe.title.clone(), restriction, names));
+            }
+        }
+        conflicts
+    }
+
+    /// How many meals and recipes are in the plan and how long cooking them
+    /// takes. Meals from the freezer are already cooked so their time isn't
+    /// counted.
+    pub fn plan_summary(&self) -> PlanSummary {
+        let mut summary = PlanSummary::default();
+        for (id, count) in self.planned_recipes() {
+            summary.meals += count;
+            summary.recipes += 1;
+            if self.plan_freezer_meals.contains(&id) {
+                continue;
+            }
+            if let Some(recipe) = self.recipes.get(&id) {
+                summary.cook_time += recipe.total_time() * count as u32;
+            }
+        }
+        summary
+    }
+
+    /// The factor a planned recipe is scaled by to feed the household.
+    pub fn plan_scale(&self, recipe_id: &str) -> Quantity {
+        match (self.household_size, self.recipes.get(recipe_id)) {
+            (Some(people), Some(recipe)) => recipe.serving_scale(people),
+            _ => Quantity::whole(1),
+        }
+    }
+
+    /// The recipes with a non-zero count in the order we intend to cook them.
+    /// Recipes that are missing from the plan order come last sorted by id.
+    pub fn planned_recipes(&self) -> Vec<(String, usize)> {
+        let mut planned = Vec::new();
+        for id in self.plan_order.iter() {
+            match self.recipe_counts.get(id) {
+                Some(count) if *count > 0 => planned.push((id.clone(), *count)),
+                _ => (),
+            }
+        }
+        for (id, count) in self.recipe_counts.iter() {
+            if *count > 0 && !self.plan_order.contains(id) {
+                planned.push((id.clone(), *count));
+            }
+        }
+        planned
+    }
+
+    fn set_plan(&mut self, plan: Vec<(String, i32)>) {
+        self.plan_order = plan
+            .iter()
+            .filter(|(_, count)| *count > 0)
+            .map(|(id, _)| id.clone())
+            .collect();
+        self.recipe_counts = BTreeMap::from_iter(plan.into_iter().map(|(k, v)| (k, v as usize)));
+    }
+
+    /// Put a recipe count back to what it was before a change that failed to
+    /// sync. Nothing happens if the count has changed again since.
+    fn undo_recipe_count(
+        &mut self,
+        id: &str,
+        count: usize,
+        previous: usize,
+        position: Option<usize>,
+    ) {
+        if self.recipe_counts.get(id).cloned().unwrap_or(0) != count {
+            return;
+        }
+        self.recipe_counts.insert(id.to_owned(), previous);
+        self.plan_order.retain(|i| i != id);
+        if previous > 0 {
+            let position = position.unwrap_or(self.plan_order.len());
+            self.plan_order
+                .insert(position.min(self.plan_order.len()), id.to_owned());
+        }
+    }
+
+    /// Take the counts from a plan the server merged with changes made
+    /// elsewhere. Our cooking order is kept and newly planned recipes go at
+    /// the end of it.
+    fn merge_plan_counts(&mut self, plan: Vec<(String, i32)>) {
+        for count in self.recipe_counts.values_mut() {
+            *count = 0;
+        }
+        for (id, count) in plan {
+            let count = count as usize;
+            if count > 0 && !self.plan_order.contains(&id) {
+                self.plan_order.push(id.clone());
+            }
+            self.recipe_counts.insert(id, count);
+        }
+        let counts = &self.recipe_counts;
+        self.plan_order
+            .retain(|id| counts.get(id).map(|count| *count > 0).unwrap_or(false));
+    }
+
+    /// Move everything we track for a recipe over to a new id.
+    fn rename_recipe(&mut self, old_id: &str, new_id: &str) {
+        if let Some(recipe) = self.recipes.remove(old_id) {
+            self.recipes.insert(new_id.to_owned(), recipe);
+        }
+        if let Some(count) = self.recipe_counts.remove(old_id) {
+            self.recipe_counts.insert(new_id.to_owned(), count);
+        }
+        if let Some(category) = self.recipe_categories.remove(old_id) {
+            self.recipe_categories.insert(new_id.to_owned(), category);
+        }
+        for id in self.plan_order.iter_mut().filter(|id| *id == old_id) {
+            *id = new_id.to_owned();
         }
     }
 }
@@ -76,22 +366,68 @@ impl AppState {
 pub enum Message {
     ResetRecipeCounts,
     UpdateRecipeCount(String, usize),
+    ReorderPlan(Vec<String>),
+    /// Add a planned recipe to a meal or take it out of one.
+    TogglePlanMeal(String, MealType),
+    /// Mark whether a planned recipe comes out of the freezer.
+    SetPlanFreezerMeal(String, bool),
+    /// Record a planned recipe as cooked.
+    MarkCooked(MarkCooked),
     AddExtra(String, String),
     RemoveExtra(usize),
     UpdateExtra(usize, String, String),
     SaveRecipe(RecipeEntry, Option<Box<dyn FnOnce()>>),
     RemoveRecipe(String, Option<Box<dyn FnOnce()>>),
+    /// A recipe the server already restored from the trash.
+    RecipeRestored(RecipeEntry),
+    /// Archive or unarchive a recipe.
+    SetArchived(String, bool),
     UpdateCategory(String, String, Option<Box<dyn FnOnce()>>),
+    UpdateCategories(Vec<(String, String)>, Option<Box<dyn FnOnce()>>),
     ResetInventory,
     AddFilteredIngredient(IngredientKey),
+    ToggleCheckedIngredient(IngredientKey),
     UpdateAmt(IngredientKey, String),
     SetUserData(UserData),
     SaveState(Option<Box<dyn FnOnce()>>),
     LoadState(Option<Box<dyn FnOnce()>>),
+    /// Fetch the recipes after changing them on the server.
+    RefreshRecipes,
+    /// Fetch the ingredient categories and staples.
+    RefreshCategories,
+    /// Fetch the plan for the selected date. Unsaved changes to it are lost.
+    RefreshPlan,
+    /// Fetch the inventory for the selected date.
+    RefreshInventory,
     UpdateStaples(String, Option<Box<dyn FnOnce()>>),
     DeletePlan(NaiveDate, Option<Box<dyn FnOnce()>>),
     SelectPlanDate(NaiveDate, Option<Box<dyn FnOnce()>>),
+    /// Start a plan for today with the recipes from the plan for this date.
+    DuplicatePlan(NaiveDate, Option<Box<dyn FnOnce()>>),
     UpdateUseStaples(bool), // TODO(jwall): Should this just be various settings?
+    UpdateUseCelsius(Option<bool>),
+    UpdateUseWeights(bool),
+    /// Set or clear the user's density for an ingredient in grams per cup.
+    SetDensity(String, Option<u32>),
+    /// Set or clear the number of people planned recipes are scaled for.
+    UpdateHouseholdSize(Option<u32>),
+    /// Set or clear the months of plan history the server keeps.
+    UpdateRetentionMonths(Option<u32>),
+    /// Set the allergen and dietary tags for an ingredient. No tags clears
+    /// them.
+    SetIngredientTags(String, BTreeSet<String>),
+    /// Set the substitutes for an ingredient. No substitutes hides the
+    /// default ones.
+    SetSubstitutes(String, Vec<String>),
+    /// Set the tags the household avoids.
+    UpdateRestrictions(BTreeSet<String>),
+    SetTheme(Theme),
+    SetWeekStart(WeekStart),
+    SetLocale(Locale),
+    /// Use this timezone instead of the device's. None follows the device.
+    SetTimezone(Option<chrono::FixedOffset>),
+    /// Replace the whole state with a snapshot from the action log.
+    RestoreState(Box<AppState>),
 }
 
 impl Debug for Message {
@@ -103,6 +439,18 @@ impl Debug for Message {
                 .field(arg0)
                 .field(arg1)
                 .finish(),
+            Self::ReorderPlan(arg0) => f.debug_tuple("ReorderPlan").field(arg0).finish(),
+            Self::TogglePlanMeal(arg0, arg1) => f
+                .debug_tuple("TogglePlanMeal")
+                .field(arg0)
+                .field(arg1)
+                .finish(),
+            Self::SetPlanFreezerMeal(arg0, arg1) => f
+                .debug_tuple("SetPlanFreezerMeal")
+                .field(arg0)
+                .field(arg1)
+                .finish(),
+            Self::MarkCooked(arg0) => f.debug_tuple("MarkCooked").field(arg0).finish(),
             Self::AddExtra(arg0, arg1) => {
                 f.debug_tuple("AddExtra").field(arg0).field(arg1).finish()
             }
@@ -115,30 +463,352 @@ impl Debug for Message {
                 .finish(),
             Self::SaveRecipe(arg0, _) => f.debug_tuple("SaveRecipe").field(arg0).finish(),
             Self::RemoveRecipe(arg0, _) => f.debug_tuple("SetCategoryMap").field(arg0).finish(),
+            Self::RecipeRestored(arg0) => f.debug_tuple("RecipeRestored").field(arg0).finish(),
+            Self::SetArchived(id, archived) => f
+                .debug_tuple("SetArchived")
+                .field(id)
+                .field(archived)
+                .finish(),
             Self::UpdateCategory(i, c, _) => {
                 f.debug_tuple("UpdateCategory").field(i).field(c).finish()
             }
+            Self::UpdateCategories(arg0, _) => {
+                f.debug_tuple("UpdateCategories").field(arg0).finish()
+            }
             Self::ResetInventory => write!(f, "ResetInventory"),
             Self::AddFilteredIngredient(arg0) => {
                 f.debug_tuple("AddFilteredIngredient").field(arg0).finish()
             }
+            Self::ToggleCheckedIngredient(arg0) => {
+                f.debug_tuple("ToggleCheckedIngredient").field(arg0).finish()
+            }
             Self::UpdateAmt(arg0, arg1) => {
                 f.debug_tuple("UpdateAmt").field(arg0).field(arg1).finish()
             }
             Self::SetUserData(arg0) => f.debug_tuple("SetUserData").field(arg0).finish(),
             Self::SaveState(_) => write!(f, "SaveState"),
             Self::LoadState(_) => write!(f, "LoadState"),
+            Self::RefreshRecipes => write!(f, "RefreshRecipes"),
+            Self::RefreshCategories => write!(f, "RefreshCategories"),
+            Self::RefreshPlan => write!(f, "RefreshPlan"),
+            Self::RefreshInventory => write!(f, "RefreshInventory"),
             Self::UpdateStaples(arg, _) => f.debug_tuple("UpdateStaples").field(arg).finish(),
             Self::UpdateUseStaples(arg) => f.debug_tuple("UpdateUseStaples").field(arg).finish(),
+            Self::UpdateUseCelsius(arg) => f.debug_tuple("UpdateUseCelsius").field(arg).finish(),
+            Self::UpdateUseWeights(arg) => f.debug_tuple("UpdateUseWeights").field(arg).finish(),
+            Self::SetDensity(name, grams) => f
+                .debug_tuple("SetDensity")
+                .field(name)
+                .field(grams)
+                .finish(),
+            Self::UpdateHouseholdSize(arg) => {
+                f.debug_tuple("UpdateHouseholdSize").field(arg).finish()
+            }
+            Self::UpdateRetentionMonths(arg) => {
+                f.debug_tuple("UpdateRetentionMonths").field(arg).finish()
+            }
+            Self::SetIngredientTags(name, tags) => f
+                .debug_tuple("SetIngredientTags")
+                .field(name)
+                .field(tags)
+                .finish(),
+            Self::SetSubstitutes(name, substitutes) => f
+                .debug_tuple("SetSubstitutes")
+                .field(name)
+                .field(substitutes)
+                .finish(),
+            Self::UpdateRestrictions(arg) => {
+                f.debug_tuple("UpdateRestrictions").field(arg).finish()
+            }
+            Self::SetTheme(arg) => f.debug_tuple("SetTheme").field(arg).finish(),
+            Self::SetWeekStart(arg) => f.debug_tuple("SetWeekStart").field(arg).finish(),
+            Self::SetLocale(arg) => f.debug_tuple("SetLocale").field(arg).finish(),
+            Self::SetTimezone(arg) => f.debug_tuple("SetTimezone").field(arg).finish(),
+            Self::RestoreState(_) => write!(f, "RestoreState"),
             Self::SelectPlanDate(arg, _) => f.debug_tuple("SelectPlanDate").field(arg).finish(),
             Self::DeletePlan(arg, _) => f.debug_tuple("DeletePlan").field(arg).finish(),
+            Self::DuplicatePlan(arg, _) => f.debug_tuple("DuplicatePlan").field(arg).finish(),
         }
     }
 }
 
+/// Apply the state changes for a message. This has no side effects so that
+/// the state changes can be reasoned about and replayed apart from the syncing
+/// that [StateMachine] does.
+pub fn reduce(state: &mut AppState, msg: &Message) {
+    match msg {
+        Message::ResetRecipeCounts => {
+            let mut map = BTreeMap::new();
+            for (id, _) in state.recipes.iter() {
+                map.insert(id.clone(), 0);
+            }
+            state.recipe_counts = map;
+            state.plan_order = Vec::new();
+        }
+        Message::UpdateRecipeCount(id, count) => {
+            if *count == 0 {
+                state.plan_order.retain(|i| i != id);
+            } else if !state.plan_order.contains(id) {
+                state.plan_order.push(id.clone());
+            }
+            state.recipe_counts.insert(id.clone(), *count);
+        }
+        Message::ReorderPlan(order) => {
+            state.plan_order = order.clone();
+        }
+        Message::TogglePlanMeal(id, meal) => {
+            let mut meals = state.meals_for(id);
+            if !meals.remove(meal) {
+                meals.insert(*meal);
+            }
+            let recipe_meal = state
+                .recipes
+                .get(id)
+                .and_then(|r| r.metadata.meal)
+                .unwrap_or_default();
+            // A planned recipe always fills at least one meal so taking it
+            // out of its only meal leaves it where it was.
+            if meals.len() == 1 && meals.contains(&recipe_meal) {
+                state.plan_meals.remove(id);
+            } else if !meals.is_empty() {
+                state.plan_meals.insert(id.clone(), meals);
+            }
+        }
+        Message::SetPlanFreezerMeal(id, from_freezer) => {
+            if *from_freezer {
+                state.plan_freezer_meals.insert(id.clone());
+            } else {
+                state.plan_freezer_meals.remove(id);
+            }
+        }
+        Message::SetArchived(recipe_id, archived) => {
+            if *archived {
+                state.archived.insert(recipe_id.clone());
+            } else {
+                state.archived.remove(recipe_id);
+            }
+        }
+        Message::MarkCooked(cooked) => {
+            state.cooked.insert(cooked.recipe_id.clone());
+            state
+                .last_cooked
+                .insert(cooked.recipe_id.clone(), chrono::Local::now().date_naive());
+        }
+        Message::AddExtra(amt, name) => {
+            state.extras.push((amt.clone(), name.clone()));
+        }
+        Message::RemoveExtra(idx) => {
+            state.extras.remove(*idx);
+        }
+        Message::UpdateExtra(idx, amt, name) => match state.extras.get_mut(*idx) {
+            Some(extra) => {
+                extra.0 = amt.clone();
+                extra.1 = name.clone();
+            }
+            None => {
+                throw_str("Attempted to remove extra that didn't exist");
+            }
+        },
+        Message::SaveRecipe(entry, _) => {
+            // The server normalizes recipe ids when it saves them so we
+            // rename our copy to match ahead of time. The entry keeps the
+            // old id so the server knows what to rename.
+            let old_id = entry.recipe_id();
+            let recipe_id = recipes::slugify(old_id);
+            if recipe_id != old_id {
+                debug!(%old_id, %recipe_id, "Renaming recipe to normalized id");
+                state.rename_recipe(old_id, &recipe_id);
+            }
+            let recipe = parse_recipe(entry.recipe_text()).expect("Failed to parse RecipeEntry");
+            state.recipes.insert(recipe_id.clone(), recipe);
+            if !state.recipe_counts.contains_key(&recipe_id) {
+                state.recipe_counts.insert(recipe_id.clone(), 0);
+            }
+            if let Some(cat) = entry.category().cloned() {
+                state
+                    .recipe_categories
+                    .entry(recipe_id.clone())
+                    .and_modify(|c| *c = cat.clone())
+                    .or_insert(cat);
+            }
+        }
+        Message::RecipeRestored(entry) => match parse_recipe(entry.recipe_text()) {
+            Ok(recipe) => {
+                let recipe_id = entry.recipe_id().to_owned();
+                state.recipes.insert(recipe_id.clone(), recipe);
+                state.recipe_counts.entry(recipe_id.clone()).or_insert(0);
+                if let Some(cat) = entry.category().cloned() {
+                    state.recipe_categories.insert(recipe_id, cat);
+                }
+            }
+            Err(err) => error!(?err, "Restored recipe doesn't parse"),
+        },
+        Message::RemoveRecipe(recipe, _) => {
+            state.recipe_counts.remove(recipe);
+            state.plan_order.retain(|i| i != recipe);
+            state.recipes.remove(recipe);
+        }
+        Message::UpdateCategory(ingredient, category, _) => {
+            state
+                .category_map
+                .insert(ingredient.clone(), category.clone());
+        }
+        Message::UpdateCategories(mappings, _) => {
+            for (ingredient, category) in mappings.iter() {
+                state
+                    .category_map
+                    .insert(ingredient.clone(), category.clone());
+            }
+        }
+        Message::ResetInventory => {
+            state.filtered_ingredients = BTreeSet::new();
+            state.modified_amts = BTreeMap::new();
+            state.extras = Vec::new();
+        }
+        Message::AddFilteredIngredient(key) => {
+            state.filtered_ingredients.insert(key.clone());
+        }
+        Message::ToggleCheckedIngredient(key) => {
+            // Checked off ingredients are filtered ingredients.
+            if !state.filtered_ingredients.remove(key) {
+                state.filtered_ingredients.insert(key.clone());
+            }
+        }
+        Message::UpdateAmt(key, amt) => {
+            state.modified_amts.insert(key.clone(), amt.clone());
+        }
+        Message::SetUserData(user_data) => {
+            state.auth = Some(user_data.clone());
+        }
+        Message::UpdateUseStaples(value) => {
+            state.use_staples = *value;
+        }
+        Message::UpdateUseCelsius(value) => {
+            state.celsius = *value;
+        }
+        Message::UpdateUseWeights(value) => {
+            state.use_weights = *value;
+        }
+        Message::SetDensity(name, Some(grams)) => {
+            state.densities.insert(name.clone(), *grams);
+        }
+        Message::SetDensity(name, None) => {
+            state.densities.remove(name);
+        }
+        Message::UpdateHouseholdSize(size) => {
+            state.household_size = *size;
+        }
+        Message::UpdateRetentionMonths(months) => {
+            state.retention_months = *months;
+        }
+        Message::SetIngredientTags(name, tags) => {
+            if tags.is_empty() {
+                state.ingredient_tags.remove(name);
+            } else {
+                state.ingredient_tags.insert(name.clone(), tags.clone());
+            }
+        }
+        Message::SetSubstitutes(name, substitutes) => {
+            if substitutes.is_empty() {
+                state.substitutions.remove(name);
+            } else {
+                state
+                    .substitutions
+                    .insert(name.clone(), substitutes.clone());
+            }
+        }
+        Message::UpdateRestrictions(restrictions) => {
+            state.restrictions = restrictions.clone();
+        }
+        Message::SetTheme(value) => {
+            state.theme = *value;
+        }
+        Message::SetWeekStart(value) => {
+            state.week_start = *value;
+        }
+        Message::SetLocale(value) => {
+            state.locale = Some(*value);
+        }
+        Message::SetTimezone(offset) => {
+            state.timezone_from_device = offset.is_none();
+            let offset = offset.unwrap_or_else(|| *chrono::Local::now().offset());
+            state.timezone = Some(client_api::format_utc_offset(&offset));
+        }
+        Message::RestoreState(snapshot) => {
+            *state = snapshot.as_ref().clone();
+        }
+        // These change the state once their requests finish so StateMachine
+        // handles them.
+        Message::SaveState(_)
+        | Message::LoadState(_)
+        | Message::RefreshRecipes
+        | Message::RefreshCategories
+        | Message::RefreshPlan
+        | Message::RefreshInventory
+        | Message::UpdateStaples(_, _)
+        | Message::DeletePlan(_, _)
+        | Message::SelectPlanDate(_, _)
+        | Message::DuplicatePlan(_, _) => (),
+    }
+}
+
+/// The parts of the state that can be fetched on their own.
+#[derive(Debug, Clone, Copy)]
+enum Refresh {
+    Recipes,
+    Categories,
+    Plan,
+    Inventory,
+}
+
 pub struct StateMachine {
     store: HttpStore,
     local_store: LocalStore,
+    log: ActionLog,
+}
+
+/// The most parse results we keep before starting over.
+const MAX_PARSE_CACHE_ENTRIES: usize = 1000;
+
+thread_local! {
+    // Recipes only change when their text does so we key parse results by a
+    // hash of the text to avoid re-parsing a large collection every time
+    // the state changes.
+    static PARSE_CACHE: RefCell<BTreeMap<u64, Result<Recipe, String>>> =
+        RefCell::new(BTreeMap::new());
+}
+
+fn content_hash(text: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    text.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Parses recipe text, reusing the result if we've seen this text before.
+pub fn parse_recipe(text: &str) -> Result<Recipe, String> {
+    let key = content_hash(text);
+    if let Some(result) = PARSE_CACHE.with(|cache| cache.borrow().get(&key).cloned()) {
+        return result;
+    }
+    let result = parse::as_recipe(text);
+    PARSE_CACHE.with(|cache| {
+        let mut cache = cache.borrow_mut();
+        if cache.len() >= MAX_PARSE_CACHE_ENTRIES {
+            debug!("Parse cache is full, clearing it");
+            cache.clear();
+        }
+        cache.insert(key, result.clone());
+    });
+    result
+}
+
+/// Drops any cached parse results for recipe text not in the entries.
+fn prune_parse_cache(entries: &Vec<RecipeEntry>) {
+    let keep = entries
+        .iter()
+        .map(|e| content_hash(e.recipe_text()))
+        .collect::<BTreeSet<u64>>();
+    PARSE_CACHE.with(|cache| cache.borrow_mut().retain(|k, _| keep.contains(k)));
 }
 
 #[instrument]
@@ -147,9 +817,10 @@ pub fn parse_recipes(
 ) -> Result<Option<BTreeMap<String, Recipe>>, String> {
     match recipe_entries {
         Some(parsed) => {
+            prune_parse_cache(parsed);
             let mut parsed_map = BTreeMap::new();
             for r in parsed {
-                let recipe = match parse::as_recipe(&r.recipe_text()) {
+                let recipe = match parse_recipe(&r.recipe_text()) {
                     Ok(r) => r,
                     Err(e) => {
                         error!("Error parsing recipe {}", e);
@@ -165,8 +836,12 @@ pub fn parse_recipes(
 }
 
 impl StateMachine {
-    pub fn new(store: HttpStore, local_store: LocalStore) -> Self {
-        Self { store, local_store }
+    pub fn new(store: HttpStore, local_store: LocalStore, log: ActionLog) -> Self {
+        Self {
+            store,
+            local_store,
+            log,
+        }
     }
 
     async fn load_state(
@@ -177,30 +852,134 @@ impl StateMachine {
         // TODO(jwall): We use a linear Signal in here to ensure that we only
         // call set on the signal once.
         let mut original: LinearSignal<AppState> = original.into();
-        if let Some(state) = local_store.fetch_app_state() {
+        if let Some(state) = store.cache_result(local_store.fetch_app_state()) {
             original = original.update(state);
         }
         let mut state = original.get().as_ref().clone();
+        info!("Checking for user account data");
+        // Sessions are short lived. A remembered device gets a new one
+        // before anything else is fetched.
+        let user_data = match store.fetch_user_data().await {
+            Some(user_data) => Some(user_data),
+            None => store.refresh_session().await,
+        };
+        if let Some(user_data) = user_data {
+            debug!("Successfully got account data from server");
+            store.cache_result(local_store.set_user_data(Some(&user_data)));
+            state.auth = Some(user_data);
+        } else {
+            debug!("Using account data from local store");
+            let user_data = store.cache_result(local_store.get_user_data());
+            state.auth = user_data;
+        }
+        Self::refresh_recipes(store, &mut state).await?;
+        Self::refresh_categories(store, &mut state).await?;
+        Self::refresh_plan(store, &mut state).await?;
+        if state.auth.is_some() {
+            let mut pending = store.cache_result(local_store.get_pending_settings());
+            if !pending.is_empty() {
+                info!("Sending settings that failed to save earlier");
+                match store.store_settings(&pending).await {
+                    Ok(_) => {
+                        store.cache_result(local_store.clear_pending_settings());
+                        pending.clear();
+                    }
+                    Err(err) => error!(?err, "Failed to save pending settings"),
+                }
+            }
+            info!("Synchronizing settings");
+            match store.fetch_settings().await {
+                Ok(mut settings) => {
+                    // Changes the server doesn't have yet are newer than
+                    // what it sent us.
+                    settings.extend(pending);
+                    if let Some(theme) = settings
+                        .get(theme::THEME_SETTING)
+                        .and_then(|t| t.parse::<Theme>().ok())
+                    {
+                        state.theme = theme;
+                        theme::apply_theme(theme);
+                    }
+                    state.densities = client_api::density_overrides(&settings);
+                    state.household_size = client_api::household_size(&settings);
+                    state.retention_months = client_api::retention_months(&settings);
+                    state.ingredient_tags = client_api::ingredient_tags(&settings);
+                    state.restrictions = client_api::dietary_restrictions(&settings);
+                    state.week_start = client_api::week_start(&settings);
+                    if let Some(locale) = settings
+                        .get(i18n::LOCALE_SETTING)
+                        .and_then(|l| l.parse::<Locale>().ok())
+                    {
+                        state.locale = Some(locale);
+                    }
+                    state.celsius =
+                        client_api::bool_setting(&settings, client_api::USE_CELSIUS_SETTING);
+                    if let Some(value) =
+                        client_api::bool_setting(&settings, client_api::USE_WEIGHTS_SETTING)
+                    {
+                        state.use_weights = value;
+                    }
+                    state.timezone_from_device = client_api::timezone_from_device(&settings);
+                    state.timezone = settings.get(client_api::TIMEZONE_SETTING).cloned();
+                    // The server uses our timezone to decide what day it is
+                    // for us so keep it in step with the browser's unless the
+                    // user picked one.
+                    let offset = *chrono::Local::now().offset();
+                    if state.timezone_from_device
+                        && client_api::utc_offset(&settings) != Some(offset)
+                    {
+                        let timezone = client_api::format_utc_offset(&offset);
+                        let settings = BTreeMap::from([(
+                            client_api::TIMEZONE_SETTING.to_owned(),
+                            timezone.clone(),
+                        )]);
+                        if let Err(err) = store.store_settings(&settings).await {
+                            error!(?err, "Failed to save timezone");
+                        }
+                        state.timezone = Some(timezone);
+                    }
+                }
+                Err(e) => {
+                    error!("{:?}", e);
+                }
+            }
+            match store.fetch_last_cooked().await {
+                Ok(last_cooked) => state.last_cooked = last_cooked,
+                Err(e) => {
+                    error!("{:?}", e);
+                }
+            }
+            match store.fetch_substitutions().await {
+                Ok(substitutions) => state.substitutions = substitutions,
+                Err(e) => {
+                    error!("{:?}", e);
+                }
+            }
+        }
+        if let Err(err) = Self::refresh_inventory(store, &mut state).await {
+            error!(?err, "Failed to fetch the inventory");
+        }
+        // Finally we store all of this app state back to our localstore
+        store.cache_result(local_store.store_app_state(&state));
+        original.update(state);
+        Ok(())
+    }
+
+    /// Fetch the recipes along with their categories and which of them are
+    /// archived.
+    async fn refresh_recipes(
+        store: &HttpStore,
+        state: &mut AppState,
+    ) -> Result<(), crate::api::Error> {
         info!("Synchronizing Recipes");
-        let recipe_entries = &store.fetch_recipes().await?;
+        let recipe_entries = store.fetch_recipes().await?;
         let recipes = parse_recipes(&recipe_entries)?;
         debug!(?recipes, "Parsed Recipes");
         if let Some(recipes) = recipes {
             state.recipes = recipes;
         };
-
-        info!("Synchronizing staples");
-        state.staples = if let Some(content) = store.fetch_staples().await? {
-            // now we need to parse staples as ingredients
-            let mut staples = parse::as_ingredient_list(&content)?;
-            Some(staples.drain(0..).collect())
-        } else {
-            Some(BTreeSet::new())
-        };
-
-        info!("Synchronizing recipe");
-        if let Some(recipe_entries) = recipe_entries {
-            local_store.set_all_recipes(recipe_entries);
+        if let Some(recipe_entries) = &recipe_entries {
+            store.cache_result(store.cache().set_all_recipes(recipe_entries).await);
             state.recipe_categories = recipe_entries
                 .iter()
                 .map(|entry| {
@@ -215,7 +994,59 @@ impl StateMachine {
                 })
                 .collect::<BTreeMap<String, String>>();
         }
+        for id in state.recipes.keys() {
+            if !state.recipe_counts.contains_key(id) {
+                state.recipe_counts.insert(id.clone(), 0);
+            }
+        }
+        if state.auth.is_some() {
+            match store.fetch_archived().await {
+                Ok(archived) => state.archived = archived,
+                Err(e) => {
+                    error!("{:?}", e);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Fetch the ingredient categories and staples. These are what the
+    /// account defaults fill in.
+    async fn refresh_categories(
+        store: &HttpStore,
+        state: &mut AppState,
+    ) -> Result<(), crate::api::Error> {
+        info!("Synchronizing staples");
+        state.staples = if let Some(content) = store.fetch_staples().await? {
+            // now we need to parse staples as ingredients
+            let mut staples = parse::as_ingredient_list(&content)?;
+            Some(staples.drain(0..).collect())
+        } else {
+            Some(BTreeSet::new())
+        };
+        info!("Synchronizing categories");
+        match store.fetch_categories().await {
+            Ok(Some(mut categories_content)) => {
+                debug!(categories=?categories_content);
+                let category_map = BTreeMap::from_iter(categories_content.drain(0..));
+                state.category_map = category_map;
+            }
+            Ok(None) => {
+                warn!("There is no category file");
+            }
+            Err(e) => {
+                error!("{:?}", e);
+            }
+        }
+        Ok(())
+    }
 
+    /// Fetch the plan dates and everything planned for the selected date.
+    /// Without a selected date the recipe counts start over at zero.
+    async fn refresh_plan(
+        store: &HttpStore,
+        state: &mut AppState,
+    ) -> Result<(), crate::api::Error> {
         info!("Fetching meal plan list");
         if let Some(mut plan_dates) = store.fetch_plan_dates().await? {
             debug!(?plan_dates, "meal plan list");
@@ -233,11 +1064,14 @@ impl StateMachine {
         };
         if let Some(plan) = plan {
             // set the counts.
-            let mut plan_map = BTreeMap::new();
-            for (id, count) in plan {
-                plan_map.insert(id, count as usize);
+            state.set_plan(plan);
+            if let Some(ref cached_plan_date) = state.selected_plan_date {
+                state.plan_meals = store.fetch_plan_meals_for_date(cached_plan_date).await?;
+                state.plan_freezer_meals = store
+                    .fetch_plan_freezer_meals_for_date(cached_plan_date)
+                    .await?;
+                state.cooked = store.fetch_cooked_for_date(cached_plan_date).await?;
             }
-            state.recipe_counts = plan_map;
             for (id, _) in state.recipes.iter() {
                 if !state.recipe_counts.contains_key(id) {
                     state.recipe_counts.insert(id.clone(), 0);
@@ -245,56 +1079,136 @@ impl StateMachine {
             }
         } else {
             // Initialize things to zero.
-            if let Some(rs) = recipe_entries {
-                for r in rs {
-                    state.recipe_counts.insert(r.recipe_id().to_owned(), 0);
-                }
+            for id in state.recipes.keys() {
+                state.recipe_counts.insert(id.clone(), 0);
             }
         }
-        info!("Checking for user account data");
-        if let Some(user_data) = store.fetch_user_data().await {
-            debug!("Successfully got account data from server");
-            local_store.set_user_data(Some(&user_data));
-            state.auth = Some(user_data);
-        } else {
-            debug!("Using account data from local store");
-            let user_data = local_store.get_user_data();
-            state.auth = user_data;
-        }
-        info!("Synchronizing categories");
-        match store.fetch_categories().await {
-            Ok(Some(mut categories_content)) => {
-                debug!(categories=?categories_content);
-                let category_map = BTreeMap::from_iter(categories_content.drain(0..));
-                state.category_map = category_map;
+        Ok(())
+    }
+
+    /// Fetch the inventory for the selected plan date or the latest one.
+    async fn refresh_inventory(
+        store: &HttpStore,
+        state: &mut AppState,
+    ) -> Result<(), crate::api::Error> {
+        let (filtered_ingredients, modified_amts, extra_items) =
+            if let Some(cached_plan_date) = &state.selected_plan_date {
+                store.fetch_inventory_for_date(cached_plan_date).await?
+            } else {
+                store.fetch_inventory_data().await?
+            };
+        info!("Synchronizing inventory data");
+        state.modified_amts = modified_amts;
+        state.filtered_ingredients = filtered_ingredients;
+        state.extras = extra_items;
+        Ok(())
+    }
+
+    /// Fetch one part of the state from the server instead of all of it.
+    /// Unlike a full load this always goes to the server since it follows
+    /// our own writes.
+    fn refresh<'ctx>(&self, cx: Scope<'ctx>, original: &'ctx Signal<AppState>, part: Refresh) {
+        let store = self.store.clone();
+        let local_store = self.local_store.clone();
+        spawn_local_scoped(cx, async move {
+            let mut state = original.get().as_ref().clone();
+            let result = match part {
+                Refresh::Recipes => Self::refresh_recipes(&store, &mut state).await,
+                Refresh::Categories => Self::refresh_categories(&store, &mut state).await,
+                Refresh::Plan => Self::refresh_plan(&store, &mut state).await,
+                Refresh::Inventory => Self::refresh_inventory(&store, &mut state).await,
+            };
+            if let Err(err) = result {
+                error!(?err, ?part, "Failed to refresh");
+                components::toast::error_message(cx, "Failed to refresh.", None);
+                return;
             }
-            Ok(None) => {
-                warn!("There is no category file");
+            store.cache_result(local_store.store_app_state(&state));
+            original.set(state);
+        });
+    }
+
+    /// Save settings to the server. The state already has the change so
+    /// settings that fail to save are kept in the local store and sent again
+    /// the next time the state is loaded.
+    fn save_settings<'ctx>(&self, cx: Scope<'ctx>, settings: BTreeMap<String, String>) {
+        let store = self.store.clone();
+        let local_store = self.local_store.clone();
+        spawn_local_scoped(cx, async move {
+            if let Err(err) = store.store_settings(&settings).await {
+                error!(?err, ?settings, "Failed to save settings");
+                store.cache_result(local_store.add_pending_settings(&settings));
             }
-            Err(e) => {
-                error!("{:?}", e);
+        });
+    }
+
+    /// Send a request for a change that is already in the state so the ui
+    /// doesn't wait on the round trip. When it succeeds `commit` merges the
+    /// server's answer into the current state. When it fails `rollback`
+    /// undoes just our change, keeping anything that happened while we
+    /// waited, and the user is told with the message for `failure`.
+    fn optimistic<'ctx, T, R, C, U>(
+        &self,
+        cx: Scope<'ctx>,
+        original: &'ctx Signal<AppState>,
+        failure: &'static str,
+        request: R,
+        commit: C,
+        rollback: U,
+    ) where
+        R: Future<Output = Result<T, crate::api::Error>> + 'ctx,
+        C: FnOnce(&mut AppState, T) + 'ctx,
+        U: FnOnce(&mut AppState) + 'ctx,
+    {
+        let store = self.store.clone();
+        let local_store = self.local_store.clone();
+        spawn_local_scoped(cx, async move {
+            let result = request.await;
+            let mut state = original.get().as_ref().clone();
+            match result {
+                Ok(value) => commit(&mut state, value),
+                Err(err) => {
+                    error!(?err, failure, "Rolling back a change that failed to sync");
+                    rollback(&mut state);
+                    components::toast::error_message(cx, &i18n::t(failure), None);
+                }
             }
-        }
-        let inventory_data = if let Some(cached_plan_date) = &state.selected_plan_date {
-            store.fetch_inventory_for_date(cached_plan_date).await
-        } else {
-            store.fetch_inventory_data().await
+            store.cache_result(local_store.store_app_state(&state));
+            original.set(state);
+        });
+    }
+
+    /// Send an item level change to the inventory for the selected plan and
+    /// merge everyone's changes back into the state. The `rollback` undoes
+    /// our optimistic change if the sync fails. Without a plan date the
+    /// inventory is saved along with the rest of the state instead.
+    fn sync_inventory<'ctx, F>(
+        &self,
+        cx: Scope<'ctx>,
+        original: &'ctx Signal<AppState>,
+        date: Option<NaiveDate>,
+        op: InventoryOp,
+        rollback: F,
+    ) where
+        F: FnOnce(&mut AppState) + 'ctx,
+    {
+        let date = match date {
+            Some(date) => date,
+            None => return,
         };
-        info!("Synchronizing inventory data");
-        match inventory_data {
-            Ok((filtered_ingredients, modified_amts, extra_items)) => {
-                state.modified_amts = modified_amts;
-                state.filtered_ingredients = filtered_ingredients;
-                state.extras = extra_items;
-            }
-            Err(e) => {
-                error!("{:?}", e);
-            }
-        }
-        // Finally we store all of this app state back to our localstore
-        local_store.store_app_state(&state);
-        original.update(state);
-        Ok(())
+        let store = self.store.clone();
+        let request = async move { store.update_inventory_for_date(&vec![op], &date).await };
+        self.optimistic(
+            cx,
+            original,
+            "state.inventory_rolled_back",
+            request,
+            |state, merged| {
+                state.filtered_ingredients = merged.filtered_ingredients.into_iter().collect();
+                state.modified_amts = merged.modified_amts.into_iter().collect();
+            },
+            rollback,
+        );
     }
 }
 
@@ -303,83 +1217,65 @@ impl MessageMapper<Message, AppState> for StateMachine {
     fn map<'ctx>(&self, cx: Scope<'ctx>, msg: Message, original: &'ctx Signal<AppState>) {
         let mut original_copy = original.get().as_ref().clone();
         debug!("handling state message");
+        reduce(&mut original_copy, &msg);
+        self.log.record(&msg, &original_copy);
         match msg {
-            Message::ResetRecipeCounts => {
-                let mut map = BTreeMap::new();
-                for (id, _) in original_copy.recipes.iter() {
-                    map.insert(id.clone(), 0);
-                }
-                original_copy.recipe_counts = map;
-            }
-            Message::UpdateRecipeCount(id, count) => {
-                original_copy.recipe_counts.insert(id, count);
-            }
-            Message::AddExtra(amt, name) => {
-                original_copy.extras.push((amt, name));
-            }
-            Message::RemoveExtra(idx) => {
-                original_copy.extras.remove(idx);
-            }
-            Message::UpdateExtra(idx, amt, name) => match original_copy.extras.get_mut(idx) {
-                Some(extra) => {
-                    extra.0 = amt;
-                    extra.1 = name;
-                }
-                None => {
-                    throw_str("Attempted to remove extra that didn't exist");
-                }
-            },
-            Message::SaveRecipe(entry, callback) => {
-                let recipe =
-                    parse::as_recipe(entry.recipe_text()).expect("Failed to parse RecipeEntry");
-                original_copy
-                    .recipes
-                    .insert(entry.recipe_id().to_owned(), recipe);
-                if !original_copy.recipe_counts.contains_key(entry.recipe_id()) {
-                    original_copy
-                        .recipe_counts
-                        .insert(entry.recipe_id().to_owned(), 0);
-                }
-                if let Some(cat) = entry.category().cloned() {
-                    original_copy
-                        .recipe_categories
-                        .entry(entry.recipe_id().to_owned())
-                        .and_modify(|c| *c = cat.clone())
-                        .or_insert(cat);
-                }
+            Message::ResetRecipeCounts
+            | Message::ReorderPlan(_)
+            | Message::TogglePlanMeal(_, _)
+            | Message::SetPlanFreezerMeal(_, _)
+            | Message::AddExtra(_, _)
+            | Message::RemoveExtra(_)
+            | Message::UpdateExtra(_, _, _)
+            | Message::UpdateUseStaples(_)
+            | Message::RecipeRestored(_) => (),
+            Message::SaveRecipe(mut entry, callback) => {
+                let old_id = entry.recipe_id().to_owned();
+                let recipe_id = recipes::slugify(&old_id);
                 let store = self.store.clone();
-                self.local_store.set_recipe_entry(&entry);
+                let local_store = self.local_store.clone();
                 spawn_local_scoped(cx, async move {
+                    let cache = store.cache();
+                    // The local cache holds the revision this device last saw so
+                    // we base the save on that revision.
+                    if entry.revision().is_none() {
+                        let cached = store.cache_result(cache.get_recipe_entry(&old_id).await);
+                        entry.set_revision(cached.and_then(|e| e.revision()));
+                    }
+                    if recipe_id != old_id {
+                        store.cache_result(cache.delete_recipe_entry(&old_id).await);
+                    }
+                    let mut local_entry = entry.clone();
+                    local_entry.set_recipe_id(recipe_id.clone());
+                    store.cache_result(cache.set_recipe_entry(&local_entry).await);
                     if let Err(e) = store.store_recipes(vec![entry]).await {
-                        // FIXME(jwall): We should have a global way to trigger error messages
                         error!(err=?e, "Unable to save Recipe");
-                        // FIXME(jwall): This should be an error message
-                        components::toast::error_message(cx, "Failed to save Recipe", None);
                     } else {
-                        components::toast::message(cx, "Saved Recipe", None);
+                        store.cache_result(local_store.delete_draft(&old_id));
+                        if recipe_id != old_id {
+                            let old_path = format!("/ui/recipe/edit/{}", old_id);
+                            let current_path =
+                                web_sys::window().and_then(|w| w.location().pathname().ok());
+                            if current_path.as_deref() == Some(old_path.as_str()) {
+                                let new_path = format!("/ui/recipe/edit/{}", recipe_id);
+                                sycamore_router::navigate(&new_path);
+                            }
+                        }
                     }
                     callback.map(|f| f());
                 });
             }
             Message::RemoveRecipe(recipe, callback) => {
-                original_copy.recipe_counts.remove(&recipe);
-                original_copy.recipes.remove(&recipe);
-                self.local_store.delete_recipe_entry(&recipe);
                 let store = self.store.clone();
                 spawn_local_scoped(cx, async move {
+                    store.cache_result(store.cache().delete_recipe_entry(&recipe).await);
                     if let Err(err) = store.delete_recipe(&recipe).await {
                         error!(?err, "Failed to delete recipe");
-                        components::toast::error_message(cx, "Unable to delete recipe", None);
-                    } else {
-                        components::toast::message(cx, "Deleted Recipe", None);
                     }
                     callback.map(|f| f());
                 });
             }
             Message::UpdateCategory(ingredient, category, callback) => {
-                original_copy
-                    .category_map
-                    .insert(ingredient.clone(), category.clone());
                 let store = self.store.clone();
                 spawn_local_scoped(cx, async move {
                     if let Err(e) = store.store_categories(&vec![(ingredient, category)]).await {
@@ -388,21 +1284,108 @@ impl MessageMapper<Message, AppState> for StateMachine {
                     callback.map(|f| f());
                 });
             }
+            Message::UpdateCategories(mappings, callback) => {
+                let store = self.store.clone();
+                spawn_local_scoped(cx, async move {
+                    if let Err(e) = store.store_categories(&mappings).await {
+                        error!(?e, "Failed to save categories");
+                    }
+                    callback.map(|f| f());
+                });
+            }
+            Message::UpdateRecipeCount(id, count) => {
+                // Count changes are sent as they happen so they merge with
+                // changes made elsewhere. If that fails the count goes back
+                // to what it was.
+                if let Some(date) = original_copy.selected_plan_date {
+                    let (previous, position) = {
+                        let state = original.get();
+                        (
+                            state.recipe_counts.get(&id).cloned().unwrap_or(0),
+                            state.plan_order.iter().position(|i| *i == id),
+                        )
+                    };
+                    let store = self.store.clone();
+                    let changes = vec![PlanChange::SetCount(id.clone(), count as i32)];
+                    let request = async move { store.update_plan_for_date(&changes, &date).await };
+                    self.optimistic(
+                        cx,
+                        original,
+                        "state.count_rolled_back",
+                        request,
+                        |state, plan| state.merge_plan_counts(plan),
+                        move |state| state.undo_recipe_count(&id, count, previous, position),
+                    );
+                }
+            }
             Message::ResetInventory => {
-                original_copy.filtered_ingredients = BTreeSet::new();
-                original_copy.modified_amts = BTreeMap::new();
-                original_copy.extras = Vec::new();
                 components::toast::message(cx, "Reset Inventory", None);
+                let previous = {
+                    let state = original.get();
+                    (
+                        state.filtered_ingredients.clone(),
+                        state.modified_amts.clone(),
+                        state.extras.clone(),
+                    )
+                };
+                let date = original_copy.selected_plan_date;
+                self.sync_inventory(cx, original, date, InventoryOp::Reset, move |state| {
+                    let (filtered, modified, extras) = previous;
+                    state.filtered_ingredients.extend(filtered);
+                    for (key, amt) in modified {
+                        state.modified_amts.entry(key).or_insert(amt);
+                    }
+                    if state.extras.is_empty() {
+                        state.extras = extras;
+                    }
+                });
             }
             Message::AddFilteredIngredient(key) => {
-                original_copy.filtered_ingredients.insert(key);
+                let was_filtered = original.get().filtered_ingredients.contains(&key);
+                let date = original_copy.selected_plan_date;
+                let op = InventoryOp::Check(key.clone());
+                self.sync_inventory(cx, original, date, op, move |state| {
+                    if !was_filtered {
+                        state.filtered_ingredients.remove(&key);
+                    }
+                });
+            }
+            Message::ToggleCheckedIngredient(key) => {
+                // We update optimistically and roll back if the sync fails.
+                let checked = original_copy.filtered_ingredients.contains(&key);
+                let date = original_copy.selected_plan_date;
+                let op = if checked {
+                    InventoryOp::Check(key.clone())
+                } else {
+                    InventoryOp::Uncheck(key.clone())
+                };
+                self.sync_inventory(cx, original, date, op, move |state| {
+                    if checked {
+                        state.filtered_ingredients.remove(&key);
+                    } else {
+                        state.filtered_ingredients.insert(key);
+                    }
+                });
             }
             Message::UpdateAmt(key, amt) => {
-                original_copy.modified_amts.insert(key, amt);
+                let previous = original.get().modified_amts.get(&key).cloned();
+                let date = original_copy.selected_plan_date;
+                let op = InventoryOp::SetAmt(key.clone(), amt);
+                self.sync_inventory(cx, original, date, op, move |state| match previous {
+                    Some(previous) => {
+                        state.modified_amts.insert(key, previous);
+                    }
+                    None => {
+                        state.modified_amts.remove(&key);
+                    }
+                });
             }
             Message::SetUserData(user_data) => {
-                self.local_store.set_user_data(Some(&user_data));
-                original_copy.auth = Some(user_data);
+                self.store
+                    .cache_result(self.local_store.set_user_data(Some(&user_data)));
+            }
+            Message::RestoreState(_) => {
+                theme::apply_theme(original_copy.theme);
             }
             Message::SaveState(f) => {
                 let mut original_copy = original_copy.clone();
@@ -421,11 +1404,8 @@ impl MessageMapper<Message, AppState> for StateMachine {
                     );
                     if let Err(e) = store.store_app_state(&original_copy).await {
                         error!(err=?e, "Error saving app state");
-                        components::toast::error_message(cx, "Failed to save user state", None);
-                    } else {
-                        components::toast::message(cx, "Saved user state", None);
                     };
-                    local_store.store_app_state(&original_copy);
+                    store.cache_result(local_store.store_app_state(&original_copy));
                     original.set(original_copy);
                     f.map(|f| f());
                 });
@@ -438,46 +1418,263 @@ impl MessageMapper<Message, AppState> for StateMachine {
                 let local_store = self.local_store.clone();
                 debug!("Loading user state.");
                 spawn_local_scoped(cx, async move {
+                    // Most loads follow our own writes which we already
+                    // have so we skip them when nothing else changed on
+                    // the server.
+                    let version = match store.check_data_version().await {
+                        Ok(None) => {
+                            debug!("Data version unchanged. Skipping load.");
+                            f.map(|f| f());
+                            return;
+                        }
+                        Ok(version) => version,
+                        Err(err) => {
+                            debug!(?err, "Unable to check the data version");
+                            None
+                        }
+                    };
                     if let Err(err) = Self::load_state(&store, &local_store, original.clone()).await
                     {
                         error!(?err, "Failed to load user state");
                         components::toast::error_message(cx, "Failed to load_state.", None);
                     } else {
+                        store.set_data_version(version);
                         components::toast::message(cx, "Loaded user state", None);
                     }
                     f.map(|f| f());
                 });
                 return;
             }
+            Message::RefreshRecipes => {
+                self.refresh(cx, original, Refresh::Recipes);
+                return;
+            }
+            Message::RefreshCategories => {
+                self.refresh(cx, original, Refresh::Categories);
+                return;
+            }
+            Message::RefreshPlan => {
+                self.refresh(cx, original, Refresh::Plan);
+                return;
+            }
+            Message::RefreshInventory => {
+                self.refresh(cx, original, Refresh::Inventory);
+                return;
+            }
             Message::UpdateStaples(content, callback) => {
                 let store = self.store.clone();
                 spawn_local_scoped(cx, async move {
                     if let Err(err) = store.store_staples(content).await {
                         error!(?err, "Failed to store staples");
-                        components::toast::error_message(cx, "Failed to store staples", None);
                     } else {
-                        components::toast::message(cx, "Updated staples", None);
                         callback.map(|f| f());
                     }
                 });
                 return;
             }
-            Message::UpdateUseStaples(value) => {
-                original_copy.use_staples = value;
+            Message::SetArchived(recipe_id, archived) => {
+                let store = self.store.clone();
+                spawn_local_scoped(cx, async move {
+                    if let Err(err) = store.set_recipe_archived(&recipe_id, archived).await {
+                        error!(?err, "Failed to archive recipe");
+                        components::toast::error_message(cx, "Failed to archive recipe", None);
+                    }
+                });
+            }
+            Message::MarkCooked(cooked) => {
+                if let Some(date) = original_copy.selected_plan_date {
+                    let store = self.store.clone();
+                    let local_store = self.local_store.clone();
+                    spawn_local_scoped(cx, async move {
+                        if let Err(err) = store.mark_cooked(&date, &cooked).await {
+                            error!(?err, "Failed to mark recipe as cooked");
+                            components::toast::error_message(
+                                cx,
+                                "Failed to mark recipe as cooked",
+                                None,
+                            );
+                            return;
+                        }
+                        // Cooking may have used up amounts in the inventory.
+                        if cooked.deplete {
+                            match store.fetch_inventory_for_date(&date).await {
+                                Ok((_, modified, _)) => {
+                                    let mut state = original.get().as_ref().clone();
+                                    state.modified_amts = modified;
+                                    store.cache_result(local_store.store_app_state(&state));
+                                    original.set(state);
+                                }
+                                Err(err) => error!(?err, "Failed to fetch the inventory"),
+                            }
+                        }
+                    });
+                }
+            }
+            Message::SetTheme(value) => {
+                theme::apply_theme(value);
+                if original_copy.auth.is_some() {
+                    self.save_settings(
+                        cx,
+                        BTreeMap::from([(
+                            theme::THEME_SETTING.to_owned(),
+                            value.as_str().to_owned(),
+                        )]),
+                    );
+                }
+            }
+            Message::SetWeekStart(value) => {
+                if original_copy.auth.is_some() {
+                    self.save_settings(
+                        cx,
+                        BTreeMap::from([(
+                            client_api::WEEK_START_SETTING.to_owned(),
+                            value.as_str().to_owned(),
+                        )]),
+                    );
+                }
+            }
+            Message::SetLocale(value) => {
+                if original_copy.auth.is_some() {
+                    self.save_settings(
+                        cx,
+                        BTreeMap::from([(
+                            i18n::LOCALE_SETTING.to_owned(),
+                            value.as_str().to_owned(),
+                        )]),
+                    );
+                }
+            }
+            Message::SetTimezone(_) => {
+                if original_copy.auth.is_some() {
+                    self.save_settings(
+                        cx,
+                        BTreeMap::from([
+                            (
+                                client_api::TIMEZONE_FROM_DEVICE_SETTING.to_owned(),
+                                original_copy.timezone_from_device.to_string(),
+                            ),
+                            (
+                                client_api::TIMEZONE_SETTING.to_owned(),
+                                original_copy.timezone.clone().unwrap_or_default(),
+                            ),
+                        ]),
+                    );
+                }
+            }
+            Message::UpdateUseCelsius(value) => {
+                if original_copy.auth.is_some() {
+                    self.save_settings(
+                        cx,
+                        BTreeMap::from([(
+                            client_api::USE_CELSIUS_SETTING.to_owned(),
+                            value.map(|v| v.to_string()).unwrap_or_default(),
+                        )]),
+                    );
+                }
+            }
+            Message::UpdateUseWeights(value) => {
+                if original_copy.auth.is_some() {
+                    self.save_settings(
+                        cx,
+                        BTreeMap::from([(
+                            client_api::USE_WEIGHTS_SETTING.to_owned(),
+                            value.to_string(),
+                        )]),
+                    );
+                }
+            }
+            Message::SetDensity(name, grams) => {
+                if original_copy.auth.is_some() {
+                    // Settings can't be deleted so an empty value clears
+                    // the override.
+                    self.save_settings(
+                        cx,
+                        BTreeMap::from([(
+                            format!("{}{}", client_api::DENSITY_SETTING_PREFIX, name),
+                            grams.map(|g| g.to_string()).unwrap_or_default(),
+                        )]),
+                    );
+                }
+            }
+            Message::UpdateHouseholdSize(size) => {
+                if original_copy.auth.is_some() {
+                    self.save_settings(
+                        cx,
+                        BTreeMap::from([(
+                            client_api::HOUSEHOLD_SIZE_SETTING.to_owned(),
+                            size.map(|s| s.to_string()).unwrap_or_default(),
+                        )]),
+                    );
+                }
+            }
+            Message::UpdateRetentionMonths(months) => {
+                if original_copy.auth.is_some() {
+                    self.save_settings(
+                        cx,
+                        BTreeMap::from([(
+                            client_api::RETENTION_SETTING.to_owned(),
+                            months.map(|m| m.to_string()).unwrap_or_default(),
+                        )]),
+                    );
+                }
+            }
+            Message::SetIngredientTags(name, tags) => {
+                if original_copy.auth.is_some() {
+                    self.save_settings(
+                        cx,
+                        BTreeMap::from([(
+                            format!("{}{}", client_api::INGREDIENT_TAGS_SETTING_PREFIX, name),
+                            tags.into_iter().collect::<Vec<String>>().join(", "),
+                        )]),
+                    );
+                }
+            }
+            Message::SetSubstitutes(name, substitutes) => {
+                if original_copy.auth.is_some() {
+                    self.save_settings(
+                        cx,
+                        BTreeMap::from([(
+                            format!("{}{}", client_api::SUBSTITUTES_SETTING_PREFIX, name),
+                            substitutes.join(" | "),
+                        )]),
+                    );
+                }
+            }
+            Message::UpdateRestrictions(restrictions) => {
+                if original_copy.auth.is_some() {
+                    self.save_settings(
+                        cx,
+                        BTreeMap::from([(
+                            client_api::RESTRICTIONS_SETTING.to_owned(),
+                            restrictions.into_iter().collect::<Vec<String>>().join(", "),
+                        )]),
+                    );
+                }
             }
             Message::SelectPlanDate(date, callback) => {
                 let store = self.store.clone();
                 let local_store = self.local_store.clone();
                 spawn_local_scoped(cx, async move {
-                    if let Some(mut plan) = store
+                    if let Some(plan) = store
                         .fetch_plan_for_date(&date)
                         .await
                         .expect("Failed to fetch plan for date")
                     {
                         // Note(jwall): This is a little unusual but because this
                         // is async code we can't rely on the set below.
-                        original_copy.recipe_counts =
-                            BTreeMap::from_iter(plan.drain(0..).map(|(k, v)| (k, v as usize)));
+                        original_copy.set_plan(plan);
+                    }
+                    match store.fetch_plan_meals_for_date(&date).await {
+                        Ok(meals) => original_copy.plan_meals = meals,
+                        Err(err) => error!(?err, "Failed to fetch the planned meals"),
+                    }
+                    match store.fetch_plan_freezer_meals_for_date(&date).await {
+                        Ok(freezer_meals) => original_copy.plan_freezer_meals = freezer_meals,
+                        Err(err) => error!(?err, "Failed to fetch the planned freezer meals"),
+                    }
+                    match store.fetch_cooked_for_date(&date).await {
+                        Ok(cooked) => original_copy.cooked = cooked,
+                        Err(err) => error!(?err, "Failed to fetch the cooked recipes"),
                     }
                     let (filtered, modified, extras) = store
                         .fetch_inventory_for_date(&date)
@@ -492,7 +1689,7 @@ impl MessageMapper<Message, AppState> for StateMachine {
                         .store_plan_for_date(vec![], &date)
                         .await
                         .expect("Failed to init meal plan for date");
-                    local_store.store_app_state(&original_copy);
+                    store.cache_result(local_store.store_app_state(&original_copy));
                     original.set(original_copy);
 
                     callback.map(|f| f());
@@ -502,27 +1699,85 @@ impl MessageMapper<Message, AppState> for StateMachine {
                 // the original signal.
                 return;
             }
+            Message::DuplicatePlan(date, callback) => {
+                let store = self.store.clone();
+                let local_store = self.local_store.clone();
+                spawn_local_scoped(cx, async move {
+                    let today = chrono::offset::Local::now().naive_local().date();
+                    let plan = match store.fetch_plan_for_date(&date).await {
+                        Ok(plan) => plan.unwrap_or_default(),
+                        Err(err) => {
+                            error!(?err, "Failed to fetch the plan to duplicate");
+                            return;
+                        }
+                    };
+                    if let Err(err) = store.store_plan_for_date(plan.clone(), &today).await {
+                        error!(?err, "Failed to save the duplicated plan");
+                        return;
+                    }
+                    let meals = match store.fetch_plan_meals_for_date(&date).await {
+                        Ok(meals) => meals,
+                        Err(err) => {
+                            error!(?err, "Failed to fetch the planned meals to duplicate");
+                            BTreeMap::new()
+                        }
+                    };
+                    if let Err(err) = store.store_plan_meals_for_date(&meals, &today).await {
+                        error!(?err, "Failed to save the duplicated planned meals");
+                    }
+                    let freezer_meals = match store.fetch_plan_freezer_meals_for_date(&date).await {
+                        Ok(freezer_meals) => freezer_meals,
+                        Err(err) => {
+                            error!(?err, "Failed to fetch the freezer meals to duplicate");
+                            BTreeSet::new()
+                        }
+                    };
+                    if let Err(err) = store
+                        .store_plan_freezer_meals_for_date(&freezer_meals, &today)
+                        .await
+                    {
+                        error!(?err, "Failed to save the duplicated freezer meals");
+                    }
+                    let (filtered, modified, extras) = store
+                        .fetch_inventory_for_date(&today)
+                        .await
+                        .expect("Failed to fetch inventory_data for date");
+                    original_copy.set_plan(plan);
+                    original_copy.plan_meals = meals;
+                    original_copy.plan_freezer_meals = freezer_meals;
+                    original_copy.cooked = BTreeSet::new();
+                    original_copy.plan_dates.insert(today);
+                    original_copy.modified_amts = modified;
+                    original_copy.filtered_ingredients = filtered;
+                    original_copy.extras = extras;
+                    original_copy.selected_plan_date = Some(today);
+                    store.cache_result(local_store.store_app_state(&original_copy));
+                    original.set(original_copy);
+
+                    callback.map(|f| f());
+                });
+                // See the note in SelectPlanDate.
+                return;
+            }
             Message::DeletePlan(date, callback) => {
                 let store = self.store.clone();
                 let local_store = self.local_store.clone();
                 spawn_local_scoped(cx, async move {
                     if let Err(err) = store.delete_plan_for_date(&date).await {
-                        components::toast::error_message(
-                            cx,
-                            "Failed to delete meal plan for date",
-                            None,
-                        );
                         error!(?err, "Error deleting plan");
                     } else {
                         original_copy.plan_dates.remove(&date);
                         // Reset all meal planning state;
                         let _ = original_copy.recipe_counts.iter_mut().map(|(_, v)| *v = 0);
+                        original_copy.plan_order = Vec::new();
+                        original_copy.plan_meals = BTreeMap::new();
+                        original_copy.plan_freezer_meals = BTreeSet::new();
+                        original_copy.cooked = BTreeSet::new();
                         original_copy.filtered_ingredients = BTreeSet::new();
                         original_copy.modified_amts = BTreeMap::new();
                         original_copy.extras = Vec::new();
-                        local_store.store_app_state(&original_copy);
+                        store.cache_result(local_store.store_app_state(&original_copy));
                         original.set(original_copy);
-                        components::toast::message(cx, "Deleted Plan", None);
 
                         callback.map(|f| f());
                     }
@@ -533,7 +1788,8 @@ impl MessageMapper<Message, AppState> for StateMachine {
                 return;
             }
         }
-        self.local_store.store_app_state(&original_copy);
+        self.store
+            .cache_result(self.local_store.store_app_state(&original_copy));
         original.set(original_copy);
     }
 }
@@ -545,5 +1801,11 @@ pub fn get_state_handler<'ctx>(
     initial: AppState,
     store: HttpStore,
 ) -> StateHandler<'ctx> {
-    Handler::new(cx, initial, StateMachine::new(store, LocalStore::new()))
+    let log = ActionLog::new();
+    provide_context(cx, log.clone());
+    Handler::new(
+        cx,
+        initial,
+        StateMachine::new(store, LocalStore::new(), log),
+    )
 }
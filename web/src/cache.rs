@@ -0,0 +1,316 @@
+// Copyright 2023 Jeremy Wall (Jeremy@marzhilsltudios.com)
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//! Local caching of the larger data we get from the server so that it is
+//! available offline.
+use std::{cell::RefCell, rc::Rc};
+
+use async_trait::async_trait;
+use js_sys::{Array, Promise};
+use recipes::RecipeEntry;
+use serde_json::{from_str, to_string};
+use tracing::{debug, error, info};
+use wasm_bindgen::{prelude::Closure, JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{
+    Event, IdbDatabase, IdbObjectStore, IdbOpenDbRequest, IdbRequest, IdbTransaction,
+    IdbTransactionMode,
+};
+
+use crate::api::{Error, LocalStore};
+
+const DB_NAME: &'static str = "kitchen";
+const DB_VERSION: u32 = 1;
+const RECIPES: &'static str = "recipes";
+const IMAGES: &'static str = "images";
+
+fn image_key(src: &str) -> String {
+    format!("image:{}", src)
+}
+
+/// A cache for recipes and images on this device.
+#[async_trait(?Send)]
+pub trait LocalCache: std::fmt::Debug {
+    /// Gets all the cached recipes.
+    async fn get_recipes(&self) -> Result<Option<Vec<RecipeEntry>>, Error>;
+
+    async fn get_recipe_entry(&self, id: &str) -> Result<Option<RecipeEntry>, Error>;
+
+    /// Sets the cached recipes to the entries passed in. Deletes any recipes
+    /// not in the list.
+    async fn set_all_recipes(&self, entries: &Vec<RecipeEntry>) -> Result<(), Error>;
+
+    async fn set_recipe_entry(&self, entry: &RecipeEntry) -> Result<(), Error>;
+
+    async fn delete_recipe_entry(&self, id: &str) -> Result<(), Error>;
+
+    /// Gets a cached image as a data url.
+    async fn get_image(&self, src: &str) -> Result<Option<String>, Error>;
+
+    /// Caches an image for the src as a data url.
+    async fn set_image(&self, src: &str, data_url: &str) -> Result<(), Error>;
+}
+
+#[async_trait(?Send)]
+impl LocalCache for LocalStore {
+    async fn get_recipes(&self) -> Result<Option<Vec<RecipeEntry>>, Error> {
+        LocalStore::get_recipes(self)
+    }
+
+    async fn get_recipe_entry(&self, id: &str) -> Result<Option<RecipeEntry>, Error> {
+        LocalStore::get_recipe_entry(self, id)
+    }
+
+    async fn set_all_recipes(&self, entries: &Vec<RecipeEntry>) -> Result<(), Error> {
+        LocalStore::set_all_recipes(self, entries)
+    }
+
+    async fn set_recipe_entry(&self, entry: &RecipeEntry) -> Result<(), Error> {
+        LocalStore::set_recipe_entry(self, entry)
+    }
+
+    async fn delete_recipe_entry(&self, id: &str) -> Result<(), Error> {
+        LocalStore::delete_recipe_entry(self, id)
+    }
+
+    async fn get_image(&self, src: &str) -> Result<Option<String>, Error> {
+        self.get(&image_key(src))
+    }
+
+    async fn set_image(&self, src: &str, data_url: &str) -> Result<(), Error> {
+        self.set(&image_key(src), data_url)
+    }
+}
+
+/// Wait for an IndexedDB request to finish and return its result.
+async fn await_request(request: &IdbRequest) -> Result<JsValue, Error> {
+    let promise = Promise::new(&mut |resolve, reject| {
+        request.set_onsuccess(Some(&resolve));
+        request.set_onerror(Some(&reject));
+    });
+    let result = JsFuture::from(promise).await;
+    request.set_onsuccess(None);
+    request.set_onerror(None);
+    result.map_err(|err| Error::from(format!("IndexedDB request failed: {:?}", err)))?;
+    Ok(request.result()?)
+}
+
+/// Wait for an IndexedDB transaction to commit.
+async fn await_transaction(transaction: &IdbTransaction) -> Result<(), Error> {
+    let promise = Promise::new(&mut |resolve, reject| {
+        transaction.set_oncomplete(Some(&resolve));
+        transaction.set_onerror(Some(&reject));
+        transaction.set_onabort(Some(&reject));
+    });
+    JsFuture::from(promise)
+        .await
+        .map_err(|err| Error::from(format!("IndexedDB transaction failed: {:?}", err)))?;
+    Ok(())
+}
+
+/// A [LocalCache] backed by IndexedDB which has far more room than local
+/// storage and doesn't block the page while it works.
+#[derive(Clone, Debug)]
+pub struct IndexedDbStore {
+    // Opening the database is async so we open it on first use.
+    db: Rc<RefCell<Option<IdbDatabase>>>,
+    local_store: LocalStore,
+}
+
+impl IndexedDbStore {
+    pub fn new(local_store: LocalStore) -> Self {
+        Self {
+            db: Rc::new(RefCell::new(None)),
+            local_store,
+        }
+    }
+
+    /// Whether the browser supports IndexedDB.
+    pub fn is_supported() -> bool {
+        web_sys::window()
+            .and_then(|w| w.indexed_db().ok())
+            .flatten()
+            .is_some()
+    }
+
+    async fn open() -> Result<IdbDatabase, Error> {
+        let factory = web_sys::window()
+            .and_then(|w| w.indexed_db().ok())
+            .flatten()
+            .ok_or_else(|| Error::from("IndexedDB is unavailable"))?;
+        let request: IdbOpenDbRequest = factory.open_with_u32(DB_NAME, DB_VERSION)?;
+        let on_upgrade = Closure::<dyn FnMut(Event)>::new(move |evt: Event| {
+            let db = evt
+                .target()
+                .and_then(|t| t.dyn_into::<IdbOpenDbRequest>().ok())
+                .and_then(|r| r.result().ok())
+                .and_then(|db| db.dyn_into::<IdbDatabase>().ok());
+            if let Some(db) = db {
+                for name in [RECIPES, IMAGES] {
+                    if !db.object_store_names().contains(name) {
+                        info!(name, "Creating IndexedDB object store");
+                        if let Err(err) = db.create_object_store(name) {
+                            error!(?err, name, "Failed to create object store");
+                        }
+                    }
+                }
+            }
+        });
+        request.set_onupgradeneeded(Some(on_upgrade.as_ref().unchecked_ref()));
+        let result = await_request(&request).await;
+        request.set_onupgradeneeded(None);
+        Ok(result?.dyn_into::<IdbDatabase>()?)
+    }
+
+    async fn db(&self) -> Result<IdbDatabase, Error> {
+        if let Some(db) = self.db.borrow().as_ref() {
+            return Ok(db.clone());
+        }
+        let db = Self::open().await?;
+        *self.db.borrow_mut() = Some(db.clone());
+        if let Err(err) = self.import_local_store(&db).await {
+            error!(?err, "Failed to move cached recipes to IndexedDB");
+        }
+        Ok(db)
+    }
+
+    /// Recipes used to be cached in local storage. Move them over so they
+    /// don't take up the local storage quota.
+    async fn import_local_store(&self, db: &IdbDatabase) -> Result<(), Error> {
+        let entries = match self.local_store.get_recipes()? {
+            Some(entries) => entries,
+            None => return Ok(()),
+        };
+        info!(count = entries.len(), "Moving cached recipes to IndexedDB");
+        let (transaction, store) = Self::store(db, RECIPES, IdbTransactionMode::Readwrite)?;
+        for entry in entries.iter() {
+            put_json(&store, entry.recipe_id(), entry)?;
+        }
+        await_transaction(&transaction).await?;
+        for entry in entries.iter() {
+            self.local_store.delete_recipe_entry(entry.recipe_id())?;
+        }
+        Ok(())
+    }
+
+    fn store(
+        db: &IdbDatabase,
+        name: &str,
+        mode: IdbTransactionMode,
+    ) -> Result<(IdbTransaction, IdbObjectStore), Error> {
+        let transaction = db.transaction_with_str_and_mode(name, mode)?;
+        let store = transaction.object_store(name)?;
+        Ok((transaction, store))
+    }
+
+    async fn get(&self, name: &str, key: &str) -> Result<Option<JsValue>, Error> {
+        let db = self.db().await?;
+        let (_, store) = Self::store(&db, name, IdbTransactionMode::Readonly)?;
+        let value = await_request(&store.get(&JsValue::from_str(key))?).await?;
+        Ok(if value.is_undefined() {
+            None
+        } else {
+            Some(value)
+        })
+    }
+
+    async fn put(&self, name: &str, key: &str, value: &JsValue) -> Result<(), Error> {
+        let db = self.db().await?;
+        let (transaction, store) = Self::store(&db, name, IdbTransactionMode::Readwrite)?;
+        store.put_with_key(value, &JsValue::from_str(key))?;
+        await_transaction(&transaction).await
+    }
+}
+
+fn put_json(store: &IdbObjectStore, key: &str, entry: &RecipeEntry) -> Result<(), Error> {
+    let serialized = to_string(entry).map_err(|e| format!("{}", e))?;
+    store.put_with_key(&JsValue::from_str(&serialized), &JsValue::from_str(key))?;
+    Ok(())
+}
+
+fn parse_entry(value: &JsValue) -> Option<RecipeEntry> {
+    let serialized = value.as_string()?;
+    match from_str(&serialized) {
+        Ok(entry) => Some(entry),
+        Err(err) => {
+            error!(?err, "Failed to parse cached recipe entry");
+            None
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl LocalCache for IndexedDbStore {
+    async fn get_recipes(&self) -> Result<Option<Vec<RecipeEntry>>, Error> {
+        let db = self.db().await?;
+        let (_, store) = Self::store(&db, RECIPES, IdbTransactionMode::Readonly)?;
+        let values: Array = await_request(&store.get_all()?).await?.dyn_into()?;
+        let entries = values
+            .iter()
+            .filter_map(|v| parse_entry(&v))
+            .collect::<Vec<RecipeEntry>>();
+        debug!(count = entries.len(), "Loaded cached recipes");
+        if entries.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(entries))
+    }
+
+    async fn get_recipe_entry(&self, id: &str) -> Result<Option<RecipeEntry>, Error> {
+        Ok(self
+            .get(RECIPES, id)
+            .await?
+            .and_then(|value| parse_entry(&value)))
+    }
+
+    async fn set_all_recipes(&self, entries: &Vec<RecipeEntry>) -> Result<(), Error> {
+        let db = self.db().await?;
+        let (transaction, store) = Self::store(&db, RECIPES, IdbTransactionMode::Readwrite)?;
+        store.clear()?;
+        for entry in entries {
+            put_json(&store, entry.recipe_id(), entry)?;
+        }
+        await_transaction(&transaction).await
+    }
+
+    async fn set_recipe_entry(&self, entry: &RecipeEntry) -> Result<(), Error> {
+        let serialized = to_string(entry).map_err(|e| format!("{}", e))?;
+        self.put(RECIPES, entry.recipe_id(), &JsValue::from_str(&serialized))
+            .await
+    }
+
+    async fn delete_recipe_entry(&self, id: &str) -> Result<(), Error> {
+        let db = self.db().await?;
+        let (transaction, store) = Self::store(&db, RECIPES, IdbTransactionMode::Readwrite)?;
+        store.delete(&JsValue::from_str(id))?;
+        await_transaction(&transaction).await
+    }
+
+    async fn get_image(&self, src: &str) -> Result<Option<String>, Error> {
+        Ok(self.get(IMAGES, src).await?.and_then(|v| v.as_string()))
+    }
+
+    async fn set_image(&self, src: &str, data_url: &str) -> Result<(), Error> {
+        self.put(IMAGES, src, &JsValue::from_str(data_url)).await
+    }
+}
+
+/// The best [LocalCache] this browser supports.
+pub fn new_cache() -> Rc<dyn LocalCache> {
+    if IndexedDbStore::is_supported() {
+        Rc::new(IndexedDbStore::new(LocalStore::new()))
+    } else {
+        debug!("IndexedDB is unsupported so caching in local storage");
+        Rc::new(LocalStore::new())
+    }
+}
@@ -12,12 +12,22 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 use sycamore::{futures::spawn_local_scoped, prelude::*};
-use tracing::{error, info};
+use tracing::{debug, error, info};
+use wasm_bindgen::JsCast;
+use web_sys::{Event, HtmlInputElement};
 
-use crate::app_state::{Message, StateHandler};
+use crate::{
+    app_state::{Message, StateHandler},
+    components::toast,
+    i18n::{t, t_args},
+};
 use recipes::RecipeEntry;
 
-const STARTER_RECIPE: &'static str = "title: TITLE_PLACEHOLDER
+/// The starter templates for a new recipe as (name, text).
+const TEMPLATES: &'static [(&'static str, &'static str)] = &[
+    (
+        "Basic",
+        "title: TITLE_PLACEHOLDER
 
 Description here.
 
@@ -26,14 +36,73 @@ step:
 1 ingredient
 
 Instructions here
-";
+",
+    ),
+    (
+        "Bread",
+        "title: TITLE_PLACEHOLDER
+
+A simple loaf of bread.
+
+step:
+
+3 cup flour
+1 1/2 tsp salt
+2 tsp yeast
+1 1/4 cup water
+
+Mix the ingredients together and knead until smooth. Let rise until doubled.
+
+step:
+
+1 tbsp butter
+
+Shape the loaf and place it in a buttered pan. Let it rise again and then bake
+until golden.
+",
+    ),
+    (
+        "Soup",
+        "title: TITLE_PLACEHOLDER
+
+A hearty soup.
+
+step:
+
+1 onion (chopped)
+2 carrot (chopped)
+2 tbsp olive oil
+
+Cook the vegetables in the oil until soft.
+
+step:
+
+4 cup broth
+1 tsp salt
+
+Add the broth and simmer for 30 minutes.
+",
+    ),
+];
+
+fn template_text(name: &str) -> &'static str {
+    TEMPLATES
+        .iter()
+        .find(|(n, _)| *n == name)
+        .unwrap_or(&TEMPLATES[0])
+        .1
+}
 
 #[component]
 pub fn AddRecipe<'ctx, G: Html>(cx: Scope<'ctx>, sh: StateHandler<'ctx>) -> View<G> {
     let recipe_title = create_signal(cx, String::new());
     let category = create_signal(cx, String::new());
+    let template = create_signal(cx, TEMPLATES[0].0.to_owned());
     let create_recipe_signal = create_signal(cx, ());
     let dirty = create_signal(cx, false);
+    // Recipe text read from a photo which replaces the template.
+    let draft = create_signal(cx, Option::<String>::None);
+    let reading_photo = create_signal(cx, false);
 
     let entry = create_memo(cx, || {
         let category = category.get().as_ref().to_owned();
@@ -42,47 +111,131 @@ pub fn AddRecipe<'ctx, G: Html>(cx: Scope<'ctx>, sh: StateHandler<'ctx>) -> View
         } else {
             Some(category)
         };
+        let text = match draft.get().as_ref() {
+            Some(text) => text.clone(),
+            None => template_text(template.get().as_str()).to_owned(),
+        };
         RecipeEntry(
-            recipe_title
-                .get()
-                .as_ref()
-                .to_lowercase()
-                .replace(" ", "_")
-                .replace("\n", ""),
-            STARTER_RECIPE
-                .replace("TITLE_PLACEHOLDER", recipe_title.get().as_str())
+            recipes::slugify(recipe_title.get().as_str()),
+            text.replace("TITLE_PLACEHOLDER", recipe_title.get().as_str())
                 .replace("\r", ""),
             category,
+            None,
         )
     });
+    let recipe_id = create_memo(cx, || entry.get().recipe_id().to_owned());
+    let exists = sh.get_selector(cx, move |state| {
+        state.get().recipes.contains_key(recipe_id.get().as_str())
+    });
+    let template_options = View::new_fragment(
+        TEMPLATES
+            .iter()
+            .map(|(name, _)| {
+                let label = t(&format!("add_recipe.template.{}", name.to_lowercase()));
+                view! {cx, option(value=*name) { (label) } }
+            })
+            .collect(),
+    );
+
+    let on_photo = move |evt: Event| {
+        let file = evt
+            .target()
+            .and_then(|t| t.dyn_into::<HtmlInputElement>().ok())
+            .and_then(|input| input.files())
+            .and_then(|files| files.get(0));
+        let file = match file {
+            Some(file) => file,
+            None => return,
+        };
+        debug!(name = %file.name(), "Reading recipe from a photo");
+        reading_photo.set(true);
+        spawn_local_scoped(cx, async move {
+            let store = crate::api::HttpStore::get_from_context(cx);
+            if let Ok(result) = store.ocr_recipe(file).await {
+                // The title comes from the title field so it can still be
+                // changed before the recipe is created.
+                let title_line = format!("title: {}", result.title);
+                draft.set(Some(result.text.replacen(
+                    &title_line,
+                    "title: TITLE_PLACEHOLDER",
+                    1,
+                )));
+                recipe_title.set(result.title);
+                dirty.set(true);
+            }
+            reading_photo.set(false);
+        });
+    };
 
     view! {cx,
-        label(for="recipe_title") { "Recipe Title" }
+        label(for="recipe_title") { (t("add_recipe.title")) }
         input(bind:value=recipe_title, type="text", name="recipe_title", id="recipe_title", on:change=move |_| {
             dirty.set(true);
         })
+        p(class="recipe_id") {
+            (if recipe_id.get().is_empty() {
+                t("add_recipe.enter_title")
+            } else if *exists.get() {
+                t_args("add_recipe.exists", &[("id", &recipe_id.get())])
+            } else {
+                t_args("add_recipe.recipe_id", &[("id", &recipe_id.get())])
+            })
+        }
+        (if draft.get().is_some() {
+            view! {cx,
+                p {
+                    (t("add_recipe.using_photo")) " "
+                    button(type="button", class="outline", on:click=move |_| draft.set(None)) { (t("add_recipe.use_template")) }
+                }
+            }
+        } else {
+            let template_options = template_options.clone();
+            view! {cx,
+                label(for="recipe_template") { (t("add_recipe.template")) }
+                select(name="recipe_template", id="recipe_template", bind:value=template) {
+                    (template_options)
+                }
+                label(for="recipe_photo") {
+                    (t("add_recipe.from_photo"))
+                    input(type="file", id="recipe_photo", name="recipe_photo", accept="image/*", capture="environment", disabled=*reading_photo.get(), on:change=on_photo)
+                }
+                (if *reading_photo.get() {
+                    view! {cx, p(aria-busy="true") { (t("add_recipe.reading_photo")) } }
+                } else {
+                    View::empty()
+                })
+            }
+        })
         button(on:click=move |_| {
             create_recipe_signal.trigger_subscribers();
             if !*dirty.get_untracked() {
                 return;
             }
+            if recipe_id.get_untracked().is_empty() {
+                toast::error_message(cx, &t("add_recipe.title_required"), None);
+                return;
+            }
             spawn_local_scoped(cx, {
                 let store = crate::api::HttpStore::get_from_context(cx);
                 async move {
                     let entry = entry.get_untracked();
-                    // TODO(jwall): Better error reporting here.
                     match store.fetch_recipe_text(entry.recipe_id()).await {
                         Ok(Some(_)) => {
-                            // TODO(jwall): We should tell the user that this id already exists
                             info!(recipe_id = entry.recipe_id(), "Recipe already exists");
+                            toast::error_message(
+                                cx,
+                                &t_args("add_recipe.exists", &[("id", &entry.recipe_id())]),
+                                None,
+                            );
                             return;
                         }
                         Ok(None) => {
                             // noop
                         }
                         Err(err) => {
-                            // TODO(jwall): We should tell the user that this is failing
-                            error!(?err)
+                            error!(?err, "Unable to check if the recipe exists");
+                            toast::error_message(cx, &t("add_recipe.check_failed"), None);
+                            return;
                         }
                     }
                     sh.dispatch(cx, Message::SaveRecipe((*entry).clone(), Some(Box::new({
@@ -91,6 +244,6 @@ pub fn AddRecipe<'ctx, G: Html>(cx: Scope<'ctx>, sh: StateHandler<'ctx>) -> View
                     }))));
                 }
             });
-        }) { "Create" }
+        }) { (t("add_recipe.create")) }
     }
 }
@@ -0,0 +1,140 @@
+// Copyright 2023 Jeremy Wall (Jeremy@marzhilsltudios.com)
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+use std::collections::BTreeMap;
+
+use client_api::{get_endpoints, Endpoint};
+use sycamore::{futures::spawn_local_scoped, prelude::*};
+use tracing::{debug, error};
+use wasm_bindgen::{JsCast, UnwrapThrowExt};
+use web_sys::{HtmlInputElement, KeyboardEvent};
+
+use crate::i18n::{t, t_args};
+
+/// What came back from the last request.
+#[derive(Debug, Clone, PartialEq)]
+struct Exchange {
+    path: String,
+    status: u16,
+    elapsed_ms: f64,
+    body: String,
+}
+
+/// Pretty print the body if it is json.
+fn pretty_body(body: &str) -> String {
+    serde_json::from_str::<serde_json::Value>(body)
+        .ok()
+        .and_then(|value| serde_json::to_string_pretty(&value).ok())
+        .unwrap_or_else(|| body.to_owned())
+}
+
+/// Lists the GET endpoints of the api and shows the raw response for any of
+/// them. Useful for seeing what the server has when devices disagree.
+#[component]
+pub fn ApiExplorer<G: Html>(cx: Scope) -> View<G> {
+    let store = create_ref(cx, crate::api::HttpStore::get_from_context(cx));
+    let endpoints = create_ref(cx, get_endpoints());
+    let selected = create_signal(cx, 0usize);
+    let params = create_signal(cx, BTreeMap::<String, String>::new());
+    let exchange = create_signal(cx, Option::<Exchange>::None);
+    let in_flight = create_signal(cx, false);
+    let endpoint = create_memo(cx, || endpoints[*selected.get()]);
+    let path = create_memo(cx, || endpoint.get().fill(&params.get()));
+    let send = move || {
+        let path = path.get_untracked().as_ref().clone();
+        in_flight.set(true);
+        spawn_local_scoped(cx, async move {
+            debug!(path, "Sending explorer request");
+            let start = js_sys::Date::now();
+            match store.fetch_raw(&path).await {
+                Ok((status, body)) => exchange.set(Some(Exchange {
+                    path,
+                    status,
+                    elapsed_ms: js_sys::Date::now() - start,
+                    body: pretty_body(&body),
+                })),
+                Err(err) => {
+                    error!(?err, path, "Explorer request failed");
+                    exchange.set(Some(Exchange {
+                        path,
+                        status: 0,
+                        elapsed_ms: js_sys::Date::now() - start,
+                        body: err.to_string(),
+                    }));
+                }
+            }
+            in_flight.set(false);
+        });
+    };
+    view! {cx,
+        h2 { (t("explorer.heading")) }
+        p { (t("explorer.description")) }
+        div(class="grid") {
+            nav(aria-label=t("explorer.endpoints")) {
+                ul(class="api-endpoints") {
+                    (View::new_fragment(endpoints.iter().enumerate().map(|(idx, endpoint)| {
+                        let Endpoint { path, description, .. } = *endpoint;
+                        let current = create_memo(cx, move || {
+                            if *selected.get() == idx { "true" } else { "false" }
+                        });
+                        view! {cx,
+                            li {
+                                button(type="button", class="outline", aria-current=current.get(), on:click=move |_| {
+                                    selected.set(idx);
+                                    params.set(BTreeMap::new());
+                                    exchange.set(None);
+                                }) { code { (path) } }
+                                " " small { (description) }
+                            }
+                        }
+                    }).collect()))
+                }
+            }
+            div(class="api-request") {
+                h3 { code { "GET /api/v2" (path.get()) } }
+                p { (t_args("explorer.responds_with", &[("type", &endpoint.get().response)])) }
+                (View::new_fragment(endpoint.get().params().into_iter().map(|name| {
+                    let id = format!("api_param_{}", name);
+                    view! {cx,
+                        label(for=id.clone()) { (name) }
+                        input(id=id, type="text", on:input=move |evt: web_sys::Event| {
+                            let input: HtmlInputElement = evt.target().unwrap_throw().unchecked_into();
+                            params.modify().insert(name.to_owned(), input.value());
+                        }, on:keydown=move |evt: web_sys::Event| {
+                            let evt: KeyboardEvent = evt.unchecked_into();
+                            if evt.key() == "Enter" {
+                                send();
+                            }
+                        })
+                    }
+                }).collect()))
+                button(type="button", disabled=*in_flight.get(), on:click=move |_| send()) { (t("explorer.send")) }
+                (if let Some(exchange) = exchange.get().as_ref().clone() {
+                    let Exchange { path, status, elapsed_ms, body } = exchange;
+                    view! {cx,
+                        p(role="status") {
+                            (t_args("explorer.result", &[
+                                ("path", &path),
+                                ("status", &status),
+                                ("ms", &(elapsed_ms.round() as u64)),
+                            ]))
+                        }
+                        pre(class="api-response") { (body) }
+                    }
+                } else {
+                    View::empty()
+                })
+            }
+        }
+    }
+}
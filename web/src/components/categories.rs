@@ -13,9 +13,15 @@
 // limitations under the License.
 use std::collections::{BTreeMap, BTreeSet};
 
-use crate::app_state::{Message, StateHandler};
-use sycamore::prelude::*;
-use tracing::instrument;
+use client_api::DefaultsApplied;
+
+use crate::{
+    app_state::{Message, StateHandler},
+    components::toast,
+    i18n::{t, t_args},
+};
+use sycamore::{futures::spawn_local_scoped, prelude::*};
+use tracing::{error, instrument};
 
 #[derive(Props)]
 struct CategoryRowProps<'ctx> {
@@ -37,6 +43,7 @@ fn CategoryRow<'ctx, G: Html>(cx: Scope<'ctx>, props: CategoryRowProps<'ctx>) ->
     let category = create_signal(cx, category);
     let ingredient_clone = ingredient.clone();
     let ingredient_clone2 = ingredient.clone();
+    let category_label = t_args("categories.category_for", &[("ingredient", &ingredient)]);
     let recipes = create_memo(cx, move || {
         ingredient_recipe_map
             .get()
@@ -54,11 +61,10 @@ fn CategoryRow<'ctx, G: Html>(cx: Scope<'ctx>, props: CategoryRowProps<'ctx>) ->
                 Indexed(
                     iterable=recipes,
                     view=|cx, r| {
-                        let recipe_name = r.clone();
-                        let href = if recipe_name == "Staples" {
-                            "/ui/manage/staples".to_owned()
+                        let (recipe_name, href) = if r == "Staples" {
+                            (t("categories.staples"), "/ui/manage/staples".to_owned())
                         } else {
-                            format!("/ui/recipe/edit/{}", r)
+                            (r.clone(), format!("/ui/recipe/edit/{}", r))
                         };
                         view!{cx,
                             a(href=href) { (recipe_name) } br()
@@ -66,7 +72,7 @@ fn CategoryRow<'ctx, G: Html>(cx: Scope<'ctx>, props: CategoryRowProps<'ctx>) ->
                     }
                 )
             }
-            td() { input(type="text", list="category_options", bind:value=category, on:change={
+            td() { input(type="text", list="category_options", aria-label=category_label, bind:value=category, on:change={
                 let ingredient_clone = ingredient.clone();
                 move |_| {
                     sh.dispatch(cx, Message::UpdateCategory(ingredient_clone.clone(), category.get_untracked().as_ref().clone(), None));
@@ -76,6 +82,94 @@ fn CategoryRow<'ctx, G: Html>(cx: Scope<'ctx>, props: CategoryRowProps<'ctx>) ->
     }
 }
 
+/// Describe what applying the default categories and staples changed.
+pub fn defaults_message(applied: &DefaultsApplied) -> String {
+    if applied.staples {
+        t_args(
+            "categories.defaults_applied_with_staples",
+            &[("count", &applied.categories)],
+        )
+    } else {
+        t_args(
+            "categories.defaults_applied",
+            &[("count", &applied.categories)],
+        )
+    }
+}
+
+#[instrument(skip_all)]
+#[component]
+fn CategorySuggestions<'ctx, G: Html>(cx: Scope<'ctx>, sh: StateHandler<'ctx>) -> View<G> {
+    let suggestions = create_signal(cx, Vec::<(String, String)>::new());
+    // Suggestions that haven't been accepted or dismissed are unsaved changes.
+    let pending = create_memo(cx, || !suggestions.get().is_empty());
+    crate::routing::track_unsaved_changes(cx, "category_suggestions", pending);
+    view! {cx,
+        button(type="button", on:click=move |_| {
+            spawn_local_scoped(cx, async move {
+                let store = crate::api::HttpStore::get_from_context(cx);
+                match store.fetch_category_suggestions().await {
+                    Ok(list) => {
+                        let list = list
+                            .into_iter()
+                            .filter_map(|(i, c)| c.map(|c| (i, c)))
+                            .collect::<Vec<(String, String)>>();
+                        if list.is_empty() {
+                            toast::message(cx, &t("categories.no_suggestions"), None);
+                        }
+                        suggestions.set(list);
+                    }
+                    Err(err) => {
+                        error!(?err, "Failed to fetch category suggestions");
+                        toast::error_message(cx, &t("categories.suggestions_failed"), None);
+                    }
+                }
+            });
+        }) { (t("categories.suggest")) } " "
+        button(type="button", class="outline", on:click=move |_| {
+            spawn_local_scoped(cx, async move {
+                let store = crate::api::HttpStore::get_from_context(cx);
+                match store.apply_defaults().await {
+                    Ok(applied) => {
+                        toast::message(cx, &defaults_message(&applied), None);
+                        sh.dispatch(cx, Message::RefreshCategories);
+                    }
+                    Err(err) => error!(?err, "Failed to apply the default categories"),
+                }
+            });
+        }) { (t("categories.use_defaults")) } " "
+        (if suggestions.get().is_empty() {
+            View::empty()
+        } else {
+            let rows = View::new_fragment(suggestions.get().iter().cloned().map(|(i, c)| {
+                view! {cx,
+                    tr() {
+                        td() { (i) }
+                        td() { (c) }
+                    }
+                }
+            }).collect());
+            view! {cx,
+                table() {
+                    tr {
+                        th { (t("categories.ingredient")) }
+                        th { (t("categories.suggested")) }
+                    }
+                    (rows)
+                }
+                button(type="button", on:click=move |_| {
+                    let mappings = suggestions.get_untracked().as_ref().clone();
+                    sh.dispatch(cx, Message::UpdateCategories(mappings, None));
+                    suggestions.set(Vec::new());
+                }) { (t("categories.accept_all")) } " "
+                button(type="button", class="outline", on:click=move |_| {
+                    suggestions.set(Vec::new());
+                }) { (t("categories.dismiss")) }
+            }
+        })
+    }
+}
+
 #[instrument(skip_all)]
 #[component]
 pub fn Categories<'ctx, G: Html>(cx: Scope<'ctx>, sh: StateHandler<'ctx>) -> View<G> {
@@ -96,6 +190,9 @@ pub fn Categories<'ctx, G: Html>(cx: Scope<'ctx>, sh: StateHandler<'ctx>) -> Vie
         let mut ingredients: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
         for (recipe_id, r) in state.recipes.iter() {
             for (_, i) in r.get_ingredients().iter() {
+                if i.recipe_reference().is_some() {
+                    continue;
+                }
                 let ingredient_name = i.name.clone();
                 ingredients
                     .entry(ingredient_name)
@@ -121,6 +218,9 @@ pub fn Categories<'ctx, G: Html>(cx: Scope<'ctx>, sh: StateHandler<'ctx>) -> Vie
         let mut ingredients = BTreeSet::new();
         for (_, r) in state.recipes.iter() {
             for (_, i) in r.get_ingredients().iter() {
+                if i.recipe_reference().is_some() {
+                    continue;
+                }
                 ingredients.insert(i.name.clone());
             }
         }
@@ -134,17 +234,18 @@ pub fn Categories<'ctx, G: Html>(cx: Scope<'ctx>, sh: StateHandler<'ctx>) -> Vie
             let cat = category_map
                 .get(i)
                 .map(|v| v.clone())
-                .unwrap_or_else(|| "None".to_owned());
+                .unwrap_or_else(|| t("categories.none"));
             mapping_list.push((i.clone(), cat));
         }
         mapping_list.sort_by(|tpl1, tpl2| tpl1.1.cmp(&tpl2.1));
         mapping_list
     });
     view! {cx,
+        CategorySuggestions(sh)
         table() {
             tr {
-                th { "Ingredient" }
-                th { "Category" }
+                th { (t("categories.ingredient")) }
+                th { (t("categories.category")) }
             }
             Keyed(
                 iterable=rows,
@@ -0,0 +1,147 @@
+// Copyright 2023 Jeremy Wall (Jeremy@marzhilsltudios.com)
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.\
+//! A confirmation dialog for destructive actions. Ask with [confirm] and
+//! await the answer. The dialog is a native `<dialog>` shown as a modal so
+//! focus stays in it, Escape cancels and both buttons work from the
+//! keyboard. Cancel comes first so it gets the focus and a stray Enter
+//! doesn't destroy anything.
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use js_sys::{Function, Promise};
+use sycamore::prelude::*;
+use tracing::{debug, error};
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::HtmlDialogElement;
+
+use crate::i18n::t;
+
+const DIALOG_ID: &'static str = "confirm-dialog";
+const MESSAGE_ID: &'static str = "confirm-dialog-message";
+
+/// The question the dialog is asking. This is provided as context at the
+/// root of the UI like the [super::toast::Notifier].
+#[derive(Clone)]
+pub struct Confirmer {
+    message: RcSignal<String>,
+    confirm_label: RcSignal<String>,
+    resolve: Rc<RefCell<Option<Function>>>,
+}
+
+impl Confirmer {
+    pub fn new() -> Self {
+        Self {
+            message: create_rc_signal(String::new()),
+            confirm_label: create_rc_signal(String::new()),
+            resolve: Rc::new(RefCell::new(None)),
+        }
+    }
+
+    pub fn provide_context(cx: Scope) {
+        provide_context(cx, Self::new());
+    }
+
+    pub fn get_from_context(cx: Scope) -> Self {
+        use_context::<Self>(cx).clone()
+    }
+
+    fn dialog() -> Option<HtmlDialogElement> {
+        web_sys::window()?
+            .document()?
+            .get_element_by_id(DIALOG_ID)?
+            .dyn_into::<HtmlDialogElement>()
+            .ok()
+    }
+
+    /// Ask the user to confirm `message`. The confirm button says
+    /// `confirm_label`. Resolves to true only if they choose it. A question
+    /// that is still open when a new one is asked is answered no.
+    pub async fn ask(&self, message: &str, confirm_label: &str) -> bool {
+        self.answer(false);
+        let dialog = match Self::dialog() {
+            Some(dialog) => dialog,
+            None => {
+                // Pages rendered without the dialog still need an
+                // answer.
+                error!("No confirm dialog present");
+                return web_sys::window()
+                    .and_then(|w| w.confirm_with_message(message).ok())
+                    .unwrap_or(false);
+            }
+        };
+        self.message.set(message.to_owned());
+        self.confirm_label.set(confirm_label.to_owned());
+        let resolve = self.resolve.clone();
+        let promise = Promise::new(&mut |resolve_fn, _reject| {
+            resolve.replace(Some(resolve_fn));
+        });
+        if let Err(err) = dialog.show_modal() {
+            error!(?err, "Unable to show the confirm dialog");
+            self.answer(false);
+        }
+        debug!(message, "Waiting for confirmation");
+        match JsFuture::from(promise).await {
+            Ok(answer) => answer.as_bool().unwrap_or(false),
+            Err(err) => {
+                error!(?err, "Confirm dialog failed");
+                false
+            }
+        }
+    }
+
+    /// Answer the open question if there is one and close the dialog.
+    fn answer(&self, confirmed: bool) {
+        let resolve = self.resolve.borrow_mut().take();
+        if let Some(resolve) = resolve {
+            debug!(confirmed, "Answered confirmation");
+            let _ = resolve.call1(&JsValue::NULL, &JsValue::from_bool(confirmed));
+        }
+        if let Some(dialog) = Self::dialog() {
+            if dialog.open() {
+                dialog.close();
+            }
+        }
+    }
+}
+
+/// Ask the user to confirm a destructive action. See [Confirmer::ask].
+pub async fn confirm<'a>(cx: Scope<'a>, message: &str, confirm_label: &str) -> bool {
+    Confirmer::get_from_context(cx)
+        .ask(message, confirm_label)
+        .await
+}
+
+#[component]
+pub fn ConfirmDialog<'a, G: Html>(cx: Scope<'a>) -> View<G> {
+    let confirmer = create_ref(cx, Confirmer::get_from_context(cx));
+    let message = create_ref(cx, confirmer.message.clone());
+    let confirm_label = create_ref(cx, confirmer.confirm_label.clone());
+    view! {cx,
+        // Escape and anything else that closes the dialog without a
+        // button counts as no.
+        dialog(id=DIALOG_ID, class="confirm", role="alertdialog", aria-modal="true",
+            aria-labelledby=MESSAGE_ID, on:close=move |_| confirmer.answer(false)) {
+            p(id=MESSAGE_ID) { (message.get()) }
+            div(class="confirm-buttons") {
+                button(type="button", class="secondary", on:click=move |_| confirmer.answer(false)) {
+                    (t("confirm.cancel"))
+                } " "
+                button(type="button", class="destructive", on:click=move |_| confirmer.answer(true)) {
+                    (confirm_label.get())
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,75 @@
+// Copyright 2023 Jeremy Wall (Jeremy@marzhilsltudios.com)
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+use client_api::MarkCooked;
+use sycamore::prelude::*;
+
+use crate::{
+    app_state::{Message, StateHandler},
+    components::toast,
+    i18n::t,
+};
+
+#[derive(Props)]
+pub struct CookedProps<'ctx> {
+    recipe_id: String,
+    sh: StateHandler<'ctx>,
+}
+
+/// Marks a planned recipe as cooked along with any extra portions that went
+/// in the freezer. Cooking uses up the ingredients in the inventory or the
+/// portions of a freezer meal unless it is unticked.
+#[component]
+pub fn Cooked<'ctx, G: Html>(cx: Scope<'ctx>, props: CookedProps<'ctx>) -> View<G> {
+    let CookedProps { recipe_id, sh } = props;
+    let recipe_id = create_ref(cx, recipe_id);
+    let cooked = sh.get_selector(cx, move |state| state.get().cooked.contains(recipe_id));
+    let from_freezer = sh.get_selector(cx, move |state| {
+        state.get().plan_freezer_meals.contains(recipe_id)
+    });
+    let portions = create_signal(cx, String::new());
+    let deplete = create_signal(cx, true);
+    view! {cx,
+        div(role="group", class="cooked no-print") {
+            (if *from_freezer.get() {
+                view! {cx,
+                    label {
+                        input(type="checkbox", bind:checked=deplete)
+                        (t("cooked.take_out"))
+                    }
+                }
+            } else {
+                view! {cx,
+                    input(type="number", min="0", aria-label=t("cooked.extra_portions"), placeholder=t("cooked.extra_portions"), bind:value=portions)
+                    label {
+                        input(type="checkbox", bind:checked=deplete)
+                        (t("cooked.use_up"))
+                    }
+                }
+            })
+            button(class="secondary", on:click=move |_| {
+                let cooked = MarkCooked {
+                    recipe_id: recipe_id.clone(),
+                    extra_portions: portions.get_untracked().trim().parse::<i32>().unwrap_or(0),
+                    deplete: *deplete.get_untracked(),
+                };
+                if let Err(err) = cooked.validate() {
+                    toast::error_message(cx, &err.message, None);
+                    return;
+                }
+                portions.set(String::new());
+                sh.dispatch(cx, Message::MarkCooked(cooked));
+            }) { (if *cooked.get() { t("cooked.cooked") } else { t("cooked.mark") }) }
+        }
+    }
+}
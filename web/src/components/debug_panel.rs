@@ -0,0 +1,83 @@
+// Copyright 2023 Jeremy Wall (Jeremy@marzhilsltudios.com)
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+use sycamore::prelude::*;
+use tracing::debug;
+
+use crate::{
+    action_log::{ActionLog, LogEntry},
+    app_state::{Message, StateHandler},
+    i18n::{t, t_args},
+};
+
+/// Shows the dispatched messages and lets you restore the state as it was
+/// after any of them. Only rendered with the debug_panel feature.
+#[component]
+pub fn DebugPanel<'ctx, G: Html>(cx: Scope<'ctx>, sh: StateHandler<'ctx>) -> View<G> {
+    let log = create_ref(cx, ActionLog::get_from_context(cx));
+    let entries = create_memo(cx, move || {
+        log.entries()
+            .get()
+            .iter()
+            .rev()
+            .cloned()
+            .collect::<Vec<LogEntry>>()
+    });
+    let selected = create_signal(cx, Option::<LogEntry>::None);
+    let selected_state = create_memo(cx, move || {
+        selected
+            .get()
+            .as_ref()
+            .as_ref()
+            .map(|entry| {
+                serde_json::to_string_pretty(entry.state.as_ref())
+                    .unwrap_or_else(|e| t_args("debug.serialize_failed", &[("error", &e)]))
+            })
+            .unwrap_or_default()
+    });
+    view! {cx,
+        details(class="debug-panel no-print") {
+            summary { (t_args("debug.action_log", &[("count", &entries.get().len())])) }
+            button(type="button", on:click=move |_| {
+                log.clear();
+                selected.set(None);
+            }) { (t("debug.clear")) }
+            ol {
+                Keyed(
+                    iterable=entries,
+                    view=move |cx, entry| {
+                        let seq = entry.seq;
+                        let message = entry.message.clone();
+                        let restore = entry.state.clone();
+                        view! {cx,
+                            li {
+                                code { (seq) ": " (message) } " "
+                                button(type="button", on:click=move |_| selected.set(Some(entry.clone()))) { (t("debug.show")) } " "
+                                button(type="button", on:click=move |_| {
+                                    debug!(seq, "Restoring state from the action log");
+                                    sh.dispatch(cx, Message::RestoreState(Box::new(restore.as_ref().clone())));
+                                }) { (t("debug.restore")) }
+                            }
+                        }
+                    },
+                    key=|entry| entry.seq,
+                )
+            }
+            (if selected.get().is_some() {
+                view! {cx, pre { (selected_state.get()) } }
+            } else {
+                View::empty()
+            })
+        }
+    }
+}
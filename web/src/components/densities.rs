@@ -0,0 +1,83 @@
+// Copyright 2023 Jeremy Wall (Jeremy@marzhilsltudios.com)
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+use sycamore::prelude::*;
+
+use crate::{
+    app_state::{Message, StateHandler},
+    components::toast,
+    i18n::t,
+};
+
+/// Lets the user set their own ingredient densities for converting between
+/// volume and weight. These override the built in densities.
+#[component]
+pub fn Densities<'ctx, G: Html>(cx: Scope<'ctx>, sh: StateHandler<'ctx>) -> View<G> {
+    let densities = sh.get_selector(cx, |state| {
+        state
+            .get()
+            .densities
+            .iter()
+            .map(|(name, grams)| (name.clone(), *grams))
+            .collect::<Vec<(String, u32)>>()
+    });
+    let name = create_signal(cx, String::new());
+    let grams = create_signal(cx, String::new());
+    view! {cx,
+        h2 { (t("densities.heading")) }
+        p { (t("densities.description")) }
+        table {
+            tr {
+                th { (t("densities.ingredient")) }
+                th { (t("densities.grams_per_cup")) }
+                th {}
+            }
+            Indexed(
+                iterable=densities,
+                view=move |cx, (ingredient, grams_per_cup)| {
+                    let remove = ingredient.clone();
+                    view! {cx,
+                        tr {
+                            td { (ingredient) }
+                            td { (grams_per_cup) }
+                            td {
+                                button(type="button", class="outline", on:click=move |_| {
+                                    sh.dispatch(cx, Message::SetDensity(remove.clone(), None));
+                                }) { (t("densities.remove")) }
+                            }
+                        }
+                    }
+                },
+            )
+        }
+        div(role="group") {
+            input(type="text", aria-label=t("densities.ingredient"), placeholder=t("densities.ingredient"), bind:value=name)
+            input(type="number", min="1", aria-label=t("densities.grams_per_cup"), placeholder=t("densities.grams_per_cup"), bind:value=grams)
+            button(on:click=move |_| {
+                let ingredient = name.get_untracked().trim().to_lowercase();
+                if ingredient.is_empty() {
+                    toast::error_message(cx, &t("densities.name_required"), None);
+                    return;
+                }
+                match grams.get_untracked().trim().parse::<u32>() {
+                    Ok(value) if value > 0 => {
+                        sh.dispatch(cx, Message::SetDensity(ingredient, Some(value)));
+                        name.set(String::new());
+                        grams.set(String::new());
+                    }
+                    _ => toast::error_message(cx, &t("densities.whole_number"), None),
+                }
+            }) { (t("densities.save")) }
+        }
+    }
+}
@@ -0,0 +1,142 @@
+// Copyright 2023 Jeremy Wall (Jeremy@marzhilsltudios.com)
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+use std::collections::BTreeSet;
+
+use client_api::parse_tags;
+use sycamore::prelude::*;
+
+use crate::{
+    app_state::{Message, StateHandler},
+    components::toast,
+    i18n::{t, t_args},
+};
+
+fn join_tags(tags: &BTreeSet<String>) -> String {
+    tags.iter().cloned().collect::<Vec<String>>().join(", ")
+}
+
+/// Lets the user tag ingredients with allergens or dietary tags and set the
+/// tags their household avoids.
+#[component]
+pub fn DietaryTags<'ctx, G: Html>(cx: Scope<'ctx>, sh: StateHandler<'ctx>) -> View<G> {
+    let ingredient_tags = sh.get_selector(cx, |state| {
+        state
+            .get()
+            .ingredient_tags
+            .iter()
+            .map(|(name, tags)| (name.clone(), join_tags(tags)))
+            .collect::<Vec<(String, String)>>()
+    });
+    let saved_restrictions = sh.get_selector(cx, |state| join_tags(&state.get().restrictions));
+    let restrictions = create_signal(cx, saved_restrictions.get_untracked().as_ref().clone());
+    create_effect(cx, || {
+        restrictions.set(saved_restrictions.get().as_ref().clone());
+    });
+    let name = create_signal(cx, String::new());
+    let tags = create_signal(cx, String::new());
+    view! {cx,
+        h2 { (t("diet.heading")) }
+        p { (t("diet.description")) }
+        div(role="group") {
+            input(type="text", aria-label=t("diet.avoid"), placeholder=t("diet.avoid"), bind:value=restrictions)
+            button(on:click=move |_| {
+                sh.dispatch(cx, Message::UpdateRestrictions(parse_tags(&restrictions.get_untracked())));
+                toast::message(cx, &t("diet.saved_restrictions"), None);
+            }) { (t("diet.save")) }
+        }
+        table {
+            tr {
+                th { (t("diet.ingredient")) }
+                th { (t("diet.tags")) }
+                th {}
+            }
+            Indexed(
+                iterable=ingredient_tags,
+                view=move |cx, (ingredient, ingredient_tags)| {
+                    let remove = ingredient.clone();
+                    view! {cx,
+                        tr {
+                            td { (ingredient) }
+                            td { (ingredient_tags) }
+                            td {
+                                button(type="button", class="outline", on:click=move |_| {
+                                    sh.dispatch(cx, Message::SetIngredientTags(remove.clone(), BTreeSet::new()));
+                                }) { (t("diet.remove")) }
+                            }
+                        }
+                    }
+                },
+            )
+        }
+        div(role="group") {
+            input(type="text", aria-label=t("diet.ingredient"), placeholder=t("diet.ingredient"), bind:value=name)
+            input(type="text", aria-label=t("diet.tags_example"), placeholder=t("diet.tags_example"), bind:value=tags)
+            button(on:click=move |_| {
+                let ingredient = name.get_untracked().trim().to_lowercase();
+                if ingredient.is_empty() {
+                    toast::error_message(cx, &t("diet.name_required"), None);
+                    return;
+                }
+                let parsed = parse_tags(&tags.get_untracked());
+                if parsed.is_empty() {
+                    toast::error_message(cx, &t("diet.tag_required"), None);
+                    return;
+                }
+                sh.dispatch(cx, Message::SetIngredientTags(ingredient, parsed));
+                name.set(String::new());
+                tags.set(String::new());
+            }) { (t("diet.save")) }
+        }
+    }
+}
+
+/// Warns about planned recipes that conflict with the household's dietary
+/// restrictions.
+#[component]
+pub fn DietWarnings<'ctx, G: Html>(cx: Scope<'ctx>, sh: StateHandler<'ctx>) -> View<G> {
+    let conflicts = sh.get_selector(cx, |state| {
+        state
+            .get()
+            .diet_conflicts()
+            .into_iter()
+            .map(|(title, restriction, ingredients)| {
+                t_args(
+                    "diet.conflict",
+                    &[
+                        ("recipe", &title),
+                        ("restriction", &restriction),
+                        ("ingredients", &join_tags(&ingredients)),
+                    ],
+                )
+            })
+            .collect::<Vec<String>>()
+    });
+    view! {cx,
+        (if conflicts.get().is_empty() {
+            View::empty()
+        } else {
+            view! {cx,
+                div(class="diet-warnings", role="alert") {
+                    h3 { (t("diet.warnings")) }
+                    ul {
+                        Indexed(
+                            iterable=conflicts,
+                            view=|cx, conflict| view! {cx, li { (conflict) } },
+                        )
+                    }
+                }
+            }
+        })
+    }
+}
@@ -14,12 +14,21 @@
 
 use sycamore::prelude::*;
 
+use crate::error_report;
+use crate::i18n::t;
+
 #[component]
 pub fn Footer<G: Html>(cx: Scope) -> View<G> {
+    let report_errors = create_signal(cx, error_report::consented());
+    create_effect(cx, || error_report::set_consent(*report_errors.get()));
     view! {cx,
         nav(class="no-print") {
             ul {
-                li { a(href="https://github.com/zaphar/kitchen") { "On Github" } }
+                li { a(href="https://github.com/zaphar/kitchen") { (t("footer.github")) } }
+                li {
+                    label(for="report_errors_cb") { (t("footer.report_errors")) }
+                    input(id="report_errors_cb", type="checkbox", bind:checked=report_errors)
+                }
             }
         }
     }
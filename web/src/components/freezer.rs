@@ -0,0 +1,144 @@
+// Copyright 2023 Jeremy Wall (Jeremy@marzhilsltudios.com)
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+use std::collections::BTreeMap;
+
+use client_api::{FreezerItem, NewFreezerItem};
+use sycamore::{futures::spawn_local_scoped, prelude::*};
+use tracing::error;
+
+use crate::{
+    app_state::{Message, StateHandler},
+    components::toast,
+    i18n::{t, t_args},
+};
+
+/// Lists the portions in the freezer and lets them be planned or used up.
+#[component]
+pub fn Freezer<'ctx, G: Html>(cx: Scope<'ctx>, sh: StateHandler<'ctx>) -> View<G> {
+    let store = create_ref(cx, crate::api::HttpStore::get_from_context(cx));
+    let items = create_signal(cx, Vec::<FreezerItem>::new());
+    spawn_local_scoped(cx, async move {
+        match store.fetch_freezer().await {
+            Ok(stored) => items.set(stored),
+            Err(err) => error!(?err, "Failed to load the freezer"),
+        }
+    });
+    let titles = sh.get_selector(cx, |state| {
+        state
+            .get()
+            .recipes
+            .iter()
+            .map(|(id, r)| (id.clone(), r.title.clone()))
+            .collect::<BTreeMap<String, String>>()
+    });
+    let new_recipe = create_signal(cx, String::new());
+    let new_portions = create_signal(cx, String::new());
+    view! {cx,
+        h2 { (t("freezer.heading")) }
+        p { (t("freezer.description")) }
+        (if items.get().is_empty() {
+            view! {cx, p { (t("freezer.empty")) } }
+        } else {
+            View::empty()
+        })
+        table(class="freezer") {
+            Keyed(
+                iterable=items,
+                view=move |cx, item| {
+                    let id = item.id;
+                    let recipe_id = item.recipe_id.clone();
+                    let title = titles
+                        .get_untracked()
+                        .get(&item.recipe_id)
+                        .cloned()
+                        .unwrap_or_else(|| item.recipe_id.clone());
+                    let portions = t_args("freezer.portions", &[("count", &item.portions)]);
+                    let frozen_on = t_args("freezer.frozen_on", &[("date", &item.frozen_on)]);
+                    view! {cx,
+                        tr {
+                            td { (title) }
+                            td { (portions) }
+                            td { (frozen_on) }
+                            td {
+                                button(type="button", class="secondary", on:click=move |_| {
+                                    sh.dispatch(cx, Message::UpdateRecipeCount(recipe_id.clone(), 1));
+                                    sh.dispatch(cx, Message::SetPlanFreezerMeal(recipe_id.clone(), true));
+                                    toast::message(cx, &t("freezer.planned"), None);
+                                }) { (t("freezer.plan_it")) } " "
+                                button(type="button", class="outline", on:click=move |_| {
+                                    spawn_local_scoped(cx, async move {
+                                        match store.take_freezer_portions(id, 1).await {
+                                            Ok(Some(left)) => {
+                                                let mut items = items.modify();
+                                                if let Some(item) = items.iter_mut().find(|i| i.id == id) {
+                                                    *item = left;
+                                                }
+                                            }
+                                            Ok(None) => items.modify().retain(|i| i.id != id),
+                                            Err(err) => error!(?err, "Failed to use a freezer portion"),
+                                        }
+                                    });
+                                }) { (t("freezer.use_one")) } " "
+                                button(type="button", class="outline", on:click=move |_| {
+                                    spawn_local_scoped(cx, async move {
+                                        match store.delete_freezer_item(id).await {
+                                            Ok(()) => items.modify().retain(|i| i.id != id),
+                                            Err(err) => error!(?err, "Failed to remove freezer item"),
+                                        }
+                                    });
+                                }) { (t("freezer.remove")) }
+                            }
+                        }
+                    }
+                },
+                key=|item| (item.id, item.portions),
+            )
+        }
+        div(role="group", class="freezer-add") {
+            select(aria-label=t("freezer.recipe"), bind:value=new_recipe) {
+                option(value="") { (t("freezer.recipe")) }
+                (View::new_fragment(titles.get().iter().map(|(id, title)| {
+                    let id = id.clone();
+                    let title = title.clone();
+                    view! {cx, option(value=id) { (title) } }
+                }).collect()))
+            }
+            input(type="number", min="1", aria-label=t("freezer.portions_placeholder"), placeholder=t("freezer.portions_placeholder"), bind:value=new_portions)
+            button(on:click=move |_| {
+                let item = NewFreezerItem {
+                    recipe_id: new_recipe.get_untracked().as_ref().clone(),
+                    portions: new_portions.get_untracked().trim().parse::<i32>().unwrap_or(0),
+                    frozen_on: chrono::Local::now().date_naive(),
+                };
+                if let Err(err) = item.validate() {
+                    toast::error_message(cx, &err.message, None);
+                    return;
+                }
+                spawn_local_scoped(cx, async move {
+                    match store.add_freezer_item(&item).await {
+                        Ok(Some(added)) => {
+                            items.modify().push(added);
+                            new_portions.set(String::new());
+                        }
+                        Ok(None) => (),
+                        Err(err) => {
+                            error!(?err, "Failed to add to the freezer");
+                            toast::error_message(cx, &t("freezer.add_failed"), None);
+                        }
+                    }
+                });
+            }) { (t("freezer.add")) }
+        }
+    }
+}
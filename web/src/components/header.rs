@@ -13,22 +13,83 @@
 // limitations under the License.
 
 use sycamore::prelude::*;
+use web_sys::HtmlElement;
 
-use crate::app_state::StateHandler;
+use crate::app_state::{Message, StateHandler};
+use crate::i18n::{self, t, Locale};
+use crate::js_lib;
+use crate::theme::Theme;
+
+/// The id of the element holding the page content.
+pub const CONTENT_ID: &'static str = "content";
+
+/// Lets keyboard users skip past the navigation to the page content.
+#[component]
+pub fn SkipLink<G: Html>(cx: Scope) -> View<G> {
+    view! {cx,
+        a(class="skip-link", href=format!("#{}", CONTENT_ID), on:click=|evt: web_sys::Event| {
+            // The router would treat the fragment as a route so we move the
+            // focus ourselves.
+            evt.prevent_default();
+            if let Ok(Some(content)) = js_lib::get_element_by_id::<HtmlElement>(CONTENT_ID) {
+                let _ = content.focus();
+            }
+        }) { (t("header.skip")) }
+    }
+}
+
+#[component]
+pub fn ThemeToggle<'ctx, G: Html>(cx: Scope<'ctx>, h: StateHandler<'ctx>) -> View<G> {
+    let theme = h.get_selector(cx, |sig| sig.get().theme);
+    let label = create_memo(cx, || match *theme.get() {
+        Theme::System => t("header.theme_auto"),
+        Theme::Light => t("header.theme_light"),
+        Theme::Dark => t("header.theme_dark"),
+    });
+    view! {cx,
+        button(type="button", class="outline", aria-label=t("header.switch_theme"), on:click=move |_| {
+            h.dispatch(cx, Message::SetTheme(theme.get_untracked().next()));
+        }) { (label.get()) }
+    }
+}
+
+/// Lets the user choose the language for the ui.
+#[component]
+pub fn LocaleSelect<'ctx, G: Html>(cx: Scope<'ctx>, h: StateHandler<'ctx>) -> View<G> {
+    let locale = create_signal(cx, i18n::current_locale().as_str().to_owned());
+    view! {cx,
+        select(aria-label=t("header.language"), bind:value=locale, on:change=move |_| {
+            if let Ok(locale) = locale.get_untracked().parse::<Locale>() {
+                h.dispatch(cx, Message::SetLocale(locale));
+            }
+        }) {
+            (View::new_fragment(Locale::ALL.iter().map(|locale| {
+                let value = locale.as_str();
+                let label = locale.label();
+                view! {cx, option(value=value) { (label) } }
+            }).collect()))
+        }
+    }
+}
 
 #[component]
 pub fn Header<'ctx, G: Html>(cx: Scope<'ctx>, h: StateHandler<'ctx>) -> View<G> {
     let login = h.get_selector(cx, |sig| match &sig.get().auth {
         Some(id) => id.user_id.clone(),
-        None => "Login".to_owned(),
+        None => t("header.login"),
     });
     view! {cx,
-        nav(class="no-print") {
-            h1(class="title") { "Kitchen" }
+        SkipLink()
+        nav(class="no-print", aria-label=t("header.main_nav")) {
+            h1(class="title") { (t("header.title")) }
             ul {
-                li { a(href="/ui/planning/select") { "MealPlan" } }
-                li { a(href="/ui/manage/ingredients") { "Manage" } }
+                li { a(href="/ui/planning/select") { (t("header.meal_plan")) } }
+                li { a(href="/ui/shop") { (t("header.shop")) } }
+                li { a(href="/ui/manage/ingredients") { (t("header.manage")) } }
+                li { a(href="/ui/settings") { (t("header.settings")) } }
                 li { a(href="/ui/login") { (login.get()) } }
+                li { ThemeToggle(h) }
+                li { LocaleSelect(h) }
             }
         }
     }
@@ -0,0 +1,47 @@
+// Copyright 2023 Jeremy Wall (Jeremy@marzhilsltudios.com)
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+use std::cell::RefCell;
+
+use sycamore::prelude::*;
+
+use crate::{i18n::t, js_lib::WakeLock};
+
+/// A switch that keeps the screen on while it is checked. Browsers without the
+/// wake lock api get a note instead.
+#[component]
+pub fn KeepScreenOn<G: Html>(cx: Scope) -> View<G> {
+    if !WakeLock::is_supported() {
+        return view! {cx,
+            small(class="no-print") { (t("keep_screen_on.unsupported")) }
+        };
+    }
+    let keep_on = create_signal(cx, false);
+    // The wake lock is released when this scope is disposed.
+    let wake_lock = create_ref(cx, RefCell::new(Option::<WakeLock>::None));
+    create_effect(cx, move || {
+        if *keep_on.get() {
+            if wake_lock.borrow().is_none() {
+                *wake_lock.borrow_mut() = WakeLock::request();
+            }
+        } else {
+            wake_lock.borrow_mut().take();
+        }
+    });
+    view! {cx,
+        label(class="no-print") {
+            input(type="checkbox", role="switch", bind:checked=keep_on)
+            (t("keep_screen_on.label"))
+        }
+    }
+}
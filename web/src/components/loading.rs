@@ -0,0 +1,71 @@
+// Copyright 2022 Jeremy Wall
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+use sycamore::prelude::*;
+
+use crate::{
+    api::{HttpStore, Operation},
+    i18n::t,
+};
+
+#[derive(Props)]
+pub struct LoadingProps<'a, G: Html> {
+    pub children: Children<'a, G>,
+    /// The operations whose data the children show.
+    pub ops: &'static [Operation],
+}
+
+/// Show a skeleton above the children while any of the operations they depend
+/// on are still loading.
+#[component]
+pub fn Loading<'a, G: Html>(cx: Scope<'a>, props: LoadingProps<'a, G>) -> View<G> {
+    let LoadingProps { children, ops } = props;
+    let children = children.call(cx);
+    let tracker = HttpStore::get_from_context(cx).tracker().clone();
+    let loading = create_selector(cx, move || ops.iter().any(|op| tracker.is_loading(*op)));
+    view! {cx,
+        (if *loading.get() {
+            view! {cx,
+                div(class="skeleton no-print", role="progressbar", aria-busy="true", aria-label=t("loading.loading")) {
+                    span(class="spinner")
+                    div(class="skeleton-line")
+                    div(class="skeleton-line")
+                    div(class="skeleton-line")
+                }
+            }
+        } else {
+            View::empty()
+        })
+        (children)
+    }
+}
+
+/// Let the user know that we lost the server and are retrying requests so they
+/// don't silently end up looking at cached data.
+#[component]
+pub fn Reconnecting<G: Html>(cx: Scope) -> View<G> {
+    let tracker = HttpStore::get_from_context(cx).tracker().clone();
+    let reconnecting = create_selector(cx, move || tracker.is_reconnecting());
+    view! {cx,
+        (if *reconnecting.get() {
+            view! {cx,
+                div(class="reconnecting no-print", role="status", aria-live="polite") {
+                    span(class="spinner")
+                    (t("loading.reconnecting"))
+                }
+            }
+        } else {
+            View::empty()
+        })
+    }
+}
@@ -12,30 +12,69 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 pub mod add_recipe;
+pub mod api_explorer;
 pub mod categories;
+pub mod confirm;
+pub mod cooked;
+pub mod debug_panel;
+pub mod densities;
+pub mod diet;
 pub mod footer;
+pub mod freezer;
 pub mod header;
+pub mod keep_screen_on;
+pub mod loading;
 pub mod number_field;
+pub mod plan_history;
 pub mod plan_list;
+pub mod plan_rules;
+pub mod plan_summary;
+pub mod prep_list;
 pub mod recipe;
 pub mod recipe_list;
+pub mod recipe_manager;
+pub mod recipe_picker;
 pub mod recipe_plan;
 pub mod recipe_selection;
+pub mod shop;
 pub mod shopping_list;
+pub mod shortcuts;
 pub mod staples;
+pub mod stats;
+pub mod substitutions;
 pub mod tabs;
 pub mod toast;
+pub mod trash;
 
 pub use add_recipe::*;
+pub use api_explorer::*;
 pub use categories::*;
+pub use cooked::*;
+pub use debug_panel::*;
+pub use densities::*;
+pub use diet::*;
 pub use footer::*;
+pub use freezer::*;
 pub use header::*;
+pub use keep_screen_on::*;
+pub use loading::*;
 pub use number_field::*;
+pub use plan_history::*;
 pub use plan_list::*;
+pub use plan_rules::*;
+pub use plan_summary::*;
+pub use prep_list::*;
 pub use recipe::*;
 pub use recipe_list::*;
+pub use recipe_manager::*;
+pub use recipe_picker::*;
 pub use recipe_plan::*;
 pub use recipe_selection::*;
+pub use shop::*;
 pub use shopping_list::*;
+pub use shortcuts::*;
 pub use staples::*;
+pub use stats::*;
+pub use substitutions::*;
 pub use tabs::*;
+pub use trash::*;
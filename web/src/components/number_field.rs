@@ -50,7 +50,9 @@ where
             input(type="number", id=id, name=name, class="item-count-sel", min=min_field, max="99", step="1", bind:valueAsNumber=counter, on:input=move |evt| {
                 on_change.as_ref().map(|f| f(evt));
             })
-            span(class="item-count-inc-dec", on:click=move |_| {
+            // The arrow keys already step the number input so these are
+            // only for pointers and screen readers can skip them.
+            span(class="item-count-inc-dec", aria-hidden="true", on:click=move |_| {
                 let i = *counter.get_untracked();
                 let target = js_lib::get_element_by_id::<HtmlInputElement>(&inc_target_id).unwrap().expect(&format!("No such element with id {}", inc_target_id));
                 counter.set(i+1.0);
@@ -59,7 +61,7 @@ where
                 target.dispatch_event(&web_sys::Event::new("input").expect("Failed to create new event")).expect("Failed to dispatch event to target");
             }) { "▲" }
             " "
-            span(class="item-count-inc-dec", on:click=move |_| {
+            span(class="item-count-inc-dec", aria-hidden="true", on:click=move |_| {
                 let i = *counter.get_untracked();
                 let target = js_lib::get_element_by_id::<HtmlInputElement>(&dec_target_id).unwrap().expect(&format!("No such element with id {}", dec_target_id));
                 if i > min {
@@ -0,0 +1,456 @@
+// Copyright 2023 Jeremy Wall (Jeremy@marzhilsltudios.com)
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+use std::collections::{BTreeMap, BTreeSet};
+use std::str::FromStr;
+
+use chrono::NaiveDate;
+use client_api::{InventoryDiff, PlanShare};
+use recipes::{
+    shopping::{shopping_list, ShoppingPlan},
+    IngredientKey,
+};
+use sycamore::{futures::spawn_local_scoped, prelude::*};
+use tracing::error;
+
+use crate::{
+    api::HttpStore,
+    app_state::{AppState, Message, StateHandler},
+    components::confirm::confirm,
+    i18n::{t, t_args},
+};
+
+/// A plan as it was saved along with the shopping list state for it.
+#[derive(Clone, Debug, PartialEq)]
+struct SavedPlan {
+    date: NaiveDate,
+    recipe_counts: Vec<(String, i32)>,
+    filtered_ingredients: BTreeSet<IngredientKey>,
+    modified_amts: BTreeMap<IngredientKey, String>,
+    extras: Vec<(String, String)>,
+}
+
+/// A saved plan ready to show.
+#[derive(Clone, Debug, PartialEq)]
+struct PlanDetails {
+    date: NaiveDate,
+    recipes: Vec<(String, i32)>,
+    shopping_list: Vec<String>,
+}
+
+impl PlanDetails {
+    fn new(state: &AppState, plan: &SavedPlan) -> Self {
+        let mut recipes = Vec::new();
+        for (id, count) in plan.recipe_counts.iter().filter(|(_, count)| *count > 0) {
+            // Recipes in old plans may have been deleted since.
+            let title = match state.recipes.get(id) {
+                Some(recipe) => recipe.title.clone(),
+                None => id.clone(),
+            };
+            recipes.push((title, *count));
+        }
+        let shopping_plan = ShoppingPlan {
+            recipe_counts: plan
+                .recipe_counts
+                .iter()
+                .filter(|(_, count)| *count > 0)
+                .map(|(id, count)| (id.clone(), *count as usize))
+                .collect(),
+            household_size: state.household_size,
+            filtered_ingredients: plan.filtered_ingredients.clone(),
+            densities: Some(state.density_table()),
+            ..Default::default()
+        };
+        let mut shopping_list = shopping_list(&shopping_plan, &state.recipes)
+            .into_iter()
+            .filter(|(_, item)| !item.filtered)
+            .map(|(key, item)| {
+                let i = item.ingredient;
+                let amt = plan
+                    .modified_amts
+                    .get(&key)
+                    .cloned()
+                    .unwrap_or_else(|| format!("{}", i.amt.normalize()));
+                match i.form {
+                    Some(form) => format!("{} {} ({})", amt, i.name, form),
+                    None => format!("{} {}", amt, i.name),
+                }
+            })
+            .collect::<Vec<String>>();
+        shopping_list.extend(
+            plan.extras
+                .iter()
+                .map(|(amt, name)| format!("{} {}", amt, name)),
+        );
+        Self {
+            date: plan.date,
+            recipes,
+            shopping_list,
+        }
+    }
+
+    fn as_text(&self) -> String {
+        let mut text = t_args("history.plan_for", &[("date", &self.date)]);
+        text.push_str(&format!("\n\n{}:\n", t("history.recipes")));
+        for (title, count) in self.recipes.iter() {
+            text.push_str(&format!("{} x {}\n", count, title));
+        }
+        text.push_str(&format!("\n{}:\n", t("history.shopping_list")));
+        for item in self.shopping_list.iter() {
+            text.push_str(item);
+            text.push('\n');
+        }
+        text
+    }
+
+    fn download_url(&self) -> String {
+        format!(
+            "data:text/plain;charset=utf-8,{}",
+            String::from(js_sys::encode_uri_component(&self.as_text()))
+        )
+    }
+}
+
+async fn load_plan(store: &HttpStore, date: NaiveDate) -> Result<SavedPlan, crate::api::Error> {
+    let recipe_counts = store.fetch_plan_for_date(&date).await?.unwrap_or_default();
+    let (filtered_ingredients, modified_amts, extras) =
+        store.fetch_inventory_for_date(&date).await?;
+    Ok(SavedPlan {
+        date,
+        recipe_counts,
+        filtered_ingredients,
+        modified_amts,
+        extras,
+    })
+}
+
+fn describe_key(key: &IngredientKey) -> String {
+    let form = key.form();
+    if form.is_empty() {
+        key.name().clone()
+    } else {
+        format!("{} ({})", key.name(), form)
+    }
+}
+
+fn describe_diff(diff: &InventoryDiff) -> Vec<String> {
+    let mut lines = Vec::new();
+    for key in diff.checked_off.iter() {
+        lines.push(t_args(
+            "history.checked_off",
+            &[("item", &describe_key(key))],
+        ));
+    }
+    for key in diff.unchecked.iter() {
+        lines.push(t_args("history.unchecked", &[("item", &describe_key(key))]));
+    }
+    for (key, old, new) in diff.changed_amts.iter() {
+        let as_planned = t("history.as_planned");
+        lines.push(t_args(
+            "history.changed",
+            &[
+                ("item", &describe_key(key)),
+                ("from", old.as_ref().unwrap_or(&as_planned)),
+                ("to", new.as_ref().unwrap_or(&as_planned)),
+            ],
+        ));
+    }
+    for (amt, name) in diff.added_extras.iter() {
+        lines.push(t_args("history.added", &[("amount", amt), ("item", name)]));
+    }
+    for (amt, name) in diff.removed_extras.iter() {
+        lines.push(t_args(
+            "history.removed",
+            &[("amount", amt), ("item", name)],
+        ));
+    }
+    lines
+}
+
+#[derive(Props)]
+pub struct InventoryChangesProps<'ctx> {
+    date: NaiveDate,
+    dates: &'ctx ReadSignal<Vec<NaiveDate>>,
+}
+
+/// Compares the inventory for a plan with another plan or with itself as it
+/// was first planned.
+#[component]
+pub fn InventoryChanges<'ctx, G: Html>(
+    cx: Scope<'ctx>,
+    props: InventoryChangesProps<'ctx>,
+) -> View<G> {
+    let InventoryChangesProps { date, dates } = props;
+    let store = create_ref(cx, HttpStore::get_from_context(cx));
+    let compare_to = create_signal(cx, date.to_string());
+    let diff = create_signal(cx, Option::<InventoryDiff>::None);
+    let others = create_memo(cx, move || {
+        dates
+            .get()
+            .iter()
+            .filter(|d| **d != date)
+            .map(|d| d.to_string())
+            .collect::<Vec<String>>()
+    });
+    let lines = create_memo(cx, move || {
+        diff.get()
+            .as_ref()
+            .as_ref()
+            .map(describe_diff)
+            .unwrap_or_default()
+    });
+    let this_plan = date.to_string();
+    view! {cx,
+        h3 { (t("history.inventory_changes")) }
+        label(for="compare_to") { (t("history.compare_with")) }
+        select(id="compare_to", bind:value=compare_to) {
+            option(value=this_plan) { (t("history.as_first_saved")) }
+            Indexed(
+                iterable=others,
+                view=|cx, other| {
+                    let label = other.clone();
+                    view! {cx, option(value=other) { (label) } }
+                },
+            )
+        }
+        button(type="button", on:click=move |_| {
+            let from = match NaiveDate::from_str(compare_to.get_untracked().as_str()) {
+                Ok(from) => from,
+                Err(err) => {
+                    error!(?err, "Invalid plan date to compare with");
+                    return;
+                }
+            };
+            spawn_local_scoped(cx, async move {
+                match store.fetch_inventory_diff(&from, &date).await {
+                    Ok(changes) => diff.set(Some(changes)),
+                    Err(err) => error!(?err, "Failed to compare inventories"),
+                }
+            });
+        }) { (t("history.compare")) }
+        (if diff.get().is_none() {
+            View::empty()
+        } else if lines.get().is_empty() {
+            view! {cx, p { (t("history.nothing_changed")) } }
+        } else {
+            view! {cx,
+                ul {
+                    Indexed(
+                        iterable=lines,
+                        view=|cx, line| view! {cx, li { (line) } },
+                    )
+                }
+            }
+        })
+    }
+}
+
+/// The full url for a share link so it can be sent to someone.
+fn share_url(share: &PlanShare) -> String {
+    let origin = web_sys::window()
+        .and_then(|w| w.location().origin().ok())
+        .unwrap_or_default();
+    format!("{}{}", origin, share.path())
+}
+
+#[derive(Props)]
+pub struct ShareWeekProps {
+    /// The first day of the week.
+    week: NaiveDate,
+}
+
+/// Shares a week of plans with a read-only link or stops sharing it.
+#[component]
+pub fn ShareWeek<G: Html>(cx: Scope, props: ShareWeekProps) -> View<G> {
+    let ShareWeekProps { week } = props;
+    let store = create_ref(cx, HttpStore::get_from_context(cx));
+    let share = create_signal(cx, Option::<PlanShare>::None);
+    spawn_local_scoped(cx, async move {
+        match store.fetch_week_share(&week).await {
+            Ok(current) => share.set(current),
+            Err(err) => error!(?err, "Failed to fetch the share for the week"),
+        }
+    });
+    view! {cx,
+        (match share.get().as_ref() {
+            Some(current) => {
+                let url = share_url(current);
+                let qr = current.qr_path();
+                view! {cx,
+                    p {
+                        label { (t("history.share_link"))
+                            input(type="text", readonly=true, value=url)
+                        }
+                        button(type="button", class="secondary", on:click=move |_| {
+                            spawn_local_scoped(cx, async move {
+                                match store.unshare_week(&week).await {
+                                    Ok(_) => share.set(None),
+                                    Err(err) => error!(?err, "Failed to stop sharing the week"),
+                                }
+                            });
+                        }) { (t("history.stop_sharing")) }
+                    }
+                    img(class="share-qr", src=qr, alt=t("history.share_qr"))
+                }
+            }
+            None => view! {cx,
+                button(type="button", class="outline", on:click=move |_| {
+                    spawn_local_scoped(cx, async move {
+                        match store.share_week(&week).await {
+                            Ok(created) => share.set(Some(created)),
+                            Err(err) => error!(?err, "Failed to share the week"),
+                        }
+                    });
+                }) { (t("history.share_week")) }
+            },
+        })
+    }
+}
+
+/// Lists previous plans and shows the recipes and shopping list for the one
+/// that is chosen.
+#[component]
+pub fn PlanHistory<'ctx, G: Html>(cx: Scope<'ctx>, sh: StateHandler<'ctx>) -> View<G> {
+    let store = create_ref(cx, HttpStore::get_from_context(cx));
+    let plan_dates = sh.get_selector(cx, |state| {
+        let mut plans = state
+            .get()
+            .plan_dates
+            .iter()
+            .cloned()
+            .collect::<Vec<NaiveDate>>();
+        plans.sort_unstable_by(|d1, d2| d2.cmp(d1));
+        plans
+    });
+    let loaded = create_signal(cx, Option::<SavedPlan>::None);
+    let details = sh.get_selector(cx, move |state| {
+        loaded
+            .get()
+            .as_ref()
+            .as_ref()
+            .map(|plan| PlanDetails::new(&state.get(), plan))
+    });
+    // The server decides which week this week is so that it agrees with
+    // the user's timezone.
+    let this_week = create_signal(cx, Option::<NaiveDate>::None);
+    let plan_weeks = sh.get_selector(cx, move |state| {
+        let weeks = state.get().plan_weeks();
+        match *this_week.get() {
+            Some(start) => weeks
+                .into_iter()
+                .filter(|(week, _)| *week == start)
+                .collect(),
+            None => weeks,
+        }
+    });
+    view! {cx,
+        div(class="grid") {
+            div() {
+                button(type="button", class="outline", on:click=move |_| {
+                    spawn_local_scoped(cx, async move {
+                        match store.fetch_this_week().await {
+                            Ok(week) => this_week.set(Some(week.start)),
+                            Err(err) => error!(?err, "Failed to fetch this week"),
+                        }
+                    });
+                }) { (t("history.this_week")) } " "
+                button(type="button", class="outline", on:click=move |_| this_week.set(None)) { (t("history.all_weeks")) }
+                (if plan_weeks.get().is_empty() {
+                    view! {cx, p { (t("history.no_plans")) } }
+                } else {
+                    View::empty()
+                })
+                Indexed(
+                    iterable=plan_weeks,
+                    view=move |cx, (week, dates)| {
+                        let week_display = t_args("history.week_of", &[("date", &week)]);
+                        let dates = create_signal(cx, dates);
+                        view! {cx,
+                            h4 { (week_display) }
+                            ShareWeek(week=week)
+                            table() {
+                                Indexed(
+                                    iterable=dates,
+                                    view=move |cx, date| {
+                                        let date_display = format!("{}", date);
+                                        view! {cx,
+                                            tr() {
+                                                td() {
+                                                    button(type="button", class="outline", on:click=move |_| {
+                                                        spawn_local_scoped(cx, async move {
+                                                            match load_plan(store, date).await {
+                                                                Ok(plan) => loaded.set(Some(plan)),
+                                                                Err(err) => error!(?err, "Failed to load plan"),
+                                                            }
+                                                        });
+                                                    }) { (date_display) }
+                                                }
+                                            }
+                                        }
+                                    },
+                                )
+                            }
+                        }
+                    },
+                )
+            }
+            div() {
+                (match details.get().as_ref() {
+                    Some(plan) => {
+                        let date = plan.date;
+                        let heading = t_args("history.plan_for", &[("date", &date)]);
+                        let filename = format!("plan-{}.txt", date);
+                        let download_url = plan.download_url();
+                        let recipes = View::new_fragment(plan.recipes.iter().map(|(title, count)| {
+                            let line = format!("{} x {}", count, title);
+                            view! {cx, li { (line) } }
+                        }).collect());
+                        let shopping_list = View::new_fragment(plan.shopping_list.iter().cloned().map(|item| {
+                            view! {cx, li { (item) } }
+                        }).collect());
+                        view! {cx,
+                            h2 { (heading) }
+                            h3 { (t("history.recipes")) }
+                            ul { (recipes) }
+                            h3 { (t("history.shopping_list")) }
+                            ul { (shopping_list) }
+                            button(type="button", on:click=move |_| {
+                                spawn_local_scoped(cx, async move {
+                                    // Copying replaces any plan already made
+                                    // for today.
+                                    let today = chrono::Local::now().date_naive();
+                                    if plan_dates.get_untracked().contains(&today)
+                                        && !confirm(cx, &t("history.confirm_replace"), &t("history.replace")).await
+                                    {
+                                        return;
+                                    }
+                                    sh.dispatch(cx, Message::DuplicatePlan(date, Some(Box::new(|| {
+                                        sycamore_router::navigate("/ui/planning/plan");
+                                    }))))
+                                });
+                            }) { (t("history.copy_to_today")) } " "
+                            button(type="button", on:click=move |_| {
+                                sh.dispatch(cx, Message::SelectPlanDate(date, Some(Box::new(|| {
+                                    sycamore_router::navigate("/ui/planning/plan");
+                                }))))
+                            }) { (t("history.open")) } " "
+                            a(role="button", class="outline", href=download_url, download=filename) { (t("history.export")) }
+                            InventoryChanges(date=date, dates=plan_dates)
+                        }
+                    }
+                    None => view! {cx, p { (t("history.choose_plan")) } },
+                })
+            }
+        }
+    }
+}
@@ -12,46 +12,66 @@ use chrono::NaiveDate;
 // WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
 // See the License for the specific language governing permissions and
 // limitations under the License.
-use sycamore::prelude::*;
+use sycamore::{futures::spawn_local_scoped, prelude::*};
 
-use crate::app_state::{Message, StateHandler};
+use crate::{
+    app_state::{Message, StateHandler},
+    components::confirm::confirm,
+    i18n::{t, t_args},
+};
 use tracing::instrument;
 
 #[derive(Props)]
 pub struct PlanListProps<'ctx> {
     sh: StateHandler<'ctx>,
-    list: &'ctx ReadSignal<Vec<NaiveDate>>,
+    /// The plan dates grouped by the first day of their week.
+    weeks: &'ctx ReadSignal<Vec<(NaiveDate, Vec<NaiveDate>)>>,
 }
 
 // TODO(jwall): We also need a "new plan button"
-#[instrument(skip_all, fields(dates=?props.list))]
+#[instrument(skip_all, fields(weeks=?props.weeks))]
 #[component]
 pub fn PlanList<'ctx, G: Html>(cx: Scope<'ctx>, props: PlanListProps<'ctx>) -> View<G> {
-    let PlanListProps { sh, list } = props;
+    let PlanListProps { sh, weeks } = props;
     view! {cx,
         div() {
-            table() {
-                Indexed(
-                    iterable=list,
-                    view=move |cx, date| {
-                        let date_display = format!("{}", date);
-                        view!{cx,
-                            tr() {
-                                td() {
-                                    span(role="button", class="outline", on:click=move |_| {
-                                        sh.dispatch(cx, Message::SelectPlanDate(date, None))
-                                    }) { (date_display) }
-                                }
-                                td() {
-                                    span(role="button", class="destructive", on:click=move |_| {
-                                        sh.dispatch(cx, Message::DeletePlan(date, None))
-                                    }) { "Delete Plan" }
-                                }
-                            }
+            Indexed(
+                iterable=weeks,
+                view=move |cx, (week, dates)| {
+                    let week_display = t_args("history.week_of", &[("date", &week)]);
+                    let dates = create_signal(cx, dates);
+                    view!{cx,
+                        h4 { (week_display) }
+                        table() {
+                            Indexed(
+                                iterable=dates,
+                                view=move |cx, date| {
+                                    let date_display = format!("{}", date);
+                                    view!{cx,
+                                        tr() {
+                                            td() {
+                                                button(type="button", class="outline", on:click=move |_| {
+                                                    sh.dispatch(cx, Message::SelectPlanDate(date, None))
+                                                }) { (date_display) }
+                                            }
+                                            td() {
+                                                button(type="button", class="destructive", on:click=move |_| {
+                                                    spawn_local_scoped(cx, async move {
+                                                        let message = t_args("plan_list.confirm_delete", &[("date", &date)]);
+                                                        if confirm(cx, &message, &t("plan_list.delete")).await {
+                                                            sh.dispatch(cx, Message::DeletePlan(date, None))
+                                                        }
+                                                    });
+                                                }) { (t("plan_list.delete")) }
+                                            }
+                                        }
+                                    }
+                                },
+                            )
                         }
-                    },
-                )
-            }
+                    }
+                },
+            )
         }
     }
 }
@@ -0,0 +1,220 @@
+// Copyright 2023 Jeremy Wall (Jeremy@marzhilsltudios.com)
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+use chrono::NaiveDate;
+use client_api::{month_name, PlanRule, PlanSuggestion, RuleViolation, StoredPlanRule};
+use sycamore::{futures::spawn_local_scoped, prelude::*};
+use tracing::error;
+
+use crate::{
+    app_state::{Message, StateHandler},
+    components::toast,
+    i18n::t,
+};
+
+fn month_options<G: Html>(cx: Scope) -> View<G> {
+    View::new_fragment(
+        (1..=12)
+            .map(|month| view! {cx, option(value=month.to_string()) { (month_name(month)) } })
+            .collect(),
+    )
+}
+
+/// Lists the user's planning rules and lets them add new ones.
+#[component]
+pub fn PlanRules<'ctx, G: Html>(cx: Scope<'ctx>) -> View<G> {
+    let store = create_ref(cx, crate::api::HttpStore::get_from_context(cx));
+    let rules = create_signal(cx, Vec::<StoredPlanRule>::new());
+    spawn_local_scoped(cx, async move {
+        match store.fetch_plan_rules().await {
+            Ok(stored) => rules.set(stored),
+            Err(err) => error!(?err, "Failed to load planning rules"),
+        }
+    });
+    let kind = create_signal(cx, "season".to_owned());
+    let tag = create_signal(cx, String::new());
+    let from_month = create_signal(cx, "10".to_owned());
+    let to_month = create_signal(cx, "3".to_owned());
+    let count = create_signal(cx, "1".to_owned());
+    let weeks = create_signal(cx, "3".to_owned());
+    let parse_number =
+        |value: &Signal<String>| value.get_untracked().trim().parse::<u32>().unwrap_or(0);
+    view! {cx,
+        h2 { (t("rules.heading")) }
+        p { (t("rules.description")) }
+        (if rules.get().is_empty() {
+            view! {cx, p { (t("rules.none")) } }
+        } else {
+            View::empty()
+        })
+        table(class="plan-rules") {
+            Keyed(
+                iterable=rules,
+                view=move |cx, stored| {
+                    let id = stored.id;
+                    view! {cx,
+                        tr {
+                            td { (stored.rule.describe()) }
+                            td {
+                                button(type="button", class="outline", on:click=move |_| {
+                                    spawn_local_scoped(cx, async move {
+                                        match store.delete_plan_rule(id).await {
+                                            Ok(()) => rules.modify().retain(|r| r.id != id),
+                                            Err(err) => error!(?err, "Failed to delete planning rule"),
+                                        }
+                                    });
+                                }) { (t("rules.remove")) }
+                            }
+                        }
+                    }
+                },
+                key=|stored| stored.id,
+            )
+        }
+        div(role="group") {
+            select(aria-label=t("rules.kind"), bind:value=kind) {
+                option(value="season") { (t("rules.season")) }
+                option(value="at_least") { (t("rules.at_least")) }
+                option(value="no_repeat") { (t("rules.no_repeat")) }
+            }
+            (match kind.get().as_str() {
+                "season" => view! {cx,
+                    input(type="text", aria-label=t("rules.tag"), placeholder=t("rules.tag"), bind:value=tag)
+                    select(aria-label=t("rules.from_month"), bind:value=from_month) { (month_options(cx)) }
+                    select(aria-label=t("rules.to_month"), bind:value=to_month) { (month_options(cx)) }
+                },
+                "at_least" => view! {cx,
+                    input(type="number", min="1", aria-label=t("rules.count"), placeholder=t("rules.count"), bind:value=count)
+                    input(type="text", aria-label=t("rules.tag"), placeholder=t("rules.tag"), bind:value=tag)
+                },
+                _ => view! {cx,
+                    input(type="number", min="1", aria-label=t("rules.weeks"), placeholder=t("rules.weeks"), bind:value=weeks)
+                },
+            })
+            button(on:click=move |_| {
+                let rule = match kind.get_untracked().as_str() {
+                    "season" => PlanRule::Season {
+                        tag: tag.get_untracked().trim().to_owned(),
+                        from_month: parse_number(from_month),
+                        to_month: parse_number(to_month),
+                    },
+                    "at_least" => PlanRule::AtLeast {
+                        tag: tag.get_untracked().trim().to_owned(),
+                        count: parse_number(count),
+                    },
+                    _ => PlanRule::NoRepeat {
+                        weeks: parse_number(weeks),
+                    },
+                };
+                if let Err(err) = rule.validate() {
+                    toast::error_message(cx, &err.message, None);
+                    return;
+                }
+                spawn_local_scoped(cx, async move {
+                    match store.save_plan_rule(&rule).await {
+                        Ok(Some(stored)) => {
+                            rules.modify().push(stored);
+                            tag.set(String::new());
+                        }
+                        Ok(None) => (),
+                        Err(err) => {
+                            error!(?err, "Failed to save planning rule");
+                            toast::error_message(cx, &t("rules.save_failed"), None);
+                        }
+                    }
+                });
+            }) { (t("rules.add")) }
+        }
+    }
+}
+
+/// Shows the rules the current plan breaks and suggests recipes to add.
+#[component]
+pub fn PlanRuleCheck<'ctx, G: Html>(cx: Scope<'ctx>, sh: StateHandler<'ctx>) -> View<G> {
+    let store = create_ref(cx, crate::api::HttpStore::get_from_context(cx));
+    let plan_date = sh.get_selector(cx, |state| {
+        state
+            .get()
+            .selected_plan_date
+            .unwrap_or_else(|| chrono::Local::now().date_naive())
+    });
+    let planned = sh.get_selector(cx, |state| {
+        state
+            .get()
+            .planned_recipes()
+            .into_iter()
+            .map(|(id, count)| (id, count as i32))
+            .collect::<Vec<(String, i32)>>()
+    });
+    let violations = create_signal(cx, Vec::<RuleViolation>::new());
+    let suggestions = create_signal(cx, Vec::<PlanSuggestion>::new());
+    create_effect(cx, move || {
+        let date: NaiveDate = *plan_date.get();
+        let plan = planned.get().as_ref().clone();
+        spawn_local_scoped(cx, async move {
+            match store.check_plan(&date, &plan).await {
+                Ok(found) => violations.set(found),
+                Err(err) => error!(?err, "Failed to check plan against rules"),
+            }
+        });
+    });
+    view! {cx,
+        div(class="plan-rule-check no-print") {
+            (if violations.get().is_empty() {
+                View::empty()
+            } else {
+                view! {cx, h3 { (t("rules.heading")) } }
+            })
+            ul(class="rule-violations") {
+                Indexed(
+                    iterable=violations,
+                    view=|cx, violation| view! {cx, li { (violation.message) } },
+                )
+            }
+            button(type="button", class="outline", on:click=move |_| {
+                let date = *plan_date.get_untracked();
+                spawn_local_scoped(cx, async move {
+                    match store.fetch_plan_suggestions(&date).await {
+                        Ok(found) => {
+                            if found.is_empty() {
+                                toast::message(cx, &t("rules.no_suggestions"), None);
+                            }
+                            suggestions.set(found);
+                        }
+                        Err(err) => error!(?err, "Failed to load plan suggestions"),
+                    }
+                });
+            }) { (t("rules.suggest")) }
+            ul(class="plan-suggestions") {
+                Keyed(
+                    iterable=suggestions,
+                    view=move |cx, suggestion| {
+                        let recipe_id = suggestion.recipe_id.clone();
+                        let reason = suggestion.reason.clone().unwrap_or_default();
+                        view! {cx,
+                            li {
+                                (suggestion.title) " "
+                                small { (reason) } " "
+                                button(type="button", class="secondary", on:click=move |_| {
+                                    sh.dispatch(cx, Message::UpdateRecipeCount(recipe_id.clone(), 1));
+                                    suggestions.modify().retain(|s| s.recipe_id != recipe_id);
+                                }) { (t("rules.add_suggestion")) }
+                            }
+                        }
+                    },
+                    key=|suggestion| suggestion.recipe_id.clone(),
+                )
+            }
+        }
+    }
+}
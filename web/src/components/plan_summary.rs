@@ -0,0 +1,37 @@
+// Copyright 2023 Jeremy Wall (Jeremy@marzhilsltudios.com)
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+use sycamore::prelude::*;
+
+use crate::{app_state::StateHandler, i18n::t_args};
+
+/// A bar of totals for the plan that updates as recipes are picked.
+#[component]
+pub fn PlanSummary<'ctx, G: Html>(cx: Scope<'ctx>, sh: StateHandler<'ctx>) -> View<G> {
+    let summary = sh.get_selector(cx, |state| state.get().plan_summary());
+    view! {cx,
+        div(class="plan-summary no-print", role="status") {
+            span { (t_args("plan_summary.meals", &[("count", &summary.get().meals)])) }
+            span { (t_args("plan_summary.recipes", &[("count", &summary.get().recipes)])) }
+            span {
+                ({
+                    let minutes = summary.get().cook_time.as_secs() / 60;
+                    t_args(
+                        "plan_summary.cook_time",
+                        &[("hours", &(minutes / 60)), ("minutes", &(minutes % 60))],
+                    )
+                })
+            }
+        }
+    }
+}
@@ -0,0 +1,80 @@
+// Copyright 2023 Jeremy Wall (Jeremy@marzhilsltudios.com)
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+use client_api::PrepList;
+use sycamore::{futures::spawn_local_scoped, prelude::*};
+use tracing::warn;
+
+use crate::{app_state::StateHandler, i18n::t};
+
+#[derive(Props)]
+pub struct PrepChecklistProps<'ctx> {
+    recipe_id: String,
+    sh: StateHandler<'ctx>,
+}
+
+/// A checklist of the ingredients to prepare before cooking a planned recipe.
+/// The list comes from the server and is worked out from the recipe we have
+/// if the server can't be reached.
+#[component]
+pub fn PrepChecklist<'ctx, G: Html>(cx: Scope<'ctx>, props: PrepChecklistProps<'ctx>) -> View<G> {
+    let PrepChecklistProps { recipe_id, sh } = props;
+    let store = crate::api::HttpStore::get_from_context(cx);
+    let recipe_id = create_ref(cx, recipe_id);
+    let people = sh.get_selector(cx, |state| state.get().household_size);
+    let local = sh.get_selector(cx, move |state| {
+        let state = state.get();
+        state.recipes.get(recipe_id).map(|recipe| {
+            PrepList::new(
+                recipe_id.as_str(),
+                &recipe.scale(state.plan_scale(recipe_id)),
+            )
+        })
+    });
+    let prep = create_signal(cx, Option::<PrepList>::None);
+    spawn_local_scoped(cx, async move {
+        let people = *people.get_untracked();
+        let list = match store.fetch_prep_list(recipe_id, people).await {
+            Ok(list) => Some(list),
+            Err(err) => {
+                warn!(?err, "Using the local recipe for the prep list");
+                local.get_untracked().as_ref().clone()
+            }
+        };
+        prep.set(list);
+    });
+    view! {cx,
+        (match prep.get().as_ref() {
+            Some(list) if !list.items.is_empty() => {
+                let items = View::new_fragment(list.items.iter().cloned().map(|item| {
+                    let form = item.form.map(|f| format!(", {}", f)).unwrap_or_default();
+                    view! {cx,
+                        li {
+                            label {
+                                input(type="checkbox")
+                                (item.amt) " " (item.name) (form)
+                            }
+                        }
+                    }
+                }).collect());
+                view! {cx,
+                    details(class="prep-list", open=true) {
+                        summary { (t("prep.heading")) }
+                        ul { (items) }
+                    }
+                }
+            }
+            _ => View::empty(),
+        })
+    }
+}
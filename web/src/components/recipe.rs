@@ -11,27 +11,41 @@
 // WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
 // See the License for the specific language governing permissions and
 // limitations under the License.
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    time::Duration,
+};
+
 use sycamore::{futures::spawn_local_scoped, prelude::*};
 use tracing::{debug, error};
 
 use crate::{
-    app_state::{Message, StateHandler},
+    app_state::{self, Message, StateHandler},
+    components::confirm::confirm,
+    i18n::{meal_label, t, t_args},
     js_lib,
 };
-use recipes::{self, RecipeEntry};
+use recipes::{
+    self,
+    unit::{DensityTable, Quantity},
+    RecipeEntry,
+};
+
+/// How often to autosave a draft of the recipe while it is being edited.
+const DRAFT_INTERVAL_MS: f64 = 2000.0;
 
 fn check_recipe_parses(
     text: &str,
     error_text: &Signal<String>,
     aria_hint: &Signal<&'static str>,
 ) -> bool {
-    if let Err(e) = recipes::parse::as_recipe(text) {
+    if let Err(e) = app_state::parse_recipe(text) {
         error!(?e, "Error parsing recipe");
         error_text.set(e);
         aria_hint.set("true");
         false
     } else {
-        error_text.set(String::from("No parse errors..."));
+        error_text.set(t("recipe.no_parse_errors"));
         aria_hint.set("false");
         true
     }
@@ -46,13 +60,16 @@ pub struct RecipeComponentProps<'ctx> {
 #[component]
 pub fn Editor<'ctx, G: Html>(cx: Scope<'ctx>, props: RecipeComponentProps<'ctx>) -> View<G> {
     let RecipeComponentProps { recipe_id, sh } = props;
-    let store = crate::api::HttpStore::get_from_context(cx);
+    let store = create_ref(cx, crate::api::HttpStore::get_from_context(cx));
     let recipe: &Signal<RecipeEntry> =
         create_signal(cx, RecipeEntry::new(&recipe_id, String::new()));
     let text = create_signal(cx, String::new());
-    let error_text = create_signal(cx, String::from("Parse results..."));
+    let error_text = create_signal(cx, t("recipe.parse_results"));
     let aria_hint = create_signal(cx, "false");
     let category = create_signal(cx, "Entree".to_owned());
+    let local_store = create_ref(cx, crate::api::LocalStore::new());
+    let draft = create_signal(cx, Option::<String>::None);
+    let draft_ts = create_signal(cx, js_sys::Date::now());
 
     spawn_local_scoped(cx, {
         let store = store.clone();
@@ -66,38 +83,89 @@ pub fn Editor<'ctx, G: Html>(cx: Scope<'ctx>, props: RecipeComponentProps<'ctx>)
                 if let Some(cat) = entry.category() {
                     category.set(cat.clone());
                 }
+                match store.cache_result(local_store.get_draft(entry.recipe_id())) {
+                    Some(saved) if saved != entry.recipe_text() => {
+                        debug!("Found an unsaved draft");
+                        draft.set(Some(saved));
+                    }
+                    Some(_) => store.cache_result(local_store.delete_draft(entry.recipe_id())),
+                    None => (),
+                }
                 recipe.set(entry);
             } else {
-                error_text.set("Unable to find recipe".to_owned());
+                error_text.set(t("recipe.not_found"));
             }
         }
     });
 
     let id = create_memo(cx, || recipe.get().recipe_id().to_owned());
     let dirty = create_signal(cx, false);
+    crate::routing::track_unsaved_changes(cx, "recipe_editor", dirty);
     let ts = create_signal(cx, js_lib::get_ms_timestamp());
 
     debug!("creating editor view");
     view! {cx,
-        label(for="recipe_category") { "Category" }
-        input(name="recipe_category", bind:value=category, on:change=move |_| dirty.set(true))
+        (if let Some(saved) = draft.get().as_ref().clone() {
+            view! {cx,
+                div(class="draft-notice", role="alert") {
+                    (t("recipe.unsaved_draft")) " "
+                    button(type="button", on:click=move |_| {
+                        text.set(saved.clone());
+                        dirty.set(true);
+                        check_recipe_parses(saved.as_str(), error_text, aria_hint);
+                        draft.set(None);
+                    }) { (t("recipe.restore_draft")) } " "
+                    button(type="button", on:click=move |_| {
+                        store.cache_result(local_store.delete_draft(id.get_untracked().as_str()));
+                        draft.set(None);
+                    }) { (t("recipe.discard_draft")) }
+                }
+            }
+        } else {
+            View::empty()
+        })
+        label(for="recipe_category") { (t("recipe.category")) }
+        input(id="recipe_category", name="recipe_category", bind:value=category, on:change=move |_| dirty.set(true))
         div(class="grid") {
             div {
-                label(for="recipe_text") { "Recipe" }
-                textarea(name="recipe_text", bind:value=text, aria-invalid=aria_hint.get(), rows=20, on:change=move |_| {
+                label(for="recipe_text") { (t("recipe.recipe")) }
+                textarea(id="recipe_text", name="recipe_text", aria-describedby="recipe_parse", bind:value=text, aria-invalid=aria_hint.get(), rows=20, on:change=move |_| {
                     dirty.set(true);
                     check_recipe_parses(text.get_untracked().as_str(), error_text, aria_hint);
+                    store.cache_result(local_store.set_draft(id.get_untracked().as_str(), text.get_untracked().as_str()));
+                    draft_ts.set(js_sys::Date::now());
                 }, on:input=move |_| {
                     let current_ts = js_lib::get_ms_timestamp();
                     if (current_ts - *ts.get_untracked()) > 100 {
                         check_recipe_parses(text.get_untracked().as_str(), error_text, aria_hint);
                         ts.set(current_ts);
                     }
+                    let now = js_sys::Date::now();
+                    if (now - *draft_ts.get_untracked()) > DRAFT_INTERVAL_MS {
+                        store.cache_result(local_store.set_draft(id.get_untracked().as_str(), text.get_untracked().as_str()));
+                        draft_ts.set(now);
+                    }
                 })
             }
-            div(class="parse") { (error_text.get()) }
+            div(id="recipe_parse", class="parse", aria-live="polite") { (error_text.get()) }
         }
-        span(role="button", on:click=move |_| {
+        button(type="button", on:click=move |_| {
+            match recipes::serialize::format_recipe(text.get_untracked().as_str()) {
+                Ok(formatted) => {
+                    if formatted != *text.get_untracked() {
+                        text.set(formatted);
+                        dirty.set(true);
+                    }
+                    check_recipe_parses(text.get_untracked().as_str(), error_text, aria_hint);
+                }
+                Err(e) => {
+                    error!(?e, "Unable to format recipe");
+                    error_text.set(e);
+                    aria_hint.set("true");
+                }
+            }
+        }) { (t("recipe.format")) } " "
+        button(type="button", on:click=move |_| {
             let unparsed = text.get_untracked();
             if check_recipe_parses(unparsed.as_str(), error_text, aria_hint) {
                 debug!("triggering a save");
@@ -116,32 +184,260 @@ pub fn Editor<'ctx, G: Html>(cx: Scope<'ctx>, props: RecipeComponentProps<'ctx>)
                                 id.get_untracked().as_ref().clone(),
                                 text.get_untracked().as_ref().clone(),
                                 category,
+                                None,
                 );
                 sh.dispatch(cx, Message::SaveRecipe(recipe_entry, None));
                 dirty.set(false);
             }
             // TODO(jwall): Show error message if trying to save when recipe doesn't parse.
-        }) { "Save" } " "
-        span(role="button", on:click=move |_| {
-            sh.dispatch(cx, Message::RemoveRecipe(id.get_untracked().as_ref().to_owned(), Some(Box::new(|| sycamore_router::navigate("/ui/planning/plan")))));
-        }) { "delete" } " "
+        }) { (t("recipe.save")) } " "
+        button(type="button", on:click=move |_| {
+            spawn_local_scoped(cx, async move {
+                if confirm(cx, &t("recipe.confirm_delete"), &t("recipe.delete")).await {
+                    sh.dispatch(cx, Message::RemoveRecipe(id.get_untracked().as_ref().to_owned(), Some(Box::new(|| sycamore_router::navigate("/ui/planning/plan")))));
+                }
+            });
+        }) { (t("recipe.delete")) } " "
     }
 }
 
+/// A step image that is cached on this device so it shows up offline.
 #[component]
-fn Steps<G: Html>(cx: Scope, steps: Vec<recipes::Step>) -> View<G> {
+fn StepImage<G: Html>(cx: Scope, src: String) -> View<G> {
+    let shown = create_signal(cx, src.clone());
+    let store = crate::api::HttpStore::get_from_context(cx);
+    spawn_local_scoped(cx, async move {
+        if let Some(data_url) = store.fetch_image(&src).await {
+            shown.set(data_url);
+        }
+    });
+    view! {cx,
+        img(class="step_image", src=shown.get())
+    }
+}
+
+/// The text to read aloud for a step including the ingredient amounts.
+fn step_speech(idx: usize, step: &recipes::Step) -> String {
+    let mut text = t_args("recipe.step_speech", &[("number", &(idx + 1))]);
+    let ingredients = step
+        .ingredients
+        .iter()
+        .map(|i| match &i.form {
+            Some(form) => format!("{} {}, {}", i.amt, i.name, form),
+            None => format!("{} {}", i.amt, i.name),
+        })
+        .collect::<Vec<String>>();
+    if !ingredients.is_empty() {
+        text.push(' ');
+        text.push_str(&t_args(
+            "recipe.you_will_need",
+            &[("ingredients", &ingredients.join("; "))],
+        ));
+    }
+    text.push(' ');
+    text.push_str(&step.instructions);
+    text
+}
+
+/// A collapsed list of what can be used in place of an ingredient.
+fn substitutes_view<G: Html>(cx: Scope, substitutes: Vec<String>) -> View<G> {
+    if substitutes.is_empty() {
+        return View::empty();
+    }
+    let items = View::new_fragment(
+        substitutes
+            .into_iter()
+            .map(|substitute| view! {cx, li { (substitute) } })
+            .collect(),
+    );
+    view! {cx,
+        details(class="substitutes no-print") {
+            summary { (t("recipe.substitutions")) }
+            ul { (items) }
+        }
+    }
+}
+
+/// A checkbox for checking `key` off while cooking. There is no checkbox when
+/// we aren't tracking progress.
+fn progress_checkbox<'ctx, G: Html>(
+    cx: Scope<'ctx>,
+    progress: Option<&'ctx Signal<BTreeSet<String>>>,
+    key: String,
+    label: String,
+) -> View<G> {
+    let progress = match progress {
+        Some(progress) => progress,
+        None => return View::empty(),
+    };
+    let key = create_ref(cx, key);
+    view! {cx,
+        input(type="checkbox", class="no-print", aria-label=label,
+            checked=progress.get().contains(key),
+            on:change=move |_| {
+                let mut checked = progress.modify();
+                if !checked.remove(key) {
+                    checked.insert(key.clone());
+                }
+            })
+    }
+}
+
+/// The ingredient list for a step.
+fn ingredients_view<'ctx, G: Html>(
+    cx: Scope<'ctx>,
+    step_idx: usize,
+    ingredients: Vec<recipes::Ingredient>,
+    substitutions: &BTreeMap<String, Vec<String>>,
+    progress: Option<&'ctx Signal<BTreeSet<String>>>,
+) -> View<G> {
+    View::new_fragment(ingredients.into_iter().map(|i| {
+        // Ingredients are checked off by name so they stay checked when
+        // switching between the scaled and original amounts.
+        let checkbox = progress_checkbox(
+            cx,
+            progress,
+            format!("ingredient:{}:{}", step_idx, i.name),
+            i.name.clone(),
+        );
+        if let Some(id) = i.recipe_reference() {
+            let href = format!("/ui/recipe/view/{}", id);
+            let id = id.to_owned();
+            return view! {cx,
+                li {
+                    (checkbox) (i.amt) " " a(href=href) { (id) }
+                }
+            };
+        }
+        let substitutes = substitutes_view(
+            cx,
+            substitutions.get(&i.name.to_lowercase()).cloned().unwrap_or_default(),
+        );
+        view! {cx,
+            li {
+                (checkbox) (i.amt) " " (i.name) " " (i.form.as_ref().map(|f| format!("({})", f)).unwrap_or(String::new()))
+                (substitutes)
+            }
+        }
+    }).collect())
+}
+
+#[derive(Props)]
+struct StepsProps {
+    steps: Vec<recipes::Step>,
+    /// The ingredients of each step before the recipe was scaled. Empty when
+    /// the recipe isn't scaled.
+    originals: Vec<Vec<recipes::Ingredient>>,
+    /// Substitutes to offer next to the ingredients they can replace.
+    substitutions: BTreeMap<String, Vec<String>>,
+    /// Track the ingredients and steps checked off while cooking today under
+    /// this recipe id.
+    progress_id: Option<String>,
+}
+
+#[component]
+fn Steps<G: Html>(cx: Scope, props: StepsProps) -> View<G> {
+    let StepsProps {
+        steps,
+        originals,
+        substitutions,
+        progress_id,
+    } = props;
+    let progress = progress_id.map(|id| {
+        let local_store = crate::api::LocalStore::new();
+        let today = chrono::Local::now().date_naive();
+        let checked = local_store
+            .get_cook_progress(&id, &today)
+            .unwrap_or_else(|err| {
+                error!(?err, "Failed to load cooking progress");
+                BTreeSet::new()
+            });
+        let progress = create_signal(cx, checked);
+        create_effect(cx, move || {
+            if let Err(err) = local_store.set_cook_progress(&id, &today, &progress.get()) {
+                error!(?err, "Failed to save cooking progress");
+            }
+        });
+        progress
+    });
+    let can_speak = js_lib::speech_supported();
+    on_cleanup(cx, js_lib::stop_speaking);
     let step_fragments = View::new_fragment(steps.iter().enumerate().map(|(idx, step)| {
         let mut step = step.clone();
-        let ingredient_fragments = View::new_fragment(step.ingredients.drain(0..).map(|i| {
+        let read_aloud = if can_speak {
+            let speech = step_speech(idx, &step);
+            view! {cx,
+                button(type="button", class="outline no-print", on:click=move |_| {
+                    js_lib::speak(&speech);
+                }) { (t("recipe.read_aloud")) }
+            }
+        } else {
+            View::empty()
+        };
+        let scaled = ingredients_view(
+            cx,
+            idx,
+            step.ingredients.drain(0..).collect(),
+            &substitutions,
+            progress,
+        );
+        // Steps without ingredients look the same either way so they don't
+        // get a toggle.
+        let (scale_toggle, ingredient_fragments) = match originals.get(idx) {
+            Some(original) if !original.is_empty() => {
+                let original =
+                    ingredients_view(cx, idx, original.clone(), &substitutions, progress);
+                let show_original = create_signal(cx, false);
+                let toggle = view! {cx,
+                    button(type="button", class="outline no-print scale-toggle",
+                        aria-pressed=show_original.get().to_string(),
+                        on:click=move |_| show_original.set(!*show_original.get_untracked())) {
+                        (if *show_original.get() {
+                            t("recipe.show_scaled")
+                        } else {
+                            t("recipe.show_original")
+                        })
+                    }
+                };
+                let fragments = view! {cx,
+                    (if *show_original.get() { original.clone() } else { scaled.clone() })
+                };
+                (toggle, fragments)
+            }
+            _ => (View::empty(), scaled),
+        };
+        let image_fragments = View::new_fragment(step.images.drain(0..).map(|src| {
+            view! {cx,
+                StepImage(src)
+            }
+        }).collect());
+        let temp_fragments = View::new_fragment(step.temperatures.drain(0..).map(|temp| {
+            view! {cx,
+                li { (t_args("recipe.temperature", &[("temperature", &temp)])) }
+            }
+        }).collect());
+        let timer_fragments = View::new_fragment(step.timers.drain(0..).map(|timer| {
+            let label = timer.label.map(|l| format!(" ({})", l)).unwrap_or_default();
+            let minutes = timer.duration.as_secs() / 60;
             view! {cx,
                 li {
-                    (i.amt) " " (i.name) " " (i.form.as_ref().map(|f| format!("({})", f)).unwrap_or(String::new()))
+                    (t_args("recipe.timer", &[("minutes", &minutes)])) (label)
                 }
             }
         }).collect());
+        let step_label = t_args("recipe.step", &[("number", &(idx + 1))]);
+        let step_checkbox =
+            progress_checkbox(cx, progress, format!("step:{}", idx), step_label.clone());
         view! {cx,
             div {
-                h3 { "Step " (idx + 1) }
+                h3 { (step_checkbox) (step_label) }
+                (read_aloud)
+                (scale_toggle)
+                (image_fragments)
+                ul(class="timers") {
+                    (temp_fragments)
+                    (timer_fragments)
+                }
                 ul(class="ingredients") {
                     (ingredient_fragments)
                 }
@@ -152,7 +448,7 @@ fn Steps<G: Html>(cx: Scope, steps: Vec<recipes::Step>) -> View<G> {
         }
     }).collect());
     view! {cx,
-            h2 { "Instructions: " }
+            h2 { (t("recipe.instructions")) }
             div(class="recipe_steps") {
                 (step_fragments)
             }
@@ -160,30 +456,295 @@ fn Steps<G: Html>(cx: Scope, steps: Vec<recipes::Step>) -> View<G> {
 }
 
 #[component]
-pub fn Viewer<'ctx, G: Html>(cx: Scope<'ctx>, props: RecipeComponentProps<'ctx>) -> View<G> {
-    let RecipeComponentProps { recipe_id, sh } = props;
+fn Metadata<G: Html>(cx: Scope, metadata: recipes::RecipeMetadata) -> View<G> {
+    if metadata.is_empty() {
+        return View::empty();
+    }
+    let serves = metadata
+        .serves
+        .map(|n| view! {cx, li { (t_args("recipe.serves", &[("count", &n)])) } })
+        .unwrap_or_else(View::empty);
+    let meal = metadata
+        .meal
+        .map(|meal| view! {cx, li { (t_args("recipe.meal", &[("meal", &meal_label(meal))])) } })
+        .unwrap_or_else(View::empty);
+    let tags = if metadata.tags.is_empty() {
+        View::empty()
+    } else {
+        let tags = metadata
+            .tags
+            .iter()
+            .cloned()
+            .collect::<Vec<String>>()
+            .join(", ");
+        view! {cx, li { (t_args("recipe.tags", &[("tags", &tags)])) } }
+    };
+    let source = match (metadata.source_url().map(|s| s.to_owned()), metadata.source) {
+        (Some(href), Some(src)) => {
+            view! {cx, li { (t("recipe.source")) " " a(href=href) { (src) } } }
+        }
+        (None, Some(src)) => view! {cx, li { (t("recipe.source")) " " (src) } },
+        _ => View::empty(),
+    };
+    view! {cx,
+        ul(class="recipe_metadata") {
+            (serves)
+            (meal)
+            (tags)
+            (source)
+        }
+    }
+}
+
+#[component]
+fn Times<G: Html>(cx: Scope, times: (Duration, Duration, Duration)) -> View<G> {
+    let (prep, cook, total) = times;
+    if total.is_zero() {
+        return View::empty();
+    }
+    view! {cx,
+        ul(class="recipe_times") {
+            li { (t_args("recipe.prep_time", &[("minutes", &(prep.as_secs() / 60))])) }
+            li { (t_args("recipe.cook_time", &[("minutes", &(cook.as_secs() / 60))])) }
+            li { (t_args("recipe.total_time", &[("minutes", &(total.as_secs() / 60))])) }
+        }
+    }
+}
+
+/// Show the ingredients measured by volume by weight instead when we know
+/// their density.
+fn weigh_ingredients(step: &mut recipes::Step, densities: &DensityTable) {
+    for i in step.ingredients.iter_mut() {
+        if let Some(amt) = densities.to_weight(&i.name, &i.amt) {
+            i.amt = amt;
+        }
+    }
+}
+
+/// Show a step's temperatures in Celsius or Fahrenheit. None leaves them in
+/// the unit the recipe was written in.
+fn convert_step_temperatures(step: &mut recipes::Step, celsius: Option<bool>) {
+    let celsius = match celsius {
+        Some(celsius) => celsius,
+        None => return,
+    };
+    step.instructions = recipes::parse::convert_temperatures(&step.instructions, celsius);
+    for temp in step.temperatures.iter_mut() {
+        *temp = if celsius {
+            temp.into_celsius()
+        } else {
+            temp.into_fahrenheit()
+        };
+    }
+}
+
+fn temperature_unit(celsius: Option<bool>) -> &'static str {
+    match celsius {
+        None => "written",
+        Some(true) => "celsius",
+        Some(false) => "fahrenheit",
+    }
+}
+
+/// Lets the user pick the unit temperatures are shown in.
+#[component]
+pub fn TemperatureSelect<'ctx, G: Html>(cx: Scope<'ctx>, sh: StateHandler<'ctx>) -> View<G> {
+    let current = sh.get_selector(cx, |state| state.get().celsius);
+    let unit = create_signal(cx, temperature_unit(*current.get_untracked()).to_owned());
+    create_effect(cx, || unit.set(temperature_unit(*current.get()).to_owned()));
+    view! {cx,
+        label {
+            (t("recipe.temperature_unit"))
+            " "
+            select(bind:value=unit, on:change=move |_| {
+                let celsius = match unit.get_untracked().as_str() {
+                    "celsius" => Some(true),
+                    "fahrenheit" => Some(false),
+                    _ => None,
+                };
+                sh.dispatch(cx, Message::UpdateUseCelsius(celsius));
+            }) {
+                option(value="written") { (t("recipe.temperature_as_written")) }
+                option(value="celsius") { (t("recipe.celsius")) }
+                option(value="fahrenheit") { (t("recipe.fahrenheit")) }
+            }
+        }
+    }
+}
+
+#[derive(Props)]
+pub struct PrintableProps<'ctx> {
+    recipe_id: String,
+    scale: &'ctx ReadSignal<recipes::unit::Quantity>,
+    sh: StateHandler<'ctx>,
+}
+
+/// A recipe rendered for printing with its quantities scaled and no
+/// interactive controls.
+#[component]
+pub fn Printable<'ctx, G: Html>(cx: Scope<'ctx>, props: PrintableProps<'ctx>) -> View<G> {
+    let PrintableProps {
+        recipe_id,
+        scale,
+        sh,
+    } = props;
+    let recipe_signal = sh.get_selector(cx, move |state| {
+        let celsius = state.get().celsius;
+        let densities = state.get().use_weights.then(|| state.get().density_table());
+        let scale = *scale.get();
+        state.get().recipes.get(&recipe_id).map(|recipe| {
+            let mut recipe = recipe.scale(scale);
+            for step in recipe.steps.iter_mut() {
+                if let Some(densities) = &densities {
+                    weigh_ingredients(step, densities);
+                }
+                convert_step_temperatures(step, celsius);
+            }
+            recipe
+        })
+    });
+    view! {cx,
+        (if let Some(recipe) = recipe_signal.get().as_ref().clone() {
+            let desc = recipe.desc.clone().unwrap_or_else(|| String::new());
+            let times = (recipe.prep_time(), recipe.cook_time(), recipe.total_time());
+            let scale = *scale.get();
+            let scale_note = if scale == 1.into() {
+                View::empty()
+            } else {
+                view! {cx, p(class="recipe_scale") { (t_args("recipe.scaled_by", &[("scale", &scale)])) } }
+            };
+            view! {cx,
+                div(class="recipe recipe-print") {
+                    h1(class="recipe_title") { (recipe.title) }
+                    (scale_note)
+                    div(class="recipe_description") {
+                        (desc)
+                    }
+                    Metadata(recipe.metadata)
+                    Times(times)
+                    Steps(steps=recipe.steps, originals=Vec::new(), substitutions=BTreeMap::new(), progress_id=None)
+                }
+            }
+        } else {
+            view! {cx, p { (t("recipe.not_found")) } }
+        })
+    }
+}
+
+#[derive(Props)]
+pub struct ViewerProps<'ctx> {
+    recipe_id: String,
+    sh: StateHandler<'ctx>,
+    /// Scale the recipe for the household like it is in the plan.
+    planned: bool,
+}
+
+#[component]
+pub fn Viewer<'ctx, G: Html>(cx: Scope<'ctx>, props: ViewerProps<'ctx>) -> View<G> {
+    let ViewerProps {
+        recipe_id,
+        sh,
+        planned,
+    } = props;
     let view = create_signal(cx, View::empty());
+    let progress_id = recipe_id.clone();
+    let use_weights = sh.get_selector(cx, |state| state.get().use_weights);
     let recipe_signal = sh.get_selector(cx, move |state| {
+        let celsius = state.get().celsius;
+        let densities = state.get().use_weights.then(|| state.get().density_table());
+        let scale = if planned {
+            state.get().plan_scale(&recipe_id)
+        } else {
+            Quantity::whole(1)
+        };
+        let household_size = state.get().household_size;
         if let Some(recipe) = state.get().recipes.get(&recipe_id) {
             let title = recipe.title.clone();
             let desc = recipe.desc.clone().unwrap_or_else(|| String::new());
-            let steps = recipe.steps.clone();
-            Some((title, desc, steps))
+            let metadata = recipe.metadata.clone();
+            let times = (recipe.prep_time(), recipe.cook_time(), recipe.total_time());
+            let scale_note = match household_size {
+                Some(people) if scale != Quantity::whole(1) => t_args(
+                    "recipe.scaled_to_serve",
+                    &[("scale", &scale), ("people", &people)],
+                ),
+                _ => String::new(),
+            };
+            let originals = if scale == Quantity::whole(1) {
+                Vec::new()
+            } else {
+                recipe
+                    .steps
+                    .iter()
+                    .map(|step| {
+                        let mut step = step.clone();
+                        if let Some(densities) = &densities {
+                            weigh_ingredients(&mut step, densities);
+                        }
+                        step.ingredients
+                    })
+                    .collect()
+            };
+            let steps = recipe
+                .scale(scale)
+                .steps
+                .into_iter()
+                .map(|mut step| {
+                    if let Some(densities) = &densities {
+                        weigh_ingredients(&mut step, densities);
+                    }
+                    convert_step_temperatures(&mut step, celsius);
+                    step
+                })
+                .collect::<Vec<recipes::Step>>();
+            let substitutions = state.get().substitutions.clone();
+            Some((
+                title,
+                desc,
+                metadata,
+                times,
+                scale_note,
+                steps,
+                originals,
+                substitutions,
+            ))
         } else {
             None
         }
     });
-    if let Some((title, desc, steps)) = recipe_signal.get().as_ref().clone() {
-        debug!("Viewing recipe.");
-        view.set(view! {cx,
-            div(class="recipe") {
-                h1(class="recipe_title") { (title) }
-                 div(class="recipe_description") {
-                     (desc)
-                 }
-                Steps(steps)
-            }
-        });
+    create_effect_scoped(cx, move |cx| {
+        if let Some((title, desc, metadata, times, scale_note, steps, originals, substitutions)) =
+            recipe_signal.get().as_ref().clone()
+        {
+            debug!("Viewing recipe.");
+            view.set(view! {cx,
+                div(class="recipe") {
+                    h1(class="recipe_title") { (title) }
+                     div(class="recipe_description") {
+                         (desc)
+                     }
+                    Metadata(metadata)
+                    (if scale_note.is_empty() {
+                        View::empty()
+                    } else {
+                        let scale_note = scale_note.clone();
+                        view! {cx, p(class="recipe_scale") { (scale_note) } }
+                    })
+                    Times(times)
+                    Steps(steps=steps, originals=originals, substitutions=substitutions, progress_id=Some(progress_id.clone()))
+                }
+            });
+        }
+    });
+    view! {cx,
+        div(class="no-print") {
+            TemperatureSelect(sh)
+            label(for="use_weights_cb") { (t("recipe.use_weights")) }
+            input(id="use_weights_cb", type="checkbox", checked=*use_weights.get(), on:change=move |_| {
+                let value = !*use_weights.get_untracked();
+                sh.dispatch(cx, Message::UpdateUseWeights(value));
+            })
+        }
+        (view.get().as_ref())
     }
-    view! {cx, (view.get().as_ref()) }
 }
@@ -11,33 +11,63 @@
 // WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
 // See the License for the specific language governing permissions and
 // limitations under the License.
-use crate::{app_state::StateHandler, components::recipe::Viewer};
+use crate::{
+    app_state::StateHandler,
+    components::{cooked::Cooked, prep_list::PrepChecklist, recipe::Viewer},
+    i18n::{meal_label, t},
+};
 
+use recipes::MealType;
 use sycamore::prelude::*;
 use tracing::{debug, instrument};
 
 #[instrument(skip_all)]
 #[component]
 pub fn RecipeList<'ctx, G: Html>(cx: Scope<'ctx>, sh: StateHandler<'ctx>) -> View<G> {
+    // Recipes are grouped by meal and keep the plan order within each meal.
     let menu_list = sh.get_selector(cx, |state| {
-        state
-            .get()
-            .recipe_counts
-            .iter()
-            .map(|(k, v)| (k.clone(), v.clone()))
-            .filter(|(_, v)| *(v) != 0)
-            .collect()
+        let state = state.get();
+        let mut planned = state
+            .planned_recipes()
+            .into_iter()
+            .flat_map(|(id, _)| {
+                state
+                    .meals_for(&id)
+                    .into_iter()
+                    .map(move |meal| (meal, id.clone()))
+            })
+            .collect::<Vec<(MealType, String)>>();
+        planned.sort_by_key(|(meal, _)| *meal);
+        let mut meals: Vec<(MealType, Vec<String>)> = Vec::new();
+        for (meal, id) in planned {
+            match meals.last_mut() {
+                Some((last, ids)) if *last == meal => ids.push(id),
+                _ => meals.push((meal, vec![id])),
+            }
+        }
+        meals
     });
     view! {cx,
-        h1 { "Recipe List" }
+        h1 { (t("recipe_list.heading")) }
         div() {
             Indexed(
                 iterable=menu_list,
-                view= move |cx, (id, _count)| {
-                    debug!(id=%id, "Rendering recipe");
+                view= move |cx, (meal, ids)| {
+                    let ids = create_signal(cx, ids);
                     view ! {cx,
-                        Viewer(recipe_id=id, sh=sh)
-                        hr()
+                        h2(class="meal") { (meal_label(meal)) }
+                        Indexed(
+                            iterable=ids,
+                            view= move |cx, id| {
+                                debug!(id=%id, "Rendering recipe");
+                                view ! {cx,
+                                    PrepChecklist(recipe_id=id.clone(), sh=sh)
+                                    Viewer(recipe_id=id.clone(), sh=sh, planned=true)
+                                    Cooked(recipe_id=id, sh=sh)
+                                    hr()
+                                }
+                            }
+                        )
                     }
                 }
             )
@@ -0,0 +1,250 @@
+// Copyright 2023 Jeremy Wall (Jeremy@marzhilsltudios.com)
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+use std::collections::BTreeSet;
+
+use client_api::BulkResult;
+use sycamore::{futures::spawn_local_scoped, prelude::*};
+use tracing::{error, instrument};
+
+use crate::{
+    api::{Error, HttpStore},
+    app_state::{Message, StateHandler},
+    components::{confirm::confirm, toast},
+    i18n::{t, t_args},
+};
+
+#[derive(Debug, Clone, PartialEq)]
+struct Row {
+    id: String,
+    title: String,
+    category: String,
+    tags: String,
+    archived: bool,
+}
+
+#[derive(Debug, Clone)]
+enum BulkAction {
+    AddTag(String),
+    RemoveTag(String),
+    SetCategory(Option<String>),
+    Archive(bool),
+    Delete,
+}
+
+impl BulkAction {
+    async fn perform(self, store: &HttpStore, ids: Vec<String>) -> Result<BulkResult, Error> {
+        match self {
+            BulkAction::AddTag(tag) => {
+                store
+                    .bulk_tags(ids, BTreeSet::from([tag]), BTreeSet::new())
+                    .await
+            }
+            BulkAction::RemoveTag(tag) => {
+                store
+                    .bulk_tags(ids, BTreeSet::new(), BTreeSet::from([tag]))
+                    .await
+            }
+            BulkAction::SetCategory(category) => store.bulk_category(ids, category).await,
+            BulkAction::Archive(archived) => store.bulk_archive(ids, archived).await,
+            BulkAction::Delete => store.bulk_delete(ids).await,
+        }
+    }
+}
+
+/// Lists every recipe with a checkbox so tags, categories, archiving and
+/// deletion can be applied to many recipes at once.
+#[instrument(skip_all)]
+#[component]
+pub fn RecipeManager<'ctx, G: Html>(cx: Scope<'ctx>, sh: StateHandler<'ctx>) -> View<G> {
+    let store = create_ref(cx, HttpStore::get_from_context(cx));
+    let filter = create_signal(cx, String::new());
+    let selected = create_signal(cx, BTreeSet::<String>::new());
+    let tag = create_signal(cx, String::new());
+    let category = create_signal(cx, String::new());
+    let rows = sh.get_selector(cx, move |state| {
+        let state = state.get();
+        let filter = filter.get().trim().to_lowercase();
+        let mut rows = state
+            .recipes
+            .iter()
+            .map(|(id, recipe)| Row {
+                id: id.clone(),
+                title: recipe.title.clone(),
+                category: state.recipe_categories.get(id).cloned().unwrap_or_default(),
+                tags: recipe
+                    .metadata
+                    .tags
+                    .iter()
+                    .cloned()
+                    .collect::<Vec<String>>()
+                    .join(", "),
+                archived: state.archived.contains(id),
+            })
+            .filter(|row| {
+                filter.is_empty()
+                    || row.title.to_lowercase().contains(&filter)
+                    || row.tags.contains(&filter)
+                    || row.category.to_lowercase().contains(&filter)
+            })
+            .collect::<Vec<Row>>();
+        rows.sort_by(|a, b| a.title.cmp(&b.title));
+        rows
+    });
+    // Recipes that went away or were filtered out are dropped from the
+    // selection so the actions only apply to what is on screen.
+    create_effect(cx, move || {
+        let visible = rows
+            .get()
+            .iter()
+            .map(|row| row.id.clone())
+            .collect::<BTreeSet<String>>();
+        if selected
+            .get_untracked()
+            .iter()
+            .any(|id| !visible.contains(id))
+        {
+            selected.modify().retain(|id| visible.contains(id));
+        }
+    });
+    let all_selected = create_selector(cx, move || {
+        !rows.get().is_empty() && selected.get().len() == rows.get().len()
+    });
+    let run = move |action: BulkAction| {
+        let ids = selected
+            .get_untracked()
+            .iter()
+            .cloned()
+            .collect::<Vec<String>>();
+        if ids.is_empty() {
+            return;
+        }
+        spawn_local_scoped(cx, async move {
+            if let BulkAction::Delete = action {
+                let message = t_args("recipe_manager.confirm_delete", &[("count", &ids.len())]);
+                if !confirm(cx, &message, &t("recipe_manager.delete")).await {
+                    return;
+                }
+            }
+            match action.perform(store, ids).await {
+                Ok(result) => {
+                    toast::message(
+                        cx,
+                        &t_args(
+                            "recipe_manager.updated",
+                            &[("count", &result.updated.len())],
+                        ),
+                        None,
+                    );
+                    if !result.not_found.is_empty() {
+                        toast::error_message(
+                            cx,
+                            &t_args(
+                                "recipe_manager.not_found",
+                                &[("count", &result.not_found.len())],
+                            ),
+                            None,
+                        );
+                    }
+                    selected.set(BTreeSet::new());
+                    sh.dispatch(cx, Message::RefreshRecipes);
+                }
+                Err(err) => {
+                    error!(?err, "Failed to update recipes");
+                    toast::error_message(cx, &t("recipe_manager.failed"), None);
+                }
+            }
+        });
+    };
+    view! {cx,
+        div(class="no-print") {
+            label(for="recipe_manager_filter") { (t("recipe_manager.filter")) }
+            input(id="recipe_manager_filter", type="search", bind:value=filter)
+        }
+        p { (t_args("recipe_manager.selected", &[("count", &selected.get().len())])) }
+        fieldset(class="bulk-actions") {
+            label(for="bulk_tag") { (t("recipe_manager.tag")) }
+            input(id="bulk_tag", type="text", bind:value=tag)
+            button(type="button", on:click=move |_| {
+                let value = tag.get_untracked().trim().to_owned();
+                if !value.is_empty() {
+                    run(BulkAction::AddTag(value));
+                }
+            }) { (t("recipe_manager.add_tag")) } " "
+            button(type="button", class="secondary", on:click=move |_| {
+                let value = tag.get_untracked().trim().to_owned();
+                if !value.is_empty() {
+                    run(BulkAction::RemoveTag(value));
+                }
+            }) { (t("recipe_manager.remove_tag")) }
+            label(for="bulk_category") { (t("recipe_manager.category")) }
+            input(id="bulk_category", type="text", bind:value=category)
+            button(type="button", on:click=move |_| {
+                let value = category.get_untracked().trim().to_owned();
+                run(BulkAction::SetCategory(if value.is_empty() { None } else { Some(value) }));
+            }) { (t("recipe_manager.set_category")) }
+            div {
+                button(type="button", on:click=move |_| run(BulkAction::Archive(true))) {
+                    (t("recipe_manager.archive"))
+                } " "
+                button(type="button", class="secondary", on:click=move |_| run(BulkAction::Archive(false))) {
+                    (t("recipe_manager.unarchive"))
+                } " "
+                button(type="button", class="secondary outline", on:click=move |_| run(BulkAction::Delete)) {
+                    (t("recipe_manager.delete"))
+                }
+            }
+        }
+        table(class="recipe-manager") {
+            tr {
+                th {
+                    input(type="checkbox", aria-label=t("recipe_manager.select_all"), checked=*all_selected.get(), on:change=move |_| {
+                        if *all_selected.get_untracked() {
+                            selected.set(BTreeSet::new());
+                        } else {
+                            selected.set(rows.get_untracked().iter().map(|row| row.id.clone()).collect());
+                        }
+                    })
+                }
+                th { (t("recipe_manager.title")) }
+                th { (t("recipe_manager.category")) }
+                th { (t("recipe_manager.tags")) }
+                th { (t("recipe_manager.archived")) }
+            }
+            Indexed(
+                iterable=rows,
+                view=move |cx, row| {
+                    let id = create_ref(cx, row.id.clone());
+                    let checked = create_selector(cx, move || selected.get().contains(id));
+                    let href = format!("/ui/recipe/view/{}", id);
+                    view! {cx,
+                        tr {
+                            td {
+                                input(type="checkbox", aria-label=row.title.clone(), checked=*checked.get(), on:change=move |_| {
+                                    let mut ids = selected.modify();
+                                    if !ids.remove(id) {
+                                        ids.insert(id.clone());
+                                    }
+                                })
+                            }
+                            td { a(href=href) { (row.title) } }
+                            td { (row.category) }
+                            td { (row.tags) }
+                            td { (if row.archived { t("recipe_manager.yes") } else { String::new() }) }
+                        }
+                    }
+                },
+            )
+        }
+    }
+}
@@ -0,0 +1,103 @@
+// Copyright 2023 Jeremy Wall (Jeremy@marzhilsltudios.com)
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+use std::cmp::Reverse;
+
+use sycamore::{futures::spawn_local_scoped, prelude::*};
+use tracing::warn;
+
+use crate::{
+    app_state::{Message, StateHandler},
+    i18n::t,
+};
+
+/// A recipe picker for small screens. Recipes are searched by title and
+/// their counts changed with buttons instead of number inputs. The most
+/// recently cooked recipes come first.
+#[component]
+pub fn RecipePicker<'ctx, G: Html>(cx: Scope<'ctx>, sh: StateHandler<'ctx>) -> View<G> {
+    let store = crate::api::HttpStore::get_from_context(cx);
+    let index = create_signal(cx, Vec::<(String, String)>::new());
+    let local_index = sh.get_selector(cx, |state| {
+        let state = state.get();
+        state
+            .recipes
+            .iter()
+            .filter(|(id, _)| !state.archived.contains(*id))
+            .map(|(id, recipe)| (id.clone(), recipe.title.clone()))
+            .collect::<Vec<(String, String)>>()
+    });
+    spawn_local_scoped(cx, async move {
+        match store.fetch_recipe_index().await {
+            Ok(entries) => index.set(
+                entries
+                    .into_iter()
+                    .map(|e| (e.recipe_id, e.title))
+                    .collect(),
+            ),
+            Err(err) => {
+                warn!(?err, "Using the local recipes for the picker");
+                index.set(local_index.get_untracked().as_ref().clone());
+            }
+        }
+    });
+    let query = create_signal(cx, String::new());
+    let matches = sh.get_selector(cx, move |state| {
+        let state = state.get();
+        let query = query.get().trim().to_lowercase();
+        let mut matches = index
+            .get()
+            .iter()
+            .filter(|(_, title)| title.to_lowercase().contains(&query))
+            .map(|(id, title)| {
+                let count = state.recipe_counts.get(id).cloned().unwrap_or(0);
+                (id.clone(), title.clone(), count)
+            })
+            .collect::<Vec<(String, String, usize)>>();
+        matches.sort_by_key(|(id, title, _)| {
+            (Reverse(state.last_cooked.get(id).cloned()), title.clone())
+        });
+        matches
+    });
+    view! {cx,
+        div(class="recipe-picker no-print") {
+            input(type="search", aria-label=t("recipe_picker.search"),
+                placeholder=t("recipe_picker.search"), bind:value=query)
+            ul {
+                Keyed(
+                    iterable=matches,
+                    view=move |cx, (id, title, count)| {
+                        let href = format!("/ui/recipe/view/{}", id);
+                        let less_id = id.clone();
+                        view! {cx,
+                            li {
+                                a(href=href) { (title) }
+                                div(role="group") {
+                                    button(type="button", class="outline", aria-label=t("recipe_picker.less"),
+                                        disabled=count == 0, on:click=move |_| {
+                                        sh.dispatch(cx, Message::UpdateRecipeCount(less_id.clone(), count.saturating_sub(1)));
+                                    }) { "−" }
+                                    output { (count) }
+                                    button(type="button", class="outline", aria-label=t("recipe_picker.more"), on:click=move |_| {
+                                        sh.dispatch(cx, Message::UpdateRecipeCount(id.clone(), count + 1));
+                                    }) { "+" }
+                                }
+                            }
+                        }
+                    },
+                    key=|(id, _, count)| (id.clone(), *count),
+                )
+            }
+        }
+    }
+}
@@ -1,4 +1,4 @@
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 
 // Copyright 2022 Jeremy Wall
 //
@@ -13,12 +13,15 @@ use std::collections::BTreeMap;
 // WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
 // See the License for the specific language governing permissions and
 // limitations under the License.
-use recipes::Recipe;
+use recipes::{unit::Quantity, MealType, Recipe};
 use sycamore::prelude::*;
 use tracing::{debug, instrument};
+use wasm_bindgen::JsCast;
+use web_sys::DragEvent;
 
 use crate::app_state::{Message, StateHandler};
-use crate::components::recipe_selection::*;
+use crate::components::{recipe_picker::RecipePicker, recipe_selection::*};
+use crate::i18n::{meal_label, t, t_args};
 
 #[derive(Props)]
 pub struct CategoryGroupProps<'ctx> {
@@ -73,50 +76,252 @@ pub fn CategoryGroup<'ctx, G: Html>(
     }
 }
 
+/// Move the dragged recipe id to the position of the target id. Dragging down
+/// the list lands after the target and dragging up lands before it.
+fn move_to(order: &Vec<String>, dragged: &String, target: &String) -> Vec<String> {
+    let mut order = order.clone();
+    match (
+        order.iter().position(|id| id == dragged),
+        order.iter().position(|id| id == target),
+    ) {
+        (Some(from), Some(to)) => {
+            let id = order.remove(from);
+            order.insert(to, id);
+        }
+        _ => (),
+    }
+    order
+}
+
+/// The planned recipes in the order we intend to cook them. Recipes can be
+/// dragged to reorder them or moved with the up and down buttons.
+#[allow(non_snake_case)]
+#[instrument(skip_all)]
+pub fn PlanOrder<'ctx, G: Html>(cx: Scope<'ctx>, sh: StateHandler<'ctx>) -> View<G> {
+    let planned = sh.get_selector(cx, |state| {
+        let state = state.get();
+        state
+            .planned_recipes()
+            .into_iter()
+            .map(|(id, count)| {
+                let title = state
+                    .recipes
+                    .get(&id)
+                    .map(|r| r.title.clone())
+                    .unwrap_or_else(|| id.clone());
+                let scale = state.plan_scale(&id);
+                let scale_note = if scale == Quantity::whole(1) {
+                    String::new()
+                } else {
+                    t_args("recipe_plan.scaled_by", &[("scale", &scale)])
+                };
+                let meals = state.meals_for(&id);
+                let frozen = state.plan_freezer_meals.contains(&id);
+                (id, title, count, scale_note, meals, frozen)
+            })
+            .collect::<Vec<(String, String, usize, String, BTreeSet<MealType>, bool)>>()
+    });
+    let order = create_memo(cx, move || {
+        planned
+            .get()
+            .iter()
+            .map(|(id, _, _, _, _, _)| id.clone())
+            .collect::<Vec<String>>()
+    });
+    let dragged = create_signal(cx, Option::<String>::None);
+    let reorder = move |target: String| {
+        if let Some(dragged_id) = dragged.get_untracked().as_ref() {
+            if dragged_id != &target {
+                let new_order = move_to(&order.get_untracked(), dragged_id, &target);
+                sh.dispatch(cx, Message::ReorderPlan(new_order));
+            }
+        }
+        dragged.set(None);
+    };
+    let shift = move |id: String, up: bool| {
+        let mut new_order = order.get_untracked().as_ref().clone();
+        if let Some(idx) = new_order.iter().position(|i| i == &id) {
+            let other = if up {
+                idx.checked_sub(1)
+            } else {
+                Some(idx + 1)
+            };
+            if let Some(other) = other.filter(|o| *o < new_order.len()) {
+                new_order.swap(idx, other);
+                sh.dispatch(cx, Message::ReorderPlan(new_order));
+            }
+        }
+    };
+    view! {cx,
+        (if planned.get().is_empty() {
+            View::empty()
+        } else {
+            view! {cx,
+                h2 { (t("recipe_plan.cooking_order")) }
+                ol(class="plan-order no-print") {
+                    Keyed(
+                        iterable=planned,
+                        view=move |cx, (id, title, count, scale_note, meals, frozen)| {
+                            let drag_id = id.clone();
+                            let drop_id = id.clone();
+                            let up_id = id.clone();
+                            let down_id = id.clone();
+                            let freezer_id = id.clone();
+                            let meal_checkboxes = View::new_fragment(MealType::ALL.iter().map(|meal| {
+                                let meal = *meal;
+                                let meal_id = id.clone();
+                                let checked = meals.contains(&meal);
+                                let label = meal_label(meal);
+                                view! {cx,
+                                    label {
+                                        input(type="checkbox", checked=checked, on:change=move |_| {
+                                            sh.dispatch(cx, Message::TogglePlanMeal(meal_id.clone(), meal));
+                                        })
+                                        (label)
+                                    } " "
+                                }
+                            }).collect());
+                            view! {cx,
+                                li(draggable="true", on:dragstart=move |evt: web_sys::Event| {
+                                    let evt: DragEvent = evt.unchecked_into();
+                                    // Some browsers won't start a drag without data.
+                                    if let Some(transfer) = evt.data_transfer() {
+                                        let _ = transfer.set_data("text/plain", &drag_id);
+                                    }
+                                    dragged.set(Some(drag_id.clone()));
+                                }, on:dragover=move |evt: web_sys::Event| {
+                                    // Allow dropping on this item.
+                                    evt.prevent_default();
+                                }, on:drop=move |evt: web_sys::Event| {
+                                    evt.prevent_default();
+                                    reorder(drop_id.clone());
+                                }) {
+                                    span(class="drag-handle", aria-hidden="true") { "☰" } " "
+                                    (title) " (" (count) ") "
+                                    small { (scale_note) } " "
+                                    span(class="plan-meals", role="group", aria-label=t("recipe_plan.meal")) {
+                                        (meal_checkboxes)
+                                    } " "
+                                    label(class="freezer-meal") {
+                                        input(type="checkbox", checked=frozen, on:change=move |_| {
+                                            sh.dispatch(cx, Message::SetPlanFreezerMeal(freezer_id.clone(), !frozen));
+                                        })
+                                        (t("recipe_plan.from_freezer"))
+                                    } " "
+                                    button(type="button", aria-label=t("recipe_plan.move_up"), on:click=move |_| shift(up_id.clone(), true)) { "↑" } " "
+                                    button(type="button", aria-label=t("recipe_plan.move_down"), on:click=move |_| shift(down_id.clone(), false)) { "↓" }
+                                }
+                            }
+                        },
+                        key=|(id, _, count, scale_note, meals, frozen)| (id.clone(), *count, scale_note.clone(), meals.clone(), *frozen),
+                    )
+                }
+            }
+        })
+    }
+}
+
 #[allow(non_snake_case)]
 #[instrument(skip_all)]
 pub fn RecipePlan<'ctx, G: Html>(cx: Scope<'ctx>, sh: StateHandler<'ctx>) -> View<G> {
-    let recipe_category_groups = sh.get_selector(cx, |state| {
+    // A max_time of 0 means there is no limit on the recipes total time.
+    let max_time = create_signal(cx, 0.0);
+    let sort_by_time = create_signal(cx, false);
+    let show_archived = create_signal(cx, false);
+    // Like the recipe counts we keep a separate signal for the input so
+    // that state updates don't cycle back into it.
+    let household_size = sh.get_selector(cx, |state| state.get().household_size.unwrap_or(0));
+    let people = create_signal(cx, *household_size.get_untracked() as f64);
+    create_effect(cx, || {
+        let updated = *household_size.get() as f64;
+        if updated != *people.get_untracked() {
+            people.set(updated);
+        }
+    });
+    let recipe_category_groups = sh.get_selector(cx, move |state| {
+        let max_secs = (*max_time.get() as u64) * 60;
+        let sort_by_time = *sort_by_time.get();
+        let show_archived = *show_archived.get();
         state
             .get()
             .recipe_categories
             .iter()
             .fold(BTreeMap::new(), |mut map, (r, cat)| {
                 debug!(?cat, recipe_id=?r, "Accumulating recipe into category");
-                map.entry(cat.clone()).or_insert(Vec::new()).push((
-                    r.clone(),
-                    state
-                        .get()
-                        .recipes
-                        .get(r)
-                        .expect(&format!("Failed to find recipe {}", r))
-                        .clone(),
-                ));
+                // Archived recipes that are already in the plan stay visible
+                // so they can be taken out of it.
+                let planned = state.get().recipe_counts.get(r).map_or(false, |c| *c > 0);
+                if state.get().archived.contains(r) && !show_archived && !planned {
+                    return map;
+                }
+                let recipe = state
+                    .get()
+                    .recipes
+                    .get(r)
+                    .expect(&format!("Failed to find recipe {}", r))
+                    .clone();
+                if max_secs == 0 || recipe.total_time().as_secs() <= max_secs {
+                    map.entry(cat.clone())
+                        .or_insert(Vec::new())
+                        .push((r.clone(), recipe));
+                }
                 map
             })
             .iter()
-            .map(|(cat, rs)| (cat.clone(), rs.clone()))
+            .map(|(cat, rs)| {
+                let mut rs = rs.clone();
+                if sort_by_time {
+                    rs.sort_by_key(|(_, r)| r.total_time());
+                }
+                (cat.clone(), rs)
+            })
             .collect::<Vec<(String, Vec<(String, Recipe)>)>>()
     });
     view! {cx,
-        Keyed(
-            iterable=recipe_category_groups,
-            view=move |cx, (cat, recipes)| {
-                view! {cx,
-                    CategoryGroup(sh=sh, category=cat, recipes=recipes, row_size=4)
-                }
-            },
-            key=|(ref cat, _)| cat.clone(),
-        )
-        span(role="button", on:click=move |_| {
-            sh.dispatch(cx, Message::LoadState(None));
-        }) { "Reset" } " "
-        span(role="button", on:click=move |_| {
+        div(class="no-print") {
+            label(for="max_total_time") { (t("recipe_plan.max_time")) }
+            input(id="max_total_time", type="number", min="0", step="5", bind:valueAsNumber=max_time)
+            label(for="sort_by_time_cb") { (t("recipe_plan.sort_by_time")) }
+            input(id="sort_by_time_cb", type="checkbox", bind:checked=sort_by_time)
+            label(for="show_archived_cb") { (t("recipe_plan.show_archived")) }
+            input(id="show_archived_cb", type="checkbox", bind:checked=show_archived)
+            label(for="household_size") { (t("recipe_plan.household_size")) }
+            input(id="household_size", type="number", min="0", step="1", bind:valueAsNumber=people, on:change=move |_| {
+                let size = *people.get_untracked();
+                let size = if size.is_nan() || size < 1.0 {
+                    None
+                } else {
+                    Some(size as u32)
+                };
+                sh.dispatch(cx, Message::UpdateHouseholdSize(size));
+            })
+        }
+        RecipePicker(sh)
+        div(class="recipe-categories") {
+            Keyed(
+                iterable=recipe_category_groups,
+                view=move |cx, (cat, recipes)| {
+                    view! {cx,
+                        CategoryGroup(sh=sh, category=cat, recipes=recipes, row_size=4)
+                    }
+                },
+                // The recipe ids are part of the key so that filtering or sorting a
+                // category re-renders it.
+                key=|(ref cat, ref recipes)| {
+                    (cat.clone(), recipes.iter().map(|(id, _)| id.clone()).collect::<Vec<String>>())
+                },
+            )
+        }
+        PlanOrder(sh)
+        button(type="button", on:click=move |_| {
+            sh.dispatch(cx, Message::RefreshPlan);
+        }) { (t("recipe_plan.reset")) } " "
+        button(type="button", on:click=move |_| {
             sh.dispatch(cx, Message::ResetRecipeCounts);
-        }) { "Clear All" } " "
-        span(role="button", on:click=move |_| {
+        }) { (t("recipe_plan.clear_all")) } " "
+        button(type="button", on:click=move |_| {
             // Poor man's click event signaling.
             sh.dispatch(cx, Message::SaveState(None));
-        }) { "Save Plan" } " "
+        }) { (t("recipe_plan.save")) } " "
     }
 }
@@ -18,6 +18,7 @@ use tracing::{debug, instrument};
 
 use crate::app_state::{Message, StateHandler};
 use crate::components::NumberField;
+use crate::i18n::t_args;
 
 #[derive(Props)]
 pub struct RecipeCheckBoxProps<'ctx> {
@@ -38,6 +39,7 @@ pub fn RecipeSelection<'ctx, G: Html>(
     let RecipeCheckBoxProps { i, title, sh } = props;
     let id = Rc::new(i);
     let id_for_count = id.clone();
+    let id_for_cooked = id.clone();
     // NOTE(jwall): The below get's a little tricky. We need a separate signal to bind for the
     // this recipes count. But we also want it to automatically update if the app_state
     // recipe count updates. We need to avoid signal update cycles so we have to do this
@@ -60,13 +62,22 @@ pub fn RecipeSelection<'ctx, G: Html>(
         }
     });
 
+    let last_cooked = sh.get_selector(cx, move |state| {
+        state
+            .get()
+            .last_cooked
+            .get(id_for_cooked.as_ref())
+            .map(|date| t_args("recipe_selection.cooked", &[("date", date)]))
+            .unwrap_or_default()
+    });
+
     let title = title.get().clone();
     let href = format!("/ui/recipe/view/{}", id);
     let name = format!("recipe_id:{}", id);
     let for_id = name.clone();
     view! {cx,
         div() {
-            label(for=for_id) { a(href=href) { (*title) } }
+            label(for=for_id) { a(href=href) { (*title) } " " small { (last_cooked.get()) } }
             NumberField(name=name, counter=count, min=0.0, on_change=Some(move |_| {
                 debug!(idx=%id, count=%(*count.get_untracked()), "setting recipe count");
                 sh.dispatch(cx, Message::UpdateRecipeCount(id.as_ref().clone(), *count.get_untracked() as usize));
@@ -0,0 +1,88 @@
+// Copyright 2023 Jeremy Wall (Jeremy@marzhilsltudios.com)
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+use recipes::IngredientKey;
+use sycamore::prelude::*;
+use tracing::{debug, instrument};
+
+use crate::{
+    app_state::{Message, StateHandler},
+    components::shopping_list::plan_shopping_list,
+    i18n::{t, t_args},
+    js_lib::WakeLock,
+};
+
+#[instrument(skip_all)]
+#[component]
+pub fn ShoppingMode<'ctx, G: Html>(cx: Scope<'ctx>, sh: StateHandler<'ctx>) -> View<G> {
+    // The wake lock is released when this scope is disposed.
+    let _wake_lock = create_ref(cx, WakeLock::request());
+    let items = sh.get_selector(cx, |state| {
+        let state = state.get();
+        debug!("building shopping mode list from state");
+        let mut items = plan_shopping_list(&state, state.use_staples)
+            .into_iter()
+            .map(|(k, item)| {
+                let i = item.ingredient;
+                let amt = state
+                    .modified_amts
+                    .get(&k)
+                    .cloned()
+                    .unwrap_or_else(|| format!("{}", i.amt.normalize()));
+                let category = state
+                    .category_map
+                    .get(&i.name)
+                    .cloned()
+                    .unwrap_or_else(|| t("shopping.other"));
+                let checked = item.filtered;
+                (checked, category, i.name, i.form, amt, k)
+            })
+            .collect::<Vec<(bool, String, String, Option<String>, String, IngredientKey)>>();
+        // Unchecked items sort first and then by category so the list follows the store.
+        items.sort();
+        items
+    });
+    let progress = create_memo(cx, || {
+        let items = items.get();
+        let done = items.iter().filter(|(checked, ..)| *checked).count();
+        (done, items.len())
+    });
+    let done = create_memo(cx, || progress.get().0);
+    let total = create_memo(cx, || progress.get().1);
+    view! {cx,
+        div(class="shop-progress") {
+            progress(value=*done.get(), max=*total.get())
+            span { (t_args("shop.progress", &[("done", &done.get()), ("total", &total.get())])) }
+        }
+        ul(class="shop-list") {
+            Indexed(
+                iterable=items,
+                view=move |cx, (checked, category, name, form, amt, k)| {
+                    let class = if checked { "shop-item checked" } else { "shop-item" };
+                    let form = form.map(|form| format!("({})", form)).unwrap_or_default();
+                    view! {cx,
+                        li(class=class) {
+                            button(class="outline", aria-pressed=checked, on:click=move |_| {
+                                sh.dispatch(cx, Message::ToggleCheckedIngredient(k.clone()));
+                            }) {
+                                span(class="shop-amt") { (amt) } " " (name) " " (form)
+                                br {}
+                                small { (category) }
+                            }
+                        }
+                    }
+                }
+            )
+        }
+    }
+}
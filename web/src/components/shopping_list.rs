@@ -11,13 +11,26 @@
 // WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
 // See the License for the specific language governing permissions and
 // limitations under the License.
-use std::collections::BTreeSet;
+use std::collections::{BTreeMap, BTreeSet};
 
-use recipes::{IngredientAccumulator, IngredientKey};
+use recipes::{
+    shopping::{shopping_list, ShoppingItem},
+    IngredientKey, MealType,
+};
 use sycamore::prelude::*;
 use tracing::{debug, info, instrument};
 
-use crate::app_state::{Message, StateHandler};
+use crate::app_state::{AppState, Message, StateHandler};
+use crate::i18n::{meal_label, t, t_args};
+
+/// The shopping list for the current plan along with the staples if
+/// requested. Filtered ingredients are included and marked as such.
+pub fn plan_shopping_list(
+    state: &AppState,
+    use_staples: bool,
+) -> BTreeMap<IngredientKey, ShoppingItem> {
+    shopping_list(&state.shopping_plan(use_staples), &state.recipes)
+}
 
 #[instrument(skip_all)]
 fn make_ingredients_rows<'ctx, G: Html>(
@@ -29,30 +42,35 @@ fn make_ingredients_rows<'ctx, G: Html>(
     let ingredients = sh.get_selector(cx, move |state| {
         let state = state.get();
         let category_map = &state.category_map;
+        let meals = state
+            .planned_recipes()
+            .into_iter()
+            .filter_map(|(id, _)| {
+                let title = state.recipes.get(&id)?.title.clone();
+                Some((title, state.meals_for(&id)))
+            })
+            .collect::<BTreeMap<String, BTreeSet<MealType>>>();
         debug!("building ingredient list from state");
-        let mut acc = IngredientAccumulator::new();
-        for (id, count) in state.recipe_counts.iter() {
-            for _ in 0..(*count) {
-                acc.accumulate_from(
-                    state
-                        .recipes
-                        .get(id)
-                        .expect(&format!("No such recipe id exists: {}", id)),
-                );
-            }
-        }
-        if *show_staples.get() {
-            if let Some(staples) = &state.staples {
-                acc.accumulate_ingredients_for("Staples", staples.iter());
-            }
-        }
-        let mut ingredients = acc
-            .ingredients()
+        let mut ingredients = plan_shopping_list(&state, *show_staples.get())
             .into_iter()
             // First we filter out any filtered ingredients
-            .filter(|(i, _)| !state.filtered_ingredients.contains(i))
+            .filter(|(_, item)| !item.filtered)
             // Then we take into account our modified amts
-            .map(|(k, (i, rs))| {
+            .map(|(k, item)| {
+                let (i, rs) = (item.ingredient, item.recipes);
+                let rs = rs
+                    .into_iter()
+                    .map(|title| match meals.get(&title) {
+                        Some(meals) => {
+                            let labels = meals
+                                .iter()
+                                .map(|meal| meal_label(*meal))
+                                .collect::<Vec<String>>();
+                            format!("{} ({})", title, labels.join(", "))
+                        }
+                        None => title,
+                    })
+                    .collect::<BTreeSet<String>>();
                 let category = category_map
                     .get(&i.name)
                     .cloned()
@@ -94,7 +112,7 @@ fn make_ingredients_rows<'ctx, G: Html>(
             iterable = ingredients,
             view = move |cx, (k, (name, form, category, amt, rs))| {
                 let category = if category == "" {
-                    "other".to_owned()
+                    t("shopping.other")
                 } else {
                     category
                 };
@@ -106,15 +124,17 @@ fn make_ingredients_rows<'ctx, G: Html>(
                     .fold(String::new(), |acc, s| format!("{}{},", acc, s))
                     .trim_end_matches(",")
                     .to_owned();
+                let amt_label = t_args("shopping.amount_of", &[("item", &name)]);
+                let remove_label = t_args("shopping.remove", &[("item", &name)]);
                 view! {cx,
                     tr {
                         td {
-                            input(bind:value=amt_signal, type="text", on:change=move |_| {
+                            input(bind:value=amt_signal, type="text", aria-label=amt_label, on:change=move |_| {
                                 sh.dispatch(cx, Message::UpdateAmt(k_clone.clone(), amt_signal.get_untracked().as_ref().clone()));
                             })
                         }
                         td {
-                            input(type="button", class="no-print destructive", value="X", on:click={
+                            input(type="button", class="no-print destructive", value="X", aria-label=remove_label, on:click={
                                 move |_| {
                                     sh.dispatch(cx, Message::AddFilteredIngredient(k.clone()));
                             }})
@@ -143,25 +163,25 @@ fn make_extras_rows<'ctx, G: Html>(cx: Scope<'ctx>, sh: StateHandler<'ctx>) -> V
                 view! {cx,
                     tr {
                         td {
-                            input(bind:value=amt_signal, type="text", on:change=move |_| {
+                            input(bind:value=amt_signal, type="text", aria-label=t("shopping.extra_amount"), on:change=move |_| {
                                 sh.dispatch(cx, Message::UpdateExtra(idx,
                                     amt_signal.get_untracked().as_ref().clone(),
                                     name_signal.get_untracked().as_ref().clone()));
                             })
                         }
                         td {
-                            input(type="button", class="no-print destructive", value="X", on:click=move |_| {
+                            input(type="button", class="no-print destructive", value="X", aria-label=t("shopping.remove_extra"), on:click=move |_| {
                                 sh.dispatch(cx, Message::RemoveExtra(idx));
                             })
                         }
                         td {
-                            input(bind:value=name_signal, type="text", on:change=move |_| {
+                            input(bind:value=name_signal, type="text", aria-label=t("shopping.extra_name"), on:change=move |_| {
                                 sh.dispatch(cx, Message::UpdateExtra(idx,
                                     amt_signal.get_untracked().as_ref().clone(),
                                     name_signal.get_untracked().as_ref().clone()));
                             })
                         }
-                        td { "Misc" }
+                        td { (t("shopping.misc")) }
                     }
                 }
             }
@@ -178,10 +198,10 @@ fn make_shopping_table<'ctx, G: Html>(
     view! {cx,
         table(class="pad-top shopping-list page-breaker container-fluid", role="grid") {
             tr {
-                th { " Quantity " }
-                th { " Delete " }
-                th { " Ingredient " }
-                th { " Recipes " }
+                th { (t("shopping.quantity")) }
+                th { (t("shopping.delete")) }
+                th { (t("shopping.ingredient")) }
+                th { (t("shopping.recipes")) }
             }
             tbody {
                 (make_ingredients_rows(cx, sh, show_staples))
@@ -197,25 +217,31 @@ pub fn ShoppingList<'ctx, G: Html>(cx: Scope<'ctx>, sh: StateHandler<'ctx>) -> V
     let show_staples = sh.get_selector(cx, |state| {
         state.get().use_staples
     });
+    // The household shares the list so pick up what was checked off
+    // elsewhere without reloading everything else.
+    let logged_in = sh.get_selector(cx, |state| state.get().auth.is_some());
+    if *logged_in.get_untracked() {
+        sh.dispatch(cx, Message::RefreshInventory);
+    }
     view! {cx,
-        h1 { "Shopping List " }
-        label(for="show_staples_cb") { "Show staples" }
+        h1 { (t("shopping.heading")) }
+        label(for="show_staples_cb") { (t("shopping.show_staples")) }
         input(id="show_staples_cb", type="checkbox", checked=*show_staples.get(), on:change=move|_| {
             let value = !*show_staples.get_untracked();
             sh.dispatch(cx, Message::UpdateUseStaples(value));
         })
         (make_shopping_table(cx, sh, show_staples))
-        span(role="button", class="no-print", on:click=move |_| {
+        button(type="button", class="no-print", on:click=move |_| {
             info!("Registering add item request for inventory");
             sh.dispatch(cx, Message::AddExtra(String::new(), String::new()));
-        }) { "Add Item" } " "
-        span(role="button", class="no-print", on:click=move |_| {
+        }) { (t("shopping.add_item")) } " "
+        button(type="button", class="no-print", on:click=move |_| {
             info!("Registering reset request for inventory");
             sh.dispatch(cx, Message::ResetInventory);
-        }) { "Reset" } " "
-        span(role="button", class="no-print", on:click=move |_| {
+        }) { (t("shopping.reset")) } " "
+        button(type="button", class="no-print", on:click=move |_| {
             info!("Registering save request for inventory");
             sh.dispatch(cx, Message::SaveState(None));
-        }) { "Save" } " "
+        }) { (t("shopping.save")) } " "
     }
 }
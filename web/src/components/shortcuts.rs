@@ -0,0 +1,285 @@
+// Copyright 2023 Jeremy Wall (Jeremy@marzhilsltudios.com)
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+use std::{cell::RefCell, rc::Rc};
+
+use sycamore::{futures::spawn_local_scoped, prelude::*};
+use tracing::debug;
+use wasm_bindgen::{prelude::Closure, JsCast, UnwrapThrowExt};
+use web_sys::{HtmlElement, HtmlInputElement, KeyboardEvent};
+
+use crate::app_state::StateHandler;
+use crate::components::confirm::Confirmer;
+use crate::i18n::t;
+
+const SWITCHER_INPUT_ID: &'static str = "quick-switcher-input";
+const SWITCHER_LIST_ID: &'static str = "quick-switcher-results";
+const MAX_RESULTS: usize = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Action {
+    ToggleSwitcher,
+    CloseSwitcher,
+    Navigate(&'static str),
+}
+
+/// The global shortcuts as (key code, ctrl or meta, alt, action).
+const SHORTCUTS: &'static [(&'static str, bool, bool, Action)] = &[
+    ("KeyK", true, false, Action::ToggleSwitcher),
+    ("Escape", false, false, Action::CloseSwitcher),
+    ("KeyS", false, true, Action::Navigate("/ui/planning/select")),
+    ("KeyP", false, true, Action::Navigate("/ui/planning/plan")),
+    ("KeyI", false, true, Action::Navigate("/ui/planning/inventory")),
+    ("KeyC", false, true, Action::Navigate("/ui/planning/cook")),
+    ("KeyN", false, true, Action::Navigate("/ui/manage/new_recipe")),
+];
+
+fn lookup_action(evt: &KeyboardEvent) -> Option<Action> {
+    let ctrl = evt.ctrl_key() || evt.meta_key();
+    let alt = evt.alt_key();
+    let code = evt.code();
+    SHORTCUTS
+        .iter()
+        .find(|(c, needs_ctrl, needs_alt, _)| *c == code && *needs_ctrl == ctrl && *needs_alt == alt)
+        .map(|(_, _, _, action)| *action)
+}
+
+/// Score a candidate against a query by matching the query characters in order.
+/// Consecutive matches and matches at the start of a word score higher. Returns
+/// None if the query does not match the candidate.
+pub fn fuzzy_score(query: &str, candidate: &str) -> Option<usize> {
+    let candidate: Vec<char> = candidate.to_lowercase().chars().collect();
+    let mut pos = 0;
+    let mut last: Option<usize> = None;
+    let mut score = 0;
+    for q in query.to_lowercase().chars().filter(|c| !c.is_whitespace()) {
+        let found = pos + candidate[pos..].iter().position(|c| *c == q)?;
+        score += 1;
+        if found > 0 && last == Some(found - 1) {
+            score += 2;
+        }
+        if found == 0 || candidate[found - 1].is_whitespace() {
+            score += 3;
+        }
+        last = Some(found);
+        pos = found + 1;
+    }
+    Some(score)
+}
+
+fn focus_switcher_input() {
+    if let Ok(Some(input)) = crate::js_lib::get_element_by_id::<HtmlInputElement>(SWITCHER_INPUT_ID) {
+        let _ = input.focus();
+    }
+}
+
+fn option_id(idx: usize) -> String {
+    format!("quick-switcher-option-{}", idx)
+}
+
+/// The open state of the switcher and the element to give the focus back to
+/// when it closes.
+#[derive(Clone)]
+struct Switcher {
+    open: RcSignal<bool>,
+    return_focus: Rc<RefCell<Option<HtmlElement>>>,
+}
+
+impl Switcher {
+    fn new() -> Self {
+        Self {
+            open: create_rc_signal(false),
+            return_focus: Rc::new(RefCell::new(None)),
+        }
+    }
+
+    fn open(&self) {
+        if *self.open.get_untracked() {
+            return;
+        }
+        *self.return_focus.borrow_mut() = web_sys::window()
+            .and_then(|w| w.document())
+            .and_then(|d| d.active_element())
+            .and_then(|e| e.dyn_into::<HtmlElement>().ok());
+        self.open.set(true);
+        focus_switcher_input();
+    }
+
+    fn close(&self) {
+        if !*self.open.get_untracked() {
+            return;
+        }
+        self.open.set(false);
+        if let Some(element) = self.return_focus.borrow_mut().take() {
+            let _ = element.focus();
+        }
+    }
+
+    fn toggle(&self) {
+        if *self.open.get_untracked() {
+            self.close();
+        } else {
+            self.open();
+        }
+    }
+}
+
+fn register_listener<'ctx>(cx: Scope<'ctx>, switcher: Switcher) {
+    let confirmer = Confirmer::get_from_context(cx);
+    let listener = Closure::<dyn Fn(KeyboardEvent)>::new(move |evt: KeyboardEvent| {
+        match lookup_action(&evt) {
+            Some(Action::ToggleSwitcher) => {
+                evt.prevent_default();
+                switcher.toggle();
+            }
+            Some(Action::CloseSwitcher) => switcher.close(),
+            Some(Action::Navigate(path)) => {
+                evt.prevent_default();
+                let confirmer = confirmer.clone();
+                wasm_bindgen_futures::spawn_local(async move {
+                    if crate::routing::confirm_navigation(&confirmer).await {
+                        debug!(path, "Navigating from keyboard shortcut");
+                        sycamore_router::navigate(path);
+                    }
+                });
+            }
+            None => (),
+        }
+    });
+    web_sys::window()
+        .expect("No window present")
+        .add_event_listener_with_callback("keydown", listener.as_ref().unchecked_ref())
+        .unwrap_throw();
+    on_cleanup(cx, move || {
+        if let Some(window) = web_sys::window() {
+            let _ = window
+                .remove_event_listener_with_callback("keydown", listener.as_ref().unchecked_ref());
+        }
+    });
+}
+
+#[component]
+pub fn KeyboardShortcuts<'ctx, G: Html>(cx: Scope<'ctx>, sh: StateHandler<'ctx>) -> View<G> {
+    // The window listener has to be 'static so we use an RcSignal to share the
+    // open state with it.
+    let switcher = create_ref(cx, Switcher::new());
+    register_listener(cx, switcher.clone());
+    let open = &switcher.open;
+    let query = create_signal(cx, String::new());
+    let selected = create_signal(cx, 0usize);
+    let recipes = sh.get_selector(cx, |state| {
+        state
+            .get()
+            .recipes
+            .iter()
+            .map(|(id, r)| (id.clone(), r.title.clone()))
+            .collect::<Vec<(String, String)>>()
+    });
+    let matches = create_memo(cx, move || {
+        let query = query.get();
+        let mut scored = recipes
+            .get()
+            .iter()
+            .filter_map(|(id, title)| {
+                fuzzy_score(&query, title)
+                    .or_else(|| fuzzy_score(&query, id))
+                    .map(|score| (score, id.clone(), title.clone()))
+            })
+            .collect::<Vec<(usize, String, String)>>();
+        scored.sort_by(|l, r| r.0.cmp(&l.0).then(l.2.cmp(&r.2)));
+        scored.truncate(MAX_RESULTS);
+        scored
+            .into_iter()
+            .map(|(_, id, title)| (id, title))
+            .collect::<Vec<(String, String)>>()
+    });
+    create_effect(cx, move || {
+        // Reset the selection whenever the matches change.
+        matches.track();
+        selected.set(0);
+    });
+    let active_option = create_memo(cx, move || {
+        if matches.get().is_empty() {
+            String::new()
+        } else {
+            option_id(*selected.get())
+        }
+    });
+    let close = move || {
+        switcher.close();
+        query.set(String::new());
+    };
+    view! {cx,
+        (if *open.get() {
+            view! {cx,
+                div(class="quick-switcher no-print", role="dialog", aria-modal="true", aria-label=t("switcher.label")) {
+                    input(id=SWITCHER_INPUT_ID, type="search", role="combobox", aria-label=t("switcher.find"),
+                        aria-expanded="true", aria-controls=SWITCHER_LIST_ID, aria-autocomplete="list",
+                        aria-activedescendant=active_option.get(),
+                        placeholder=t("switcher.find"), bind:value=query, on:keydown=move |evt: web_sys::Event| {
+                        let evt: KeyboardEvent = evt.unchecked_into();
+                        let count = matches.get_untracked().len();
+                        match evt.key().as_str() {
+                            "ArrowDown" if count > 0 => {
+                                evt.prevent_default();
+                                selected.set((*selected.get_untracked() + 1) % count);
+                            }
+                            "ArrowUp" if count > 0 => {
+                                evt.prevent_default();
+                                selected.set((*selected.get_untracked() + count - 1) % count);
+                            }
+                            "Enter" => {
+                                evt.prevent_default();
+                                if let Some((id, _)) = matches.get_untracked().get(*selected.get_untracked()) {
+                                    let path = if evt.shift_key() {
+                                        format!("/ui/recipe/edit/{}", id)
+                                    } else {
+                                        format!("/ui/recipe/view/{}", id)
+                                    };
+                                    close();
+                                    spawn_local_scoped(cx, async move {
+                                        let confirmer = Confirmer::get_from_context(cx);
+                                        if crate::routing::confirm_navigation(&confirmer).await {
+                                            sycamore_router::navigate(&path);
+                                        }
+                                    });
+                                }
+                            }
+                            "Tab" => {
+                                // The switcher is modal so the focus stays
+                                // in it.
+                                evt.prevent_default();
+                            }
+                            _ => (),
+                        }
+                    })
+                    ul(id=SWITCHER_LIST_ID, role="listbox", aria-label=t("switcher.results")) {
+                        (View::new_fragment(matches.get().iter().cloned().enumerate().map(|(idx, (id, title))| {
+                            let is_selected = idx == *selected.get();
+                            let class = if is_selected { "selected" } else { "" };
+                            let href = format!("/ui/recipe/view/{}", id);
+                            view! {cx,
+                                li(id=option_id(idx), class=class, role="option", aria-selected=is_selected.to_string()) {
+                                    a(href=href, tabindex="-1", on:click=move |_| close()) { (title) }
+                                }
+                            }
+                        }).collect()))
+                    }
+                    small { (t("switcher.help")) }
+                }
+            }
+        } else {
+            View::empty()
+        })
+    }
+}
@@ -15,6 +15,7 @@ use sycamore::{futures::spawn_local_scoped, prelude::*};
 use tracing::{debug, error};
 
 use crate::app_state::{Message, StateHandler};
+use crate::i18n::t;
 use crate::js_lib;
 use recipes::{self, parse};
 
@@ -29,7 +30,7 @@ fn check_ingredients_parses(
         aria_hint.set("true");
         false
     } else {
-        error_text.set(String::from("No parse errors..."));
+        error_text.set(t("recipe.no_parse_errors"));
         aria_hint.set("false");
         true
     }
@@ -48,7 +49,7 @@ pub fn IngredientsEditor<'ctx, G: Html>(
     let IngredientComponentProps { sh } = props;
     let store = crate::api::HttpStore::get_from_context(cx);
     let text = create_signal(cx, String::new());
-    let error_text = create_signal(cx, String::from("Parse results..."));
+    let error_text = create_signal(cx, t("recipe.parse_results"));
     let aria_hint = create_signal(cx, "false");
 
     spawn_local_scoped(cx, {
@@ -62,18 +63,19 @@ pub fn IngredientsEditor<'ctx, G: Html>(
                 check_ingredients_parses(entry.as_str(), error_text, aria_hint);
                 text.set(entry);
             } else {
-                error_text.set("Unable to find staples".to_owned());
+                error_text.set(t("staples.not_found"));
             }
         }
     });
 
     let dirty = create_signal(cx, false);
+    crate::routing::track_unsaved_changes(cx, "staples_editor", dirty);
     let ts = create_signal(cx, js_lib::get_ms_timestamp());
 
     debug!("creating editor view");
     view! {cx,
         div(class="grid") {
-            textarea(bind:value=text, aria-invalid=aria_hint.get(), rows=20, on:change=move |_| {
+            textarea(aria-label=t("staples.label"), aria-describedby="staples_parse", bind:value=text, aria-invalid=aria_hint.get(), rows=20, on:change=move |_| {
                 dirty.set(true);
             }, on:input=move |_| {
                 let current_ts = js_lib::get_ms_timestamp();
@@ -82,9 +84,9 @@ pub fn IngredientsEditor<'ctx, G: Html>(
                     ts.set(current_ts);
                 }
             })
-            div(class="parse") { (error_text.get()) }
+            div(id="staples_parse", class="parse", aria-live="polite") { (error_text.get()) }
         }
-        span(role="button", on:click=move |_| {
+        button(type="button", on:click=move |_| {
             let unparsed = text.get();
             if !*dirty.get_untracked() {
                 debug!("Staples text is unchanged");
@@ -94,7 +96,8 @@ pub fn IngredientsEditor<'ctx, G: Html>(
             if check_ingredients_parses(unparsed.as_str(), error_text, aria_hint) {
                 debug!("Staples text is changed");
                 sh.dispatch(cx, Message::UpdateStaples(unparsed.as_ref().clone(), None));
+                dirty.set(false);
             }
-        }) { "Save" }
+        }) { (t("staples.save")) }
     }
 }
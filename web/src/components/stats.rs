@@ -0,0 +1,81 @@
+// Copyright 2023 Jeremy Wall (Jeremy@marzhilsltudios.com)
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+use client_api::{IngredientCount, PlanStats, RecipeCount};
+use sycamore::{futures::spawn_local_scoped, prelude::*};
+use tracing::error;
+
+use crate::i18n::{t, t_args};
+
+/// Numbers about the recipes and plans worked out from the plan history.
+#[component]
+pub fn Stats<G: Html>(cx: Scope) -> View<G> {
+    let store = create_ref(cx, crate::api::HttpStore::get_from_context(cx));
+    let recipes = create_signal(cx, Vec::<RecipeCount>::new());
+    let ingredients = create_signal(cx, Vec::<IngredientCount>::new());
+    let plans = create_signal(cx, PlanStats::default());
+    spawn_local_scoped(cx, async move {
+        match store.fetch_recipe_stats().await {
+            Ok(counts) => recipes.set(counts),
+            Err(err) => error!(?err, "Failed to load recipe stats"),
+        }
+        match store.fetch_ingredient_stats().await {
+            Ok(counts) => ingredients.set(counts),
+            Err(err) => error!(?err, "Failed to load ingredient stats"),
+        }
+        match store.fetch_plan_stats().await {
+            Ok(stats) => plans.set(stats),
+            Err(err) => error!(?err, "Failed to load plan stats"),
+        }
+    });
+    let average = create_memo(cx, || {
+        format!("{:.1}", plans.get().average_recipes_per_week)
+    });
+    let months = create_memo(cx, || plans.get().months.clone());
+    view! {cx,
+        h2 { (t("stats.plans")) }
+        p { (t_args("stats.average", &[("count", &average.get()), ("weeks", &plans.get().weeks)])) }
+        table(class="stats") {
+            Keyed(
+                iterable=months,
+                view=|cx, month| view! {cx,
+                    tr { td { (month.month) } td { (month.plans) } }
+                },
+                key=|month| month.month.clone(),
+            )
+        }
+        h2 { (t("stats.recipes")) }
+        ol {
+            Keyed(
+                iterable=recipes,
+                view=|cx, recipe| {
+                    let href = format!("/ui/recipe/view/{}", recipe.recipe_id);
+                    view! {cx,
+                        li { a(href=href) { (recipe.title) } " " small { (t_args("stats.times", &[("count", &recipe.count)])) } }
+                    }
+                },
+                key=|recipe| recipe.recipe_id.clone(),
+            )
+        }
+        h2 { (t("stats.ingredients")) }
+        ol {
+            Keyed(
+                iterable=ingredients,
+                view=|cx, ingredient| view! {cx,
+                    li { (ingredient.name) " " small { (t_args("stats.meals", &[("count", &ingredient.count)])) } }
+                },
+                key=|ingredient| ingredient.name.clone(),
+            )
+        }
+    }
+}
@@ -0,0 +1,88 @@
+// Copyright 2023 Jeremy Wall (Jeremy@marzhilsltudios.com)
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+use sycamore::prelude::*;
+
+use crate::{
+    app_state::{Message, StateHandler},
+    components::toast,
+    i18n::t,
+};
+
+/// Lets the user change what the recipe viewer offers in place of an
+/// ingredient. The server's defaults are shown until they are replaced.
+#[component]
+pub fn Substitutions<'ctx, G: Html>(cx: Scope<'ctx>, sh: StateHandler<'ctx>) -> View<G> {
+    let substitutions = sh.get_selector(cx, |state| {
+        state
+            .get()
+            .substitutions
+            .iter()
+            .map(|(name, substitutes)| (name.clone(), substitutes.join(" | ")))
+            .collect::<Vec<(String, String)>>()
+    });
+    let name = create_signal(cx, String::new());
+    let substitutes = create_signal(cx, String::new());
+    view! {cx,
+        h2 { (t("substitutions.heading")) }
+        p { (t("substitutions.description")) }
+        table {
+            tr {
+                th { (t("substitutions.ingredient")) }
+                th { (t("substitutions.substitutes")) }
+                th {}
+            }
+            Indexed(
+                iterable=substitutions,
+                view=move |cx, (ingredient, listed)| {
+                    let edit = (ingredient.clone(), listed.clone());
+                    let remove = ingredient.clone();
+                    view! {cx,
+                        tr {
+                            td { (ingredient) }
+                            td { (listed) }
+                            td {
+                                button(type="button", class="outline", on:click=move |_| {
+                                    name.set(edit.0.clone());
+                                    substitutes.set(edit.1.clone());
+                                }) { (t("substitutions.edit")) } " "
+                                button(type="button", class="outline secondary", on:click=move |_| {
+                                    sh.dispatch(cx, Message::SetSubstitutes(remove.clone(), Vec::new()));
+                                }) { (t("substitutions.remove")) }
+                            }
+                        }
+                    }
+                },
+            )
+        }
+        div(role="group") {
+            input(type="text", aria-label=t("substitutions.ingredient"), placeholder=t("substitutions.ingredient"), bind:value=name)
+            input(type="text", aria-label=t("substitutions.substitutes"), placeholder=t("substitutions.placeholder"), bind:value=substitutes)
+            button(on:click=move |_| {
+                let ingredient = name.get_untracked().trim().to_lowercase();
+                if ingredient.is_empty() {
+                    toast::error_message(cx, &t("substitutions.name_required"), None);
+                    return;
+                }
+                let listed = client_api::parse_substitutes(substitutes.get_untracked().as_str());
+                if listed.is_empty() {
+                    toast::error_message(cx, &t("substitutions.substitutes_required"), None);
+                    return;
+                }
+                sh.dispatch(cx, Message::SetSubstitutes(ingredient, listed));
+                name.set(String::new());
+                substitutes.set(String::new());
+            }) { (t("substitutions.save")) }
+        }
+    }
+}
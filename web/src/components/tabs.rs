@@ -14,11 +14,13 @@
 use sycamore::prelude::*;
 use tracing::debug;
 
+use crate::i18n::t;
+
 #[derive(Props)]
 pub struct TabState<'a, G: Html> {
     pub children: Children<'a, G>,
     pub selected: Option<String>,
-    tablist: Vec<(String, &'static str)>,
+    tablist: Vec<(String, String)>,
 }
 
 #[component]
@@ -32,22 +34,26 @@ pub fn TabbedView<'a, G: Html>(cx: Scope<'a>, state: TabState<'a, G>) -> View<G>
     let menu = View::new_fragment(
         tablist
             .iter()
-            .map(|&(ref href, show)| {
+            .map(|(href, show)| {
                 let href = href.clone();
-                debug!(?selected, show, "identifying tab");
-                let class = if selected.as_ref().map_or(false, |selected| selected == show) {
-                    "no-print selected"
+                let show = show.clone();
+                debug!(?selected, %show, "identifying tab");
+                let is_selected = selected
+                    .as_ref()
+                    .map_or(false, |selected| *selected == show);
+                let (class, current) = if is_selected {
+                    ("no-print selected", "page")
                 } else {
-                    "no-print"
+                    ("no-print", "false")
                 };
                 view! {cx,
-                    li(class=class) { a(href=href) { (show) } }
+                    li(class=class) { a(href=href, aria-current=current) { (show) } }
                 }
             })
             .collect(),
     );
     view! {cx,
-        nav {
+        nav(aria-label=t("tabs.label")) {
             ul(class="tabs") {
                 (menu)
             }
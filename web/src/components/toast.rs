@@ -11,69 +11,131 @@
 // WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
 // See the License for the specific language governing permissions and
 // limitations under the License.\
-use sycamore::{easing, motion, prelude::*};
-use tracing::debug;
-use wasm_bindgen::UnwrapThrowExt;
+use std::cell::Cell;
+use std::rc::Rc;
+
+use sycamore::prelude::*;
+use tracing::{debug, error};
+use wasm_bindgen::{prelude::Closure, JsCast};
 
 const SECTION_ID: &'static str = "toast-container";
 
-#[component]
-pub fn Container<'a, G: Html>(cx: Scope<'a>) -> View<G> {
-    view! {cx,
-        section(id=SECTION_ID) { }
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Level {
+    Info,
+    Error,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Notification {
+    id: u64,
+    level: Level,
+    message: String,
+}
+
+/// The notifications currently being shown. This is provided as context at the
+/// root of the UI so anything with a scope, or a clone of it, can notify the
+/// user.
+#[derive(Clone)]
+pub struct Notifier {
+    notifications: RcSignal<Vec<Notification>>,
+    next_id: Rc<Cell<u64>>,
+}
+
+impl std::fmt::Debug for Notifier {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Notifier")
+            .field("notifications", &self.notifications.get_untracked())
+            .finish()
     }
 }
 
-pub fn create_output_element(msg: &str, class: &str) -> web_sys::Element {
-    let document = web_sys::window()
-        .expect("No window present")
-        .document()
-        .expect("No document in window");
-    let output = document.create_element("output").unwrap_throw();
-    let message_node = document.create_text_node(msg);
-    output.set_attribute("class", class).unwrap_throw();
-    output.set_attribute("role", "status").unwrap_throw();
-    output.append_child(&message_node).unwrap_throw();
-    output
+impl Notifier {
+    pub fn new() -> Self {
+        Self {
+            notifications: create_rc_signal(Vec::new()),
+            next_id: Rc::new(Cell::new(0)),
+        }
+    }
+
+    pub fn provide_context(cx: Scope) {
+        provide_context(cx, Self::new());
+    }
+
+    pub fn get_from_context(cx: Scope) -> Self {
+        use_context::<Self>(cx).clone()
+    }
+
+    pub fn info(&self, msg: &str) {
+        self.notify(Level::Info, msg, None);
+    }
+
+    pub fn error(&self, msg: &str) {
+        self.notify(Level::Error, msg, None);
+    }
+
+    pub fn notify(&self, level: Level, msg: &str, timeout: Option<chrono::Duration>) {
+        // Several requests can fail for the same reason at once so we don't
+        // stack up identical messages.
+        if self
+            .notifications
+            .get_untracked()
+            .iter()
+            .any(|n| n.level == level && n.message == msg)
+        {
+            return;
+        }
+        let id = self.next_id.get();
+        self.next_id.set(id + 1);
+        debug!(id, ?level, msg, "Showing notification");
+        self.notifications.modify().push(Notification {
+            id,
+            level,
+            message: msg.to_owned(),
+        });
+        let timeout = timeout.unwrap_or_else(|| chrono::Duration::seconds(3));
+        let notifications = self.notifications.clone();
+        let dismiss = Closure::once_into_js(move || {
+            notifications.modify().retain(|n| n.id != id);
+        });
+        let scheduled = web_sys::window().map(|w| {
+            w.set_timeout_with_callback_and_timeout_and_arguments_0(
+                dismiss.unchecked_ref(),
+                timeout.num_milliseconds() as i32,
+            )
+        });
+        if let Some(Err(err)) = scheduled {
+            error!(?err, "Failed to schedule notification timeout");
+        }
+    }
 }
 
-fn show_toast<'a>(cx: Scope<'a>, msg: &str, class: &str, timeout: Option<chrono::Duration>) {
-    let timeout = timeout.unwrap_or_else(|| chrono::Duration::seconds(3));
-    // Insert a toast output element into the container.
-    let tweened = motion::create_tweened_signal(
-        cx,
-        0.0 as f32,
-        timeout
-            .to_std()
-            .expect("Failed to convert timeout duration."),
-        easing::quad_in,
-    );
-    tweened.set(1.0);
-    create_effect_scoped(cx, move |_cx| {
-        if !tweened.is_tweening() {
-            debug!("Detected message timeout.");
-            let container = crate::js_lib::get_element_by_id::<web_sys::HtmlElement>(SECTION_ID)
-                .expect("Failed to get toast-container")
-                .expect("No toast-container");
-            if let Some(node_to_remove) = container.first_element_child() {
-                // Always remove the first child if there is one.
-                container.remove_child(&node_to_remove).unwrap_throw();
-            }
+#[component]
+pub fn Container<'a, G: Html>(cx: Scope<'a>) -> View<G> {
+    let notifications = create_ref(cx, Notifier::get_from_context(cx).notifications);
+    view! {cx,
+        section(id=SECTION_ID) {
+            Keyed(
+                iterable=notifications,
+                view=|cx, notification| {
+                    let class = match notification.level {
+                        Level::Info => "toast",
+                        Level::Error => "toast error",
+                    };
+                    view! {cx,
+                        output(class=class, role="status") { (notification.message) }
+                    }
+                },
+                key=|notification| notification.id,
+            )
         }
-    });
-    let output_element = create_output_element(msg, class);
-    crate::js_lib::get_element_by_id::<web_sys::HtmlElement>(SECTION_ID)
-        .expect("Failed to get toast-container")
-        .expect("No toast-container")
-        // Always append after the last child.
-        .append_child(&output_element)
-        .unwrap_throw();
+    }
 }
 
 pub fn message<'a>(cx: Scope<'a>, msg: &str, timeout: Option<chrono::Duration>) {
-    show_toast(cx, msg, "toast", timeout);
+    Notifier::get_from_context(cx).notify(Level::Info, msg, timeout);
 }
 
 pub fn error_message<'a>(cx: Scope<'a>, msg: &str, timeout: Option<chrono::Duration>) {
-    show_toast(cx, msg, "toast error", timeout);
+    Notifier::get_from_context(cx).notify(Level::Error, msg, timeout);
 }
@@ -0,0 +1,89 @@
+// Copyright 2023 Jeremy Wall (Jeremy@marzhilsltudios.com)
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+use client_api::TrashedRecipe;
+use sycamore::{futures::spawn_local_scoped, prelude::*};
+use tracing::error;
+
+use crate::{
+    app_state::{parse_recipe, Message, StateHandler},
+    i18n::{t, t_args},
+};
+
+/// Lists deleted recipes so they can be restored or removed for good.
+#[component]
+pub fn RecipeTrash<'ctx, G: Html>(cx: Scope<'ctx>, sh: StateHandler<'ctx>) -> View<G> {
+    let store = create_ref(cx, crate::api::HttpStore::get_from_context(cx));
+    let trash = create_signal(cx, Vec::<TrashedRecipe>::new());
+    spawn_local_scoped(cx, async move {
+        match store.fetch_trash().await {
+            Ok(entries) => trash.set(entries),
+            Err(err) => error!(?err, "Failed to load the trash"),
+        }
+    });
+    let remove = move |recipe_id: &str| {
+        trash
+            .modify()
+            .retain(|trashed| trashed.entry.recipe_id() != recipe_id);
+    };
+    view! {cx,
+        (if trash.get().is_empty() {
+            view! {cx, p { (t("trash.empty")) } }
+        } else {
+            View::empty()
+        })
+        table(class="trash") {
+            Keyed(
+                iterable=trash,
+                view=move |cx, trashed| {
+                    let recipe_id = create_ref(cx, trashed.entry.recipe_id().to_owned());
+                    let title = parse_recipe(trashed.entry.recipe_text())
+                        .map(|recipe| recipe.title)
+                        .unwrap_or_else(|_| recipe_id.clone());
+                    let deleted_at = trashed.deleted_at.format("%Y-%m-%d %H:%M").to_string();
+                    let expires_at = trashed.expires_at.format("%Y-%m-%d").to_string();
+                    view! {cx,
+                        tr {
+                            td { (title) }
+                            td { (t_args("trash.deleted", &[("date", &deleted_at)])) }
+                            td { (t_args("trash.expires", &[("date", &expires_at)])) }
+                            td {
+                                button(type="button", on:click=move |_| {
+                                    spawn_local_scoped(cx, async move {
+                                        match store.restore_recipe(recipe_id).await {
+                                            Ok(Some(entry)) => {
+                                                remove(recipe_id);
+                                                sh.dispatch(cx, Message::RecipeRestored(entry));
+                                            }
+                                            Ok(None) => remove(recipe_id),
+                                            Err(err) => error!(?err, "Failed to restore recipe"),
+                                        }
+                                    });
+                                }) { (t("trash.restore")) } " "
+                                button(type="button", class="secondary", on:click=move |_| {
+                                    spawn_local_scoped(cx, async move {
+                                        match store.purge_recipe(recipe_id).await {
+                                            Ok(()) => remove(recipe_id),
+                                            Err(err) => error!(?err, "Failed to delete recipe"),
+                                        }
+                                    });
+                                }) { (t("trash.purge")) }
+                            }
+                        }
+                    }
+                },
+                key=|trashed| trashed.entry.recipe_id().to_owned(),
+            )
+        }
+    }
+}
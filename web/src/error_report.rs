@@ -0,0 +1,151 @@
+// Copyright 2023 Jeremy Wall (Jeremy@marzhilsltudios.com)
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//! Sends panics and logged errors to the server so failures on someone
+//! else's device show up in the server logs. Nothing is sent unless the user
+//! turned it on. The choice is kept in local storage so it works before
+//! anyone logs in.
+use std::cell::Cell;
+use std::fmt::Debug;
+
+use client_api::{ClientError, ClientErrorKind};
+use tracing::{
+    field::{Field, Visit},
+    Event, Level, Subscriber,
+};
+use tracing_subscriber::{layer::Context, Layer};
+use web_sys::{Blob, BlobPropertyBag};
+
+use crate::js_lib;
+
+/// The local storage key for whether the user agreed to send reports.
+pub const CONSENT_KEY: &'static str = "report_errors";
+
+const REPORT_PATH: &'static str = "/api/v2/client_errors";
+
+/// The most reports we send from one page load so a loop logging errors
+/// doesn't flood the server.
+const MAX_REPORTS: u32 = 10;
+
+thread_local! {
+    static SENT: Cell<u32> = Cell::new(0);
+    // Sending a report can log errors itself which would come right back
+    // here.
+    static REPORTING: Cell<bool> = Cell::new(false);
+}
+
+/// Whether the user agreed to send error reports.
+pub fn consented() -> bool {
+    js_lib::get_storage()
+        .and_then(|s| s.get_item(CONSENT_KEY).ok().flatten())
+        .map(|v| v == "true")
+        .unwrap_or(false)
+}
+
+pub fn set_consent(consent: bool) {
+    if let Some(storage) = js_lib::get_storage() {
+        let _ = if consent {
+            storage.set_item(CONSENT_KEY, "true")
+        } else {
+            storage.remove_item(CONSENT_KEY)
+        };
+    }
+}
+
+fn send(report: &ClientError) -> Option<()> {
+    let body = serde_json::to_string(report).ok()?;
+    let parts = js_sys::Array::of1(&body.into());
+    let mut options = BlobPropertyBag::new();
+    options.type_("application/json");
+    let blob = Blob::new_with_str_sequence_and_options(&parts, &options).ok()?;
+    // A beacon is sent by the browser even if the page is going away or our
+    // wasm has panicked.
+    web_sys::window()?
+        .navigator()
+        .send_beacon_with_opt_blob(REPORT_PATH, Some(&blob))
+        .ok()?
+        .then_some(())
+}
+
+/// Send a report if the user agreed to it.
+pub fn report(kind: ClientErrorKind, message: String, location: Option<String>) {
+    if REPORTING.with(|r| r.replace(true)) {
+        return;
+    }
+    if consented() && SENT.with(|s| s.get()) < MAX_REPORTS {
+        let window = web_sys::window();
+        let report = ClientError {
+            kind,
+            message,
+            location,
+            url: window
+                .as_ref()
+                .and_then(|w| w.location().href().ok())
+                .unwrap_or_default(),
+            user_agent: window
+                .as_ref()
+                .and_then(|w| w.navigator().user_agent().ok())
+                .unwrap_or_default(),
+            version: env!("CARGO_PKG_VERSION").to_owned(),
+        };
+        if send(&report).is_some() {
+            SENT.with(|s| s.set(s.get() + 1));
+        }
+    }
+    REPORTING.with(|r| r.set(false));
+}
+
+/// Log panics to the console like console_error_panic_hook and report them.
+pub fn set_panic_hook() {
+    std::panic::set_hook(Box::new(|info| {
+        console_error_panic_hook::hook(info);
+        report(
+            ClientErrorKind::Panic,
+            info.to_string(),
+            info.location().map(|l| l.to_string()),
+        );
+    }));
+}
+
+/// Collects an event's message and fields into one line.
+#[derive(Default)]
+struct EventMessage(String);
+
+impl Visit for EventMessage {
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        if field.name() == "message" {
+            self.0.insert_str(0, &format!("{:?}", value));
+        } else {
+            self.0.push_str(&format!(" {}={:?}", field.name(), value));
+        }
+    }
+}
+
+/// A tracing layer that reports `error!` events.
+pub struct ReportLayer;
+
+impl<S: Subscriber> Layer<S> for ReportLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let metadata = event.metadata();
+        if *metadata.level() != Level::ERROR {
+            return;
+        }
+        let mut message = EventMessage::default();
+        event.record(&mut message);
+        let location = match (metadata.file(), metadata.line()) {
+            (Some(file), Some(line)) => format!("{}:{}", file, line),
+            _ => metadata.target().to_owned(),
+        };
+        report(ClientErrorKind::Error, message.0, Some(location));
+    }
+}
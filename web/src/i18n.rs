@@ -0,0 +1,186 @@
+// Copyright 2023 Jeremy Wall (Jeremy@marzhilsltudios.com)
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//! Translated strings for the ui. Each locale has a catalog of messages keyed
+//! by id in `static/i18n/<locale>.json`. The English catalog is built in and
+//! the others are fetched when they are chosen. Messages missing from a
+//! catalog fall back to English.
+use std::{cell::RefCell, collections::BTreeMap, fmt::Display};
+
+use recipes::MealType;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, error, warn};
+use wasm_bindgen::UnwrapThrowExt;
+
+use crate::api::LocalStore;
+
+/// The settings key the locale is stored under in the settings api.
+pub const LOCALE_SETTING: &'static str = "locale";
+
+const ENGLISH_CATALOG: &'static str = include_str!("../static/i18n/en.json");
+
+type Catalog = BTreeMap<String, String>;
+
+/// The languages we have message catalogs for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Locale {
+    En,
+    Es,
+}
+
+impl Default for Locale {
+    fn default() -> Self {
+        Locale::En
+    }
+}
+
+impl Locale {
+    pub const ALL: [Locale; 2] = [Locale::En, Locale::Es];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Locale::En => "en",
+            Locale::Es => "es",
+        }
+    }
+
+    /// The name of the language in that language.
+    pub fn label(&self) -> &'static str {
+        match self {
+            Locale::En => "English",
+            Locale::Es => "Español",
+        }
+    }
+
+    /// The browser's preferred language if we have a catalog for it.
+    pub fn from_browser() -> Self {
+        web_sys::window()
+            .and_then(|w| w.navigator().language())
+            .and_then(|lang| lang.parse().ok())
+            .unwrap_or_default()
+    }
+}
+
+impl std::str::FromStr for Locale {
+    type Err = String;
+
+    /// Parse a language tag like `es` or `es-MX`. Only the language is used.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let language = s
+            .split(|c| c == '-' || c == '_')
+            .next()
+            .unwrap_or_default()
+            .to_lowercase();
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|l| l.as_str() == language)
+            .ok_or_else(|| format!("Unsupported language {}", s))
+    }
+}
+
+fn parse_catalog(locale: Locale, content: &str) -> Option<Catalog> {
+    match serde_json::from_str(content) {
+        Ok(catalog) => Some(catalog),
+        Err(err) => {
+            error!(?locale, ?err, "Invalid message catalog");
+            None
+        }
+    }
+}
+
+thread_local! {
+    static ENGLISH: Catalog = parse_catalog(Locale::En, ENGLISH_CATALOG).unwrap_or_default();
+    static CURRENT: RefCell<(Locale, Catalog)> = RefCell::new((Locale::En, Catalog::new()));
+}
+
+fn catalog_key(locale: Locale) -> String {
+    format!("i18n:{}", locale.as_str())
+}
+
+/// Fetch the catalog for a locale from the server. The last catalog we got is
+/// kept in local storage so the ui stays translated offline.
+async fn fetch_catalog(locale: Locale, local_store: &LocalStore) -> Option<Catalog> {
+    let path = format!("/ui/static/i18n/{}.json", locale.as_str());
+    let fetched = match reqwasm::http::Request::get(&path).send().await {
+        Ok(resp) if resp.status() == 200 => resp.text().await.ok(),
+        Ok(resp) => {
+            let status = resp.status();
+            warn!(path, status, "Failed to fetch message catalog");
+            None
+        }
+        Err(err) => {
+            warn!(path, ?err, "Failed to fetch message catalog");
+            None
+        }
+    };
+    let content = match fetched {
+        Some(content) => {
+            if let Err(err) = local_store.set(&catalog_key(locale), &content) {
+                error!(?err, "Failed to cache message catalog");
+            }
+            content
+        }
+        None => local_store.get(&catalog_key(locale)).ok().flatten()?,
+    };
+    parse_catalog(locale, &content)
+}
+
+/// Load the catalog for a locale and use it for messages from now on. Views
+/// look their messages up when they are built so they have to be built again
+/// to pick up the change.
+pub async fn set_locale(locale: Locale, local_store: &LocalStore) {
+    let catalog = match locale {
+        Locale::En => Catalog::new(),
+        _ => fetch_catalog(locale, local_store).await.unwrap_or_default(),
+    };
+    debug!(?locale, messages = catalog.len(), "Loaded message catalog");
+    CURRENT.with(|current| *current.borrow_mut() = (locale, catalog));
+    if let Some(root) = web_sys::window()
+        .and_then(|w| w.document())
+        .and_then(|d| d.document_element())
+    {
+        root.set_attribute("lang", locale.as_str()).unwrap_throw();
+    }
+}
+
+/// The locale messages are currently translated into.
+pub fn current_locale() -> Locale {
+    CURRENT.with(|current| current.borrow().0)
+}
+
+/// The message for `id` in the current locale.
+pub fn t(id: &str) -> String {
+    CURRENT
+        .with(|current| current.borrow().1.get(id).cloned())
+        .or_else(|| ENGLISH.with(|english| english.get(id).cloned()))
+        .unwrap_or_else(|| {
+            warn!(id, "No message for id");
+            id.to_owned()
+        })
+}
+
+/// The message for `id` in the current locale with each `{name}` in it
+/// replaced by the argument with that name.
+pub fn t_args(id: &str, args: &[(&str, &dyn Display)]) -> String {
+    let mut message = t(id);
+    for (name, value) in args {
+        message = message.replace(&format!("{{{}}}", name), &value.to_string());
+    }
+    message
+}
+
+/// The name of a meal in the current locale.
+pub fn meal_label(meal: MealType) -> String {
+    t(&format!("meal.{}", meal.as_str()))
+}
@@ -11,22 +11,43 @@
 // WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
 // See the License for the specific language governing permissions and
 // limitations under the License.
-use js_sys::Date;
-use wasm_bindgen::JsCast;
+use std::{cell::RefCell, rc::Rc};
+
+use js_sys::{Array, Date, Function, Promise, Reflect};
+use tracing::{debug, error};
+use wasm_bindgen::{prelude::Closure, JsCast, JsValue};
 use web_sys::{window, Element, Storage};
 
-pub fn get_storage() -> Storage {
-    window()
-        .expect("No Window Present")
-        .local_storage()
-        .expect("Failed to get storage")
-        .expect("No storage available")
+/// Get the browser's local storage. Returns None if it is disabled or
+/// unavailable.
+pub fn get_storage() -> Option<Storage> {
+    match window()?.local_storage() {
+        Ok(storage) => storage,
+        Err(err) => {
+            error!(?err, "Failed to get local storage");
+            None
+        }
+    }
 }
 
 pub fn get_ms_timestamp() -> u32 {
     Date::new_0().get_milliseconds()
 }
 
+/// Sleep for the given number of milliseconds without blocking the browser.
+pub async fn sleep(ms: i32) {
+    let promise = Promise::new(&mut |resolve, _reject| {
+        let scheduled = window().map_or(false, |w| {
+            w.set_timeout_with_callback_and_timeout_and_arguments_0(&resolve, ms)
+                .is_ok()
+        });
+        if !scheduled {
+            let _ = resolve.call0(&JsValue::NULL);
+        }
+    });
+    let _ = wasm_bindgen_futures::JsFuture::from(promise).await;
+}
+
 pub fn get_element_by_id<E>(id: &str) -> Result<Option<E>, Element>
 where
     E: JsCast,
@@ -41,3 +62,170 @@ where
         None => Ok(None),
     }
 }
+
+/// Get a query parameter from the current location's search string.
+pub fn get_query_param(name: &str) -> Option<String> {
+    let search = window()?.location().search().ok()?;
+    search
+        .trim_start_matches('?')
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(k, _)| *k == name)
+        .and_then(|(_, v)| js_sys::decode_uri_component(v).ok())
+        .map(|v| String::from(v))
+}
+
+fn get_js_method(target: &JsValue, name: &str) -> Option<Function> {
+    Reflect::get(target, &JsValue::from_str(name))
+        .ok()
+        .and_then(|f| f.dyn_into::<Function>().ok())
+}
+
+fn speech_synthesis() -> Option<JsValue> {
+    let synth = Reflect::get(&window()?, &JsValue::from_str("speechSynthesis")).ok()?;
+    if synth.is_undefined() {
+        return None;
+    }
+    Some(synth)
+}
+
+/// Whether the browser can read text aloud.
+pub fn speech_supported() -> bool {
+    speech_synthesis().is_some()
+}
+
+/// Read the text aloud replacing anything that is already being read.
+/// Returns false if the browser can't read text aloud.
+pub fn speak(text: &str) -> bool {
+    let synth = match speech_synthesis() {
+        Some(synth) => synth,
+        None => {
+            debug!("Speech synthesis is not supported");
+            return false;
+        }
+    };
+    let utterance = window()
+        .and_then(|w| Reflect::get(&w, &JsValue::from_str("SpeechSynthesisUtterance")).ok())
+        .and_then(|ctor| ctor.dyn_into::<Function>().ok())
+        .and_then(|ctor| Reflect::construct(&ctor, &Array::of1(&JsValue::from_str(text))).ok());
+    let (utterance, speak) = match (utterance, get_js_method(&synth, "speak")) {
+        (Some(utterance), Some(speak)) => (utterance, speak),
+        _ => {
+            error!("Unable to set up speech synthesis");
+            return false;
+        }
+    };
+    stop_speaking();
+    if let Err(err) = speak.call1(&synth, &utterance) {
+        error!(?err, "Failed to read text aloud");
+        return false;
+    }
+    true
+}
+
+/// Stop reading anything aloud.
+pub fn stop_speaking() {
+    if let Some(synth) = speech_synthesis() {
+        if let Some(cancel) = get_js_method(&synth, "cancel") {
+            let _ = cancel.call0(&synth);
+        }
+    }
+}
+
+fn request_wake_lock(sentinel: &Rc<RefCell<Option<JsValue>>>) -> Option<()> {
+    let navigator = window()?.navigator();
+    let wake_lock = Reflect::get(&navigator, &JsValue::from_str("wakeLock")).ok()?;
+    let request = get_js_method(&wake_lock, "request")?;
+    let promise: Promise = request
+        .call1(&wake_lock, &JsValue::from_str("screen"))
+        .ok()?
+        .dyn_into()
+        .ok()?;
+    let on_lock = {
+        let sentinel = sentinel.clone();
+        Closure::once_into_js(move |lock: JsValue| {
+            debug!("Acquired screen wake lock");
+            *sentinel.borrow_mut() = Some(lock);
+        })
+    };
+    let on_err = Closure::once_into_js(move |err: JsValue| {
+        error!(?err, "Failed to acquire screen wake lock");
+    });
+    // We call then through reflection so the once closures can free
+    // themselves after they run.
+    let then = get_js_method(&promise, "then")?;
+    let _ = then.call2(&promise, &on_lock, &on_err);
+    Some(())
+}
+
+fn page_is_visible() -> bool {
+    window()
+        .and_then(|w| w.document())
+        .and_then(|d| Reflect::get(&d, &JsValue::from_str("visibilityState")).ok())
+        .and_then(|state| state.as_string())
+        .map_or(false, |state| state == "visible")
+}
+
+/// A screen wake lock that keeps the screen on while it is held. The lock is
+/// released when this is dropped.
+pub struct WakeLock {
+    sentinel: Rc<RefCell<Option<JsValue>>>,
+    on_visible: Closure<dyn FnMut()>,
+}
+
+impl WakeLock {
+    /// Whether the browser supports the wake lock api.
+    pub fn is_supported() -> bool {
+        window()
+            .and_then(|w| Reflect::get(&w.navigator(), &JsValue::from_str("wakeLock")).ok())
+            .map_or(false, |wake_lock| !wake_lock.is_undefined())
+    }
+
+    /// Request a screen wake lock. Returns None if the browser doesn't support
+    /// the wake lock api.
+    pub fn request() -> Option<Self> {
+        if !Self::is_supported() {
+            debug!("Wake lock api is not supported");
+            return None;
+        }
+        let sentinel = Rc::new(RefCell::new(None));
+        request_wake_lock(&sentinel)?;
+        // The browser releases the lock whenever the page is hidden so we
+        // take it again when the page comes back.
+        let on_visible = {
+            let sentinel = sentinel.clone();
+            Closure::<dyn FnMut()>::new(move || {
+                if page_is_visible() {
+                    request_wake_lock(&sentinel);
+                }
+            })
+        };
+        if let Some(document) = window().and_then(|w| w.document()) {
+            let _ = document.add_event_listener_with_callback(
+                "visibilitychange",
+                on_visible.as_ref().unchecked_ref(),
+            );
+        }
+        Some(Self {
+            sentinel,
+            on_visible,
+        })
+    }
+}
+
+impl Drop for WakeLock {
+    fn drop(&mut self) {
+        if let Some(document) = window().and_then(|w| w.document()) {
+            let _ = document.remove_event_listener_with_callback(
+                "visibilitychange",
+                self.on_visible.as_ref().unchecked_ref(),
+            );
+        }
+        if let Some(lock) = self.sentinel.borrow_mut().take() {
+            if let Some(release) = get_js_method(&lock, "release") {
+                debug!("Releasing screen wake lock");
+                let _ = release.call0(&lock);
+            }
+        }
+    }
+}
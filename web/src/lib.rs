@@ -11,14 +11,22 @@
 // WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
 // See the License for the specific language governing permissions and
 // limitations under the License.
+mod action_log;
 mod api;
 mod app_state;
+mod cache;
 mod components;
+mod error_report;
+mod i18n;
 mod js_lib;
 mod pages;
 mod routing;
+mod theme;
+mod transport;
 mod web;
 mod linear;
+#[cfg(all(test, target_arch = "wasm32"))]
+mod test;
 
 use sycamore::prelude::*;
 use wasm_bindgen::prelude::wasm_bindgen;
@@ -26,7 +34,7 @@ use wasm_bindgen::prelude::wasm_bindgen;
 use web::UI;
 
 fn configure_tracing() {
-    console_error_panic_hook::set_once();
+    error_report::set_panic_hook();
     use tracing::Level;
     use tracing_subscriber::{filter::LevelFilter, fmt::format::Pretty, prelude::*};
     use tracing_web::{performance_layer, MakeConsoleWriter};
@@ -44,6 +52,7 @@ fn configure_tracing() {
     tracing_subscriber::registry()
         .with(perf_layer)
         .with(fmt_layer)
+        .with(error_report::ReportLayer)
         .init();
 }
 
@@ -15,40 +15,58 @@ use sycamore::futures::spawn_local_scoped;
 use sycamore::prelude::*;
 use tracing::{debug, info};
 
-use crate::app_state::{Message, StateHandler};
+use crate::{
+    app_state::{Message, StateHandler},
+    i18n::t,
+};
 
 #[component]
 pub fn LoginForm<'ctx, G: Html>(cx: Scope<'ctx>, sh: StateHandler<'ctx>) -> View<G> {
     let username = create_signal(cx, "".to_owned());
     let password = create_signal(cx, "".to_owned());
+    let remember = create_signal(cx, false);
     view! {cx,
-        form() {
-            label(for="username") { "Username" }
-            input(type="text", id="username", bind:value=username)
-            label(for="password") { "Password" }
-            input(type="password", bind:value=password)
-            span(role="button", on:click=move |_| {
-                info!("Attempting login request");
-                let (username, password) = ((*username.get_untracked()).clone(), (*password.get_untracked()).clone());
-                if username != "" && password != "" {
-                    spawn_local_scoped(cx, async move {
-                        let store = crate::api::HttpStore::get_from_context(cx);
-                        debug!("authenticating against ui");
-                        if let Some(user_data) = store.authenticate(username, password).await {
-                            sh.dispatch(cx, Message::SetUserData(user_data));
-                            sh.dispatch(cx, Message::LoadState(Some(Box::new(|| sycamore_router::navigate("/ui/planning/plan")))));
-                        }
-                    });
-                }
-                debug!("triggering login click subscribers");
-            }) { "Login" } " "
+        form(on:submit=move |evt: web_sys::Event| {
+            evt.prevent_default();
+            info!("Attempting login request");
+            let (username, password) = ((*username.get_untracked()).clone(), (*password.get_untracked()).clone());
+            let remember = *remember.get_untracked();
+            if username != "" && password != "" {
+                spawn_local_scoped(cx, async move {
+                    let store = crate::api::HttpStore::get_from_context(cx);
+                    debug!("authenticating against ui");
+                    if let Some(user_data) = store.authenticate(username, password, remember).await {
+                        sh.dispatch(cx, Message::SetUserData(user_data));
+                        sh.dispatch(cx, Message::LoadState(Some(Box::new(|| sycamore_router::navigate(super::start_page())))));
+                    }
+                });
+            }
+            debug!("triggering login click subscribers");
+        }) {
+            label(for="username") { (t("login.username")) }
+            input(type="text", id="username", autocomplete="username", bind:value=username)
+            label(for="password") { (t("login.password")) }
+            input(type="password", id="password", autocomplete="current-password", bind:value=password)
+            label(for="remember") {
+                input(type="checkbox", id="remember", bind:checked=remember)
+                (t("login.remember"))
+            }
+            button(type="submit") { (t("login.login")) }
         }
     }
 }
 
 #[component]
 pub fn LoginPage<'ctx, G: Html>(cx: Scope<'ctx>, sh: StateHandler<'ctx>) -> View<G> {
+    let logged_in = sh.get_selector(cx, |state| state.get().auth.is_some());
     view! {cx,
             LoginForm(sh)
+            (if *logged_in.get() {
+                view! {cx,
+                    a(href="/api/v2/account/export", download="") { (t("login.export")) }
+                }
+            } else {
+                View::empty()
+            })
     }
 }
@@ -12,7 +12,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 use super::ManagePage;
-use crate::{app_state::StateHandler, components::add_recipe::AddRecipe};
+use crate::{app_state::StateHandler, components::add_recipe::AddRecipe, i18n::t};
 
 use sycamore::prelude::*;
 
@@ -20,7 +20,7 @@ use sycamore::prelude::*;
 pub fn AddRecipePage<'ctx, G: Html>(cx: Scope<'ctx>, sh: StateHandler<'ctx>) -> View<G> {
     view! {cx,
         ManagePage(
-            selected=Some("New Recipe".to_owned()),
+            selected=Some(t("tab.manage.new_recipe")),
         ) { AddRecipe(sh) }
     }
 }
@@ -12,7 +12,11 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 use super::ManagePage;
-use crate::{app_state::StateHandler, components::categories::*};
+use crate::{
+    app_state::StateHandler,
+    components::{categories::*, densities::*, diet::*, substitutions::*},
+    i18n::t,
+};
 
 use sycamore::prelude::*;
 
@@ -20,7 +24,12 @@ use sycamore::prelude::*;
 pub fn IngredientsPage<'ctx, G: Html>(cx: Scope<'ctx>, sh: StateHandler<'ctx>) -> View<G> {
     view! {cx,
         ManagePage(
-            selected=Some("Ingredients".to_owned()),
-        ) { Categories(sh) }
+            selected=Some(t("tab.manage.ingredients")),
+        ) {
+            Categories(sh)
+            Densities(sh)
+            DietaryTags(sh)
+            Substitutions(sh)
+        }
     }
 }
@@ -11,16 +11,22 @@
 // WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
 // See the License for the specific language governing permissions and
 // limitations under the License.
-use crate::components::tabs::*;
+use crate::{components::tabs::*, i18n::t};
 use sycamore::prelude::*;
 
 pub mod add_recipe;
 pub mod ingredients;
+pub mod recipes;
 pub mod staples;
+pub mod stats;
+pub mod trash;
 
 pub use add_recipe::*;
 pub use ingredients::*;
+pub use recipes::*;
 pub use staples::*;
+pub use stats::*;
+pub use trash::*;
 
 #[derive(Props)]
 pub struct PageState<'a, G: Html> {
@@ -32,10 +38,19 @@ pub struct PageState<'a, G: Html> {
 pub fn ManagePage<'a, G: Html>(cx: Scope<'a>, state: PageState<'a, G>) -> View<G> {
     let PageState { children, selected } = state;
     let children = children.call(cx);
-    let manage_tabs: Vec<(String, &'static str)> = vec![
-        ("/ui/manage/ingredients".to_owned(), "Ingredients"),
-        ("/ui/manage/staples".to_owned(), "Staples"),
-        ("/ui/manage/new_recipe".to_owned(), "New Recipe"),
+    let manage_tabs: Vec<(String, String)> = vec![
+        (
+            "/ui/manage/ingredients".to_owned(),
+            t("tab.manage.ingredients"),
+        ),
+        ("/ui/manage/recipes".to_owned(), t("tab.manage.recipes")),
+        ("/ui/manage/staples".to_owned(), t("tab.manage.staples")),
+        (
+            "/ui/manage/new_recipe".to_owned(),
+            t("tab.manage.new_recipe"),
+        ),
+        ("/ui/manage/trash".to_owned(), t("tab.manage.trash")),
+        ("/ui/manage/stats".to_owned(), t("tab.manage.stats")),
     ];
 
     view! {cx,
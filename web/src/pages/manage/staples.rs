@@ -12,7 +12,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 use super::ManagePage;
-use crate::{app_state::StateHandler, components::staples::IngredientsEditor};
+use crate::{app_state::StateHandler, components::staples::IngredientsEditor, i18n::t};
 
 use sycamore::prelude::*;
 use tracing::instrument;
@@ -22,7 +22,7 @@ use tracing::instrument;
 pub fn StaplesPage<'ctx, G: Html>(cx: Scope<'ctx>, sh: StateHandler<'ctx>) -> View<G> {
     view! {cx,
         ManagePage(
-            selected=Some("Staples".to_owned()),
+            selected=Some(t("tab.manage.staples")),
         ) { IngredientsEditor(sh=sh) }
     }
 }
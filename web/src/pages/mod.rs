@@ -11,12 +11,20 @@
 // WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
 // See the License for the specific language governing permissions and
 // limitations under the License.
+mod debug;
 mod login;
 mod manage;
+mod onboarding;
 mod planning;
 mod recipe;
+mod settings;
+mod shop;
 
+pub use debug::*;
 pub use login::*;
 pub use manage::*;
+pub use onboarding::*;
 pub use planning::*;
 pub use recipe::*;
+pub use settings::*;
+pub use shop::*;
@@ -0,0 +1,124 @@
+// Copyright 2023 Jeremy Wall (Jeremy@marzhilsltudios.com)
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+use sycamore::{futures::spawn_local_scoped, prelude::*};
+use tracing::error;
+
+use crate::{
+    api::{HttpStore, LocalStore},
+    app_state::{Message, StateHandler},
+    components::{defaults_message, toast},
+    i18n::{t, t_args},
+};
+
+/// Set once the user has been through the welcome page on this device.
+const ONBOARDING_DONE_KEY: &'static str = "onboarding_done";
+
+/// Where to send someone who just logged in. Accounts the server says are
+/// empty get the welcome page until it has been finished or skipped.
+pub fn start_page() -> &'static str {
+    let local_store = LocalStore::new();
+    let empty = local_store
+        .get_user_data()
+        .ok()
+        .flatten()
+        .map_or(false, |user_data| user_data.empty);
+    let done = local_store
+        .get(ONBOARDING_DONE_KEY)
+        .ok()
+        .flatten()
+        .is_some();
+    if empty && !done {
+        "/ui/welcome"
+    } else {
+        "/ui/planning/plan"
+    }
+}
+
+fn finish_onboarding() {
+    if let Err(err) = LocalStore::new().set(ONBOARDING_DONE_KEY, "true") {
+        error!(?err, "Failed to remember that onboarding is done");
+    }
+}
+
+#[component]
+pub fn OnboardingPage<'ctx, G: Html>(cx: Scope<'ctx>, sh: StateHandler<'ctx>) -> View<G> {
+    let recipe_count = sh.get_selector(cx, |state| state.get().recipes.len());
+    let mapping_count = sh.get_selector(cx, |state| state.get().category_map.len());
+    let household_size = sh.get_selector(cx, |state| state.get().household_size.unwrap_or(0));
+    let people = create_signal(cx, *household_size.get_untracked() as f64);
+    let working = create_signal(cx, false);
+    view! {cx,
+        h2 { (t("onboarding.title")) }
+        p { (t("onboarding.intro")) }
+        section(aria-labelledby="onboarding_recipes") {
+            h3(id="onboarding_recipes") { (t("onboarding.recipes")) }
+            p { (t_args("onboarding.recipe_count", &[("count", &recipe_count.get())])) }
+            button(type="button", disabled=*working.get(), on:click=move |_| {
+                working.set(true);
+                spawn_local_scoped(cx, async move {
+                    let store = HttpStore::get_from_context(cx);
+                    match store.add_sample_recipes().await {
+                        Ok(count) => {
+                            toast::message(cx, &t_args("onboarding.samples_added", &[("count", &count)]), None);
+                            sh.dispatch(cx, Message::RefreshRecipes);
+                        }
+                        Err(err) => error!(?err, "Failed to add sample recipes"),
+                    }
+                    working.set(false);
+                });
+            }) { (t("onboarding.add_samples")) }
+            a(href="/ui/manage/new_recipe") { (t("onboarding.write_recipe")) }
+        }
+        section(aria-labelledby="onboarding_categories") {
+            h3(id="onboarding_categories") { (t("onboarding.categories")) }
+            p { (t_args("onboarding.mapping_count", &[("count", &mapping_count.get())])) }
+            button(type="button", disabled=*working.get(), on:click=move |_| {
+                working.set(true);
+                spawn_local_scoped(cx, async move {
+                    let store = HttpStore::get_from_context(cx);
+                    match store.apply_defaults().await {
+                        Ok(applied) => {
+                            toast::message(cx, &defaults_message(&applied), None);
+                            sh.dispatch(cx, Message::RefreshCategories);
+                        }
+                        Err(err) => error!(?err, "Failed to apply the default categories"),
+                    }
+                    working.set(false);
+                });
+            }) { (t("onboarding.use_defaults")) }
+            a(href="/ui/manage/ingredients") { (t("onboarding.edit_categories")) }
+        }
+        section(aria-labelledby="onboarding_household") {
+            h3(id="onboarding_household") { (t("onboarding.household")) }
+            label(for="onboarding_household_size") { (t("recipe_plan.household_size")) }
+            input(id="onboarding_household_size", type="number", min="0", step="1", bind:valueAsNumber=people, on:change=move |_| {
+                let size = *people.get_untracked();
+                let size = if size.is_nan() || size < 1.0 {
+                    None
+                } else {
+                    Some(size as u32)
+                };
+                sh.dispatch(cx, Message::UpdateHouseholdSize(size));
+            })
+        }
+        button(type="button", on:click=move |_| {
+            finish_onboarding();
+            sycamore_router::navigate("/ui/planning/select");
+        }) { (t("onboarding.done")) }
+        button(type="button", class="secondary", on:click=move |_| {
+            finish_onboarding();
+            sycamore_router::navigate("/ui/planning/plan");
+        }) { (t("onboarding.skip")) }
+    }
+}
@@ -14,13 +14,20 @@
 use sycamore::prelude::*;
 
 use super::PlanningPage;
-use crate::{app_state::StateHandler, components::recipe_list::*};
+use crate::{
+    app_state::StateHandler,
+    components::{recipe_list::*, KeepScreenOn},
+    i18n::t,
+};
 
 #[component]
 pub fn CookPage<'ctx, G: Html>(cx: Scope<'ctx>, sh: StateHandler<'ctx>) -> View<G> {
     view! {cx,
         PlanningPage(
-            selected=Some("Cook".to_owned()),
-        ) { RecipeList(sh) }
+            selected=Some(t("tab.planning.cook")),
+        ) {
+            KeepScreenOn()
+            RecipeList(sh)
+        }
     }
 }
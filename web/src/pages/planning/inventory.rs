@@ -14,13 +14,23 @@
 use sycamore::prelude::*;
 
 use super::PlanningPage;
-use crate::{app_state::StateHandler, components::shopping_list::*};
+use crate::{
+    api::Operation,
+    app_state::StateHandler,
+    components::{shopping_list::*, DietWarnings, Loading},
+    i18n::t,
+};
 
 #[component]
 pub fn InventoryPage<'ctx, G: Html>(cx: Scope<'ctx>, sh: StateHandler<'ctx>) -> View<G> {
     view! {cx,
         PlanningPage(
-            selected=Some("Inventory".to_owned()),
-        ) { ShoppingList(sh) }
+            selected=Some(t("tab.planning.inventory")),
+        ) {
+            Loading(ops=&[Operation::Recipes, Operation::Plan, Operation::Inventory]) {
+                DietWarnings(sh)
+                ShoppingList(sh)
+            }
+        }
     }
 }
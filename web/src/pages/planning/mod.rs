@@ -11,17 +11,23 @@
 // WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
 // See the License for the specific language governing permissions and
 // limitations under the License.
-use crate::components::tabs::*;
+use crate::{components::tabs::*, i18n::t};
 use sycamore::prelude::*;
 
 pub mod cook;
+pub mod freezer;
+pub mod history;
 pub mod inventory;
 pub mod plan;
+pub mod rules;
 pub mod select;
 
 pub use cook::*;
+pub use freezer::*;
+pub use history::*;
 pub use inventory::*;
 pub use plan::*;
+pub use rules::*;
 pub use select::*;
 
 #[derive(Props)]
@@ -34,11 +40,17 @@ pub struct PageState<'a, G: Html> {
 pub fn PlanningPage<'a, G: Html>(cx: Scope<'a>, state: PageState<'a, G>) -> View<G> {
     let PageState { children, selected } = state;
     let children = children.call(cx);
-    let planning_tabs: Vec<(String, &'static str)> = vec![
-        ("/ui/planning/select".to_owned(), "Select"),
-        ("/ui/planning/plan".to_owned(), "Plan"),
-        ("/ui/planning/inventory".to_owned(), "Inventory"),
-        ("/ui/planning/cook".to_owned(), "Cook"),
+    let planning_tabs: Vec<(String, String)> = vec![
+        ("/ui/planning/select".to_owned(), t("tab.planning.select")),
+        ("/ui/planning/plan".to_owned(), t("tab.planning.plan")),
+        (
+            "/ui/planning/inventory".to_owned(),
+            t("tab.planning.inventory"),
+        ),
+        ("/ui/planning/cook".to_owned(), t("tab.planning.cook")),
+        ("/ui/planning/freezer".to_owned(), t("tab.planning.freezer")),
+        ("/ui/planning/history".to_owned(), t("tab.planning.history")),
+        ("/ui/planning/rules".to_owned(), t("tab.planning.rules")),
     ];
 
     view! {cx,
@@ -12,7 +12,12 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 use super::PlanningPage;
-use crate::{app_state::StateHandler, components::recipe_plan::*};
+use crate::{
+    api::Operation,
+    app_state::StateHandler,
+    components::{recipe_plan::*, DietWarnings, Loading, PlanRuleCheck, PlanSummary},
+    i18n::t,
+};
 
 use sycamore::prelude::*;
 
@@ -20,7 +25,14 @@ use sycamore::prelude::*;
 pub fn PlanPage<'ctx, G: Html>(cx: Scope<'ctx>, sh: StateHandler<'ctx>) -> View<G> {
     view! {cx,
         PlanningPage(
-            selected=Some("Plan".to_owned()),
-        ) { RecipePlan(sh) }
+            selected=Some(t("tab.planning.plan")),
+        ) {
+            Loading(ops=&[Operation::Recipes, Operation::Plan]) {
+                PlanSummary(sh)
+                RecipePlan(sh)
+                DietWarnings(sh)
+                PlanRuleCheck(sh)
+            }
+        }
     }
 }
@@ -15,34 +15,37 @@ use super::PlanningPage;
 use crate::{
     app_state::{Message, StateHandler},
     components::PlanList,
+    i18n::t,
 };
 
-use chrono::NaiveDate;
+use client_api::WeekStart;
 use sycamore::prelude::*;
 
 #[component]
 pub fn SelectPage<'ctx, G: Html>(cx: Scope<'ctx>, sh: StateHandler<'ctx>) -> View<G> {
-    let plan_dates = sh.get_selector(cx, |state| {
-        let mut plans = state
-            .get()
-            .plan_dates
-            .iter()
-            .cloned()
-            .collect::<Vec<NaiveDate>>();
-        plans.sort_unstable_by(|d1, d2| d2.cmp(d1));
-        plans
-    });
+    let plan_weeks = sh.get_selector(cx, |state| state.get().plan_weeks());
+    let current_week_start = sh.get_selector(cx, |state| state.get().week_start);
+    let week_start = create_signal(cx, current_week_start.get_untracked().as_str().to_owned());
     view! {cx,
         PlanningPage(
-            selected=Some("Select".to_owned()),
+            selected=Some(t("tab.planning.select")),
         ) {
-            PlanList(sh=sh, list=plan_dates)
-            span(role="button", on:click=move |_| {
+            label(for="week_start") { (t("select.week_start")) }
+            select(id="week_start", bind:value=week_start, on:change=move |_| {
+                if let Ok(value) = week_start.get_untracked().parse::<WeekStart>() {
+                    sh.dispatch(cx, Message::SetWeekStart(value));
+                }
+            }) {
+                option(value="sunday") { (t("select.sunday")) }
+                option(value="monday") { (t("select.monday")) }
+            }
+            PlanList(sh=sh, weeks=plan_weeks)
+            button(type="button", on:click=move |_| {
                 sh.dispatch(cx, Message::SelectPlanDate(chrono::offset::Local::now().naive_local().date(), Some(Box::new(|| {
                     sycamore_router::navigate("/ui/planning/plan");
                 }))))
             }) {
-                "Start Plan for Today"
+                (t("select.start_today"))
             }
         }
     }
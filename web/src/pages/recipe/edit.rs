@@ -12,7 +12,9 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 use super::{RecipePage, RecipePageProps};
-use crate::components::recipe::Editor;
+use crate::api::Operation;
+use crate::components::{recipe::Editor, Loading};
+use crate::i18n::t;
 
 use sycamore::prelude::*;
 use tracing::instrument;
@@ -23,8 +25,10 @@ pub fn RecipeEditPage<'ctx, G: Html>(cx: Scope<'ctx>, props: RecipePageProps<'ct
     let RecipePageProps { recipe, sh } = props;
     view! {cx,
         RecipePage(
-            selected=Some("Edit".to_owned()),
+            selected=Some(t("tab.recipe.edit")),
             recipe=recipe.clone(),
-        ) { Editor(recipe_id=recipe, sh=sh) }
+        ) {
+            Loading(ops=&[Operation::Recipes]) { Editor(recipe_id=recipe, sh=sh) }
+        }
     }
 }
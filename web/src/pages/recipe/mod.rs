@@ -13,11 +13,13 @@
 // limitations under the License.
 use sycamore::prelude::*;
 
-use crate::{app_state::StateHandler, components::tabs::*};
+use crate::{app_state::StateHandler, components::tabs::*, i18n::t};
 
 mod edit;
+mod print;
 mod view;
 pub use edit::*;
+pub use print::*;
 pub use view::*;
 
 #[derive(Props)]
@@ -41,9 +43,13 @@ pub fn RecipePage<'ctx, G: Html>(cx: Scope<'ctx>, state: PageState<'ctx, G>) ->
         recipe,
     } = state;
     let children = children.call(cx);
-    let recipe_tabs: Vec<(String, &'static str)> = vec![
-        (format!("/ui/recipe/view/{}", recipe), "View"),
-        (format!("/ui/recipe/edit/{}", recipe), "Edit"),
+    let recipe_tabs: Vec<(String, String)> = vec![
+        (format!("/ui/recipe/view/{}", recipe), t("tab.recipe.view")),
+        (format!("/ui/recipe/edit/{}", recipe), t("tab.recipe.edit")),
+        (
+            format!("/ui/recipe/print/{}", recipe),
+            t("tab.recipe.print"),
+        ),
     ];
     view! {cx,
         TabbedView(
@@ -0,0 +1,57 @@
+// Copyright 2023 Jeremy Wall (Jeremy@marzhilsltudios.com)
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+use crate::{components::recipe::Printable, i18n::t, js_lib};
+
+use recipes::unit::Quantity;
+use sycamore::prelude::*;
+use tracing::{debug, instrument};
+
+use super::RecipePageProps;
+
+fn parse_scale(scale: &str) -> Quantity {
+    match recipes::parse::as_quantity(scale) {
+        Ok(scale) => scale,
+        Err(err) => {
+            debug!(?err, "Invalid scale for printed recipe");
+            Quantity::whole(1)
+        }
+    }
+}
+
+#[instrument(skip_all, fields(recipe=props.recipe))]
+#[component()]
+pub fn RecipePrintPage<'ctx, G: Html>(cx: Scope<'ctx>, props: RecipePageProps<'ctx>) -> View<G> {
+    let RecipePageProps { recipe, sh } = props;
+    // The initial scale comes from the `scale` query parameter and can be a
+    // whole number or a fraction like `1/2`.
+    let scale_text = create_signal(
+        cx,
+        js_lib::get_query_param("scale").unwrap_or_else(|| "1".to_owned()),
+    );
+    let scale = create_memo(cx, move || parse_scale(scale_text.get().as_str()));
+    let back = format!("/ui/recipe/view/{}", recipe);
+    view! {cx,
+        div(class="no-print") {
+            a(href=back) { (t("print.back")) } " "
+            label(for="print_scale") { (t("print.scale")) }
+            input(id="print_scale", type="text", bind:value=scale_text)
+            button(type="button", on:click=|_| {
+                if let Some(window) = web_sys::window() {
+                    let _ = window.print();
+                }
+            }) { (t("print.print")) }
+        }
+        Printable(recipe_id=recipe, scale=scale, sh=sh)
+    }
+}
@@ -11,7 +11,10 @@
 // WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
 // See the License for the specific language governing permissions and
 // limitations under the License.
-use crate::components::recipe::Viewer;
+use crate::api::Operation;
+use crate::app_state::Message;
+use crate::components::{recipe::Viewer, Loading};
+use crate::i18n::t;
 
 use sycamore::prelude::*;
 use tracing::instrument;
@@ -22,10 +25,27 @@ use super::{RecipePage, RecipePageProps};
 #[component()]
 pub fn RecipeViewPage<'ctx, G: Html>(cx: Scope<'ctx>, props: RecipePageProps<'ctx>) -> View<G> {
     let RecipePageProps { recipe, sh } = props;
+    let archived = sh.get_selector(cx, {
+        let recipe = recipe.clone();
+        move |state| state.get().archived.contains(&recipe)
+    });
+    let recipe_id = recipe.clone();
     view! {cx,
         RecipePage(
-            selected=Some("View".to_owned()),
+            selected=Some(t("tab.recipe.view")),
             recipe=recipe.clone(),
-        ) { Viewer(recipe_id=recipe, sh=sh) }
+        ) {
+            Loading(ops=&[Operation::Recipes]) { Viewer(recipe_id=recipe, sh=sh, planned=false) }
+            div(class="no-print") {
+                (if *archived.get() {
+                    view! {cx, p { (t("recipe.archived_note")) } }
+                } else {
+                    View::empty()
+                })
+                button(type="button", class="outline", on:click=move |_| {
+                    sh.dispatch(cx, Message::SetArchived(recipe_id.clone(), !*archived.get_untracked()));
+                }) { (if *archived.get() { t("recipe.unarchive") } else { t("recipe.archive") }) }
+            }
+        }
     }
 }
@@ -0,0 +1,157 @@
+// Copyright 2023 Jeremy Wall (Jeremy@marzhilsltudios.com)
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+use client_api::WeekStart;
+use sycamore::prelude::*;
+
+use crate::{
+    app_state::{Message, StateHandler},
+    components::{LocaleSelect, TemperatureSelect},
+    i18n::t,
+    theme::Theme,
+};
+
+#[component]
+pub fn SettingsPage<'ctx, G: Html>(cx: Scope<'ctx>, sh: StateHandler<'ctx>) -> View<G> {
+    let logged_in = sh.get_selector(cx, |state| state.get().auth.is_some());
+    let use_weights = sh.get_selector(cx, |state| state.get().use_weights);
+    let timezone_from_device = sh.get_selector(cx, |state| state.get().timezone_from_device);
+    let current_timezone = sh.get_selector(cx, |state| {
+        state
+            .get()
+            .timezone
+            .clone()
+            .unwrap_or_else(|| client_api::format_utc_offset(chrono::Local::now().offset()))
+    });
+    let timezone = create_signal(cx, current_timezone.get_untracked().as_ref().clone());
+    create_effect(cx, || timezone.set(current_timezone.get().as_ref().clone()));
+    let timezone_invalid = create_signal(cx, false);
+    let current_week_start = sh.get_selector(cx, |state| state.get().week_start);
+    let week_start = create_signal(cx, current_week_start.get_untracked().as_str().to_owned());
+    create_effect(cx, || {
+        week_start.set(current_week_start.get().as_str().to_owned())
+    });
+    let current_theme = sh.get_selector(cx, |state| state.get().theme);
+    let theme = create_signal(cx, current_theme.get_untracked().as_str().to_owned());
+    create_effect(cx, || theme.set(current_theme.get().as_str().to_owned()));
+    let household_size = sh.get_selector(cx, |state| state.get().household_size.unwrap_or(0));
+    let people = create_signal(cx, *household_size.get_untracked() as f64);
+    let retention = sh.get_selector(cx, |state| state.get().retention_months.unwrap_or(0));
+    let months = create_signal(cx, *retention.get_untracked() as f64);
+    create_effect(cx, || months.set(*retention.get() as f64));
+    view! {cx,
+        h2 { (t("settings.title")) }
+        (if *logged_in.get() {
+            View::empty()
+        } else {
+            view! {cx, p { (t("settings.local_only")) } }
+        })
+        fieldset {
+            legend { (t("settings.units")) }
+            TemperatureSelect(sh)
+            label(for="settings_weights") {
+                input(id="settings_weights", type="checkbox", checked=*use_weights.get(), on:change=move |_| {
+                    sh.dispatch(cx, Message::UpdateUseWeights(!*use_weights.get_untracked()));
+                })
+                (t("recipe.use_weights"))
+            }
+        }
+        fieldset {
+            legend { (t("settings.timezone")) }
+            label(for="settings_timezone_device") {
+                input(id="settings_timezone_device", type="checkbox", checked=*timezone_from_device.get(), on:change=move |_| {
+                    if *timezone_from_device.get_untracked() {
+                        let offset = client_api::parse_utc_offset(&timezone.get_untracked());
+                        sh.dispatch(cx, Message::SetTimezone(Some(offset.unwrap_or(*chrono::Local::now().offset()))));
+                    } else {
+                        timezone_invalid.set(false);
+                        sh.dispatch(cx, Message::SetTimezone(None));
+                    }
+                })
+                (t("settings.timezone_from_device"))
+            }
+            label(for="settings_timezone") { (t("settings.timezone_offset")) }
+            input(id="settings_timezone", type="text", bind:value=timezone, disabled=*timezone_from_device.get(),
+                aria-invalid=if *timezone_invalid.get() { "true" } else { "false" },
+                aria-describedby="settings_timezone_hint", on:change=move |_| {
+                match client_api::parse_utc_offset(&timezone.get_untracked()) {
+                    Some(offset) => {
+                        timezone_invalid.set(false);
+                        sh.dispatch(cx, Message::SetTimezone(Some(offset)));
+                    }
+                    None => timezone_invalid.set(true),
+                }
+            })
+            small(id="settings_timezone_hint") {
+                (if *timezone_invalid.get() { t("settings.invalid_timezone") } else { t("settings.timezone_hint") })
+            }
+            label(for="settings_week_start") { (t("select.week_start")) }
+            select(id="settings_week_start", bind:value=week_start, on:change=move |_| {
+                if let Ok(value) = week_start.get_untracked().parse::<WeekStart>() {
+                    sh.dispatch(cx, Message::SetWeekStart(value));
+                }
+            }) {
+                option(value="sunday") { (t("select.sunday")) }
+                option(value="monday") { (t("select.monday")) }
+            }
+        }
+        fieldset {
+            legend { (t("settings.appearance")) }
+            label(for="settings_theme") { (t("settings.theme")) }
+            select(id="settings_theme", bind:value=theme, on:change=move |_| {
+                if let Ok(value) = theme.get_untracked().parse::<Theme>() {
+                    sh.dispatch(cx, Message::SetTheme(value));
+                }
+            }) {
+                option(value="system") { (t("header.theme_auto")) }
+                option(value="light") { (t("header.theme_light")) }
+                option(value="dark") { (t("header.theme_dark")) }
+            }
+            LocaleSelect(sh)
+        }
+        fieldset {
+            legend { (t("settings.household")) }
+            label(for="settings_household_size") { (t("recipe_plan.household_size")) }
+            input(id="settings_household_size", type="number", min="0", step="1", bind:valueAsNumber=people, on:change=move |_| {
+                let size = *people.get_untracked();
+                let size = if size.is_nan() || size < 1.0 {
+                    None
+                } else {
+                    Some(size as u32)
+                };
+                sh.dispatch(cx, Message::UpdateHouseholdSize(size));
+            })
+        }
+        (if *logged_in.get() {
+            view! {cx,
+                fieldset {
+                    legend { (t("settings.history")) }
+                    label(for="settings_retention") { (t("settings.retention_months")) }
+                    input(id="settings_retention", type="number", min="0", step="1", bind:valueAsNumber=months,
+                        aria-describedby="settings_retention_hint", on:change=move |_| {
+                        let value = *months.get_untracked();
+                        let value = if value.is_nan() || value < 1.0 {
+                            None
+                        } else {
+                            Some(value as u32)
+                        };
+                        sh.dispatch(cx, Message::UpdateRetentionMonths(value));
+                    })
+                    small(id="settings_retention_hint") { (t("settings.retention_hint")) }
+                }
+            }
+        } else {
+            View::empty()
+        })
+    }
+}
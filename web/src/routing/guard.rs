@@ -0,0 +1,153 @@
+// Copyright 2023 Jeremy Wall (Jeremy@marzhilsltudios.com)
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//! A navigation guard that warns before leaving a page with unsaved changes.
+//! Editors register their dirty state with `track_unsaved_changes` and the
+//! guard asks for confirmation before following a link away from them.
+use std::{cell::RefCell, collections::BTreeSet};
+
+use sycamore::prelude::*;
+use tracing::debug;
+use wasm_bindgen::{prelude::Closure, JsCast, JsValue, UnwrapThrowExt};
+use web_sys::{Element, Event};
+
+use crate::components::confirm::Confirmer;
+use crate::i18n::t;
+
+const CONFIRM_MESSAGE: &'static str = "You have unsaved changes. Leave this page anyway?";
+
+thread_local! {
+    static UNSAVED: RefCell<BTreeSet<&'static str>> = RefCell::new(BTreeSet::new());
+}
+
+fn set_unsaved(source: &'static str, unsaved: bool) {
+    UNSAVED.with(|sources| {
+        let mut sources = sources.borrow_mut();
+        if unsaved {
+            sources.insert(source);
+        } else {
+            sources.remove(source);
+        }
+    });
+}
+
+/// Returns true if any editor has unsaved changes.
+pub fn has_unsaved_changes() -> bool {
+    UNSAVED.with(|sources| !sources.borrow().is_empty())
+}
+
+/// Ask the user to confirm leaving the page if there are unsaved changes.
+/// Returns true if navigation should continue.
+pub async fn confirm_navigation(confirmer: &Confirmer) -> bool {
+    if !has_unsaved_changes() {
+        return true;
+    }
+    let confirmed = confirmer
+        .ask(&t("guard.unsaved_changes"), &t("guard.leave"))
+        .await;
+    if confirmed {
+        // The user chose to abandon their changes.
+        UNSAVED.with(|sources| sources.borrow_mut().clear());
+    }
+    confirmed
+}
+
+/// Track an editor's dirty signal under the given source name. The source is
+/// cleared when the editor's scope is disposed.
+pub fn track_unsaved_changes<'ctx>(
+    cx: Scope<'ctx>,
+    source: &'static str,
+    dirty: &'ctx ReadSignal<bool>,
+) {
+    create_effect(cx, move || set_unsaved(source, *dirty.get()));
+    on_cleanup(cx, move || set_unsaved(source, false));
+}
+
+fn link_href(evt: &Event) -> Option<String> {
+    let target: Element = evt.target()?.dyn_into().ok()?;
+    let link = target.closest("a[href]").ok()??;
+    link.get_attribute("href")
+}
+
+/// Follow a link the guard stopped. Links into the app go through the
+/// router.
+fn follow_link(href: &str) {
+    if href.starts_with("/ui") {
+        sycamore_router::navigate(href);
+    } else if let Some(window) = web_sys::window() {
+        let _ = window.location().set_href(href);
+    }
+}
+
+/// Install the window listeners for the navigation guard. Link clicks are
+/// caught in the capture phase so they can be stopped before the router sees
+/// them.
+pub fn register_navigation_guard<'ctx>(cx: Scope<'ctx>) {
+    let window = web_sys::window().expect("No window present");
+    let confirmer = Confirmer::get_from_context(cx);
+    let click_listener = Closure::<dyn Fn(Event)>::new(move |evt: Event| {
+        let href = match link_href(&evt) {
+            Some(href) => href,
+            None => return,
+        };
+        if href.starts_with('#') || !has_unsaved_changes() {
+            return;
+        }
+        // The answer comes after the click is over so we stop it now and
+        // follow the link ourselves if they choose to leave.
+        evt.prevent_default();
+        evt.stop_propagation();
+        let confirmer = confirmer.clone();
+        wasm_bindgen_futures::spawn_local(async move {
+            if confirm_navigation(&confirmer).await {
+                follow_link(&href);
+            } else {
+                debug!(%href, "Blocked navigation with unsaved changes");
+            }
+        });
+    });
+    let unload_listener = Closure::<dyn Fn(Event)>::new(move |evt: Event| {
+        if has_unsaved_changes() {
+            evt.prevent_default();
+            // Some browsers only prompt if returnValue is set.
+            let _ = js_sys::Reflect::set(
+                &evt,
+                &JsValue::from_str("returnValue"),
+                &JsValue::from_str(CONFIRM_MESSAGE),
+            );
+        }
+    });
+    window
+        .add_event_listener_with_callback_and_bool(
+            "click",
+            click_listener.as_ref().unchecked_ref(),
+            true,
+        )
+        .unwrap_throw();
+    window
+        .add_event_listener_with_callback("beforeunload", unload_listener.as_ref().unchecked_ref())
+        .unwrap_throw();
+    on_cleanup(cx, move || {
+        if let Some(window) = web_sys::window() {
+            let _ = window.remove_event_listener_with_callback_and_bool(
+                "click",
+                click_listener.as_ref().unchecked_ref(),
+                true,
+            );
+            let _ = window.remove_event_listener_with_callback(
+                "beforeunload",
+                unload_listener.as_ref().unchecked_ref(),
+            );
+        }
+    });
+}
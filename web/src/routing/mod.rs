@@ -14,13 +14,19 @@
 
 use crate::{
     app_state::StateHandler,
-    components::{toast::Container, Footer, Header},
+    components::{
+        confirm::ConfirmDialog, toast::Container, DebugPanel, Footer, Header, KeyboardShortcuts,
+        Reconnecting, CONTENT_ID,
+    },
     pages::*,
 };
 use sycamore::prelude::*;
 use sycamore_router::{HistoryIntegration, Route, Router};
 use tracing::{debug, instrument};
 
+mod guard;
+pub use guard::*;
+
 #[derive(Route, Debug)]
 pub enum Routes {
     #[to("/ui/planning/<_..>")]
@@ -31,6 +37,14 @@ pub enum Routes {
     Manage(ManageRoutes),
     #[to("/ui/login")]
     Login,
+    #[to("/ui/shop")]
+    Shop,
+    #[to("/ui/settings")]
+    Settings,
+    #[to("/ui/welcome")]
+    Welcome,
+    #[to("/ui/debug")]
+    Debug,
     #[not_found]
     NotFound,
 }
@@ -41,6 +55,8 @@ pub enum RecipeRoutes {
     Edit(String),
     #[to("/view/<id>")]
     View(String),
+    #[to("/print/<id>")]
+    Print(String),
     #[not_found]
     NotFound,
 }
@@ -54,8 +70,14 @@ pub enum ManageRoutes {
     Categories,
     #[to("/ingredients")]
     Ingredients,
+    #[to("/recipes")]
+    Recipes,
     #[to("/staples")]
     Staples,
+    #[to("/trash")]
+    Trash,
+    #[to("/stats")]
+    Stats,
     #[not_found]
     NotFound,
 }
@@ -70,6 +92,12 @@ pub enum PlanningRoutes {
     Inventory,
     #[to("/cook")]
     Cook,
+    #[to("/freezer")]
+    Freezer,
+    #[to("/history")]
+    History,
+    #[to("/rules")]
+    Rules,
     #[not_found]
     NotFound,
 }
@@ -97,15 +125,39 @@ fn route_switch<'ctx, G: Html>(route: &Routes, cx: Scope<'ctx>, sh: StateHandler
         Routes::Planning(Cook) => view! {cx,
             CookPage(sh)
         },
+        Routes::Planning(Freezer) => view! {cx,
+            FreezerPage(sh)
+        },
+        Routes::Planning(History) => view! {cx,
+            PlanHistoryPage(sh)
+        },
+        Routes::Planning(Rules) => view! {cx,
+            PlanRulesPage()
+        },
         Routes::Login => view! {cx,
             LoginPage(sh)
         },
+        Routes::Shop => view! {cx,
+            ShopPage(sh)
+        },
+        Routes::Settings => view! {cx,
+            SettingsPage(sh)
+        },
+        Routes::Welcome => view! {cx,
+            OnboardingPage(sh)
+        },
+        Routes::Debug => view! {cx,
+            DebugPage(sh)
+        },
         Routes::Recipe(RecipeRoutes::View(id)) => view! {cx,
             RecipeViewPage(recipe=id.clone(), sh=sh)
         },
         Routes::Recipe(RecipeRoutes::Edit(id)) => view! {cx,
             RecipeEditPage(recipe=id.clone(), sh=sh)
         },
+        Routes::Recipe(RecipeRoutes::Print(id)) => view! {cx,
+            RecipePrintPage(recipe=id.clone(), sh=sh)
+        },
         Routes::Manage(Categories) => view! {cx,
             IngredientsPage(sh)
         },
@@ -115,9 +167,18 @@ fn route_switch<'ctx, G: Html>(route: &Routes, cx: Scope<'ctx>, sh: StateHandler
         Routes::Manage(NewRecipe) => view! {cx,
             AddRecipePage(sh)
         },
+        Routes::Manage(Recipes) => view! {cx,
+            ManageRecipesPage(sh)
+        },
         Routes::Manage(Staples) => view! {cx,
             StaplesPage(sh)
         },
+        Routes::Manage(Trash) => view! {cx,
+            TrashPage(sh)
+        },
+        Routes::Manage(Stats) => view! {cx,
+            StatsPage()
+        },
         Routes::NotFound
         | Routes::Manage(ManageRoutes::NotFound)
         | Routes::Planning(PlanningRoutes::NotFound)
@@ -131,16 +192,27 @@ fn route_switch<'ctx, G: Html>(route: &Routes, cx: Scope<'ctx>, sh: StateHandler
 #[component]
 pub fn Handler<'ctx, G: Html>(cx: Scope<'ctx>, props: HandlerProps<'ctx>) -> View<G> {
     let HandlerProps { sh } = props;
+    register_navigation_guard(cx);
     view! {cx,
         Router(
             integration=HistoryIntegration::new(),
             view=move |cx: Scope, route: &ReadSignal<Routes>| {
+                // The print view skips the navigation chrome so that only
+                // the recipe ends up on paper.
+                let chrome = create_selector(cx, move || {
+                    !matches!(route.get().as_ref(), Routes::Recipe(RecipeRoutes::Print(_)))
+                });
                 view!{cx,
                     div(class="app") {
                         Container()
-                        Header(sh)
-                        (route_switch(route.get().as_ref(), cx, sh))
-                        Footer { }
+                        ConfirmDialog()
+                        Reconnecting()
+                        (if *chrome.get() { view! {cx, Header(sh) KeyboardShortcuts(sh) } } else { View::empty() })
+                        div(id=CONTENT_ID, tabindex="-1") {
+                            (route_switch(route.get().as_ref(), cx, sh))
+                        }
+                        (if *chrome.get() { view! {cx, Footer { } } } else { View::empty() })
+                        (if cfg!(feature = "debug_panel") { view! {cx, DebugPanel(sh) } } else { View::empty() })
                     }
                 }
             },
@@ -0,0 +1,377 @@
+// Copyright 2023 Jeremy Wall (Jeremy@marzhilsltudios.com)
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//! Component tests. These run in a headless browser with `make wasm-test`
+//! and answer the requests the components make with a [MockTransport].
+use std::{cell::RefCell, collections::BTreeMap, rc::Rc};
+
+use async_trait::async_trait;
+use chrono::NaiveDate;
+use client_api::{Response, SavedRecipe};
+use recipes::RecipeEntry;
+use serde::Serialize;
+use sycamore::prelude::*;
+use sycamore_router::Route;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_test::*;
+use web_sys::{
+    Element, Event, HtmlDialogElement, HtmlElement, HtmlInputElement, HtmlTextAreaElement,
+};
+
+use crate::{
+    api::HttpStore,
+    app_state::{self, parse_recipe, AppState, StateHandler},
+    components::{
+        confirm::{ConfirmDialog, Confirmer},
+        recipe::Editor,
+        toast::Notifier,
+        ShoppingList,
+    },
+    i18n::t,
+    js_lib,
+    routing::{self, ManageRoutes, PlanningRoutes, RecipeRoutes, Routes},
+    transport::{self, Method, Transport},
+};
+
+wasm_bindgen_test_configure!(run_in_browser);
+
+const PANCAKES: &'static str = "title: Pancakes
+
+step:
+
+1 cup flour
+1 cup milk
+2 egg
+
+Whisk everything together and fry in a hot pan.
+";
+
+const OMELET: &'static str = "title: Omelet
+
+step:
+
+3 egg
+1 tbsp butter
+
+Beat the eggs and cook them in the butter.
+";
+
+/// Answers requests with canned responses and remembers them so tests can
+/// check what was sent. Requests without a response get a 404.
+#[derive(Debug, Default)]
+struct MockTransport {
+    responses: RefCell<BTreeMap<(Method, String), (u16, String)>>,
+    requests: RefCell<Vec<transport::Request>>,
+}
+
+impl MockTransport {
+    fn respond<T: Serialize>(&self, method: Method, path: &str, status: u16, body: &T) {
+        let body = serde_json::to_string(body).expect("Unable to serialize response");
+        self.responses
+            .borrow_mut()
+            .insert((method, path.to_owned()), (status, body));
+    }
+
+    /// The requests sent to a path.
+    fn sent(&self, method: Method, path: &str) -> Vec<transport::Request> {
+        self.requests
+            .borrow()
+            .iter()
+            .filter(|r| r.method() == method && r.url() == path)
+            .cloned()
+            .collect()
+    }
+}
+
+#[async_trait(?Send)]
+impl Transport for MockTransport {
+    async fn send(
+        &self,
+        request: transport::Request,
+    ) -> Result<transport::Response, transport::Error> {
+        let key = (request.method(), request.url().to_owned());
+        let resp = match self.responses.borrow().get(&key) {
+            Some((status, body)) => transport::Response::new(*status, request.url(), body.as_str()),
+            None => transport::Response::new(404, request.url(), ""),
+        };
+        self.requests.borrow_mut().push(request);
+        Ok(resp)
+    }
+}
+
+/// Render a view in a new element on the page with the same context the
+/// app provides.
+fn mount<F>(transport: Rc<MockTransport>, state: AppState, f: F) -> Element
+where
+    F: for<'ctx> FnOnce(Scope<'ctx>, StateHandler<'ctx>) -> View<DomNode> + 'static,
+{
+    let document = web_sys::window()
+        .and_then(|w| w.document())
+        .expect("No document in window");
+    let container = document
+        .create_element("div")
+        .expect("Unable to create element");
+    document
+        .body()
+        .expect("No body in document")
+        .append_child(&container)
+        .expect("Unable to add element");
+    sycamore::render_to(
+        move |cx| {
+            Notifier::provide_context(cx);
+            Confirmer::provide_context(cx);
+            let store = HttpStore::with_transport(
+                "/api".to_owned(),
+                Notifier::get_from_context(cx),
+                transport,
+            );
+            provide_context(cx, Rc::new(store.clone()));
+            let sh = app_state::get_state_handler(cx, state, store);
+            let view = f(cx, sh);
+            view! {cx,
+                (view)
+                ConfirmDialog()
+            }
+        },
+        &container,
+    );
+    container
+}
+
+/// Give spawned futures and the local cache a chance to finish.
+async fn settle() {
+    js_lib::sleep(100).await;
+}
+
+fn find<E: JsCast>(parent: &Element, selector: &str) -> E {
+    parent
+        .query_selector(selector)
+        .ok()
+        .flatten()
+        .and_then(|e| e.dyn_into::<E>().ok())
+        .unwrap_or_else(|| panic!("Nothing matched {}", selector))
+}
+
+fn find_all(parent: &Element, selector: &str) -> Vec<Element> {
+    let nodes = parent
+        .query_selector_all(selector)
+        .expect("Invalid selector");
+    (0..nodes.length())
+        .filter_map(|i| nodes.item(i))
+        .filter_map(|n| n.dyn_into::<Element>().ok())
+        .collect()
+}
+
+fn button(parent: &Element, label: &str) -> HtmlElement {
+    find_all(parent, "button")
+        .into_iter()
+        .find(|b| b.text_content().as_deref().map(str::trim) == Some(label))
+        .and_then(|b| b.dyn_into::<HtmlElement>().ok())
+        .unwrap_or_else(|| panic!("No {} button", label))
+}
+
+fn fire(target: &Element, event: &str) {
+    let event = Event::new(event).expect("Unable to create event");
+    target
+        .dispatch_event(&event)
+        .expect("Unable to dispatch event");
+}
+
+fn planned_state(date: Option<NaiveDate>) -> AppState {
+    let mut state = AppState::new();
+    for (id, text) in [("pancakes", PANCAKES), ("omelet", OMELET)] {
+        let recipe = parse_recipe(text).expect("Test recipe doesn't parse");
+        state.recipes.insert(id.to_owned(), recipe);
+        state.recipe_counts.insert(id.to_owned(), 1);
+    }
+    state.selected_plan_date = date;
+    state
+}
+
+/// The name and amount of each row in the shopping list.
+fn shopping_rows(container: &Element) -> Vec<(String, String)> {
+    find_all(container, "table.shopping-list tbody tr")
+        .iter()
+        .map(|row| {
+            let amt = find::<HtmlInputElement>(row, "input[type=text]").value();
+            // The ingredient cell also has the category.
+            let cell = find_all(row, "td")[2].text_content().unwrap_or_default();
+            let name = cell.split_whitespace().next().unwrap_or_default();
+            (name.to_owned(), amt)
+        })
+        .collect()
+}
+
+#[wasm_bindgen_test]
+fn test_routes_match_paths() {
+    assert!(matches!(
+        Routes::NotFound.match_path("/ui/recipe/edit/pancakes"),
+        Routes::Recipe(RecipeRoutes::Edit(id)) if id == "pancakes"
+    ));
+    assert!(matches!(
+        Routes::NotFound.match_path("/ui/planning/inventory"),
+        Routes::Planning(PlanningRoutes::Inventory)
+    ));
+    assert!(matches!(
+        Routes::NotFound.match_path("/ui/manage/trash"),
+        Routes::Manage(ManageRoutes::Trash)
+    ));
+    assert!(matches!(
+        Routes::NotFound.match_path("/ui/planning/nowhere"),
+        Routes::Planning(PlanningRoutes::NotFound)
+    ));
+    assert!(matches!(
+        Routes::NotFound.match_path("/elsewhere"),
+        Routes::NotFound
+    ));
+}
+
+#[wasm_bindgen_test]
+async fn test_editor_saves_changes() {
+    let transport = Rc::new(MockTransport::default());
+    transport.respond(
+        Method::Get,
+        "/api/v2/recipe/pancakes",
+        200,
+        &Response::Success(Some(RecipeEntry::new("pancakes", PANCAKES))),
+    );
+    transport.respond(
+        Method::Post,
+        "/api/v2/recipes",
+        200,
+        &Response::Success(vec![SavedRecipe {
+            recipe_id: "pancakes".to_owned(),
+            revision: 2,
+            renamed_from: None,
+        }]),
+    );
+    let container = mount(transport.clone(), AppState::new(), |cx, sh| {
+        view! {cx, Editor(recipe_id="pancakes".to_owned(), sh=sh) }
+    });
+    settle().await;
+
+    let text = find::<HtmlTextAreaElement>(&container, "#recipe_text");
+    assert_eq!(text.value(), PANCAKES);
+    let changed = PANCAKES.replace("2 egg", "3 egg");
+    text.set_value(&changed);
+    fire(&text, "input");
+    fire(&text, "change");
+    assert!(routing::has_unsaved_changes());
+
+    button(&container, &t("recipe.save")).click();
+    settle().await;
+
+    let sent = transport.sent(Method::Post, "/api/v2/recipes");
+    assert_eq!(sent.len(), 1);
+    let saved: Vec<RecipeEntry> =
+        serde_json::from_str(sent[0].text().expect("No body was sent")).expect("Invalid body");
+    assert_eq!(saved.len(), 1);
+    assert_eq!(saved[0].recipe_id(), "pancakes");
+    assert_eq!(saved[0].recipe_text(), changed);
+    assert!(!routing::has_unsaved_changes());
+    container.remove();
+}
+
+#[wasm_bindgen_test]
+async fn test_editor_delete_can_be_cancelled() {
+    let transport = Rc::new(MockTransport::default());
+    transport.respond(
+        Method::Get,
+        "/api/v2/recipe/pancakes",
+        200,
+        &Response::Success(Some(RecipeEntry::new("pancakes", PANCAKES))),
+    );
+    let container = mount(transport.clone(), AppState::new(), |cx, sh| {
+        view! {cx, Editor(recipe_id="pancakes".to_owned(), sh=sh) }
+    });
+    settle().await;
+
+    button(&container, &t("recipe.delete")).click();
+    settle().await;
+    let dialog = find::<HtmlDialogElement>(&container, "#confirm-dialog");
+    assert!(dialog.open());
+    assert_eq!(
+        dialog
+            .text_content()
+            .map(|s| s.contains(&t("recipe.confirm_delete"))),
+        Some(true)
+    );
+
+    button(&dialog, &t("confirm.cancel")).click();
+    settle().await;
+    assert!(!dialog.open());
+    assert!(transport
+        .sent(Method::Delete, "/api/v2/recipe/pancakes")
+        .is_empty());
+    container.remove();
+}
+
+#[wasm_bindgen_test]
+async fn test_shopping_list_combines_recipes() {
+    let transport = Rc::new(MockTransport::default());
+    let container = mount(transport.clone(), planned_state(None), |cx, sh| {
+        view! {cx, ShoppingList(sh) }
+    });
+    settle().await;
+
+    let rows = shopping_rows(&container);
+    let names = rows
+        .iter()
+        .map(|(name, _)| name.as_str())
+        .collect::<Vec<&str>>();
+    assert_eq!(names, vec!["butter", "egg", "flour", "milk"]);
+    assert_eq!(rows[1].1, "5");
+    let rows = find_all(&container, "table.shopping-list tbody tr");
+    let egg_row = &rows[1];
+    let recipes = find_all(egg_row, "td")[3]
+        .text_content()
+        .unwrap_or_default();
+    assert!(recipes.contains("Omelet"));
+    assert!(recipes.contains("Pancakes"));
+
+    // Without a plan date there is nothing to sync so removing an
+    // ingredient only changes the list.
+    find::<HtmlElement>(egg_row, "input[type=button]").click();
+    settle().await;
+    let names = shopping_rows(&container)
+        .into_iter()
+        .map(|(name, _)| name)
+        .collect::<Vec<String>>();
+    assert_eq!(names, vec!["butter", "flour", "milk"]);
+    assert!(transport.requests.borrow().is_empty());
+    container.remove();
+}
+
+#[wasm_bindgen_test]
+async fn test_shopping_list_restores_unsynced_removal() {
+    let transport = Rc::new(MockTransport::default());
+    let date = NaiveDate::from_ymd_opt(2023, 10, 21).unwrap();
+    let container = mount(transport.clone(), planned_state(Some(date)), |cx, sh| {
+        view! {cx, ShoppingList(sh) }
+    });
+    settle().await;
+
+    let rows = find_all(&container, "table.shopping-list tbody tr");
+    find::<HtmlElement>(&rows[1], "input[type=button]").click();
+    settle().await;
+
+    // The mock has no inventory endpoint so the sync fails and the egg
+    // comes back.
+    assert!(!transport.requests.borrow().is_empty());
+    let names = shopping_rows(&container)
+        .into_iter()
+        .map(|(name, _)| name)
+        .collect::<Vec<String>>();
+    assert_eq!(names, vec!["butter", "egg", "flour", "milk"]);
+    container.remove();
+}
@@ -0,0 +1,84 @@
+// Copyright 2023 Jeremy Wall (Jeremy@marzhilsltudios.com)
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+use wasm_bindgen::UnwrapThrowExt;
+
+/// The settings key the theme is stored under in the settings api.
+pub const THEME_SETTING: &'static str = "theme";
+
+/// The color theme for the UI. `System` follows the browsers color scheme
+/// preference.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Theme {
+    System,
+    Light,
+    Dark,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme::System
+    }
+}
+
+impl Theme {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Theme::System => "system",
+            Theme::Light => "light",
+            Theme::Dark => "dark",
+        }
+    }
+
+    /// The theme to switch to next when toggling.
+    pub fn next(&self) -> Self {
+        match self {
+            Theme::System => Theme::Light,
+            Theme::Light => Theme::Dark,
+            Theme::Dark => Theme::System,
+        }
+    }
+}
+
+impl std::str::FromStr for Theme {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "system" => Ok(Theme::System),
+            "light" => Ok(Theme::Light),
+            "dark" => Ok(Theme::Dark),
+            _ => Err(format!("Unknown theme {}", s)),
+        }
+    }
+}
+
+/// Apply the theme to the document. Our css and pico both key off of the
+/// data-theme attribute on the root element.
+pub fn apply_theme(theme: Theme) {
+    debug!(?theme, "Applying theme");
+    let root = web_sys::window()
+        .expect("No window present")
+        .document()
+        .expect("No document in window")
+        .document_element()
+        .expect("No root element in document");
+    match theme {
+        Theme::System => root.remove_attribute("data-theme").unwrap_throw(),
+        Theme::Light | Theme::Dark => root
+            .set_attribute("data-theme", theme.as_str())
+            .unwrap_throw(),
+    }
+}
@@ -0,0 +1,251 @@
+// Copyright 2023 Jeremy Wall (Jeremy@marzhilsltudios.com)
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//! How the [HttpStore](crate::api::HttpStore) reaches the server. Every
+//! request goes through a [Transport] so the ui can be run against canned
+//! responses without a server.
+use std::collections::BTreeMap;
+
+use async_trait::async_trait;
+use serde::de::DeserializeOwned;
+use wasm_bindgen::JsValue;
+
+/// Failures sending a request or reading a response.
+pub type Error = reqwasm::Error;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Method {
+    Get,
+    Post,
+    Patch,
+    Delete,
+}
+
+#[derive(Clone, Debug)]
+pub enum Body {
+    Text(String),
+    File(web_sys::File),
+}
+
+impl From<String> for Body {
+    fn from(item: String) -> Self {
+        Self::Text(item)
+    }
+}
+
+impl From<&String> for Body {
+    fn from(item: &String) -> Self {
+        Self::Text(item.clone())
+    }
+}
+
+impl From<&str> for Body {
+    fn from(item: &str) -> Self {
+        Self::Text(item.to_owned())
+    }
+}
+
+impl From<web_sys::File> for Body {
+    fn from(item: web_sys::File) -> Self {
+        Self::File(item)
+    }
+}
+
+impl From<Body> for JsValue {
+    fn from(item: Body) -> Self {
+        match item {
+            Body::Text(text) => JsValue::from_str(&text),
+            Body::File(file) => file.into(),
+        }
+    }
+}
+
+/// A request to the server.
+#[derive(Clone, Debug)]
+pub struct Request {
+    method: Method,
+    url: String,
+    headers: Vec<(String, String)>,
+    body: Option<Body>,
+}
+
+impl Request {
+    pub fn new(method: Method, url: &str) -> Self {
+        Self {
+            method,
+            url: url.to_owned(),
+            headers: Vec::new(),
+            body: None,
+        }
+    }
+
+    pub fn get(url: &str) -> Self {
+        Self::new(Method::Get, url)
+    }
+
+    pub fn post(url: &str) -> Self {
+        Self::new(Method::Post, url)
+    }
+
+    pub fn patch(url: &str) -> Self {
+        Self::new(Method::Patch, url)
+    }
+
+    pub fn delete(url: &str) -> Self {
+        Self::new(Method::Delete, url)
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_owned(), value.to_owned()));
+        self
+    }
+
+    pub fn body<B: Into<Body>>(mut self, body: B) -> Self {
+        self.body = Some(body.into());
+        self
+    }
+
+    pub fn method(&self) -> Method {
+        self.method
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// The value of a header. Header names are case insensitive.
+    pub fn get_header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// The body if it is text.
+    pub fn text(&self) -> Option<&str> {
+        match &self.body {
+            Some(Body::Text(text)) => Some(text.as_str()),
+            _ => None,
+        }
+    }
+}
+
+/// The headers of a [Response].
+#[derive(Clone, Debug, Default)]
+pub struct Headers(BTreeMap<String, String>);
+
+impl Headers {
+    /// Header names are case insensitive.
+    pub fn get(&self, name: &str) -> Option<String> {
+        self.0.get(&name.to_ascii_lowercase()).cloned()
+    }
+
+    pub fn set(&mut self, name: &str, value: &str) {
+        self.0.insert(name.to_ascii_lowercase(), value.to_owned());
+    }
+}
+
+/// A response from the server. The body has already been read so it can
+/// be looked at more than once.
+#[derive(Clone, Debug)]
+pub struct Response {
+    status: u16,
+    url: String,
+    headers: Headers,
+    body: Vec<u8>,
+}
+
+impl Response {
+    pub fn new<B: Into<Vec<u8>>>(status: u16, url: &str, body: B) -> Self {
+        Self {
+            status,
+            url: url.to_owned(),
+            headers: Headers::default(),
+            body: body.into(),
+        }
+    }
+
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.set(name, value);
+        self
+    }
+
+    pub fn status(&self) -> u16 {
+        self.status
+    }
+
+    pub fn url(&self) -> String {
+        self.url.clone()
+    }
+
+    pub fn headers(&self) -> &Headers {
+        &self.headers
+    }
+
+    pub async fn json<T: DeserializeOwned>(&self) -> Result<T, Error> {
+        serde_json::from_slice(&self.body).map_err(Error::SerdeError)
+    }
+
+    pub async fn text(&self) -> Result<String, Error> {
+        String::from_utf8(self.body.clone()).map_err(|e| Error::GlooError(e.to_string()))
+    }
+
+    pub async fn binary(&self) -> Result<Vec<u8>, Error> {
+        Ok(self.body.clone())
+    }
+}
+
+/// Sends requests to the server.
+#[async_trait(?Send)]
+pub trait Transport: std::fmt::Debug {
+    async fn send(&self, request: Request) -> Result<Response, Error>;
+}
+
+/// A [Transport] that uses the browser's fetch api.
+#[derive(Debug, Default)]
+pub struct FetchTransport;
+
+#[async_trait(?Send)]
+impl Transport for FetchTransport {
+    async fn send(&self, request: Request) -> Result<Response, Error> {
+        let Request {
+            method,
+            url,
+            headers,
+            body,
+        } = request;
+        let mut raw = match method {
+            Method::Get => reqwasm::http::Request::get(&url),
+            Method::Post => reqwasm::http::Request::post(&url),
+            Method::Patch => reqwasm::http::Request::patch(&url),
+            Method::Delete => reqwasm::http::Request::delete(&url),
+        };
+        for (name, value) in headers.iter() {
+            raw = raw.header(name, value);
+        }
+        if let Some(body) = body {
+            raw = raw.body(JsValue::from(body));
+        }
+        let resp = raw.send().await?;
+        let mut headers = Headers::default();
+        for (name, value) in resp.headers().entries() {
+            headers.set(&name, &value);
+        }
+        Ok(Response {
+            status: resp.status(),
+            url: resp.url(),
+            headers,
+            body: resp.binary().await?,
+        })
+    }
+}
@@ -11,34 +11,63 @@
 // WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
 // See the License for the specific language governing permissions and
 // limitations under the License.
+use std::cell::Cell;
+
 use sycamore::{futures::spawn_local_scoped, prelude::*};
 use tracing::{info, debug, instrument};
 
 use crate::app_state::Message;
+use crate::i18n::{self, Locale};
 use crate::{api, routing::Handler as RouteHandler};
 
 #[instrument]
 #[component]
 pub fn UI<G: Html>(cx: Scope) -> View<G> {
+    crate::components::toast::Notifier::provide_context(cx);
+    crate::components::confirm::Confirmer::provide_context(cx);
     api::HttpStore::provide_context(cx, "/api".to_owned());
     let store = api::HttpStore::get_from_context(cx).as_ref().clone();
     info!("Starting UI");
     let local_store = api::LocalStore::new();
-    let app_state = if let Some(app_state) = local_store.fetch_app_state() {
+    if !local_store.is_available() {
+        crate::components::toast::Notifier::get_from_context(cx)
+            .error("Local storage is unavailable. Your data won't be available offline.");
+    }
+    let app_state = if let Some(app_state) = store.cache_result(local_store.fetch_app_state()) {
         app_state
     } else {
         crate::app_state::AppState::new()
     };
     debug!(?app_state, "Loaded app state from local storage");
+    crate::theme::apply_theme(app_state.theme);
     let sh = crate::app_state::get_state_handler(cx, app_state, store);
     let view = create_signal(cx, View::empty());
-    spawn_local_scoped(cx, {
-        async move {
-            sh.dispatch(cx, Message::LoadState(None));
+    // Views look up their messages when they are built so we build them
+    // again when the locale changes.
+    let locale = sh.get_selector(cx, |state| state.get().locale);
+    let rendered = create_ref(cx, Cell::new(false));
+    create_effect(cx, move || {
+        let locale = (*locale.get()).unwrap_or_else(Locale::from_browser);
+        spawn_local_scoped(cx, async move {
+            if rendered.get() && i18n::current_locale() == locale {
+                return;
+            }
+            i18n::set_locale(locale, &api::LocalStore::new()).await;
             view.set(view! { cx,
                 RouteHandler(sh=sh)
             });
-        }
+            rendered.set(true);
+            // The server may have prerendered the page for us. Now that the app
+            // is rendering we don't need that markup anymore.
+            if let Ok(Some(prerendered)) =
+                crate::js_lib::get_element_by_id::<web_sys::Element>("prerender")
+            {
+                prerendered.remove();
+            }
+        });
+    });
+    spawn_local_scoped(cx, async move {
+        sh.dispatch(cx, Message::LoadState(None));
     });
 
     view! { cx, (view.get().as_ref()) }